@@ -0,0 +1,59 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Context construction benchmarks: building the precomputed tables for
+//! each kind of context, against the ways of avoiding it (cloning,
+//! importing an exported context and sharing `new_verify_only_static`).
+//! Run outside the enclave with
+//! `cargo bench --no-default-features --features std`.
+
+#[macro_use]
+extern crate criterion;
+extern crate secp256k1;
+
+use criterion::{black_box, Criterion};
+use secp256k1::{All, Secp256k1, SignOnly, VerifyOnly};
+
+fn creation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create");
+    group.sample_size(20);
+    group.bench_function("all", |b| b.iter(|| Secp256k1::new()));
+    group.bench_function("signing_only", |b| b.iter(|| Secp256k1::signing_only()));
+    group.bench_function("verification_only", |b| b.iter(|| Secp256k1::verification_only()));
+    group.bench_function("without_caps", |b| b.iter(|| Secp256k1::without_caps()));
+    group.finish();
+}
+
+fn amortized(c: &mut Criterion) {
+    let full = Secp256k1::new();
+    let exported = full.export_context();
+    let mut group = c.benchmark_group("amortized");
+    group.bench_function("clone", |b| b.iter(|| black_box(&full).clone()));
+    group.bench_function("import_context", |b| {
+        b.iter(|| Secp256k1::import_context(black_box(&exported)).unwrap())
+    });
+    // The first call builds the context; this measures the ones after it
+    Secp256k1::new_verify_only_static();
+    group.bench_function("new_verify_only_static", |b| b.iter(|| Secp256k1::new_verify_only_static()));
+    group.finish();
+
+    for &(name, cost) in &[("all", Secp256k1::<All>::creation_cost_hint()),
+                           ("signing_only", Secp256k1::<SignOnly>::creation_cost_hint()),
+                           ("verification_only", Secp256k1::<VerifyOnly>::creation_cost_hint())] {
+        println!("{}: {} bytes of tables", name, cost.table_bytes());
+    }
+}
+
+criterion_group!(benches, creation, amortized);
+criterion_main!(benches);
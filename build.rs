@@ -23,20 +23,248 @@
 
 extern crate cc;
 
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The crate's own SHA-256, for pinning an external library
+#[path = "src/hashes.rs"]
+#[allow(dead_code)]
+mod hashes;
+// Needed by `hashes` for wiping HMAC keys
+#[path = "src/cleanup.rs"]
+#[allow(dead_code)]
+mod cleanup;
+
+/// Reads a numeric build setting from the environment, falling back to
+/// `default` if it is unset
+fn setting(name: &str, default: u32, valid: &[u32]) -> u32 {
+    println!("cargo:rerun-if-env-changed={}", name);
+    match env::var(name) {
+        Ok(value) => match value.parse() {
+            Ok(n) if valid.contains(&n) => n,
+            _ => panic!("{} must be one of {:?}, got {:?}", name, valid, value),
+        },
+        Err(_) => default,
+    }
+}
+
+/// The flags of the SGX SDK's LVI load mitigation
+const LVI_FLAGS: &'static [&'static str] = &[
+    "-mindirect-branch-register",
+    "-Wa,-mlfence-after-load=yes",
+    "-Wa,-mlfence-before-indirect-branch=register",
+    "-Wa,-mlfence-before-ret=shl",
+];
+
+/// The C library functions libsecp256k1 may call with the "no-ocall"
+/// feature: the SGX SDK's trusted libc implements all of them inside the
+/// enclave, and none needs the host
+const ENCLAVE_LIBC: &'static [&'static str] = &[
+    "abort", "calloc", "free", "malloc", "memcmp", "memcpy", "memmove", "memset", "realloc",
+    "snprintf", "strlen", "strnlen", "strrchr", "vsnprintf",
+    // Stack protector and _FORTIFY_SOURCE
+    "__stack_chk_fail", "__memcpy_chk", "__memmove_chk", "__memset_chk", "__snprintf_chk",
+    "__vsnprintf_chk",
+];
+
+/// Fails the build if `lib` calls anything outside itself but
+/// `ENCLAVE_LIBC` and the crate's own `rustsecp256k1_` functions, such as
+/// `time`, `getrandom` or file I/O, which inside an enclave would be an
+/// OCALL to the untrusted host
+fn check_no_ocall(lib: &Path) {
+    println!("cargo:rerun-if-env-changed=NM");
+    let nm = env::var("NM").unwrap_or_else(|_| "nm".to_string());
+    let output = Command::new(&nm).arg("-g").arg("--format=posix").arg(lib).output()
+        .unwrap_or_else(|e| panic!("the no-ocall feature needs `{}` to list symbols: {}", nm, e));
+    if !output.status.success() {
+        panic!("`{}` cannot read {}", nm, lib.display());
+    }
+
+    // Lines are "name type [value size]", or "archive[member]:" headers
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let symbols: Vec<(&str, &str)> = listing.lines()
+        .filter(|line| !line.ends_with(':'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?, fields.next()?))
+        })
+        .collect();
+    let defined: Vec<&str> = symbols.iter().filter(|&&(_, kind)| kind != "U").map(|&(name, _)| name).collect();
+    let mut outside: Vec<&str> = symbols.iter()
+        .filter(|&&(name, kind)| kind == "U" && !defined.contains(&name))
+        .map(|&(name, _)| name)
+        .filter(|name| !ENCLAVE_LIBC.contains(name) && !name.starts_with("rustsecp256k1_"))
+        .collect();
+    outside.sort();
+    outside.dedup();
+    if !outside.is_empty() {
+        panic!("{} calls functions which may leave the enclave, which the no-ocall feature forbids: {}",
+               lib.display(), outside.join(", "));
+    }
+}
+
+/// The SHA-256 of the vendored sources, for `build_info`: of every file
+/// under `depend/secp256k1`, in order of path, as its path, its length and
+/// its contents
+fn source_digest() -> String {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e)) {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, files);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    let root = Path::new("depend/secp256k1");
+    println!("cargo:rerun-if-changed={}", root.display());
+    let mut files = Vec::new();
+    walk(root, &mut files);
+    files.sort();
+    let mut engine = hashes::Sha256::new();
+    for file in files {
+        let name = file.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/");
+        let data = fs::read(&file).unwrap_or_else(|e| panic!("cannot read {}: {}", file.display(), e));
+        engine.input(&(name.len() as u64).to_be_bytes());
+        engine.input(name.as_bytes());
+        engine.input(&(data.len() as u64).to_be_bytes());
+        engine.input(&data);
+    }
+    engine.result().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Links a user-provided static libsecp256k1 in place of the vendored
+/// sources, after checking its hash against any pin and checking that it
+/// provides the functions and behaviour the bindings expect
+fn link_external() {
+    println!("cargo:rerun-if-env-changed=SECP256K1_EXTERNAL_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=SECP256K1_EXTERNAL_LIB_SHA256");
+    let dir = env::var("SECP256K1_EXTERNAL_LIB_DIR")
+        .expect("the external-secp feature needs SECP256K1_EXTERNAL_LIB_DIR");
+    let lib = Path::new(&dir).join("libsecp256k1.a");
+    println!("cargo:rerun-if-changed={}", lib.display());
+
+    let data = fs::read(&lib).unwrap_or_else(|e| panic!("cannot read {}: {}", lib.display(), e));
+    let digest: String = hashes::sha256(&data).iter().map(|b| format!("{:02x}", b)).collect();
+    match env::var("SECP256K1_EXTERNAL_LIB_SHA256") {
+        Ok(ref pin) if pin.trim().to_lowercase() == digest => {}
+        Ok(pin) => panic!("{} has SHA-256 {}, but SECP256K1_EXTERNAL_LIB_SHA256 pins {}",
+                          lib.display(), digest, pin.trim()),
+        Err(_) => println!("cargo:warning=linking unpinned {} with SHA-256 {}", lib.display(), digest),
+    }
+    println!("cargo:rustc-env=SECP256K1_LIBRARY_SHA256={}", digest);
+
+    // Link and run a known-answer test against the vendored headers
+    let check = PathBuf::from(env::var("OUT_DIR").unwrap()).join("check_external");
+    let status = cc::Build::new().get_compiler().to_command()
+        .arg("-Idepend/secp256k1/include")
+        .arg("depend/check_external.c")
+        .arg(&lib)
+        .arg("-o").arg(&check)
+        .status()
+        .expect("cannot run the C compiler");
+    if !status.success() {
+        panic!("{} lacks functions the bindings need; build it from depend/secp256k1", lib.display());
+    }
+    let status = Command::new(&check).status().expect("cannot run the external library check");
+    if !status.success() {
+        panic!("{} failed the known-answer tests", lib.display());
+    }
+    if cfg!(feature = "no-ocall") {
+        check_no_ocall(&lib);
+    }
+
+    // The lax DER parser is contrib code outside the library proper
+    cc::Build::new().include("depend/secp256k1/include")
+        .file("depend/secp256k1/contrib/lax_der_parsing.c")
+        .compile("liblax_der_parsing.a");
+    println!("cargo:rustc-link-search=native={}", dir);
+    println!("cargo:rustc-link-lib=static=secp256k1");
+}
+
 fn main() {
     #[cfg(not(all(target_os = "linux", target_arch = "x86_64", target_pointer_width = "64")))]
         panic!("only support linux on x86_64 arch with 64-bit width");
 
-    // Check whether we can use 64-bit compilation
+    // The crate features, as `build_info` reports them
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(var, _)| var.strip_prefix("CARGO_FEATURE_").map(|name| name.to_lowercase().replace('_', "-")))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=SECP256K1_FEATURES={}", features.join(","));
+
+    // Check whether the compiler supports __int128
     let check = cc::Build::new().file("depend/check_uint128_t.c")
         .cargo_metadata(false)
         .try_compile("check_uint128_t")
         .is_ok();
 
-    if !check {
-        panic!("only support 64-bit platform")
+    // Field and scalar representation: 5x52/4x64 limbs with 128-bit
+    // products, or 10x26/8x32 limbs with 64-bit products. Autodetection
+    // compiles a test program, which some SGX cross toolchains get wrong.
+    let int128 = match (cfg!(feature = "widemul-int128"), cfg!(feature = "widemul-int64")) {
+        (true, true) => panic!("the widemul-int128 and widemul-int64 features are mutually exclusive"),
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => {
+            if !check {
+                println!("cargo:warning=__int128 is unavailable, using 64-bit arithmetic");
+            }
+            check
+        }
+    };
+    if cfg!(feature = "asm") && !int128 {
+        panic!("the asm feature needs 128-bit arithmetic");
+    }
+
+    // Everything these features do needs a secret key or a signing context
+    if cfg!(feature = "verify-only") {
+        let signing = [("sealing", cfg!(feature = "sealing")), ("capi", cfg!(feature = "capi")),
+                       ("bench", cfg!(feature = "bench")), ("fuzz", cfg!(feature = "fuzz")),
+                       ("differential", cfg!(feature = "differential")), ("psbt", cfg!(feature = "psbt")),
+                       ("hazmat", cfg!(feature = "hazmat")),
+                       ("encrypted-keys", cfg!(feature = "encrypted-keys"))];
+        if let Some(&(name, _)) = signing.iter().find(|&&(_, on)| on) {
+            panic!("the verify-only feature cannot be combined with the {} feature", name);
+        }
+    }
+
+    // Trusted time and protected files are services of the untrusted host
+    if cfg!(feature = "no-ocall") {
+        let host = [("trusted-time", cfg!(feature = "trusted-time")),
+                    ("protected-fs", cfg!(feature = "protected-fs"))];
+        if let Some(&(name, _)) = host.iter().find(|&&(_, on)| on) {
+            panic!("the no-ocall feature cannot be combined with the {} feature, which makes OCALLs", name);
+        }
     }
 
+    // wasm32-unknown-unknown has no C library; see "WebAssembly" in the README
+    let wasm = env::var("CARGO_CFG_TARGET_ARCH").map(|arch| arch == "wasm32").unwrap_or(false) &&
+               env::var("CARGO_CFG_TARGET_OS").map(|os| os == "unknown").unwrap_or(false);
+    if wasm && cfg!(feature = "asm") {
+        panic!("the asm feature is only available on x86_64");
+    }
+    if wasm && cfg!(feature = "lvi-mitigation") {
+        panic!("the lvi-mitigation feature is only available on x86_64");
+    }
+
+    if cfg!(feature = "external-secp") {
+        if cfg!(feature = "context-allocator") {
+            panic!("the context-allocator feature needs the vendored sources, not external-secp");
+        }
+        if cfg!(feature = "lvi-mitigation") {
+            panic!("the lvi-mitigation feature builds the vendored sources; build the external library \
+                    with the SDK's mitigation flags and SECP256K1_LVI_MITIGATION defined instead");
+        }
+        link_external();
+        return;
+    }
+
+    println!("cargo:rustc-env=SECP256K1_LIBRARY_SHA256={}", source_digest());
+
     // Actual build
     let mut base_config = cc::Build::new();
     base_config.include("depend/secp256k1")
@@ -51,20 +279,77 @@ fn main() {
         .define("USE_SCALAR_INV_BUILTIN", Some("1"))
         .define("USE_ENDOMORPHISM", Some("1"))
         .define("ENABLE_MODULE_ECDH", Some("1"))
-        .define("ENABLE_MODULE_RECOVERY", Some("1"));
+        .define("ENABLE_MODULE_RECOVERY", Some("1"))
+        .define("ENABLE_MODULE_SCALAR_OPS", Some("1"))
+        .define("ENABLE_MODULE_CONTEXT_EXPORT", Some("1"))
+        .define("ENABLE_MODULE_PREPARED", Some("1"))
+        .define("ENABLE_MODULE_MSM", Some("1"))
+        .define("ENABLE_MODULE_TWEAK_BATCH", Some("1"));
 
-    if let Ok(target_endian) = std::env::var("CARGO_CFG_TARGET_ENDIAN") {
+    // Leaves out building the signing table, which no context can use
+    if cfg!(feature = "verify-only") {
+        base_config.define("VERIFY_ONLY", Some("1"));
+    }
+
+    // Routes every allocation to `allocator::set_allocator`'s allocator
+    if cfg!(feature = "context-allocator") {
+        base_config.define("USE_CONTEXT_ALLOCATOR", Some("1"));
+    }
+
+    // Precomputed table sizes; see "Memory usage" in the README
+    let (window_size, gen_prec_bits) = if cfg!(feature = "lowmemory") { (4, 2) } else { (15, 4) };
+    let window_sizes: Vec<u32> = (2..25).collect();
+    let window_size = setting("SECP256K1_ECMULT_WINDOW_SIZE", window_size, &window_sizes);
+    let gen_prec_bits = setting("SECP256K1_ECMULT_GEN_PREC_BITS", gen_prec_bits, &[2, 4, 8]);
+    base_config.define("ECMULT_WINDOW_SIZE", Some(window_size.to_string().as_str()))
+        .define("ECMULT_GEN_PREC_BITS", Some(gen_prec_bits.to_string().as_str()));
+
+    // The inline assembly only uses general-purpose instructions, all of
+    // which are legal inside an enclave
+    if cfg!(feature = "asm") {
+        base_config.define("USE_ASM_X86_64", Some("1"));
+    }
+
+    // The SGX SDK's LVI mitigation (MITIGATION-CVE-2020-0551=LOAD): indirect
+    // branches through registers, and an LFENCE after every load and before
+    // every indirect branch and return, which the assembler inserts into the
+    // compiled C and the inline assembly alike. It needs binutils 2.34 or
+    // later; an unhardened library is never built in its place.
+    if cfg!(feature = "lvi-mitigation") {
+        for flag in LVI_FLAGS {
+            match base_config.is_flag_supported(flag) {
+                Ok(true) => { base_config.flag(flag); }
+                _ => panic!("the C toolchain doesn't support {}, which the lvi-mitigation feature needs", flag),
+            }
+        }
+        base_config.define("SECP256K1_LVI_MITIGATION", Some("1"));
+    }
+
+    if wasm {
+        base_config.include("depend/wasm-sysroot");
+    }
+
+    if let Ok(target_endian) = env::var("CARGO_CFG_TARGET_ENDIAN") {
         if target_endian == "big" {
             base_config.define("WORDS_BIGENDIAN", Some("1"));
         }
     }
 
-    base_config.define("USE_FIELD_5X52", Some("1"))
-        .define("USE_SCALAR_4X64", Some("1"))
-        .define("HAVE___INT128", Some("1"));
+    if int128 {
+        base_config.define("USE_FIELD_5X52", Some("1"))
+            .define("USE_SCALAR_4X64", Some("1"))
+            .define("HAVE___INT128", Some("1"));
+    } else {
+        base_config.define("USE_FIELD_10X26", Some("1"))
+            .define("USE_SCALAR_8X32", Some("1"));
+    }
 
     // secp256k1
     base_config.file("depend/secp256k1/contrib/lax_der_parsing.c")
         .file("depend/secp256k1/src/secp256k1.c")
         .compile("libsecp256k1.a");
+
+    if cfg!(feature = "no-ocall") {
+        check_no_ocall(&PathBuf::from(env::var("OUT_DIR").unwrap()).join("libsecp256k1.a"));
+    }
 }
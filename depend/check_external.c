@@ -0,0 +1,130 @@
+/* Checks an externally built libsecp256k1 against the headers vendored with
+ * this crate: linking fails if any function the bindings use is missing, and
+ * the known-answer tests below fail if the library disagrees with the
+ * headers on struct layouts or behaviour. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "secp256k1.h"
+#include "secp256k1_context_export.h"
+#include "secp256k1_ecdh.h"
+#include "secp256k1_msm.h"
+#include "secp256k1_prepared.h"
+#include "secp256k1_recovery.h"
+#include "secp256k1_scalar_ops.h"
+#include "secp256k1_tweak_batch.h"
+
+typedef void (*any_fn)(void);
+
+/* Every function the Rust bindings declare, except the lax DER parser which
+ * the crate always compiles itself. Not static, so that the linker has to
+ * resolve every entry. */
+const any_fn required[] = {
+    (any_fn)secp256k1_context_create,
+    (any_fn)secp256k1_context_clone,
+    (any_fn)secp256k1_context_destroy,
+    (any_fn)secp256k1_context_randomize,
+    (any_fn)secp256k1_context_capabilities,
+    (any_fn)secp256k1_context_table_sizes,
+    (any_fn)secp256k1_context_export_size,
+    (any_fn)secp256k1_context_export,
+    (any_fn)secp256k1_context_import,
+    (any_fn)secp256k1_context_preallocated_size,
+    (any_fn)secp256k1_context_preallocated_create,
+    (any_fn)secp256k1_context_rebuild_sign,
+    (any_fn)secp256k1_build_info,
+    (any_fn)secp256k1_scratch_space_create,
+    (any_fn)secp256k1_scratch_space_create_preallocated,
+    (any_fn)secp256k1_scratch_space_max_size,
+    (any_fn)secp256k1_scratch_space_destroy,
+    (any_fn)secp256k1_ec_pubkey_parse,
+    (any_fn)secp256k1_ec_pubkey_serialize,
+    (any_fn)secp256k1_ec_pubkey_cmp,
+    (any_fn)secp256k1_ecdsa_signature_parse_der,
+    (any_fn)secp256k1_ecdsa_signature_parse_compact,
+    (any_fn)secp256k1_ecdsa_signature_serialize_der,
+    (any_fn)secp256k1_ecdsa_signature_serialize_compact,
+    (any_fn)secp256k1_ecdsa_recoverable_signature_parse_compact,
+    (any_fn)secp256k1_ecdsa_recoverable_signature_serialize_compact,
+    (any_fn)secp256k1_ecdsa_recoverable_signature_convert,
+    (any_fn)secp256k1_ecdsa_signature_normalize,
+    (any_fn)secp256k1_ecdsa_verify,
+    (any_fn)secp256k1_ecdsa_sign,
+    (any_fn)secp256k1_ecdsa_sign_recoverable,
+    (any_fn)secp256k1_ecdsa_recover,
+    (any_fn)secp256k1_ec_seckey_verify,
+    (any_fn)secp256k1_ec_pubkey_create,
+    (any_fn)secp256k1_ec_privkey_tweak_add,
+    (any_fn)secp256k1_ec_pubkey_tweak_add,
+    (any_fn)secp256k1_ec_pubkey_tweak_add_batch,
+    (any_fn)secp256k1_ec_privkey_tweak_mul,
+    (any_fn)secp256k1_ec_pubkey_tweak_mul,
+    (any_fn)secp256k1_ec_pubkey_combine,
+    (any_fn)secp256k1_ecdh,
+    (any_fn)secp256k1_prepared_pubkey_size,
+    (any_fn)secp256k1_prepared_pubkey_create,
+    (any_fn)secp256k1_ecdsa_verify_prepared,
+    (any_fn)secp256k1_ec_pubkey_msm,
+    (any_fn)secp256k1_ec_pubkey_msm_scratch_size,
+    (any_fn)secp256k1_ec_scalar_verify,
+    (any_fn)secp256k1_ec_scalar_reduce512,
+    (any_fn)secp256k1_ec_scalar_add,
+    (any_fn)secp256k1_ec_scalar_mul,
+    (any_fn)secp256k1_ec_scalar_negate,
+    (any_fn)secp256k1_ec_scalar_inverse,
+};
+
+/* The same vectors as the crate's self test */
+static const unsigned char public_key[33] = {
+    0x02, 0xb9, 0x8a, 0x7f, 0xb8, 0xcc, 0x00, 0x70, 0x48, 0x62, 0x5b, 0x64, 0x46, 0xad, 0x49, 0xa1,
+    0xb3, 0xa7, 0x22, 0xdf, 0x8c, 0x1c, 0xa9, 0x75, 0xb8, 0x71, 0x60, 0x02, 0x3e, 0x14, 0xd1, 0x90,
+    0x97
+};
+static const unsigned char signature[64] = {
+    0x7a, 0xb8, 0x29, 0xf4, 0xef, 0xdb, 0xa2, 0x92, 0x98, 0xf5, 0xae, 0xce, 0x0c, 0xb0, 0x63, 0xdd,
+    0x7d, 0xf8, 0x4a, 0xed, 0xcc, 0x37, 0x4a, 0x90, 0x5d, 0x80, 0xc2, 0x71, 0x0c, 0xd0, 0x86, 0xb5,
+    0x27, 0x4a, 0x1f, 0xb9, 0x11, 0x37, 0xe3, 0x91, 0x39, 0x59, 0xf7, 0xc2, 0x92, 0x9e, 0x9c, 0xda,
+    0x53, 0xbe, 0x2a, 0x76, 0x06, 0x20, 0x2f, 0xd1, 0x41, 0x50, 0x05, 0xc1, 0x9c, 0x44, 0x58, 0x25
+};
+
+static int fail(const char *what) {
+    fprintf(stderr, "external libsecp256k1 check failed: %s\n", what);
+    return 1;
+}
+
+int main(void) {
+    unsigned char seckey[32], msg[32], output[64];
+    size_t len = sizeof(public_key);
+    secp256k1_context *ctx;
+    secp256k1_pubkey pubkey;
+    secp256k1_ecdsa_signature sig;
+    int ok;
+
+    if (secp256k1_context_no_precomp == NULL) {
+        return fail("static context");
+    }
+    memset(seckey, 0xcd, sizeof(seckey));
+    memset(msg, 0x5a, sizeof(msg));
+
+    ctx = secp256k1_context_create(SECP256K1_CONTEXT_SIGN | SECP256K1_CONTEXT_VERIFY);
+    if (ctx == NULL) {
+        return fail("context creation");
+    }
+    ok = secp256k1_ec_pubkey_create(ctx, &pubkey, seckey) &&
+         secp256k1_ec_pubkey_serialize(ctx, output, &len, &pubkey, SECP256K1_EC_COMPRESSED) &&
+         len == sizeof(public_key) && memcmp(output, public_key, len) == 0;
+    if (!ok) {
+        secp256k1_context_destroy(ctx);
+        return fail("public key derivation");
+    }
+    ok = secp256k1_ecdsa_sign(ctx, &sig, msg, seckey, secp256k1_nonce_function_rfc6979, NULL) &&
+         secp256k1_ecdsa_signature_serialize_compact(ctx, output, &sig) &&
+         memcmp(output, signature, sizeof(signature)) == 0 &&
+         secp256k1_ecdsa_verify(ctx, &sig, msg, &pubkey);
+    secp256k1_context_destroy(ctx);
+    if (!ok) {
+        return fail("signing");
+    }
+    return 0;
+}
@@ -179,6 +179,13 @@ typedef int (*secp256k1_nonce_function)(
 #define SECP256K1_TAG_PUBKEY_HYBRID_EVEN 0x06
 #define SECP256K1_TAG_PUBKEY_HYBRID_ODD 0x07
 
+/** A simple secp256k1 context object with no precomputed tables. These are useful for
+ *  type serialization/parsing functions which require a context object to maintain
+ *  API consistency, but currently do not require expensive precomputations or dynamic
+ *  allocations.
+ */
+SECP256K1_API extern const secp256k1_context *secp256k1_context_no_precomp;
+
 /** Create a secp256k1 context object.
  *
  *  Returns: a newly created context object.
@@ -267,6 +274,33 @@ SECP256K1_API SECP256K1_WARN_UNUSED_RESULT secp256k1_scratch_space* secp256k1_sc
     size_t max_size
 ) SECP256K1_ARG_NONNULL(1);
 
+/** Create a secp256k1 scratch space object inside caller-provided memory,
+ *  for environments where large allocations are unavailable. Nothing is
+ *  allocated; the scratch space structure and all its frames live in mem.
+ *
+ *  Returns: a scratch space pointing into mem, or NULL if size is too small
+ *           to hold the scratch space structure.
+ *  Args: ctx:  an existing context object (cannot be NULL)
+ *  In:   mem:  memory for the scratch space, which must stay valid and
+ *              unused until the scratch space is destroyed (cannot be NULL)
+ *        size: size of mem in bytes
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT secp256k1_scratch_space* secp256k1_scratch_space_create_preallocated(
+    const secp256k1_context* ctx,
+    void *mem,
+    size_t size
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2);
+
+/** Get the most memory a scratch space will hand out for its frames.
+ *
+ *  Returns: the max_size it was created with, or for a preallocated scratch
+ *           space the part of mem left after its own structure
+ *  Args:   scratch: an existing scratch space (cannot be NULL)
+ */
+SECP256K1_API size_t secp256k1_scratch_space_max_size(
+    const secp256k1_scratch_space* scratch
+) SECP256K1_ARG_NONNULL(1);
+
 /** Destroy a secp256k1 scratch space.
  *
  *  The pointer may not be used afterwards.
@@ -320,6 +354,21 @@ SECP256K1_API int secp256k1_ec_pubkey_serialize(
     unsigned int flags
 ) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3) SECP256K1_ARG_NONNULL(4);
 
+/** Compare two public keys using lexicographic (of compressed serialization) order
+ *
+ *  Returns: <0 if the first public key is less than the second
+ *           >0 if the first public key is greater than the second
+ *           0 if the two public keys are equal
+ *  Args: ctx:      a secp256k1 context object.
+ *  In:   pubkey1:  first public key to compare
+ *        pubkey2:  second public key to compare
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_pubkey_cmp(
+    const secp256k1_context* ctx,
+    const secp256k1_pubkey* pubkey1,
+    const secp256k1_pubkey* pubkey2
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
 /** Parse an ECDSA signature in compact (64 bytes) format.
  *
  *  Returns: 1 when the signature could be parsed, 0 otherwise.
@@ -0,0 +1,163 @@
+#ifndef SECP256K1_CONTEXT_EXPORT_H
+#define SECP256K1_CONTEXT_EXPORT_H
+
+#include "secp256k1.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** Export and import of a context, including its precomputed tables and
+ *  blinding state, so that a restarted process can skip building them.
+ *
+ *  The serialization is versioned and ends with a SHA-256 of everything
+ *  before it:
+ *    magic "SCTX" (4 bytes), version (1), flags (1: bit 0 verification
+ *    tables, bit 1 signing tables), ECMULT window size (1), build options
+ *    (1: bit 0 endomorphism, bits 1-4 ECMULT_GEN_PREC_BITS),
+ *    [verification tables: raw ge_storage arrays],
+ *    [signing tables: raw ge_storage array, 32-byte blinding scalar,
+ *     3x32-byte blinding point coordinates and 1 infinity byte],
+ *    SHA-256 (32 bytes)
+ *
+ *  The tables use the in-memory representation of this build, so a blob can
+ *  only be imported by the same build on the same architecture. The blinding
+ *  state is sensitive: exported blobs must be kept confidential.
+ */
+
+/** Report which precomputed tables a context has.
+ *  Returns: SECP256K1_CONTEXT_SIGN if it has the signing tables,
+ *           SECP256K1_CONTEXT_VERIFY if it has the verification tables,
+ *           both ORed together or SECP256K1_CONTEXT_NONE
+ *  Args:    ctx:       a context object (cannot be NULL)
+ */
+SECP256K1_API unsigned int secp256k1_context_capabilities(
+  const secp256k1_context* ctx
+) SECP256K1_ARG_NONNULL(1);
+
+/** Compute the sizes of the precomputed tables secp256k1_context_create
+ *  builds for a set of capabilities. Building them dominates the cost of
+ *  creating a context and takes time roughly proportional to their size.
+ *  Out:     sign_size:   the size of the signing tables, or 0 if flags
+ *                        does not ask for them (cannot be NULL)
+ *           verify_size: the size of the verification tables, or 0 if flags
+ *                        does not ask for them (cannot be NULL)
+ *  In:      flags:       capabilities, as passed to secp256k1_context_create
+ */
+SECP256K1_API void secp256k1_context_table_sizes(
+  size_t *sign_size,
+  size_t *verify_size,
+  unsigned int flags
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2);
+
+/** Compute the size of the serialization of a context.
+ *  Returns: the number of bytes `secp256k1_context_export` writes
+ *  Args:    ctx:       a context object (cannot be NULL)
+ */
+SECP256K1_API size_t secp256k1_context_export_size(
+  const secp256k1_context* ctx
+) SECP256K1_ARG_NONNULL(1);
+
+/** Serialize a context.
+ *  Returns: 1: success
+ *           0: outputlen does not match secp256k1_context_export_size
+ *  Args:    ctx:       a context object (cannot be NULL)
+ *  Out:     output:    pointer to an outputlen-byte array
+ *  In:      outputlen: the size of output
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_context_export(
+  const secp256k1_context* ctx,
+  unsigned char *output,
+  size_t outputlen
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2);
+
+/** Create a context from a serialization made by `secp256k1_context_export`.
+ *  Returns: a newly created context object, to be freed with
+ *           secp256k1_context_destroy, or NULL if the input is truncated,
+ *           corrupted, of another version, from an incompatible build or
+ *           lacks the tables requested by flags
+ *  In:      input:     pointer to an inputlen-byte serialization
+ *           inputlen:  the size of input
+ *           flags:     the capabilities the context must have, as passed to
+ *                      secp256k1_context_create
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT secp256k1_context* secp256k1_context_import(
+  const unsigned char *input,
+  size_t inputlen,
+  unsigned int flags
+) SECP256K1_ARG_NONNULL(1);
+
+/** Compute the size of a buffer which can hold a context created by
+ *  secp256k1_context_preallocated_create, with its tables, at any alignment.
+ *  Returns: the buffer size in bytes
+ *  In:      flags:     capabilities, as passed to secp256k1_context_create
+ */
+SECP256K1_API size_t secp256k1_context_preallocated_size(
+  unsigned int flags
+);
+
+/** Create a context, and build its tables, in memory owned by the caller.
+ *  Building the tables still allocates temporary memory, which is freed
+ *  before returning. secp256k1_context_destroy clears the context without
+ *  freeing anything; the buffer must outlive the context and its clones do
+ *  not use it.
+ *  Returns: a context object within prealloc, or NULL if prealloclen is less
+ *           than secp256k1_context_preallocated_size(flags) or flags are
+ *           invalid
+ *  In:      prealloc:    pointer to a prealloclen-byte buffer, at any
+ *                        alignment (cannot be NULL)
+ *           prealloclen: the size of prealloc
+ *           flags:       capabilities, as passed to secp256k1_context_create
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT secp256k1_context* secp256k1_context_preallocated_create(
+  void *prealloc,
+  size_t prealloclen,
+  unsigned int flags
+) SECP256K1_ARG_NONNULL(1);
+
+/** Rebuild a context's signing tables from scratch, in the memory they
+ *  already occupy, and reset its blinding to the default; randomize the
+ *  context again afterwards. Building the tables allocates temporary memory,
+ *  which is freed before returning.
+ *  Returns: 1: the tables were rebuilt
+ *           0: the context has no signing tables
+ *  Args:    ctx:       a context object (cannot be NULL)
+ */
+SECP256K1_API int secp256k1_context_rebuild_sign(
+  secp256k1_context* ctx
+) SECP256K1_ARG_NONNULL(1);
+
+/** Options secp256k1_build_info reports, as defined when the library was
+ *  compiled. SECP256K1_LVI_MITIGATION must be defined by whoever compiles the
+ *  library with the SGX SDK's LVI mitigation flags. */
+#define SECP256K1_BUILD_LVI_MITIGATION (1 << 0)
+#define SECP256K1_BUILD_ASM_X86_64 (1 << 1)
+#define SECP256K1_BUILD_WIDEMUL_INT128 (1 << 2)
+#define SECP256K1_BUILD_VERIFY_ONLY (1 << 3)
+#define SECP256K1_BUILD_CONTEXT_ALLOCATOR (1 << 4)
+#define SECP256K1_BUILD_ENDOMORPHISM (1 << 5)
+/* Optional modules compiled in */
+#define SECP256K1_BUILD_MODULE_ECDH (1 << 8)
+#define SECP256K1_BUILD_MODULE_RECOVERY (1 << 9)
+#define SECP256K1_BUILD_MODULE_SCALAR_OPS (1 << 10)
+#define SECP256K1_BUILD_MODULE_CONTEXT_EXPORT (1 << 11)
+#define SECP256K1_BUILD_MODULE_PREPARED (1 << 12)
+#define SECP256K1_BUILD_MODULE_MSM (1 << 13)
+#define SECP256K1_BUILD_MODULE_TWEAK_BATCH (1 << 14)
+
+/** Report how the library was compiled.
+ *  Returns: the SECP256K1_BUILD_* options the library was compiled with,
+ *           ORed together
+ *  Out:     window_size:   ECMULT_WINDOW_SIZE (cannot be NULL)
+ *           gen_prec_bits: ECMULT_GEN_PREC_BITS (cannot be NULL)
+ */
+SECP256K1_API unsigned int secp256k1_build_info(
+  unsigned int *window_size,
+  unsigned int *gen_prec_bits
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECP256K1_CONTEXT_EXPORT_H */
@@ -0,0 +1,70 @@
+#ifndef SECP256K1_MSM_H
+#define SECP256K1_MSM_H
+
+#include "secp256k1.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** Compute a multi-scalar multiplication of public keys,
+ *  out = scalars[0]*pubkeys[0] + ... + scalars[n-1]*pubkeys[n-1].
+ *
+ *  Uses Strauss' algorithm for few points and Pippenger's for many. The
+ *  intermediate tables live in the scratch space; if they don't all fit, the
+ *  points are processed in batches, which is slower but gives the same result.
+ *
+ *  Returns:  1: success
+ *            0: the sum is the point at infinity (including when n is 0), a
+ *               scalar overflows or a public key is invalid
+ *           -1: the scratch space is too small for even a single point
+ *  Args:    ctx:      a secp256k1 context object, initialized for verification
+ *           scratch:  scratch space for the intermediate tables
+ *  Out:     out:      pointer to a public key object for the result
+ *  In:      scalars:  array of pointers to 32-byte big-endian scalars
+ *           pubkeys:  array of pointers to public keys
+ *           n:        the number of terms
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_pubkey_msm(
+  const secp256k1_context* ctx,
+  secp256k1_scratch_space *scratch,
+  secp256k1_pubkey *out,
+  const unsigned char * const *scalars,
+  const secp256k1_pubkey * const *pubkeys,
+  size_t n
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
+/** Compute out = a*pubkey + b*G in a single multiplication, without a
+ *  scratch space. Runs in variable time, so neither scalar may be secret.
+ *
+ *  Returns:  1: success
+ *            0: the result is the point at infinity, a scalar overflows or
+ *               the public key is invalid
+ *  Args:    ctx:      a secp256k1 context object, initialized for verification
+ *  Out:     out:      pointer to a public key object for the result
+ *  In:      pubkey:   pointer to the public key to multiply by a
+ *           a32:      pointer to a 32-byte big-endian scalar
+ *           b32:      pointer to a 32-byte big-endian scalar
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_pubkey_mul_add_generator(
+  const secp256k1_context* ctx,
+  secp256k1_pubkey *out,
+  const secp256k1_pubkey *pubkey,
+  const unsigned char *a32,
+  const unsigned char *b32
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3) SECP256K1_ARG_NONNULL(4)
+  SECP256K1_ARG_NONNULL(5);
+
+/** Compute the scratch space size with which secp256k1_ec_pubkey_msm
+ *  processes n terms in a single batch. Smaller scratch spaces work too, down
+ *  to the size for a single term, at the cost of speed.
+ *  Returns: the size in bytes to pass to secp256k1_scratch_space_create, or
+ *           0 if n is 0
+ */
+SECP256K1_API size_t secp256k1_ec_pubkey_msm_scratch_size(size_t n);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECP256K1_MSM_H */
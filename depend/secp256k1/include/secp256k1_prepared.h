@@ -0,0 +1,59 @@
+#ifndef SECP256K1_PREPARED_H
+#define SECP256K1_PREPARED_H
+
+#include "secp256k1.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** Opaque data structure that holds a public key together with a table of
+ *  its precomputed odd multiples, for verifying many signatures by the same
+ *  key.
+ *
+ *  The caller allocates secp256k1_prepared_pubkey_size() bytes for it,
+ *  aligned to 8 bytes. The table uses the in-memory representation of this
+ *  build, so it must not be stored or sent to another process.
+ */
+typedef struct secp256k1_prepared_pubkey_struct secp256k1_prepared_pubkey;
+
+/** Compute the size of a prepared public key.
+ *  Returns: the number of bytes to allocate for a secp256k1_prepared_pubkey
+ */
+SECP256K1_API size_t secp256k1_prepared_pubkey_size(void);
+
+/** Precompute the verification table for a public key.
+ *  Returns: 1: success
+ *           0: the public key is invalid
+ *  Args:    ctx:       a context object (cannot be NULL)
+ *  Out:     prepared:  pointer to secp256k1_prepared_pubkey_size() bytes
+ *  In:      pubkey:    pointer to the public key to prepare
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_prepared_pubkey_create(
+  const secp256k1_context* ctx,
+  secp256k1_prepared_pubkey *prepared,
+  const secp256k1_pubkey *pubkey
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
+/** Verify an ECDSA signature with a prepared public key. This gives the same
+ *  result as secp256k1_ecdsa_verify with the original public key, but is
+ *  faster.
+ *  Returns: 1: correct signature
+ *           0: incorrect or unparseable signature
+ *  Args:    ctx:       a secp256k1 context object, initialized for verification
+ *  In:      sig:       the signature being verified (cannot be NULL)
+ *           msg32:     the 32-byte message hash being verified (cannot be NULL)
+ *           prepared:  pointer to a prepared public key (cannot be NULL)
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ecdsa_verify_prepared(
+  const secp256k1_context* ctx,
+  const secp256k1_ecdsa_signature *sig,
+  const unsigned char *msg32,
+  const secp256k1_prepared_pubkey *prepared
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3) SECP256K1_ARG_NONNULL(4);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECP256K1_PREPARED_H */
@@ -0,0 +1,96 @@
+#ifndef SECP256K1_SCALAR_OPS_H
+#define SECP256K1_SCALAR_OPS_H
+
+#include "secp256k1.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** All scalars in this module are 32-byte big-endian integers modulo the
+ *  curve order. Unlike secret keys, zero is a valid scalar.
+ */
+
+/** Check that a 32-byte array is a scalar below the curve order.
+ *  Returns: 1: scalar is in range
+ *           0: scalar is greater than or equal to the curve order
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  In:      scalar:   pointer to a 32-byte scalar
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_scalar_verify(
+  const secp256k1_context* ctx,
+  const unsigned char *scalar
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2);
+
+/** Reduce a 64-byte big-endian integer modulo the curve order.
+ *  Returns: 1 always
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  Out:     result:   pointer to a 32-byte array for the reduced scalar
+ *  In:      input64:  pointer to a 64-byte big-endian integer
+ */
+SECP256K1_API int secp256k1_ec_scalar_reduce512(
+  const secp256k1_context* ctx,
+  unsigned char *result,
+  const unsigned char *input64
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
+/** Add two scalars modulo the curve order.
+ *  Returns: 1: success
+ *           0: one of the inputs was out of range
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  Out:     result:   pointer to a 32-byte array for the sum
+ *  In:      a, b:     pointers to 32-byte scalars
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_scalar_add(
+  const secp256k1_context* ctx,
+  unsigned char *result,
+  const unsigned char *a,
+  const unsigned char *b
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3) SECP256K1_ARG_NONNULL(4);
+
+/** Multiply two scalars modulo the curve order.
+ *  Returns: 1: success
+ *           0: one of the inputs was out of range
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  Out:     result:   pointer to a 32-byte array for the product
+ *  In:      a, b:     pointers to 32-byte scalars
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_scalar_mul(
+  const secp256k1_context* ctx,
+  unsigned char *result,
+  const unsigned char *a,
+  const unsigned char *b
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3) SECP256K1_ARG_NONNULL(4);
+
+/** Negate a scalar modulo the curve order.
+ *  Returns: 1: success
+ *           0: the input was out of range
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  Out:     result:   pointer to a 32-byte array for the negation
+ *  In:      a:        pointer to a 32-byte scalar
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_scalar_negate(
+  const secp256k1_context* ctx,
+  unsigned char *result,
+  const unsigned char *a
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
+/** Compute the multiplicative inverse of a scalar modulo the curve order,
+ *  in constant time.
+ *  Returns: 1: success
+ *           0: the input was zero or out of range
+ *  Args:    ctx:      pointer to a context object (cannot be NULL)
+ *  Out:     result:   pointer to a 32-byte array for the inverse
+ *  In:      a:        pointer to a 32-byte scalar
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_scalar_inverse(
+  const secp256k1_context* ctx,
+  unsigned char *result,
+  const unsigned char *a
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(2) SECP256K1_ARG_NONNULL(3);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECP256K1_SCALAR_OPS_H */
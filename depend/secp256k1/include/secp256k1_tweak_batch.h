@@ -0,0 +1,37 @@
+#ifndef SECP256K1_TWEAK_BATCH_H
+#define SECP256K1_TWEAK_BATCH_H
+
+#include "secp256k1.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/** Tweak every public key in an array by adding tweak times the generator.
+ *  Gives the same results as calling secp256k1_ec_pubkey_tweak_add on each
+ *  key, but tweak*G is computed only once and the results are normalized
+ *  with one field inversion per 64 keys.
+ *
+ *  Returns: 1: success
+ *           0: the tweak is out of range (chance of around 1 in 2^128 for
+ *              uniformly random 32-byte arrays), a public key is invalid or
+ *              a result would be the point at infinity. The contents of
+ *              pubkeys are then unspecified.
+ *  Args:    ctx:      pointer to a context object initialized for validation
+ *                     (cannot be NULL)
+ *  In/Out:  pubkeys:  array of n public keys, tweaked in place
+ *  In:      n:        the number of keys
+ *           tweak:    pointer to a 32-byte tweak
+ */
+SECP256K1_API SECP256K1_WARN_UNUSED_RESULT int secp256k1_ec_pubkey_tweak_add_batch(
+  const secp256k1_context* ctx,
+  secp256k1_pubkey *pubkeys,
+  size_t n,
+  const unsigned char *tweak
+) SECP256K1_ARG_NONNULL(1) SECP256K1_ARG_NONNULL(4);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECP256K1_TWEAK_BATCH_H */
@@ -22,6 +22,9 @@ typedef struct {
 
 static void secp256k1_ecmult_context_init(secp256k1_ecmult_context *ctx);
 static void secp256k1_ecmult_context_build(secp256k1_ecmult_context *ctx, const secp256k1_callback *cb);
+/* Like secp256k1_ecmult_context_build, but with the tables in storage unless it is NULL */
+static void secp256k1_ecmult_context_build_into(secp256k1_ecmult_context *ctx, void *storage,
+                                                const secp256k1_callback *cb);
 static void secp256k1_ecmult_context_clone(secp256k1_ecmult_context *dst,
                                            const secp256k1_ecmult_context *src, const secp256k1_callback *cb);
 static void secp256k1_ecmult_context_clear(secp256k1_ecmult_context *ctx);
@@ -31,16 +31,24 @@
 #else
 /* optimal for 128-bit and 256-bit exponents. */
 #define WINDOW_A 5
-/** larger numbers may result in slightly better performance, at the cost of
-    exponentially larger precomputed tables. */
+/** Larger values of ECMULT_WINDOW_SIZE may result in slightly better
+ *  verification performance, at the cost of exponentially larger precomputed
+ *  tables: each holds 2^(ECMULT_WINDOW_SIZE-2) 64-byte entries, and there are
+ *  two of them with USE_ENDOMORPHISM. */
+#ifndef ECMULT_WINDOW_SIZE
 #ifdef USE_ENDOMORPHISM
-/** Two tables for window size 15: 1.375 MiB. */
-#define WINDOW_G 15
+/** Two tables for window size 15: 1 MiB. */
+#define ECMULT_WINDOW_SIZE 15
 #else
-/** One table for window size 16: 1.375 MiB. */
-#define WINDOW_G 16
+/** One table for window size 16: 1 MiB. */
+#define ECMULT_WINDOW_SIZE 16
 #endif
 #endif
+#if ECMULT_WINDOW_SIZE < 2 || ECMULT_WINDOW_SIZE > 24
+#  error Set ECMULT_WINDOW_SIZE to an integer in range [2..24].
+#endif
+#define WINDOW_G ECMULT_WINDOW_SIZE
+#endif
 
 #ifdef USE_ENDOMORPHISM
     #define WNAF_BITS 128
@@ -190,6 +198,12 @@ static void secp256k1_ecmult_context_init(secp256k1_ecmult_context *ctx) {
 }
 
 static void secp256k1_ecmult_context_build(secp256k1_ecmult_context *ctx, const secp256k1_callback *cb) {
+    secp256k1_ecmult_context_build_into(ctx, NULL, cb);
+}
+
+static void secp256k1_ecmult_context_build_into(secp256k1_ecmult_context *ctx, void *storage,
+                                                const secp256k1_callback *cb) {
+    size_t table_size = sizeof((*ctx->pre_g)[0]) * ECMULT_TABLE_SIZE(WINDOW_G);
     secp256k1_gej gj;
 
     if (ctx->pre_g != NULL) {
@@ -199,7 +213,7 @@ static void secp256k1_ecmult_context_build(secp256k1_ecmult_context *ctx, const
     /* get the generator */
     secp256k1_gej_set_ge(&gj, &secp256k1_ge_const_g);
 
-    ctx->pre_g = (secp256k1_ge_storage (*)[])checked_malloc(cb, sizeof((*ctx->pre_g)[0]) * ECMULT_TABLE_SIZE(WINDOW_G));
+    ctx->pre_g = (secp256k1_ge_storage (*)[])(storage != NULL ? storage : checked_malloc(cb, table_size));
 
     /* precompute the tables with odd multiples */
     secp256k1_ecmult_odd_multiples_table_storage_var(ECMULT_TABLE_SIZE(WINDOW_G), *ctx->pre_g, &gj, cb);
@@ -209,7 +223,9 @@ static void secp256k1_ecmult_context_build(secp256k1_ecmult_context *ctx, const
         secp256k1_gej g_128j;
         int i;
 
-        ctx->pre_g_128 = (secp256k1_ge_storage (*)[])checked_malloc(cb, sizeof((*ctx->pre_g_128)[0]) * ECMULT_TABLE_SIZE(WINDOW_G));
+        /* With storage, this table follows the first */
+        ctx->pre_g_128 = (secp256k1_ge_storage (*)[])(storage != NULL ? (unsigned char *)storage + table_size
+                                                                        : checked_malloc(cb, table_size));
 
         /* calculate 2^128*generator */
         g_128j = gj;
@@ -0,0 +1,313 @@
+/**********************************************************************
+ * Copyright (c) 2018 The rust-secp256k1-sgx developers               *
+ * Distributed under the MIT software license, see the accompanying   *
+ * file COPYING or http://www.opensource.org/licenses/mit-license.php.*
+ **********************************************************************/
+
+#ifndef SECP256K1_MODULE_CONTEXT_EXPORT_MAIN_H
+#define SECP256K1_MODULE_CONTEXT_EXPORT_MAIN_H
+
+#include "include/secp256k1_context_export.h"
+
+#define CONTEXT_EXPORT_VERSION 1
+#define CONTEXT_EXPORT_HEADER_SIZE 8
+#define CONTEXT_EXPORT_HASH_SIZE 32
+#define CONTEXT_EXPORT_FLAG_VERIFY 1
+#define CONTEXT_EXPORT_FLAG_SIGN 2
+/* Blinding scalar, blinding point x, y, z and its infinity flag */
+#define CONTEXT_EXPORT_BLIND_SIZE (32 + 3 * 32 + 1)
+/* Alignment of the context and each table in a preallocated buffer */
+#define CONTEXT_PREALLOCATED_ALIGNMENT 16
+
+static const unsigned char context_export_magic[4] = { 'S', 'C', 'T', 'X' };
+
+static size_t secp256k1_context_export_verify_size(void) {
+    size_t size = sizeof(secp256k1_ge_storage) * ECMULT_TABLE_SIZE(WINDOW_G);
+#ifdef USE_ENDOMORPHISM
+    size *= 2;
+#endif
+    return size;
+}
+
+static size_t secp256k1_context_export_sign_size(void) {
+    return sizeof(secp256k1_ge_storage) * ECMULT_GEN_PREC_N * ECMULT_GEN_PREC_G + CONTEXT_EXPORT_BLIND_SIZE;
+}
+
+static unsigned char secp256k1_context_export_build_options(void) {
+    unsigned char options = ECMULT_GEN_PREC_BITS << 1;
+#ifdef USE_ENDOMORPHISM
+    options |= 1;
+#endif
+    return options;
+}
+
+static size_t secp256k1_context_export_size_for(unsigned char flags) {
+    size_t size = CONTEXT_EXPORT_HEADER_SIZE + CONTEXT_EXPORT_HASH_SIZE;
+    if (flags & CONTEXT_EXPORT_FLAG_VERIFY) {
+        size += secp256k1_context_export_verify_size();
+    }
+    if (flags & CONTEXT_EXPORT_FLAG_SIGN) {
+        size += secp256k1_context_export_sign_size();
+    }
+    return size;
+}
+
+static unsigned char secp256k1_context_export_flags(const secp256k1_context* ctx) {
+    unsigned char flags = 0;
+    if (secp256k1_ecmult_context_is_built(&ctx->ecmult_ctx)) {
+        flags |= CONTEXT_EXPORT_FLAG_VERIFY;
+    }
+    if (secp256k1_ecmult_gen_context_is_built(&ctx->ecmult_gen_ctx)) {
+        flags |= CONTEXT_EXPORT_FLAG_SIGN;
+    }
+    return flags;
+}
+
+unsigned int secp256k1_context_capabilities(const secp256k1_context* ctx) {
+    unsigned char flags;
+    unsigned int ret = SECP256K1_FLAGS_TYPE_CONTEXT;
+    VERIFY_CHECK(ctx != NULL);
+    flags = secp256k1_context_export_flags(ctx);
+    if (flags & CONTEXT_EXPORT_FLAG_VERIFY) {
+        ret |= SECP256K1_FLAGS_BIT_CONTEXT_VERIFY;
+    }
+    if (flags & CONTEXT_EXPORT_FLAG_SIGN) {
+        ret |= SECP256K1_FLAGS_BIT_CONTEXT_SIGN;
+    }
+    return ret;
+}
+
+void secp256k1_context_table_sizes(size_t *sign_size, size_t *verify_size, unsigned int flags) {
+    *sign_size = 0;
+    *verify_size = 0;
+    if (flags & SECP256K1_FLAGS_BIT_CONTEXT_SIGN) {
+        *sign_size = secp256k1_context_export_sign_size() - CONTEXT_EXPORT_BLIND_SIZE;
+    }
+    if (flags & SECP256K1_FLAGS_BIT_CONTEXT_VERIFY) {
+        *verify_size = secp256k1_context_export_verify_size();
+    }
+}
+
+static size_t secp256k1_context_preallocated_round(size_t size) {
+    return (size + CONTEXT_PREALLOCATED_ALIGNMENT - 1) / CONTEXT_PREALLOCATED_ALIGNMENT * CONTEXT_PREALLOCATED_ALIGNMENT;
+}
+
+size_t secp256k1_context_preallocated_size(unsigned int flags) {
+    size_t sign_size, verify_size;
+    secp256k1_context_table_sizes(&sign_size, &verify_size, flags);
+    /* Up to an alignment's worth is skipped to align the context */
+    return CONTEXT_PREALLOCATED_ALIGNMENT - 1 + secp256k1_context_preallocated_round(sizeof(secp256k1_context)) +
+           secp256k1_context_preallocated_round(sign_size) + verify_size;
+}
+
+secp256k1_context* secp256k1_context_preallocated_create(void *prealloc, size_t prealloclen, unsigned int flags) {
+    size_t sign_size, verify_size;
+    unsigned char *p = (unsigned char *)prealloc;
+    secp256k1_context* ret;
+
+    if (prealloc == NULL || (flags & SECP256K1_FLAGS_TYPE_MASK) != SECP256K1_FLAGS_TYPE_CONTEXT ||
+        prealloclen < secp256k1_context_preallocated_size(flags)) {
+        return NULL;
+    }
+    secp256k1_context_table_sizes(&sign_size, &verify_size, flags);
+    p += (CONTEXT_PREALLOCATED_ALIGNMENT - (uintptr_t)p % CONTEXT_PREALLOCATED_ALIGNMENT) % CONTEXT_PREALLOCATED_ALIGNMENT;
+    ret = (secp256k1_context*)p;
+    p += secp256k1_context_preallocated_round(sizeof(secp256k1_context));
+
+    ret->illegal_callback = default_illegal_callback;
+    ret->error_callback = default_error_callback;
+    ret->preallocated = 1;
+    secp256k1_ecmult_context_init(&ret->ecmult_ctx);
+    secp256k1_ecmult_gen_context_init(&ret->ecmult_gen_ctx);
+    if (flags & SECP256K1_FLAGS_BIT_CONTEXT_SIGN) {
+        secp256k1_ecmult_gen_context_build_into(&ret->ecmult_gen_ctx, p, &ret->error_callback);
+        p += secp256k1_context_preallocated_round(sign_size);
+    }
+    if (flags & SECP256K1_FLAGS_BIT_CONTEXT_VERIFY) {
+        secp256k1_ecmult_context_build_into(&ret->ecmult_ctx, p, &ret->error_callback);
+    }
+    return ret;
+}
+
+int secp256k1_context_rebuild_sign(secp256k1_context* ctx) {
+    void *storage;
+    VERIFY_CHECK(ctx != NULL);
+    if (!secp256k1_ecmult_gen_context_is_built(&ctx->ecmult_gen_ctx)) {
+        return 0;
+    }
+    /* Build into the table's own memory, which may be preallocated */
+    storage = ctx->ecmult_gen_ctx.prec;
+    ctx->ecmult_gen_ctx.prec = NULL;
+    secp256k1_ecmult_gen_context_build_into(&ctx->ecmult_gen_ctx, storage, &ctx->error_callback);
+    return 1;
+}
+
+size_t secp256k1_context_export_size(const secp256k1_context* ctx) {
+    VERIFY_CHECK(ctx != NULL);
+    return secp256k1_context_export_size_for(secp256k1_context_export_flags(ctx));
+}
+
+int secp256k1_context_export(const secp256k1_context* ctx, unsigned char *output, size_t outputlen) {
+    secp256k1_sha256 hash;
+    unsigned char flags;
+    unsigned char *p = output;
+    size_t table_size = sizeof(secp256k1_ge_storage) * ECMULT_TABLE_SIZE(WINDOW_G);
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(output != NULL);
+
+    flags = secp256k1_context_export_flags(ctx);
+    if (outputlen != secp256k1_context_export_size_for(flags)) {
+        return 0;
+    }
+
+    memcpy(p, context_export_magic, 4);
+    p[4] = CONTEXT_EXPORT_VERSION;
+    p[5] = flags;
+    p[6] = WINDOW_G;
+    p[7] = secp256k1_context_export_build_options();
+    p += CONTEXT_EXPORT_HEADER_SIZE;
+
+    if (flags & CONTEXT_EXPORT_FLAG_VERIFY) {
+        memcpy(p, ctx->ecmult_ctx.pre_g, table_size);
+        p += table_size;
+#ifdef USE_ENDOMORPHISM
+        memcpy(p, ctx->ecmult_ctx.pre_g_128, table_size);
+        p += table_size;
+#endif
+    }
+    if (flags & CONTEXT_EXPORT_FLAG_SIGN) {
+        secp256k1_gej initial = ctx->ecmult_gen_ctx.initial;
+        memcpy(p, ctx->ecmult_gen_ctx.prec, sizeof(*ctx->ecmult_gen_ctx.prec));
+        p += sizeof(*ctx->ecmult_gen_ctx.prec);
+        secp256k1_scalar_get_b32(p, &ctx->ecmult_gen_ctx.blind);
+        secp256k1_fe_normalize(&initial.x);
+        secp256k1_fe_normalize(&initial.y);
+        secp256k1_fe_normalize(&initial.z);
+        secp256k1_fe_get_b32(p + 32, &initial.x);
+        secp256k1_fe_get_b32(p + 64, &initial.y);
+        secp256k1_fe_get_b32(p + 96, &initial.z);
+        p[128] = initial.infinity ? 1 : 0;
+        p += CONTEXT_EXPORT_BLIND_SIZE;
+        secp256k1_gej_clear(&initial);
+    }
+
+    secp256k1_sha256_initialize(&hash);
+    secp256k1_sha256_write(&hash, output, p - output);
+    secp256k1_sha256_finalize(&hash, p);
+    return 1;
+}
+
+secp256k1_context* secp256k1_context_import(const unsigned char *input, size_t inputlen, unsigned int required) {
+    secp256k1_sha256 hash;
+    unsigned char digest[CONTEXT_EXPORT_HASH_SIZE];
+    unsigned char flags;
+    const unsigned char *p = input;
+    size_t table_size = sizeof(secp256k1_ge_storage) * ECMULT_TABLE_SIZE(WINDOW_G);
+    secp256k1_context* ret;
+    int overflow;
+
+    if (input == NULL || inputlen < CONTEXT_EXPORT_HEADER_SIZE + CONTEXT_EXPORT_HASH_SIZE) {
+        return NULL;
+    }
+    flags = input[5];
+    if (memcmp(input, context_export_magic, 4) != 0 ||
+        input[4] != CONTEXT_EXPORT_VERSION ||
+        (flags & ~(CONTEXT_EXPORT_FLAG_VERIFY | CONTEXT_EXPORT_FLAG_SIGN)) != 0 ||
+        input[6] != WINDOW_G ||
+        input[7] != secp256k1_context_export_build_options() ||
+        inputlen != secp256k1_context_export_size_for(flags)) {
+        return NULL;
+    }
+    if (((required & SECP256K1_FLAGS_BIT_CONTEXT_VERIFY) && !(flags & CONTEXT_EXPORT_FLAG_VERIFY)) ||
+        ((required & SECP256K1_FLAGS_BIT_CONTEXT_SIGN) && !(flags & CONTEXT_EXPORT_FLAG_SIGN))) {
+        return NULL;
+    }
+    secp256k1_sha256_initialize(&hash);
+    secp256k1_sha256_write(&hash, input, inputlen - CONTEXT_EXPORT_HASH_SIZE);
+    secp256k1_sha256_finalize(&hash, digest);
+    if (memcmp(digest, input + inputlen - CONTEXT_EXPORT_HASH_SIZE, CONTEXT_EXPORT_HASH_SIZE) != 0) {
+        return NULL;
+    }
+    p += CONTEXT_EXPORT_HEADER_SIZE;
+
+    ret = (secp256k1_context*)checked_malloc(&default_error_callback, sizeof(secp256k1_context));
+    ret->illegal_callback = default_illegal_callback;
+    ret->error_callback = default_error_callback;
+    ret->preallocated = 0;
+    secp256k1_ecmult_context_init(&ret->ecmult_ctx);
+    secp256k1_ecmult_gen_context_init(&ret->ecmult_gen_ctx);
+
+    if (flags & CONTEXT_EXPORT_FLAG_VERIFY) {
+        ret->ecmult_ctx.pre_g = (secp256k1_ge_storage (*)[])checked_malloc(&ret->error_callback, table_size);
+        memcpy(ret->ecmult_ctx.pre_g, p, table_size);
+        p += table_size;
+#ifdef USE_ENDOMORPHISM
+        ret->ecmult_ctx.pre_g_128 = (secp256k1_ge_storage (*)[])checked_malloc(&ret->error_callback, table_size);
+        memcpy(ret->ecmult_ctx.pre_g_128, p, table_size);
+        p += table_size;
+#endif
+    }
+    if (flags & CONTEXT_EXPORT_FLAG_SIGN) {
+        secp256k1_ecmult_gen_context *gen = &ret->ecmult_gen_ctx;
+        gen->prec = (secp256k1_ge_storage (*)[ECMULT_GEN_PREC_N][ECMULT_GEN_PREC_G])checked_malloc(&ret->error_callback, sizeof(*gen->prec));
+        memcpy(gen->prec, p, sizeof(*gen->prec));
+        p += sizeof(*gen->prec);
+        secp256k1_scalar_set_b32(&gen->blind, p, &overflow);
+        if (overflow ||
+            !secp256k1_fe_set_b32(&gen->initial.x, p + 32) ||
+            !secp256k1_fe_set_b32(&gen->initial.y, p + 64) ||
+            !secp256k1_fe_set_b32(&gen->initial.z, p + 96) ||
+            p[128] > 1) {
+            secp256k1_context_destroy(ret);
+            return NULL;
+        }
+        gen->initial.infinity = p[128];
+    }
+    return ret;
+}
+
+unsigned int secp256k1_build_info(unsigned int *window_size, unsigned int *gen_prec_bits) {
+    unsigned int options = 0;
+#ifdef SECP256K1_LVI_MITIGATION
+    options |= SECP256K1_BUILD_LVI_MITIGATION;
+#endif
+#ifdef USE_ASM_X86_64
+    options |= SECP256K1_BUILD_ASM_X86_64;
+#endif
+#ifdef USE_FIELD_5X52
+    options |= SECP256K1_BUILD_WIDEMUL_INT128;
+#endif
+#ifdef VERIFY_ONLY
+    options |= SECP256K1_BUILD_VERIFY_ONLY;
+#endif
+#ifdef USE_CONTEXT_ALLOCATOR
+    options |= SECP256K1_BUILD_CONTEXT_ALLOCATOR;
+#endif
+#ifdef USE_ENDOMORPHISM
+    options |= SECP256K1_BUILD_ENDOMORPHISM;
+#endif
+#ifdef ENABLE_MODULE_ECDH
+    options |= SECP256K1_BUILD_MODULE_ECDH;
+#endif
+#ifdef ENABLE_MODULE_RECOVERY
+    options |= SECP256K1_BUILD_MODULE_RECOVERY;
+#endif
+#ifdef ENABLE_MODULE_SCALAR_OPS
+    options |= SECP256K1_BUILD_MODULE_SCALAR_OPS;
+#endif
+    options |= SECP256K1_BUILD_MODULE_CONTEXT_EXPORT;
+#ifdef ENABLE_MODULE_PREPARED
+    options |= SECP256K1_BUILD_MODULE_PREPARED;
+#endif
+#ifdef ENABLE_MODULE_MSM
+    options |= SECP256K1_BUILD_MODULE_MSM;
+#endif
+#ifdef ENABLE_MODULE_TWEAK_BATCH
+    options |= SECP256K1_BUILD_MODULE_TWEAK_BATCH;
+#endif
+    *window_size = ECMULT_WINDOW_SIZE;
+    *gen_prec_bits = ECMULT_GEN_PREC_BITS;
+    return options;
+}
+
+#endif /* SECP256K1_MODULE_CONTEXT_EXPORT_MAIN_H */
@@ -0,0 +1,129 @@
+/**********************************************************************
+ * Copyright (c) 2018 The rust-secp256k1-sgx developers               *
+ * Distributed under the MIT software license, see the accompanying   *
+ * file COPYING or http://www.opensource.org/licenses/mit-license.php.*
+ **********************************************************************/
+
+#ifndef SECP256K1_MODULE_MSM_MAIN_H
+#define SECP256K1_MODULE_MSM_MAIN_H
+
+#include "include/secp256k1_msm.h"
+
+typedef struct {
+    const secp256k1_context *ctx;
+    const unsigned char * const *scalars;
+    const secp256k1_pubkey * const *pubkeys;
+    int invalid;
+} secp256k1_msm_data;
+
+static int secp256k1_msm_callback(secp256k1_scalar *sc, secp256k1_ge *pt, size_t idx, void *data) {
+    secp256k1_msm_data *d = (secp256k1_msm_data *)data;
+    int overflow;
+
+    secp256k1_scalar_set_b32(sc, d->scalars[idx], &overflow);
+    if (overflow || !secp256k1_pubkey_load(d->ctx, pt, d->pubkeys[idx])) {
+        d->invalid = 1;
+        return 0;
+    }
+    return 1;
+}
+
+int secp256k1_ec_pubkey_msm(const secp256k1_context* ctx, secp256k1_scratch_space *scratch, secp256k1_pubkey *out, const unsigned char * const *scalars, const secp256k1_pubkey * const *pubkeys, size_t n) {
+    secp256k1_msm_data data;
+    secp256k1_gej rj;
+    secp256k1_ge r;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(secp256k1_ecmult_context_is_built(&ctx->ecmult_ctx));
+    ARG_CHECK(scratch != NULL);
+    ARG_CHECK(out != NULL);
+    memset(out, 0, sizeof(*out));
+    ARG_CHECK(n == 0 || scalars != NULL);
+    ARG_CHECK(n == 0 || pubkeys != NULL);
+
+    data.ctx = ctx;
+    data.scalars = scalars;
+    data.pubkeys = pubkeys;
+    data.invalid = 0;
+    if (!secp256k1_ecmult_multi_var(&ctx->ecmult_ctx, scratch, &rj, NULL, secp256k1_msm_callback, &data, n)) {
+        /* ecmult_multi_var fails either because a callback did or because
+         * the scratch space can't hold a single point */
+        return data.invalid ? 0 : -1;
+    }
+    if (secp256k1_gej_is_infinity(&rj)) {
+        return 0;
+    }
+    secp256k1_ge_set_gej_var(&r, &rj);
+    secp256k1_pubkey_save(out, &r);
+    return 1;
+}
+
+int secp256k1_ec_pubkey_mul_add_generator(const secp256k1_context* ctx, secp256k1_pubkey *out, const secp256k1_pubkey *pubkey, const unsigned char *a32, const unsigned char *b32) {
+    secp256k1_ge p;
+    secp256k1_gej pj;
+    secp256k1_scalar a, b;
+    int overflow = 0;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(secp256k1_ecmult_context_is_built(&ctx->ecmult_ctx));
+    ARG_CHECK(out != NULL);
+    memset(out, 0, sizeof(*out));
+    ARG_CHECK(pubkey != NULL);
+    ARG_CHECK(a32 != NULL);
+    ARG_CHECK(b32 != NULL);
+
+    secp256k1_scalar_set_b32(&a, a32, &overflow);
+    if (overflow || !secp256k1_pubkey_load(ctx, &p, pubkey)) {
+        return 0;
+    }
+    secp256k1_scalar_set_b32(&b, b32, &overflow);
+    if (overflow) {
+        return 0;
+    }
+    /* Strauss' algorithm with the generator's precomputed table, as
+     * signature verification uses */
+    secp256k1_gej_set_ge(&pj, &p);
+    secp256k1_ecmult(&ctx->ecmult_ctx, &pj, &pj, &a, &b);
+    if (secp256k1_gej_is_infinity(&pj)) {
+        return 0;
+    }
+    secp256k1_ge_set_gej(&p, &pj);
+    secp256k1_pubkey_save(out, &p);
+    return 1;
+}
+
+/* Whether ecmult_multi_var can process n points in one batch with a scratch
+ * space of the given size, mirroring its choice of algorithm */
+static int secp256k1_msm_fits(size_t size, size_t n) {
+    secp256k1_scratch scratch;
+    memset(&scratch, 0, sizeof(scratch));
+    scratch.max_size = size;
+    if (secp256k1_pippenger_max_points(&scratch) < n) {
+        return 0;
+    }
+    return n >= ECMULT_PIPPENGER_THRESHOLD || secp256k1_strauss_max_points(&scratch) >= n;
+}
+
+size_t secp256k1_ec_pubkey_msm_scratch_size(size_t n) {
+    size_t lo = 0, hi = 1024;
+    if (n == 0) {
+        return 0;
+    }
+    if (n > ECMULT_MAX_POINTS_PER_BATCH) {
+        n = ECMULT_MAX_POINTS_PER_BATCH;
+    }
+    while (!secp256k1_msm_fits(hi, n)) {
+        lo = hi;
+        hi *= 2;
+    }
+    /* The smallest fitting size is in (lo, hi] */
+    while (hi - lo > 1) {
+        size_t mid = lo + (hi - lo) / 2;
+        if (secp256k1_msm_fits(mid, n)) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    return hi;
+}
+
+#endif /* SECP256K1_MODULE_MSM_MAIN_H */
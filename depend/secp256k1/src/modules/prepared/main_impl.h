@@ -0,0 +1,180 @@
+/**********************************************************************
+ * Copyright (c) 2018 The rust-secp256k1-sgx developers               *
+ * Distributed under the MIT software license, see the accompanying   *
+ * file COPYING or http://www.opensource.org/licenses/mit-license.php.*
+ **********************************************************************/
+
+#ifndef SECP256K1_MODULE_PREPARED_MAIN_H
+#define SECP256K1_MODULE_PREPARED_MAIN_H
+
+#include "include/secp256k1_prepared.h"
+
+/* wNAF window for prepared keys. Plain verification uses WINDOW_A = 5 and
+ * rebuilds its 8-entry table on every call; a stored table can afford 64
+ * entries, which saves about a third of the point additions for the key. */
+#define PREPARED_WINDOW 8
+
+struct secp256k1_prepared_pubkey_struct {
+    secp256k1_ge_storage pre[ECMULT_TABLE_SIZE(PREPARED_WINDOW)];
+#ifdef USE_ENDOMORPHISM
+    secp256k1_ge_storage pre_lam[ECMULT_TABLE_SIZE(PREPARED_WINDOW)];
+#endif
+};
+
+size_t secp256k1_prepared_pubkey_size(void) {
+    return sizeof(secp256k1_prepared_pubkey);
+}
+
+int secp256k1_prepared_pubkey_create(const secp256k1_context* ctx, secp256k1_prepared_pubkey *prepared, const secp256k1_pubkey *pubkey) {
+    secp256k1_gej prej[ECMULT_TABLE_SIZE(PREPARED_WINDOW)];
+    secp256k1_fe zr[ECMULT_TABLE_SIZE(PREPARED_WINDOW)];
+    secp256k1_ge pre[ECMULT_TABLE_SIZE(PREPARED_WINDOW)];
+    secp256k1_ge q;
+    secp256k1_gej qj;
+    int i;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(prepared != NULL);
+    ARG_CHECK(pubkey != NULL);
+
+    if (!secp256k1_pubkey_load(ctx, &q, pubkey)) {
+        return 0;
+    }
+    secp256k1_gej_set_ge(&qj, &q);
+    secp256k1_ecmult_odd_multiples_table(ECMULT_TABLE_SIZE(PREPARED_WINDOW), prej, zr, &qj);
+    secp256k1_ge_set_table_gej_var(pre, prej, zr, ECMULT_TABLE_SIZE(PREPARED_WINDOW));
+    for (i = 0; i < ECMULT_TABLE_SIZE(PREPARED_WINDOW); i++) {
+        secp256k1_ge_to_storage(&prepared->pre[i], &pre[i]);
+#ifdef USE_ENDOMORPHISM
+        secp256k1_ge_mul_lambda(&q, &pre[i]);
+        secp256k1_ge_to_storage(&prepared->pre_lam[i], &q);
+#endif
+    }
+    return 1;
+}
+
+/* Computes na*A + ng*G like secp256k1_ecmult, with A's multiples taken from
+ * the prepared table. All table entries are affine, so no Z correction is
+ * needed at the end. */
+static void secp256k1_ecmult_prepared(const secp256k1_ecmult_context *ctx, secp256k1_gej *r, const secp256k1_prepared_pubkey *prepared, const secp256k1_scalar *na, const secp256k1_scalar *ng) {
+    secp256k1_ge tmpa;
+#ifdef USE_ENDOMORPHISM
+    secp256k1_scalar na_1, na_lam, ng_1, ng_128;
+    int wnaf_na_1[130];
+    int wnaf_na_lam[130];
+    int wnaf_ng_1[129];
+    int wnaf_ng_128[129];
+    int bits_na_1, bits_na_lam, bits_ng_1, bits_ng_128;
+#else
+    int wnaf_na[256];
+    int wnaf_ng[256];
+    int bits_na, bits_ng;
+#endif
+    int bits;
+    int i;
+
+#ifdef USE_ENDOMORPHISM
+    secp256k1_scalar_split_lambda(&na_1, &na_lam, na);
+    bits_na_1   = secp256k1_ecmult_wnaf(wnaf_na_1,   130, &na_1,   PREPARED_WINDOW);
+    bits_na_lam = secp256k1_ecmult_wnaf(wnaf_na_lam, 130, &na_lam, PREPARED_WINDOW);
+    secp256k1_scalar_split_128(&ng_1, &ng_128, ng);
+    bits_ng_1   = secp256k1_ecmult_wnaf(wnaf_ng_1,   129, &ng_1,   WINDOW_G);
+    bits_ng_128 = secp256k1_ecmult_wnaf(wnaf_ng_128, 129, &ng_128, WINDOW_G);
+    bits = bits_na_1;
+    if (bits_na_lam > bits) {
+        bits = bits_na_lam;
+    }
+    if (bits_ng_1 > bits) {
+        bits = bits_ng_1;
+    }
+    if (bits_ng_128 > bits) {
+        bits = bits_ng_128;
+    }
+#else
+    bits_na = secp256k1_ecmult_wnaf(wnaf_na, 256, na, PREPARED_WINDOW);
+    bits_ng = secp256k1_ecmult_wnaf(wnaf_ng, 256, ng, WINDOW_G);
+    bits = bits_na > bits_ng ? bits_na : bits_ng;
+#endif
+
+    secp256k1_gej_set_infinity(r);
+    for (i = bits - 1; i >= 0; i--) {
+        int n;
+        secp256k1_gej_double_var(r, r, NULL);
+#ifdef USE_ENDOMORPHISM
+        if (i < bits_na_1 && (n = wnaf_na_1[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, prepared->pre, n, PREPARED_WINDOW);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+        if (i < bits_na_lam && (n = wnaf_na_lam[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, prepared->pre_lam, n, PREPARED_WINDOW);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+        if (i < bits_ng_1 && (n = wnaf_ng_1[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, *ctx->pre_g, n, WINDOW_G);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+        if (i < bits_ng_128 && (n = wnaf_ng_128[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, *ctx->pre_g_128, n, WINDOW_G);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+#else
+        if (i < bits_na && (n = wnaf_na[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, prepared->pre, n, PREPARED_WINDOW);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+        if (i < bits_ng && (n = wnaf_ng[i])) {
+            ECMULT_TABLE_GET_GE_STORAGE(&tmpa, *ctx->pre_g, n, WINDOW_G);
+            secp256k1_gej_add_ge_var(r, r, &tmpa, NULL);
+        }
+#endif
+    }
+}
+
+/* secp256k1_ecdsa_sig_verify with a prepared key */
+static int secp256k1_ecdsa_sig_verify_prepared(const secp256k1_ecmult_context *ctx, const secp256k1_scalar *sigr, const secp256k1_scalar *sigs, const secp256k1_prepared_pubkey *prepared, const secp256k1_scalar *message) {
+    unsigned char c[32];
+    secp256k1_scalar sn, u1, u2;
+    secp256k1_fe xr;
+    secp256k1_gej pr;
+
+    if (secp256k1_scalar_is_zero(sigr) || secp256k1_scalar_is_zero(sigs)) {
+        return 0;
+    }
+
+    secp256k1_scalar_inverse_var(&sn, sigs);
+    secp256k1_scalar_mul(&u1, &sn, message);
+    secp256k1_scalar_mul(&u2, &sn, sigr);
+    secp256k1_ecmult_prepared(ctx, &pr, prepared, &u2, &u1);
+    if (secp256k1_gej_is_infinity(&pr)) {
+        return 0;
+    }
+
+    /* See secp256k1_ecdsa_sig_verify for why comparing against xr and
+     * xr + n avoids an inversion */
+    secp256k1_scalar_get_b32(c, sigr);
+    secp256k1_fe_set_b32(&xr, c);
+    if (secp256k1_gej_eq_x_var(&xr, &pr)) {
+        return 1;
+    }
+    if (secp256k1_fe_cmp_var(&xr, &secp256k1_ecdsa_const_p_minus_order) >= 0) {
+        return 0;
+    }
+    secp256k1_fe_add(&xr, &secp256k1_ecdsa_const_order_as_fe);
+    return secp256k1_gej_eq_x_var(&xr, &pr);
+}
+
+int secp256k1_ecdsa_verify_prepared(const secp256k1_context* ctx, const secp256k1_ecdsa_signature *sig, const unsigned char *msg32, const secp256k1_prepared_pubkey *prepared) {
+    secp256k1_scalar r, s;
+    secp256k1_scalar m;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(secp256k1_ecmult_context_is_built(&ctx->ecmult_ctx));
+    ARG_CHECK(msg32 != NULL);
+    ARG_CHECK(sig != NULL);
+    ARG_CHECK(prepared != NULL);
+
+    secp256k1_scalar_set_b32(&m, msg32, NULL);
+    secp256k1_ecdsa_signature_load(ctx, &r, &s, sig);
+    return (!secp256k1_scalar_is_high(&s) &&
+            secp256k1_ecdsa_sig_verify_prepared(&ctx->ecmult_ctx, &r, &s, prepared, &m));
+}
+
+#endif /* SECP256K1_MODULE_PREPARED_MAIN_H */
@@ -0,0 +1,109 @@
+/**********************************************************************
+ * Copyright (c) 2018 The rust-secp256k1-sgx developers               *
+ * Distributed under the MIT software license, see the accompanying   *
+ * file COPYING or http://www.opensource.org/licenses/mit-license.php.*
+ **********************************************************************/
+
+#ifndef SECP256K1_MODULE_SCALAR_OPS_MAIN_H
+#define SECP256K1_MODULE_SCALAR_OPS_MAIN_H
+
+#include "include/secp256k1_scalar_ops.h"
+
+int secp256k1_ec_scalar_verify(const secp256k1_context* ctx, const unsigned char *scalar) {
+    secp256k1_scalar s;
+    int overflow;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(scalar != NULL);
+
+    secp256k1_scalar_set_b32(&s, scalar, &overflow);
+    secp256k1_scalar_clear(&s);
+    return !overflow;
+}
+
+int secp256k1_ec_scalar_reduce512(const secp256k1_context* ctx, unsigned char *result, const unsigned char *input64) {
+    /* 2^256 mod n */
+    static const secp256k1_scalar two_256 = SECP256K1_SCALAR_CONST(
+        0x00000000UL, 0x00000000UL, 0x00000000UL, 0x00000001UL,
+        0x45512319UL, 0x50B75FC4UL, 0x402DA173UL, 0x2FC9BEBFUL
+    );
+    secp256k1_scalar hi, lo;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(result != NULL);
+    ARG_CHECK(input64 != NULL);
+
+    /* Both halves are reduced on load, which is fine since hi * 2^256 only
+     * matters modulo n. */
+    secp256k1_scalar_set_b32(&hi, input64, NULL);
+    secp256k1_scalar_set_b32(&lo, input64 + 32, NULL);
+    secp256k1_scalar_mul(&hi, &hi, &two_256);
+    secp256k1_scalar_add(&hi, &hi, &lo);
+    secp256k1_scalar_get_b32(result, &hi);
+    secp256k1_scalar_clear(&hi);
+    secp256k1_scalar_clear(&lo);
+    return 1;
+}
+
+int secp256k1_ec_scalar_add(const secp256k1_context* ctx, unsigned char *result, const unsigned char *a, const unsigned char *b) {
+    secp256k1_scalar sa, sb;
+    int overflow_a, overflow_b;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(result != NULL);
+    ARG_CHECK(a != NULL);
+    ARG_CHECK(b != NULL);
+
+    secp256k1_scalar_set_b32(&sa, a, &overflow_a);
+    secp256k1_scalar_set_b32(&sb, b, &overflow_b);
+    secp256k1_scalar_add(&sa, &sa, &sb);
+    secp256k1_scalar_get_b32(result, &sa);
+    secp256k1_scalar_clear(&sa);
+    secp256k1_scalar_clear(&sb);
+    return !overflow_a && !overflow_b;
+}
+
+int secp256k1_ec_scalar_mul(const secp256k1_context* ctx, unsigned char *result, const unsigned char *a, const unsigned char *b) {
+    secp256k1_scalar sa, sb;
+    int overflow_a, overflow_b;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(result != NULL);
+    ARG_CHECK(a != NULL);
+    ARG_CHECK(b != NULL);
+
+    secp256k1_scalar_set_b32(&sa, a, &overflow_a);
+    secp256k1_scalar_set_b32(&sb, b, &overflow_b);
+    secp256k1_scalar_mul(&sa, &sa, &sb);
+    secp256k1_scalar_get_b32(result, &sa);
+    secp256k1_scalar_clear(&sa);
+    secp256k1_scalar_clear(&sb);
+    return !overflow_a && !overflow_b;
+}
+
+int secp256k1_ec_scalar_negate(const secp256k1_context* ctx, unsigned char *result, const unsigned char *a) {
+    secp256k1_scalar sa;
+    int overflow;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(result != NULL);
+    ARG_CHECK(a != NULL);
+
+    secp256k1_scalar_set_b32(&sa, a, &overflow);
+    secp256k1_scalar_negate(&sa, &sa);
+    secp256k1_scalar_get_b32(result, &sa);
+    secp256k1_scalar_clear(&sa);
+    return !overflow;
+}
+
+int secp256k1_ec_scalar_inverse(const secp256k1_context* ctx, unsigned char *result, const unsigned char *a) {
+    secp256k1_scalar sa;
+    int overflow, ret;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(result != NULL);
+    ARG_CHECK(a != NULL);
+
+    secp256k1_scalar_set_b32(&sa, a, &overflow);
+    ret = !overflow && !secp256k1_scalar_is_zero(&sa);
+    secp256k1_scalar_inverse(&sa, &sa);
+    secp256k1_scalar_get_b32(result, &sa);
+    secp256k1_scalar_clear(&sa);
+    return ret;
+}
+
+#endif /* SECP256K1_MODULE_SCALAR_OPS_MAIN_H */
@@ -0,0 +1,70 @@
+/**********************************************************************
+ * Copyright (c) 2018 The rust-secp256k1-sgx developers               *
+ * Distributed under the MIT software license, see the accompanying   *
+ * file COPYING or http://www.opensource.org/licenses/mit-license.php.*
+ **********************************************************************/
+
+#ifndef SECP256K1_MODULE_TWEAK_BATCH_MAIN_H
+#define SECP256K1_MODULE_TWEAK_BATCH_MAIN_H
+
+#include "include/secp256k1_tweak_batch.h"
+
+/* Keys normalized per field inversion; bounds the stack use to about 15 kB */
+#define TWEAK_BATCH_CHUNK 64
+
+int secp256k1_ec_pubkey_tweak_add_batch(const secp256k1_context* ctx, secp256k1_pubkey *pubkeys, size_t n, const unsigned char *tweak) {
+    secp256k1_gej sums[TWEAK_BATCH_CHUNK];
+    secp256k1_fe zs[TWEAK_BATCH_CHUNK];
+    secp256k1_fe zis[TWEAK_BATCH_CHUNK];
+    secp256k1_scalar term, zero;
+    secp256k1_gej tj;
+    secp256k1_ge t, p;
+    size_t i, j, len;
+    int overflow;
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(secp256k1_ecmult_context_is_built(&ctx->ecmult_ctx));
+    ARG_CHECK(n == 0 || pubkeys != NULL);
+    ARG_CHECK(tweak != NULL);
+
+    secp256k1_scalar_set_b32(&term, tweak, &overflow);
+    if (overflow) {
+        return 0;
+    }
+    /* tweak*G, shared by the whole batch */
+    secp256k1_scalar_set_int(&zero, 0);
+    secp256k1_gej_set_infinity(&tj);
+    secp256k1_ecmult(&ctx->ecmult_ctx, &tj, &tj, &zero, &term);
+    if (secp256k1_gej_is_infinity(&tj)) {
+        /* A zero tweak leaves the keys unchanged, but they must be valid */
+        for (i = 0; i < n; i++) {
+            if (!secp256k1_pubkey_load(ctx, &p, &pubkeys[i])) {
+                return 0;
+            }
+        }
+        return 1;
+    }
+    secp256k1_ge_set_gej(&t, &tj);
+
+    for (i = 0; i < n; i += len) {
+        len = n - i < TWEAK_BATCH_CHUNK ? n - i : TWEAK_BATCH_CHUNK;
+        for (j = 0; j < len; j++) {
+            if (!secp256k1_pubkey_load(ctx, &p, &pubkeys[i + j])) {
+                return 0;
+            }
+            secp256k1_gej_set_ge(&sums[j], &p);
+            secp256k1_gej_add_ge_var(&sums[j], &sums[j], &t, NULL);
+            if (secp256k1_gej_is_infinity(&sums[j])) {
+                return 0;
+            }
+            zs[j] = sums[j].z;
+        }
+        secp256k1_fe_inv_all_var(zis, zs, len);
+        for (j = 0; j < len; j++) {
+            secp256k1_ge_set_gej_zinv(&p, &sums[j], &zis[j]);
+            secp256k1_pubkey_save(&pubkeys[i + j], &p);
+        }
+    }
+    return 1;
+}
+
+#endif /* SECP256K1_MODULE_TWEAK_BATCH_MAIN_H */
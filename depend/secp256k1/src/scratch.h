@@ -18,10 +18,16 @@ typedef struct secp256k1_scratch_space_struct {
     size_t frame;
     size_t max_size;
     const secp256k1_callback* error_callback;
+    /* If not NULL, frames are carved from this buffer instead of malloc'd */
+    unsigned char *prealloc;
 } secp256k1_scratch;
 
 static secp256k1_scratch* secp256k1_scratch_create(const secp256k1_callback* error_callback, size_t max_size);
 
+/** Creates a scratch space inside `mem`, which holds both the scratch
+ *  structure and the frames. Returns NULL if `size` is too small. */
+static secp256k1_scratch* secp256k1_scratch_create_preallocated(const secp256k1_callback* error_callback, void *mem, size_t size);
+
 static void secp256k1_scratch_destroy(secp256k1_scratch* scratch);
 
 /** Attempts to allocate a new stack frame with `n` available bytes. Returns 1 on success, 0 on failure */
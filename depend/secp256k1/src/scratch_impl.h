@@ -25,10 +25,28 @@ static secp256k1_scratch* secp256k1_scratch_create(const secp256k1_callback* err
     return ret;
 }
 
+static secp256k1_scratch* secp256k1_scratch_create_preallocated(const secp256k1_callback* error_callback, void *mem, size_t size) {
+    secp256k1_scratch* ret;
+    size_t header = ((sizeof(*ret) + ALIGNMENT - 1) / ALIGNMENT) * ALIGNMENT;
+    size_t skip = (ALIGNMENT - (size_t)mem % ALIGNMENT) % ALIGNMENT;
+
+    if (size < skip + header) {
+        return NULL;
+    }
+    ret = (secp256k1_scratch*)((unsigned char *)mem + skip);
+    memset(ret, 0, sizeof(*ret));
+    ret->max_size = size - skip - header;
+    ret->error_callback = error_callback;
+    ret->prealloc = (unsigned char *)ret + header;
+    return ret;
+}
+
 static void secp256k1_scratch_destroy(secp256k1_scratch* scratch) {
     if (scratch != NULL) {
         VERIFY_CHECK(scratch->frame == 0);
-        free(scratch);
+        if (scratch->prealloc == NULL) {
+            free(scratch);
+        }
     }
 }
 
@@ -49,7 +67,21 @@ static int secp256k1_scratch_allocate_frame(secp256k1_scratch* scratch, size_t n
 
     if (n <= secp256k1_scratch_max_allocation(scratch, objects)) {
         n += objects * ALIGNMENT;
-        scratch->data[scratch->frame] = checked_malloc(scratch->error_callback, n);
+        if (scratch->prealloc != NULL) {
+            /* Frames are stacked, so the next one starts where the previous
+             * ones end; all frame sizes are multiples of ALIGNMENT */
+            size_t i, used = 0;
+            for (i = 0; i < scratch->frame; i++) {
+                used += scratch->frame_size[i];
+            }
+            n = ((n + ALIGNMENT - 1) / ALIGNMENT) * ALIGNMENT;
+            if (n > scratch->max_size - used) {
+                return 0;
+            }
+            scratch->data[scratch->frame] = scratch->prealloc + used;
+        } else {
+            scratch->data[scratch->frame] = checked_malloc(scratch->error_callback, n);
+        }
         if (scratch->data[scratch->frame] == NULL) {
             return 0;
         }
@@ -65,7 +97,9 @@ static int secp256k1_scratch_allocate_frame(secp256k1_scratch* scratch, size_t n
 static void secp256k1_scratch_deallocate_frame(secp256k1_scratch* scratch) {
     VERIFY_CHECK(scratch->frame > 0);
     scratch->frame -= 1;
-    free(scratch->data[scratch->frame]);
+    if (scratch->prealloc == NULL) {
+        free(scratch->data[scratch->frame]);
+    }
 }
 
 static void *secp256k1_scratch_alloc(secp256k1_scratch* scratch, size_t size) {
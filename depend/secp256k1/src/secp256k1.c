@@ -54,12 +54,24 @@ struct secp256k1_context_struct {
     secp256k1_ecmult_gen_context ecmult_gen_ctx;
     secp256k1_callback illegal_callback;
     secp256k1_callback error_callback;
+    /* Whether the context and its tables live in memory owned by the caller */
+    int preallocated;
 };
 
+static const secp256k1_context secp256k1_context_no_precomp_ = {
+    { 0 },
+    { 0 },
+    { default_illegal_callback_fn, 0 },
+    { default_error_callback_fn, 0 },
+    0
+};
+const secp256k1_context *secp256k1_context_no_precomp = &secp256k1_context_no_precomp_;
+
 secp256k1_context* secp256k1_context_create(unsigned int flags) {
     secp256k1_context* ret = (secp256k1_context*)checked_malloc(&default_error_callback, sizeof(secp256k1_context));
     ret->illegal_callback = default_illegal_callback;
     ret->error_callback = default_error_callback;
+    ret->preallocated = 0;
 
     if (EXPECT((flags & SECP256K1_FLAGS_TYPE_MASK) != SECP256K1_FLAGS_TYPE_CONTEXT, 0)) {
             secp256k1_callback_call(&ret->illegal_callback,
@@ -85,6 +97,7 @@ secp256k1_context* secp256k1_context_clone(const secp256k1_context* ctx) {
     secp256k1_context* ret = (secp256k1_context*)checked_malloc(&ctx->error_callback, sizeof(secp256k1_context));
     ret->illegal_callback = ctx->illegal_callback;
     ret->error_callback = ctx->error_callback;
+    ret->preallocated = 0;
     secp256k1_ecmult_context_clone(&ret->ecmult_ctx, &ctx->ecmult_ctx, &ctx->error_callback);
     secp256k1_ecmult_gen_context_clone(&ret->ecmult_gen_ctx, &ctx->ecmult_gen_ctx, &ctx->error_callback);
     return ret;
@@ -92,6 +105,12 @@ secp256k1_context* secp256k1_context_clone(const secp256k1_context* ctx) {
 
 void secp256k1_context_destroy(secp256k1_context* ctx) {
     if (ctx != NULL) {
+        if (ctx->preallocated) {
+            /* Nothing to free, but the blinding must not outlive the context */
+            secp256k1_scalar_clear(&ctx->ecmult_gen_ctx.blind);
+            secp256k1_gej_clear(&ctx->ecmult_gen_ctx.initial);
+            return;
+        }
         secp256k1_ecmult_context_clear(&ctx->ecmult_ctx);
         secp256k1_ecmult_gen_context_clear(&ctx->ecmult_gen_ctx);
 
@@ -120,6 +139,16 @@ secp256k1_scratch_space* secp256k1_scratch_space_create(const secp256k1_context*
     return secp256k1_scratch_create(&ctx->error_callback, max_size);
 }
 
+secp256k1_scratch_space* secp256k1_scratch_space_create_preallocated(const secp256k1_context* ctx, void *mem, size_t size) {
+    VERIFY_CHECK(ctx != NULL);
+    ARG_CHECK(mem != NULL);
+    return secp256k1_scratch_create_preallocated(&ctx->error_callback, mem, size);
+}
+
+size_t secp256k1_scratch_space_max_size(const secp256k1_scratch_space* scratch) {
+    return scratch->max_size;
+}
+
 void secp256k1_scratch_space_destroy(secp256k1_scratch_space* scratch) {
     secp256k1_scratch_destroy(scratch);
 }
@@ -195,6 +224,28 @@ int secp256k1_ec_pubkey_serialize(const secp256k1_context* ctx, unsigned char *o
     return ret;
 }
 
+int secp256k1_ec_pubkey_cmp(const secp256k1_context* ctx, const secp256k1_pubkey* pubkey0, const secp256k1_pubkey* pubkey1) {
+    unsigned char out[2][33];
+    const secp256k1_pubkey* pk[2];
+    int i;
+
+    VERIFY_CHECK(ctx != NULL);
+    pk[0] = pubkey0; pk[1] = pubkey1;
+    for (i = 0; i < 2; i++) {
+        size_t out_size = sizeof(out[i]);
+        /* If the public key is NULL or invalid, ec_pubkey_serialize will call
+         * the illegal_callback and return 0. In that case we will serialize the
+         * key as all zeros which is less than any valid public key. This
+         * results in consistent comparisons even if NULL or invalid pubkeys are
+         * involved and prevents edge cases such as sorting algorithms that use
+         * this function and do not terminate as a result. */
+        if (!secp256k1_ec_pubkey_serialize(ctx, out[i], &out_size, pk[i], SECP256K1_EC_COMPRESSED)) {
+            memset(out[i], 0, sizeof(out[i]));
+        }
+    }
+    return memcmp(out[0], out[1], sizeof(out[0]));
+}
+
 static void secp256k1_ecdsa_signature_load(const secp256k1_context* ctx, secp256k1_scalar* r, secp256k1_scalar* s, const secp256k1_ecdsa_signature* sig) {
     (void)ctx;
     if (sizeof(secp256k1_scalar) == 32) {
@@ -595,3 +646,23 @@ int secp256k1_ec_pubkey_combine(const secp256k1_context* ctx, secp256k1_pubkey *
 #ifdef ENABLE_MODULE_RECOVERY
 # include "modules/recovery/main_impl.h"
 #endif
+
+#ifdef ENABLE_MODULE_SCALAR_OPS
+# include "modules/scalar_ops/main_impl.h"
+#endif
+
+#ifdef ENABLE_MODULE_CONTEXT_EXPORT
+# include "modules/context_export/main_impl.h"
+#endif
+
+#ifdef ENABLE_MODULE_PREPARED
+# include "modules/prepared/main_impl.h"
+#endif
+
+#ifdef ENABLE_MODULE_MSM
+# include "modules/msm/main_impl.h"
+#endif
+
+#ifdef ENABLE_MODULE_TWEAK_BATCH
+# include "modules/tweak_batch/main_impl.h"
+#endif
@@ -15,6 +15,16 @@
 #include <stdint.h>
 #include <stdio.h>
 
+#ifdef USE_CONTEXT_ALLOCATOR
+/* Provided by the Rust bindings, which forward to the installed allocator */
+void *rustsecp256k1_malloc(size_t size);
+void *rustsecp256k1_realloc(void *ptr, size_t size);
+void rustsecp256k1_free(void *ptr);
+#define malloc rustsecp256k1_malloc
+#define realloc rustsecp256k1_realloc
+#define free rustsecp256k1_free
+#endif
+
 typedef struct {
     void (*fn)(const char *text, void* data);
     const void* data;
@@ -0,0 +1,13 @@
+/* Minimal stdlib.h for wasm32-unknown-unknown. The functions are provided
+ * by the Rust crate (src/wasm.rs). */
+#ifndef SECP256K1_WASM_STDLIB_H
+#define SECP256K1_WASM_STDLIB_H
+
+#include <stddef.h>
+
+void *malloc(size_t size);
+void *realloc(void *ptr, size_t size);
+void free(void *ptr);
+void abort(void) __attribute__((noreturn));
+
+#endif
@@ -0,0 +1,23 @@
+/* Minimal string.h for wasm32-unknown-unknown. The mem* functions are
+ * provided by Rust's compiler_builtins. */
+#ifndef SECP256K1_WASM_STRING_H
+#define SECP256K1_WASM_STRING_H
+
+#include <stddef.h>
+
+void *memcpy(void *dest, const void *src, size_t n);
+void *memset(void *s, int c, size_t n);
+int memcmp(const void *s1, const void *s2, size_t n);
+
+/* Only used to name the source file in log messages, which are dropped */
+static inline char *strrchr(const char *s, int c) {
+    const char *ret = NULL;
+    do {
+        if (*s == (char)c) {
+            ret = s;
+        }
+    } while (*s++);
+    return (char *)ret;
+}
+
+#endif
@@ -0,0 +1,245 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Context allocator
+//! With the "context-allocator" feature, the vendored library is built to
+//! allocate through the `ContextAllocator` installed with `set_allocator`
+//! instead of the C library's `malloc` and `free`. That covers contexts and
+//! their tables, clones, imported contexts, scratch spaces and the
+//! temporary buffers of table building and batch operations, so an enclave
+//! can keep all of them in, say, a locked arena which is zeroed on free.
+//! Together with `ContextBuilder::preallocated`, which takes the context
+//! itself off the heap, this decides where every piece of intermediate
+//! state lives.
+//!
+//! Until an allocator is installed, and after `clear_allocator`, blocks come
+//! from the Rust global allocator through `HeapAllocator`, which zeroes them
+//! before freeing. Each block remembers the allocator which made it, so
+//! switching allocators while contexts are alive is safe: their tables are
+//! freed by the allocator they came from.
+//!
+//! The feature needs the vendored sources, so it cannot be combined with
+//! "external-secp".
+
+use std::alloc::{self, Layout};
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{cmp, ptr, slice};
+
+use types::{c_void, size_t};
+
+use cleanup::wipe;
+
+/// The alignment every block must have, that of `max_align_t` on the
+/// supported targets
+pub const ALIGNMENT: usize = 16;
+
+/// A source of memory for libsecp256k1. It is called from whichever thread
+/// is inside the C library, possibly several at once, and must not panic.
+///
+/// # Safety
+/// The C code relies on what `alloc` returns: a block of at least `size`
+/// bytes aligned to `ALIGNMENT`, which nothing else uses until it is passed
+/// to `free`.
+pub unsafe trait ContextAllocator: Sync {
+    /// Allocates `size` bytes aligned to `ALIGNMENT`, or returns null if
+    /// there is no memory left. `size` is never zero.
+    fn alloc(&self, size: usize) -> *mut u8;
+
+    /// Frees `ptr`, which this allocator returned from `alloc(size)`
+    ///
+    /// # Safety
+    /// `ptr` and `size` must come from a single earlier call to `alloc`,
+    /// and the block must not be used afterwards.
+    unsafe fn free(&self, ptr: *mut u8, size: usize);
+}
+
+/// Allocates from the Rust global allocator and zeroes blocks before
+/// freeing them. This is what the library uses when no other allocator is
+/// installed.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct HeapAllocator;
+
+unsafe impl ContextAllocator for HeapAllocator {
+    fn alloc(&self, size: usize) -> *mut u8 {
+        match Layout::from_size_align(size, ALIGNMENT) {
+            Ok(layout) if size > 0 => unsafe { alloc::alloc(layout) },
+            _ => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, size: usize) {
+        wipe(slice::from_raw_parts_mut(ptr, size));
+        alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, ALIGNMENT));
+    }
+}
+
+static HEAP: HeapAllocator = HeapAllocator;
+
+/// The installed allocator, guarded by a spin lock like `metrics`' sink
+struct Slot {
+    lock: AtomicBool,
+    allocator: UnsafeCell<Option<&'static ContextAllocator>>,
+}
+
+unsafe impl Sync for Slot {}
+
+static SLOT: Slot = Slot { lock: AtomicBool::new(false), allocator: UnsafeCell::new(None) };
+
+fn with_allocator<T, F: FnOnce(&mut Option<&'static ContextAllocator>) -> T>(f: F) -> T {
+    while SLOT.lock.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {}
+    let ret = f(unsafe { &mut *SLOT.allocator.get() });
+    SLOT.lock.store(false, Ordering::Release);
+    ret
+}
+
+/// Makes all subsequent allocations by libsecp256k1 come from
+/// `allocator`, replacing any allocator installed before
+pub fn set_allocator(allocator: &'static ContextAllocator) {
+    with_allocator(|slot| *slot = Some(allocator));
+}
+
+/// Goes back to allocating from `HeapAllocator`
+pub fn clear_allocator() {
+    with_allocator(|slot| *slot = None);
+}
+
+fn allocator() -> &'static ContextAllocator {
+    with_allocator(|slot| slot.unwrap_or(&HEAP))
+}
+
+/// Precedes every block handed to the C code, since `free` is told neither
+/// the size nor the allocator
+#[repr(C)]
+struct Header {
+    size: usize,
+    allocator: &'static ContextAllocator,
+}
+
+/// The space reserved for the header, which keeps the block behind it
+/// aligned
+const HEADER: usize = 2 * ALIGNMENT;
+
+/// The `malloc` the library is built to call
+///
+/// # Safety
+/// As for the C function.
+#[no_mangle]
+pub unsafe extern "C" fn rustsecp256k1_malloc(size: size_t) -> *mut c_void {
+    let allocator = allocator();
+    let size = match size.checked_add(HEADER) {
+        Some(size) => size,
+        None => return ptr::null_mut(),
+    };
+    let block = allocator.alloc(size);
+    if block.is_null() {
+        return ptr::null_mut();
+    }
+    ptr::write(block as *mut Header, Header { size, allocator });
+    block.add(HEADER) as *mut c_void
+}
+
+/// The `free` the library is built to call
+///
+/// # Safety
+/// As for the C function.
+#[no_mangle]
+pub unsafe extern "C" fn rustsecp256k1_free(p: *mut c_void) {
+    if p.is_null() {
+        return;
+    }
+    let block = (p as *mut u8).sub(HEADER);
+    let header = ptr::read(block as *const Header);
+    header.allocator.free(block, header.size);
+}
+
+/// The `realloc` the library is built to call
+///
+/// # Safety
+/// As for the C function.
+#[no_mangle]
+pub unsafe extern "C" fn rustsecp256k1_realloc(p: *mut c_void, size: size_t) -> *mut c_void {
+    if p.is_null() {
+        return rustsecp256k1_malloc(size);
+    }
+    let ret = rustsecp256k1_malloc(size);
+    if !ret.is_null() {
+        let old_size = (*((p as *const u8).sub(HEADER) as *const Header)).size - HEADER;
+        ptr::copy_nonoverlapping(p as *const u8, ret as *mut u8, cmp::min(old_size, size));
+        rustsecp256k1_free(p);
+    }
+    ret
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::cell::Cell;
+    use std::mem;
+
+    use super::{clear_allocator, set_allocator, ContextAllocator, HeapAllocator, Header, HEADER};
+    use {All, Message, Secp256k1, SecretKey};
+
+    thread_local! {
+        static LIVE: Cell<(usize, usize)> = Cell::new((0, 0));
+    }
+
+    /// Counts the blocks and bytes allocated on each thread and not yet
+    /// freed, so tests running in parallel don't disturb each other. Their
+    /// blocks can be freed on another thread, hence the wrapping.
+    struct Counting;
+
+    unsafe impl ContextAllocator for Counting {
+        fn alloc(&self, size: usize) -> *mut u8 {
+            LIVE.with(|live| live.set((live.get().0.wrapping_add(1), live.get().1.wrapping_add(size))));
+            HeapAllocator.alloc(size)
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, size: usize) {
+            LIVE.with(|live| live.set((live.get().0.wrapping_sub(1), live.get().1.wrapping_sub(size))));
+            HeapAllocator.free(ptr, size)
+        }
+    }
+
+    static COUNTING: Counting = Counting;
+
+    fn live() -> (usize, usize) {
+        LIVE.with(|live| live.get())
+    }
+
+    #[test]
+    fn allocations() {
+        assert!(mem::size_of::<Header>() <= HEADER);
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let before = Secp256k1::new();
+
+        set_allocator(&COUNTING);
+        let secp = Secp256k1::new();
+        let (blocks, bytes) = live();
+        assert!(blocks > 0);
+        assert!(bytes >= Secp256k1::<All>::creation_cost_hint().table_bytes());
+        let cloned = secp.clone();
+        assert!(live().0 > blocks);
+        drop(cloned);
+        assert_eq!(live(), (blocks, bytes));
+        assert_eq!(secp.sign(&msg, &sk), before.sign(&msg, &sk));
+
+        // Blocks go back to the allocator which made them
+        clear_allocator();
+        drop(before);
+        assert_eq!(live(), (blocks, bytes));
+        drop(secp);
+        assert_eq!(live(), (0, 0));
+    }
+}
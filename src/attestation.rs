@@ -0,0 +1,275 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Attestation
+//! Binding a public key to an enclave with SGX attestation. The enclave puts
+//! `report_data(&pk)` into the REPORT_DATA field of a report for the quoting
+//! enclave (`create_key_report` does this with the "attestation" feature),
+//! the quote goes to the relying party, and the relying party calls
+//! `verify_key_binding` to check the key and learn which enclave holds it.
+//!
+//! `verify_key_binding` does not check the quote's signature: that is the
+//! job of the attestation service (IAS for EPID quotes, DCAP's quote
+//! verification library otherwise), which also judges whether the TCB
+//! levels reported here are up to date. The verifier half only parses bytes
+//! and builds for any target.
+
+use key::PublicKey;
+use super::Error::{self, InvalidQuote, KeyBindingMismatch};
+
+#[cfg(feature = "attestation")]
+use sgx_tse::rsgx_create_report;
+#[cfg(feature = "attestation")]
+use sgx_types::{sgx_report_data_t, sgx_report_t, sgx_target_info_t};
+
+/// The size (in bytes) of the REPORT_DATA field
+pub const REPORT_DATA_SIZE: usize = 64;
+
+/// Fills the REPORT_DATA bytes after the key. It identifies (and versions)
+/// the encoding, so REPORT_DATA produced for other purposes can't be mistaken
+/// for a key binding.
+pub const KEY_BINDING_TAG: &'static [u8; 31] = b"rust-secp256k1-sgx key binding\x01";
+
+// Quote layout, shared by EPID (versions 1 and 2) and DCAP (version 3)
+// quotes: a 48-byte header, the 384-byte report body, then the length of
+// the signature and the signature itself
+const QUOTE_BODY_SIZE: usize = 432;
+const REPORT_BODY_OFFSET: usize = 48;
+// Offsets within the report body
+const CPU_SVN: usize = 0;
+const MISC_SELECT: usize = 16;
+const ATTRIBUTES: usize = 48;
+const MR_ENCLAVE: usize = 64;
+const MR_SIGNER: usize = 128;
+const ISV_PROD_ID: usize = 256;
+const ISV_SVN: usize = 258;
+const CONFIG_SVN: usize = 260;
+const REPORT_DATA: usize = 320;
+
+/// The DEBUG attribute flag
+const FLAG_DEBUG: u64 = 0x2;
+
+/// The TCB components of a quote. Whether they are current is for the
+/// attestation service to decide.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TcbLevel {
+    /// Security version of the CPU microcode and platform
+    pub cpu_svn: [u8; 16],
+    /// Security version of the quoting enclave
+    pub qe_svn: u16,
+    /// Security version of the provisioning certification enclave
+    pub pce_svn: u16,
+}
+
+/// The identity of the enclave which attested to a key
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EnclaveIdentity {
+    /// Quote format version
+    pub version: u16,
+    /// Measurement of the enclave
+    pub mr_enclave: [u8; 32],
+    /// Hash of the enclave's signing key
+    pub mr_signer: [u8; 32],
+    /// Product ID of the enclave
+    pub isv_prod_id: u16,
+    /// Security version of the enclave
+    pub isv_svn: u16,
+    /// Config security version of the enclave
+    pub config_svn: u16,
+    /// Attribute flags of the enclave
+    pub flags: u64,
+    /// XFRM attributes of the enclave
+    pub xfrm: u64,
+    /// MISCSELECT of the enclave
+    pub misc_select: u32,
+    /// TCB levels of the platform and the quoting enclave
+    pub tcb: TcbLevel,
+}
+
+impl EnclaveIdentity {
+    /// Whether the enclave runs in debug mode, where its memory (and so the
+    /// key) can be read by the host
+    #[inline]
+    pub fn is_debug(&self) -> bool {
+        self.flags & FLAG_DEBUG != 0
+    }
+}
+
+/// The REPORT_DATA which binds `pk`: its compressed serialization followed
+/// by `KEY_BINDING_TAG`
+pub fn report_data(pk: &PublicKey) -> [u8; REPORT_DATA_SIZE] {
+    let mut data = [0u8; REPORT_DATA_SIZE];
+    let serialized = pk.serialize();
+    data[..serialized.len()].copy_from_slice(&serialized);
+    data[serialized.len()..].copy_from_slice(KEY_BINDING_TAG);
+    data
+}
+
+/// Creates a report for `target_info` (normally the quoting enclave's) which
+/// binds `pk`. Fails with `EnclaveService` if EREPORT fails.
+#[cfg(feature = "attestation")]
+pub fn create_key_report(target_info: &sgx_target_info_t, pk: &PublicKey) -> Result<sgx_report_t, Error> {
+    let data = sgx_report_data_t { d: report_data(pk) };
+    rsgx_create_report(target_info, &data).map_err(|_| Error::EnclaveService)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    data[offset] as u16 | (data[offset + 1] as u16) << 8
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u16_at(data, offset) as u32 | (u16_at(data, offset + 2) as u32) << 16
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u32_at(data, offset) as u64 | (u32_at(data, offset + 4) as u64) << 32
+}
+
+fn copy_at(data: &[u8], offset: usize, out: &mut [u8]) {
+    let len = out.len();
+    out.copy_from_slice(&data[offset..offset + len]);
+}
+
+/// Checks that `quote` binds `expected` and returns the identity of the
+/// enclave which produced it. `quote` is either a whole quote, signature
+/// included, or just its 432-byte body (as IAS reports it). Fails with
+/// `InvalidQuote` if the quote is malformed or of an unknown version, and
+/// with `KeyBindingMismatch` if its REPORT_DATA is not
+/// `report_data(expected)`.
+pub fn verify_key_binding(quote: &[u8], expected: &PublicKey) -> Result<EnclaveIdentity, Error> {
+    // Either the body alone, or the body, the signature length and exactly
+    // that much signature
+    if quote.len() != QUOTE_BODY_SIZE &&
+       (quote.len() < QUOTE_BODY_SIZE + 4 ||
+        u32_at(quote, QUOTE_BODY_SIZE) as usize != quote.len() - QUOTE_BODY_SIZE - 4) {
+        return Err(InvalidQuote);
+    }
+    let version = u16_at(quote, 0);
+    if version == 0 || version > 3 {
+        return Err(InvalidQuote);
+    }
+
+    let body = &quote[REPORT_BODY_OFFSET..QUOTE_BODY_SIZE];
+    if body[REPORT_DATA..] != report_data(expected)[..] {
+        return Err(KeyBindingMismatch);
+    }
+
+    let mut identity = EnclaveIdentity {
+        version,
+        mr_enclave: [0; 32],
+        mr_signer: [0; 32],
+        isv_prod_id: u16_at(body, ISV_PROD_ID),
+        isv_svn: u16_at(body, ISV_SVN),
+        config_svn: u16_at(body, CONFIG_SVN),
+        flags: u64_at(body, ATTRIBUTES),
+        xfrm: u64_at(body, ATTRIBUTES + 8),
+        misc_select: u32_at(body, MISC_SELECT),
+        tcb: TcbLevel {
+            cpu_svn: [0; 16],
+            qe_svn: u16_at(quote, 8),
+            pce_svn: u16_at(quote, 10),
+        },
+    };
+    copy_at(body, MR_ENCLAVE, &mut identity.mr_enclave);
+    copy_at(body, MR_SIGNER, &mut identity.mr_signer);
+    copy_at(body, CPU_SVN, &mut identity.tcb.cpu_svn);
+    Ok(identity)
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use rng::thread_rng;
+    use super::{report_data, verify_key_binding, EnclaveIdentity, TcbLevel};
+    use super::{ATTRIBUTES, CONFIG_SVN, CPU_SVN, ISV_PROD_ID, ISV_SVN, MISC_SELECT, MR_ENCLAVE, MR_SIGNER,
+                QUOTE_BODY_SIZE, REPORT_BODY_OFFSET, REPORT_DATA};
+    use super::super::{PublicKey, Secp256k1};
+    use super::super::Error::{InvalidQuote, KeyBindingMismatch};
+
+    // A version 2 quote with recognizable fields and a 16-byte "signature"
+    fn quote(pk: &PublicKey) -> Vec<u8> {
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE + 4 + 16];
+        quote[0] = 2;
+        quote[8] = 7;
+        quote[10] = 9;
+        {
+            let body = &mut quote[REPORT_BODY_OFFSET..QUOTE_BODY_SIZE];
+            body[CPU_SVN..CPU_SVN + 16].copy_from_slice(&[0x11; 16]);
+            body[MISC_SELECT] = 0x01;
+            body[ATTRIBUTES] = 0x05;
+            body[ATTRIBUTES + 8] = 0x03;
+            body[MR_ENCLAVE..MR_ENCLAVE + 32].copy_from_slice(&[0xe1; 32]);
+            body[MR_SIGNER..MR_SIGNER + 32].copy_from_slice(&[0x51; 32]);
+            body[ISV_PROD_ID] = 3;
+            body[ISV_SVN + 1] = 1;
+            body[CONFIG_SVN] = 4;
+            body[REPORT_DATA..].copy_from_slice(&report_data(pk));
+        }
+        quote[QUOTE_BODY_SIZE] = 16;
+        quote
+    }
+
+    #[test]
+    fn binding() {
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let quote = quote(&pk);
+
+        let identity = verify_key_binding(&quote, &pk).unwrap();
+        assert_eq!(identity, EnclaveIdentity {
+            version: 2,
+            mr_enclave: [0xe1; 32],
+            mr_signer: [0x51; 32],
+            isv_prod_id: 3,
+            isv_svn: 0x100,
+            config_svn: 4,
+            flags: 0x05,
+            xfrm: 0x03,
+            misc_select: 1,
+            tcb: TcbLevel { cpu_svn: [0x11; 16], qe_svn: 7, pce_svn: 9 },
+        });
+        assert!(!identity.is_debug());
+        let mut debug = quote.clone();
+        debug[REPORT_BODY_OFFSET + ATTRIBUTES] |= 0x02;
+        assert!(verify_key_binding(&debug, &pk).unwrap().is_debug());
+        // The body on its own works too
+        assert_eq!(verify_key_binding(&quote[..QUOTE_BODY_SIZE], &pk), Ok(identity));
+
+        let (_, other) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(verify_key_binding(&quote, &other), Err(KeyBindingMismatch));
+        // The report data must carry the tag
+        let mut untagged = quote.clone();
+        untagged[REPORT_BODY_OFFSET + REPORT_DATA + 63] ^= 1;
+        assert_eq!(verify_key_binding(&untagged, &pk), Err(KeyBindingMismatch));
+    }
+
+    #[test]
+    fn malformed() {
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let quote = quote(&pk);
+
+        assert_eq!(verify_key_binding(&[], &pk), Err(InvalidQuote));
+        assert_eq!(verify_key_binding(&quote[..QUOTE_BODY_SIZE - 1], &pk), Err(InvalidQuote));
+        assert_eq!(verify_key_binding(&quote[..QUOTE_BODY_SIZE + 2], &pk), Err(InvalidQuote));
+        assert_eq!(verify_key_binding(&quote[..quote.len() - 1], &pk), Err(InvalidQuote));
+        let mut longer = quote.clone();
+        longer.push(0);
+        assert_eq!(verify_key_binding(&longer, &pk), Err(InvalidQuote));
+        for &version in [0u8, 4].iter() {
+            let mut bad = quote.clone();
+            bad[0] = version;
+            assert_eq!(verify_key_binding(&bad, &pk), Err(InvalidQuote));
+        }
+    }
+}
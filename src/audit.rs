@@ -0,0 +1,472 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Usage reports and destruction certificates
+//! Signed statements of how the keys in a `Vault` have been used, for
+//! auditors of a signing service. `Vault::attested_usage_report` signs the
+//! statistics of every key (see `vault::KeyUsage`) with an enclave identity
+//! key held in the same vault. As with `certificate`, the auditor attests
+//! the identity key once (see `attestation::verify_key_binding`), and after
+//! that only checks report signatures.
+//!
+//! An auditor holding the log of requests can check a key's usage chain
+//! against it with `vault::usage_chain`, which shows whether the key
+//! signed anything the log doesn't account for. Reports are only as
+//! fresh as the vault's clock is trustworthy, so compare the report time
+//! with a trusted one, or ask for reports often enough that a replayed one
+//! stands out by its counts.
+//!
+//! `Vault::destroy` shreds a key and returns a `DestructionCertificate`,
+//! signed by the identity key, which states the key's final statistics for
+//! compliance records. The key is wiped from enclave memory, and a vault's
+//! `UsageCounter` is advanced to the `vault::DESTROYED` tombstone, so that
+//! a monotonic counter refuses the key from any older sealed copy of the
+//! vault. `FileBackedVault::destroy` also overwrites the vault file. Copies
+//! sealed outside the vault are beyond its reach; only the counter stops
+//! them.
+//!
+//! Reports and certificates are signed with ECDSA over the tagged hash of
+//! their encoded body. Requires compilation with the "hashes" feature.
+
+use std::vec::Vec;
+
+use constants;
+use hashes::{tagged_hash, KEY_DESTRUCTION_TAG, USAGE_REPORT_TAG};
+use key::{KeyId, PublicKey};
+#[cfg(feature = "protected-fs")]
+use vault::FileBackedVault;
+use vault::{KeyHandle, KeyUsage, Vault};
+use super::Error::{self, InvalidDestructionCertificate, InvalidUsageReport};
+use {Message, Secp256k1, Signature, Signing, Verification};
+
+/// Format version of the encoding
+const VERSION: u8 = 1;
+
+/// The size (in bytes) of the encoded report time and key count, after
+/// the version byte
+const HEADER_SIZE: usize = 1 + 8 + 4;
+
+/// The size (in bytes) of the encoded statistics of one key
+pub const KEY_USAGE_SIZE: usize = 4 + constants::PUBLIC_KEY_SIZE + 8 + 8 + 8 + 32;
+
+/// The size (in bytes) of an encoded certificate body
+const CERTIFICATE_BODY_SIZE: usize = 1 + 8 + KEY_USAGE_SIZE;
+
+/// The usage statistics of a vault's keys, signed by an identity key
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UsageReport {
+    time: Option<u64>,
+    keys: Vec<KeyUsage>,
+    signature: Signature,
+}
+
+// Body layout: version (1 byte), report time (8, zero for none), key count
+// (4), then for each key its handle (4), public key (33), use count (8),
+// limit (8, all ones for none), last use time (8, zero for none) and usage
+// chain (32). Integers are big-endian. A destruction certificate's body
+// is the version, the destruction time and the destroyed key's statistics
+// in the same encoding.
+fn write_be(out: &mut Vec<u8>, n: u64, len: usize) {
+    for i in (0..len).rev() {
+        out.push((n >> (8 * i)) as u8);
+    }
+}
+
+fn read_be(data: &[u8]) -> u64 {
+    data.iter().fold(0, |n, &byte| n << 8 | byte as u64)
+}
+
+fn encode_body(time: Option<u64>, keys: &[KeyUsage]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_SIZE + KEY_USAGE_SIZE * keys.len());
+    out.push(VERSION);
+    write_be(&mut out, time.unwrap_or(0), 8);
+    write_be(&mut out, keys.len() as u64, 4);
+    for key in keys {
+        encode_key_usage(&mut out, key);
+    }
+    out
+}
+
+fn encode_key_usage(out: &mut Vec<u8>, key: &KeyUsage) {
+    write_be(out, key.handle.to_u32() as u64, 4);
+    out.extend_from_slice(&key.public_key.serialize());
+    write_be(out, key.uses, 8);
+    write_be(out, key.limit.unwrap_or(!0), 8);
+    write_be(out, key.last_used.unwrap_or(0), 8);
+    out.extend_from_slice(&key.chain);
+}
+
+/// Parses the `KEY_USAGE_SIZE` bytes of `encode_key_usage`, or returns
+/// `None` for an invalid public key
+fn decode_key_usage(chunk: &[u8]) -> Option<KeyUsage> {
+    let limit = read_be(&chunk[45..53]);
+    let last_used = read_be(&chunk[53..61]);
+    let mut chain = [0u8; 32];
+    chain.copy_from_slice(&chunk[61..]);
+    Some(KeyUsage {
+        handle: KeyHandle::from_u32(read_be(&chunk[..4]) as u32),
+        public_key: PublicKey::from_slice(&chunk[4..37]).ok()?,
+        uses: read_be(&chunk[37..45]),
+        limit: if limit == !0 { None } else { Some(limit) },
+        last_used: if last_used == 0 { None } else { Some(last_used) },
+        chain,
+    })
+}
+
+fn signing_message(body: &[u8]) -> Message {
+    Message::from(tagged_hash(USAGE_REPORT_TAG, body))
+}
+
+fn encode_certificate_body(time: Option<u64>, key: &KeyUsage) -> Vec<u8> {
+    let mut out = Vec::with_capacity(CERTIFICATE_BODY_SIZE);
+    out.push(VERSION);
+    write_be(&mut out, time.unwrap_or(0), 8);
+    encode_key_usage(&mut out, key);
+    out
+}
+
+fn certificate_signing_message(body: &[u8]) -> Message {
+    Message::from(tagged_hash(KEY_DESTRUCTION_TAG, body))
+}
+
+impl UsageReport {
+    /// The time from the vault's clock when the report was made (seconds
+    /// since the Unix epoch), if it told one
+    #[inline]
+    pub fn time(&self) -> Option<u64> {
+        self.time
+    }
+
+    /// The statistics of each key, oldest key first. Only trust them after
+    /// `verify`.
+    #[inline]
+    pub fn keys(&self) -> &[KeyUsage] {
+        &self.keys
+    }
+
+    /// The statistics of the key with identifier `id`, if the report
+    /// covers it. Only trust them after `verify`.
+    pub fn key(&self, id: &KeyId) -> Option<&KeyUsage> {
+        self.keys.iter().find(|usage| usage.key_id() == *id)
+    }
+
+    /// The identity key's signature
+    #[inline]
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// The message the identity key signs
+    pub fn signing_message(&self) -> Message {
+        signing_message(&encode_body(self.time, &self.keys))
+    }
+
+    /// Checks that `identity` signed the report and returns the statistics.
+    /// Fails with `IncorrectSignature` for a wrong signature.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, identity: &PublicKey)
+                                   -> Result<&[KeyUsage], Error> {
+        secp.verify(&self.signing_message(), &self.signature, identity)?;
+        Ok(&self.keys)
+    }
+
+    /// Encodes the report: the body followed by the compact signature
+    pub fn serialize<C>(&self, secp: &Secp256k1<C>) -> Vec<u8> {
+        let mut out = encode_body(self.time, &self.keys);
+        out.extend_from_slice(&self.signature.serialize_compact(secp));
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidUsageReport`
+    /// for any other data; the signature is only checked by `verify`.
+    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<UsageReport, Error> {
+        if data.len() < HEADER_SIZE + constants::COMPACT_SIGNATURE_SIZE || data[0] != VERSION {
+            return Err(InvalidUsageReport);
+        }
+        let count = read_be(&data[9..13]) as usize;
+        let body_len = count.checked_mul(KEY_USAGE_SIZE).and_then(|n| n.checked_add(HEADER_SIZE));
+        if body_len.and_then(|n| n.checked_add(constants::COMPACT_SIGNATURE_SIZE)) != Some(data.len()) {
+            return Err(InvalidUsageReport);
+        }
+        let body_len = data.len() - constants::COMPACT_SIGNATURE_SIZE;
+
+        let mut keys = Vec::with_capacity(count);
+        for chunk in data[HEADER_SIZE..body_len].chunks(KEY_USAGE_SIZE) {
+            keys.push(decode_key_usage(chunk).ok_or(InvalidUsageReport)?);
+        }
+        let time = read_be(&data[1..9]);
+        Ok(UsageReport {
+            time: if time == 0 { None } else { Some(time) },
+            keys,
+            signature: Signature::from_compact(secp, &data[body_len..]).map_err(|_| InvalidUsageReport)?,
+        })
+    }
+}
+
+/// The final usage statistics of a destroyed vault key, signed by an
+/// identity key
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DestructionCertificate {
+    time: Option<u64>,
+    key: KeyUsage,
+    signature: Signature,
+}
+
+impl DestructionCertificate {
+    /// The time from the vault's clock when the key was destroyed (seconds
+    /// since the Unix epoch), if it told one
+    #[inline]
+    pub fn time(&self) -> Option<u64> {
+        self.time
+    }
+
+    /// The statistics of the destroyed key as of its destruction. Only
+    /// trust them after `verify`.
+    #[inline]
+    pub fn key(&self) -> &KeyUsage {
+        &self.key
+    }
+
+    /// The identity key's signature
+    #[inline]
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// The message the identity key signs
+    pub fn signing_message(&self) -> Message {
+        certificate_signing_message(&encode_certificate_body(self.time, &self.key))
+    }
+
+    /// Checks that `identity` signed the certificate and returns the
+    /// statistics of the destroyed key. Fails with `IncorrectSignature` for
+    /// a wrong signature.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, identity: &PublicKey)
+                                   -> Result<&KeyUsage, Error> {
+        secp.verify(&self.signing_message(), &self.signature, identity)?;
+        Ok(&self.key)
+    }
+
+    /// Encodes the certificate: the body followed by the compact signature
+    pub fn serialize<C>(&self, secp: &Secp256k1<C>) -> Vec<u8> {
+        let mut out = encode_certificate_body(self.time, &self.key);
+        out.extend_from_slice(&self.signature.serialize_compact(secp));
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with
+    /// `InvalidDestructionCertificate` for any other data; the signature is
+    /// only checked by `verify`.
+    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<DestructionCertificate, Error> {
+        if data.len() != CERTIFICATE_BODY_SIZE + constants::COMPACT_SIGNATURE_SIZE || data[0] != VERSION {
+            return Err(InvalidDestructionCertificate);
+        }
+        let time = read_be(&data[1..9]);
+        Ok(DestructionCertificate {
+            time: if time == 0 { None } else { Some(time) },
+            key: decode_key_usage(&data[9..CERTIFICATE_BODY_SIZE]).ok_or(InvalidDestructionCertificate)?,
+            signature: Signature::from_compact(secp, &data[CERTIFICATE_BODY_SIZE..])
+                .map_err(|_| InvalidDestructionCertificate)?,
+        })
+    }
+}
+
+impl Vault {
+    /// Reports the usage statistics of every key, and the time from the
+    /// vault's clock, signed with the identity key for `identity`. The
+    /// statistics are taken before signing, which counts as a use of the
+    /// identity key. Fails like `Vault::sign`.
+    pub fn attested_usage_report<C: Signing>(&mut self, secp: &Secp256k1<C>, identity: KeyHandle)
+                                             -> Result<UsageReport, Error> {
+        let time = self.now();
+        let keys = self.key_usages();
+        let signature = self.sign(secp, identity, &signing_message(&encode_body(time, &keys)))?;
+        Ok(UsageReport { time, keys, signature })
+    }
+
+    /// Destroys the key for `handle`: advances the vault's `UsageCounter`,
+    /// if any, to the `vault::DESTROYED` tombstone, then removes and wipes
+    /// the key with its presignatures and approval policy. Returns a
+    /// certificate of its final statistics, and the time from the vault's
+    /// clock, signed with the identity key for `identity`.
+    ///
+    /// The statistics are taken before signing, which counts as a use of
+    /// the identity key. Fails like `Vault::sign` if the identity key can't
+    /// sign, and with the counter's error if it can't be advanced, keeping
+    /// the key in both cases.
+    pub fn destroy<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, identity: KeyHandle)
+                               -> Result<DestructionCertificate, Error> {
+        let time = self.now();
+        let key = self.key_usage(handle)?;
+        let msg = certificate_signing_message(&encode_certificate_body(time, &key));
+        let signature = self.sign(secp, identity, &msg)?;
+        self.shred(handle)?;
+        Ok(DestructionCertificate { time, key, signature })
+    }
+}
+
+#[cfg(feature = "protected-fs")]
+impl FileBackedVault {
+    /// Destroys the key for `handle` like `Vault::destroy`, then saves the
+    /// vault so that the file no longer holds it. Fails like `save` (and
+    /// gives no certificate) if the vault can't be saved; the key is gone
+    /// from memory and the counter regardless, and leaves the file with the
+    /// next successful `save`.
+    pub fn destroy<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, identity: KeyHandle)
+                               -> Result<DestructionCertificate, Error> {
+        let certificate = self.vault_mut().destroy(secp, handle, identity)?;
+        self.save()?;
+        Ok(certificate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use key::KeyId;
+    use rng::thread_rng;
+    use super::{DestructionCertificate, UsageReport, CERTIFICATE_BODY_SIZE, HEADER_SIZE, KEY_USAGE_SIZE};
+    use super::super::{Error, Message, Secp256k1};
+    use super::super::vault::{usage_chain, Clock, KeyHandle, UsageCounter, Vault, DESTROYED};
+    use super::super::Error::{IncorrectSignature, InvalidDestructionCertificate, InvalidUsageReport, KeyExhausted,
+                              Storage, UnknownKeyHandle};
+
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now(&mut self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    // Records the counts it is advanced to, and fails while `fail` is set
+    #[derive(Clone, Default)]
+    struct LogCounter {
+        log: Arc<Mutex<Vec<(KeyHandle, u64)>>>,
+        fail: Arc<Mutex<bool>>,
+    }
+
+    impl UsageCounter for LogCounter {
+        fn advance(&mut self, handle: KeyHandle, uses: u64) -> Result<(), Error> {
+            if *self.fail.lock().unwrap() {
+                return Err(Storage);
+            }
+            self.log.lock().unwrap().push((handle, uses));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn report() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let identity = vault.generate(&s, &mut thread_rng());
+        let key = vault.generate(&s, &mut thread_rng());
+        let identity_pk = vault.public_key(identity).unwrap();
+        vault.set_clock(FixedClock(1_600_000_000));
+        vault.set_limit(key, Some(10)).unwrap();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        vault.sign(&s, key, &msg).unwrap();
+
+        let report = vault.attested_usage_report(&s, identity).unwrap();
+        assert_eq!(report.time(), Some(1_600_000_000));
+        let keys = report.verify(&s, &identity_pk).unwrap();
+        assert_eq!(keys.len(), 2);
+        // Taken before the report's own signature
+        assert_eq!(keys[0].uses, 0);
+        assert_eq!(vault.uses(identity), Ok(1));
+        assert_eq!(keys[1], vault.key_usage(key).unwrap());
+        assert_eq!((keys[1].uses, keys[1].limit, keys[1].last_used), (1, Some(10), Some(1_600_000_000)));
+        assert_eq!(keys[1].chain, usage_chain(&[0; 32], &msg[..]));
+        assert_eq!(report.key(&vault.key_id(key).unwrap()), Some(&keys[1]));
+        assert_eq!(report.key(&identity_pk.fingerprint()), Some(&keys[0]));
+        assert_eq!(report.key(&KeyId::from_bytes([0; 20])), None);
+        // Signed by the identity key only
+        assert_eq!(report.verify(&s, &vault.public_key(key).unwrap()), Err(IncorrectSignature));
+
+        let data = report.serialize(&s);
+        assert_eq!(data.len(), HEADER_SIZE + 2 * KEY_USAGE_SIZE + 64);
+        let parsed = UsageReport::from_slice(&s, &data).unwrap();
+        assert_eq!(parsed, report);
+        assert!(parsed.verify(&s, &identity_pk).is_ok());
+        // Any change to the body breaks the signature
+        let mut tampered = data.clone();
+        tampered[HEADER_SIZE + KEY_USAGE_SIZE + 44] ^= 1;
+        let tampered = UsageReport::from_slice(&s, &tampered).unwrap();
+        assert_eq!(tampered.keys()[1].uses, 0);
+        assert_eq!(tampered.verify(&s, &identity_pk), Err(IncorrectSignature));
+
+        assert_eq!(UsageReport::from_slice(&s, &data[..data.len() - 1]), Err(InvalidUsageReport));
+        let mut bad_version = data.clone();
+        bad_version[0] = 2;
+        assert_eq!(UsageReport::from_slice(&s, &bad_version), Err(InvalidUsageReport));
+        let mut bad_count = data.clone();
+        bad_count[12] = 3;
+        assert_eq!(UsageReport::from_slice(&s, &bad_count), Err(InvalidUsageReport));
+
+        // An exhausted identity key makes no reports
+        vault.set_limit(identity, Some(1)).unwrap();
+        assert_eq!(vault.attested_usage_report(&s, identity), Err(KeyExhausted));
+    }
+    #[test]
+    fn destruction() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let identity = vault.generate(&s, &mut thread_rng());
+        let key = vault.generate(&s, &mut thread_rng());
+        let identity_pk = vault.public_key(identity).unwrap();
+        let key_pk = vault.public_key(key).unwrap();
+        let counter = LogCounter::default();
+        vault.set_counter(counter.clone());
+        vault.set_clock(FixedClock(1_600_000_000));
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        vault.sign(&s, key, &msg).unwrap();
+        let usage = vault.key_usage(key).unwrap();
+
+        // Nothing is destroyed while the counter can't record the tombstone
+        *counter.fail.lock().unwrap() = true;
+        assert_eq!(vault.destroy(&s, key, identity), Err(Storage));
+        assert_eq!(vault.public_key(key), Ok(key_pk));
+        *counter.fail.lock().unwrap() = false;
+
+        let certificate = vault.destroy(&s, key, identity).unwrap();
+        assert_eq!(vault.public_key(key), Err(UnknownKeyHandle));
+        assert_eq!(vault.sign(&s, key, &msg), Err(UnknownKeyHandle));
+        assert_eq!(vault.destroy(&s, key, identity), Err(UnknownKeyHandle));
+        assert_eq!(vault.handles(), vec![identity]);
+        assert_eq!(counter.log.lock().unwrap().last(), Some(&(key, DESTROYED)));
+
+        assert_eq!(certificate.time(), Some(1_600_000_000));
+        assert_eq!(certificate.verify(&s, &identity_pk), Ok(&usage));
+        assert_eq!(certificate.key().public_key, key_pk);
+        assert_eq!(certificate.verify(&s, &key_pk), Err(IncorrectSignature));
+        // Not interchangeable with a usage report
+        let report = vault.attested_usage_report(&s, identity).unwrap();
+        assert!(report.signing_message() != certificate.signing_message());
+
+        let data = certificate.serialize(&s);
+        assert_eq!(data.len(), CERTIFICATE_BODY_SIZE + 64);
+        let parsed = DestructionCertificate::from_slice(&s, &data).unwrap();
+        assert_eq!(parsed, certificate);
+        let mut tampered = data.clone();
+        tampered[9 + 44] ^= 1;
+        let tampered = DestructionCertificate::from_slice(&s, &tampered).unwrap();
+        assert_eq!(tampered.verify(&s, &identity_pk), Err(IncorrectSignature));
+        assert_eq!(DestructionCertificate::from_slice(&s, &data[1..]), Err(InvalidDestructionCertificate));
+        let mut bad_version = data.clone();
+        bad_version[0] = 2;
+        assert_eq!(DestructionCertificate::from_slice(&s, &bad_version), Err(InvalidDestructionCertificate));
+        assert_eq!(UsageReport::from_slice(&s, &data), Err(InvalidUsageReport));
+    }
+}
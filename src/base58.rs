@@ -0,0 +1,139 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Base58
+//! Minimal Base58Check encoding and decoding, with Bitcoin's alphabet, for
+//! the Bitcoin key formats. Inputs are a few dozen bytes, so the quadratic
+//! conversion is fine.
+
+use std::string::String;
+use std::vec::Vec;
+
+use hashes::sha256;
+
+const ALPHABET: &'static [u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The first four bytes of `SHA256(SHA256(data))`
+fn checksum(data: &[u8]) -> [u8; 4] {
+    let hash = sha256(&sha256(data));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Appends the encoding of `data` to `out`
+pub fn encode(data: &[u8], out: &mut String) {
+    // Base-58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(data.len() * 138 / 100 + 1);
+    for &byte in data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    for _ in data.iter().take_while(|&&b| b == 0) {
+        out.push('1');
+    }
+    for &digit in digits.iter().rev() {
+        out.push(ALPHABET[digit as usize] as char);
+    }
+}
+
+/// Decodes `s`, or returns `None` if it has a character outside the
+/// alphabet
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    // Bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len() * 733 / 1000 + 1);
+    for c in s.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    bytes.resize(bytes.len() + zeros, 0);
+    bytes.reverse();
+    Some(bytes)
+}
+
+/// Appends the encoding of `data` followed by its checksum to `out`
+pub fn encode_check(data: &[u8], out: &mut String) {
+    let mut buf = data.to_vec();
+    buf.extend_from_slice(&checksum(data));
+    encode(&buf, out);
+}
+
+/// Decodes `s` and checks and strips its checksum
+pub fn decode_check(s: &str) -> Option<Vec<u8>> {
+    let mut ret = decode(s)?;
+    if ret.len() < 4 {
+        return None;
+    }
+    let data_len = ret.len() - 4;
+    if ret[data_len..] != checksum(&ret[..data_len]) {
+        return None;
+    }
+    ret.truncate(data_len);
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use super::{decode, decode_check, encode, encode_check};
+
+    #[test]
+    fn vectors() {
+        // From Bitcoin Core's base58_encode_decode.json
+        for &(hex, b58) in &[("", ""), ("61", "2g"), ("626262", "a3gV"), ("636363", "aPEr"),
+                             ("73696d706c792061206c6f6e6720737472696e67", "2cFupjhnEsSn59qHXstmK2ffpLv2"),
+                             ("00eb15231dfceb60925886b67d065299925915aeb172c06647",
+                              "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"),
+                             ("516b6fcd0f", "ABnLTmg"), ("bf4f89001e670274dd", "3SEo3LWLoPntC"),
+                             ("572e4794", "3EFU7m"), ("ecac89cad93923c02321", "EJDM8drfXA6uyA"),
+                             ("10c8511e", "Rt5zm"), ("00000000000000000000", "1111111111")] {
+            let mut data = [0; 25];
+            let len = ::hex::decode_from(hex, &mut data).unwrap();
+            let mut s = String::new();
+            encode(&data[..len], &mut s);
+            assert_eq!(s, b58);
+            assert_eq!(decode(b58).unwrap(), &data[..len]);
+        }
+        assert_eq!(decode("0OIl"), None);
+    }
+
+    #[test]
+    fn check() {
+        // The address of the hash160 of the uncompressed public key of 1
+        let data = [0x00, 0x91, 0xb2, 0x4b, 0xf9, 0xf5, 0x28, 0x85, 0x32, 0x96, 0x0a, 0xc6, 0x87, 0xab,
+                    0xb0, 0x35, 0x12, 0x7b, 0x1d, 0x28, 0xa5];
+        let mut s = String::new();
+        encode_check(&data, &mut s);
+        assert_eq!(s, "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm");
+        assert_eq!(decode_check(&s).unwrap(), &data[..]);
+        assert_eq!(decode_check("1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZn"), None);
+        assert_eq!(decode_check("1"), None);
+    }
+}
@@ -0,0 +1,132 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Base64
+//! Minimal base64 encoding and decoding shared by the key import/export
+//! formats. Decoding is strict: only canonical encodings are accepted.
+
+use std::string::String;
+use std::vec::Vec;
+
+/// The standard alphabet of RFC 4648, used with padding (e.g. in PEM)
+pub const STANDARD: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The URL-safe alphabet of RFC 4648, used without padding (e.g. in JWK)
+pub const URL_SAFE: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Appends the encoding of `data` to `out`, padding with `=` if `pad` is set
+pub fn encode(data: &[u8], alphabet: &[u8; 64], pad: bool, out: &mut String) {
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..chunk.len() + 1 {
+            out.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if pad {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+}
+
+/// Decodes `s`, which must be padded exactly if `pad` is set and must not
+/// contain any padding otherwise
+pub fn decode(s: &str, alphabet: &[u8; 64], pad: bool) -> Option<Vec<u8>> {
+    let mut s = s.as_bytes();
+    if pad {
+        if s.len() % 4 != 0 {
+            return None;
+        }
+        for _ in 0..2 {
+            if s.last() == Some(&b'=') {
+                s = &s[..s.len() - 1];
+            }
+        }
+    }
+    if s.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut ret = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in s {
+        let v = alphabet.iter().position(|a| a == c)?;
+        acc = acc << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            ret.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    // Reject non-canonical encodings with nonzero trailing bits
+    if acc != 0 {
+        return None;
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, STANDARD, URL_SAFE};
+
+    #[test]
+    fn rfc4648_vectors() {
+        for &(input, expected) in &[("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                                    ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="),
+                                    ("foobar", "Zm9vYmFy")] {
+            let mut s = String::new();
+            encode(input.as_bytes(), STANDARD, true, &mut s);
+            assert_eq!(s, expected);
+            assert_eq!(decode(expected, STANDARD, true), Some(input.as_bytes().to_vec()));
+
+            let unpadded = expected.trim_end_matches('=');
+            let mut s = String::new();
+            encode(input.as_bytes(), URL_SAFE, false, &mut s);
+            assert_eq!(s, unpadded);
+            assert_eq!(decode(unpadded, URL_SAFE, false), Some(input.as_bytes().to_vec()));
+        }
+    }
+
+    #[test]
+    fn alphabets() {
+        let mut s = String::new();
+        encode(&[0xfb, 0xff], STANDARD, true, &mut s);
+        assert_eq!(s, "+/8=");
+        let mut s = String::new();
+        encode(&[0xfb, 0xff], URL_SAFE, false, &mut s);
+        assert_eq!(s, "-_8");
+        assert_eq!(decode("-_8", STANDARD, false), None);
+        assert_eq!(decode("+/8", URL_SAFE, false), None);
+    }
+
+    #[test]
+    fn strict_decoding() {
+        // Missing, excess or unexpected padding
+        assert_eq!(decode("Zg", STANDARD, true), None);
+        assert_eq!(decode("Zg===", STANDARD, true), None);
+        assert_eq!(decode("Zg==", URL_SAFE, false), None);
+        // Impossible length
+        assert_eq!(decode("Zm9vY", URL_SAFE, false), None);
+        // Nonzero trailing bits
+        assert_eq!(decode("Zh==", STANDARD, true), None);
+        assert_eq!(decode("Zm9=", STANDARD, true), None);
+        // Whitespace is not skipped
+        assert_eq!(decode("Zm9v Zg==", STANDARD, true), None);
+    }
+}
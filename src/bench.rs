@@ -0,0 +1,289 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Benchmarks
+//! Throughput measurements of key generation, signing, verification and
+//! ECDH which run inside the enclave, so that operators can compare CPU
+//! generations and SGX configurations (EPC size, mitigations, context
+//! randomization) by calling `run` from an ECALL and returning the
+//! serialized `Report`.
+//!
+//! The enclave has no clock of its own which is safe to read everywhere:
+//! `RDTSC` raises #UD inside SGX1 enclaves. Time is therefore read from a
+//! caller-supplied `Timer`, e.g. an OCALL to the host's monotonic clock, or
+//! `Rdtsc` where the instruction is known to be allowed. The timer is read
+//! once before and once after all iterations of a benchmark, so a slow
+//! timer only costs accuracy when there are few iterations. Requires
+//! compilation with the "bench" feature.
+
+use constants;
+use ecdh::SharedSecret;
+use key::{PublicKey, SecretKey};
+use super::Error::{self, TimeUnavailable};
+use {Message, Secp256k1, Signing, Verification};
+
+/// A source of time in ticks of any fixed length, e.g. nanoseconds or TSC
+/// cycles
+pub trait Timer {
+    /// The current time, or `None` if it can't be read
+    fn now(&mut self) -> Option<u64>;
+}
+
+/// Reads the CPU's time stamp counter. Its ticks are reference cycles, at
+/// the nominal frequency of the CPU.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug)]
+pub struct Rdtsc(());
+
+#[cfg(target_arch = "x86_64")]
+impl Rdtsc {
+    /// Creates the timer
+    ///
+    /// # Safety
+    /// `RDTSC` must be allowed where the timer is read: outside enclaves,
+    /// or inside SGX2 enclaves. SGX1 enclaves fault on it.
+    pub unsafe fn new() -> Rdtsc {
+        Rdtsc(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Timer for Rdtsc {
+    #[inline]
+    fn now(&mut self) -> Option<u64> {
+        Some(unsafe { ::std::arch::x86_64::_rdtsc() })
+    }
+}
+
+/// A benchmarked operation
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Benchmark {
+    /// Derivation of a public key from a secret key, which is the cost of
+    /// generating a keypair apart from the randomness
+    Keygen,
+    /// `Secp256k1::sign`
+    Sign,
+    /// `Secp256k1::verify`
+    Verify,
+    /// `ecdh::SharedSecret::new`
+    Ecdh,
+}
+
+/// The number of `Benchmark`s
+pub const BENCHMARK_COUNT: usize = 4;
+
+impl Benchmark {
+    /// Every benchmark, in the order `run` measures them
+    pub const ALL: [Benchmark; BENCHMARK_COUNT] = [
+        Benchmark::Keygen,
+        Benchmark::Sign,
+        Benchmark::Verify,
+        Benchmark::Ecdh,
+    ];
+
+    /// The byte identifying the benchmark in a serialized `Report`
+    pub fn id(self) -> u8 {
+        match self {
+            Benchmark::Keygen => 0,
+            Benchmark::Sign => 1,
+            Benchmark::Verify => 2,
+            Benchmark::Ecdh => 3,
+        }
+    }
+}
+
+/// The time taken by one benchmark
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Measurement {
+    /// The operation measured
+    pub benchmark: Benchmark,
+    /// How many times it ran
+    pub iterations: u32,
+    /// The timer ticks all iterations took together
+    pub ticks: u64,
+}
+
+impl Measurement {
+    /// The average ticks per operation, or `None` without any iterations
+    pub fn ticks_per_op(&self) -> Option<u64> {
+        if self.iterations == 0 {
+            None
+        } else {
+            Some(self.ticks / self.iterations as u64)
+        }
+    }
+
+    /// Operations per second for a timer ticking `ticks_per_second` times a
+    /// second, or `None` if no time was measured
+    pub fn ops_per_second(&self, ticks_per_second: u64) -> Option<u64> {
+        if self.ticks == 0 {
+            None
+        } else {
+            Some((self.iterations as u128 * ticks_per_second as u128 / self.ticks as u128) as u64)
+        }
+    }
+}
+
+/// The version of the `Report` encoding
+const VERSION: u8 = 1;
+
+/// The size (in bytes) of a serialized `Measurement`: the benchmark id,
+/// then the big-endian iterations (4) and ticks (8)
+const MEASUREMENT_SIZE: usize = 1 + 4 + 8;
+
+/// The size (in bytes) of a serialized `Report`
+pub const REPORT_SIZE: usize = 1 + BENCHMARK_COUNT * MEASUREMENT_SIZE;
+
+/// The results of `run`, one measurement per benchmark in the order of
+/// `Benchmark::ALL`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Report {
+    /// The measurements
+    pub measurements: [Measurement; BENCHMARK_COUNT],
+}
+
+impl Report {
+    /// The measurement of `benchmark`
+    pub fn get(&self, benchmark: Benchmark) -> &Measurement {
+        &self.measurements[benchmark.id() as usize]
+    }
+
+    /// Encodes the report for an `[out, size=REPORT_SIZE]` ECALL buffer: a
+    /// version byte, then each measurement
+    pub fn serialize(&self) -> [u8; REPORT_SIZE] {
+        let mut ret = [0; REPORT_SIZE];
+        ret[0] = VERSION;
+        for (out, measurement) in ret[1..].chunks_mut(MEASUREMENT_SIZE).zip(self.measurements.iter()) {
+            out[0] = measurement.benchmark.id();
+            out[1..5].copy_from_slice(&measurement.iterations.to_be_bytes());
+            out[5..].copy_from_slice(&measurement.ticks.to_be_bytes());
+        }
+        ret
+    }
+}
+
+/// Inputs which differ in every iteration, so that nothing can be cached
+/// between them
+fn input(i: u32) -> [u8; 32] {
+    let mut ret = [0x5a; 32];
+    ret[28..].copy_from_slice(&i.to_be_bytes());
+    ret
+}
+
+/// Runs `benchmark` `iterations` times on fixed inputs and measures how
+/// long that takes. Fails with `TimeUnavailable` if the timer can't be
+/// read.
+pub fn measure<C: Signing + Verification, T: Timer>(secp: &Secp256k1<C>, timer: &mut T, benchmark: Benchmark,
+                                                     iterations: u32) -> Result<Measurement, Error> {
+    let sk = SecretKey::from_slice(&[0xcd; constants::SECRET_KEY_SIZE]).expect("valid benchmark key");
+    let pk = PublicKey::from_secret_key(secp, &sk);
+    let msg = Message::from_slice(&input(0)).expect("32-byte message");
+    let sig = secp.sign(&msg, &sk);
+
+    let start = timer.now().ok_or(TimeUnavailable)?;
+    for i in 0..iterations {
+        match benchmark {
+            Benchmark::Keygen => {
+                let sk = SecretKey::from_slice(&input(i)).expect("valid benchmark key");
+                PublicKey::from_secret_key(secp, &sk);
+            }
+            Benchmark::Sign => {
+                secp.sign(&Message::from_slice(&input(i)).expect("32-byte message"), &sk);
+            }
+            Benchmark::Verify => {
+                secp.verify(&msg, &sig, &pk).expect("benchmark signature verifies");
+            }
+            Benchmark::Ecdh => {
+                let sk = SecretKey::from_slice(&input(i)).expect("valid benchmark key");
+                SharedSecret::new(secp, &pk, &sk);
+            }
+        }
+    }
+    let end = timer.now().ok_or(TimeUnavailable)?;
+    Ok(Measurement { benchmark, iterations, ticks: end.wrapping_sub(start) })
+}
+
+/// Runs every benchmark `iterations` times, in the order of
+/// `Benchmark::ALL`
+pub fn run<C: Signing + Verification, T: Timer>(secp: &Secp256k1<C>, timer: &mut T, iterations: u32)
+                                                 -> Result<Report, Error> {
+    let mut measurements = [Measurement { benchmark: Benchmark::Keygen, iterations: 0, ticks: 0 }; BENCHMARK_COUNT];
+    for (measurement, &benchmark) in measurements.iter_mut().zip(Benchmark::ALL.iter()) {
+        *measurement = measure(secp, timer, benchmark, iterations)?;
+    }
+    Ok(Report { measurements })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{measure, run, Benchmark, Measurement, Timer, REPORT_SIZE};
+    use super::super::Error::TimeUnavailable;
+    use Secp256k1;
+
+    // A timer which ticks 10 times per reading
+    struct Ticking(u64);
+
+    impl Timer for Ticking {
+        fn now(&mut self) -> Option<u64> {
+            self.0 += 10;
+            Some(self.0)
+        }
+    }
+
+    struct Broken;
+
+    impl Timer for Broken {
+        fn now(&mut self) -> Option<u64> {
+            None
+        }
+    }
+
+    #[test]
+    fn report() {
+        let s = Secp256k1::new();
+        let report = run(&s, &mut Ticking(0), 3).unwrap();
+        for (measurement, &benchmark) in report.measurements.iter().zip(Benchmark::ALL.iter()) {
+            assert_eq!(*measurement, Measurement { benchmark, iterations: 3, ticks: 10 });
+            assert_eq!(report.get(benchmark), measurement);
+            assert_eq!(benchmark.id() as usize, Benchmark::ALL.iter().position(|&b| b == benchmark).unwrap());
+        }
+        assert_eq!(run(&s, &mut Broken, 3), Err(TimeUnavailable));
+
+        let data = report.serialize();
+        assert_eq!(data.len(), REPORT_SIZE);
+        assert_eq!(&data[..14], &[1, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 10]);
+        assert_eq!(data[1 + 3 * 13], 3);
+    }
+
+    #[test]
+    fn rates() {
+        let measurement = Measurement { benchmark: Benchmark::Sign, iterations: 500, ticks: 2_000_000 };
+        assert_eq!(measurement.ticks_per_op(), Some(4000));
+        // At 1 GHz, 4us per signature
+        assert_eq!(measurement.ops_per_second(1_000_000_000), Some(250_000));
+        let empty = Measurement { benchmark: Benchmark::Sign, iterations: 0, ticks: 0 };
+        assert_eq!((empty.ticks_per_op(), empty.ops_per_second(1)), (None, None));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn rdtsc() {
+        use super::Rdtsc;
+
+        // Tests don't run in SGX1 enclaves
+        let mut timer = unsafe { Rdtsc::new() };
+        let measurement = measure(&Secp256k1::new(), &mut timer, Benchmark::Verify, 10).unwrap();
+        assert!(measurement.ticks > 0);
+    }
+}
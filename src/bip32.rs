@@ -0,0 +1,139 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # BIP32 derivation primitive
+//! The raw step of BIP32 key derivation: `(IL, IR) = HMAC-SHA512(c, data)`,
+//! with `IL` added to the parent key and `IR` the child's chain code. The
+//! caller builds `data`, so custom derivation schemes can be put together
+//! from it; for standard BIP32 it is `0x00 || k || index` for hardened
+//! children and `K || index` otherwise, with `K` compressed and `index`
+//! big-endian. Extended key encoding and paths are left to wallet code.
+//! Requires compilation with the "hashes" feature.
+
+use cleanup::wipe;
+use hashes::hmac_sha512;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error::{self, InvalidTweak};
+use {Secp256k1, Verification};
+
+/// The size (in bytes) of a chain code
+pub const CHAIN_CODE_SIZE: usize = 32;
+
+/// The HMAC key for deriving a master key from a seed
+pub const MASTER_KEY_HMAC_KEY: &'static [u8] = b"Bitcoin seed";
+
+/// Splits `HMAC-SHA512(key, data)` into its halves `(IL, IR)`
+pub fn hmac_split(key: &[u8], data: &[u8]) -> ([u8; 32], [u8; CHAIN_CODE_SIZE]) {
+    let mut mac = hmac_sha512(key, data);
+    let mut il = [0u8; 32];
+    let mut ir = [0u8; CHAIN_CODE_SIZE];
+    il.copy_from_slice(&mac[..32]);
+    ir.copy_from_slice(&mac[32..]);
+    wipe(&mut mac);
+    (il, ir)
+}
+
+/// The master secret key and chain code for `seed`. Fails with
+/// `InvalidSecretKey` if `IL` is zero or not below the curve order, in
+/// which case BIP32 says to use another seed.
+pub fn master_key(seed: &[u8]) -> Result<(SecretKey, [u8; CHAIN_CODE_SIZE]), Error> {
+    let (mut il, chain_code) = hmac_split(MASTER_KEY_HMAC_KEY, seed);
+    let sk = SecretKey::from_slice(&il);
+    wipe(&mut il);
+    sk.map(|sk| (sk, chain_code))
+}
+
+/// `IL` as a tweak, failing with `InvalidTweak` if it is not below the
+/// curve order
+fn tweak(il: &[u8; 32]) -> Result<Scalar, Error> {
+    Scalar::from_be_bytes(*il).map_err(|_| InvalidTweak)
+}
+
+/// Derives the child secret key `parent + IL` and its chain code `IR` for
+/// `data`. Fails with `InvalidTweak` if `IL` is not below the curve order
+/// or the child key is zero, in which case BIP32 says to proceed with the
+/// next index.
+pub fn derive_secret_key<C>(secp: &Secp256k1<C>, parent: &SecretKey, chain_code: &[u8; CHAIN_CODE_SIZE],
+                            data: &[u8]) -> Result<(SecretKey, [u8; CHAIN_CODE_SIZE]), Error> {
+    let (mut il, child_chain_code) = hmac_split(chain_code, data);
+    let tweak = tweak(&il);
+    wipe(&mut il);
+    let mut tweak = tweak?;
+    let mut child = *parent;
+    let res = child.add_assign(secp, &tweak);
+    tweak.wipe();
+    res.map(|_| (child, child_chain_code))
+}
+
+/// Derives the child public key `parent + IL * G` and its chain code `IR`
+/// for `data`, matching `derive_secret_key` for the same data. Fails with
+/// `InvalidTweak` if `IL` is not below the curve order, and with
+/// `PointAtInfinity` if the child key is the point at infinity.
+pub fn derive_public_key<C: Verification>(secp: &Secp256k1<C>, parent: &PublicKey,
+                                          chain_code: &[u8; CHAIN_CODE_SIZE], data: &[u8])
+                                          -> Result<(PublicKey, [u8; CHAIN_CODE_SIZE]), Error> {
+    let (il, child_chain_code) = hmac_split(chain_code, data);
+    let mut child = *parent;
+    child.add_exp_assign(secp, &tweak(&il)?)?;
+    Ok((child, child_chain_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{derive_public_key, derive_secret_key, hmac_split, master_key, tweak};
+    use super::super::{PublicKey, Secp256k1};
+    use super::super::Error::InvalidTweak;
+
+    #[test]
+    fn bip32_vector_1() {
+        let s = Secp256k1::new();
+        let seed = hex!("000102030405060708090a0b0c0d0e0f");
+        let (master, chain_code) = master_key(&seed).unwrap();
+        assert_eq!(master.secret_bytes()[..],
+                   hex!("e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35")[..]);
+        assert_eq!(chain_code[..], hex!("873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508")[..]);
+
+        // m/0H
+        let mut data = vec![0];
+        data.extend_from_slice(&master.secret_bytes()[..]);
+        data.extend_from_slice(&[0x80, 0, 0, 0]);
+        let (sk, chain_code) = derive_secret_key(&s, &master, &chain_code, &data).unwrap();
+        assert_eq!(sk.secret_bytes()[..], hex!("edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea")[..]);
+        assert_eq!(chain_code[..], hex!("47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141")[..]);
+
+        // m/0H/1, both ways
+        let pk = PublicKey::from_secret_key(&s, &sk);
+        let mut data = pk.serialize().to_vec();
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        let (child_sk, sk_chain_code) = derive_secret_key(&s, &sk, &chain_code, &data).unwrap();
+        let (child_pk, pk_chain_code) = derive_public_key(&s, &pk, &chain_code, &data).unwrap();
+        assert_eq!(child_sk.secret_bytes()[..],
+                   hex!("3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368")[..]);
+        assert_eq!(child_pk.serialize()[..],
+                   hex!("03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c")[..]);
+        assert_eq!(PublicKey::from_secret_key(&s, &child_sk), child_pk);
+        assert_eq!(sk_chain_code[..], hex!("2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19")[..]);
+        assert_eq!(pk_chain_code, sk_chain_code);
+        assert_eq!(hmac_split(&chain_code, &data).1, sk_chain_code);
+    }
+
+    #[test]
+    fn invalid_il() {
+        // An IL of at least the curve order is rejected rather than reduced
+        assert_eq!(tweak(&[0xff; 32]), Err(InvalidTweak));
+        assert!(tweak(&[0x7f; 32]).is_ok());
+        assert!(master_key(&[7; 32]).is_ok());
+    }
+}
@@ -0,0 +1,272 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # BIP38
+//! Decryption of BIP38 passphrase-protected Bitcoin private keys, the
+//! `6P...` strings of paper wallets and older wallet exports, so a user's
+//! legacy key can be moved into an enclave with its passphrase and never
+//! appear in the clear on the host. Both kinds are supported: keys
+//! encrypted directly, and keys made with EC multiplication from an
+//! intermediate code, with or without lot and sequence numbers.
+//!
+//! The passphrase is normalized to Unicode NFC, as BIP38 requires. Each
+//! decryption runs scrypt with N = 16384, r = 8 and p = 8, which takes 16
+//! MiB of heap and a noticeable fraction of a second. Only keys for
+//! mainnet addresses check out. Requires compilation with the "bip38"
+//! feature.
+
+use std::string::String;
+
+use aes::Aes256;
+use aes::cipher::{BlockDecrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use unicode_normalization::UnicodeNormalization;
+
+use base58;
+use cleanup::wipe;
+use constants;
+use hashes::{hash160, sha256};
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error::{self, InvalidBip38Key, KeyDecryptionFailed};
+use {Secp256k1, Signing};
+
+/// The size (in bytes) of a decoded BIP38 key
+const PAYLOAD_SIZE: usize = 39;
+
+const PREFIX: u8 = 0x01;
+const NON_EC_MULTIPLY: u8 = 0x42;
+const EC_MULTIPLY: u8 = 0x43;
+
+const FLAG_NON_EC_MULTIPLY: u8 = 0xc0;
+const FLAG_COMPRESSED: u8 = 0x20;
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+
+/// Runs scrypt with cost 2^`log_n`, filling `out`
+fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, out: &mut [u8]) {
+    let params = ::scrypt::Params::new(log_n, r, p, out.len()).expect("valid BIP38 parameters");
+    ::scrypt::scrypt(password, salt, &params, out).expect("valid output length");
+}
+
+/// `AES256Decrypt(block, key) XOR mask`, in place
+fn decrypt_block(block: &mut [u8], key: &[u8], mask: &[u8]) {
+    Aes256::new(GenericArray::from_slice(key)).decrypt_block(GenericArray::from_mut_slice(block));
+    for (b, m) in block.iter_mut().zip(mask) {
+        *b ^= m;
+    }
+}
+
+/// The first four bytes of `SHA256(SHA256(address))` for the mainnet P2PKH
+/// address of `sk`, which a BIP38 key carries to check the passphrase
+fn address_hash<C: Signing>(secp: &Secp256k1<C>, sk: &SecretKey, compressed: bool) -> [u8; 4] {
+    let pk = PublicKey::from_secret_key(secp, sk);
+    let mut payload = [0; 21];
+    payload[1..].copy_from_slice(&if compressed {
+        hash160(&pk.serialize())
+    } else {
+        hash160(&pk.serialize_uncompressed())
+    });
+    let mut address = String::new();
+    base58::encode_check(&payload, &mut address);
+    let hash = sha256(&sha256(address.as_bytes()));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Decrypts a key encrypted directly, from the 16-byte halves `encrypted`
+fn decrypt_non_ec(passphrase: &[u8], address_hash: &[u8], encrypted: &[u8]) -> Result<SecretKey, Error> {
+    let mut derived = [0; 64];
+    scrypt(passphrase, address_hash, 14, 8, 8, &mut derived);
+    let mut data = [0; constants::SECRET_KEY_SIZE];
+    data.copy_from_slice(encrypted);
+    decrypt_block(&mut data[..16], &derived[32..], &derived[..16]);
+    decrypt_block(&mut data[16..], &derived[32..], &derived[16..32]);
+    wipe(&mut derived);
+    let ret = SecretKey::from_slice(&data).map_err(|_| KeyDecryptionFailed);
+    wipe(&mut data);
+    ret
+}
+
+/// Decrypts a key made by EC multiplication, from the owner entropy and
+/// the encrypted parts which follow the address hash
+fn decrypt_ec<C: Signing>(secp: &Secp256k1<C>, passphrase: &[u8], address_hash: &[u8], lot_sequence: bool,
+                          rest: &[u8]) -> Result<SecretKey, Error> {
+    let (owner_entropy, encrypted) = rest.split_at(8);
+    let owner_salt = if lot_sequence { &owner_entropy[..4] } else { owner_entropy };
+    let mut pass_factor = [0; 32];
+    scrypt(passphrase, owner_salt, 14, 8, 8, &mut pass_factor);
+    if lot_sequence {
+        let mut buf = [0; 40];
+        buf[..32].copy_from_slice(&pass_factor);
+        buf[32..].copy_from_slice(owner_entropy);
+        pass_factor = sha256(&sha256(&buf));
+        wipe(&mut buf);
+    }
+    let mut ret = match SecretKey::from_slice(&pass_factor) {
+        Ok(sk) => sk,
+        Err(_) => {
+            wipe(&mut pass_factor);
+            return Err(KeyDecryptionFailed);
+        }
+    };
+    wipe(&mut pass_factor);
+    let pass_point = PublicKey::from_secret_key(secp, &ret).serialize();
+
+    let mut salt = [0; 12];
+    salt[..4].copy_from_slice(address_hash);
+    salt[4..].copy_from_slice(owner_entropy);
+    let mut derived = [0; 64];
+    scrypt(&pass_point, &salt, 10, 1, 1, &mut derived);
+
+    // The second part holds the end of the first, which holds the start of
+    // seedb
+    let mut seed = [0; 32];
+    seed[..8].copy_from_slice(&encrypted[..8]);
+    seed[16..].copy_from_slice(&encrypted[8..]);
+    decrypt_block(&mut seed[16..], &derived[32..], &derived[16..32]);
+    seed.copy_within(16..24, 8);
+    decrypt_block(&mut seed[..16], &derived[32..], &derived[..16]);
+    seed.copy_within(24..32, 16);
+    wipe(&mut derived);
+    let mut factor = sha256(&sha256(&seed[..24]));
+    wipe(&mut seed);
+
+    let res = match Scalar::from_be_bytes(factor) {
+        Ok(factor) => ret.mul_assign(secp, &factor).map_err(|_| KeyDecryptionFailed),
+        Err(_) => Err(KeyDecryptionFailed),
+    };
+    wipe(&mut factor);
+    res.map(|_| ret)
+}
+
+/// Decrypts the BIP38 key `encrypted` with `passphrase`, returning the
+/// secret key and whether its address uses the compressed public key.
+/// Fails with `InvalidBip38Key` if `encrypted` is not a BIP38 key, and
+/// with `KeyDecryptionFailed` if the passphrase is wrong.
+pub fn decrypt<C: Signing>(secp: &Secp256k1<C>, encrypted: &str, passphrase: &str)
+                           -> Result<(SecretKey, bool), Error> {
+    let payload = match base58::decode_check(encrypted) {
+        Some(payload) => payload,
+        None => return Err(InvalidBip38Key),
+    };
+    if payload.len() != PAYLOAD_SIZE || payload[0] != PREFIX {
+        return Err(InvalidBip38Key);
+    }
+    let flag = payload[2];
+    let compressed = flag & FLAG_COMPRESSED != 0;
+    let address_hash = &payload[3..7];
+
+    let mut normalized: String = passphrase.nfc().collect();
+    let res = match payload[1] {
+        NON_EC_MULTIPLY if flag & !FLAG_COMPRESSED == FLAG_NON_EC_MULTIPLY => {
+            decrypt_non_ec(normalized.as_bytes(), address_hash, &payload[7..])
+        }
+        EC_MULTIPLY if flag & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) == 0 => {
+            decrypt_ec(secp, normalized.as_bytes(), address_hash, flag & FLAG_LOT_SEQUENCE != 0, &payload[7..])
+        }
+        _ => Err(InvalidBip38Key),
+    };
+    unsafe { wipe(normalized.as_bytes_mut()); }
+
+    let sk = res?;
+    if address_hash != self::address_hash(secp, &sk, compressed) {
+        return Err(KeyDecryptionFailed);
+    }
+    Ok((sk, compressed))
+}
+
+impl SecretKey {
+    /// Decrypts the BIP38 key `encrypted` with `passphrase`, as
+    /// `bip38::decrypt`
+    #[inline]
+    pub fn from_bip38<C: Signing>(secp: &Secp256k1<C>, encrypted: &str, passphrase: &str)
+                                  -> Result<SecretKey, Error> {
+        decrypt(secp, encrypted, passphrase).map(|(sk, _)| sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decrypt;
+    use key::SecretKey;
+    use Secp256k1;
+    use super::super::Error::{InvalidBip38Key, KeyDecryptionFailed};
+
+    fn key(hex: &str) -> SecretKey {
+        let mut data = [0; 32];
+        assert_eq!(::hex::decode_from(hex, &mut data), Some(32));
+        SecretKey::from_slice(&data).unwrap()
+    }
+
+    #[test]
+    fn non_ec_multiply() {
+        let secp = Secp256k1::signing_only();
+        // The vectors of BIP38
+        for &(encrypted, passphrase, sk, compressed) in &[
+            ("6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg", "TestingOneTwoThree",
+             "cbf4b9f70470856bb4f40f80b87edb90865997ffee6df315ab166d713af433a5", false),
+            ("6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq", "Satoshi",
+             "09c2686880095b1a4c249ee3ac4eea8a014f11e6f986d0b5025ac1f39afbd9ae", false),
+            ("6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo", "TestingOneTwoThree",
+             "cbf4b9f70470856bb4f40f80b87edb90865997ffee6df315ab166d713af433a5", true),
+            ("6PYLtMnXvfG3oJde97zRyLYFZCYizPU5T3LwgdYJz1fRhh16bU7u6PPmY7", "Satoshi",
+             "09c2686880095b1a4c249ee3ac4eea8a014f11e6f986d0b5025ac1f39afbd9ae", true),
+        ] {
+            assert_eq!(decrypt(&secp, encrypted, passphrase), Ok((key(sk), compressed)));
+        }
+        let encrypted = "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByq";
+        assert_eq!(SecretKey::from_bip38(&secp, encrypted, "Satoshi"),
+                   Ok(key("09c2686880095b1a4c249ee3ac4eea8a014f11e6f986d0b5025ac1f39afbd9ae")));
+        assert_eq!(SecretKey::from_bip38(&secp, encrypted, "satoshi"), Err(KeyDecryptionFailed));
+    }
+
+    #[test]
+    fn ec_multiply() {
+        let secp = Secp256k1::signing_only();
+        for &(encrypted, passphrase, sk) in &[
+            ("6PfQu77ygVyJLZjfvMLyhLMQbYnu5uguoJJ4kMCLqWwPEdfpwANVS76gTX", "TestingOneTwoThree",
+             "a43a940577f4e97f5c4d39eb14ff083a98187c64ea7c99ef7ce460833959a519"),
+            ("6PfLGnQs6VZnrNpmVKfjotbnQuaJK4KZoPFrAjx1JMJUa1Ft8gnf5WxfKd", "Satoshi",
+             "c2c8036df268f498099350718c4a3ef3984d2be84618c2650f5171dcc5eb660a"),
+            // With lot and sequence numbers
+            ("6PgNBNNzDkKdhkT6uJntUXwwzQV8Rr2tZcbkDcuC9DZRsS6AtHts4Ypo1j", "MOLON LABE",
+             "44ea95afbf138356a05ea32110dfd627232d0f2991ad221187be356f19fa8190"),
+            ("6PgGWtx25kUg8QWvwuJAgorN6k9FbE25rv5dMRwu5SKMnfpfVe5mar2ngH",
+             "\u{039c}\u{039f}\u{039b}\u{03a9}\u{039d} \u{039b}\u{0391}\u{0392}\u{0395}",
+             "ca2759aa4adb0f96c414f36abeb8db59342985be9fa50faac228c8e7d90e3006"),
+        ] {
+            assert_eq!(decrypt(&secp, encrypted, passphrase), Ok((key(sk), false)));
+        }
+        assert_eq!(decrypt(&secp, "6PfLGnQs6VZnrNpmVKfjotbnQuaJK4KZoPFrAjx1JMJUa1Ft8gnf5WxfKd", "TestingOneTwoThree"),
+                   Err(KeyDecryptionFailed));
+    }
+
+    #[test]
+    fn unicode_passphrase() {
+        // BIP38's vector, whose passphrase only matches once normalized
+        let secp = Secp256k1::signing_only();
+        assert_eq!(SecretKey::from_bip38(&secp, "6PRW5o9FLp4gJDDVqJQKJFTpMvdsSGJxMYHtHaQBF3ooa8mwD69bapcDQn",
+                                         "\u{03d2}\u{0301}\u{0000}\u{10400}\u{1f4a9}"),
+                   Ok(key("64eeab5f9be2a01a8365a579511eb3373c87c40da6d2a25f05bda68fe077b66e")));
+    }
+
+    #[test]
+    fn malformed() {
+        let secp = Secp256k1::signing_only();
+        for encrypted in &["", "6PRNFFkZc2NZ6dJqFfhRoFNMR9Lnyj7dYGrzdgXXVMXcxoKTePPX1dWByr",
+                           // A WIF private key
+                           "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ", "6P0OIl"] {
+            assert_eq!(decrypt(&secp, encrypted, "Satoshi"), Err(InvalidBip38Key));
+        }
+    }
+}
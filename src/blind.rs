@@ -0,0 +1,319 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Blind Schnorr signatures
+//! A signer (`BlindSigner`) signs a message it never sees, and the client
+//! (`Unblinder`) turns the result into an ordinary BIP340 signature which
+//! the signer cannot link to the session that produced it, as used for
+//! e-cash tokens:
+//!
+//! 1. the signer picks a nonce `k` and sends `R = k * G`;
+//! 2. the client picks blinding factors `a` and `b`, computes
+//!    `R' = R + a * G + b * P` and the BIP340 challenge `e'` for `R'`, its
+//!    message and the signer's key `P`, and sends `e = e' + b`;
+//! 3. the signer answers with `s = k + e * x`, and the client's signature
+//!    is `(R'.x, s + a)`.
+//!
+//! **Warning:** blind Schnorr signatures are only secure while sessions
+//! don't overlap. With many sessions open at once, a client can solve the
+//! ROS problem across them and get one more signature than the signer
+//! made, which for e-cash means forged coins. `BlindSigner` therefore
+//! refuses to open more than `max_sessions` sessions at a time, one by
+//! default; raise the limit only if an extra signature does no harm.
+//! Requires compilation with the "hashes" feature.
+
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use generator::Generator;
+use hashes::{TaggedHasher, BIP340_CHALLENGE_TAG};
+use key::{Parity, PublicKey, SecretKey, XOnlyPublicKey};
+use scalar::Scalar;
+use super::Error::{self, IncorrectSignature, InvalidPublicKey, TooManyBlindSessions, UnknownBlindSession};
+use {Message, Secp256k1, Signing, Verification};
+
+/// The size (in bytes) of a BIP340 signature
+pub const SCHNORR_SIGNATURE_SIZE: usize = 64;
+
+/// The number of sessions a `BlindSigner` keeps open at once by default
+pub const DEFAULT_MAX_SESSIONS: usize = 1;
+
+/// The BIP340 challenge for the nonce `r`, key `pk` (both x-only) and `msg`
+pub(crate) fn challenge(r: &[u8], pk: &[u8; 32], msg: &Message) -> Scalar {
+    let engine = TaggedHasher::new(BIP340_CHALLENGE_TAG).chain(r).chain(pk).chain(&msg[..]);
+    Scalar::from_be_bytes_mod_order(&engine.result())
+}
+
+/// `a * p + b * q`, or `None` for the point at infinity
+pub(crate) fn mul_add<C: Verification>(secp: &Secp256k1<C>, a: &Scalar, p: &PublicKey, b: &Scalar, q: &PublicKey)
+                                       -> Option<PublicKey> {
+    let mut ap = *p;
+    let mut bq = *q;
+    match (ap.mul_assign(secp, a).is_ok(), bq.mul_assign(secp, b).is_ok()) {
+        (true, true) => ap.combine(secp, &bq).ok(),
+        (true, false) => Some(ap),
+        (false, true) => Some(bq),
+        (false, false) => None,
+    }
+}
+
+/// Checks a BIP340 signature for `msg` under the x-only key `pk`. Fails with
+/// `InvalidPublicKey` if `pk` is not on the curve, and with
+/// `IncorrectSignature` if the signature is wrong.
+pub fn verify_schnorr<C: Verification>(secp: &Secp256k1<C>, sig: &[u8; SCHNORR_SIGNATURE_SIZE], msg: &Message,
+                                       pk: &[u8; 32]) -> Result<(), Error> {
+    let p = XOnlyPublicKey::from_slice(pk).map_err(|_| InvalidPublicKey)?.public_key(Parity::Even);
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&sig[32..]);
+    let s = Scalar::from_be_bytes(s).map_err(|_| IncorrectSignature)?;
+    let e = challenge(&sig[..32], pk, msg);
+    // R = s * G - e * P must have even y and x-coordinate r
+    match mul_add(secp, &s, &Generator::g().into(), &e.negate(), &p).map(|r| r.x_only_public_key()) {
+        Some((r, Parity::Even)) if r.serialize()[..] == sig[..32] => Ok(()),
+        _ => Err(IncorrectSignature),
+    }
+}
+
+/// The signer's side: answers blinded challenges with its key, keeping
+/// each session's nonce until it is used or the session is aborted.
+/// Secrets are wiped on drop.
+pub struct BlindSigner {
+    key: Scalar,
+    public_key: [u8; 32],
+    sessions: Vec<(u64, Scalar)>,
+    next_session: u64,
+    max_sessions: usize,
+}
+
+impl Drop for BlindSigner {
+    fn drop(&mut self) {
+        self.key.wipe();
+        for &mut (_, ref mut nonce) in self.sessions.iter_mut() {
+            nonce.wipe();
+        }
+    }
+}
+
+impl BlindSigner {
+    /// Creates a signer for `sk`, allowing `DEFAULT_MAX_SESSIONS` open
+    /// sessions
+    pub fn new<C: Signing>(secp: &Secp256k1<C>, sk: &SecretKey) -> BlindSigner {
+        let (pk, parity) = PublicKey::from_secret_key(secp, sk).x_only_public_key();
+        let key = Scalar::from_secret_key(sk);
+        BlindSigner {
+            // BIP340 keys have even y, so sign with the negated key if needed
+            key: if parity == Parity::Even { key } else { key.negate() },
+            public_key: pk.serialize(),
+            sessions: Vec::new(),
+            next_session: 0,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+        }
+    }
+
+    /// The x-only public key which the unblinded signatures verify under
+    #[inline]
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public_key
+    }
+
+    /// The most sessions which may be open at once
+    #[inline]
+    pub fn max_sessions(&self) -> usize {
+        self.max_sessions
+    }
+
+    /// Sets the most sessions which may be open at once. See the module
+    /// documentation before raising it above one.
+    #[inline]
+    pub fn set_max_sessions(&mut self, max_sessions: usize) {
+        self.max_sessions = max_sessions;
+    }
+
+    /// The number of open sessions
+    #[inline]
+    pub fn open_sessions(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Opens a session and returns its ID and the nonce point to send to
+    /// the client. Fails with `TooManyBlindSessions` if `max_sessions` are
+    /// already open.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn start<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, rng: &mut R)
+                                                     -> Result<(u64, PublicKey), Error> {
+        if self.sessions.len() >= self.max_sessions {
+            return Err(TooManyBlindSessions);
+        }
+        let sk = SecretKey::new(secp, rng);
+        let nonce_point = PublicKey::from_secret_key(secp, &sk);
+        let session = self.next_session;
+        self.next_session += 1;
+        self.sessions.push((session, Scalar::from_secret_key(&sk)));
+        Ok((session, nonce_point))
+    }
+
+    /// Answers the client's blinded challenge and closes the session. Fails
+    /// with `UnknownBlindSession` if the session is not open.
+    pub fn respond(&mut self, session: u64, challenge: &Scalar) -> Result<Scalar, Error> {
+        let position = self.sessions.iter().position(|&(id, _)| id == session).ok_or(UnknownBlindSession)?;
+        let (_, mut nonce) = self.sessions.swap_remove(position);
+        let s = nonce.add(&challenge.mul(&self.key));
+        nonce.wipe();
+        Ok(s)
+    }
+
+    /// Closes a session without answering. Fails with `UnknownBlindSession`
+    /// if the session is not open.
+    pub fn abort(&mut self, session: u64) -> Result<(), Error> {
+        let position = self.sessions.iter().position(|&(id, _)| id == session).ok_or(UnknownBlindSession)?;
+        let (_, mut nonce) = self.sessions.swap_remove(position);
+        nonce.wipe();
+        Ok(())
+    }
+}
+
+/// The client's side of a session: blinds the challenge for its message
+/// and unblinds the signer's answer
+pub struct Unblinder {
+    public_key: [u8; 32],
+    msg: Message,
+    nonce: PublicKey,
+    blinded_nonce: PublicKey,
+    alpha: Scalar,
+    challenge: Scalar,
+}
+
+impl Unblinder {
+    /// Blinds a session for `msg` with the signer's x-only key `signer` and
+    /// its nonce point. Fails with `InvalidPublicKey` if `signer` is not on
+    /// the curve.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new<C: Signing + Verification, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R,
+                                                                  signer: &[u8; 32], nonce: &PublicKey,
+                                                                  msg: &Message) -> Result<Unblinder, Error> {
+        let p = XOnlyPublicKey::from_slice(signer).map_err(|_| InvalidPublicKey)?.public_key(Parity::Even);
+        let g: PublicKey = Generator::g().into();
+        loop {
+            let alpha = Scalar::from_secret_key(&SecretKey::new(secp, rng));
+            let beta = Scalar::from_secret_key(&SecretKey::new(secp, rng));
+            // R' = R + alpha * G + beta * P; BIP340 needs it to have even y,
+            // which half of all blinding factors give
+            let blinded_nonce = match mul_add(secp, &alpha, &g, &beta, &p).and_then(|t| nonce.combine(secp, &t).ok()) {
+                Some(r) if r.x_only_public_key().1 == Parity::Even => r,
+                _ => continue,
+            };
+            let challenge = challenge(&blinded_nonce.x_only_public_key().0.serialize(), signer, msg).add(&beta);
+            return Ok(Unblinder {
+                public_key: *signer,
+                msg: *msg,
+                nonce: *nonce,
+                blinded_nonce,
+                alpha,
+                challenge,
+            });
+        }
+    }
+
+    /// The blinded challenge to send to the signer
+    #[inline]
+    pub fn challenge(&self) -> Scalar {
+        self.challenge
+    }
+
+    /// Checks the signer's answer and returns the BIP340 signature. Fails
+    /// with `IncorrectSignature` if the answer is wrong.
+    pub fn unblind<C: Verification>(&self, secp: &Secp256k1<C>, s: &Scalar)
+                                    -> Result<[u8; SCHNORR_SIGNATURE_SIZE], Error> {
+        // s * G - e * P = R
+        let p = XOnlyPublicKey::from_slice(&self.public_key)?.public_key(Parity::Even);
+        if mul_add(secp, s, &Generator::g().into(), &self.challenge.negate(), &p) != Some(self.nonce) {
+            return Err(IncorrectSignature);
+        }
+        let mut sig = [0u8; SCHNORR_SIGNATURE_SIZE];
+        sig[..32].copy_from_slice(&self.blinded_nonce.x_only_public_key().0.serialize());
+        sig[32..].copy_from_slice(&s.add(&self.alpha).to_be_bytes());
+        debug_assert_eq!(verify_schnorr(secp, &sig, &self.msg, &self.public_key), Ok(()));
+        Ok(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{verify_schnorr, BlindSigner, Unblinder};
+    use super::super::{Message, Secp256k1, SecretKey};
+    use super::super::scalar::Scalar;
+    use hex::decode_array;
+    use super::super::Error::{IncorrectSignature, InvalidPublicKey, TooManyBlindSessions, UnknownBlindSession};
+
+    #[test]
+    fn bip340_vector() {
+        let s = Secp256k1::new();
+        let pk: [u8; 32] = decode_array("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659");
+        let msg = Message::from(decode_array::<32>("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89"));
+        let sig: [u8; 64] = decode_array("6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341\
+                                          8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a");
+        assert_eq!(verify_schnorr(&s, &sig, &msg, &pk), Ok(()));
+
+        let mut bad = sig;
+        bad[63] ^= 1;
+        assert_eq!(verify_schnorr(&s, &bad, &msg, &pk), Err(IncorrectSignature));
+        let mut bad = sig;
+        bad[0] ^= 1;
+        assert_eq!(verify_schnorr(&s, &bad, &msg, &pk), Err(IncorrectSignature));
+        assert_eq!(verify_schnorr(&s, &sig, &msg, &[0; 32]), Err(InvalidPublicKey));
+    }
+
+    #[test]
+    fn blind_signing() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        // Keys with either parity of y
+        for _ in 0..8 {
+            let mut signer = BlindSigner::new(&s, &SecretKey::new(&s, &mut thread_rng()));
+            let (session, nonce) = signer.start(&s, &mut thread_rng()).unwrap();
+            let client = Unblinder::new(&s, &mut thread_rng(), &signer.public_key(), &nonce, &msg).unwrap();
+            let answer = signer.respond(session, &client.challenge()).unwrap();
+            let sig = client.unblind(&s, &answer).unwrap();
+            assert_eq!(verify_schnorr(&s, &sig, &msg, &signer.public_key()), Ok(()));
+            // The signer never saw the signature's nonce
+            assert!(nonce.serialize()[1..] != sig[..32]);
+
+            assert_eq!(signer.respond(session, &client.challenge()), Err(UnknownBlindSession));
+            assert_eq!(client.unblind(&s, &answer.add(&Scalar::ONE)), Err(IncorrectSignature));
+        }
+    }
+
+    #[test]
+    fn session_limit() {
+        let s = Secp256k1::new();
+        let mut signer = BlindSigner::new(&s, &SecretKey::new(&s, &mut thread_rng()));
+        let (first, _) = signer.start(&s, &mut thread_rng()).unwrap();
+        assert_eq!(signer.start(&s, &mut thread_rng()).err(), Some(TooManyBlindSessions));
+        signer.abort(first).unwrap();
+        assert_eq!(signer.abort(first), Err(UnknownBlindSession));
+
+        signer.set_max_sessions(2);
+        let (first, _) = signer.start(&s, &mut thread_rng()).unwrap();
+        let (second, _) = signer.start(&s, &mut thread_rng()).unwrap();
+        assert!(first != second);
+        assert_eq!(signer.open_sessions(), 2);
+        assert_eq!(signer.start(&s, &mut thread_rng()).err(), Some(TooManyBlindSessions));
+        signer.respond(second, &Scalar::ONE).unwrap();
+        assert_eq!(signer.open_sessions(), 1);
+        assert!(signer.start(&s, &mut thread_rng()).is_ok());
+    }
+}
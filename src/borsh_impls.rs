@@ -0,0 +1,124 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Borsh
+//! Implementations of the `borsh` 1.x traits, using fixed-size encodings
+//! without a length prefix: 32 bytes for a `SecretKey`, the 33-byte
+//! compressed form for a `PublicKey` and the 64-byte compact `r || s` for
+//! a `Signature`. Decoding checks the data as `from_slice` does and fails
+//! with `InvalidData`. Requires compilation with the "borsh" feature.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::io::{Error, ErrorKind, Read, Result, Write};
+
+#[cfg(not(feature = "verify-only"))]
+use cleanup;
+use constants;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use super::Signature;
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl BorshSerialize for SecretKey {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self[..])
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl BorshDeserialize for SecretKey {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<SecretKey> {
+        let mut buf = [0u8; constants::SECRET_KEY_SIZE];
+        let res = reader.read_exact(&mut buf)
+            .and_then(|_| SecretKey::from_slice(&buf).map_err(|_| Error::new(ErrorKind::InvalidData, "invalid secret key")));
+        cleanup::wipe(&mut buf);
+        res
+    }
+}
+
+impl BorshSerialize for PublicKey {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.serialize())
+    }
+}
+
+impl BorshDeserialize for PublicKey {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<PublicKey> {
+        let mut buf = [0u8; constants::PUBLIC_KEY_SIZE];
+        reader.read_exact(&mut buf)?;
+        if buf[0] != 0x02 && buf[0] != 0x03 {
+            return Err(Error::new(ErrorKind::InvalidData, "public key not compressed"));
+        }
+        PublicKey::from_slice(&buf).map_err(|_| Error::new(ErrorKind::InvalidData, "invalid public key"))
+    }
+}
+
+impl BorshSerialize for Signature {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.compact_bytes())
+    }
+}
+
+impl BorshDeserialize for Signature {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Signature> {
+        let mut buf = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        reader.read_exact(&mut buf)?;
+        Signature::from_compact_bytes(&buf).map_err(|_| Error::new(ErrorKind::InvalidData, "invalid signature"))
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use borsh::{self, BorshDeserialize};
+
+    use rng::thread_rng;
+    use super::super::{Message, PublicKey, Secp256k1, SecretKey, Signature};
+
+    #[test]
+    fn round_trip() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let sig = s.sign(&Message::from_slice(&[1; 32]).unwrap(), &sk);
+
+        #[cfg(not(feature = "no-secret-export"))]
+        assert_eq!(borsh::to_vec(&sk).unwrap()[..], sk[..]);
+        assert_eq!(SecretKey::try_from_slice(&sk.secret_bytes()[..]).unwrap(), sk);
+        let encoded = borsh::to_vec(&pk).unwrap();
+        assert_eq!(encoded[..], pk.serialize()[..]);
+        assert_eq!(PublicKey::try_from_slice(&encoded).unwrap(), pk);
+        let encoded = borsh::to_vec(&sig).unwrap();
+        assert_eq!(encoded[..], sig.serialize_compact(&s)[..]);
+        assert_eq!(Signature::try_from_slice(&encoded).unwrap(), sig);
+
+        // Inside other data, with no length prefix
+        let encoded = borsh::to_vec(&(pk, sig, 7u8)).unwrap();
+        assert_eq!(encoded.len(), 33 + 64 + 1);
+        assert_eq!(<(PublicKey, Signature, u8)>::try_from_slice(&encoded).unwrap(), (pk, sig, 7));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(SecretKey::try_from_slice(&[0; 32]).is_err());
+        assert!(SecretKey::try_from_slice(&[1; 31]).is_err());
+        assert!(SecretKey::try_from_slice(&[1; 33]).is_err());
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let mut encoded = pk.serialize();
+        encoded[0] = 0x04;
+        assert!(PublicKey::try_from_slice(&encoded).is_err());
+        assert!(PublicKey::try_from_slice(&pk.serialize_uncompressed()).is_err());
+        assert!(Signature::try_from_slice(&[0xff; 64]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Verification caching
+//! A bounded cache of signatures which have already verified, for enclave
+//! relayers which see the same signed objects over and over again across
+//! ECALLs. Entries are keyed by a tagged SHA-256 hash of the message,
+//! compact signature and compressed public key, and the least recently used
+//! one is evicted once the cache is full. Only successful verifications are
+//! cached, so a failing signature is checked again every time it shows up.
+//! Requires compilation with the "hashes" feature.
+
+use std::collections::BTreeMap;
+
+use hashes::{TaggedHasher, VERIFICATION_CACHE_TAG, SHA256_SIZE};
+use key::PublicKey;
+use {Error, Message, Secp256k1, Signature, Verification};
+
+/// Counters kept by a `VerificationCache`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct CacheStats {
+    /// Lookups which found the signature in the cache
+    pub hits: u64,
+    /// Lookups which had to go through libsecp256k1
+    pub misses: u64,
+    /// Entries dropped to make room for new ones
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// The fraction of lookups which were hits, or 0 if there were none
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// A least-recently-used set of (message, signature, public key) triples
+/// known to verify
+#[derive(Clone, Debug)]
+pub struct VerificationCache {
+    capacity: usize,
+    // Entry key to the tick of its last use, and the other way around
+    entries: BTreeMap<[u8; SHA256_SIZE], u64>,
+    by_use: BTreeMap<u64, [u8; SHA256_SIZE]>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl VerificationCache {
+    /// Creates an empty cache holding at most `capacity` signatures. Panics
+    /// if `capacity` is zero.
+    pub fn new(capacity: usize) -> VerificationCache {
+        assert!(capacity > 0, "a verification cache needs room for at least one entry");
+        VerificationCache {
+            capacity,
+            entries: BTreeMap::new(),
+            by_use: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The most signatures the cache holds
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of signatures in the cache
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the cache holds no signatures
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The hit, miss and eviction counts since the cache was created or its
+    /// statistics were last reset
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Zeroes the hit, miss and eviction counts
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// Drops every entry, keeping the statistics
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.by_use.clear();
+    }
+
+    /// Looks up an entry, marking it as the most recently used if found
+    fn lookup(&mut self, key: &[u8; SHA256_SIZE]) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some(last_use) => {
+                self.by_use.remove(last_use);
+                self.by_use.insert(tick, *key);
+                *last_use = tick;
+                self.stats.hits += 1;
+                true
+            }
+            None => {
+                self.stats.misses += 1;
+                false
+            }
+        }
+    }
+
+    /// Adds an entry which was just looked up, evicting the least recently
+    /// used one if the cache is full
+    fn insert(&mut self, key: [u8; SHA256_SIZE]) {
+        if self.entries.len() == self.capacity {
+            let oldest = self.by_use.iter().next().map(|(&tick, &key)| (tick, key));
+            if let Some((tick, oldest)) = oldest {
+                self.by_use.remove(&tick);
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+        self.entries.insert(key, self.tick);
+        self.by_use.insert(self.tick, key);
+    }
+}
+
+/// The cache key for a signature
+fn entry_key(msg: &Message, sig: &Signature, pk: &PublicKey) -> [u8; SHA256_SIZE] {
+    TaggedHasher::new(VERIFICATION_CACHE_TAG)
+        .chain(&msg[..])
+        .chain(&sig.compact_bytes())
+        .chain(&pk.serialize())
+        .result()
+}
+
+impl<C: Verification> Secp256k1<C> {
+    /// Checks that `sig` is a valid ECDSA signature for `msg` using the
+    /// public key `pk`, like `verify`, but returns straight away if `cache`
+    /// already holds it and adds it to `cache` once it has verified.
+    pub fn verify_cached(&self, cache: &mut VerificationCache, msg: &Message, sig: &Signature, pk: &PublicKey)
+                         -> Result<(), Error> {
+        let key = entry_key(msg, sig, pk);
+        if cache.lookup(&key) {
+            return Ok(());
+        }
+        self.verify(msg, sig, pk)?;
+        cache.insert(key);
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use super::{CacheStats, VerificationCache};
+    use rng::thread_rng;
+    use super::super::{Message, Secp256k1};
+    use super::super::Error::IncorrectSignature;
+
+    #[test]
+    fn hits_and_misses() {
+        let s = Secp256k1::new();
+        let mut cache = VerificationCache::new(4);
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let sig = s.sign(&msg, &sk);
+
+        assert_eq!(cache.stats().hit_rate(), 0.0);
+        assert_eq!(s.verify_cached(&mut cache, &msg, &sig, &pk), Ok(()));
+        assert_eq!(s.verify_cached(&mut cache, &msg, &sig, &pk), Ok(()));
+        assert_eq!(s.verify_cached(&mut cache, &msg, &sig, &pk), Ok(()));
+        assert_eq!(cache.len(), 1);
+
+        // Failures are not cached
+        let wrong = Message::from_slice(&[2; 32]).unwrap();
+        assert_eq!(s.verify_cached(&mut cache, &wrong, &sig, &pk), Err(IncorrectSignature));
+        assert_eq!(s.verify_cached(&mut cache, &wrong, &sig, &pk), Err(IncorrectSignature));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 3, evictions: 0 });
+        assert_eq!(cache.stats().hit_rate(), 0.4);
+
+        cache.reset_stats();
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(s.verify_cached(&mut cache, &msg, &sig, &pk), Ok(()));
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 1, evictions: 0 });
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let s = Secp256k1::new();
+        let mut cache = VerificationCache::new(2);
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msgs: Vec<_> = (0..3).map(|i| Message::from_slice(&[i + 1; 32]).unwrap()).collect();
+        let sigs: Vec<_> = msgs.iter().map(|msg| s.sign(msg, &sk)).collect();
+
+        s.verify_cached(&mut cache, &msgs[0], &sigs[0], &pk).unwrap();
+        s.verify_cached(&mut cache, &msgs[1], &sigs[1], &pk).unwrap();
+        // Using the first makes the second the oldest
+        s.verify_cached(&mut cache, &msgs[0], &sigs[0], &pk).unwrap();
+        s.verify_cached(&mut cache, &msgs[2], &sigs[2], &pk).unwrap();
+        assert_eq!(cache.len(), cache.capacity());
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3, evictions: 1 });
+
+        cache.reset_stats();
+        s.verify_cached(&mut cache, &msgs[0], &sigs[0], &pk).unwrap();
+        s.verify_cached(&mut cache, &msgs[2], &sigs[2], &pk).unwrap();
+        s.verify_cached(&mut cache, &msgs[1], &sigs[1], &pk).unwrap();
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1, evictions: 1 });
+    }
+}
@@ -0,0 +1,198 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Callbacks
+//! libsecp256k1 reports illegal arguments and failed internal consistency
+//! checks through per-context callbacks. The vendored library's defaults
+//! only log (to nowhere, inside an enclave), so `Secp256k1::set_error_handler`
+//! lets these be routed to Rust instead.
+//!
+//! After an illegal argument callback returns, the C function returns a
+//! failure, which the bindings turn into an `Err` or a panic on the Rust
+//! side of the call. `record` is a ready-made handler which keeps the last
+//! message in a static buffer, to be picked up with `last_error`.
+
+use std::cell::UnsafeCell;
+use std::slice;
+use std::str;
+use std::string::String;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use types::{c_char, c_void};
+
+use ffi;
+use super::Secp256k1;
+
+/// The kind of problem libsecp256k1 reported
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CallbackKind {
+    /// An argument violated the API contract, e.g. a null pointer
+    IllegalArgument,
+    /// An internal consistency check failed, e.g. an allocation failure
+    InternalError,
+}
+
+/// A handler for libsecp256k1 callbacks. It runs inside the C call that
+/// detected the problem, so it must not panic: unwinding through C is
+/// undefined behaviour (and aborts with recent compilers).
+pub type ErrorHandler = fn(CallbackKind, &str);
+
+/// Messages longer than this are truncated by `record`
+pub const MESSAGE_BUFFER_SIZE: usize = 128;
+
+struct Recorded {
+    kind: Option<CallbackKind>,
+    len: usize,
+    message: [u8; MESSAGE_BUFFER_SIZE],
+}
+
+/// The recorded message, guarded by a spin lock since handlers may run on
+/// any thread
+struct Slot {
+    lock: AtomicBool,
+    recorded: UnsafeCell<Recorded>,
+}
+
+unsafe impl Sync for Slot {}
+
+static SLOT: Slot = Slot {
+    lock: AtomicBool::new(false),
+    recorded: UnsafeCell::new(Recorded { kind: None, len: 0, message: [0; MESSAGE_BUFFER_SIZE] }),
+};
+
+/// Runs `f` on the recorded message while holding the lock
+fn with_recorded<T, F: FnOnce(&mut Recorded) -> T>(f: F) -> T {
+    while SLOT.lock.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {}
+    let ret = f(unsafe { &mut *SLOT.recorded.get() });
+    SLOT.lock.store(false, Ordering::Release);
+    ret
+}
+
+/// A handler which stores the message in a static buffer, replacing any
+/// earlier one, for `last_error` to return
+pub fn record(kind: CallbackKind, message: &str) {
+    // Truncate on a character boundary
+    let mut len = message.len().min(MESSAGE_BUFFER_SIZE);
+    while !message.is_char_boundary(len) {
+        len -= 1;
+    }
+    with_recorded(|recorded| {
+        recorded.kind = Some(kind);
+        recorded.len = len;
+        recorded.message[..len].copy_from_slice(&message.as_bytes()[..len]);
+    })
+}
+
+/// Returns and clears the message stored by `record`, if any
+pub fn last_error() -> Option<(CallbackKind, String)> {
+    with_recorded(|recorded| {
+        recorded.kind.take().map(|kind| {
+            let message = str::from_utf8(&recorded.message[..recorded.len]).unwrap_or("");
+            (kind, String::from(message))
+        })
+    })
+}
+
+unsafe fn dispatch(kind: CallbackKind, message: *const c_char, data: *mut c_void) {
+    let handler: ErrorHandler = ::std::mem::transmute(data);
+    let mut len = 0;
+    while *message.offset(len as isize) != 0 {
+        len += 1;
+    }
+    let bytes = slice::from_raw_parts(message as *const u8, len);
+    handler(kind, str::from_utf8(bytes).unwrap_or("<message is not UTF-8>"));
+}
+
+unsafe extern "C" fn illegal_callback(message: *const c_char, data: *mut c_void) {
+    dispatch(CallbackKind::IllegalArgument, message, data)
+}
+
+unsafe extern "C" fn error_callback(message: *const c_char, data: *mut c_void) {
+    dispatch(CallbackKind::InternalError, message, data)
+}
+
+impl<C> Secp256k1<C> {
+    /// Routes this context's illegal argument and internal error callbacks
+    /// to `handler`. Clones of the context inherit it.
+    pub fn set_error_handler(&mut self, handler: ErrorHandler) {
+        let data = handler as *const c_void;
+        unsafe {
+            ffi::secp256k1_context_set_illegal_callback(self.ctx, Some(illegal_callback), data);
+            ffi::secp256k1_context_set_error_callback(self.ctx, Some(error_callback), data);
+        }
+    }
+
+    /// Restores libsecp256k1's default callbacks
+    pub fn reset_error_handler(&mut self) {
+        unsafe {
+            ffi::secp256k1_context_set_illegal_callback(self.ctx, None, ::std::ptr::null());
+            ffi::secp256k1_context_set_error_callback(self.ctx, None, ::std::ptr::null());
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ffi;
+    use super::{record, last_error, CallbackKind, MESSAGE_BUFFER_SIZE};
+    use super::super::Secp256k1;
+
+    /// Passes a null input to `secp256k1_ec_pubkey_parse`, which libsecp256k1
+    /// reports as an illegal argument
+    fn misuse<C>(s: &Secp256k1<C>) -> i32 {
+        let mut pk = ffi::PublicKey::new();
+        unsafe { ffi::secp256k1_ec_pubkey_parse(s.ctx, &mut pk, ptr::null(), 33) }
+    }
+
+    #[test]
+    fn custom_handler() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        fn count(kind: CallbackKind, message: &str) {
+            assert_eq!(kind, CallbackKind::IllegalArgument);
+            assert_eq!(message, "input != NULL");
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let mut s = Secp256k1::new();
+        s.set_error_handler(count);
+        assert_eq!(misuse(&s), 0);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        // Clones keep the handler
+        assert_eq!(misuse(&s.clone()), 0);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+
+        s.reset_error_handler();
+        assert_eq!(misuse(&s), 0);
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn recording() {
+        let mut s = Secp256k1::new();
+        s.set_error_handler(record);
+        assert_eq!(misuse(&s), 0);
+        assert_eq!(last_error(), Some((CallbackKind::IllegalArgument, "input != NULL".to_string())));
+        assert_eq!(last_error(), None);
+
+        // Long messages are truncated without splitting characters
+        let long: String = ::std::iter::repeat("é").take(MESSAGE_BUFFER_SIZE).collect();
+        record(CallbackKind::InternalError, &long);
+        let (kind, message) = last_error().unwrap();
+        assert_eq!(kind, CallbackKind::InternalError);
+        assert_eq!(message, &long[..MESSAGE_BUFFER_SIZE]);
+    }
+}
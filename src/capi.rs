@@ -0,0 +1,187 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Flat C ABI
+//! `extern "C"` functions for trusted C and C++ code in the same enclave,
+//! e.g. the bodies of ECALLs declared in an EDL file, which can then use
+//! this crate without a Rust shim of their own:
+//!
+//! ```c
+//! int secp_sgx_pubkey_create(unsigned char *pubkey33, const unsigned char *seckey32);
+//! int secp_sgx_sign(unsigned char *sig64, const unsigned char *msg32, const unsigned char *seckey32);
+//! int secp_sgx_verify(const unsigned char *sig64, const unsigned char *msg32,
+//!                     const unsigned char *pubkey, size_t pubkey_len);
+//! ```
+//!
+//! Every buffer belongs to the caller and has the fixed size in its name,
+//! which matches `[in, size=N]`/`[out, size=N]` EDL attributes. Public keys
+//! are written compressed and read in either form (33 or 65 bytes), and
+//! signatures are compact `r || s`. As in libsecp256k1, each function
+//! returns 1 on success and 0 on failure, including for null pointers, and
+//! leaves its output untouched on failure. Signing and key derivation use
+//! the global context, and verification the shared verification-only one,
+//! so the first call pays for building the tables. A panic inside a call is
+//! caught and reported as failure rather than unwinding into C. Requires
+//! compilation with the "capi" feature.
+
+use std::{panic, slice};
+
+use constants;
+use key::{PublicKey, SecretKey};
+use types::{c_int, c_uchar, size_t};
+use {Message, Secp256k1, Signature, SECP256K1};
+
+/// Derives the compressed public key of `seckey32` into `pubkey33`. Fails
+/// if the secret key is not valid.
+///
+/// # Safety
+/// Non-null pointers must point to buffers of the sizes in their names.
+#[no_mangle]
+pub unsafe extern "C" fn secp_sgx_pubkey_create(pubkey33: *mut c_uchar, seckey32: *const c_uchar) -> c_int {
+    guard(move || {
+        if pubkey33.is_null() || seckey32.is_null() {
+            return 0;
+        }
+        let mut sk = match SecretKey::from_slice(slice::from_raw_parts(seckey32, constants::SECRET_KEY_SIZE)) {
+            Ok(sk) => sk,
+            Err(_) => return 0,
+        };
+        let pk = PublicKey::from_secret_key(SECP256K1, &sk);
+        sk.wipe();
+        slice::from_raw_parts_mut(pubkey33, constants::PUBLIC_KEY_SIZE).copy_from_slice(&pk.serialize());
+        1
+    })
+}
+
+/// Signs the 32-byte hash `msg32` with `seckey32`, writing the low-S
+/// compact signature to `sig64`. Fails if the secret key is not valid.
+///
+/// # Safety
+/// Non-null pointers must point to buffers of the sizes in their names.
+#[no_mangle]
+pub unsafe extern "C" fn secp_sgx_sign(sig64: *mut c_uchar, msg32: *const c_uchar, seckey32: *const c_uchar) -> c_int {
+    guard(move || {
+        if sig64.is_null() || msg32.is_null() || seckey32.is_null() {
+            return 0;
+        }
+        let msg = match Message::from_slice(slice::from_raw_parts(msg32, constants::MESSAGE_SIZE)) {
+            Ok(msg) => msg,
+            Err(_) => return 0,
+        };
+        let mut sk = match SecretKey::from_slice(slice::from_raw_parts(seckey32, constants::SECRET_KEY_SIZE)) {
+            Ok(sk) => sk,
+            Err(_) => return 0,
+        };
+        let sig = SECP256K1.sign(&msg, &sk);
+        sk.wipe();
+        slice::from_raw_parts_mut(sig64, constants::COMPACT_SIGNATURE_SIZE).copy_from_slice(&sig.compact_bytes());
+        1
+    })
+}
+
+/// Verifies the compact signature `sig64` on the 32-byte hash `msg32`
+/// against the `pubkey_len`-byte serialized public key `pubkey`. Returns 1
+/// only for a valid low-S signature; malformed inputs give 0.
+///
+/// # Safety
+/// Non-null pointers must point to buffers of the sizes in their names,
+/// `pubkey_len` bytes for `pubkey`.
+#[no_mangle]
+pub unsafe extern "C" fn secp_sgx_verify(sig64: *const c_uchar, msg32: *const c_uchar,
+                                         pubkey: *const c_uchar, pubkey_len: size_t) -> c_int {
+    guard(move || {
+        if sig64.is_null() || msg32.is_null() || pubkey.is_null() {
+            return 0;
+        }
+        let mut compact = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        compact.copy_from_slice(slice::from_raw_parts(sig64, constants::COMPACT_SIGNATURE_SIZE));
+        let sig = match Signature::from_compact_bytes(&compact) {
+            Ok(sig) => sig,
+            Err(_) => return 0,
+        };
+        let msg = match Message::from_slice(slice::from_raw_parts(msg32, constants::MESSAGE_SIZE)) {
+            Ok(msg) => msg,
+            Err(_) => return 0,
+        };
+        let pk = match PublicKey::from_slice(slice::from_raw_parts(pubkey, pubkey_len)) {
+            Ok(pk) => pk,
+            Err(_) => return 0,
+        };
+        Secp256k1::new_verify_only_static().verify(&msg, &sig, &pk).is_ok() as c_int
+    })
+}
+
+/// Runs the body of an exported function, turning a panic into failure
+/// since unwinding across the C boundary is undefined behaviour
+fn guard<F: FnOnce() -> c_int + panic::UnwindSafe>(body: F) -> c_int {
+    panic::catch_unwind(body).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::{secp_sgx_pubkey_create, secp_sgx_sign, secp_sgx_verify};
+    use {Message, PublicKey, SECP256K1, SecretKey};
+
+    #[test]
+    fn round_trip() {
+        let seckey = [0x42u8; 32];
+        let msg = [7u8; 32];
+        let (mut pubkey, mut sig) = ([0u8; 33], [0u8; 64]);
+        unsafe {
+            assert_eq!(secp_sgx_pubkey_create(pubkey.as_mut_ptr(), seckey.as_ptr()), 1);
+            assert_eq!(secp_sgx_sign(sig.as_mut_ptr(), msg.as_ptr(), seckey.as_ptr()), 1);
+            assert_eq!(secp_sgx_verify(sig.as_ptr(), msg.as_ptr(), pubkey.as_ptr(), 33), 1);
+        }
+
+        // The same as the Rust API
+        let sk = SecretKey::from_slice(&seckey).unwrap();
+        let pk = PublicKey::from_secret_key(SECP256K1, &sk);
+        assert_eq!(pubkey, pk.serialize());
+        let expected = SECP256K1.sign(&Message::from_slice(&msg).unwrap(), &sk);
+        assert_eq!(sig, expected.serialize_compact(SECP256K1));
+
+        // Uncompressed keys verify too, other messages don't
+        let uncompressed = pk.serialize_uncompressed();
+        let other = [8u8; 32];
+        unsafe {
+            assert_eq!(secp_sgx_verify(sig.as_ptr(), msg.as_ptr(), uncompressed.as_ptr(), 65), 1);
+            assert_eq!(secp_sgx_verify(sig.as_ptr(), other.as_ptr(), pubkey.as_ptr(), 33), 0);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let (zero, msg) = ([0u8; 32], [7u8; 32]);
+        let (mut pubkey, mut sig) = ([0u8; 33], [0u8; 64]);
+        unsafe {
+            // Invalid inputs fail without touching the output
+            assert_eq!(secp_sgx_pubkey_create(pubkey.as_mut_ptr(), zero.as_ptr()), 0);
+            assert_eq!(secp_sgx_sign(sig.as_mut_ptr(), msg.as_ptr(), zero.as_ptr()), 0);
+            assert_eq!(pubkey, [0u8; 33]);
+            assert_eq!(sig, [0u8; 64]);
+            assert_eq!(secp_sgx_pubkey_create(ptr::null_mut(), msg.as_ptr()), 0);
+            assert_eq!(secp_sgx_sign(sig.as_mut_ptr(), ptr::null(), msg.as_ptr()), 0);
+
+            let seckey = [1u8; 32];
+            assert_eq!(secp_sgx_pubkey_create(pubkey.as_mut_ptr(), seckey.as_ptr()), 1);
+            assert_eq!(secp_sgx_sign(sig.as_mut_ptr(), msg.as_ptr(), seckey.as_ptr()), 1);
+            assert_eq!(secp_sgx_verify(sig.as_ptr(), msg.as_ptr(), pubkey.as_ptr(), 32), 0);
+            assert_eq!(secp_sgx_verify(sig.as_ptr(), msg.as_ptr(), ptr::null(), 33), 0);
+            let high = [0xffu8; 64];
+            assert_eq!(secp_sgx_verify(high.as_ptr(), msg.as_ptr(), pubkey.as_ptr(), 33), 0);
+        }
+    }
+}
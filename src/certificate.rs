@@ -0,0 +1,284 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Key certificates
+//! Compact certificates by which a long-lived enclave identity key vouches
+//! for short-lived operational keys. The relying party attests the identity
+//! key once (see `attestation::verify_key_binding`); after that, each new
+//! operational key only needs a certificate check, not another round of
+//! remote attestation.
+//!
+//! A certificate names the subject key, its validity period, an
+//! application-defined policy and the identity of the enclave holding the
+//! key. It is signed with ECDSA over the SHA-256 of a domain tag and the
+//! encoded body, and encodes to `CERTIFICATE_SIZE` bytes. Requires
+//! compilation with the "hashes" feature.
+
+use constants;
+use hashes::Sha256;
+use key::{PublicKey, SecretKey};
+use vault::{KeyHandle, Vault};
+use super::Error::{self, CertificateExpired, InvalidCertificate};
+use {Message, Secp256k1, Signature, Signing, Verification};
+
+/// Format version of the encoding
+const VERSION: u8 = 1;
+
+/// Prefixed to the body before hashing, so that certificate signatures
+/// can't be confused with signatures over anything else
+const SIGNING_TAG: &'static [u8] = b"rust-secp256k1-sgx key certificate";
+
+/// The size (in bytes) of an encoded certificate body
+pub const BODY_SIZE: usize = 1 + constants::PUBLIC_KEY_SIZE + 8 + 8 + 4 + 32 + 32 + 2 + 2;
+
+/// The size (in bytes) of an encoded certificate
+pub const CERTIFICATE_SIZE: usize = BODY_SIZE + constants::COMPACT_SIGNATURE_SIZE;
+
+/// What a certificate says about its subject key
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CertificateBody {
+    /// The certified key
+    pub subject: PublicKey,
+    /// Start of the validity period, inclusive (seconds since the Unix epoch)
+    pub not_before: u64,
+    /// End of the validity period, exclusive (seconds since the Unix epoch)
+    pub not_after: u64,
+    /// Application-defined policy bits, e.g. what the key may be used for
+    pub policy: u32,
+    /// Measurement of the enclave holding the key
+    pub mr_enclave: [u8; 32],
+    /// Hash of the signing key of the enclave holding the key
+    pub mr_signer: [u8; 32],
+    /// Product ID of the enclave holding the key
+    pub isv_prod_id: u16,
+    /// Security version of the enclave holding the key
+    pub isv_svn: u16,
+}
+
+/// A certificate body signed by the issuer's key
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct KeyCertificate {
+    body: CertificateBody,
+    signature: Signature,
+}
+
+// Body layout: version (1 byte), subject (33), not_before (8), not_after
+// (8), policy (4), mr_enclave (32), mr_signer (32), isv_prod_id (2) and
+// isv_svn (2). Integers are big-endian.
+fn write_be(out: &mut [u8], n: u64) {
+    let len = out.len();
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = (n >> (8 * (len - 1 - i))) as u8;
+    }
+}
+
+fn read_be(data: &[u8]) -> u64 {
+    data.iter().fold(0, |n, &byte| n << 8 | byte as u64)
+}
+
+impl CertificateBody {
+    /// Encodes the body
+    pub fn serialize(&self) -> [u8; BODY_SIZE] {
+        let mut out = [0u8; BODY_SIZE];
+        out[0] = VERSION;
+        out[1..34].copy_from_slice(&self.subject.serialize());
+        write_be(&mut out[34..42], self.not_before);
+        write_be(&mut out[42..50], self.not_after);
+        write_be(&mut out[50..54], self.policy as u64);
+        out[54..86].copy_from_slice(&self.mr_enclave);
+        out[86..118].copy_from_slice(&self.mr_signer);
+        write_be(&mut out[118..120], self.isv_prod_id as u64);
+        write_be(&mut out[120..122], self.isv_svn as u64);
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidCertificate`
+    /// for any other data.
+    pub fn from_slice(data: &[u8]) -> Result<CertificateBody, Error> {
+        if data.len() != BODY_SIZE || data[0] != VERSION {
+            return Err(InvalidCertificate);
+        }
+        let mut mr_enclave = [0; 32];
+        mr_enclave.copy_from_slice(&data[54..86]);
+        let mut mr_signer = [0; 32];
+        mr_signer.copy_from_slice(&data[86..118]);
+        Ok(CertificateBody {
+            subject: PublicKey::from_slice(&data[1..34]).map_err(|_| InvalidCertificate)?,
+            not_before: read_be(&data[34..42]),
+            not_after: read_be(&data[42..50]),
+            policy: read_be(&data[50..54]) as u32,
+            mr_enclave,
+            mr_signer,
+            isv_prod_id: read_be(&data[118..120]) as u16,
+            isv_svn: read_be(&data[120..122]) as u16,
+        })
+    }
+
+    /// The message the issuer signs
+    pub fn signing_message(&self) -> Message {
+        let mut engine = Sha256::new();
+        engine.input(SIGNING_TAG);
+        engine.input(&self.serialize());
+        Message::from(engine.result())
+    }
+
+    /// Signs the body with the issuer's secret key
+    pub fn sign<C: Signing>(&self, secp: &Secp256k1<C>, issuer: &SecretKey) -> KeyCertificate {
+        KeyCertificate { body: *self, signature: secp.sign(&self.signing_message(), issuer) }
+    }
+
+    /// Whether `time` lies within the validity period
+    #[inline]
+    pub fn is_valid_at(&self, time: u64) -> bool {
+        self.not_before <= time && time < self.not_after
+    }
+}
+
+impl KeyCertificate {
+    /// The signed body. Only trust it after `verify`.
+    #[inline]
+    pub fn body(&self) -> &CertificateBody {
+        &self.body
+    }
+
+    /// The issuer's signature
+    #[inline]
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Checks that `issuer` signed the certificate and that `time` lies in
+    /// its validity period, and returns the body. Fails with
+    /// `IncorrectSignature` for a wrong signature and with
+    /// `CertificateExpired` outside the validity period.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, issuer: &PublicKey, time: u64)
+                                   -> Result<&CertificateBody, Error> {
+        secp.verify(&self.body.signing_message(), &self.signature, issuer)?;
+        if !self.body.is_valid_at(time) {
+            return Err(CertificateExpired);
+        }
+        Ok(&self.body)
+    }
+
+    /// Encodes the certificate: the body followed by the compact signature
+    pub fn serialize<C>(&self, secp: &Secp256k1<C>) -> [u8; CERTIFICATE_SIZE] {
+        let mut out = [0u8; CERTIFICATE_SIZE];
+        out[..BODY_SIZE].copy_from_slice(&self.body.serialize());
+        out[BODY_SIZE..].copy_from_slice(&self.signature.serialize_compact(secp));
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidCertificate`
+    /// for any other data; the signature is only checked by `verify`.
+    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<KeyCertificate, Error> {
+        if data.len() != CERTIFICATE_SIZE {
+            return Err(InvalidCertificate);
+        }
+        Ok(KeyCertificate {
+            body: CertificateBody::from_slice(&data[..BODY_SIZE])?,
+            signature: Signature::from_compact(secp, &data[BODY_SIZE..]).map_err(|_| InvalidCertificate)?,
+        })
+    }
+}
+
+impl Vault {
+    /// Issues a certificate for `body` with the identity key for `issuer`.
+    /// The signature counts as a use of the issuer key. Fails like
+    /// `Vault::sign`.
+    pub fn certify<C: Signing>(&mut self, secp: &Secp256k1<C>, issuer: KeyHandle, body: &CertificateBody)
+                               -> Result<KeyCertificate, Error> {
+        let signature = self.sign(secp, issuer, &body.signing_message())?;
+        Ok(KeyCertificate { body: *body, signature })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{CertificateBody, KeyCertificate, BODY_SIZE, CERTIFICATE_SIZE};
+    use super::super::Secp256k1;
+    use super::super::vault::Vault;
+    use super::super::Error::{CertificateExpired, IncorrectSignature, InvalidCertificate};
+
+    fn body(s: &Secp256k1<::All>) -> CertificateBody {
+        let (_, subject) = s.generate_keypair(&mut thread_rng());
+        CertificateBody {
+            subject,
+            not_before: 1_000,
+            not_after: 2_000,
+            policy: 0x0000_0105,
+            mr_enclave: [0xe1; 32],
+            mr_signer: [0x51; 32],
+            isv_prod_id: 3,
+            isv_svn: 0x0102,
+        }
+    }
+
+    #[test]
+    fn issue_and_verify() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let identity = vault.generate(&s, &mut thread_rng());
+        let identity_pk = vault.public_key(identity).unwrap();
+        let body = body(&s);
+
+        let cert = vault.certify(&s, identity, &body).unwrap();
+        assert_eq!(vault.uses(identity), Ok(1));
+        assert_eq!(cert.verify(&s, &identity_pk, 1_000), Ok(&body));
+        assert_eq!(cert.verify(&s, &identity_pk, 1_999), Ok(&body));
+        assert_eq!(cert.verify(&s, &identity_pk, 999), Err(CertificateExpired));
+        assert_eq!(cert.verify(&s, &identity_pk, 2_000), Err(CertificateExpired));
+        // The issuer must match
+        assert_eq!(cert.verify(&s, &body.subject, 1_500), Err(IncorrectSignature));
+
+        // Signing directly with a secret key gives an equally valid certificate
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(body.sign(&s, &sk).verify(&s, &pk, 1_500), Ok(&body));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let body = body(&s);
+        let cert = body.sign(&s, &sk);
+
+        let encoded = cert.serialize(&s);
+        assert_eq!(encoded.len(), CERTIFICATE_SIZE);
+        assert_eq!(CERTIFICATE_SIZE, 186);
+        assert_eq!(encoded[..BODY_SIZE], body.serialize()[..]);
+        // Integers are big-endian
+        assert_eq!(encoded[1 + 33..1 + 33 + 8], [0, 0, 0, 0, 0, 0, 0x03, 0xe8]);
+        assert_eq!(encoded[BODY_SIZE - 2..BODY_SIZE], [0x01, 0x02]);
+
+        let decoded = KeyCertificate::from_slice(&s, &encoded).unwrap();
+        assert_eq!(decoded, cert);
+        assert_eq!(decoded.verify(&s, &pk, 1_500), Ok(&body));
+
+        // Any change to the body breaks the signature
+        let mut tampered = encoded;
+        tampered[1 + 33 + 8 + 7] ^= 1;
+        let tampered = KeyCertificate::from_slice(&s, &tampered).unwrap();
+        assert_eq!(tampered.body().not_after, 2_001);
+        assert_eq!(tampered.verify(&s, &pk, 1_500), Err(IncorrectSignature));
+
+        assert_eq!(KeyCertificate::from_slice(&s, &encoded[1..]), Err(InvalidCertificate));
+        let mut bad_version = encoded;
+        bad_version[0] = 2;
+        assert_eq!(KeyCertificate::from_slice(&s, &bad_version), Err(InvalidCertificate));
+        let mut bad_key = encoded;
+        bad_key[1] = 0x05;
+        assert_eq!(KeyCertificate::from_slice(&s, &bad_key), Err(InvalidCertificate));
+    }
+}
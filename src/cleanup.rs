@@ -0,0 +1,155 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Cleanup
+//! Wiping of temporary buffers which held secret data.
+//!
+//! Rust-side temporaries are always wiped with volatile writes. The C
+//! library leaves copies of the secret key and nonce in the stack frames of
+//! signing, ECDH and key derivation calls, which Rust cannot reach directly;
+//! with the "secure-cleanup" feature, `scrub_stack` is called after those
+//! calls to overwrite the stack area they used.
+
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+/// Bytes of stack below the caller which `scrub_stack` overwrites. This is
+/// comfortably more than the deepest libsecp256k1 call path uses.
+#[cfg(any(test, feature = "secure-cleanup"))]
+pub const STACK_SCRUB_SIZE: usize = 8192;
+
+/// Overwrites `data` with zeros in a way the compiler will not elide
+#[inline]
+pub fn wipe(data: &mut [u8]) {
+    for byte in data.iter_mut() {
+        unsafe { ptr::write_volatile(byte, 0); }
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrites the `STACK_SCRUB_SIZE` bytes of stack below the caller's
+/// frame, where the frames of a just-returned FFI call lived
+#[cfg(feature = "secure-cleanup")]
+#[inline(never)]
+pub fn scrub_stack() {
+    let mut area = [0u8; STACK_SCRUB_SIZE];
+    wipe(&mut area);
+}
+
+/// Without the "secure-cleanup" feature, stack scrubbing is a no-op
+#[cfg(not(feature = "secure-cleanup"))]
+#[inline(always)]
+pub fn scrub_stack() {}
+
+#[cfg(test)]
+mod tests {
+    use super::wipe;
+
+    #[test]
+    fn wipe_zeroes() {
+        let mut data = [0xa5; 40];
+        wipe(&mut data);
+        assert_eq!(data[..], [0; 40][..]);
+    }
+}
+
+// Stack scanning relies on frame layout, which is only predictable without
+// optimizations, so these tests are limited to debug builds
+#[cfg(all(test, debug_assertions, not(feature = "verify-only")))]
+mod stack_tests {
+    use std::ptr;
+
+    use super::{wipe, STACK_SCRUB_SIZE};
+    use ecdh::SharedSecret;
+    use key::{SecretKey, PublicKey};
+    use {Message, Secp256k1};
+
+    /// A key whose bytes are unlikely to occur on the stack by chance
+    fn key() -> SecretKey {
+        let mut bytes = [0; 32];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = (i as u8).wrapping_mul(37).wrapping_add(0x5b);
+        }
+        SecretKey::from_slice(&bytes).unwrap()
+    }
+
+    /// Runs `f` a kilobyte further down the stack than the caller, so that
+    /// its frames end up in the area `dead_stack_contains` scans
+    #[inline(never)]
+    fn deeper<F: FnOnce()>(f: F) {
+        let mut pad = [0u8; 1024];
+        wipe(&mut pad);
+        f()
+    }
+
+    /// Scans the dead stack area left by calls made through `deeper` for
+    /// `needle`. This reads memory Rust considers unallocated, which is fine
+    /// for a test but must never be done elsewhere.
+    #[inline(never)]
+    fn dead_stack_contains(needle: &[u8]) -> bool {
+        let marker = 0u8;
+        // Skip this function's own frame
+        let top = &marker as *const u8 as usize - 512;
+        (top - STACK_SCRUB_SIZE..top).any(|addr| {
+            needle.iter().enumerate().all(|(i, &b)| unsafe { ptr::read_volatile((addr + i) as *const u8) } == b)
+        })
+    }
+
+    /// Leaves a copy of `data` in a dead stack frame, like a careless FFI
+    /// call would
+    #[inline(never)]
+    fn leak(data: &[u8]) {
+        let mut copy = [0u8; 32];
+        copy.copy_from_slice(data);
+        unsafe { ptr::read_volatile(&copy); }
+    }
+
+    #[test]
+    fn stack_scanner() {
+        let sk = key();
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+        deeper(|| leak(&sk.secret_bytes()[..]));
+        assert!(dead_stack_contains(&sk.secret_bytes()[..]));
+    }
+
+    #[cfg(feature = "secure-cleanup")]
+    #[test]
+    fn scrub_clears_leftovers() {
+        #[inline(never)]
+        fn leak_and_scrub(data: &[u8]) {
+            leak(data);
+            super::scrub_stack();
+        }
+
+        let sk = key();
+        deeper(|| leak_and_scrub(&sk.secret_bytes()[..]));
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+    }
+
+    #[test]
+    fn secret_operations_leave_no_copies() {
+        let s = Secp256k1::new();
+        let sk = key();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&s, &sk);
+        deeper(|| { PublicKey::from_secret_key(&s, &sk); });
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+        deeper(|| { s.sign(&msg, &sk); });
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+        deeper(|| { s.sign_recoverable(&msg, &sk); });
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+        deeper(|| { SharedSecret::new(&s, &pk, &sk); });
+        assert!(!dead_stack_contains(&sk.secret_bytes()[..]));
+    }
+}
@@ -0,0 +1,184 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Nonce commitments
+//! Commit-and-reveal for the nonce points of interactive signing. If a
+//! signer could see the other signers' nonces before choosing its own, it
+//! could pick it to cancel theirs, and with many concurrent sessions it can
+//! forge signatures through Wagner's generalized birthday attack. Having
+//! every signer commit to its nonce point first, and only accept revealed
+//! nonces which match their commitments, takes that choice away.
+//!
+//! A commitment is the tagged hash (`hashes::NONCE_COMMITMENT_TAG`) of a
+//! context and the compressed nonce point. The context should name the
+//! session and the signer, e.g. a session ID and signer index, so that a
+//! commitment can't be replayed into another session or by another signer;
+//! it can be empty when the protocol binds them otherwise. Commitments
+//! cross ECALLs as their 32 bytes, with serde and `sgx_serialize` support
+//! behind the "serde" and "sgx-serialize" features. Requires compilation
+//! with the "hashes" feature.
+
+use std::fmt;
+
+use hashes::{TaggedHasher, NONCE_COMMITMENT_TAG};
+use hex;
+use key::PublicKey;
+use super::Error::{self, InvalidNonceCommitment, NonceCommitmentMismatch};
+
+/// The size (in bytes) of a serialized `NonceCommitment`
+pub const NONCE_COMMITMENT_SIZE: usize = 32;
+
+/// A binding commitment to a nonce point
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NonceCommitment([u8; NONCE_COMMITMENT_SIZE]);
+
+impl NonceCommitment {
+    /// Commits to `nonce` with an empty context
+    #[inline]
+    pub fn commit(nonce: &PublicKey) -> NonceCommitment {
+        NonceCommitment::commit_with_context(nonce, &[])
+    }
+
+    /// Commits to `nonce` in `context`, which must be the same when the
+    /// nonce is revealed
+    pub fn commit_with_context(nonce: &PublicKey, context: &[u8]) -> NonceCommitment {
+        // The nonce point has a fixed size, so the context needs no length
+        NonceCommitment(TaggedHasher::new(NONCE_COMMITMENT_TAG).chain(context).chain(&nonce.serialize()).result())
+    }
+
+    /// Checks that `nonce` is the point committed to with an empty context.
+    /// Fails with `NonceCommitmentMismatch` otherwise.
+    #[inline]
+    pub fn verify(&self, nonce: &PublicKey) -> Result<(), Error> {
+        self.verify_with_context(nonce, &[])
+    }
+
+    /// Checks that `nonce` is the point committed to in `context`. Fails
+    /// with `NonceCommitmentMismatch` otherwise.
+    pub fn verify_with_context(&self, nonce: &PublicKey, context: &[u8]) -> Result<(), Error> {
+        let expected = NonceCommitment::commit_with_context(nonce, context);
+        if ::constant_time_eq(&self.0, &expected.0) { Ok(()) } else { Err(NonceCommitmentMismatch) }
+    }
+
+    /// Checks every revealed nonce against the commitment at the same
+    /// position, each in its own context. Fails with
+    /// `NonceCommitmentMismatch` if any does not match, or if the numbers
+    /// of commitments, nonces and contexts differ.
+    pub fn verify_all<T: AsRef<[u8]>>(commitments: &[NonceCommitment], nonces: &[PublicKey], contexts: &[T])
+                                      -> Result<(), Error> {
+        if commitments.len() != nonces.len() || commitments.len() != contexts.len() {
+            return Err(NonceCommitmentMismatch);
+        }
+        // Check them all, so that the time taken doesn't tell which failed
+        let mut ok = true;
+        for ((commitment, nonce), context) in commitments.iter().zip(nonces.iter()).zip(contexts.iter()) {
+            ok &= commitment.verify_with_context(nonce, context.as_ref()).is_ok();
+        }
+        if ok { Ok(()) } else { Err(NonceCommitmentMismatch) }
+    }
+
+    /// The commitment as bytes
+    #[inline]
+    pub fn serialize(&self) -> [u8; NONCE_COMMITMENT_SIZE] {
+        self.0
+    }
+
+    /// Parses a commitment. Fails with `InvalidNonceCommitment` if `data`
+    /// is not 32 bytes.
+    pub fn from_slice(data: &[u8]) -> Result<NonceCommitment, Error> {
+        if data.len() != NONCE_COMMITMENT_SIZE {
+            return Err(InvalidNonceCommitment);
+        }
+        let mut ret = [0u8; NONCE_COMMITMENT_SIZE];
+        ret.copy_from_slice(data);
+        Ok(NonceCommitment(ret))
+    }
+}
+
+impl From<[u8; NONCE_COMMITMENT_SIZE]> for NonceCommitment {
+    #[inline]
+    fn from(bytes: [u8; NONCE_COMMITMENT_SIZE]) -> NonceCommitment {
+        NonceCommitment(bytes)
+    }
+}
+
+impl fmt::Debug for NonceCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("NonceCommitment(")?;
+        hex::format(&self.0, f)?;
+        f.write_str(")")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for NonceCommitment {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for NonceCommitment {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<NonceCommitment, D::Error> {
+        use ::serde::de::Error;
+
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        NonceCommitment::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::NonceCommitment;
+    use super::super::Error::{InvalidNonceCommitment, NonceCommitmentMismatch};
+    use Secp256k1;
+
+    #[test]
+    fn commit_and_verify() {
+        let s = Secp256k1::new();
+        let (_, r) = s.generate_keypair(&mut thread_rng());
+        let (_, other) = s.generate_keypair(&mut thread_rng());
+
+        let c = NonceCommitment::commit(&r);
+        assert_eq!(c.verify(&r), Ok(()));
+        assert_eq!(c.verify(&other), Err(NonceCommitmentMismatch));
+        assert_eq!(c.verify_with_context(&r, b"session 1"), Err(NonceCommitmentMismatch));
+
+        let c1 = NonceCommitment::commit_with_context(&r, b"session 1");
+        assert!(c1 != c);
+        assert_eq!(c1.verify_with_context(&r, b"session 1"), Ok(()));
+        assert_eq!(c1.verify_with_context(&r, b"session 2"), Err(NonceCommitmentMismatch));
+
+        let c2 = NonceCommitment::commit_with_context(&other, b"session 2");
+        assert_eq!(NonceCommitment::verify_all(&[c1, c2], &[r, other], &[b"session 1", b"session 2"]), Ok(()));
+        assert_eq!(NonceCommitment::verify_all(&[c1, c2], &[other, r], &[b"session 1", b"session 2"]),
+                   Err(NonceCommitmentMismatch));
+        assert_eq!(NonceCommitment::verify_all(&[c1, c2], &[r], &[b"session 1", b"session 2"]),
+                   Err(NonceCommitmentMismatch));
+
+        assert_eq!(NonceCommitment::from_slice(&c1.serialize()), Ok(c1));
+        assert_eq!(NonceCommitment::from(c1.serialize()), c1);
+        assert_eq!(NonceCommitment::from_slice(&[0; 31]), Err(InvalidNonceCommitment));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_test::{Token, assert_tokens};
+        static BYTES: [u8; 32] = [0x5a; 32];
+
+        assert_tokens(&NonceCommitment::from(BYTES), &[Token::BorrowedBytes(&BYTES[..])]);
+    }
+}
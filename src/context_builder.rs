@@ -0,0 +1,240 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Context builder
+//! `Secp256k1::builder` composes the options for creating a context, so
+//! that each new option doesn't need its own constructor for every
+//! capability type:
+//!
+//! ```ignore
+//! static mut BUF: [u8; 2 << 20] = [0; 2 << 20];
+//! let secp = Secp256k1::builder()
+//!     .signing()
+//!     .verification()
+//!     .randomize_with(&mut rng)
+//!     .preallocated(unsafe { &mut BUF })
+//!     .build()?;
+//! ```
+//!
+//! The capabilities chosen decide the type of the context, so a builder
+//! which asked for `signing` and `verification` builds a `Secp256k1<All>`.
+
+use std::marker::PhantomData;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use types::{c_uint, c_void};
+
+use cleanup::wipe;
+use ffi;
+#[cfg(any(test, feature = "rand"))]
+use rng;
+use super::{None, Secp256k1, VerifyOnly};
+#[cfg(not(feature = "verify-only"))]
+use super::{All, SignOnly};
+use super::Error::{self, NotEnoughMemory};
+
+/// Options for creating a context with the capabilities `C`, from
+/// `Secp256k1::builder`
+pub struct ContextBuilder<C> {
+    flags: c_uint,
+    seed: Option<[u8; 32]>,
+    buffer: Option<&'static mut [u8]>,
+    phantom: PhantomData<C>,
+}
+
+impl Secp256k1<None> {
+    /// Starts building a context, with no capabilities, no randomization
+    /// and heap-allocated tables
+    pub fn builder() -> ContextBuilder<None> {
+        ContextBuilder { flags: ffi::SECP256K1_START_NONE, seed: Option::None, buffer: Option::None,
+                         phantom: PhantomData }
+    }
+}
+
+impl<C> ContextBuilder<C> {
+    /// Adds the capability flag `flag`, making a context of type `D`
+    fn with_flag<D>(self, flag: c_uint) -> ContextBuilder<D> {
+        ContextBuilder { flags: self.flags | flag, seed: self.seed, buffer: self.buffer, phantom: PhantomData }
+    }
+
+    /// Randomizes the context with a seed drawn from `rng` now, as
+    /// `Secp256k1::randomize` does. Requires compilation with the "rand"
+    /// feature.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn randomize_with<R: RngCore + CryptoRng>(mut self, rng: &mut R) -> ContextBuilder<C> {
+        let mut seed = [0; 32];
+        rng.fill_bytes(&mut seed);
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Randomizes the context with the enclave's entropy, from
+    /// `rng::thread_rng`, or with the "no-ocall" feature from the installed
+    /// `rng::EntropySource`. Requires compilation with the "rand" feature.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn randomize(self) -> ContextBuilder<C> {
+        self.randomize_with(&mut rng::ambient())
+    }
+
+    /// Places the context and its precomputed tables in `buf` instead of the
+    /// heap, e.g. in a static buffer outside the enclave's heap budget.
+    /// Building the tables still needs temporary heap memory. The buffer
+    /// must be at least `preallocated_size` bytes, and is never freed.
+    pub fn preallocated(mut self, buf: &'static mut [u8]) -> ContextBuilder<C> {
+        self.buffer = Some(buf);
+        self
+    }
+
+    /// The size of the buffer `preallocated` needs for the capabilities
+    /// chosen so far
+    pub fn preallocated_size(&self) -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(self.flags) }
+    }
+
+    /// Creates the context. Fails with `NotEnoughMemory` if the buffer given
+    /// to `preallocated` is smaller than `preallocated_size`.
+    pub fn build(self) -> Result<Secp256k1<C>, Error> {
+        let ContextBuilder { flags, seed, buffer, .. } = self;
+        let mut ret = match buffer {
+            Some(buf) => {
+                debug_log!(debug, "creating context with flags {:#x} in a {}-byte buffer", flags, buf.len());
+                let ctx = unsafe {
+                    ffi::secp256k1_context_preallocated_create(buf.as_mut_ptr() as *mut c_void, buf.len(), flags)
+                };
+                if ctx.is_null() {
+                    return Err(NotEnoughMemory);
+                }
+                Secp256k1 { ctx, phantom: PhantomData }
+            }
+            Option::None => Secp256k1::create(flags),
+        };
+        if let Some(mut seed) = seed {
+            ret.randomize_with_seed(&seed);
+            wipe(&mut seed);
+        }
+        Ok(ret)
+    }
+}
+
+impl ContextBuilder<None> {
+    /// Adds the signing capability
+    #[cfg(not(feature = "verify-only"))]
+    pub fn signing(self) -> ContextBuilder<SignOnly> {
+        self.with_flag(ffi::SECP256K1_START_SIGN)
+    }
+
+    /// Adds the verification capability
+    pub fn verification(self) -> ContextBuilder<VerifyOnly> {
+        self.with_flag(ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl ContextBuilder<SignOnly> {
+    /// Adds the verification capability
+    pub fn verification(self) -> ContextBuilder<All> {
+        self.with_flag(ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl ContextBuilder<VerifyOnly> {
+    /// Adds the signing capability
+    pub fn signing(self) -> ContextBuilder<All> {
+        self.with_flag(ffi::SECP256K1_START_SIGN)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::boxed::Box;
+    use std::vec;
+
+    use key::{PublicKey, SecretKey};
+    use rng::thread_rng;
+    use super::super::{All, Message, Secp256k1, SignOnly, VerifyOnly};
+    use super::super::Error::NotEnoughMemory;
+
+    fn leak(len: usize) -> &'static mut [u8] {
+        Box::leak(vec![0u8; len].into_boxed_slice())
+    }
+
+    #[test]
+    fn capabilities() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+
+        let all: Secp256k1<All> = Secp256k1::builder().signing().verification().build().unwrap();
+        let pk = PublicKey::from_secret_key(&all, &sk);
+        let sig = all.sign(&msg, &sk);
+        assert_eq!(all.verify(&msg, &sig, &pk), Ok(()));
+        let all: Secp256k1<All> = Secp256k1::builder().verification().signing().build().unwrap();
+        assert_eq!(all.sign(&msg, &sk), sig);
+
+        let sign: Secp256k1<SignOnly> = Secp256k1::builder().signing().build().unwrap();
+        assert_eq!(sign.sign(&msg, &sk), sig);
+        let verify: Secp256k1<VerifyOnly> = Secp256k1::builder().verification().build().unwrap();
+        assert_eq!(verify.verify(&msg, &sig, &pk), Ok(()));
+        Secp256k1::builder().build().unwrap();
+    }
+
+    #[test]
+    fn randomized() {
+        #[cfg(feature = "no-ocall")]
+        ::rng::install_test_source();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let plain = Secp256k1::new();
+        let randomized = Secp256k1::builder().signing().verification().randomize_with(&mut thread_rng())
+            .build().unwrap();
+        // Randomization only blinds the computation
+        assert_eq!(randomized.sign(&msg, &sk), plain.sign(&msg, &sk));
+        assert!(randomized.self_test().passed());
+        let randomized = Secp256k1::builder().signing().randomize().build().unwrap();
+        assert_eq!(randomized.sign(&msg, &sk), plain.sign(&msg, &sk));
+    }
+
+    #[test]
+    fn preallocated() {
+        #[cfg(feature = "no-ocall")]
+        ::rng::install_test_source();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let plain = Secp256k1::new();
+
+        let builder = Secp256k1::builder().signing().verification();
+        let size = builder.preallocated_size();
+        assert!(size >= Secp256k1::<All>::creation_cost_hint().table_bytes());
+        assert!(Secp256k1::builder().signing().preallocated_size() < size);
+
+        // Any alignment will do
+        let buf = leak(size + 1);
+        let secp = builder.randomize().preallocated(&mut buf[1..]).build().unwrap();
+        let pk = PublicKey::from_secret_key(&secp, &sk);
+        let sig = secp.sign(&msg, &sk);
+        assert_eq!(sig, plain.sign(&msg, &sk));
+        assert_eq!(secp.verify(&msg, &sig, &pk), Ok(()));
+        assert!(secp.self_test().passed());
+        // Clones live on the heap and outlive nothing
+        let cloned = secp.clone();
+        drop(secp);
+        assert_eq!(cloned.sign(&msg, &sk), sig);
+
+        let short = leak(Secp256k1::preallocate_verification_size() - 1);
+        let small = Secp256k1::builder().verification().preallocated(short).build();
+        assert_eq!(small.err(), Some(NotEnoughMemory));
+    }
+}
@@ -0,0 +1,158 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Context export
+//! Serialization of a context's precomputed tables and blinding state, so
+//! that short-lived enclaves can skip building them on every launch.
+//!
+//! The format is versioned and carries a SHA-256 checksum, and is tied to
+//! the libsecp256k1 build which produced it. It holds the blinding state,
+//! so it must never leave the enclave unencrypted; seal it with
+//! `sgx_tseal`, for example:
+//!
+//! ```ignore
+//! let blob = secp.export_context();
+//! let sealed = SgxSealedData::<[u8]>::seal_data(&[], &blob)?;
+//! // ... on the next launch, after unsealing:
+//! let secp = Secp256k1::import_context(unsealed.get_decrypt_txt())?;
+//! ```
+
+use std::marker::PhantomData;
+use std::vec::Vec;
+
+use types::c_uint;
+
+use cleanup;
+use ffi;
+use super::{Secp256k1, VerifyOnly};
+#[cfg(not(feature = "verify-only"))]
+use super::{All, SignOnly};
+use super::Error::{self, InvalidContext};
+
+impl<C> Secp256k1<C> {
+    /// Serializes the context, including its precomputed tables and its
+    /// blinding state, for `import_context`. The result is around 1.5 MiB
+    /// for a context with all capabilities and must be kept confidential.
+    pub fn export_context(&self) -> Vec<u8> {
+        unsafe {
+            let len = ffi::secp256k1_context_export_size(self.ctx);
+            let mut ret = vec![0; len];
+            let err = ffi::secp256k1_context_export(self.ctx, ret.as_mut_ptr(), len);
+            // Only fails for a buffer of another size than the context's
+            assert!(err == 1);
+            ret
+        }
+    }
+
+    /// Recreates a context with the capabilities in `flags` from `data`
+    fn import(data: &[u8], flags: c_uint) -> Result<Secp256k1<C>, Error> {
+        let ctx = unsafe { ffi::secp256k1_context_import(data.as_ptr(), data.len(), flags) };
+        // The import copies the blinding state through the stack
+        cleanup::scrub_stack();
+        if ctx.is_null() {
+            Err(InvalidContext)
+        } else {
+            Ok(Secp256k1 { ctx, phantom: PhantomData })
+        }
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl Secp256k1<All> {
+    /// Recreates a context with all capabilities from the output of
+    /// `export_context`. Fails if `data` is corrupted, was produced by a
+    /// different build, or lacks signing or verification tables.
+    pub fn import_context(data: &[u8]) -> Result<Secp256k1<All>, Error> {
+        Secp256k1::import(data, ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl Secp256k1<SignOnly> {
+    /// Recreates a signing context from the output of `export_context`.
+    /// Fails if `data` is corrupted, was produced by a different build, or
+    /// lacks signing tables.
+    pub fn import_signing_only(data: &[u8]) -> Result<Secp256k1<SignOnly>, Error> {
+        Secp256k1::import(data, ffi::SECP256K1_START_SIGN)
+    }
+}
+
+impl Secp256k1<VerifyOnly> {
+    /// Recreates a verification context from the output of
+    /// `export_context`. Fails if `data` is corrupted, was produced by a
+    /// different build, or lacks verification tables.
+    pub fn import_verification_only(data: &[u8]) -> Result<Secp256k1<VerifyOnly>, Error> {
+        Secp256k1::import(data, ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use key::{SecretKey, PublicKey};
+    use rng::thread_rng;
+    use super::super::{Message, Secp256k1};
+    use super::super::Error::InvalidContext;
+
+    #[test]
+    fn round_trip() {
+        let mut s = Secp256k1::new();
+        s.randomize(&mut thread_rng());
+        let blob = s.export_context();
+        let imported = Secp256k1::import_context(&blob).unwrap();
+        // The blinding state comes along, so a second export is identical
+        assert_eq!(imported.export_context(), blob);
+
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&imported, &sk);
+        assert_eq!(pk, PublicKey::from_secret_key(&s, &sk));
+        let sig = imported.sign(&msg, &sk);
+        assert_eq!(sig, s.sign(&msg, &sk));
+        assert!(imported.verify(&msg, &sig, &pk).is_ok());
+        assert!(imported.self_test().passed());
+    }
+
+    #[test]
+    fn capabilities() {
+        let full = Secp256k1::new().export_context();
+        let sign = Secp256k1::signing_only().export_context();
+        let verify = Secp256k1::verification_only().export_context();
+        assert!(sign.len() < full.len() && verify.len() < full.len());
+
+        assert!(Secp256k1::import_signing_only(&full).is_ok());
+        assert!(Secp256k1::import_verification_only(&full).is_ok());
+        assert!(Secp256k1::import_signing_only(&sign).is_ok());
+        assert!(Secp256k1::import_verification_only(&verify).is_ok());
+
+        assert_eq!(Secp256k1::import_context(&sign).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::import_context(&verify).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::import_signing_only(&verify).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::import_verification_only(&sign).err(), Some(InvalidContext));
+    }
+
+    #[test]
+    fn corruption() {
+        let blob = Secp256k1::signing_only().export_context();
+        assert_eq!(Secp256k1::import_signing_only(&[]).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::import_signing_only(&blob[..blob.len() - 1]).err(), Some(InvalidContext));
+
+        // Header (magic, version, flags, window, options), tables, blinding
+        // state and checksum
+        for &i in &[0, 4, 5, 6, 7, 100, blob.len() - 100, blob.len() - 1] {
+            let mut bad = blob.clone();
+            bad[i] ^= 1;
+            assert_eq!(Secp256k1::import_signing_only(&bad).err(), Some(InvalidContext));
+        }
+    }
+}
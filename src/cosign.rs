@@ -0,0 +1,1189 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Co-signing sessions
+//! Resumable state machines for t-of-n Schnorr co-signing, so that an
+//! enclave which keeps no state between ECALLs can take part in (or
+//! coordinate) a signing session by sealing the state after each step and
+//! unsealing it for the next.
+//!
+//! Each signer holds a Shamir share `x_i` of the group secret key, with
+//! `X_i = x_i * G`; key generation (by a dealer or a DKG) is out of scope.
+//! A session for a message and a set of at least t signers runs in three
+//! rounds:
+//!
+//! 1. every signer picks a nonce `k_i` and sends a commitment to
+//!    `R_i = k_i * G`;
+//! 2. once all commitments are in, every signer reveals `R_i`, which must
+//!    match its commitment;
+//! 3. with `R = sum(R_i)` and `e = challenge(R, X, m)`, every signer sends
+//!    `s_i = k_i + e * l_i * x_i`, where `l_i` is its Lagrange coefficient
+//!    for the signing set.
+//!
+//! The coordinator checks each `s_i` against `R_i` and `X_i`, and the
+//! signature is `(R, sum(s_i))`, with `s * G = R + e * X`. For 2-of-2 (or
+//! any n-of-n) signing, all signers take part in every session.
+//!
+//! `Coordinator` and `SignerSession` serialize to bytes after any step.
+//! A `SignerSession` contains the secret nonce, so it must be sealed before
+//! it leaves the enclave, and it must never be resumed from an older state
+//! than its latest: replaying a session which has already revealed its
+//! nonce lets the other signers pick new commitments, and two partial
+//! signatures with the same nonce reveal the key share. Store it with
+//! rollback protection, such as a `vault::FileBackedVault` generation or a
+//! monotonic counter.
+//!
+//! A signer in an enclave which can't keep a secret nonce safe between
+//! steps can start its session with `SignerSession::new_deterministic`
+//! instead, in the manner of BIP327's deterministic signer. It commits
+//! last, once the coordinator has every other signer's commitment, and
+//! derives its nonce from its share, the group key, the message, a session
+//! id, the next value of a `NonceCounter` and those commitments. Since the
+//! commitments bind the other nonces, the nonce is a function of
+//! everything that goes into the challenge, so replaying the session from
+//! any older state can only give the same partial signature again. Such a
+//! session holds nothing secret between steps: the nonce is derived again
+//! from the share when signing. Requires compilation with the "hashes"
+//! feature.
+
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use cleanup::wipe;
+use constants;
+use hashes::Sha256;
+use key::{PublicKey, SecretKey};
+use le_bytes::{read_u32, u32_bytes};
+use scalar::{self, Scalar};
+use super::Error::{self, CoSigningOutOfOrder, IncorrectSignature, InvalidCoSigningSession, InvalidNonce,
+                   InvalidPartialSignature, InvalidSignature, NonceCommitmentMismatch, NonceCounterNotMonotonic};
+use {Message, Secp256k1, Signing, Verification};
+
+/// Prefixed to the nonce before hashing it into a commitment
+const COMMITMENT_TAG: &'static [u8] = b"rust-secp256k1-sgx cosign commitment";
+
+/// Prefixed to the nonce, group key and message before hashing them into
+/// the challenge
+const CHALLENGE_TAG: &'static [u8] = b"rust-secp256k1-sgx cosign challenge";
+
+/// Prefixed to the share, session and other signers' commitments before
+/// hashing them into a deterministic nonce
+const DETERMINISTIC_NONCE_TAG: &'static [u8] = b"rust-secp256k1-sgx cosign deterministic nonce";
+
+const COORDINATOR_MAGIC: &'static [u8; 8] = b"secpcrd\x01";
+const SIGNER_MAGIC: &'static [u8; 8] = b"secpsgn\x01";
+
+// Coordinator state: magic, group key, message, participant count (u32),
+// then per participant its index (u32), which of the commitment, nonce and
+// partial signature are present (bit flags), its share key and the three
+// values, zero-filled when absent. Integers are little-endian.
+const COORDINATOR_HEADER_SIZE: usize = 8 + constants::PUBLIC_KEY_SIZE + constants::MESSAGE_SIZE + 4;
+const PARTICIPANT_SIZE: usize = 4 + 1 + constants::PUBLIC_KEY_SIZE + 32 + constants::PUBLIC_KEY_SIZE + 32;
+const HAS_COMMITMENT: u8 = 1;
+const HAS_NONCE: u8 = 2;
+const HAS_PARTIAL: u8 = 4;
+
+// Signer state: magic, index (u32), group key, message, the kind of nonce
+// (1 byte), the secret nonce (zero once spent) or for a derived nonce the
+// session id, its point, the commitment count (u32), then per commitment
+// the index (u32) and the commitment. A derived nonce's counter value
+// (u64) and the other signers' commitments (count and entries as above)
+// follow.
+const SIGNER_HEADER_SIZE: usize = 8 + 4 + constants::PUBLIC_KEY_SIZE + constants::MESSAGE_SIZE + 1 + 32 +
+                                  constants::PUBLIC_KEY_SIZE + 4;
+const COMMITMENT_ENTRY_SIZE: usize = 4 + 32;
+const NONCE_SPENT: u8 = 0;
+const NONCE_RANDOM: u8 = 1;
+const NONCE_DERIVED: u8 = 2;
+
+/// The size (in bytes) of an encoded `CoSignature`
+pub const COSIGNATURE_SIZE: usize = constants::PUBLIC_KEY_SIZE + 32;
+
+/// What a session is waiting for
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Round {
+    /// Nonce commitments
+    Commit,
+    /// Nonces
+    Reveal,
+    /// Partial signatures
+    Sign,
+    /// Nothing; the session is complete
+    Done,
+}
+
+/// A co-signed Schnorr signature `(R, s)` with `s * G = R + e * X`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CoSignature {
+    /// The combined nonce
+    pub r: PublicKey,
+    /// The sum of the partial signatures
+    pub s: Scalar,
+}
+
+impl CoSignature {
+    /// Checks the signature for `msg` under the group key. Fails with
+    /// `IncorrectSignature` if it is wrong.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, group_key: &PublicKey, msg: &Message)
+                                   -> Result<(), Error> {
+        if check_equation(secp, &self.s, &self.r, &challenge(&self.r, group_key, msg), group_key) {
+            Ok(())
+        } else {
+            Err(IncorrectSignature)
+        }
+    }
+
+    /// Encodes the signature: `R` compressed, then `s` big-endian
+    pub fn serialize(&self) -> [u8; COSIGNATURE_SIZE] {
+        let mut out = [0u8; COSIGNATURE_SIZE];
+        out[..constants::PUBLIC_KEY_SIZE].copy_from_slice(&self.r.serialize());
+        out[constants::PUBLIC_KEY_SIZE..].copy_from_slice(&self.s.to_be_bytes());
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` for
+    /// any other data.
+    pub fn from_slice(data: &[u8]) -> Result<CoSignature, Error> {
+        if data.len() != COSIGNATURE_SIZE {
+            return Err(InvalidSignature);
+        }
+        Ok(CoSignature {
+            r: PublicKey::from_slice(&data[..constants::PUBLIC_KEY_SIZE]).map_err(|_| InvalidSignature)?,
+            s: scalar_at(&data[constants::PUBLIC_KEY_SIZE..]).map_err(|_| InvalidSignature)?,
+        })
+    }
+}
+
+/// The commitment a signer sends for its nonce point in the first round
+pub fn nonce_commitment(index: u32, nonce: &PublicKey) -> [u8; 32] {
+    let mut engine = Sha256::new();
+    engine.input(COMMITMENT_TAG);
+    engine.input(&u32_bytes(index));
+    engine.input(&nonce.serialize());
+    engine.result()
+}
+
+/// The challenge `e` for the combined nonce `r`, the group key and `msg`
+pub fn challenge(r: &PublicKey, group_key: &PublicKey, msg: &Message) -> Scalar {
+    let mut engine = Sha256::new();
+    engine.input(CHALLENGE_TAG);
+    engine.input(&r.serialize());
+    engine.input(&group_key.serialize());
+    engine.input(&msg[..]);
+    Scalar::from_be_bytes_mod_order(&engine.result())
+}
+
+/// The Lagrange coefficient at zero of the share with `index`, for the
+/// signing set `indices` (which must contain `index`). Fails with
+/// `InvalidCoSigningSession` if an index is zero or repeated.
+pub fn lagrange_coefficient(index: u32, indices: &[u32]) -> Result<Scalar, Error> {
+    let xs: Vec<Scalar> = indices.iter().map(|&i| Scalar::from_u64(i as u64)).collect();
+    scalar::lagrange_coefficient(&xs, &Scalar::from_u64(index as u64)).map_err(|_| InvalidCoSigningSession)
+}
+
+/// A counter for `SignerSession::new_deterministic` which survives
+/// restarts, such as an SGX monotonic counter or one kept in a
+/// rollback-protected vault
+pub trait NonceCounter {
+    /// Advances the counter and returns its new value, which must exceed
+    /// every value returned before
+    fn next(&mut self) -> Result<u64, Error>;
+}
+
+/// Draws counter values for deterministic nonces from a `NonceCounter`,
+/// checking that each one exceeds the last
+pub struct NonceAccount<N> {
+    counter: N,
+    last: Option<u64>,
+}
+
+impl<N: NonceCounter> NonceAccount<N> {
+    /// Draws values from `counter`
+    pub fn new(counter: N) -> NonceAccount<N> {
+        NonceAccount { counter, last: None }
+    }
+
+    /// The last value drawn, if any
+    #[inline]
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Draws the next value. Fails with `NonceCounterNotMonotonic` if it
+    /// does not exceed the last one, and with the counter's error if it
+    /// fails.
+    fn next(&mut self) -> Result<u64, Error> {
+        let value = self.counter.next()?;
+        if self.last.map_or(false, |last| value <= last) {
+            return Err(NonceCounterNotMonotonic);
+        }
+        self.last = Some(value);
+        Ok(value)
+    }
+
+    /// Unwraps the counter
+    pub fn into_inner(self) -> N {
+        self.counter
+    }
+}
+
+/// Whether `s * G = r + e * x`
+fn check_equation<C: Verification>(secp: &Secp256k1<C>, s: &Scalar, r: &PublicKey, e: &Scalar, x: &PublicKey)
+                                   -> bool {
+    let mut lhs = PublicKey::from_slice(&constants::GENERATOR_G_COMPRESSED).expect("generator is valid");
+    let mut ex = *x;
+    if lhs.mul_assign(secp, s).is_err() || ex.mul_assign(secp, e).is_err() {
+        return false;
+    }
+    match r.combine(secp, &ex) {
+        Ok(rhs) => lhs == rhs,
+        Err(_) => false,
+    }
+}
+
+/// The sum of `points`, failing if it is the point at infinity
+fn sum_points<C, I: Iterator<Item = PublicKey>>(secp: &Secp256k1<C>, mut points: I) -> Result<PublicKey, Error> {
+    let first = points.next().ok_or(InvalidCoSigningSession)?;
+    points.try_fold(first, |sum, point| sum.combine(secp, &point))
+}
+
+fn check_indices(indices: &[u32]) -> Result<(), Error> {
+    for (i, &index) in indices.iter().enumerate() {
+        if index == 0 || indices[..i].contains(&index) {
+            return Err(InvalidCoSigningSession);
+        }
+    }
+    Ok(())
+}
+
+fn array32_at(data: &[u8]) -> [u8; 32] {
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(&data[..32]);
+    ret
+}
+
+fn scalar_at(data: &[u8]) -> Result<Scalar, Error> {
+    Scalar::from_be_bytes(array32_at(data))
+}
+
+fn message_at(data: &[u8]) -> Message {
+    Message::from_slice(&data[..constants::MESSAGE_SIZE]).expect("32 bytes")
+}
+
+struct Participant {
+    index: u32,
+    share: PublicKey,
+    commitment: Option<[u8; 32]>,
+    nonce: Option<PublicKey>,
+    partial: Option<Scalar>,
+}
+
+/// The coordinator's side of a session: collects each round's messages,
+/// checks them and aggregates the partial signatures. It holds nothing
+/// secret.
+pub struct Coordinator {
+    group_key: PublicKey,
+    msg: Message,
+    participants: Vec<Participant>,
+}
+
+impl Coordinator {
+    /// Starts a session for signing `msg` under `group_key` with the signers
+    /// in `signers`, given by share index and share key. Fails with
+    /// `InvalidCoSigningSession` if there are no signers or an index is zero
+    /// or repeated.
+    pub fn new(group_key: PublicKey, msg: Message, signers: &[(u32, PublicKey)]) -> Result<Coordinator, Error> {
+        let indices: Vec<u32> = signers.iter().map(|&(index, _)| index).collect();
+        if indices.is_empty() {
+            return Err(InvalidCoSigningSession);
+        }
+        check_indices(&indices)?;
+        Ok(Coordinator {
+            group_key,
+            msg,
+            participants: signers.iter().map(|&(index, share)| Participant {
+                index,
+                share,
+                commitment: None,
+                nonce: None,
+                partial: None,
+            }).collect(),
+        })
+    }
+
+    /// The group key
+    #[inline]
+    pub fn group_key(&self) -> &PublicKey {
+        &self.group_key
+    }
+
+    /// The message being signed
+    #[inline]
+    pub fn message(&self) -> &Message {
+        &self.msg
+    }
+
+    /// The share indices of the signers, in session order
+    pub fn indices(&self) -> Vec<u32> {
+        self.participants.iter().map(|p| p.index).collect()
+    }
+
+    /// What the session is waiting for
+    pub fn round(&self) -> Round {
+        if self.participants.iter().any(|p| p.commitment.is_none()) {
+            Round::Commit
+        } else if self.participants.iter().any(|p| p.nonce.is_none()) {
+            Round::Reveal
+        } else if self.participants.iter().any(|p| p.partial.is_none()) {
+            Round::Sign
+        } else {
+            Round::Done
+        }
+    }
+
+    fn participant(&mut self, index: u32) -> Result<&mut Participant, Error> {
+        self.participants.iter_mut().find(|p| p.index == index).ok_or(InvalidCoSigningSession)
+    }
+
+    /// Records the nonce commitment of the signer with `index`. Delivering
+    /// the same commitment again has no effect, so a step can safely be
+    /// retried after resuming. Fails with `InvalidCoSigningSession` for an
+    /// unknown signer, and with `CoSigningOutOfOrder` outside the commitment
+    /// round or for a different commitment.
+    pub fn add_commitment(&mut self, index: u32, commitment: [u8; 32]) -> Result<(), Error> {
+        let round = self.round();
+        let participant = self.participant(index)?;
+        match participant.commitment {
+            Some(c) if c == commitment => Ok(()),
+            None if round == Round::Commit => {
+                participant.commitment = Some(commitment);
+                Ok(())
+            }
+            _ => Err(CoSigningOutOfOrder),
+        }
+    }
+
+    /// The commitments received so far, by share index, which a
+    /// deterministic signer takes before committing last
+    pub fn received_commitments(&self) -> Vec<(u32, [u8; 32])> {
+        self.participants.iter().filter_map(|p| p.commitment.map(|c| (p.index, c))).collect()
+    }
+
+    /// All commitments, by share index, once the commitment round is over
+    pub fn commitments(&self) -> Option<Vec<(u32, [u8; 32])>> {
+        if self.round() == Round::Commit {
+            return None;
+        }
+        Some(self.participants.iter().map(|p| (p.index, p.commitment.expect("round is over"))).collect())
+    }
+
+    /// Records the nonce point of the signer with `index`. Fails like
+    /// `add_commitment`, and with `NonceCommitmentMismatch` if the nonce
+    /// does not match the signer's commitment.
+    pub fn add_nonce(&mut self, index: u32, nonce: PublicKey) -> Result<(), Error> {
+        let round = self.round();
+        let participant = self.participant(index)?;
+        match participant.nonce {
+            Some(n) if n == nonce => Ok(()),
+            None if round == Round::Reveal => {
+                if Some(nonce_commitment(index, &nonce)) != participant.commitment {
+                    return Err(NonceCommitmentMismatch);
+                }
+                participant.nonce = Some(nonce);
+                Ok(())
+            }
+            _ => Err(CoSigningOutOfOrder),
+        }
+    }
+
+    /// All nonce points, by share index, once the reveal round is over
+    pub fn nonces(&self) -> Option<Vec<(u32, PublicKey)>> {
+        match self.round() {
+            Round::Commit | Round::Reveal => None,
+            _ => Some(self.participants.iter().map(|p| (p.index, p.nonce.expect("round is over"))).collect()),
+        }
+    }
+
+    /// Checks and records the partial signature of the signer with `index`.
+    /// Fails like `add_commitment`, and with `InvalidPartialSignature` if the
+    /// partial signature does not match the signer's nonce and share key.
+    pub fn add_partial_signature<C: Verification>(&mut self, secp: &Secp256k1<C>, index: u32, partial: Scalar)
+                                                  -> Result<(), Error> {
+        let round = self.round();
+        let indices = self.indices();
+        let e = match self.nonces() {
+            Some(nonces) => challenge(&sum_points(secp, nonces.into_iter().map(|(_, n)| n))?,
+                                      &self.group_key, &self.msg),
+            None => Scalar::ZERO,
+        };
+        let participant = self.participant(index)?;
+        match participant.partial {
+            Some(s) if s == partial => Ok(()),
+            None if round == Round::Sign => {
+                let weighted = e.mul(&lagrange_coefficient(index, &indices)?);
+                if !check_equation(secp, &partial, &participant.nonce.expect("round is over"), &weighted,
+                                   &participant.share) {
+                    return Err(InvalidPartialSignature);
+                }
+                participant.partial = Some(partial);
+                Ok(())
+            }
+            _ => Err(CoSigningOutOfOrder),
+        }
+    }
+
+    /// Combines the partial signatures into the group's signature. Fails
+    /// with `CoSigningOutOfOrder` until every partial signature is in.
+    pub fn aggregate<C>(&self, secp: &Secp256k1<C>) -> Result<CoSignature, Error> {
+        if self.round() != Round::Done {
+            return Err(CoSigningOutOfOrder);
+        }
+        Ok(CoSignature {
+            r: sum_points(secp, self.participants.iter().map(|p| p.nonce.expect("session is done")))?,
+            s: self.participants.iter().fold(Scalar::ZERO, |s, p| s.add(&p.partial.expect("session is done"))),
+        })
+    }
+
+    /// Encodes the session state
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(COORDINATOR_HEADER_SIZE + PARTICIPANT_SIZE * self.participants.len());
+        out.extend_from_slice(COORDINATOR_MAGIC);
+        out.extend_from_slice(&self.group_key.serialize());
+        out.extend_from_slice(&self.msg[..]);
+        out.extend_from_slice(&u32_bytes(self.participants.len() as u32));
+        for p in &self.participants {
+            out.extend_from_slice(&u32_bytes(p.index));
+            let flags = if p.commitment.is_some() { HAS_COMMITMENT } else { 0 } |
+                        if p.nonce.is_some() { HAS_NONCE } else { 0 } |
+                        if p.partial.is_some() { HAS_PARTIAL } else { 0 };
+            out.push(flags);
+            out.extend_from_slice(&p.share.serialize());
+            out.extend_from_slice(&p.commitment.unwrap_or([0; 32]));
+            match p.nonce {
+                Some(nonce) => out.extend_from_slice(&nonce.serialize()),
+                None => out.extend_from_slice(&[0; constants::PUBLIC_KEY_SIZE]),
+            }
+            out.extend_from_slice(&p.partial.unwrap_or(Scalar::ZERO).to_be_bytes());
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with
+    /// `InvalidCoSigningSession` for any other data. Nonces and partial
+    /// signatures are checked again, so the state only needs to be
+    /// integrity-protected against rollback, not trusted.
+    pub fn from_slice<C: Verification>(secp: &Secp256k1<C>, data: &[u8]) -> Result<Coordinator, Error> {
+        if data.len() < COORDINATOR_HEADER_SIZE || data[..8] != COORDINATOR_MAGIC[..] {
+            return Err(InvalidCoSigningSession);
+        }
+        let count = read_u32(&data[COORDINATOR_HEADER_SIZE - 4..]) as usize;
+        if count.checked_mul(PARTICIPANT_SIZE).and_then(|n| n.checked_add(COORDINATOR_HEADER_SIZE)) !=
+           Some(data.len()) {
+            return Err(InvalidCoSigningSession);
+        }
+        let group_key = PublicKey::from_slice(&data[8..8 + constants::PUBLIC_KEY_SIZE])
+            .map_err(|_| InvalidCoSigningSession)?;
+        let msg = message_at(&data[8 + constants::PUBLIC_KEY_SIZE..]);
+
+        let mut signers = Vec::with_capacity(count);
+        let mut delivered = Vec::with_capacity(count);
+        for chunk in data[COORDINATOR_HEADER_SIZE..].chunks(PARTICIPANT_SIZE) {
+            let share = PublicKey::from_slice(&chunk[5..38]).map_err(|_| InvalidCoSigningSession)?;
+            signers.push((read_u32(chunk), share));
+            delivered.push((chunk[4], chunk));
+        }
+        let mut session = Coordinator::new(group_key, msg, &signers)?;
+
+        // Replay the steps round by round, which checks them all again
+        for &(flag, offset) in [(HAS_COMMITMENT, 38), (HAS_NONCE, 70), (HAS_PARTIAL, 103)].iter() {
+            for &(flags, chunk) in &delivered {
+                if flags & !(HAS_COMMITMENT | HAS_NONCE | HAS_PARTIAL) != 0 {
+                    return Err(InvalidCoSigningSession);
+                }
+                if flags & flag == 0 {
+                    continue;
+                }
+                let index = read_u32(chunk);
+                let res = match flag {
+                    HAS_COMMITMENT => session.add_commitment(index, array32_at(&chunk[offset..])),
+                    HAS_NONCE => PublicKey::from_slice(&chunk[offset..offset + constants::PUBLIC_KEY_SIZE])
+                        .and_then(|nonce| session.add_nonce(index, nonce)),
+                    _ => scalar_at(&chunk[offset..])
+                        .and_then(|partial| session.add_partial_signature(secp, index, partial)),
+                };
+                res.map_err(|_| InvalidCoSigningSession)?;
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// What a deterministic nonce is derived from, besides the share and the
+/// session's index, group key and message
+struct Derivation {
+    session_id: [u8; 32],
+    counter: u64,
+    /// The other signers' commitments, sorted by index
+    others: Vec<(u32, [u8; 32])>,
+}
+
+/// A signer's secret nonce
+enum Nonce {
+    /// Drawn at random and held until spent
+    Random(Scalar),
+    /// Derived from the share, again when signing
+    Derived(Derivation),
+    /// Spent on the partial signature
+    Spent,
+}
+
+impl Drop for Nonce {
+    fn drop(&mut self) {
+        if let Nonce::Random(ref mut nonce) = *self {
+            nonce.wipe();
+        }
+    }
+}
+
+/// The deterministic nonce of the signer with `index` and `share`
+fn derive_nonce(share: &SecretKey, index: u32, group_key: &PublicKey, msg: &Message, derivation: &Derivation)
+                -> Result<Scalar, Error> {
+    let mut engine = Sha256::new();
+    engine.input(DETERMINISTIC_NONCE_TAG);
+    engine.input(share.secret_bytes());
+    engine.input(&u32_bytes(index));
+    engine.input(&group_key.serialize());
+    engine.input(&msg[..]);
+    engine.input(&derivation.session_id);
+    engine.input(&derivation.counter.to_le_bytes());
+    engine.input(&u32_bytes(derivation.others.len() as u32));
+    for &(index, ref commitment) in &derivation.others {
+        engine.input(&u32_bytes(index));
+        engine.input(commitment);
+    }
+    let mut hash = engine.result();
+    let nonce = Scalar::from_be_bytes_mod_order(&hash);
+    wipe(&mut hash);
+    if nonce.is_zero() {
+        return Err(InvalidNonce);
+    }
+    Ok(nonce)
+}
+
+/// The commitments in `commitments` other than that of `index`, sorted
+fn other_commitments(commitments: &[(u32, [u8; 32])], index: u32) -> Vec<(u32, [u8; 32])> {
+    let mut ret: Vec<(u32, [u8; 32])> = commitments.iter().filter(|&&(i, _)| i != index).cloned().collect();
+    ret.sort_unstable_by_key(|&(i, _)| i);
+    ret
+}
+
+/// What the nonce of `SignerSession::new_deterministic` is derived from,
+/// besides the share and the account's counter
+#[derive(Copy, Clone, Debug)]
+pub struct DeterministicParams<'a> {
+    /// The share index
+    pub index: u32,
+    /// The group key being signed for
+    pub group_key: PublicKey,
+    /// The message being signed
+    pub msg: Message,
+    /// Identifies the session to the signer
+    pub session_id: &'a [u8; 32],
+    /// The commitments of all other signers (see
+    /// `Coordinator::received_commitments`)
+    pub others: &'a [(u32, [u8; 32])],
+}
+
+/// A signer's side of a session. It holds a random secret nonce until the
+/// partial signature is made, and wipes it then and on drop; a
+/// deterministic session holds no secret at all.
+pub struct SignerSession {
+    index: u32,
+    group_key: PublicKey,
+    msg: Message,
+    nonce: Nonce,
+    nonce_point: PublicKey,
+    commitments: Vec<(u32, [u8; 32])>,
+}
+
+impl SignerSession {
+    /// Starts a session for the share with `index`, picking a fresh nonce
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new<C: Signing, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R, index: u32,
+                                                   group_key: PublicKey, msg: Message)
+                                                   -> Result<SignerSession, Error> {
+        if index == 0 {
+            return Err(InvalidCoSigningSession);
+        }
+        let nonce = Scalar::from_secret_key(&SecretKey::new(secp, rng));
+        Ok(SignerSession {
+            index,
+            group_key,
+            msg,
+            nonce: Nonce::Random(nonce),
+            nonce_point: PublicKey::from_scalar_mul_g(secp, &nonce)?,
+            commitments: Vec::new(),
+        })
+    }
+
+    /// Starts a session for `share` with `params.index` whose nonce is
+    /// derived from the share, `params` and the next value drawn from
+    /// `account`. Fails with `InvalidCoSigningSession` if an index is zero
+    /// or repeated, and with the account's error if its counter fails or
+    /// goes backwards.
+    pub fn new_deterministic<C: Signing, N: NonceCounter>(secp: &Secp256k1<C>, account: &mut NonceAccount<N>,
+                                                          share: &SecretKey, params: DeterministicParams)
+                                                          -> Result<SignerSession, Error> {
+        let mut indices: Vec<u32> = params.others.iter().map(|&(index, _)| index).collect();
+        indices.push(params.index);
+        check_indices(&indices)?;
+        let derivation = Derivation {
+            session_id: *params.session_id,
+            counter: account.next()?,
+            others: other_commitments(params.others, params.index),
+        };
+        let mut nonce = derive_nonce(share, params.index, &params.group_key, &params.msg, &derivation)?;
+        let nonce_point = PublicKey::from_scalar_mul_g(secp, &nonce);
+        nonce.wipe();
+        Ok(SignerSession {
+            index: params.index,
+            group_key: params.group_key,
+            msg: params.msg,
+            nonce: Nonce::Derived(derivation),
+            nonce_point: nonce_point?,
+            commitments: Vec::new(),
+        })
+    }
+
+    /// What the session is waiting for: the commitments of all signers
+    /// (`Commit`), their nonces (`Sign`), or nothing (`Done`)
+    pub fn round(&self) -> Round {
+        match self.nonce {
+            Nonce::Spent => Round::Done,
+            _ if self.commitments.is_empty() => Round::Commit,
+            _ => Round::Sign,
+        }
+    }
+
+    /// The share index
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Whether the nonce is derived, as by `new_deterministic`, rather than
+    /// random. False once the session is done and the nonce spent.
+    #[inline]
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self.nonce, Nonce::Derived(_))
+    }
+
+    /// The commitment to send in the first round
+    pub fn commitment(&self) -> [u8; 32] {
+        nonce_commitment(self.index, &self.nonce_point)
+    }
+
+    /// Takes the commitments of all signers, which must include this one's,
+    /// and returns the nonce point to reveal. Revealing again with the same
+    /// commitments returns the same nonce point. Fails with
+    /// `InvalidCoSigningSession` if an index is zero or repeated, this
+    /// signer's commitment is wrong or missing, or for a deterministic
+    /// session the other commitments differ from those its nonce was
+    /// derived from, and with `CoSigningOutOfOrder` for different
+    /// commitments than before.
+    pub fn reveal(&mut self, commitments: &[(u32, [u8; 32])]) -> Result<PublicKey, Error> {
+        match self.round() {
+            Round::Commit => {}
+            Round::Sign if self.commitments[..] == commitments[..] => return Ok(self.nonce_point),
+            _ => return Err(CoSigningOutOfOrder),
+        }
+        let indices: Vec<u32> = commitments.iter().map(|&(index, _)| index).collect();
+        check_indices(&indices)?;
+        if !commitments.contains(&(self.index, self.commitment())) {
+            return Err(InvalidCoSigningSession);
+        }
+        if let Nonce::Derived(ref derivation) = self.nonce {
+            if derivation.others != other_commitments(commitments, self.index) {
+                return Err(InvalidCoSigningSession);
+            }
+        }
+        self.commitments = commitments.to_vec();
+        Ok(self.nonce_point)
+    }
+
+    /// Takes the nonce points of all signers, checks them against their
+    /// commitments and returns this signer's partial signature with `share`.
+    /// A random nonce is then wiped, so such a session signs at most once;
+    /// a deterministic session is resumable, and signs the same way every
+    /// time. Fails with `CoSigningOutOfOrder` before `reveal` or after
+    /// signing, with `InvalidCoSigningSession` if the signers differ from
+    /// those which committed (a signer given twice, or this one missing,
+    /// included) or a deterministic session is given another share than it
+    /// started with, and with `NonceCommitmentMismatch` if a
+    /// nonce does not match its commitment.
+    pub fn sign<C: Signing>(&mut self, secp: &Secp256k1<C>, share: &SecretKey, nonces: &[(u32, PublicKey)])
+                            -> Result<Scalar, Error> {
+        if self.round() != Round::Sign {
+            return Err(CoSigningOutOfOrder);
+        }
+        // Every committed signer exactly once, this one included, so that the
+        // nonce and the Lagrange coefficient are those of the signer set
+        let nonce_indices: Vec<u32> = nonces.iter().map(|&(index, _)| index).collect();
+        check_indices(&nonce_indices)?;
+        if nonces.len() != self.commitments.len() || !nonce_indices.contains(&self.index) {
+            return Err(InvalidCoSigningSession);
+        }
+        for &(index, ref nonce) in nonces {
+            match self.commitments.iter().find(|&&(i, _)| i == index) {
+                Some(&(_, commitment)) if commitment == nonce_commitment(index, nonce) => {}
+                Some(_) => return Err(NonceCommitmentMismatch),
+                None => return Err(InvalidCoSigningSession),
+            }
+        }
+        let indices: Vec<u32> = self.commitments.iter().map(|&(index, _)| index).collect();
+        let r = sum_points(secp, nonces.iter().map(|&(_, n)| n))?;
+        let weighted = challenge(&r, &self.group_key, &self.msg).mul(&lagrange_coefficient(self.index, &indices)?);
+
+        let mut nonce = match self.nonce {
+            Nonce::Random(nonce) => nonce,
+            Nonce::Derived(ref derivation) => {
+                let mut nonce = derive_nonce(share, self.index, &self.group_key, &self.msg, derivation)?;
+                if PublicKey::from_scalar_mul_g(secp, &nonce) != Ok(self.nonce_point) {
+                    nonce.wipe();
+                    return Err(InvalidCoSigningSession);
+                }
+                nonce
+            }
+            Nonce::Spent => unreachable!("round is Sign"),
+        };
+        let mut x = Scalar::from_secret_key(share);
+        let partial = nonce.add(&weighted.mul(&x));
+        x.wipe();
+        nonce.wipe();
+        self.nonce = Nonce::Spent;
+        Ok(partial)
+    }
+
+    /// Encodes the session state, a random secret nonce included
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SIGNER_HEADER_SIZE + COMMITMENT_ENTRY_SIZE * self.commitments.len());
+        out.extend_from_slice(SIGNER_MAGIC);
+        out.extend_from_slice(&u32_bytes(self.index));
+        out.extend_from_slice(&self.group_key.serialize());
+        out.extend_from_slice(&self.msg[..]);
+        match self.nonce {
+            Nonce::Random(nonce) => {
+                out.push(NONCE_RANDOM);
+                out.extend_from_slice(&nonce.to_be_bytes());
+            }
+            Nonce::Derived(ref derivation) => {
+                out.push(NONCE_DERIVED);
+                out.extend_from_slice(&derivation.session_id);
+            }
+            Nonce::Spent => {
+                out.push(NONCE_SPENT);
+                out.extend_from_slice(&[0; 32]);
+            }
+        }
+        out.extend_from_slice(&self.nonce_point.serialize());
+        out.extend_from_slice(&u32_bytes(self.commitments.len() as u32));
+        for &(index, ref commitment) in &self.commitments {
+            out.extend_from_slice(&u32_bytes(index));
+            out.extend_from_slice(commitment);
+        }
+        if let Nonce::Derived(ref derivation) = self.nonce {
+            out.extend_from_slice(&derivation.counter.to_le_bytes());
+            out.extend_from_slice(&u32_bytes(derivation.others.len() as u32));
+            for &(index, ref commitment) in &derivation.others {
+                out.extend_from_slice(&u32_bytes(index));
+                out.extend_from_slice(commitment);
+            }
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with
+    /// `InvalidCoSigningSession` for any other data.
+    pub fn from_slice<C: Signing>(secp: &Secp256k1<C>, data: &[u8]) -> Result<SignerSession, Error> {
+        if data.len() < SIGNER_HEADER_SIZE || data[..8] != SIGNER_MAGIC[..] {
+            return Err(InvalidCoSigningSession);
+        }
+        let count = read_u32(&data[SIGNER_HEADER_SIZE - 4..]) as usize;
+        let end = count.checked_mul(COMMITMENT_ENTRY_SIZE).and_then(|n| n.checked_add(SIGNER_HEADER_SIZE))
+            .ok_or(InvalidCoSigningSession)?;
+        let mut offset = 12;
+        let group_key = PublicKey::from_slice(&data[offset..offset + constants::PUBLIC_KEY_SIZE])
+            .map_err(|_| InvalidCoSigningSession)?;
+        offset += constants::PUBLIC_KEY_SIZE;
+        let msg = message_at(&data[offset..]);
+        offset += constants::MESSAGE_SIZE;
+        let kind = data[offset];
+        let secret = &data[offset + 1..offset + 33];
+        offset += 33;
+        let nonce_point = PublicKey::from_slice(&data[offset..offset + constants::PUBLIC_KEY_SIZE])
+            .map_err(|_| InvalidCoSigningSession)?;
+
+        // A derived nonce's inputs follow the commitments
+        let derived_end = if kind == NONCE_DERIVED {
+            if data.len() < end + 12 {
+                return Err(InvalidCoSigningSession);
+            }
+            (read_u32(&data[end + 8..]) as usize).checked_mul(COMMITMENT_ENTRY_SIZE)
+                .and_then(|n| n.checked_add(end + 12))
+                .ok_or(InvalidCoSigningSession)?
+        } else {
+            end
+        };
+        if derived_end != data.len() {
+            return Err(InvalidCoSigningSession);
+        }
+        let nonce = match kind {
+            NONCE_SPENT if secret.iter().all(|&b| b == 0) => Nonce::Spent,
+            NONCE_RANDOM => {
+                let nonce = scalar_at(secret).map_err(|_| InvalidCoSigningSession)?;
+                if PublicKey::from_scalar_mul_g(secp, &nonce) != Ok(nonce_point) {
+                    return Err(InvalidCoSigningSession);
+                }
+                Nonce::Random(nonce)
+            }
+            NONCE_DERIVED => {
+                let mut counter = [0u8; 8];
+                counter.copy_from_slice(&data[end..end + 8]);
+                Nonce::Derived(Derivation {
+                    session_id: array32_at(secret),
+                    counter: u64::from_le_bytes(counter),
+                    others: data[end + 12..].chunks(COMMITMENT_ENTRY_SIZE)
+                        .map(|chunk| (read_u32(chunk), array32_at(&chunk[4..])))
+                        .collect(),
+                })
+            }
+            _ => return Err(InvalidCoSigningSession),
+        };
+
+        let session = SignerSession {
+            index: read_u32(&data[8..]),
+            group_key,
+            msg,
+            nonce,
+            nonce_point,
+            commitments: data[SIGNER_HEADER_SIZE..end].chunks(COMMITMENT_ENTRY_SIZE)
+                .map(|chunk| (read_u32(chunk), array32_at(&chunk[4..])))
+                .collect(),
+        };
+        if session.index == 0 {
+            return Err(InvalidCoSigningSession);
+        }
+        if let Nonce::Derived(ref derivation) = session.nonce {
+            let mut indices: Vec<u32> = derivation.others.iter().map(|&(index, _)| index).collect();
+            indices.push(session.index);
+            check_indices(&indices)?;
+            if derivation.others != other_commitments(&derivation.others, session.index) {
+                return Err(InvalidCoSigningSession);
+            }
+        }
+        if count > 0 {
+            let indices: Vec<u32> = session.commitments.iter().map(|&(index, _)| index).collect();
+            check_indices(&indices)?;
+            if !session.commitments.contains(&(session.index, session.commitment())) {
+                return Err(InvalidCoSigningSession);
+            }
+            if let Nonce::Derived(ref derivation) = session.nonce {
+                if derivation.others != other_commitments(&session.commitments, session.index) {
+                    return Err(InvalidCoSigningSession);
+                }
+            }
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{lagrange_coefficient, CoSignature, Coordinator, DeterministicParams, NonceAccount, NonceCounter, Round,
+                SignerSession};
+    use super::super::{Message, PublicKey, Secp256k1, SecretKey};
+    use super::super::scalar::Scalar;
+    use super::super::Error::{self, CoSigningOutOfOrder, IncorrectSignature, InvalidCoSigningSession,
+                              InvalidPartialSignature, NonceCommitmentMismatch, NonceCounterNotMonotonic};
+
+    // Shamir shares of a random key for indices 1 to n, with threshold t
+    fn deal(s: &Secp256k1<::All>, t: usize, n: u32) -> (PublicKey, Vec<(u32, SecretKey)>) {
+        let coefficients: Vec<Scalar> = (0..t).map(|_| Scalar::from_secret_key(&SecretKey::new(s, &mut thread_rng())))
+                                              .collect();
+        let group_key = PublicKey::from_scalar_mul_g(s, &coefficients[0]).unwrap();
+        let shares = (1..n + 1).map(|i| {
+            let x = Scalar::from_u64(i as u64);
+            let y = coefficients.iter().rev().fold(Scalar::ZERO, |acc, c| acc.mul(&x).add(c));
+            (i, SecretKey::from_slice(&y.to_be_bytes()).unwrap())
+        }).collect();
+        (group_key, shares)
+    }
+
+    // Runs a session, resuming every state from its encoding after each step
+    fn run(s: &Secp256k1<::All>, group_key: PublicKey, msg: Message, shares: &[(u32, SecretKey)])
+           -> CoSignature {
+        let signers: Vec<(u32, PublicKey)> = shares.iter()
+            .map(|&(i, ref sk)| (i, PublicKey::from_secret_key(s, sk)))
+            .collect();
+        let mut coordinator = Coordinator::new(group_key, msg, &signers).unwrap();
+        let mut sessions: Vec<Vec<u8>> = shares.iter()
+            .map(|&(i, _)| SignerSession::new(s, &mut thread_rng(), i, group_key, msg).unwrap().serialize())
+            .collect();
+
+        for (state, &(i, _)) in sessions.iter().zip(shares) {
+            let session = SignerSession::from_slice(s, state).unwrap();
+            assert_eq!(session.round(), Round::Commit);
+            coordinator.add_commitment(i, session.commitment()).unwrap();
+            coordinator = Coordinator::from_slice(s, &coordinator.serialize()).unwrap();
+        }
+        assert_eq!(coordinator.round(), Round::Reveal);
+        let commitments = coordinator.commitments().unwrap();
+        for (state, &(i, _)) in sessions.iter_mut().zip(shares) {
+            let mut session = SignerSession::from_slice(s, state).unwrap();
+            coordinator.add_nonce(i, session.reveal(&commitments).unwrap()).unwrap();
+            *state = session.serialize();
+            coordinator = Coordinator::from_slice(s, &coordinator.serialize()).unwrap();
+        }
+        assert_eq!(coordinator.round(), Round::Sign);
+        let nonces = coordinator.nonces().unwrap();
+        for (state, &(i, ref sk)) in sessions.iter_mut().zip(shares) {
+            let mut session = SignerSession::from_slice(s, state).unwrap();
+            assert_eq!(session.round(), Round::Sign);
+            coordinator.add_partial_signature(s, i, session.sign(s, sk, &nonces).unwrap()).unwrap();
+            *state = session.serialize();
+            coordinator = Coordinator::from_slice(s, &coordinator.serialize()).unwrap();
+        }
+        for state in &sessions {
+            assert_eq!(SignerSession::from_slice(s, state).unwrap().round(), Round::Done);
+        }
+        assert_eq!(coordinator.round(), Round::Done);
+        coordinator.aggregate(s).unwrap()
+    }
+
+    #[test]
+    fn sessions() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+
+        let (group_key, shares) = deal(&s, 2, 2);
+        let sig = run(&s, group_key, msg, &shares);
+        assert_eq!(sig.verify(&s, &group_key, &msg), Ok(()));
+        assert_eq!(sig.verify(&s, &group_key, &Message::from_slice(&[0x43; 32]).unwrap()), Err(IncorrectSignature));
+        assert_eq!(CoSignature::from_slice(&sig.serialize()), Ok(sig));
+
+        // Any two of three shares can sign
+        let (group_key, shares) = deal(&s, 2, 3);
+        for pair in [[0, 1], [0, 2], [2, 1]].iter() {
+            let subset = [shares[pair[0]], shares[pair[1]]];
+            assert_eq!(run(&s, group_key, msg, &subset).verify(&s, &group_key, &msg), Ok(()));
+        }
+        let (other_key, _) = deal(&s, 2, 3);
+        assert_eq!(run(&s, group_key, msg, &shares[..2]).verify(&s, &other_key, &msg), Err(IncorrectSignature));
+    }
+
+    #[test]
+    fn lagrange() {
+        // Interpolating f(x) = x at zero gives zero; f(x) = 1 gives one
+        let indices = [1, 4, 6];
+        let coefficients: Vec<Scalar> = indices.iter().map(|&i| lagrange_coefficient(i, &indices).unwrap()).collect();
+        let x = indices.iter().zip(&coefficients)
+            .fold(Scalar::ZERO, |acc, (&i, l)| acc.add(&l.mul(&Scalar::from_u64(i as u64))));
+        assert_eq!(x, Scalar::ZERO);
+        let one = coefficients.iter().fold(Scalar::ZERO, |acc, l| acc.add(l));
+        assert_eq!(one, Scalar::ONE);
+
+        assert_eq!(lagrange_coefficient(2, &indices), Err(InvalidCoSigningSession));
+        assert_eq!(lagrange_coefficient(1, &[1, 4, 1]), Err(InvalidCoSigningSession));
+        assert_eq!(lagrange_coefficient(0, &[0, 4]), Err(InvalidCoSigningSession));
+    }
+
+    #[test]
+    fn misbehaviour() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let (group_key, shares) = deal(&s, 2, 2);
+        let signers: Vec<(u32, PublicKey)> = shares.iter()
+            .map(|&(i, ref sk)| (i, PublicKey::from_secret_key(&s, sk)))
+            .collect();
+        assert_eq!(Coordinator::new(group_key, msg, &[]).err(), Some(InvalidCoSigningSession));
+        assert_eq!(Coordinator::new(group_key, msg, &[signers[0], signers[0]]).err(),
+                   Some(InvalidCoSigningSession));
+
+        let mut coordinator = Coordinator::new(group_key, msg, &signers).unwrap();
+        let mut a = SignerSession::new(&s, &mut thread_rng(), 1, group_key, msg).unwrap();
+        let mut b = SignerSession::new(&s, &mut thread_rng(), 2, group_key, msg).unwrap();
+        assert_eq!(a.sign(&s, &shares[0].1, &[]), Err(CoSigningOutOfOrder));
+        assert_eq!(coordinator.add_commitment(3, a.commitment()), Err(InvalidCoSigningSession));
+        coordinator.add_commitment(1, a.commitment()).unwrap();
+        // Retries are fine, changes are not
+        assert_eq!(coordinator.add_commitment(1, a.commitment()), Ok(()));
+        assert_eq!(coordinator.add_commitment(1, b.commitment()), Err(CoSigningOutOfOrder));
+        assert_eq!(coordinator.add_nonce(1, a.reveal(&[(1, a.commitment())]).unwrap()), Err(CoSigningOutOfOrder));
+        assert!(coordinator.commitments().is_none());
+        coordinator.add_commitment(2, b.commitment()).unwrap();
+
+        let commitments = coordinator.commitments().unwrap();
+        // `a` has already revealed for a different set of commitments
+        assert_eq!(a.reveal(&commitments), Err(CoSigningOutOfOrder));
+        let mut a = SignerSession::new(&s, &mut thread_rng(), 1, group_key, msg).unwrap();
+        assert_eq!(a.reveal(&commitments), Err(InvalidCoSigningSession));
+        let b_nonce = b.reveal(&commitments).unwrap();
+        assert_eq!(b.reveal(&commitments), Ok(b_nonce));
+        assert_eq!(coordinator.add_nonce(1, b_nonce), Err(NonceCommitmentMismatch));
+        coordinator.add_nonce(2, b_nonce).unwrap();
+
+        // `b` won't sign with the nonces of another session
+        let mut coordinator = Coordinator::new(group_key, msg, &signers).unwrap();
+        coordinator.add_commitment(1, a.commitment()).unwrap();
+        let mut b = SignerSession::new(&s, &mut thread_rng(), 2, group_key, msg).unwrap();
+        coordinator.add_commitment(2, b.commitment()).unwrap();
+        let commitments = coordinator.commitments().unwrap();
+        coordinator.add_nonce(1, a.reveal(&commitments).unwrap()).unwrap();
+        coordinator.add_nonce(2, b.reveal(&commitments).unwrap()).unwrap();
+        let nonces = coordinator.nonces().unwrap();
+        assert_eq!(b.sign(&s, &shares[1].1, &[nonces[0], (2, b_nonce)]), Err(NonceCommitmentMismatch));
+        assert_eq!(b.sign(&s, &shares[1].1, &nonces[..1]), Err(InvalidCoSigningSession));
+        assert_eq!(b.sign(&s, &shares[1].1, &[nonces[0], (3, nonces[1].1)]), Err(InvalidCoSigningSession));
+        // Nor with a signer given twice, or without its own nonce
+        assert_eq!(b.sign(&s, &shares[1].1, &[nonces[0], nonces[0]]), Err(InvalidCoSigningSession));
+        assert_eq!(b.sign(&s, &shares[1].1, &[nonces[1], nonces[1]]), Err(InvalidCoSigningSession));
+
+        // A partial signature with the wrong share is caught
+        let partial = b.sign(&s, &shares[0].1, &nonces).unwrap();
+        assert_eq!(coordinator.add_partial_signature(&s, 2, partial), Err(InvalidPartialSignature));
+        assert_eq!(b.sign(&s, &shares[1].1, &nonces), Err(CoSigningOutOfOrder));
+        assert_eq!(coordinator.aggregate(&s), Err(CoSigningOutOfOrder));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let (group_key, shares) = deal(&s, 2, 2);
+        let signers: Vec<(u32, PublicKey)> = shares.iter()
+            .map(|&(i, ref sk)| (i, PublicKey::from_secret_key(&s, sk)))
+            .collect();
+
+        let mut coordinator = Coordinator::new(group_key, msg, &signers).unwrap();
+        let a = SignerSession::new(&s, &mut thread_rng(), 1, group_key, msg).unwrap();
+        coordinator.add_commitment(1, a.commitment()).unwrap();
+        let encoded = coordinator.serialize();
+        let decoded = Coordinator::from_slice(&s, &encoded).unwrap();
+        assert_eq!(decoded.serialize(), encoded);
+        assert_eq!(decoded.indices(), vec![1, 2]);
+        assert_eq!(*decoded.message(), msg);
+
+        assert_eq!(Coordinator::from_slice(&s, &encoded[1..]).err(), Some(InvalidCoSigningSession));
+        let mut bad_flags = encoded.clone();
+        let second = encoded.len() - super::PARTICIPANT_SIZE;
+        bad_flags[second + 4] = super::HAS_NONCE;
+        assert_eq!(Coordinator::from_slice(&s, &bad_flags).err(), Some(InvalidCoSigningSession));
+        let mut duplicate = encoded.clone();
+        duplicate[second] = 1;
+        assert_eq!(Coordinator::from_slice(&s, &duplicate).err(), Some(InvalidCoSigningSession));
+
+        let encoded = a.serialize();
+        let decoded = SignerSession::from_slice(&s, &encoded).unwrap();
+        assert_eq!(decoded.serialize(), encoded);
+        assert_eq!(decoded.commitment(), a.commitment());
+        assert_eq!(SignerSession::from_slice(&s, &encoded[..encoded.len() - 1]).err(),
+                   Some(InvalidCoSigningSession));
+        // The nonce must match its point
+        let mut bad_nonce = encoded.clone();
+        bad_nonce[8 + 4 + 33 + 32 + 1 + 31] ^= 1;
+        assert_eq!(SignerSession::from_slice(&s, &bad_nonce).err(), Some(InvalidCoSigningSession));
+    }
+
+    /// Counts up by `step`, which a broken counter sets to zero
+    struct Counter {
+        value: u64,
+        step: u64,
+    }
+
+    impl NonceCounter for Counter {
+        fn next(&mut self) -> Result<u64, Error> {
+            self.value += self.step;
+            Ok(self.value)
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let (group_key, shares) = deal(&s, 2, 3);
+        let signers: Vec<(u32, PublicKey)> = shares[..2].iter()
+            .map(|&(i, ref sk)| (i, PublicKey::from_secret_key(&s, sk)))
+            .collect();
+        let mut account = NonceAccount::new(Counter { value: 0, step: 1 });
+        let session_id = [0x5e; 32];
+
+        // The random signer commits first, the deterministic one last
+        let mut coordinator = Coordinator::new(group_key, msg, &signers).unwrap();
+        let mut a = SignerSession::new(&s, &mut thread_rng(), 1, group_key, msg).unwrap();
+        coordinator.add_commitment(1, a.commitment()).unwrap();
+        let others = coordinator.received_commitments();
+        assert_eq!(others, vec![(1, a.commitment())]);
+        let params = DeterministicParams { index: 2, group_key, msg, session_id: &session_id, others: &others };
+        let b = SignerSession::new_deterministic(&s, &mut account, &shares[1].1, params).unwrap();
+        assert!(b.is_deterministic());
+        assert_eq!(account.last(), Some(1));
+        coordinator.add_commitment(2, b.commitment()).unwrap();
+
+        // Nothing secret is stored, and every state can be resumed
+        let created = b.serialize();
+        assert!(!created.windows(32).any(|w| w == &shares[1].1.secret_bytes()[..]));
+        let commitments = coordinator.commitments().unwrap();
+        coordinator.add_nonce(1, a.reveal(&commitments).unwrap()).unwrap();
+        let mut b = SignerSession::from_slice(&s, &created).unwrap();
+        coordinator.add_nonce(2, b.reveal(&commitments).unwrap()).unwrap();
+        let revealed = b.serialize();
+        let nonces = coordinator.nonces().unwrap();
+        coordinator.add_partial_signature(&s, 1, a.sign(&s, &shares[0].1, &nonces).unwrap()).unwrap();
+        let mut b = SignerSession::from_slice(&s, &revealed).unwrap();
+        assert_eq!(b.sign(&s, &shares[2].1, &nonces), Err(InvalidCoSigningSession));
+        let partial = b.sign(&s, &shares[1].1, &nonces).unwrap();
+        assert_eq!(b.round(), Round::Done);
+        assert_eq!(SignerSession::from_slice(&s, &b.serialize()).unwrap().round(), Round::Done);
+        coordinator.add_partial_signature(&s, 2, partial).unwrap();
+        assert_eq!(coordinator.aggregate(&s).unwrap().verify(&s, &group_key, &msg), Ok(()));
+
+        // Replaying gives the same partial signature
+        let mut replayed = SignerSession::from_slice(&s, &created).unwrap();
+        replayed.reveal(&commitments).unwrap();
+        assert_eq!(replayed.sign(&s, &shares[1].1, &nonces), Ok(partial));
+        // and other commitments than those the nonce was derived from fail
+        let mut replayed = SignerSession::from_slice(&s, &created).unwrap();
+        let c = SignerSession::new(&s, &mut thread_rng(), 1, group_key, msg).unwrap();
+        assert_eq!(replayed.reveal(&[(1, c.commitment()), (2, b.commitment())]), Err(InvalidCoSigningSession));
+
+        // The next counter value gives another nonce
+        let next = SignerSession::new_deterministic(&s, &mut account, &shares[1].1, params).unwrap();
+        assert!(next.commitment() != b.commitment());
+        let repeated = [(2, a.commitment())];
+        let params_repeated = DeterministicParams { others: &repeated, ..params };
+        assert_eq!(SignerSession::new_deterministic(&s, &mut account, &shares[1].1, params_repeated).err(),
+                   Some(InvalidCoSigningSession));
+
+        // A counter which doesn't advance is refused
+        let mut stuck = NonceAccount::new(Counter { value: 7, step: 0 });
+        assert!(SignerSession::new_deterministic(&s, &mut stuck, &shares[1].1, params).is_ok());
+        assert_eq!(SignerSession::new_deterministic(&s, &mut stuck, &shares[1].1, params).err(),
+                   Some(NonceCounterNotMonotonic));
+        assert_eq!(stuck.into_inner().value, 7);
+    }
+}
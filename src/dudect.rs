@@ -0,0 +1,271 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Timing leakage tests
+//! Statistical tests in the style of dudect ("dude, is my code constant
+//! time?") for the operations which handle secrets: signing, ECDH and
+//! secret key comparison. Each run times the operation many times, once
+//! with a fixed secret and once with a fresh random one, in random order,
+//! and compares the two timing distributions with Welch's t-test. A large
+//! |t| means the time depends on the secret, which a malicious OS timing
+//! ECALLs could exploit; checking it from inside the enclave validates the
+//! build flags and platform actually deployed.
+//!
+//! A small |t| is no proof of constant time: it only says that this many
+//! measurements, on this platform, with this timer, found nothing. Time is
+//! read from a `bench::Timer`, once before and once after every operation,
+//! so the timer must be much finer than one operation (e.g. `Rdtsc`).
+//! Requires compilation with the "dudect" feature.
+
+use std::vec::Vec;
+
+use rand_core::{RngCore, CryptoRng};
+
+use bench::Timer;
+use constants;
+use ecdh::SharedSecret;
+use key::{PublicKey, SecretKey};
+use super::Error::{self, TimeUnavailable};
+use {Message, Secp256k1, Signing};
+
+/// The |t| above which a `Leakage` counts as leaking. dudect itself
+/// calls 4.5 "probably" and 10 "definitely" not constant time.
+pub const T_THRESHOLD: f64 = 4.5;
+
+/// The percentage of fastest measurements kept for the cropped test. The
+/// slowest ones are mostly interrupts and enclave exits, which add noise
+/// unrelated to the secret.
+const CROP_PERCENTILE: usize = 90;
+
+/// An operation on a secret whose timing is tested
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Target {
+    /// `Secp256k1::sign` with the secret as the key
+    Sign,
+    /// `ecdh::SharedSecret::new` with the secret as the key
+    Ecdh,
+    /// `SecretKey::ct_eq` of a fixed key with the secret
+    Compare,
+}
+
+impl Target {
+    /// Every target, in the order `run` tests them
+    pub const ALL: [Target; 3] = [Target::Sign, Target::Ecdh, Target::Compare];
+}
+
+/// Running mean and variance of one class of measurements
+#[derive(Copy, Clone, Debug)]
+struct Moments {
+    n: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn new() -> Moments {
+        Moments { n: 0, mean: 0.0, m2: 0.0 }
+    }
+
+    fn push(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        if self.n < 2 { 0.0 } else { self.m2 / (self.n - 1) as f64 }
+    }
+}
+
+/// Welch's t statistic of two classes: 0 if their means agree, infinite if
+/// they differ without any variance
+fn welch_t(a: &Moments, b: &Moments) -> f64 {
+    if a.n == 0 || b.n == 0 || a.mean == b.mean {
+        return 0.0;
+    }
+    let se = (a.variance() / a.n as f64 + b.variance() / b.n as f64).sqrt();
+    (a.mean - b.mean) / se
+}
+
+/// The result of testing one target
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Leakage {
+    /// The operation tested
+    pub target: Target,
+    /// The number of measurements with the fixed secret, then with random
+    /// ones
+    pub samples: [u64; 2],
+    /// Welch's t statistic over all measurements
+    pub t: f64,
+    /// Welch's t statistic over the fastest `CROP_PERCENTILE` percent of the
+    /// measurements
+    pub t_cropped: f64,
+}
+
+impl Leakage {
+    /// The larger |t| of the two tests
+    pub fn max_t(&self) -> f64 {
+        let (t, t_cropped) = (self.t.abs(), self.t_cropped.abs());
+        if t > t_cropped { t } else { t_cropped }
+    }
+
+    /// Whether either test found a timing difference above `T_THRESHOLD`
+    pub fn is_leaking(&self) -> bool {
+        self.max_t() > T_THRESHOLD
+    }
+}
+
+/// Times `target` `samples` times, each with the fixed secret or a random
+/// one as decided by `rng`, and tests whether the timings differ. Fails
+/// with `TimeUnavailable` if the timer can't be read.
+pub fn test<C: Signing, T: Timer, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, timer: &mut T, rng: &mut R,
+                                                         target: Target, samples: u32) -> Result<Leakage, Error> {
+    let fixed = SecretKey::from_slice(&[0xcd; constants::SECRET_KEY_SIZE]).expect("valid fixed key");
+    let point = PublicKey::from_secret_key(secp, &SecretKey::new(secp, rng));
+    let msg = Message::from_slice(&[0x5a; constants::MESSAGE_SIZE]).expect("32-byte message");
+
+    let mut measurements = Vec::with_capacity(samples as usize);
+    for _ in 0..samples {
+        let class = (rng.next_u32() & 1) as usize;
+        let secret = if class == 0 { fixed } else { SecretKey::new(secp, rng) };
+        let start = timer.now().ok_or(TimeUnavailable)?;
+        match target {
+            Target::Sign => { secp.sign(&msg, &secret); }
+            Target::Ecdh => { SharedSecret::new(secp, &point, &secret); }
+            Target::Compare => { fixed.ct_eq(&secret); }
+        }
+        let end = timer.now().ok_or(TimeUnavailable)?;
+        measurements.push((class, end.wrapping_sub(start)));
+    }
+
+    let mut sorted: Vec<u64> = measurements.iter().map(|&(_, ticks)| ticks).collect();
+    sorted.sort_unstable();
+    let crop = sorted.get(sorted.len() * CROP_PERCENTILE / 100).cloned().unwrap_or(0);
+
+    let mut all = [Moments::new(); 2];
+    let mut cropped = [Moments::new(); 2];
+    for &(class, ticks) in &measurements {
+        all[class].push(ticks as f64);
+        if ticks <= crop {
+            cropped[class].push(ticks as f64);
+        }
+    }
+    Ok(Leakage {
+        target,
+        samples: [all[0].n, all[1].n],
+        t: welch_t(&all[0], &all[1]),
+        t_cropped: welch_t(&cropped[0], &cropped[1]),
+    })
+}
+
+/// Tests every target with `samples` measurements each, in the order of
+/// `Target::ALL`
+pub fn run<C: Signing, T: Timer, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, timer: &mut T, rng: &mut R,
+                                                        samples: u32) -> Result<[Leakage; 3], Error> {
+    Ok([
+        test(secp, timer, rng, Target::Sign, samples)?,
+        test(secp, timer, rng, Target::Ecdh, samples)?,
+        test(secp, timer, rng, Target::Compare, samples)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, test, welch_t, Leakage, Moments, Target, T_THRESHOLD};
+    use super::super::Error::TimeUnavailable;
+    use bench::Timer;
+    use rng::thread_rng;
+    use Secp256k1;
+
+    // A timer which ticks once per reading, so every operation takes 1
+    struct Ticking(u64);
+
+    impl Timer for Ticking {
+        fn now(&mut self) -> Option<u64> {
+            self.0 += 1;
+            Some(self.0)
+        }
+    }
+
+    struct Broken;
+
+    impl Timer for Broken {
+        fn now(&mut self) -> Option<u64> {
+            None
+        }
+    }
+
+    #[test]
+    fn welch() {
+        let mut a = Moments::new();
+        let mut b = Moments::new();
+        for &x in &[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            a.push(x);
+            b.push(x + 1.0);
+        }
+        assert_eq!(a.mean, 5.0);
+        assert!((a.variance() - 32.0 / 7.0).abs() < 1e-9);
+        // Means 1 apart, standard error sqrt(2 * (32 / 7) / 8)
+        assert!((welch_t(&a, &b) + 1.0 / (8.0f64 / 7.0).sqrt()).abs() < 1e-9);
+        assert_eq!(welch_t(&a, &a), 0.0);
+        assert_eq!(welch_t(&a, &Moments::new()), 0.0);
+
+        let mut c = Moments::new();
+        c.push(3.0);
+        c.push(3.0);
+        assert_eq!(welch_t(&c, &Moments::new()), 0.0);
+        let mut d = c;
+        d.push(3.0);
+        assert_eq!(welch_t(&c, &d), 0.0);
+        d.push(7.0);
+        assert!(welch_t(&c, &d).is_finite());
+    }
+
+    #[test]
+    fn leakage() {
+        let leakage = Leakage { target: Target::Sign, samples: [10, 10], t: -12.0, t_cropped: 3.0 };
+        assert_eq!(leakage.max_t(), 12.0);
+        assert!(leakage.is_leaking());
+        let leakage = Leakage { t: 1.0, t_cropped: -T_THRESHOLD, ..leakage };
+        assert_eq!(leakage.max_t(), T_THRESHOLD);
+        assert!(!leakage.is_leaking());
+    }
+
+    #[test]
+    fn constant_timer() {
+        let s = Secp256k1::new();
+        let results = run(&s, &mut Ticking(0), &mut thread_rng(), 64).unwrap();
+        for (leakage, &target) in results.iter().zip(Target::ALL.iter()) {
+            assert_eq!(leakage.target, target);
+            assert_eq!(leakage.samples[0] + leakage.samples[1], 64);
+            assert_eq!((leakage.t, leakage.t_cropped), (0.0, 0.0));
+            assert!(!leakage.is_leaking());
+        }
+        assert_eq!(test(&s, &mut Broken, &mut thread_rng(), Target::Compare, 1), Err(TimeUnavailable));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn rdtsc() {
+        use bench::Rdtsc;
+
+        // Tests don't run in SGX1 enclaves. Timings on a shared test machine
+        // are too noisy to assert on |t|.
+        let mut timer = unsafe { Rdtsc::new() };
+        let leakage = test(&Secp256k1::new(), &mut timer, &mut thread_rng(), Target::Compare, 1000).unwrap();
+        assert_eq!(leakage.samples[0] + leakage.samples[1], 1000);
+        assert!(leakage.t.is_finite() && leakage.t_cropped.is_finite());
+    }
+}
@@ -16,11 +16,37 @@
 //! Support for shared secret computations
 //!
 
-use std::ops;
+use std::{ops, ptr};
 
-use super::Secp256k1;
+use super::{Secp256k1, Verification};
+use cleanup;
+use constants;
 use key::{SecretKey, PublicKey};
 use ffi;
+#[cfg(feature = "hashes")]
+use hashes::TaggedHasher;
+use scalar::Scalar;
+
+/// The tag of the hash in `SharedSecret::new_for_hop`
+#[cfg(feature = "hashes")]
+pub const HOP_TAG: &[u8] = b"rust-secp256k1-sgx/chained-ecdh";
+
+/// Applies `scalar` to a point received from the previous party of a
+/// multi-party (chained) Diffie-Hellman exchange, returning `scalar * point`
+/// to pass on to the next party. For three parties with secret keys `a`,
+/// `b` and `c`, Bob turns Alice's `aG` into `abG` for Carol, who gets the
+/// shared secret from it with her own key. Requires a verify-capable
+/// context.
+pub fn chain_point<C: Verification>(secp: &Secp256k1<C>, point: &PublicKey, scalar: &SecretKey) -> PublicKey {
+    let mut tweak = Scalar::from_secret_key(scalar);
+    let mut ret = *point;
+    let res = ret.mul_assign(secp, &tweak);
+    tweak.wipe();
+    // A valid secret key is a valid tweak, and the product of a point with
+    // a nonzero scalar is never infinity
+    assert!(res.is_ok());
+    ret
+}
 
 /// A tag used for recovering the public key from a compact signature
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -30,12 +56,61 @@ impl SharedSecret {
     /// Creates a new shared secret from a pubkey and secret key
     #[inline]
     pub fn new<C>(secp: &Secp256k1<C>, point: &PublicKey, scalar: &SecretKey) -> SharedSecret {
-        unsafe {
+        measure!(Ecdh, |_| true, unsafe {
             let mut ss = ffi::SharedSecret::blank();
             let res = ffi::secp256k1_ecdh(secp.ctx, &mut ss, point.as_ptr(), scalar.as_ptr());
-            debug_assert_eq!(res, 1);
+            // Only fails for a secret key which is zero or out of range
+            assert!(res == 1);
+            cleanup::scrub_stack();
+            SharedSecret(ss)
+        })
+    }
+
+    /// Creates a shared secret by passing the affine coordinates `x` and `y`
+    /// (32 bytes each, big-endian) of the point `scalar * point` to
+    /// `hash_function`, for protocols which derive the secret differently
+    /// from `new`, which hashes the compressed point with SHA-256. Returning
+    /// `x` itself gives the raw x-coordinate used by e.g. BIP324. The
+    /// coordinates are wiped once `hash_function` returns. Requires a
+    /// verify-capable context.
+    pub fn new_with_hash<C: Verification, F>(secp: &Secp256k1<C>, point: &PublicKey, scalar: &SecretKey,
+                                             mut hash_function: F) -> SharedSecret
+        where F: FnMut(&[u8; 32], &[u8; 32]) -> [u8; 32]
+    {
+        measure!(Ecdh, |_| true, {
+            let mut ser = chain_point(secp, point, scalar).serialize_uncompressed();
+            let mut x = [0u8; 32];
+            let mut y = [0u8; 32];
+            x.copy_from_slice(&ser[1..33]);
+            y.copy_from_slice(&ser[33..constants::UNCOMPRESSED_PUBLIC_KEY_SIZE]);
+            let mut out = hash_function(&x, &y);
+            let mut ss = ffi::SharedSecret::new();
+            unsafe { ptr::copy_nonoverlapping(out.as_ptr(), ss.as_mut_ptr(), out.len()); }
+            cleanup::wipe(&mut out);
+            cleanup::wipe(&mut ser);
+            cleanup::wipe(&mut x);
+            cleanup::wipe(&mut y);
+            cleanup::scrub_stack();
             SharedSecret(ss)
-        }
+        })
+    }
+
+    /// Creates the shared secret for hop `hop` of a chained exchange (see
+    /// `chain_point`) or of a route of pairwise exchanges, as the tagged
+    /// hash of the hop number (big-endian) and the compressed point
+    /// `scalar * point`. Including the hop keeps the secrets of different
+    /// hops apart even if a point is reused. Requires a verify-capable
+    /// context. Requires compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn new_for_hop<C: Verification>(secp: &Secp256k1<C>, point: &PublicKey, scalar: &SecretKey, hop: u32)
+                                        -> SharedSecret {
+        SharedSecret::new_with_hash(secp, point, scalar, |x, y| {
+            let mut engine = TaggedHasher::new(HOP_TAG);
+            engine.input(&[(hop >> 24) as u8, (hop >> 16) as u8, (hop >> 8) as u8, hop as u8]);
+            engine.input(&[0x02 | (y[31] & 1)]);
+            engine.input(x);
+            engine.result()
+        })
     }
 
     /// Obtains a raw pointer suitable for use with FFI functions
@@ -43,6 +118,13 @@ impl SharedSecret {
     pub fn as_ptr(&self) -> *const ffi::SharedSecret {
         &self.0 as *const _
     }
+
+    /// Compares two shared secrets in constant time. This is what `==` does
+    /// as well; the method exists to make the intent explicit at call sites.
+    #[inline]
+    pub fn ct_eq(&self, other: &SharedSecret) -> bool {
+        ::constant_time_eq(&self.0[..], &other.0[..])
+    }
 }
 
 /// Creates a new shared secret from a FFI shared secret
@@ -92,8 +174,9 @@ impl ops::Index<ops::RangeFull> for SharedSecret {
 
 #[cfg(test)]
 mod tests {
-    use rand::thread_rng;
-    use super::SharedSecret;
+    use rng::thread_rng;
+    use scalar::Scalar;
+    use super::{chain_point, SharedSecret};
     use super::super::Secp256k1;
 
     #[test]
@@ -107,12 +190,88 @@ mod tests {
         let sec_odd = SharedSecret::new(&s, &pk1, &sk1);
         assert_eq!(sec1, sec2);
         assert!(sec_odd != sec2);
+        assert!(sec1.ct_eq(&sec2));
+        assert!(!sec_odd.ct_eq(&sec2));
+    }
+
+    #[test]
+    fn ecdh_with_hash() {
+        let s = Secp256k1::new();
+        let (sk1, pk1) = s.generate_keypair(&mut thread_rng());
+        let (sk2, pk2) = s.generate_keypair(&mut thread_rng());
+
+        let x_only = |x: &[u8; 32], _: &[u8; 32]| *x;
+        let sec1 = SharedSecret::new_with_hash(&s, &pk1, &sk2, x_only);
+        let sec2 = SharedSecret::new_with_hash(&s, &pk2, &sk1, x_only);
+        assert_eq!(sec1, sec2);
+
+        // The coordinates are those of sk1 * sk2 * G
+        let mut point = pk1;
+        point.mul_assign(&s, &Scalar::from_secret_key(&sk2)).unwrap();
+        let ser = point.serialize_uncompressed();
+        assert_eq!(sec1[..], ser[1..33]);
+        let y_only = SharedSecret::new_with_hash(&s, &pk1, &sk2, |_, y| *y);
+        assert_eq!(y_only[..], ser[33..]);
+    }
+
+    #[test]
+    fn chained() {
+        let s = Secp256k1::new();
+        let (a, a_pk) = s.generate_keypair(&mut thread_rng());
+        let (b, b_pk) = s.generate_keypair(&mut thread_rng());
+        let (c, c_pk) = s.generate_keypair(&mut thread_rng());
+
+        // Every party ends up with abcG, whatever the order
+        let ab = chain_point(&s, &a_pk, &b);
+        let bc = chain_point(&s, &b_pk, &c);
+        let ca = chain_point(&s, &c_pk, &a);
+        assert_eq!(ab, chain_point(&s, &b_pk, &a));
+        let abc = chain_point(&s, &ab, &c);
+        assert_eq!(chain_point(&s, &bc, &a), abc);
+        assert_eq!(chain_point(&s, &ca, &b), abc);
+        assert_eq!(SharedSecret::new(&s, &ab, &c), SharedSecret::new(&s, &bc, &a));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn hops() {
+        let s = Secp256k1::new();
+        let (a, a_pk) = s.generate_keypair(&mut thread_rng());
+        let (b, b_pk) = s.generate_keypair(&mut thread_rng());
+        let (c, c_pk) = s.generate_keypair(&mut thread_rng());
+
+        let carol = SharedSecret::new_for_hop(&s, &chain_point(&s, &a_pk, &b), &c, 2);
+        let alice = SharedSecret::new_for_hop(&s, &chain_point(&s, &b_pk, &c), &a, 2);
+        assert_eq!(carol, alice);
+        // Each hop gets its own secret from the same point
+        assert!(SharedSecret::new_for_hop(&s, &chain_point(&s, &a_pk, &b), &c, 1) != carol);
+        assert!(SharedSecret::new(&s, &chain_point(&s, &a_pk, &b), &c) != carol);
+        assert_eq!(SharedSecret::new_for_hop(&s, &c_pk, &a, 0), SharedSecret::new_for_hop(&s, &a_pk, &c, 0));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn ecdh_with_hash_default() {
+        use hashes::Sha256;
+
+        let s = Secp256k1::new();
+        let (sk1, _) = s.generate_keypair(&mut thread_rng());
+        let (_, pk2) = s.generate_keypair(&mut thread_rng());
+
+        // Hashing the compressed point reproduces `new`
+        let sec = SharedSecret::new_with_hash(&s, &pk2, &sk1, |x, y| {
+            let mut engine = Sha256::new();
+            engine.input(&[0x02 | (y[31] & 1)]);
+            engine.input(x);
+            engine.result()
+        });
+        assert_eq!(sec, SharedSecret::new(&s, &pk2, &sk1));
     }
 }
 
 #[cfg(all(test, feature = "unstable"))]
 mod benches {
-    use rand::thread_rng;
+    use rng::thread_rng;
     use test::{Bencher, black_box};
 
     use super::SharedSecret;
@@ -0,0 +1,441 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # EIP-712
+//! Hashing of Ethereum typed structured data, so that an enclave wallet
+//! computes the digest it signs from the data itself rather than trusting
+//! a digest from the host. A struct type is built with `StructType::new`
+//! and `field`, a message is the `Value`s of its fields in order, and
+//! `signing_hash` combines it with the `Domain` into the message to sign
+//! with `Secp256k1::sign_recoverable`.
+//!
+//! Values are checked against their types: a `uint8` must fit in 8 bits,
+//! a `bytes4` must be 4 bytes long, a fixed-size array must have its size,
+//! and so on. Struct types are identified by name in the type encoding, so
+//! two struct types with the same name in one message must be the same.
+//! Requires compilation with the "ethereum" feature.
+
+use std::boxed::Box;
+use std::fmt::{self, Write};
+use std::string::String;
+use std::vec::Vec;
+
+use hashes::{keccak256, Keccak256};
+use super::Error::{self, InvalidTypedData};
+use Message;
+
+/// The type of a field
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Type {
+    /// `bool`
+    Bool,
+    /// `uintN`, with N a multiple of 8 from 8 to 256
+    Uint(u16),
+    /// `intN`, with N a multiple of 8 from 8 to 256
+    Int(u16),
+    /// `address`
+    Address,
+    /// `bytesN`, with N from 1 to 32
+    FixedBytes(u8),
+    /// `bytes`
+    Bytes,
+    /// `string`
+    String,
+    /// `T[]`, or `T[n]` with a size
+    Array(Box<Type>, Option<usize>),
+    /// A struct
+    Struct(StructType),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Type::Bool => f.write_str("bool"),
+            Type::Uint(bits) => write!(f, "uint{}", bits),
+            Type::Int(bits) => write!(f, "int{}", bits),
+            Type::Address => f.write_str("address"),
+            Type::FixedBytes(len) => write!(f, "bytes{}", len),
+            Type::Bytes => f.write_str("bytes"),
+            Type::String => f.write_str("string"),
+            Type::Array(ref elem, Some(len)) => write!(f, "{}[{}]", elem, len),
+            Type::Array(ref elem, None) => write!(f, "{}[]", elem),
+            Type::Struct(ref ty) => f.write_str(&ty.name),
+        }
+    }
+}
+
+/// The value of a field
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Value {
+    /// A `bool`
+    Bool(bool),
+    /// A `uintN`, as 32 big-endian bytes
+    Uint([u8; 32]),
+    /// An `intN`, as 32 big-endian bytes of two's complement
+    Int([u8; 32]),
+    /// An `address`
+    Address([u8; 20]),
+    /// A `bytesN`
+    FixedBytes(Vec<u8>),
+    /// A `bytes`
+    Bytes(Vec<u8>),
+    /// A `string`
+    String(String),
+    /// An array, of any size
+    Array(Vec<Value>),
+    /// A struct, the values of its fields in order
+    Struct(Vec<Value>),
+}
+
+impl Value {
+    /// The `uintN` `n`
+    pub fn uint(n: u64) -> Value {
+        let mut ret = [0; 32];
+        ret[24..].copy_from_slice(&n.to_be_bytes());
+        Value::Uint(ret)
+    }
+
+    /// The `intN` `n`
+    pub fn int(n: i64) -> Value {
+        let mut ret = if n < 0 { [0xff; 32] } else { [0; 32] };
+        ret[24..].copy_from_slice(&n.to_be_bytes());
+        Value::Int(ret)
+    }
+
+    /// The `string` `s`
+    pub fn string(s: &str) -> Value {
+        Value::String(s.into())
+    }
+}
+
+/// A struct type: its name and its fields' names and types, in order
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StructType {
+    name: String,
+    fields: Vec<(String, Type)>,
+}
+
+impl StructType {
+    /// Starts the struct type `name`, without fields
+    pub fn new(name: &str) -> StructType {
+        StructType {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds the field `name` of type `ty`
+    pub fn field(mut self, name: &str, ty: Type) -> StructType {
+        self.fields.push((name.into(), ty));
+        self
+    }
+
+    /// The name of the type
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The type encoding: this type followed by the struct types it
+    /// references, directly or not, sorted by name
+    pub fn encode_type(&self) -> String {
+        let mut deps = vec![self];
+        self.collect_deps(&mut deps);
+        deps[1..].sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut ret = String::new();
+        for ty in deps {
+            ret.push_str(&ty.name);
+            ret.push('(');
+            for (i, (name, field)) in ty.fields.iter().enumerate() {
+                if i > 0 {
+                    ret.push(',');
+                }
+                write!(ret, "{} {}", field, name).expect("writing to a string");
+            }
+            ret.push(')');
+        }
+        ret
+    }
+
+    /// The Keccak-256 of the type encoding
+    pub fn type_hash(&self) -> [u8; 32] {
+        keccak256(self.encode_type().as_bytes())
+    }
+
+    /// The hash of the struct with the field values `values`. Fails with
+    /// `InvalidTypedData` if they do not match the fields' types.
+    pub fn hash_struct(&self, values: &[Value]) -> Result<[u8; 32], Error> {
+        if values.len() != self.fields.len() {
+            return Err(InvalidTypedData);
+        }
+        let mut engine = Keccak256::new();
+        engine.input(&self.type_hash());
+        for ((_, ty), value) in self.fields.iter().zip(values) {
+            engine.input(&encode_value(ty, value)?);
+        }
+        Ok(engine.result())
+    }
+
+    /// Adds the struct types referenced by this one which are not in `deps`
+    fn collect_deps<'a>(&'a self, deps: &mut Vec<&'a StructType>) {
+        for (_, field) in &self.fields {
+            let mut ty = field;
+            while let Type::Array(ref elem, _) = *ty {
+                ty = elem;
+            }
+            if let Type::Struct(ref inner) = *ty {
+                if !deps.iter().any(|dep| dep.name == inner.name) {
+                    deps.push(inner);
+                    inner.collect_deps(deps);
+                }
+            }
+        }
+    }
+}
+
+/// The 32-byte encoding of `value` as a field of type `ty`
+fn encode_value(ty: &Type, value: &Value) -> Result<[u8; 32], Error> {
+    let mut ret = [0u8; 32];
+    match (ty, value) {
+        (Type::Bool, Value::Bool(b)) => ret[31] = *b as u8,
+        (&Type::Uint(bits), Value::Uint(n)) if valid_bits(bits) => {
+            if n[..32 - bits as usize / 8].iter().any(|&b| b != 0) {
+                return Err(InvalidTypedData);
+            }
+            ret = *n;
+        }
+        (&Type::Int(bits), Value::Int(n)) if valid_bits(bits) => {
+            // Everything above the top bit must be copies of it
+            let top = 32 - bits as usize / 8;
+            let extension = if n[top] & 0x80 != 0 { 0xff } else { 0 };
+            if n[..top].iter().any(|&b| b != extension) {
+                return Err(InvalidTypedData);
+            }
+            ret = *n;
+        }
+        (Type::Address, Value::Address(address)) => ret[12..].copy_from_slice(address),
+        (&Type::FixedBytes(len), Value::FixedBytes(bytes)) if (1..=32).contains(&len) => {
+            if bytes.len() != len as usize {
+                return Err(InvalidTypedData);
+            }
+            ret[..bytes.len()].copy_from_slice(bytes);
+        }
+        (Type::Bytes, Value::Bytes(bytes)) => ret = keccak256(bytes),
+        (Type::String, Value::String(s)) => ret = keccak256(s.as_bytes()),
+        (Type::Array(elem, len), Value::Array(items)) => {
+            if len.is_some_and(|len| len != items.len()) {
+                return Err(InvalidTypedData);
+            }
+            let mut engine = Keccak256::new();
+            for item in items {
+                engine.input(&encode_value(elem, item)?);
+            }
+            ret = engine.result();
+        }
+        (Type::Struct(inner), Value::Struct(values)) => ret = inner.hash_struct(values)?,
+        _ => return Err(InvalidTypedData),
+    }
+    Ok(ret)
+}
+
+/// Whether `uintN` and `intN` exist for `bits`
+fn valid_bits(bits: u16) -> bool {
+    (8..=256).contains(&bits) && bits & 7 == 0
+}
+
+/// The EIP-712 domain, which keeps signatures for one application (and
+/// chain, and contract) from being valid for another. Only the fields
+/// which are set are part of it.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Domain {
+    name: Option<String>,
+    version: Option<String>,
+    chain_id: Option<u64>,
+    verifying_contract: Option<[u8; 20]>,
+    salt: Option<[u8; 32]>,
+}
+
+impl Domain {
+    /// Starts a domain without fields
+    pub fn new() -> Domain {
+        Domain::default()
+    }
+
+    /// Sets the name of the application or protocol
+    pub fn name(mut self, name: &str) -> Domain {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the version of the application or protocol
+    pub fn version(mut self, version: &str) -> Domain {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Sets the EIP-155 chain ID
+    pub fn chain_id(mut self, chain_id: u64) -> Domain {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// Sets the address of the contract which verifies the signature
+    pub fn verifying_contract(mut self, address: [u8; 20]) -> Domain {
+        self.verifying_contract = Some(address);
+        self
+    }
+
+    /// Sets the salt
+    pub fn salt(mut self, salt: [u8; 32]) -> Domain {
+        self.salt = Some(salt);
+        self
+    }
+
+    /// The domain separator: the hash of the `EIP712Domain` struct with the
+    /// fields which are set
+    pub fn separator(&self) -> [u8; 32] {
+        let mut ty = StructType::new("EIP712Domain");
+        let mut values = Vec::new();
+        if let Some(ref name) = self.name {
+            ty = ty.field("name", Type::String);
+            values.push(Value::string(name));
+        }
+        if let Some(ref version) = self.version {
+            ty = ty.field("version", Type::String);
+            values.push(Value::string(version));
+        }
+        if let Some(chain_id) = self.chain_id {
+            ty = ty.field("chainId", Type::Uint(256));
+            values.push(Value::uint(chain_id));
+        }
+        if let Some(address) = self.verifying_contract {
+            ty = ty.field("verifyingContract", Type::Address);
+            values.push(Value::Address(address));
+        }
+        if let Some(salt) = self.salt {
+            ty = ty.field("salt", Type::FixedBytes(32));
+            values.push(Value::FixedBytes(salt.to_vec()));
+        }
+        ty.hash_struct(&values).expect("domain values match their types")
+    }
+}
+
+/// The message to sign for the struct of type `ty` with the field values
+/// `values` in `domain`: the Keccak-256 of `0x19 0x01`, the domain
+/// separator and the struct hash. Fails with `InvalidTypedData` if the
+/// values do not match the fields' types.
+pub fn signing_hash(domain: &Domain, ty: &StructType, values: &[Value]) -> Result<Message, Error> {
+    let mut engine = Keccak256::new();
+    engine.input(&[0x19, 0x01]);
+    engine.input(&domain.separator());
+    engine.input(&ty.hash_struct(values)?);
+    Ok(Message::from(engine.result()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use hashes::keccak256;
+    use hex::decode_array;
+    use super::{signing_hash, Domain, StructType, Type, Value};
+    use super::super::Error::InvalidTypedData;
+    use {Secp256k1, SecretKey};
+
+    fn person() -> StructType {
+        StructType::new("Person").field("name", Type::String).field("wallet", Type::Address)
+    }
+
+    // The example of the EIP itself
+    #[test]
+    fn mail() {
+        let mail = StructType::new("Mail")
+            .field("from", Type::Struct(person()))
+            .field("to", Type::Struct(person()))
+            .field("contents", Type::String);
+        assert_eq!(mail.encode_type(), "Mail(Person from,Person to,string contents)Person(string name,address wallet)");
+        assert_eq!(mail.type_hash(), decode_array::<32>("a0cedeb2dc280ba39b857546d74f5549c3a1d7bdc2dd96bf881f76108e23dac2"));
+
+        let cow = decode_array::<20>("CD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826");
+        let bob = decode_array::<20>("bBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB");
+        let values = [
+            Value::Struct(vec![Value::string("Cow"), Value::Address(cow)]),
+            Value::Struct(vec![Value::string("Bob"), Value::Address(bob)]),
+            Value::string("Hello, Bob!"),
+        ];
+        assert_eq!(mail.hash_struct(&values),
+                   Ok(decode_array::<32>("c52c0ee5d84264471806290a3f2c4cecfc5490626bf912d01f240d7a274b371e")));
+
+        let domain = Domain::new()
+            .name("Ether Mail")
+            .version("1")
+            .chain_id(1)
+            .verifying_contract(decode_array::<20>("CcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"));
+        assert_eq!(domain.separator(),
+                   decode_array::<32>("f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"));
+        let msg = signing_hash(&domain, &mail, &values).unwrap();
+        assert_eq!(msg[..], decode_array::<32>("be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"));
+
+        // Signed by Cow, whose key is the hash of "cow"
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&keccak256(b"cow")).unwrap();
+        let sig = s.sign_recoverable(&msg, &sk);
+        let mut rsv = sig.serialize_compact_rsv();
+        rsv[64] += 27;
+        assert_eq!(rsv[..], hex!("4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+                                  07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562\
+                                  1c")[..]);
+        let pk = s.recover(&msg, &sig).unwrap();
+        assert_eq!(keccak256(&pk.serialize_uncompressed()[1..])[12..], cow[..]);
+    }
+
+    #[test]
+    fn type_checks() {
+        let ty = StructType::new("T")
+            .field("a", Type::Uint(8))
+            .field("b", Type::Int(16))
+            .field("c", Type::FixedBytes(2))
+            .field("d", Type::Array(Box::new(Type::Struct(person())), Some(1)))
+            .field("e", Type::Array(Box::new(Type::Bool), None));
+        assert_eq!(ty.encode_type(), "T(uint8 a,int16 b,bytes2 c,Person[1] d,bool[] e)Person(string name,address wallet)");
+        let values = vec![
+            Value::uint(255),
+            Value::int(-32768),
+            Value::FixedBytes(vec![1, 2]),
+            Value::Array(vec![Value::Struct(vec![Value::string("Cow"), Value::Address([0; 20])])]),
+            Value::Array(vec![]),
+        ];
+        assert!(ty.hash_struct(&values).is_ok());
+
+        let bad = [
+            (0, Value::uint(256)),
+            (0, Value::int(1)),
+            (1, Value::int(32768)),
+            (1, Value::int(-32769)),
+            (2, Value::FixedBytes(vec![1, 2, 3])),
+            (3, Value::Array(vec![])),
+            (3, Value::Array(vec![Value::Struct(vec![Value::string("Cow")])])),
+            (4, Value::Array(vec![Value::uint(1)])),
+        ];
+        for &(i, ref value) in bad.iter() {
+            let mut changed = values.clone();
+            changed[i] = value.clone();
+            assert_eq!(ty.hash_struct(&changed), Err(InvalidTypedData));
+        }
+        assert_eq!(ty.hash_struct(&values[..4]), Err(InvalidTypedData));
+        let odd = StructType::new("Odd").field("x", Type::Uint(7));
+        assert_eq!(odd.hash_struct(&[Value::uint(1)]), Err(InvalidTypedData));
+    }
+}
@@ -0,0 +1,471 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Encrypted secret keys
+//! A portable file format for a secret key encrypted under a 32-byte key
+//! encryption key (KEK) or a passphrase, for provisioning keys into an
+//! enclave. Unlike SGX sealing it is bound to no platform or enclave
+//! identity: anyone holding the KEK or passphrase can decrypt it, so it
+//! suits moving a key in from a provisioning service or an offline backup,
+//! after which the enclave seals it for itself.
+//!
+//! The key is encrypted with XChaCha20-Poly1305 under a key derived from
+//! the credential and a random salt: with HKDF-SHA256 from a KEK, and with
+//! scrypt ("scrypt-kdf" feature) or Argon2id ("argon2-kdf" feature) from a
+//! passphrase. The layout is the magic `SKEY` (4 bytes), the version (1),
+//! the KDF (1), its three parameters (4 each, little-endian), the salt
+//! (16), the nonce (24), the encrypted key (32) and the tag (16). The
+//! header up to the nonce is authenticated, so a file whose parameters
+//! were tampered with fails to decrypt. Requires compilation with the
+//! "encrypted-keys" feature.
+
+use std::{fmt, str};
+
+use chacha20poly1305::{Key, Tag, XChaCha20Poly1305, XNonce};
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use rand_core::{RngCore, CryptoRng};
+
+use cleanup::wipe;
+use constants;
+use hashes::hkdf_sha256;
+use hex;
+use key::SecretKey;
+use super::Error::{self, InvalidEncryptedKey, KeyDecryptionFailed};
+
+/// The size (in bytes) of an encrypted secret key
+pub const ENCRYPTED_KEY_SIZE: usize = HEADER_SIZE + NONCE_SIZE + constants::SECRET_KEY_SIZE + TAG_SIZE;
+
+/// The version `SecretKey::to_encrypted` writes
+pub const ENCRYPTED_KEY_VERSION: u8 = 1;
+
+/// The most memory a KDF may be asked to use, in bytes. Decryption refuses
+/// parameters beyond it, so a hostile file can't exhaust the enclave heap.
+pub const MAX_KDF_MEMORY: u64 = 1 << 30;
+
+const MAGIC: &'static [u8; 4] = b"SKEY";
+/// Magic, version, KDF, parameters and salt: everything authenticated
+const HEADER_SIZE: usize = 18 + SALT_SIZE;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 24;
+const TAG_SIZE: usize = 16;
+const KEK_INFO: &'static [u8] = b"rust-secp256k1-sgx encrypted key v1";
+
+const KDF_KEK: u8 = 0;
+#[cfg(feature = "scrypt-kdf")]
+const KDF_SCRYPT: u8 = 1;
+#[cfg(feature = "argon2-kdf")]
+const KDF_ARGON2ID: u8 = 2;
+
+/// What a secret key is encrypted under, and how the encryption key is
+/// derived from it
+#[derive(Copy, Clone, Debug)]
+pub enum Protection<'a> {
+    /// A 32-byte key encryption key, stretched with HKDF-SHA256
+    Kek(&'a [u8; 32]),
+    /// A passphrase stretched with scrypt, with cost `2^log_n`, block size
+    /// `r` and parallelism `p`
+    #[cfg(feature = "scrypt-kdf")]
+    Scrypt {
+        /// The passphrase
+        passphrase: &'a [u8],
+        /// The base-2 logarithm of the cost
+        log_n: u8,
+        /// The block size
+        r: u32,
+        /// The parallelism
+        p: u32,
+    },
+    /// A passphrase stretched with Argon2id
+    #[cfg(feature = "argon2-kdf")]
+    Argon2id {
+        /// The passphrase
+        passphrase: &'a [u8],
+        /// The memory to use, in KiB
+        memory_kib: u32,
+        /// The number of passes over the memory
+        iterations: u32,
+        /// The degree of parallelism
+        lanes: u32,
+    },
+}
+
+impl<'a> Protection<'a> {
+    /// Scrypt with cost 2^15, block size 8 and parallelism 1, which takes
+    /// 32 MiB and fits a typical enclave heap
+    #[cfg(feature = "scrypt-kdf")]
+    pub fn scrypt(passphrase: &'a [u8]) -> Protection<'a> {
+        Protection::Scrypt { passphrase, log_n: 15, r: 8, p: 1 }
+    }
+
+    /// Argon2id with 19 MiB of memory, 2 iterations and 1 lane, as OWASP
+    /// recommends
+    #[cfg(feature = "argon2-kdf")]
+    pub fn argon2id(passphrase: &'a [u8]) -> Protection<'a> {
+        Protection::Argon2id { passphrase, memory_kib: 19 * 1024, iterations: 2, lanes: 1 }
+    }
+}
+
+/// What decrypts an `EncryptedSecretKey`
+#[derive(Copy, Clone, Debug)]
+pub enum Credential<'a> {
+    /// The key encryption key the secret key was encrypted under
+    Kek(&'a [u8; 32]),
+    /// The passphrase the secret key was encrypted under, whichever KDF
+    /// stretched it
+    Passphrase(&'a [u8]),
+}
+
+/// Fails for unknown KDFs, those this build doesn't have, and parameters
+/// out of range
+fn check_kdf(kdf: u8, params: [u32; 3]) -> Result<(), Error> {
+    let ok = match kdf {
+        KDF_KEK => params == [0; 3],
+        #[cfg(feature = "scrypt-kdf")]
+        KDF_SCRYPT => {
+            // Scrypt needs 128 * r * 2^log_n bytes
+            let [log_n, r, p] = params;
+            log_n < 32 && r <= 1 << 23 && p <= 16 && (128 * r as u64) << log_n <= MAX_KDF_MEMORY
+                && ::scrypt::Params::new(log_n as u8, r, p, 32).is_ok()
+        }
+        #[cfg(feature = "argon2-kdf")]
+        KDF_ARGON2ID => {
+            let [memory_kib, iterations, lanes] = params;
+            memory_kib as u64 * 1024 <= MAX_KDF_MEMORY && iterations <= 64 && lanes <= 16
+                && ::argon2::Params::new(memory_kib, iterations, lanes, Some(32)).is_ok()
+        }
+        _ => false,
+    };
+    if ok { Ok(()) } else { Err(InvalidEncryptedKey) }
+}
+
+/// Derives the encryption key from `secret` (a KEK or passphrase) with the
+/// checked KDF and parameters
+fn derive(kdf: u8, params: [u32; 3], secret: &[u8], salt: &[u8], key: &mut [u8; 32]) -> Result<(), Error> {
+    match kdf {
+        KDF_KEK => {
+            debug_assert_eq!(params, [0; 3]);
+            hkdf_sha256(salt, secret, KEK_INFO, key);
+            Ok(())
+        }
+        #[cfg(feature = "scrypt-kdf")]
+        KDF_SCRYPT => {
+            let params = ::scrypt::Params::new(params[0] as u8, params[1], params[2], 32)
+                .map_err(|_| InvalidEncryptedKey)?;
+            ::scrypt::scrypt(secret, salt, &params, key).map_err(|_| InvalidEncryptedKey)
+        }
+        #[cfg(feature = "argon2-kdf")]
+        KDF_ARGON2ID => {
+            let params = ::argon2::Params::new(params[0], params[1], params[2], Some(32))
+                .map_err(|_| InvalidEncryptedKey)?;
+            ::argon2::Argon2::new(::argon2::Algorithm::Argon2id, ::argon2::Version::V0x13, params)
+                .hash_password_into(secret, salt, key)
+                .map_err(|_| InvalidEncryptedKey)
+        }
+        _ => Err(InvalidEncryptedKey),
+    }
+}
+
+/// A secret key encrypted under a KEK or passphrase, in the format
+/// described in the module documentation
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EncryptedSecretKey([u8; ENCRYPTED_KEY_SIZE]);
+
+impl EncryptedSecretKey {
+    /// Parses an encrypted key, checking its header. Fails with
+    /// `InvalidEncryptedKey` if `data` is not an encrypted key of a known
+    /// version, or uses a KDF this build doesn't have or parameters beyond
+    /// `MAX_KDF_MEMORY`.
+    pub fn from_slice(data: &[u8]) -> Result<EncryptedSecretKey, Error> {
+        if data.len() != ENCRYPTED_KEY_SIZE || &data[..4] != MAGIC || data[4] != ENCRYPTED_KEY_VERSION {
+            return Err(InvalidEncryptedKey);
+        }
+        let mut ret = [0; ENCRYPTED_KEY_SIZE];
+        ret.copy_from_slice(data);
+        let ret = EncryptedSecretKey(ret);
+        check_kdf(ret.0[5], ret.params())?;
+        Ok(ret)
+    }
+
+    /// The encoded key
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; ENCRYPTED_KEY_SIZE] {
+        &self.0
+    }
+
+    /// Whether the key is encrypted under a passphrase rather than a KEK
+    #[inline]
+    pub fn is_passphrase_protected(&self) -> bool {
+        self.0[5] != KDF_KEK
+    }
+
+    fn params(&self) -> [u32; 3] {
+        let mut ret = [0; 3];
+        for (i, param) in ret.iter_mut().enumerate() {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&self.0[6 + 4 * i..10 + 4 * i]);
+            *param = u32::from_le_bytes(bytes);
+        }
+        ret
+    }
+
+    /// Decrypts the key, as `SecretKey::from_encrypted`
+    pub fn decrypt(&self, credential: Credential) -> Result<SecretKey, Error> {
+        let secret = match (credential, self.is_passphrase_protected()) {
+            (Credential::Kek(kek), false) => &kek[..],
+            (Credential::Passphrase(passphrase), true) => passphrase,
+            _ => return Err(KeyDecryptionFailed),
+        };
+        let (header, rest) = self.0.split_at(HEADER_SIZE);
+        let (nonce, rest) = rest.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(constants::SECRET_KEY_SIZE);
+
+        let mut key = [0; 32];
+        derive(self.0[5], self.params(), secret, &header[HEADER_SIZE - SALT_SIZE..], &mut key)?;
+        let mut data = [0; constants::SECRET_KEY_SIZE];
+        data.copy_from_slice(ciphertext);
+        let res = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt_in_place_detached(XNonce::from_slice(nonce), header, &mut data, Tag::from_slice(tag));
+        wipe(&mut key);
+        let ret = match res {
+            Ok(()) => SecretKey::from_slice(&data),
+            Err(_) => Err(KeyDecryptionFailed),
+        };
+        wipe(&mut data);
+        ret
+    }
+}
+
+impl SecretKey {
+    /// Encrypts the key under `protection`, with a salt and nonce drawn from
+    /// `rng`. Fails with `InvalidEncryptedKey` if the KDF parameters are out
+    /// of the range `EncryptedSecretKey::from_slice` accepts. With the
+    /// "production-hardening" feature this fails with `DebugEnclave` in a
+    /// debug enclave, and with "no-secret-export" it does not exist.
+    #[cfg(not(feature = "no-secret-export"))]
+    pub fn to_encrypted<R: RngCore + CryptoRng>(&self, protection: Protection, rng: &mut R)
+                                                -> Result<EncryptedSecretKey, Error> {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::check_secret_export()?;
+
+        let (kdf, params, secret): (u8, [u32; 3], &[u8]) = match protection {
+            Protection::Kek(kek) => (KDF_KEK, [0; 3], &kek[..]),
+            #[cfg(feature = "scrypt-kdf")]
+            Protection::Scrypt { passphrase, log_n, r, p } => (KDF_SCRYPT, [log_n as u32, r, p], passphrase),
+            #[cfg(feature = "argon2-kdf")]
+            Protection::Argon2id { passphrase, memory_kib, iterations, lanes } =>
+                (KDF_ARGON2ID, [memory_kib, iterations, lanes], passphrase),
+        };
+        check_kdf(kdf, params)?;
+
+        let mut ret = [0; ENCRYPTED_KEY_SIZE];
+        ret[..4].copy_from_slice(MAGIC);
+        ret[4] = ENCRYPTED_KEY_VERSION;
+        ret[5] = kdf;
+        for (i, param) in params.iter().enumerate() {
+            ret[6 + 4 * i..10 + 4 * i].copy_from_slice(&param.to_le_bytes());
+        }
+        rng.fill_bytes(&mut ret[HEADER_SIZE - SALT_SIZE..HEADER_SIZE + NONCE_SIZE]);
+
+        let (header, rest) = ret.split_at_mut(HEADER_SIZE);
+        let (nonce, rest) = rest.split_at_mut(NONCE_SIZE);
+        let (data, tag) = rest.split_at_mut(constants::SECRET_KEY_SIZE);
+        let mut key = [0; 32];
+        derive(kdf, params, secret, &header[HEADER_SIZE - SALT_SIZE..], &mut key)?;
+        data.copy_from_slice(self.secret_bytes());
+        let res = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt_in_place_detached(XNonce::from_slice(nonce), header, data);
+        wipe(&mut key);
+        // Encryption only fails for plaintexts beyond 2^38 bytes
+        tag.copy_from_slice(&res.expect("32-byte plaintext"));
+        Ok(EncryptedSecretKey(ret))
+    }
+
+    /// Decrypts a key encrypted with `to_encrypted`. Fails with
+    /// `KeyDecryptionFailed` if `credential` is wrong, of the wrong kind,
+    /// or the encrypted key was tampered with.
+    #[inline]
+    pub fn from_encrypted(encrypted: &EncryptedSecretKey, credential: Credential) -> Result<SecretKey, Error> {
+        encrypted.decrypt(credential)
+    }
+}
+
+impl fmt::Display for EncryptedSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        hex::format(&self.0, f)
+    }
+}
+
+impl fmt::Debug for EncryptedSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EncryptedSecretKey({})", self)
+    }
+}
+
+impl str::FromStr for EncryptedSecretKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<EncryptedSecretKey, Error> {
+        let mut ret = [0; ENCRYPTED_KEY_SIZE];
+        match hex::decode_from(s, &mut ret) {
+            Some(ENCRYPTED_KEY_SIZE) => EncryptedSecretKey::from_slice(&ret),
+            _ => Err(InvalidEncryptedKey),
+        }
+    }
+}
+
+/// Serializes as a hex string for human-readable formats and as bytes
+/// otherwise
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for EncryptedSecretKey {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            s.serialize_bytes(&self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for EncryptedSecretKey {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<EncryptedSecretKey, D::Error> {
+        use ::serde::de::Error;
+
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = EncryptedSecretKey;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded encrypted secret key")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<EncryptedSecretKey, E> {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        EncryptedSecretKey::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Credential, EncryptedSecretKey, Protection, ENCRYPTED_KEY_SIZE};
+    use key::SecretKey;
+    use rng::thread_rng;
+    use super::super::Error::{InvalidEncryptedKey, KeyDecryptionFailed};
+
+    #[test]
+    fn kek() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let kek = [0x11; 32];
+        let enc = sk.to_encrypted(Protection::Kek(&kek), &mut thread_rng()).unwrap();
+        assert_eq!(&enc.as_bytes()[..6], b"SKEY\x01\x00");
+        assert!(!enc.is_passphrase_protected());
+        assert_eq!(SecretKey::from_encrypted(&enc, Credential::Kek(&kek)), Ok(sk));
+
+        // Fresh salt and nonce every time
+        let again = sk.to_encrypted(Protection::Kek(&kek), &mut thread_rng()).unwrap();
+        assert!(again != enc);
+        assert_eq!(EncryptedSecretKey::from_slice(again.as_bytes()), Ok(again));
+        assert_eq!(again.decrypt(Credential::Kek(&kek)), Ok(sk));
+
+        assert_eq!(enc.decrypt(Credential::Kek(&[0x12; 32])), Err(KeyDecryptionFailed));
+        assert_eq!(enc.decrypt(Credential::Passphrase(b"hunter2")), Err(KeyDecryptionFailed));
+
+        // Every byte past the magic and version is authenticated
+        for i in 6..ENCRYPTED_KEY_SIZE {
+            let mut bytes = *enc.as_bytes();
+            bytes[i] ^= 1;
+            match EncryptedSecretKey::from_slice(&bytes) {
+                Ok(tampered) => assert_eq!(tampered.decrypt(Credential::Kek(&kek)), Err(KeyDecryptionFailed)),
+                Err(e) => assert_eq!(e, InvalidEncryptedKey),
+            }
+        }
+    }
+
+    #[cfg(feature = "scrypt-kdf")]
+    #[test]
+    fn scrypt() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let protection = Protection::Scrypt { passphrase: b"correct horse", log_n: 10, r: 8, p: 1 };
+        let enc = sk.to_encrypted(protection, &mut thread_rng()).unwrap();
+        assert!(enc.is_passphrase_protected());
+        assert_eq!(enc.decrypt(Credential::Passphrase(b"correct horse")), Ok(sk));
+        assert_eq!(enc.decrypt(Credential::Passphrase(b"battery staple")), Err(KeyDecryptionFailed));
+        assert_eq!(enc.decrypt(Credential::Kek(&[0; 32])), Err(KeyDecryptionFailed));
+
+        // Parameters beyond the memory limit are refused both ways
+        let greedy = Protection::Scrypt { passphrase: b"correct horse", log_n: 24, r: 8, p: 1 };
+        assert_eq!(sk.to_encrypted(greedy, &mut thread_rng()), Err(InvalidEncryptedKey));
+        let mut bytes = *enc.as_bytes();
+        bytes[6] = 24;
+        assert_eq!(EncryptedSecretKey::from_slice(&bytes), Err(InvalidEncryptedKey));
+    }
+
+    #[cfg(feature = "argon2-kdf")]
+    #[test]
+    fn argon2id() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let protection = Protection::Argon2id { passphrase: b"correct horse", memory_kib: 64, iterations: 1,
+                                                lanes: 1 };
+        let enc = sk.to_encrypted(protection, &mut thread_rng()).unwrap();
+        assert_eq!(enc.as_bytes()[5], 2);
+        assert_eq!(enc.decrypt(Credential::Passphrase(b"correct horse")), Ok(sk));
+        assert_eq!(enc.decrypt(Credential::Passphrase(b"battery staple")), Err(KeyDecryptionFailed));
+
+        let mut bytes = *enc.as_bytes();
+        bytes[6..10].copy_from_slice(&(2u32 << 20).to_le_bytes());
+        assert_eq!(EncryptedSecretKey::from_slice(&bytes), Err(InvalidEncryptedKey));
+    }
+
+    #[test]
+    fn malformed() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let enc = sk.to_encrypted(Protection::Kek(&[0x11; 32]), &mut thread_rng()).unwrap();
+        let bytes = *enc.as_bytes();
+
+        assert_eq!(EncryptedSecretKey::from_slice(&bytes[..105]), Err(InvalidEncryptedKey));
+        for &(i, v) in &[(0, b'X'), (4, 2), (5, 9), (6, 1)] {
+            let mut bad = bytes;
+            bad[i] = v;
+            assert_eq!(EncryptedSecretKey::from_slice(&bad), Err(InvalidEncryptedKey));
+        }
+        assert_eq!(enc.to_string().parse(), Ok(enc));
+        assert_eq!("00".parse::<EncryptedSecretKey>(), Err(InvalidEncryptedKey));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use std::boxed::Box;
+        use std::string::ToString;
+        use serde_test::{Configure, Readable, Token, assert_tokens, assert_de_tokens_error};
+
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let enc = sk.to_encrypted(Protection::Kek(&[0x11; 32]), &mut thread_rng()).unwrap();
+        let hex: &'static str = Box::leak(enc.to_string().into_boxed_str());
+        assert_eq!(hex.len(), 2 * ENCRYPTED_KEY_SIZE);
+        let bytes: &'static [u8] = Box::leak(Box::new(*enc.as_bytes()));
+        assert_tokens(&enc.readable(), &[Token::BorrowedStr(hex)]);
+        assert_tokens(&enc.compact(), &[Token::BorrowedBytes(bytes)]);
+        assert_de_tokens_error::<Readable<EncryptedSecretKey>>(&[Token::BorrowedStr(&hex[2..])],
+                                                               "secp: malformed encrypted secret key");
+    }
+}
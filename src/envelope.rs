@@ -0,0 +1,311 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Sealed-data envelopes
+//! A versioned frame around sealed data (secret keys, vault state, exported
+//! contexts) which says what is inside and under which key policy it was
+//! sealed, so that an upgraded enclave recognizes data sealed by an older
+//! one instead of misreading it. The ciphertext itself is opaque here, e.g.
+//! the output of the SDK's `sgx_tseal`; pass `Envelope::header` as its
+//! additional authenticated data, so that the kind and policy can't be
+//! swapped without unsealing failing.
+//!
+//! The layout is the magic `SEAL` (4 bytes), the version (1), the kind (1),
+//! the key policy (1), a reserved zero byte (1), the ciphertext length (4,
+//! little-endian) and the ciphertext. Version 1 is the only version so far;
+//! data sealed before envelopes existed counts as version 0. `migrate`
+//! brings data of any known version to the current one, and refuses data
+//! from a newer enclave rather than guessing at it. Requires compilation
+//! with the "sealing" feature.
+
+use std::vec::Vec;
+
+use le_bytes::{read_u32, u32_bytes};
+use sealing::KeyPolicy;
+use super::Error::{self, InvalidEnvelope, UnsupportedEnvelopeVersion};
+
+/// The version `Envelope::to_bytes` writes
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// The size (in bytes) of the header in front of the ciphertext
+pub const ENVELOPE_HEADER_SIZE: usize = 12;
+
+const MAGIC: &'static [u8; 4] = b"SEAL";
+
+/// What sealed data holds
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SealedKind {
+    /// A secret key
+    SecretKey,
+    /// The state of a `vault::Vault`
+    Vault,
+    /// A context from `Secp256k1::export_context`
+    Context,
+}
+
+impl SealedKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            SealedKind::SecretKey => 1,
+            SealedKind::Vault => 2,
+            SealedKind::Context => 3,
+        }
+    }
+
+    fn from_u8(byte: u8) -> Result<SealedKind, Error> {
+        match byte {
+            1 => Ok(SealedKind::SecretKey),
+            2 => Ok(SealedKind::Vault),
+            3 => Ok(SealedKind::Context),
+            _ => Err(InvalidEnvelope),
+        }
+    }
+}
+
+fn policy_to_u8(policy: KeyPolicy) -> u8 {
+    match policy {
+        KeyPolicy::MrEnclave => 1,
+        KeyPolicy::MrSigner => 2,
+    }
+}
+
+fn policy_from_u8(byte: u8) -> Result<KeyPolicy, Error> {
+    match byte {
+        1 => Ok(KeyPolicy::MrEnclave),
+        2 => Ok(KeyPolicy::MrSigner),
+        _ => Err(InvalidEnvelope),
+    }
+}
+
+/// Sealed data with what it holds and how it was sealed
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Envelope {
+    /// What the ciphertext holds
+    pub kind: SealedKind,
+    /// The key policy the ciphertext was sealed under
+    pub policy: KeyPolicy,
+    /// The sealed data
+    pub ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    /// Wraps `ciphertext`, sealed under `policy`, which holds a `kind`
+    pub fn new(kind: SealedKind, policy: KeyPolicy, ciphertext: Vec<u8>) -> Envelope {
+        Envelope { kind, policy, ciphertext }
+    }
+
+    /// The header for the current version, which the sealing should
+    /// authenticate. Panics if the ciphertext is 4 GiB or longer.
+    pub fn header(&self) -> [u8; ENVELOPE_HEADER_SIZE] {
+        assert!(self.ciphertext.len() <= u32::max_value() as usize, "ciphertext too long for an envelope");
+        let mut header = [0u8; ENVELOPE_HEADER_SIZE];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = ENVELOPE_VERSION;
+        header[5] = self.kind.to_u8();
+        header[6] = policy_to_u8(self.policy);
+        header[8..].copy_from_slice(&u32_bytes(self.ciphertext.len() as u32));
+        header
+    }
+
+    /// Serializes the envelope in the current version
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(ENVELOPE_HEADER_SIZE + self.ciphertext.len());
+        ret.extend_from_slice(&self.header());
+        ret.extend_from_slice(&self.ciphertext);
+        ret
+    }
+
+    /// Parses an envelope of any known version. Fails with
+    /// `UnsupportedEnvelopeVersion` if it is from a newer version, and with
+    /// `InvalidEnvelope` if it is not an envelope or is malformed.
+    pub fn from_bytes(data: &[u8]) -> Result<Envelope, Error> {
+        match version(data)? {
+            0 => Err(InvalidEnvelope),
+            1 => Envelope::from_v1(data),
+            _ => Err(UnsupportedEnvelopeVersion),
+        }
+    }
+
+    fn from_v1(data: &[u8]) -> Result<Envelope, Error> {
+        if data.len() < ENVELOPE_HEADER_SIZE || data[7] != 0 {
+            return Err(InvalidEnvelope);
+        }
+        let kind = SealedKind::from_u8(data[5])?;
+        let policy = policy_from_u8(data[6])?;
+        let ciphertext = &data[ENVELOPE_HEADER_SIZE..];
+        if read_u32(&data[8..]) as usize != ciphertext.len() {
+            return Err(InvalidEnvelope);
+        }
+        Ok(Envelope { kind, policy, ciphertext: ciphertext.to_vec() })
+    }
+}
+
+/// The envelope version of sealed data: 0 if it doesn't start with the
+/// envelope magic, i.e. it was sealed before envelopes. Fails with
+/// `InvalidEnvelope` if it is too short to tell.
+pub fn version(data: &[u8]) -> Result<u8, Error> {
+    if data.len() < MAGIC.len() + 1 {
+        return Err(InvalidEnvelope);
+    }
+    if data[..4] == MAGIC[..] { Ok(data[4]) } else { Ok(0) }
+}
+
+/// Brings sealed data to the current envelope version. Data from before
+/// envelopes is wrapped as a `legacy_kind` sealed under `legacy_policy`,
+/// which only the caller can know; enveloped data keeps its own kind and
+/// policy. The ciphertext is never touched, so this needs no sealing key.
+/// Fails like `Envelope::from_bytes`, in particular with
+/// `UnsupportedEnvelopeVersion` for data from a newer enclave, which must
+/// not be overwritten.
+pub fn migrate(data: &[u8], legacy_kind: SealedKind, legacy_policy: KeyPolicy) -> Result<Vec<u8>, Error> {
+    if version(data)? == 0 {
+        return Ok(Envelope::new(legacy_kind, legacy_policy, data.to_vec()).to_bytes());
+    }
+    Envelope::from_bytes(data).map(|envelope| envelope.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate, version, Envelope, SealedKind, ENVELOPE_HEADER_SIZE, ENVELOPE_VERSION};
+    use super::super::Error::{InvalidEnvelope, UnsupportedEnvelopeVersion};
+    use sealing::KeyPolicy;
+
+    fn example() -> Envelope {
+        Envelope::new(SealedKind::Vault, KeyPolicy::MrSigner, vec![0xaa, 0xbb, 0xcc])
+    }
+
+    #[test]
+    fn layout() {
+        let bytes = example().to_bytes();
+        // Pins the format, which must never change for version 1
+        assert_eq!(bytes, b"SEAL\x01\x02\x02\x00\x03\x00\x00\x00\xaa\xbb\xcc".to_vec());
+        assert_eq!(bytes[..ENVELOPE_HEADER_SIZE], example().header()[..]);
+        assert_eq!(Envelope::from_bytes(&bytes), Ok(example()));
+        assert_eq!(version(&bytes), Ok(ENVELOPE_VERSION));
+
+        let kinds = [SealedKind::SecretKey, SealedKind::Vault, SealedKind::Context];
+        for &kind in kinds.iter() {
+            for &policy in [KeyPolicy::MrEnclave, KeyPolicy::MrSigner].iter() {
+                let envelope = Envelope::new(kind, policy, Vec::new());
+                assert_eq!(Envelope::from_bytes(&envelope.to_bytes()), Ok(envelope));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = example().to_bytes();
+        let with = |i: usize, byte: u8| {
+            let mut modified = bytes.clone();
+            modified[i] = byte;
+            Envelope::from_bytes(&modified)
+        };
+        assert_eq!(with(0, b's'), Err(InvalidEnvelope));
+        assert_eq!(with(4, 0), Err(InvalidEnvelope));
+        assert_eq!(with(4, 2), Err(UnsupportedEnvelopeVersion));
+        assert_eq!(with(5, 0), Err(InvalidEnvelope));
+        assert_eq!(with(6, 3), Err(InvalidEnvelope));
+        assert_eq!(with(7, 1), Err(InvalidEnvelope));
+        assert_eq!(with(8, 2), Err(InvalidEnvelope));
+        assert_eq!(with(11, 0x80), Err(InvalidEnvelope));
+
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(Envelope::from_bytes(&longer), Err(InvalidEnvelope));
+        assert_eq!(version(b"SEA"), Err(InvalidEnvelope));
+        assert_eq!(version(b"\x04\x00\x02\x00\x00\x00"), Ok(0));
+    }
+
+    #[test]
+    fn truncations_and_bit_flips() {
+        // Every truncation and single bit flip either fails or parses to
+        // an envelope which serializes back to the modified bytes
+        let bytes = example().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Envelope::from_bytes(&bytes[..len]).is_err());
+        }
+        for i in 0..bytes.len() * 8 {
+            let mut flipped = bytes.clone();
+            flipped[i / 8] ^= 1 << (i % 8);
+            if let Ok(envelope) = Envelope::from_bytes(&flipped) {
+                assert_eq!(envelope.to_bytes(), flipped);
+                assert!(i >= ENVELOPE_HEADER_SIZE * 8 || envelope != example());
+            }
+        }
+    }
+
+    #[test]
+    fn migration() {
+        // SDK sealed data starts with its key name, 0x0004, so is never
+        // mistaken for an envelope
+        let legacy = [0x04, 0x00, 0x02, 0x00, 0x11, 0x22, 0x33];
+        let migrated = migrate(&legacy, SealedKind::SecretKey, KeyPolicy::MrEnclave).unwrap();
+        assert_eq!(Envelope::from_bytes(&migrated),
+                   Ok(Envelope::new(SealedKind::SecretKey, KeyPolicy::MrEnclave, legacy.to_vec())));
+
+        // Current envelopes are kept as they are, whatever the legacy defaults
+        assert_eq!(migrate(&migrated, SealedKind::Context, KeyPolicy::MrSigner), Ok(migrated.clone()));
+
+        let mut newer = migrated.clone();
+        newer[4] = ENVELOPE_VERSION + 1;
+        assert_eq!(migrate(&newer, SealedKind::SecretKey, KeyPolicy::MrEnclave), Err(UnsupportedEnvelopeVersion));
+        newer.truncate(4);
+        assert_eq!(migrate(&newer, SealedKind::SecretKey, KeyPolicy::MrEnclave), Err(InvalidEnvelope));
+    }
+
+    #[cfg(feature = "fuzz")]
+    mod fuzz {
+        use proptest::collection::vec;
+        use proptest::prelude::*;
+
+        use super::super::{migrate, Envelope, SealedKind};
+        use sealing::KeyPolicy;
+
+        fn kind() -> impl Strategy<Value = SealedKind> {
+            prop_oneof![Just(SealedKind::SecretKey), Just(SealedKind::Vault), Just(SealedKind::Context)]
+        }
+
+        fn policy() -> impl Strategy<Value = KeyPolicy> {
+            prop_oneof![Just(KeyPolicy::MrEnclave), Just(KeyPolicy::MrSigner)]
+        }
+
+        proptest! {
+            #[test]
+            fn parser_round_trips(data in vec(any::<u8>(), 0..64)) {
+                if let Ok(envelope) = Envelope::from_bytes(&data) {
+                    prop_assert_eq!(envelope.to_bytes(), data);
+                }
+            }
+
+            #[test]
+            fn headers_round_trip(header in vec(any::<u8>(), 12), ciphertext in vec(any::<u8>(), 0..32)) {
+                let mut data = b"SEAL\x01".to_vec();
+                data.extend_from_slice(&header[5..]);
+                data.extend_from_slice(&ciphertext);
+                if let Ok(envelope) = Envelope::from_bytes(&data) {
+                    prop_assert_eq!(&envelope.ciphertext, &ciphertext);
+                    prop_assert_eq!(envelope.to_bytes(), data);
+                }
+            }
+
+            #[test]
+            fn migration_is_idempotent(data in vec(any::<u8>(), 0..64), kind in kind(), policy in policy()) {
+                if let Ok(migrated) = migrate(&data, kind, policy) {
+                    prop_assert!(Envelope::from_bytes(&migrated).is_ok());
+                    prop_assert_eq!(migrate(&migrated, kind, policy), Ok(migrated));
+                }
+            }
+        }
+    }
+}
@@ -16,10 +16,28 @@
 //! # FFI bindings
 //! Direct bindings to the underlying C library functions. These should
 //! not be needed for most users.
+//!
+//! With the "expose-ffi" feature this module is part of the documented API
+//! and follows the crate's versioning, so that enclaves which need a C call
+//! the safe wrappers don't offer can make it without forking the crate.
+//! It holds the declarations for every module compiled into the vendored
+//! library (ECDH, recovery, scalar operations, context export, prepared
+//! public keys, multi-scalar multiplication, batch tweaks). Without the
+//! feature it is still reachable but hidden from the documentation and
+//! may change in any release.
+//!
+//! The C functions are documented in the vendored headers,
+//! `depend/secp256k1/include/secp256k1*.h`.
+
+// The declarations mirror those headers rather than repeat their docs
+#![allow(missing_docs)]
+
 use std::mem;
 use std::hash;
 
-use libc::{c_int, c_uchar, c_uint, c_void, size_t};
+use types::{c_char, c_int, c_uchar, c_uint, c_void};
+#[cfg(not(feature = "fuzztarget"))]
+use types::size_t;
 
 /// Flag for context to enable no precomputation
 pub const SECP256K1_START_NONE: c_uint = (1 << 0) | 0;
@@ -32,6 +50,33 @@ pub const SECP256K1_SER_UNCOMPRESSED: c_uint = (1 << 1) | 0;
 /// Flag for keys to indicate compressed serialization format
 pub const SECP256K1_SER_COMPRESSED: c_uint = (1 << 1) | (1 << 8);
 
+/// Build option: compiled with the SGX SDK's LVI mitigation flags
+pub const SECP256K1_BUILD_LVI_MITIGATION: c_uint = 1 << 0;
+/// Build option: field and scalar arithmetic in x86_64 assembly
+pub const SECP256K1_BUILD_ASM_X86_64: c_uint = 1 << 1;
+/// Build option: 5x52 field and 4x64 scalar limbs with 128-bit products
+pub const SECP256K1_BUILD_WIDEMUL_INT128: c_uint = 1 << 2;
+/// Build option: the code which builds the signing table is left out
+pub const SECP256K1_BUILD_VERIFY_ONLY: c_uint = 1 << 3;
+/// Build option: allocations go through the crate's `allocator` module
+pub const SECP256K1_BUILD_CONTEXT_ALLOCATOR: c_uint = 1 << 4;
+/// Build option: verification splits scalars with the GLV endomorphism
+pub const SECP256K1_BUILD_ENDOMORPHISM: c_uint = 1 << 5;
+/// Build option: the ECDH module
+pub const SECP256K1_BUILD_MODULE_ECDH: c_uint = 1 << 8;
+/// Build option: the public key recovery module
+pub const SECP256K1_BUILD_MODULE_RECOVERY: c_uint = 1 << 9;
+/// Build option: the scalar arithmetic module
+pub const SECP256K1_BUILD_MODULE_SCALAR_OPS: c_uint = 1 << 10;
+/// Build option: the context export, import and preallocation module
+pub const SECP256K1_BUILD_MODULE_CONTEXT_EXPORT: c_uint = 1 << 11;
+/// Build option: the prepared public key module
+pub const SECP256K1_BUILD_MODULE_PREPARED: c_uint = 1 << 12;
+/// Build option: the multi-scalar multiplication module
+pub const SECP256K1_BUILD_MODULE_MSM: c_uint = 1 << 13;
+/// Build option: the batch tweak module
+pub const SECP256K1_BUILD_MODULE_TWEAK_BATCH: c_uint = 1 << 14;
+
 /// A nonce generation function. Ordinary users of the library
 /// never need to see this type; only if you need to control
 /// nonce generation do you need to use it. I have deliberately
@@ -43,8 +88,12 @@ pub type NonceFn = unsafe extern "C" fn(nonce32: *mut c_uchar,
                                         msg32: *const c_uchar,
                                         key32: *const c_uchar,
                                         algo16: *const c_uchar,
-                                        attempt: c_uint,
-                                        data: *const c_void);
+                                        data: *mut c_void,
+                                        attempt: c_uint) -> c_int;
+
+/// A callback for illegal arguments or failed internal consistency checks,
+/// taking a NUL-terminated message and the pointer passed when it was set
+pub type CallbackFn = unsafe extern "C" fn(message: *const c_char, data: *mut c_void);
 
 
 /// A Secp256k1 context, containing various precomputed values and such
@@ -65,6 +114,9 @@ impl PublicKey {
     pub fn new() -> PublicKey { PublicKey([0; 64]) }
     /// Create a new (uninitialized) public key usable for the FFI interface
     pub unsafe fn blank() -> PublicKey { mem::uninitialized() }
+    /// Create a public key from its library-internal representation, without
+    /// checking that it encodes a valid curve point
+    pub unsafe fn from_array_unchecked(data: [c_uchar; 64]) -> PublicKey { PublicKey(data) }
 }
 
 impl hash::Hash for PublicKey {
@@ -112,12 +164,25 @@ impl SharedSecret {
     pub unsafe fn blank() -> SharedSecret { mem::uninitialized() }
 }
 
+/// A public key with a table of precomputed multiples. Its size is given by
+/// `secp256k1_prepared_pubkey_size`, so it is only ever handled through
+/// pointers into a buffer of that size, aligned to 8 bytes.
+#[repr(C)] pub struct PreparedPublicKey(c_int);
+
+/// Memory for the intermediate tables of multi-point algorithms. Created
+/// with `secp256k1_scratch_space_create` and freed with
+/// `secp256k1_scratch_space_destroy`; it must not be shared between threads.
+#[repr(C)] pub struct ScratchSpace(c_int);
+
 #[cfg(not(feature = "fuzztarget"))]
 extern "C" {
     pub static secp256k1_nonce_function_rfc6979: NonceFn;
 
     pub static secp256k1_nonce_function_default: NonceFn;
 
+    /// A context without precomputed tables, for parsing and serialization
+    pub static secp256k1_context_no_precomp: *const Context;
+
     // Contexts
     pub fn secp256k1_context_create(flags: c_uint) -> *mut Context;
 
@@ -129,12 +194,51 @@ extern "C" {
                                        seed32: *const c_uchar)
                                        -> c_int;
 
-    // TODO secp256k1_context_set_illegal_callback
-    // TODO secp256k1_context_set_error_callback
-    // (Actually, I don't really want these exposed; if either of these
-    // are ever triggered it indicates a bug in rust-secp256k1, since
-    // one goal is to use Rust's type system to eliminate all possible
-    // bad inputs.)
+    pub fn secp256k1_context_capabilities(cx: *const Context) -> c_uint;
+
+    pub fn secp256k1_context_table_sizes(sign_size: *mut size_t, verify_size: *mut size_t, flags: c_uint);
+
+    pub fn secp256k1_context_export_size(cx: *const Context) -> size_t;
+
+    pub fn secp256k1_context_export(cx: *const Context, output: *mut c_uchar,
+                                    out_len: size_t)
+                                    -> c_int;
+
+    pub fn secp256k1_context_import(input: *const c_uchar, in_len: size_t,
+                                    flags: c_uint)
+                                    -> *mut Context;
+
+    pub fn secp256k1_context_preallocated_size(flags: c_uint) -> size_t;
+
+    pub fn secp256k1_context_preallocated_create(prealloc: *mut c_void, prealloc_len: size_t,
+                                                 flags: c_uint)
+                                                 -> *mut Context;
+
+    pub fn secp256k1_context_rebuild_sign(cx: *mut Context) -> c_int;
+
+    pub fn secp256k1_build_info(window_size: *mut c_uint, gen_prec_bits: *mut c_uint) -> c_uint;
+
+    pub fn secp256k1_context_set_illegal_callback(cx: *mut Context,
+                                                  fun: Option<CallbackFn>,
+                                                  data: *const c_void);
+
+    pub fn secp256k1_context_set_error_callback(cx: *mut Context,
+                                                fun: Option<CallbackFn>,
+                                                data: *const c_void);
+
+    // Scratch spaces
+    pub fn secp256k1_scratch_space_create(cx: *const Context,
+                                          max_size: size_t)
+                                          -> *mut ScratchSpace;
+
+    pub fn secp256k1_scratch_space_create_preallocated(cx: *const Context,
+                                                       mem: *mut c_void,
+                                                       size: size_t)
+                                                       -> *mut ScratchSpace;
+
+    pub fn secp256k1_scratch_space_max_size(scratch: *const ScratchSpace) -> size_t;
+
+    pub fn secp256k1_scratch_space_destroy(scratch: *mut ScratchSpace);
 
     // Pubkeys
     pub fn secp256k1_ec_pubkey_parse(cx: *const Context, pk: *mut PublicKey,
@@ -146,6 +250,10 @@ extern "C" {
                                          compressed: c_uint)
                                          -> c_int;
 
+    pub fn secp256k1_ec_pubkey_cmp(cx: *const Context, pk1: *const PublicKey,
+                                   pk2: *const PublicKey)
+                                   -> c_int;
+
     // Signatures
     pub fn secp256k1_ecdsa_signature_parse_der(cx: *const Context, sig: *mut Signature,
                                                input: *const c_uchar, in_len: size_t)
@@ -232,6 +340,12 @@ extern "C" {
                                          tweak: *const c_uchar)
                                          -> c_int;
 
+    pub fn secp256k1_ec_pubkey_tweak_add_batch(cx: *const Context,
+                                               pks: *mut PublicKey,
+                                               n: size_t,
+                                               tweak: *const c_uchar)
+                                               -> c_int;
+
     pub fn secp256k1_ec_privkey_tweak_mul(cx: *const Context,
                                           sk: *mut c_uchar,
                                           tweak: *const c_uchar)
@@ -253,18 +367,95 @@ extern "C" {
                           point: *const PublicKey,
                           scalar: *const c_uchar)
                           -> c_int;
+
+    // Prepared public keys
+    pub fn secp256k1_prepared_pubkey_size() -> size_t;
+
+    pub fn secp256k1_prepared_pubkey_create(cx: *const Context,
+                                            prepared: *mut PreparedPublicKey,
+                                            pk: *const PublicKey)
+                                            -> c_int;
+
+    pub fn secp256k1_ecdsa_verify_prepared(cx: *const Context,
+                                           sig: *const Signature,
+                                           msg32: *const c_uchar,
+                                           prepared: *const PreparedPublicKey)
+                                           -> c_int;
+
+    // Multi-scalar multiplication
+    pub fn secp256k1_ec_pubkey_msm(cx: *const Context,
+                                   scratch: *mut ScratchSpace,
+                                   out: *mut PublicKey,
+                                   scalars: *const *const c_uchar,
+                                   pks: *const *const PublicKey,
+                                   n: size_t)
+                                   -> c_int;
+
+    pub fn secp256k1_ec_pubkey_msm_scratch_size(n: size_t) -> size_t;
+
+    pub fn secp256k1_ec_pubkey_mul_add_generator(cx: *const Context,
+                                                 out: *mut PublicKey,
+                                                 pk: *const PublicKey,
+                                                 a: *const c_uchar,
+                                                 b: *const c_uchar)
+                                                 -> c_int;
 }
 
+// Scalar arithmetic does not depend on context flags, so fuzz builds use the
+// real implementations as well
+extern "C" {
+    pub fn secp256k1_ec_scalar_verify(cx: *const Context,
+                                      scalar: *const c_uchar)
+                                      -> c_int;
+
+    pub fn secp256k1_ec_scalar_reduce512(cx: *const Context,
+                                         out: *mut c_uchar,
+                                         input64: *const c_uchar)
+                                         -> c_int;
+
+    pub fn secp256k1_ec_scalar_add(cx: *const Context,
+                                   out: *mut c_uchar,
+                                   a: *const c_uchar,
+                                   b: *const c_uchar)
+                                   -> c_int;
+
+    pub fn secp256k1_ec_scalar_mul(cx: *const Context,
+                                   out: *mut c_uchar,
+                                   a: *const c_uchar,
+                                   b: *const c_uchar)
+                                   -> c_int;
+
+    pub fn secp256k1_ec_scalar_negate(cx: *const Context,
+                                      out: *mut c_uchar,
+                                      a: *const c_uchar)
+                                      -> c_int;
+
+    pub fn secp256k1_ec_scalar_inverse(cx: *const Context,
+                                       out: *mut c_uchar,
+                                       a: *const c_uchar)
+                                       -> c_int;
+}
+
+/// Fake implementations for the "fuzztarget" feature. They are cheap and
+/// deterministic and keep the calling conventions (including the context
+/// flag checks) of the real functions, but they are NOT SECURE: secret keys
+/// can be read straight off public keys and signatures.
 #[cfg(feature = "fuzztarget")]
 mod fuzz_dummy {
-    use libc::{c_int, c_uchar, c_uint, c_void, size_t};
+    use types::{c_int, c_uchar, c_uint, c_void, size_t};
     use ffi::*;
+    use std::boxed::Box;
+    use std::mem;
     use std::ptr;
 
     extern "C" {
         pub static secp256k1_nonce_function_rfc6979: NonceFn;
     }
 
+    /// A dummy context without precomputed tables
+    #[allow(non_upper_case_globals)]
+    pub static secp256k1_context_no_precomp: &'static Context = &Context(SECP256K1_START_NONE as i32);
+
     // Contexts
     /// Creates a dummy context, tracking flags to ensure proper calling semantics
     pub unsafe fn secp256k1_context_create(flags: c_uint) -> *mut Context {
@@ -291,12 +482,76 @@ mod fuzz_dummy {
         1
     }
 
-    // TODO secp256k1_context_set_illegal_callback
-    // TODO secp256k1_context_set_error_callback
-    // (Actually, I don't really want these exposed; if either of these
-    // are ever triggered it indicates a bug in rust-secp256k1, since
-    // one goal is to use Rust's type system to eliminate all possible
-    // bad inputs.)
+    /// Dummy contexts have the capabilities they were created with
+    pub unsafe fn secp256k1_context_capabilities(cx: *const Context) -> c_uint {
+        (*cx).0 as c_uint
+    }
+
+    /// Dummy contexts have no tables
+    pub unsafe fn secp256k1_context_table_sizes(sign_size: *mut size_t, verify_size: *mut size_t, _flags: c_uint) {
+        *sign_size = 0;
+        *verify_size = 0;
+    }
+
+    /// Dummy contexts serialize to their four flag bytes
+    pub unsafe fn secp256k1_context_export_size(_cx: *const Context) -> size_t {
+        4
+    }
+
+    /// Writes the flags of a dummy context
+    pub unsafe fn secp256k1_context_export(cx: *const Context, output: *mut c_uchar,
+                                           out_len: size_t)
+                                           -> c_int {
+        if out_len != 4 { return 0; }
+        ptr::copy(&(*cx).0 as *const i32 as *const c_uchar, output, 4);
+        1
+    }
+
+    /// Creates a dummy context from its flags
+    pub unsafe fn secp256k1_context_import(input: *const c_uchar, in_len: size_t,
+                                           flags: c_uint)
+                                           -> *mut Context {
+        if in_len != 4 { return ptr::null_mut(); }
+        let mut stored = 0i32;
+        ptr::copy(input, &mut stored as *mut i32 as *mut c_uchar, 4);
+        if stored as c_uint & flags != flags { return ptr::null_mut(); }
+        Box::into_raw(Box::new(Context(stored)))
+    }
+
+    /// Dummy contexts need no buffer
+    pub unsafe fn secp256k1_context_preallocated_size(_flags: c_uint) -> size_t {
+        0
+    }
+
+    /// Creates a dummy context on the heap, ignoring the buffer, so that
+    /// destroying it works as for any other
+    pub unsafe fn secp256k1_context_preallocated_create(_prealloc: *mut c_void, _prealloc_len: size_t,
+                                                        flags: c_uint)
+                                                        -> *mut Context {
+        secp256k1_context_create(flags)
+    }
+
+    /// Dummy contexts have no tables to rebuild, but succeed if they could sign
+    pub unsafe fn secp256k1_context_rebuild_sign(cx: *mut Context) -> c_int {
+        ((*cx).0 as c_uint & SECP256K1_START_SIGN == SECP256K1_START_SIGN) as c_int
+    }
+
+    /// No C library is compiled for fuzzing, so there are no options to report
+    pub unsafe fn secp256k1_build_info(window_size: *mut c_uint, gen_prec_bits: *mut c_uint) -> c_uint {
+        *window_size = 0;
+        *gen_prec_bits = 0;
+        0
+    }
+
+    /// Dummy contexts never call back, so callbacks are ignored
+    pub unsafe fn secp256k1_context_set_illegal_callback(_cx: *mut Context,
+                                                         _fun: Option<CallbackFn>,
+                                                         _data: *const c_void) {}
+
+    /// Dummy contexts never call back, so callbacks are ignored
+    pub unsafe fn secp256k1_context_set_error_callback(_cx: *mut Context,
+                                                       _fun: Option<CallbackFn>,
+                                                       _data: *const c_void) {}
 
     // Pubkeys
     /// Parse 33/65 byte pubkey into PublicKey, losing compressed information
@@ -351,6 +606,25 @@ mod fuzz_dummy {
         1
      }
 
+    /// Compares two PublicKeys by their compressed serialization
+    pub unsafe fn secp256k1_ec_pubkey_cmp(cx: *const Context, pk1: *const PublicKey,
+                                          pk2: *const PublicKey)
+                                          -> c_int {
+        let mut out = [[0u8; 33]; 2];
+        for (out, pk) in out.iter_mut().zip(&[pk1, pk2]) {
+            let mut out_len = 33;
+            if secp256k1_ec_pubkey_serialize(cx, out.as_mut_ptr(), &mut out_len, *pk,
+                                             SECP256K1_SER_COMPRESSED) != 1 {
+                *out = [0; 33];
+            }
+        }
+        match out[0][..].cmp(&out[1][..]) {
+            ::std::cmp::Ordering::Less => -1,
+            ::std::cmp::Ordering::Equal => 0,
+            ::std::cmp::Ordering::Greater => 1,
+        }
+    }
+
     // Signatures
     pub unsafe fn secp256k1_ecdsa_signature_parse_der(cx: *const Context, sig: *mut Signature,
                                                       input: *const c_uchar, in_len: size_t)
@@ -574,6 +848,18 @@ mod fuzz_dummy {
         1
     }
 
+    /// Tweaks each key with `secp256k1_ec_pubkey_tweak_add`
+    pub unsafe fn secp256k1_ec_pubkey_tweak_add_batch(cx: *const Context,
+                                                      pks: *mut PublicKey,
+                                                      n: size_t,
+                                                      tweak: *const c_uchar)
+                                                      -> c_int {
+        for i in 0..n {
+            if secp256k1_ec_pubkey_tweak_add(cx, pks.offset(i as isize), tweak) != 1 { return 0; }
+        }
+        1
+    }
+
     /// Copies the last 16 bytes of tweak into the last 16 bytes of sk
     pub unsafe fn secp256k1_ec_privkey_tweak_mul(cx: *const Context,
                                                  sk: *mut c_uchar,
@@ -640,6 +926,189 @@ mod fuzz_dummy {
         (*out).0[16] = 0x00; // result should always be a valid secret key
         1
     }
+
+    /// Dummy prepared keys are a copy of the public key
+    pub unsafe fn secp256k1_prepared_pubkey_size() -> size_t {
+        mem::size_of::<PublicKey>()
+    }
+
+    /// Copies pk into prepared
+    pub unsafe fn secp256k1_prepared_pubkey_create(cx: *const Context,
+                                                   prepared: *mut PreparedPublicKey,
+                                                   pk: *const PublicKey)
+                                                   -> c_int {
+        if test_pk_validate(cx, pk) != 1 { return 0; }
+        ptr::copy(pk, prepared as *mut PublicKey, 1);
+        1
+    }
+
+    /// Verifies against the copied public key
+    pub unsafe fn secp256k1_ecdsa_verify_prepared(cx: *const Context,
+                                                  sig: *const Signature,
+                                                  msg32: *const c_uchar,
+                                                  prepared: *const PreparedPublicKey)
+                                                  -> c_int {
+        secp256k1_ecdsa_verify(cx, sig, msg32, prepared as *const PublicKey)
+    }
+
+    /// Allocates a dummy scratch space holding its size limit
+    pub unsafe fn secp256k1_scratch_space_create(cx: *const Context,
+                                                 max_size: size_t)
+                                                 -> *mut ScratchSpace {
+        assert!(!cx.is_null());
+        Box::into_raw(Box::new(ScratchSpace(max_size.min(c_int::max_value() as size_t) as c_int)))
+    }
+
+    /// Allocates a dummy scratch space holding its size limit, ignoring mem
+    pub unsafe fn secp256k1_scratch_space_create_preallocated(cx: *const Context,
+                                                              _mem: *mut c_void,
+                                                              size: size_t)
+                                                              -> *mut ScratchSpace {
+        secp256k1_scratch_space_create(cx, size)
+    }
+
+    /// Returns the dummy scratch space's limit
+    pub unsafe fn secp256k1_scratch_space_max_size(scratch: *const ScratchSpace) -> size_t {
+        (*scratch).0 as size_t
+    }
+
+    /// Frees a dummy scratch space
+    pub unsafe fn secp256k1_scratch_space_destroy(scratch: *mut ScratchSpace) {
+        Box::from_raw(scratch);
+    }
+
+    /// Combines the first (up to 32) keys with `secp256k1_ec_pubkey_combine`
+    /// and tweaks the result by the first scalar; fails with -1 if the
+    /// scratch space's limit is zero
+    pub unsafe fn secp256k1_ec_pubkey_msm(cx: *const Context,
+                                          scratch: *mut ScratchSpace,
+                                          out: *mut PublicKey,
+                                          scalars: *const *const c_uchar,
+                                          pks: *const *const PublicKey,
+                                          n: size_t)
+                                          -> c_int {
+        assert!((*cx).0 as u32 & SECP256K1_START_VERIFY == SECP256K1_START_VERIFY);
+        if (*scratch).0 == 0 { return -1; }
+        if n == 0 { return 0; }
+        if secp256k1_ec_pubkey_combine(cx, out, pks, n.min(32) as c_int) != 1 { return 0; }
+        secp256k1_ec_pubkey_tweak_mul(cx, out, *scalars)
+    }
+
+    /// Dummy scratch spaces only need a nonzero limit
+    pub unsafe fn secp256k1_ec_pubkey_msm_scratch_size(n: size_t) -> size_t {
+        n
+    }
+
+    /// Tweaks the key by `a` with `secp256k1_ec_pubkey_tweak_mul`, then by
+    /// `b` with `secp256k1_ec_pubkey_tweak_add`
+    pub unsafe fn secp256k1_ec_pubkey_mul_add_generator(cx: *const Context,
+                                                        out: *mut PublicKey,
+                                                        pk: *const PublicKey,
+                                                        a: *const c_uchar,
+                                                        b: *const c_uchar)
+                                                        -> c_int {
+        *out = *pk;
+        if secp256k1_ec_pubkey_tweak_mul(cx, out, a) != 1 { return 0; }
+        secp256k1_ec_pubkey_tweak_add(cx, out, b)
+    }
 }
 #[cfg(feature = "fuzztarget")]
 pub use self::fuzz_dummy::*;
+
+#[cfg(all(test, feature = "fuzztarget"))]
+mod fuzz_tests {
+    use key::{PublicKey, SecretKey};
+    use ecdh::SharedSecret;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn fakes_keep_behaviour() {
+        let s = Secp256k1::new();
+        let sk1 = SecretKey::from_slice(&[1; 32]).unwrap();
+        let sk2 = SecretKey::from_slice(&[2; 32]).unwrap();
+        let pk1 = PublicKey::from_secret_key(&s, &sk1);
+        let pk2 = PublicKey::from_secret_key(&s, &sk2);
+        assert_eq!(PublicKey::from_slice(&pk1.serialize()), Ok(pk1));
+
+        let msg = Message::from_slice(&[3; 32]).unwrap();
+        let sig = s.sign(&msg, &sk1);
+        assert_eq!(s.sign(&msg, &sk1), sig);
+        assert!(s.verify(&msg, &sig, &pk1).is_ok());
+        assert!(s.verify(&msg, &sig, &pk2).is_err());
+        assert_eq!(::Signature::from_compact(&s, &sig.serialize_compact(&s)), Ok(sig));
+
+        assert_eq!(SharedSecret::new(&s, &pk1, &sk2), SharedSecret::new(&s, &pk2, &sk1));
+    }
+}
+
+#[cfg(all(test, not(feature = "fuzztarget")))]
+mod tests {
+    use std::mem;
+    #[cfg(not(feature = "verify-only"))]
+    use std::ptr;
+
+    #[cfg(not(feature = "verify-only"))]
+    use types::c_uchar;
+
+    use super::*;
+
+    /// A value followed by guard bytes, to catch the C library writing past
+    /// the end of the Rust type
+    #[cfg(not(feature = "verify-only"))]
+    #[repr(C)]
+    struct Guarded<T> {
+        value: T,
+        guard: [c_uchar; 32],
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    impl<T> Guarded<T> {
+        fn new(value: T) -> Guarded<T> {
+            Guarded { value, guard: [0xa5; 32] }
+        }
+
+        fn intact(&self) -> bool {
+            self.guard == [0xa5; 32]
+        }
+    }
+
+    #[test]
+    fn type_layouts() {
+        // The C headers declare each of these as a struct holding a single
+        // unsigned char array, which has size and alignment of the array
+        assert_eq!(mem::size_of::<PublicKey>(), 64);
+        assert_eq!(mem::size_of::<Signature>(), 64);
+        assert_eq!(mem::size_of::<RecoverableSignature>(), 65);
+        assert_eq!(mem::size_of::<SharedSecret>(), 32);
+        assert_eq!(mem::align_of::<PublicKey>(), 1);
+        assert_eq!(mem::align_of::<Signature>(), 1);
+        assert_eq!(mem::align_of::<RecoverableSignature>(), 1);
+        assert_eq!(mem::align_of::<SharedSecret>(), 1);
+        assert_eq!(mem::size_of::<Option<NonceFn>>(), mem::size_of::<usize>());
+        assert_eq!(mem::size_of::<Option<CallbackFn>>(), mem::size_of::<usize>());
+    }
+
+    // Signing needs the generator table, which "verify-only" never builds
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn library_writes_stay_in_bounds() {
+        let sk = [0xcd; 32];
+        let msg = [0x5a; 32];
+        let mut pk = Guarded::new(PublicKey::new());
+        let mut sig = Guarded::new(Signature::new());
+        let mut recsig = Guarded::new(RecoverableSignature::new());
+        let mut secret = Guarded::new(SharedSecret::new());
+        unsafe {
+            let cx = secp256k1_context_create(SECP256K1_START_SIGN | SECP256K1_START_VERIFY);
+            assert_eq!(secp256k1_ec_pubkey_create(cx, &mut pk.value, sk.as_ptr()), 1);
+            assert_eq!(secp256k1_ecdsa_sign(cx, &mut sig.value, msg.as_ptr(), sk.as_ptr(),
+                                            secp256k1_nonce_function_rfc6979, ptr::null()), 1);
+            assert_eq!(secp256k1_ecdsa_sign_recoverable(cx, &mut recsig.value, msg.as_ptr(), sk.as_ptr(),
+                                                        secp256k1_nonce_function_rfc6979, ptr::null()), 1);
+            assert_eq!(secp256k1_ecdh(cx, &mut secret.value, &pk.value, sk.as_ptr()), 1);
+            assert_eq!(secp256k1_ecdsa_verify(cx, &sig.value, msg.as_ptr(), &pk.value), 1);
+            secp256k1_context_destroy(cx);
+        }
+        assert!(pk.intact() && sig.intact() && recsig.intact() && secret.intact());
+    }
+}
@@ -0,0 +1,161 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Fuzzing and property testing
+//! `arbitrary::Arbitrary` for `Message`, `SecretKey`, `PublicKey` and
+//! `Signature`, and proptest strategies for them, for fuzz and property
+//! tests of code built on this library. Everything generated is valid:
+//! input bytes which don't make a valid key fall back to the key 1, and
+//! signatures are parsed from compact bytes with the top bits of `r` and
+//! `s` cleared when needed. Keys are derived from the generated bytes, so
+//! they are as secret as the fuzzer's input, i.e. not at all. Requires
+//! compilation with the "fuzz" feature.
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::{any, Strategy};
+
+use constants;
+use global::SECP256K1;
+use key::{self, PublicKey, SecretKey};
+use super::{Message, Signature};
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Message> {
+        Ok(Message::from(<[u8; constants::MESSAGE_SIZE]>::arbitrary(u)?))
+    }
+}
+
+/// A secret key from `bytes`, or the key 1 if they are not valid
+fn secret_key_from(bytes: &[u8; constants::SECRET_KEY_SIZE]) -> SecretKey {
+    SecretKey::from_slice(bytes).unwrap_or(key::ONE_KEY)
+}
+
+/// A signature from compact `bytes`, clearing the top bits of `r` and `s`
+/// (which puts both below the curve order) if they don't parse as is
+fn signature_from(bytes: &[u8; constants::COMPACT_SIGNATURE_SIZE]) -> Signature {
+    Signature::from_compact_bytes(bytes).unwrap_or_else(|_| {
+        let mut masked = *bytes;
+        masked[0] &= 0x7f;
+        masked[32] &= 0x7f;
+        Signature::from_compact_bytes(&masked).expect("r and s below 2^255 parse")
+    })
+}
+
+impl<'a> Arbitrary<'a> for SecretKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<SecretKey> {
+        Ok(secret_key_from(&<[u8; constants::SECRET_KEY_SIZE]>::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PublicKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<PublicKey> {
+        Ok(PublicKey::from_secret_key(SECP256K1, &SecretKey::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Signature {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Signature> {
+        let mut bytes = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        u.fill_buffer(&mut bytes)?;
+        Ok(signature_from(&bytes))
+    }
+}
+
+/// A proptest strategy for messages
+pub fn message() -> impl Strategy<Value = Message> {
+    any::<[u8; constants::MESSAGE_SIZE]>().prop_map(Message::from)
+}
+
+/// A proptest strategy for secret keys
+pub fn secret_key() -> impl Strategy<Value = SecretKey> {
+    any::<[u8; constants::SECRET_KEY_SIZE]>().prop_map(|bytes| secret_key_from(&bytes))
+}
+
+/// A proptest strategy for public keys
+pub fn public_key() -> impl Strategy<Value = PublicKey> {
+    secret_key().prop_map(|sk| PublicKey::from_secret_key(SECP256K1, &sk))
+}
+
+/// A proptest strategy for keypairs
+pub fn keypair() -> impl Strategy<Value = (SecretKey, PublicKey)> {
+    secret_key().prop_map(|sk| (sk, PublicKey::from_secret_key(SECP256K1, &sk)))
+}
+
+/// A proptest strategy for signatures which are well-formed but not made by
+/// any particular key; see `signed_message` for ones which verify
+pub fn signature() -> impl Strategy<Value = Signature> {
+    (any::<[u8; 32]>(), any::<[u8; 32]>()).prop_map(|(r, s)| {
+        let mut bytes = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        bytes[..32].copy_from_slice(&r);
+        bytes[32..].copy_from_slice(&s);
+        signature_from(&bytes)
+    })
+}
+
+/// A proptest strategy for a message, a valid signature on it and the
+/// signing public key
+pub fn signed_message() -> impl Strategy<Value = (Message, Signature, PublicKey)> {
+    (message(), keypair()).prop_map(|(msg, (sk, pk))| (msg, SECP256K1.sign(&msg, &sk), pk))
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::*;
+
+    use super::super::{Message, PublicKey, SECP256K1, SecretKey, Signature};
+    use super::super::key::ONE_KEY;
+
+    #[test]
+    fn arbitrary() {
+        let data = [0x5a; 200];
+        let mut u = Unstructured::new(&data);
+        let msg = Message::arbitrary(&mut u).unwrap();
+        assert_eq!(msg[..], [0x5a; 32][..]);
+        let sk = SecretKey::arbitrary(&mut u).unwrap();
+        assert_eq!(sk.secret_bytes()[..], [0x5a; 32][..]);
+        let pk = PublicKey::arbitrary(&mut u).unwrap();
+        assert_eq!(pk, PublicKey::from_secret_key(SECP256K1, &sk));
+        Signature::arbitrary(&mut u).unwrap();
+
+        // Invalid bytes still give valid values
+        let data = [0xff; 200];
+        let mut u = Unstructured::new(&data);
+        assert_eq!(SecretKey::arbitrary(&mut u).unwrap(), ONE_KEY);
+        let sig = Signature::arbitrary(&mut u).unwrap();
+        assert_eq!(sig.serialize_compact(SECP256K1)[0], 0x7f);
+        // As does running out of data
+        let mut u = Unstructured::new(&[]);
+        assert_eq!(SecretKey::arbitrary(&mut u).unwrap(), ONE_KEY);
+    }
+
+    proptest! {
+        #[test]
+        fn signed_messages_verify((msg, sig, pk) in super::signed_message()) {
+            prop_assert!(SECP256K1.verify(&msg, &sig, &pk).is_ok());
+        }
+
+        #[test]
+        fn keypairs_match((sk, pk) in super::keypair(), other in super::public_key()) {
+            prop_assert_eq!(PublicKey::from_secret_key(SECP256K1, &sk), pk);
+            prop_assert!(PublicKey::from_slice(&other.serialize()).is_ok());
+        }
+
+        #[test]
+        fn signatures_round_trip(sig in super::signature()) {
+            let compact = sig.serialize_compact(SECP256K1);
+            prop_assert_eq!(Signature::from_compact(SECP256K1, &compact).unwrap(), sig);
+        }
+    }
+}
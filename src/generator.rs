@@ -0,0 +1,174 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Generators
+//! Nothing-up-my-sleeve alternative generators, whose discrete logarithm
+//! with respect to `G` (or each other) nobody knows. Pedersen commitments,
+//! DLEQ proofs and key blinding all need such a second generator `H`; a
+//! generator derived from a published tag and seed can be rederived by
+//! anyone, which shows that no trapdoor was chosen.
+//!
+//! `Generator::from_seed` hashes by try-and-increment: the x-coordinate is
+//! the tagged hash of the seed and a 4-byte little-endian counter, for the
+//! first counter that gives a point on the curve, and the y-coordinate is
+//! the even one. This takes variable time, about two hashes on average, so
+//! seeds must be public. Requires compilation with the "hashes" feature.
+
+use std::fmt;
+
+use constants;
+use hashes::TaggedHasher;
+use key::PublicKey;
+use scalar::Scalar;
+use {Error, Secp256k1, Verification};
+
+/// A generator of the secp256k1 group
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Generator(PublicKey);
+
+impl Generator {
+    /// The standard generator `G`
+    #[inline]
+    pub fn g() -> Generator {
+        Generator(PublicKey::from_slice(&constants::GENERATOR_G_COMPRESSED).expect("generator is valid"))
+    }
+
+    /// Derives the generator for `seed` in the domain of the hash tag `tag`
+    pub fn from_seed(tag: &[u8], seed: &[u8]) -> Generator {
+        let prefix = TaggedHasher::new(tag).chain(seed);
+        let mut counter = 0u32;
+        loop {
+            let mut engine = prefix.clone();
+            engine.input(&[counter as u8, (counter >> 8) as u8, (counter >> 16) as u8, (counter >> 24) as u8]);
+            let mut compressed = [0x02; constants::PUBLIC_KEY_SIZE];
+            compressed[1..].copy_from_slice(&engine.result());
+            // About half of all x-coordinates are on the curve
+            if let Ok(point) = PublicKey::from_slice(&compressed) {
+                return Generator(point);
+            }
+            counter += 1;
+        }
+    }
+
+    /// The generator as a curve point
+    #[inline]
+    pub fn as_public_key(&self) -> &PublicKey {
+        &self.0
+    }
+
+    /// Computes `scalar` times the generator. Fails with `PointAtInfinity`
+    /// if `scalar` is zero.
+    #[inline]
+    pub fn mul<C: Verification>(&self, secp: &Secp256k1<C>, scalar: &Scalar) -> Result<PublicKey, Error> {
+        let mut ret = self.0;
+        ret.mul_assign(secp, scalar)?;
+        Ok(ret)
+    }
+
+    /// Encodes the generator as a compressed point
+    #[inline]
+    pub fn serialize(&self) -> [u8; constants::PUBLIC_KEY_SIZE] {
+        self.0.serialize()
+    }
+
+    /// Parses a point as a generator. Every point other than infinity
+    /// generates the group, so this accepts any valid public key, without
+    /// telling whether it was derived by `from_seed`.
+    #[inline]
+    pub fn from_slice(data: &[u8]) -> Result<Generator, Error> {
+        PublicKey::from_slice(data).map(Generator)
+    }
+}
+
+impl From<PublicKey> for Generator {
+    #[inline]
+    fn from(pk: PublicKey) -> Generator {
+        Generator(pk)
+    }
+}
+
+impl From<Generator> for PublicKey {
+    #[inline]
+    fn from(generator: Generator) -> PublicKey {
+        generator.0
+    }
+}
+
+impl fmt::Debug for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Generator(")?;
+        ::hex::format(&self.serialize(), f)?;
+        f.write_str(")")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Generator {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        ::serde::Serialize::serialize(&self.0, s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Generator {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<Generator, D::Error> {
+        <PublicKey as ::serde::Deserialize>::deserialize(d).map(Generator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Generator;
+    use super::super::{constants, PublicKey};
+    #[cfg(not(feature = "verify-only"))]
+    use super::super::{Scalar, Secp256k1};
+    use super::super::Error::InvalidPublicKeyPrefix;
+
+    #[test]
+    fn derivation() {
+        let h = Generator::from_seed(b"test/generator", b"H");
+        assert_eq!(Generator::from_seed(b"test/generator", b"H"), h);
+        assert!(Generator::from_seed(b"test/generator", b"J") != h);
+        assert!(Generator::from_seed(b"test/other", b"H") != h);
+        assert!(h != Generator::g());
+        assert_eq!(h.serialize()[0], 0x02);
+
+        assert_eq!(Generator::from_slice(&h.serialize()), Ok(h));
+        assert_eq!(Generator::from_slice(&[0x05; 33]), Err(InvalidPublicKeyPrefix));
+        assert_eq!(Generator::g().serialize(), constants::GENERATOR_G_COMPRESSED);
+        assert_eq!(PublicKey::from(h), *h.as_public_key());
+    }
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn multiplication() {
+        let s = Secp256k1::new();
+        let mut bytes = [0u8; 32];
+        bytes[31] = 7;
+        let seven = Scalar::from_be_bytes(bytes).unwrap();
+
+        let pk = Generator::g().mul(&s, &seven).unwrap();
+        assert_eq!(pk, PublicKey::from_scalar_mul_g(&s, &seven).unwrap());
+        assert!(Generator::g().mul(&s, &Scalar::ZERO).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_test::{Token, assert_tokens};
+        static G: [u8; 33] = constants::GENERATOR_G_COMPRESSED;
+
+        assert_tokens(&Generator::g(), &[Token::BorrowedBytes(&G[..])]);
+    }
+}
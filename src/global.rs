@@ -0,0 +1,177 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Global context
+//! A lazily constructed, process-wide context for call sites which don't
+//! want to carry a `Secp256k1` handle around. The precomputation tables are
+//! only built the first time the context is used.
+//!
+//! `with_thread_local_context` instead gives each thread a context of its
+//! own, also built on first use, so that threads don't share blinding. In
+//! an enclave this needs the bound TCS policy, under which thread-locals
+//! are dropped when their thread exits.
+//!
+//! Code which only verifies can share `Secp256k1::new_verify_only_static`,
+//! which skips the signing tables.
+//!
+//! With the "no-ocall" feature, these contexts are randomized from the
+//! `rng::EntropySource` installed when they are built, so the source should
+//! be installed before their first use; without one they aren't randomized.
+
+use std::boxed::Box;
+#[cfg(not(feature = "verify-only"))]
+use std::ops::Deref;
+use std::ptr;
+use std::sync::Once;
+
+use {Secp256k1, VerifyOnly};
+#[cfg(not(feature = "verify-only"))]
+use All;
+
+/// Proxy for the global context; dereferences to a `Secp256k1<All>`
+#[cfg(not(feature = "verify-only"))]
+#[derive(Debug, Copy, Clone)]
+pub struct GlobalContext {
+    __private: (),
+}
+
+/// A global context with all capabilities, built on first use. When compiled
+/// with the "rand" feature it is also randomized on construction.
+#[cfg(not(feature = "verify-only"))]
+pub static SECP256K1: &'static GlobalContext = &GlobalContext { __private: () };
+
+#[cfg(not(feature = "verify-only"))]
+impl Deref for GlobalContext {
+    type Target = Secp256k1<All>;
+
+    fn deref(&self) -> &Secp256k1<All> {
+        static ONCE: Once = Once::new();
+        static mut CONTEXT: *const Secp256k1<All> = ptr::null();
+        ONCE.call_once(|| unsafe {
+            #[allow(unused_mut)]
+            let mut ctx = Secp256k1::new();
+            #[cfg(feature = "rand")]
+            randomize(&mut ctx);
+            // Deliberately leaked: the context lives for the rest of the program
+            CONTEXT = Box::into_raw(Box::new(ctx));
+        });
+        unsafe {
+            debug_assert!(!CONTEXT.is_null());
+            &*CONTEXT
+        }
+    }
+}
+
+impl Secp256k1<VerifyOnly> {
+    /// Returns a verification context shared by the whole process, built
+    /// the first time any thread asks for it, so that verifying code pays
+    /// for the tables once instead of once per context. Verification
+    /// doesn't use blinding, so unlike `SECP256K1` it is never randomized
+    /// and nothing is lost by sharing it.
+    pub fn new_verify_only_static() -> &'static Secp256k1<VerifyOnly> {
+        static ONCE: Once = Once::new();
+        static mut CONTEXT: *const Secp256k1<VerifyOnly> = ptr::null();
+        ONCE.call_once(|| unsafe {
+            // Deliberately leaked, like the global context
+            CONTEXT = Box::into_raw(Box::new(Secp256k1::verification_only()));
+        });
+        unsafe {
+            debug_assert!(!CONTEXT.is_null());
+            &*CONTEXT
+        }
+    }
+}
+
+/// Runs `f` with the calling thread's own context, which has all
+/// capabilities and is built the first time the thread calls this. When
+/// compiled with the "rand" feature it is also randomized on construction,
+/// with a seed of its own.
+#[cfg(not(feature = "verify-only"))]
+pub fn with_thread_local_context<T, F: FnOnce(&Secp256k1<All>) -> T>(f: F) -> T {
+    thread_local! {
+        static CONTEXT: Secp256k1<All> = {
+            #[allow(unused_mut)]
+            let mut ctx = Secp256k1::new();
+            #[cfg(feature = "rand")]
+            randomize(&mut ctx);
+            ctx
+        };
+    }
+    CONTEXT.with(f)
+}
+
+/// Randomizes a context built on first use, unless the "no-ocall" feature
+/// is on and no entropy source is installed yet, in which case it is left
+/// as `Secp256k1::new` made it
+#[cfg(all(feature = "rand", not(feature = "verify-only")))]
+fn randomize(ctx: &mut Secp256k1<All>) {
+    use rand_core::RngCore;
+
+    let mut seed = [0; 32];
+    if ::rng::ambient().try_fill_bytes(&mut seed).is_ok() {
+        ctx.randomize_with_seed(&seed);
+    }
+    ::cleanup::wipe(&mut seed);
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::thread;
+
+    use super::{with_thread_local_context, SECP256K1};
+    use super::super::{Message, Secp256k1};
+    use key::{PublicKey, SecretKey};
+
+    #[test]
+    fn global_context() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&SECP256K1, &sk);
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+
+        let sig = SECP256K1.sign(&msg, &sk);
+        assert!(SECP256K1.verify(&msg, &sig, &pk).is_ok());
+        // Every dereference hands out the same context
+        assert_eq!(SECP256K1.ctx, SECP256K1.ctx);
+    }
+
+    #[test]
+    fn verify_only_static() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&SECP256K1, &sk);
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let sig = SECP256K1.sign(&msg, &sk);
+
+        let ctx = Secp256k1::new_verify_only_static();
+        assert!(ctx.verify(&msg, &sig, &pk).is_ok());
+        let other = thread::spawn(|| Secp256k1::new_verify_only_static().ctx as usize).join().unwrap();
+        assert_eq!(ctx.ctx as usize, other);
+    }
+
+    #[test]
+    fn thread_local_context() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let (ctx, sig) = with_thread_local_context(|s| (s.ctx as usize, s.sign(&msg, &sk)));
+        // The same context every time on this thread...
+        assert_eq!(with_thread_local_context(|s| s.ctx as usize), ctx);
+        // ...and another one on another thread, making the same signatures
+        let (other_ctx, other_sig) = thread::spawn(move || {
+            with_thread_local_context(|s| (s.ctx as usize, s.sign(&msg, &sk)))
+        }).join().unwrap();
+        assert!(other_ctx != ctx);
+        assert_eq!(other_sig, sig);
+        let pk = PublicKey::from_secret_key(&SECP256K1, &sk);
+        assert!(with_thread_local_context(|s| s.verify(&msg, &sig, &pk)).is_ok());
+    }
+}
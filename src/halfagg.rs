@@ -0,0 +1,319 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Half-aggregation of Schnorr signatures
+//! Non-interactive half-aggregation of BIP340 signatures, as specified in
+//! the half-aggregation BIP draft: `n` signatures `(r_i, s_i)` become one
+//! aggregate `r_0 || ... || r_{n-1} || s` of `32 * (n + 1)` bytes, with
+//! `s = sum(z_i * s_i)`, nearly halving their size. The randomizers are
+//! `z_0 = 1` and, for `i > 0`, the `HalfAgg/randomizer` tagged hash of
+//! `r_0 || pk_0 || m_0 || ... || r_i || pk_i || m_i`.
+//!
+//! Anyone can aggregate, without any secret, so a relayer can collect
+//! attestations from many enclaves and forward one aggregate. Aggregation
+//! is incremental: an `Aggregator` can be resumed from an aggregate and the
+//! keys and messages it covers. Nothing checks the signatures on the way
+//! in; an invalid one just makes the aggregate invalid. The aggregate is
+//! verified in one multi-scalar multiplication over its `2n` points.
+//! An aggregate holds at most 65535 signatures. Requires compilation with
+//! the "hashes" feature.
+
+use std::vec::Vec;
+
+use blind::{challenge, SCHNORR_SIGNATURE_SIZE};
+use generator::Generator;
+use hashes::{TaggedHasher, HALFAGG_RANDOMIZER_TAG};
+use key::{Parity, PublicKey, XOnlyPublicKey};
+use scalar::Scalar;
+use scratch::ScratchSpace;
+use super::Error::{self, AggregateTooLarge, IncorrectSignature, InvalidPublicKey, InvalidSignature,
+                   PointAtInfinity};
+use {Message, Secp256k1, Verification};
+
+/// The most signatures an aggregate can hold
+pub const MAX_AGGREGATE_SIGNATURES: usize = 0xffff;
+
+/// A half-aggregated signature: the signatures' nonces and the combined `s`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AggregateSignature {
+    rs: Vec<[u8; 32]>,
+    s: Scalar,
+}
+
+impl AggregateSignature {
+    /// The number of signatures aggregated
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rs.len()
+    }
+
+    /// Whether no signatures are aggregated
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rs.is_empty()
+    }
+
+    /// Encodes the aggregate: each `r`, then `s` big-endian
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 * (self.rs.len() + 1));
+        for r in &self.rs {
+            out.extend_from_slice(r);
+        }
+        out.extend_from_slice(&self.s[..]);
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` if
+    /// the length is not a positive multiple of 32 or `s` is not below the
+    /// curve order, and with `AggregateTooLarge` for too many signatures.
+    pub fn from_slice(data: &[u8]) -> Result<AggregateSignature, Error> {
+        if data.is_empty() || data.len() % 32 != 0 {
+            return Err(InvalidSignature);
+        }
+        let (rs, s) = data.split_at(data.len() - 32);
+        if rs.len() / 32 > MAX_AGGREGATE_SIGNATURES {
+            return Err(AggregateTooLarge);
+        }
+        Ok(AggregateSignature {
+            rs: rs.chunks(32).map(array32).collect(),
+            s: Scalar::from_be_bytes(array32(s)).map_err(|_| InvalidSignature)?,
+        })
+    }
+
+    /// Checks the aggregate against the x-only keys and messages of its
+    /// signatures, in order, with the multi-scalar multiplication working in
+    /// `scratch`. Fails with `InvalidPublicKey` if a key is not on the
+    /// curve, with `IncorrectSignature` if the aggregate is wrong or covers
+    /// a different number of signatures, and with `NotEnoughMemory` if
+    /// `scratch` can't hold a single term.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, scratch: &mut ScratchSpace,
+                                   signed: &[([u8; 32], Message)]) -> Result<(), Error> {
+        if signed.len() != self.rs.len() {
+            return Err(IncorrectSignature);
+        }
+        // s * G = sum(z_i * (R_i + e_i * P_i)), so with -s * G added the sum
+        // of all terms is the point at infinity
+        let mut terms = Vec::with_capacity(2 * signed.len() + 1);
+        let mut engine = TaggedHasher::new(HALFAGG_RANDOMIZER_TAG);
+        for (i, (r, &(pk, msg))) in self.rs.iter().zip(signed.iter()).enumerate() {
+            let p = XOnlyPublicKey::from_slice(&pk).map_err(|_| InvalidPublicKey)?.public_key(Parity::Even);
+            let big_r = XOnlyPublicKey::from_slice(r).map_err(|_| IncorrectSignature)?.public_key(Parity::Even);
+            let z = randomizer(&mut engine, i, r, &pk, &msg);
+            terms.push((z, big_r));
+            terms.push((z.mul(&challenge(r, &pk, &msg)), p));
+        }
+        terms.push((self.s.negate(), Generator::g().into()));
+        match PublicKey::msm_with_scratch(secp, scratch, &terms) {
+            Err(PointAtInfinity) => Ok(()),
+            Ok(_) => Err(IncorrectSignature),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Builds an aggregate signature one signature at a time
+#[derive(Clone)]
+pub struct Aggregator {
+    engine: TaggedHasher,
+    rs: Vec<[u8; 32]>,
+    s: Scalar,
+}
+
+impl Default for Aggregator {
+    fn default() -> Aggregator {
+        Aggregator::new()
+    }
+}
+
+impl Aggregator {
+    /// Starts an empty aggregate
+    pub fn new() -> Aggregator {
+        Aggregator { engine: TaggedHasher::new(HALFAGG_RANDOMIZER_TAG), rs: Vec::new(), s: Scalar::ZERO }
+    }
+
+    /// Resumes aggregating into `aggregate`, which covers the signatures for
+    /// the x-only keys and messages in `signed`, in order. Fails with
+    /// `InvalidSignature` if their number differs from the aggregate's.
+    pub fn resume(aggregate: &AggregateSignature, signed: &[([u8; 32], Message)]) -> Result<Aggregator, Error> {
+        if signed.len() != aggregate.rs.len() {
+            return Err(InvalidSignature);
+        }
+        let mut engine = TaggedHasher::new(HALFAGG_RANDOMIZER_TAG);
+        for (r, &(pk, msg)) in aggregate.rs.iter().zip(signed.iter()) {
+            engine.input(r);
+            engine.input(&pk);
+            engine.input(&msg[..]);
+        }
+        Ok(Aggregator { engine, rs: aggregate.rs.clone(), s: aggregate.s })
+    }
+
+    /// Adds the BIP340 signature `sig` for `msg` under the x-only key `pk`.
+    /// Fails with `InvalidSignature` if its `s` is not below the curve
+    /// order, and with `AggregateTooLarge` if the aggregate is full.
+    pub fn add(&mut self, pk: &[u8; 32], msg: &Message, sig: &[u8; SCHNORR_SIGNATURE_SIZE]) -> Result<(), Error> {
+        if self.rs.len() >= MAX_AGGREGATE_SIGNATURES {
+            return Err(AggregateTooLarge);
+        }
+        let r = array32(&sig[..32]);
+        let s = Scalar::from_be_bytes(array32(&sig[32..])).map_err(|_| InvalidSignature)?;
+        let z = randomizer(&mut self.engine, self.rs.len(), &r, pk, msg);
+        self.s = self.s.add(&z.mul(&s));
+        self.rs.push(r);
+        Ok(())
+    }
+
+    /// The number of signatures added so far
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.rs.len()
+    }
+
+    /// Whether no signatures have been added
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rs.is_empty()
+    }
+
+    /// The aggregate of the signatures added so far
+    pub fn aggregate(&self) -> AggregateSignature {
+        AggregateSignature { rs: self.rs.clone(), s: self.s }
+    }
+}
+
+/// Aggregates BIP340 signatures, each given with its x-only key and
+/// message. Fails like `Aggregator::add`.
+pub fn aggregate(signatures: &[([u8; 32], Message, [u8; SCHNORR_SIGNATURE_SIZE])])
+                 -> Result<AggregateSignature, Error> {
+    let mut aggregator = Aggregator::new();
+    for &(pk, msg, sig) in signatures {
+        aggregator.add(&pk, &msg, &sig)?;
+    }
+    Ok(aggregator.aggregate())
+}
+
+/// Feeds the `index`-th signature into `engine` and returns its randomizer
+fn randomizer(engine: &mut TaggedHasher, index: usize, r: &[u8; 32], pk: &[u8; 32], msg: &Message) -> Scalar {
+    engine.input(r);
+    engine.input(pk);
+    engine.input(&msg[..]);
+    if index == 0 {
+        return Scalar::ONE;
+    }
+    Scalar::from_be_bytes_mod_order(&engine.clone().result())
+}
+
+fn array32(data: &[u8]) -> [u8; 32] {
+    let mut ret = [0u8; 32];
+    ret.copy_from_slice(&data[..32]);
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use blind::{self, SCHNORR_SIGNATURE_SIZE};
+    use scalar::Scalar;
+    use scratch::ScratchSpace;
+    use super::{aggregate, AggregateSignature, Aggregator};
+    use super::super::Error::{IncorrectSignature, InvalidPublicKey, InvalidSignature};
+    use {Message, PublicKey, Secp256k1, SecretKey};
+
+    /// A BIP340 signature with a random nonce, and the x-only key
+    fn sign(s: &Secp256k1<::All>, msg: &Message) -> ([u8; 32], [u8; SCHNORR_SIGNATURE_SIZE]) {
+        let even = |sk: SecretKey| {
+            let pk = PublicKey::from_secret_key(s, &sk).serialize();
+            let x = Scalar::from_secret_key(&sk);
+            (if pk[0] == 0x02 { x } else { x.negate() }, super::array32(&pk[1..]))
+        };
+        let (d, pk) = even(SecretKey::new(s, &mut thread_rng()));
+        let (k, r) = even(SecretKey::new(s, &mut thread_rng()));
+        let mut sig = [0u8; SCHNORR_SIGNATURE_SIZE];
+        sig[..32].copy_from_slice(&r);
+        sig[32..].copy_from_slice(&k.add(&blind::challenge(&r, &pk, msg).mul(&d))[..]);
+        assert_eq!(blind::verify_schnorr(s, &sig, msg, &pk), Ok(()));
+        (pk, sig)
+    }
+
+    #[test]
+    fn aggregate_and_verify() {
+        let s = Secp256k1::new();
+        let mut scratch = ScratchSpace::new(&s, 1 << 20).unwrap();
+        let sigs: Vec<_> = (0..20u8).map(|i| {
+            let msg = Message::from_slice(&[i; 32]).unwrap();
+            let (pk, sig) = sign(&s, &msg);
+            (pk, msg, sig)
+        }).collect();
+        let signed: Vec<_> = sigs.iter().map(|&(pk, msg, _)| (pk, msg)).collect();
+
+        let agg = aggregate(&sigs).unwrap();
+        assert_eq!(agg.len(), 20);
+        assert_eq!(agg.serialize().len(), 32 * 21);
+        assert_eq!(agg.verify(&s, &mut scratch, &signed), Ok(()));
+        assert_eq!(AggregateSignature::from_slice(&agg.serialize()), Ok(agg.clone()));
+
+        // Incrementally, from a serialized aggregate
+        let first = aggregate(&sigs[..7]).unwrap();
+        let first = AggregateSignature::from_slice(&first.serialize()).unwrap();
+        assert_eq!(first.verify(&s, &mut scratch, &signed[..7]), Ok(()));
+        let mut aggregator = Aggregator::resume(&first, &signed[..7]).unwrap();
+        for &(pk, msg, sig) in &sigs[7..] {
+            aggregator.add(&pk, &msg, &sig).unwrap();
+        }
+        assert_eq!(aggregator.aggregate(), agg);
+
+        // Wrong order, message, key or signature
+        let mut swapped = signed.clone();
+        swapped.swap(3, 4);
+        assert_eq!(agg.verify(&s, &mut scratch, &swapped), Err(IncorrectSignature));
+        let mut wrong = signed.clone();
+        wrong[5].1 = Message::from_slice(&[0xff; 32]).unwrap();
+        assert_eq!(agg.verify(&s, &mut scratch, &wrong), Err(IncorrectSignature));
+        wrong[5] = (sign(&s, &signed[5].1).0, signed[5].1);
+        assert_eq!(agg.verify(&s, &mut scratch, &wrong), Err(IncorrectSignature));
+        wrong[5].0 = [0; 32];
+        assert_eq!(agg.verify(&s, &mut scratch, &wrong), Err(InvalidPublicKey));
+        assert_eq!(agg.verify(&s, &mut scratch, &signed[1..]), Err(IncorrectSignature));
+        let mut bad = sigs.clone();
+        bad[9].2[63] ^= 1;
+        assert_eq!(aggregate(&bad).unwrap().verify(&s, &mut scratch, &signed), Err(IncorrectSignature));
+        // Without randomizers, two wrong signatures could cancel out
+        let mut cancel = sigs.clone();
+        let one = Scalar::ONE;
+        let s0 = Scalar::from_be_bytes(super::array32(&cancel[0].2[32..])).unwrap().add(&one);
+        let s1 = Scalar::from_be_bytes(super::array32(&cancel[1].2[32..])).unwrap().add(&one.negate());
+        cancel[0].2[32..].copy_from_slice(&s0[..]);
+        cancel[1].2[32..].copy_from_slice(&s1[..]);
+        assert_eq!(aggregate(&cancel).unwrap().verify(&s, &mut scratch, &signed), Err(IncorrectSignature));
+
+        // The empty aggregate
+        let empty = aggregate(&[]).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.serialize(), vec![0; 32]);
+        assert_eq!(empty.verify(&s, &mut scratch, &[]), Ok(()));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(AggregateSignature::from_slice(&[]), Err(InvalidSignature));
+        assert_eq!(AggregateSignature::from_slice(&[0; 33]), Err(InvalidSignature));
+        assert_eq!(AggregateSignature::from_slice(&[0xff; 64]), Err(InvalidSignature));
+
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let mut aggregator = Aggregator::new();
+        assert_eq!(aggregator.add(&[1; 32], &msg, &[0xff; 64]), Err(InvalidSignature));
+        assert!(aggregator.is_empty());
+        let agg = aggregate(&[([1; 32], msg, [1; 64])]).unwrap();
+        assert_eq!(Aggregator::resume(&agg, &[]).err(), Some(InvalidSignature));
+    }
+}
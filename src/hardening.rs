@@ -0,0 +1,95 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Production hardening
+//! The memory of an enclave launched in debug mode can be read by the host,
+//! so a production key loaded into one is as good as leaked, and anything
+//! it exports even more so. With the "production-hardening" feature the
+//! operations which hand out secret key material refuse to run in a debug
+//! enclave:
+//!
+//! * `SecretKey::as_ref`, indexing a `SecretKey` (`sk[..]`, `sk[i]`),
+//!   `SecretKey::display_secret` and converting a `SecretKey` into a
+//!   `Scalar` panic, since they have no way to return an error. This also
+//!   covers the exports built on indexing: the DER, PEM and JWK encoders,
+//!   borsh and sgx_serialize serialization and the k256 conversion;
+//! * serializing a `SecretKey` with serde (including `serde_fixed`) fails;
+//! * `SecretKey::to_encrypted` fails with `DebugEnclave`;
+//! * `FileBackedVault::save`, and so `FileBackedVault::sign`, fails with
+//!   `DebugEnclave`.
+//!
+//! The raw pointer of `SecretKey::as_ptr`, meant for FFI, is not guarded.
+//!
+//! The enclave's DEBUG attribute is read from its own report the first time
+//! it is needed. Signing and key derivation keep working, so a debug build
+//! can still be tested end to end with throwaway keys. Enclaves which never
+//! need to export a key at all can build with "no-secret-export" instead,
+//! which removes these operations at compile time.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sgx_tse::rsgx_self_report;
+use sgx_types::SGX_FLAGS_DEBUG;
+
+use super::Error::{self, DebugEnclave};
+
+const UNKNOWN: usize = 0;
+const PRODUCTION: usize = 1;
+const DEBUG: usize = 2;
+
+/// The enclave's mode, as one of the constants above
+static MODE: AtomicUsize = AtomicUsize::new(UNKNOWN);
+
+fn mode_from_flags(flags: u64) -> usize {
+    if flags & SGX_FLAGS_DEBUG != 0 { DEBUG } else { PRODUCTION }
+}
+
+/// Whether the enclave was launched in debug mode
+pub fn is_debug_enclave() -> bool {
+    let mut mode = MODE.load(Ordering::Relaxed);
+    if mode == UNKNOWN {
+        // Attributes are fixed at launch, so racing threads store the same
+        mode = mode_from_flags(rsgx_self_report().body.attributes.flags);
+        MODE.store(mode, Ordering::Relaxed);
+    }
+    mode == DEBUG
+}
+
+/// Fails with `DebugEnclave` if secret key material must not leave this
+/// enclave because it runs in debug mode
+pub fn check_secret_export() -> Result<(), Error> {
+    if is_debug_enclave() { Err(DebugEnclave) } else { Ok(()) }
+}
+
+/// Like `check_secret_export`, for callers which can't return an error
+pub(crate) fn assert_secret_export() {
+    if is_debug_enclave() {
+        panic!("refusing to export secret key material from a debug enclave");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sgx_types::SGX_FLAGS_DEBUG;
+
+    use super::{mode_from_flags, DEBUG, PRODUCTION};
+
+    #[test]
+    fn flags() {
+        assert_eq!(mode_from_flags(0), PRODUCTION);
+        assert_eq!(mode_from_flags(0x1 | 0x4), PRODUCTION);
+        assert_eq!(mode_from_flags(SGX_FLAGS_DEBUG), DEBUG);
+        assert_eq!(mode_from_flags(0x1 | SGX_FLAGS_DEBUG), DEBUG);
+    }
+}
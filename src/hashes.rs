@@ -0,0 +1,1064 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Hashes
+//! A self-contained SHA-256 implementation, so that arbitrary data can be
+//! turned into messages and keys without pulling a hashing crate into the
+//! enclave, along with HMAC-SHA256 and HKDF-SHA256 (RFC 5869) built on it.
+//! SHA-512 and HMAC-SHA512 are provided for BIP32-style derivation,
+//! Keccak-256 for Ethereum's message digests, and RIPEMD-160 for Bitcoin's
+//! `hash160` key identifiers.
+//!
+//! Every domain-separated hash in the crate is a BIP340 tagged hash, built
+//! with `TaggedHasher` (or `tagged_hash` in one shot) from one of the tags
+//! below, so protocols layered on top can reproduce them exactly.
+//!
+//! Requires compilation with the "hashes" feature.
+
+/// The size (in bytes) of a SHA-256 digest
+pub const SHA256_SIZE: usize = 32;
+
+/// The size (in bytes) of a SHA-512 digest
+pub const SHA512_SIZE: usize = 64;
+
+/// The size (in bytes) of a Keccak-256 digest
+pub const KECCAK256_SIZE: usize = 32;
+
+/// The size (in bytes) of a RIPEMD-160 digest
+pub const RIPEMD160_SIZE: usize = 20;
+
+/// The tag of BIP340 Schnorr challenges, as used by `blind`
+pub const BIP340_CHALLENGE_TAG: &'static [u8] = b"BIP0340/challenge";
+
+/// The tag of BIP340 auxiliary randomness, as used by `psbt`
+pub const BIP340_AUX_TAG: &'static [u8] = b"BIP0340/aux";
+
+/// The tag of BIP340 nonces, as used by `psbt`
+pub const BIP340_NONCE_TAG: &'static [u8] = b"BIP0340/nonce";
+
+/// The tag of BIP341 taproot output key tweaks
+pub const TAPTWEAK_TAG: &'static [u8] = b"TapTweak";
+
+/// The tag of BIP341 script tree leaf hashes
+pub const TAPLEAF_TAG: &'static [u8] = b"TapLeaf";
+
+/// The tag of BIP341 script tree branch hashes
+pub const TAPBRANCH_TAG: &'static [u8] = b"TapBranch";
+
+/// The tag of the BIP352 input hash
+pub const BIP352_INPUTS_TAG: &'static [u8] = b"BIP0352/Inputs";
+
+/// The tag of BIP352 per-output shared secret tweaks
+pub const BIP352_SHARED_SECRET_TAG: &'static [u8] = b"BIP0352/SharedSecret";
+
+/// The tag of BIP352 label tweaks
+pub const BIP352_LABEL_TAG: &'static [u8] = b"BIP0352/Label";
+
+/// The tag of the hash of a ring member onto a point in `ring`
+pub const RING_HASH_TO_POINT_TAG: &'static [u8] = b"rust-secp256k1-sgx/ring/hash-to-point";
+
+/// The tag of ring signature challenges
+pub const RING_CHALLENGE_TAG: &'static [u8] = b"rust-secp256k1-sgx/ring/challenge";
+
+/// The tag of the key tweaks of `whitelist` signatures
+pub const WHITELIST_TWEAK_TAG: &'static [u8] = b"rust-secp256k1-sgx/whitelist/tweak";
+
+/// The tag of whitelist signature challenges
+pub const WHITELIST_CHALLENGE_TAG: &'static [u8] = b"rust-secp256k1-sgx/whitelist/challenge";
+
+/// The tag of the seed of the generator `H` of `rangeproof`
+pub const RANGE_PROOF_GENERATOR_TAG: &'static [u8] = b"rust-secp256k1-sgx/range-proof/generator";
+
+/// The tag of range proof challenges
+pub const RANGE_PROOF_CHALLENGE_TAG: &'static [u8] = b"rust-secp256k1-sgx/range-proof/challenge";
+
+/// The tag of the entry keys of a `cache::VerificationCache`
+pub const VERIFICATION_CACHE_TAG: &'static [u8] = b"rust-secp256k1-sgx/verification-cache";
+
+/// The tag of the randomizers of `halfagg` aggregate signatures
+pub const HALFAGG_RANDOMIZER_TAG: &'static [u8] = b"HalfAgg/randomizer";
+
+/// The tag of `PublicKey::blinding_factor`
+pub const KEY_BLINDING_TAG: &'static [u8] = b"rust-secp256k1-sgx/key-blinding";
+
+/// The tag of the hash of `oprf` inputs to the curve
+pub const OPRF_HASH_TO_CURVE_TAG: &'static [u8] = b"rust-secp256k1-sgx/oprf/hash-to-curve";
+
+/// The tag of `oprf` outputs
+pub const OPRF_FINALIZE_TAG: &'static [u8] = b"rust-secp256k1-sgx/oprf/finalize";
+
+/// The tag of `commitment::NonceCommitment`s
+pub const NONCE_COMMITMENT_TAG: &'static [u8] = b"rust-secp256k1-sgx/nonce-commitment";
+
+/// The tag of the usage chains of `vault` keys
+pub const VAULT_USAGE_CHAIN_TAG: &'static [u8] = b"rust-secp256k1-sgx/vault/usage-chain";
+
+/// The tag of the messages signed by approvers of `vault` key uses
+pub const VAULT_APPROVAL_TAG: &'static [u8] = b"rust-secp256k1-sgx/vault/approval";
+
+/// The tag of the signed body of an `audit::UsageReport`
+pub const USAGE_REPORT_TAG: &'static [u8] = b"rust-secp256k1-sgx/usage-report";
+
+/// The tag of the signed body of an `audit::DestructionCertificate`
+pub const KEY_DESTRUCTION_TAG: &'static [u8] = b"rust-secp256k1-sgx/key-destruction";
+
+/// The tag of `Message::from_hashed_data`
+pub const MESSAGE_HASH_TAG: &'static [u8] = b"rust-secp256k1-sgx/message";
+
+/// The tag of `SecretKey::from_hashed_data`
+pub const SECRET_KEY_HASH_TAG: &'static [u8] = b"rust-secp256k1-sgx/secret-key";
+
+const BLOCK_SIZE: usize = 64;
+
+const SHA512_BLOCK_SIZE: usize = 128;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const K512: [u64; 80] = [
+    0x428a2f98d728ae22, 0x7137449123ef65cd, 0xb5c0fbcfec4d3b2f, 0xe9b5dba58189dbbc, 0x3956c25bf348b538,
+    0x59f111f1b605d019, 0x923f82a4af194f9b, 0xab1c5ed5da6d8118, 0xd807aa98a3030242, 0x12835b0145706fbe,
+    0x243185be4ee4b28c, 0x550c7dc3d5ffb4e2, 0x72be5d74f27b896f, 0x80deb1fe3b1696b1, 0x9bdc06a725c71235,
+    0xc19bf174cf692694, 0xe49b69c19ef14ad2, 0xefbe4786384f25e3, 0x0fc19dc68b8cd5b5, 0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275, 0x4a7484aa6ea6e483, 0x5cb0a9dcbd41fbd4, 0x76f988da831153b5, 0x983e5152ee66dfab,
+    0xa831c66d2db43210, 0xb00327c898fb213f, 0xbf597fc7beef0ee4, 0xc6e00bf33da88fc2, 0xd5a79147930aa725,
+    0x06ca6351e003826f, 0x142929670a0e6e70, 0x27b70a8546d22ffc, 0x2e1b21385c26c926, 0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df, 0x650a73548baf63de, 0x766a0abb3c77b2a8, 0x81c2c92e47edaee6, 0x92722c851482353b,
+    0xa2bfe8a14cf10364, 0xa81a664bbc423001, 0xc24b8b70d0f89791, 0xc76c51a30654be30, 0xd192e819d6ef5218,
+    0xd69906245565a910, 0xf40e35855771202a, 0x106aa07032bbd1b8, 0x19a4c116b8d2d0c8, 0x1e376c085141ab53,
+    0x2748774cdf8eeb99, 0x34b0bcb5e19b48a8, 0x391c0cb3c5c95a63, 0x4ed8aa4ae3418acb, 0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3, 0x748f82ee5defb2fc, 0x78a5636f43172f60, 0x84c87814a1f0ab72, 0x8cc702081a6439ec,
+    0x90befffa23631e28, 0xa4506cebde82bde9, 0xbef9a3f7b2c67915, 0xc67178f2e372532b, 0xca273eceea26619c,
+    0xd186b8c721c0c207, 0xeada7dd6cde0eb1e, 0xf57d4f7fee6ed178, 0x06f067aa72176fba, 0x0a637dc5a2c898a6,
+    0x113f9804bef90dae, 0x1b710b35131c471b, 0x28db77f523047d84, 0x32caab7b40c72493, 0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c, 0x4cc5d4becb3e42b6, 0x597f299cfc657e2a, 0x5fcb6fab3ad6faec, 0x6c44198c4a475817,
+];
+
+const INITIAL_STATE_512: [u64; 8] = [
+    0x6a09e667f3bcc908, 0xbb67ae8584caa73b, 0x3c6ef372fe94f82b, 0xa54ff53a5f1d36f1,
+    0x510e527fade682d1, 0x9b05688c2b3e6c1f, 0x1f83d9abfb41bd6b, 0x5be0cd19137e2179,
+];
+
+/// An incremental SHA-256 engine
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buffer: [u8; BLOCK_SIZE],
+    buffer_len: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    /// Creates a new engine with the standard SHA-256 initial state
+    pub fn new() -> Sha256 {
+        Sha256 {
+            state: INITIAL_STATE,
+            buffer: [0; BLOCK_SIZE],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    /// Creates an engine for the BIP340 tagged hash with `tag`, i.e. one
+    /// which has already been fed `SHA256(tag) || SHA256(tag)`
+    pub fn tagged(tag: &[u8]) -> Sha256 {
+        let tag_hash = sha256(tag);
+        let mut engine = Sha256::new();
+        engine.input(&tag_hash);
+        engine.input(&tag_hash);
+        engine
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = ::std::cmp::min(BLOCK_SIZE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            self.process_block(&block);
+            self.buffer_len = 0;
+        }
+
+        while data.len() >= BLOCK_SIZE {
+            self.process_block(&data[..BLOCK_SIZE]);
+            data = &data[BLOCK_SIZE..];
+        }
+
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffer_len = data.len();
+    }
+
+    /// Pads the input and returns the final digest
+    pub fn result(mut self) -> [u8; SHA256_SIZE] {
+        let bit_length = self.length.wrapping_mul(8);
+
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        let pad_len = if self.buffer_len < 56 { 56 - self.buffer_len } else { 120 - self.buffer_len };
+        for i in 0..8 {
+            padding[pad_len + i] = (bit_length >> (56 - 8 * i)) as u8;
+        }
+        // `input` would count the padding towards the message length, which
+        // no longer matters since the length has already been captured
+        self.input(&padding[..pad_len + 8]);
+        debug_assert_eq!(self.buffer_len, 0);
+
+        let mut ret = [0; SHA256_SIZE];
+        for (i, word) in self.state.iter().enumerate() {
+            ret[4 * i] = (word >> 24) as u8;
+            ret[4 * i + 1] = (word >> 16) as u8;
+            ret[4 * i + 2] = (word >> 8) as u8;
+            ret[4 * i + 3] = *word as u8;
+        }
+        ret
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        debug_assert_eq!(block.len(), BLOCK_SIZE);
+
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = (block[4 * i] as u32) << 24 | (block[4 * i + 1] as u32) << 16 |
+                   (block[4 * i + 2] as u32) << 8 | block[4 * i + 3] as u32;
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut a = self.state[0];
+        let mut b = self.state[1];
+        let mut c = self.state[2];
+        let mut d = self.state[3];
+        let mut e = self.state[4];
+        let mut f = self.state[5];
+        let mut g = self.state[6];
+        let mut h = self.state[7];
+
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+        self.state[4] = self.state[4].wrapping_add(e);
+        self.state[5] = self.state[5].wrapping_add(f);
+        self.state[6] = self.state[6].wrapping_add(g);
+        self.state[7] = self.state[7].wrapping_add(h);
+    }
+}
+
+/// Computes the SHA-256 digest of `data` in one shot
+pub fn sha256(data: &[u8]) -> [u8; SHA256_SIZE] {
+    let mut engine = Sha256::new();
+    engine.input(data);
+    engine.result()
+}
+
+/// An incremental SHA-512 engine
+#[derive(Clone)]
+pub struct Sha512 {
+    state: [u64; 8],
+    buffer: [u8; SHA512_BLOCK_SIZE],
+    buffer_len: usize,
+    length: u64,
+}
+
+impl Sha512 {
+    /// Creates a new engine with the standard SHA-512 initial state
+    pub fn new() -> Sha512 {
+        Sha512 {
+            state: INITIAL_STATE_512,
+            buffer: [0; SHA512_BLOCK_SIZE],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = ::std::cmp::min(SHA512_BLOCK_SIZE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < SHA512_BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            self.process_block(&block);
+            self.buffer_len = 0;
+        }
+
+        while data.len() >= SHA512_BLOCK_SIZE {
+            self.process_block(&data[..SHA512_BLOCK_SIZE]);
+            data = &data[SHA512_BLOCK_SIZE..];
+        }
+
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffer_len = data.len();
+    }
+
+    /// Pads the input and returns the final digest
+    pub fn result(mut self) -> [u8; SHA512_SIZE] {
+        let bit_length = self.length.wrapping_mul(8);
+
+        // The length field is 128 bits; messages of 2^61 bytes or more are
+        // not supported, so its high half is always zero
+        let mut padding = [0u8; SHA512_BLOCK_SIZE + 16];
+        padding[0] = 0x80;
+        let pad_len = if self.buffer_len < 112 { 112 - self.buffer_len } else { 240 - self.buffer_len };
+        for i in 0..8 {
+            padding[pad_len + 8 + i] = (bit_length >> (56 - 8 * i)) as u8;
+        }
+        self.input(&padding[..pad_len + 16]);
+        debug_assert_eq!(self.buffer_len, 0);
+
+        let mut ret = [0; SHA512_SIZE];
+        for (i, word) in self.state.iter().enumerate() {
+            for j in 0..8 {
+                ret[8 * i + j] = (word >> (56 - 8 * j)) as u8;
+            }
+        }
+        ret
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        debug_assert_eq!(block.len(), SHA512_BLOCK_SIZE);
+
+        let mut w = [0u64; 80];
+        for i in 0..16 {
+            for j in 0..8 {
+                w[i] = w[i] << 8 | block[8 * i + j] as u64;
+            }
+        }
+        for i in 16..80 {
+            let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+            let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let mut a = self.state[0];
+        let mut b = self.state[1];
+        let mut c = self.state[2];
+        let mut d = self.state[3];
+        let mut e = self.state[4];
+        let mut f = self.state[5];
+        let mut g = self.state[6];
+        let mut h = self.state[7];
+
+        for i in 0..80 {
+            let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K512[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+        self.state[4] = self.state[4].wrapping_add(e);
+        self.state[5] = self.state[5].wrapping_add(f);
+        self.state[6] = self.state[6].wrapping_add(g);
+        self.state[7] = self.state[7].wrapping_add(h);
+    }
+}
+
+/// Computes the SHA-512 digest of `data` in one shot
+pub fn sha512(data: &[u8]) -> [u8; SHA512_SIZE] {
+    let mut engine = Sha512::new();
+    engine.input(data);
+    engine.result()
+}
+
+/// An incremental HMAC-SHA256 engine
+#[derive(Clone)]
+pub struct HmacSha256 {
+    inner: Sha256,
+    outer: Sha256,
+}
+
+impl HmacSha256 {
+    /// Creates a new engine keyed with `key`, which may have any length
+    pub fn new(key: &[u8]) -> HmacSha256 {
+        let mut block = [0u8; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            block[..SHA256_SIZE].copy_from_slice(&sha256(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha256::new();
+        let mut outer = Sha256::new();
+        for byte in block.iter_mut() {
+            *byte ^= 0x36;
+        }
+        inner.input(&block);
+        for byte in block.iter_mut() {
+            *byte ^= 0x36 ^ 0x5c;
+        }
+        outer.input(&block);
+        ::cleanup::wipe(&mut block);
+        HmacSha256 { inner, outer }
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, data: &[u8]) {
+        self.inner.input(data);
+    }
+
+    /// Returns the final MAC
+    pub fn result(self) -> [u8; SHA256_SIZE] {
+        let mut outer = self.outer;
+        outer.input(&self.inner.result());
+        outer.result()
+    }
+}
+
+/// Computes HMAC-SHA256 of `data` under `key` in one shot
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; SHA256_SIZE] {
+    let mut engine = HmacSha256::new(key);
+    engine.input(data);
+    engine.result()
+}
+
+/// An incremental HMAC-SHA512 engine
+#[derive(Clone)]
+pub struct HmacSha512 {
+    inner: Sha512,
+    outer: Sha512,
+}
+
+impl HmacSha512 {
+    /// Creates a new engine keyed with `key`, which may have any length
+    pub fn new(key: &[u8]) -> HmacSha512 {
+        let mut block = [0u8; SHA512_BLOCK_SIZE];
+        if key.len() > SHA512_BLOCK_SIZE {
+            block[..SHA512_SIZE].copy_from_slice(&sha512(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Sha512::new();
+        let mut outer = Sha512::new();
+        for byte in block.iter_mut() {
+            *byte ^= 0x36;
+        }
+        inner.input(&block);
+        for byte in block.iter_mut() {
+            *byte ^= 0x36 ^ 0x5c;
+        }
+        outer.input(&block);
+        ::cleanup::wipe(&mut block);
+        HmacSha512 { inner, outer }
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, data: &[u8]) {
+        self.inner.input(data);
+    }
+
+    /// Returns the final MAC
+    pub fn result(self) -> [u8; SHA512_SIZE] {
+        let mut outer = self.outer;
+        outer.input(&self.inner.result());
+        outer.result()
+    }
+}
+
+/// Computes HMAC-SHA512 of `data` under `key` in one shot
+pub fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; SHA512_SIZE] {
+    let mut engine = HmacSha512::new(key);
+    engine.input(data);
+    engine.result()
+}
+
+/// Fills `okm` with HKDF-SHA256 output (RFC 5869) for the input keying
+/// material `ikm`, the optional `salt` (empty means none) and the context
+/// `info`. Panics if `okm` is longer than 255 * 32 bytes, the most HKDF can
+/// produce.
+pub fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8], okm: &mut [u8]) {
+    assert!(okm.len() <= 255 * SHA256_SIZE, "HKDF output too long");
+
+    let mut prk = hmac_sha256(salt, ikm);
+    let mut block = [0u8; SHA256_SIZE];
+    for (i, chunk) in okm.chunks_mut(SHA256_SIZE).enumerate() {
+        let mut engine = HmacSha256::new(&prk);
+        if i > 0 {
+            engine.input(&block);
+        }
+        engine.input(info);
+        engine.input(&[i as u8 + 1]);
+        block = engine.result();
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+    ::cleanup::wipe(&mut prk);
+    ::cleanup::wipe(&mut block);
+}
+
+/// An incremental BIP340 tagged hash, `SHA256(SHA256(tag) || SHA256(tag) || data)`
+#[derive(Clone)]
+pub struct TaggedHasher(Sha256);
+
+impl TaggedHasher {
+    /// Starts a tagged hash with `tag`
+    pub fn new(tag: &[u8]) -> TaggedHasher {
+        TaggedHasher(Sha256::tagged(tag))
+    }
+
+    /// Feeds more data into the hash
+    pub fn input(&mut self, data: &[u8]) {
+        self.0.input(data);
+    }
+
+    /// Feeds more data into the hash and returns it, so that the hash of a
+    /// few fields can be written as one expression
+    pub fn chain(mut self, data: &[u8]) -> TaggedHasher {
+        self.0.input(data);
+        self
+    }
+
+    /// Returns the final digest
+    pub fn result(self) -> [u8; SHA256_SIZE] {
+        self.0.result()
+    }
+}
+
+/// Computes the BIP340 tagged hash of `data` with `tag` in one shot
+pub fn tagged_hash(tag: &[u8], data: &[u8]) -> [u8; SHA256_SIZE] {
+    TaggedHasher::new(tag).chain(data).result()
+}
+
+/// The bytes of input Keccak-256 absorbs per permutation
+const KECCAK256_RATE: usize = 136;
+
+const KECCAK_ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000, 0x000000000000808b,
+    0x0000000080000001, 0x8000000080008081, 0x8000000000008009, 0x000000000000008a, 0x0000000000000088,
+    0x0000000080008009, 0x000000008000000a, 0x000000008000808b, 0x800000000000008b, 0x8000000000008089,
+    0x8000000000008003, 0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// The rotation of each lane, indexed by `x + 5 * y`
+const KECCAK_ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27,
+    36, 44, 6, 55, 20,
+    3, 10, 43, 25, 39,
+    41, 45, 15, 21, 8,
+    18, 2, 61, 56, 14,
+];
+
+/// The Keccak-f[1600] permutation, on lanes indexed by `x + 5 * y`
+pub(crate) fn keccak_f(a: &mut [u64; 25]) {
+    for rc in KECCAK_ROUND_CONSTANTS.iter() {
+        let mut c = [0u64; 5];
+        for (x, column) in c.iter_mut().enumerate() {
+            *column = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+
+        let mut b = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = a[x + 5 * y].rotate_left(KECCAK_ROTATIONS[x + 5 * y]);
+            }
+        }
+        for x in 0..5 {
+            for y in 0..5 {
+                a[x + 5 * y] = b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+            }
+        }
+
+        a[0] ^= *rc;
+    }
+}
+
+/// An incremental Keccak-256 engine. This is the original Keccak padding
+/// which Ethereum uses, not the standardized SHA3-256, so the digests
+/// differ from SHA3-256 ones.
+#[derive(Clone)]
+pub struct Keccak256 {
+    state: [u64; 25],
+    buffer: [u8; KECCAK256_RATE],
+    buffer_len: usize,
+}
+
+impl Keccak256 {
+    /// Creates a new engine
+    pub fn new() -> Keccak256 {
+        Keccak256 {
+            state: [0; 25],
+            buffer: [0; KECCAK256_RATE],
+            buffer_len: 0,
+        }
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, mut data: &[u8]) {
+        if self.buffer_len > 0 {
+            let take = ::std::cmp::min(KECCAK256_RATE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < KECCAK256_RATE {
+                return;
+            }
+            let block = self.buffer;
+            self.absorb(&block);
+            self.buffer_len = 0;
+        }
+
+        while data.len() >= KECCAK256_RATE {
+            self.absorb(&data[..KECCAK256_RATE]);
+            data = &data[KECCAK256_RATE..];
+        }
+
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffer_len = data.len();
+    }
+
+    /// Pads the input and returns the final digest
+    pub fn result(mut self) -> [u8; KECCAK256_SIZE] {
+        let mut block = [0u8; KECCAK256_RATE];
+        block[..self.buffer_len].copy_from_slice(&self.buffer[..self.buffer_len]);
+        block[self.buffer_len] = 0x01;
+        block[KECCAK256_RATE - 1] |= 0x80;
+        self.absorb(&block);
+
+        let mut ret = [0; KECCAK256_SIZE];
+        for (out, lane) in ret.chunks_mut(8).zip(self.state.iter()) {
+            out.copy_from_slice(&lane.to_le_bytes());
+        }
+        ret
+    }
+
+    fn absorb(&mut self, block: &[u8]) {
+        debug_assert_eq!(block.len(), KECCAK256_RATE);
+
+        for (lane, bytes) in self.state.iter_mut().zip(block.chunks(8)) {
+            let mut word = [0; 8];
+            word.copy_from_slice(bytes);
+            *lane ^= u64::from_le_bytes(word);
+        }
+        keccak_f(&mut self.state);
+    }
+}
+
+/// Computes the Keccak-256 digest of `data` in one shot
+pub fn keccak256(data: &[u8]) -> [u8; KECCAK256_SIZE] {
+    let mut engine = Keccak256::new();
+    engine.input(data);
+    engine.result()
+}
+
+const RIPEMD160_INITIAL_STATE: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+/// The message word each step of the left and right lines reads
+const RIPEMD160_WORDS: [[usize; 80]; 2] = [
+    [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+        7, 4, 13, 1, 10, 6, 15, 3, 12, 0, 9, 5, 2, 14, 11, 8,
+        3, 10, 14, 4, 9, 15, 8, 1, 2, 7, 0, 6, 13, 11, 5, 12,
+        1, 9, 11, 10, 0, 8, 12, 4, 13, 3, 7, 15, 14, 5, 6, 2,
+        4, 0, 5, 9, 7, 12, 2, 10, 14, 1, 3, 8, 11, 6, 15, 13,
+    ],
+    [
+        5, 14, 7, 0, 9, 2, 11, 4, 13, 6, 15, 8, 1, 10, 3, 12,
+        6, 11, 3, 7, 0, 13, 5, 10, 14, 15, 8, 12, 4, 9, 1, 2,
+        15, 5, 1, 3, 7, 14, 6, 9, 11, 8, 12, 2, 10, 0, 4, 13,
+        8, 6, 4, 1, 3, 11, 15, 0, 5, 12, 2, 13, 9, 7, 10, 14,
+        12, 15, 10, 4, 1, 5, 8, 7, 6, 2, 13, 14, 0, 3, 9, 11,
+    ],
+];
+
+/// The rotation of each step of the left and right lines
+const RIPEMD160_ROTATIONS: [[u32; 80]; 2] = [
+    [
+        11, 14, 15, 12, 5, 8, 7, 9, 11, 13, 14, 15, 6, 7, 9, 8,
+        7, 6, 8, 13, 11, 9, 7, 15, 7, 12, 15, 9, 11, 7, 13, 12,
+        11, 13, 6, 7, 14, 9, 13, 15, 14, 8, 13, 6, 5, 12, 7, 5,
+        11, 12, 14, 15, 14, 15, 9, 8, 9, 14, 5, 6, 8, 6, 5, 12,
+        9, 15, 5, 11, 6, 8, 13, 12, 5, 12, 13, 14, 11, 8, 5, 6,
+    ],
+    [
+        8, 9, 9, 11, 13, 15, 15, 5, 7, 7, 8, 11, 14, 14, 12, 6,
+        9, 13, 15, 7, 12, 8, 9, 11, 7, 7, 12, 7, 6, 15, 13, 11,
+        9, 7, 15, 11, 8, 6, 6, 14, 12, 13, 5, 14, 13, 13, 7, 5,
+        15, 5, 8, 11, 14, 14, 6, 14, 6, 9, 12, 9, 12, 5, 15, 8,
+        8, 5, 12, 9, 12, 5, 14, 6, 8, 13, 6, 5, 15, 13, 11, 11,
+    ],
+];
+
+/// The constant of each round of the left and right lines
+const RIPEMD160_CONSTANTS: [[u32; 5]; 2] = [
+    [0x00000000, 0x5a827999, 0x6ed9eba1, 0x8f1bbcdc, 0xa953fd4e],
+    [0x50a28be6, 0x5c4dd124, 0x6d703ef3, 0x7a6d76e9, 0x00000000],
+];
+
+/// The boolean function of round `round`; the right line runs them in
+/// reverse order
+fn ripemd160_f(round: usize, x: u32, y: u32, z: u32) -> u32 {
+    match round {
+        0 => x ^ y ^ z,
+        1 => (x & y) | (!x & z),
+        2 => (x | !y) ^ z,
+        3 => (x & z) | (y & !z),
+        _ => x ^ (y | !z),
+    }
+}
+
+/// An incremental RIPEMD-160 engine
+#[derive(Clone)]
+pub struct Ripemd160 {
+    state: [u32; 5],
+    buffer: [u8; BLOCK_SIZE],
+    buffer_len: usize,
+    length: u64,
+}
+
+impl Ripemd160 {
+    /// Creates a new engine
+    pub fn new() -> Ripemd160 {
+        Ripemd160 {
+            state: RIPEMD160_INITIAL_STATE,
+            buffer: [0; BLOCK_SIZE],
+            buffer_len: 0,
+            length: 0,
+        }
+    }
+
+    /// Feeds more data into the engine
+    pub fn input(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffer_len > 0 {
+            let take = ::std::cmp::min(BLOCK_SIZE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            self.process_block(&block);
+            self.buffer_len = 0;
+        }
+
+        while data.len() >= BLOCK_SIZE {
+            self.process_block(&data[..BLOCK_SIZE]);
+            data = &data[BLOCK_SIZE..];
+        }
+
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffer_len = data.len();
+    }
+
+    /// Pads the input and returns the final digest
+    pub fn result(mut self) -> [u8; RIPEMD160_SIZE] {
+        let bit_length = self.length.wrapping_mul(8);
+
+        // As for SHA-256, but with the length little-endian
+        let mut padding = [0u8; BLOCK_SIZE + 8];
+        padding[0] = 0x80;
+        let pad_len = if self.buffer_len < 56 { 56 - self.buffer_len } else { 120 - self.buffer_len };
+        padding[pad_len..pad_len + 8].copy_from_slice(&bit_length.to_le_bytes());
+        self.input(&padding[..pad_len + 8]);
+        debug_assert_eq!(self.buffer_len, 0);
+
+        let mut ret = [0; RIPEMD160_SIZE];
+        for (out, word) in ret.chunks_mut(4).zip(self.state.iter()) {
+            out.copy_from_slice(&word.to_le_bytes());
+        }
+        ret
+    }
+
+    fn process_block(&mut self, block: &[u8]) {
+        debug_assert_eq!(block.len(), BLOCK_SIZE);
+
+        let mut x = [0u32; 16];
+        for (word, bytes) in x.iter_mut().zip(block.chunks(4)) {
+            *word = (bytes[0] as u32) | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24;
+        }
+
+        // The left and right lines, each as (a, b, c, d, e)
+        let mut lines = [self.state, self.state];
+        for (line, v) in lines.iter_mut().enumerate() {
+            for j in 0..80 {
+                let round = j / 16;
+                let f = if line == 0 { round } else { 4 - round };
+                let t = v[0].wrapping_add(ripemd160_f(f, v[1], v[2], v[3]))
+                            .wrapping_add(x[RIPEMD160_WORDS[line][j]])
+                            .wrapping_add(RIPEMD160_CONSTANTS[line][round])
+                            .rotate_left(RIPEMD160_ROTATIONS[line][j])
+                            .wrapping_add(v[4]);
+                *v = [v[4], t, v[1], v[2].rotate_left(10), v[3]];
+            }
+        }
+
+        let (l, r) = (lines[0], lines[1]);
+        let h = self.state;
+        self.state = [
+            h[1].wrapping_add(l[2]).wrapping_add(r[3]),
+            h[2].wrapping_add(l[3]).wrapping_add(r[4]),
+            h[3].wrapping_add(l[4]).wrapping_add(r[0]),
+            h[4].wrapping_add(l[0]).wrapping_add(r[1]),
+            h[0].wrapping_add(l[1]).wrapping_add(r[2]),
+        ];
+    }
+}
+
+/// Computes the RIPEMD-160 digest of `data` in one shot
+pub fn ripemd160(data: &[u8]) -> [u8; RIPEMD160_SIZE] {
+    let mut engine = Ripemd160::new();
+    engine.input(data);
+    engine.result()
+}
+
+/// Computes Bitcoin's `hash160`, `RIPEMD160(SHA256(data))`
+pub fn hash160(data: &[u8]) -> [u8; RIPEMD160_SIZE] {
+    ripemd160(&sha256(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sha256, Sha512, sha256, sha512, hmac_sha256, hmac_sha512, hkdf_sha256, HmacSha256};
+    use super::{tagged_hash, TaggedHasher, BIP340_CHALLENGE_TAG};
+    use super::{keccak256, Keccak256};
+    use super::{hash160, ripemd160, Ripemd160};
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(&sha256(b"")[..],
+                   &hex!("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")[..]);
+        assert_eq!(&sha256(b"abc")[..],
+                   &hex!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")[..]);
+        assert_eq!(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")[..],
+                   &hex!("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")[..]);
+    }
+
+    #[test]
+    fn sha256_incremental() {
+        let data = [0x5a; 1000];
+        let expected = sha256(&data);
+        for split in &[0, 1, 55, 56, 63, 64, 65, 128, 999, 1000] {
+            let mut engine = Sha256::new();
+            engine.input(&data[..*split]);
+            engine.input(&data[*split..]);
+            assert_eq!(engine.result(), expected);
+        }
+
+        let mut engine = Sha256::new();
+        for _ in 0..1000000 / 1000 {
+            engine.input(&[b'a'; 1000]);
+        }
+        assert_eq!(&engine.result()[..],
+                   &hex!("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")[..]);
+    }
+
+    #[test]
+    fn tagged() {
+        let mut engine = Sha256::tagged(b"BIP0340/challenge");
+        engine.input(b"abc");
+        let tag_hash = sha256(b"BIP0340/challenge");
+        let mut data = Vec::new();
+        data.extend_from_slice(&tag_hash);
+        data.extend_from_slice(&tag_hash);
+        data.extend_from_slice(b"abc");
+        assert_eq!(engine.result(), sha256(&data));
+    }
+
+    #[test]
+    fn tagged_hasher() {
+        // BIP340 test vector 0: the challenge for the zero message
+        let r = hex!("E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA8215");
+        let pk = hex!("F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9");
+        let e = TaggedHasher::new(BIP340_CHALLENGE_TAG).chain(&r).chain(&pk).chain(&[0; 32]).result();
+
+        let mut data = r.clone();
+        data.extend_from_slice(&pk);
+        data.extend_from_slice(&[0; 32]);
+        assert_eq!(tagged_hash(BIP340_CHALLENGE_TAG, &data), e);
+        let mut engine = Sha256::tagged(BIP340_CHALLENGE_TAG);
+        engine.input(&data);
+        assert_eq!(engine.result(), e);
+
+        let mut hasher = TaggedHasher::new(b"other");
+        hasher.input(&data);
+        assert!(hasher.result() != e);
+        assert_eq!(&tagged_hash(b"", b"")[..],
+                   &hex!("2dba5dbc339e7316aea2683faf839c1b7b1ee2313db792112588118df066aa35")[..]);
+    }
+
+    #[test]
+    fn sha512_vectors() {
+        assert_eq!(&sha512(b"")[..],
+                   &hex!("cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e")[..]);
+        assert_eq!(&sha512(b"abc")[..],
+                   &hex!("ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f")[..]);
+        assert_eq!(&sha512(b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu")[..],
+                   &hex!("8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909")[..]);
+
+        let data = [0x5a; 1000];
+        let expected = sha512(&data);
+        for split in &[0, 1, 111, 112, 127, 128, 129, 256, 999] {
+            let mut engine = Sha512::new();
+            engine.input(&data[..*split]);
+            engine.input(&data[*split..]);
+            assert_eq!(&engine.result()[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn hmac_sha512_vectors() {
+        // RFC 4231 test cases 1, 2 and 6 (a key longer than a block)
+        assert_eq!(&hmac_sha512(&[0x0b; 20], b"Hi There")[..],
+                   &hex!("87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854")[..]);
+        assert_eq!(&hmac_sha512(b"Jefe", b"what do ya want for nothing?")[..],
+                   &hex!("164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737")[..]);
+        assert_eq!(&hmac_sha512(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")[..],
+                   &hex!("80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598")[..]);
+    }
+
+    #[test]
+    fn hmac_sha256_vectors() {
+        // RFC 4231 test cases 1, 2 and 6 (a key longer than a block)
+        assert_eq!(&hmac_sha256(&[0x0b; 20], b"Hi There")[..],
+                   &hex!("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7")[..]);
+        assert_eq!(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")[..],
+                   &hex!("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")[..]);
+        assert_eq!(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")[..],
+                   &hex!("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54")[..]);
+
+        let mut engine = HmacSha256::new(b"Jefe");
+        engine.input(b"what do ya want ");
+        engine.input(b"for nothing?");
+        assert_eq!(engine.result(), hmac_sha256(b"Jefe", b"what do ya want for nothing?"));
+    }
+
+    #[test]
+    fn hkdf_sha256_vectors() {
+        // RFC 5869 test cases 1 and 3
+        let mut okm = [0; 42];
+        hkdf_sha256(&hex!("000102030405060708090a0b0c"), &[0x0b; 22], &hex!("f0f1f2f3f4f5f6f7f8f9"), &mut okm);
+        assert_eq!(&okm[..],
+                   &hex!("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")[..]);
+
+        hkdf_sha256(&[], &[0x0b; 22], &[], &mut okm);
+        assert_eq!(&okm[..],
+                   &hex!("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8")[..]);
+    }
+
+    #[test]
+    fn keccak256_vectors() {
+        assert_eq!(&keccak256(b"")[..],
+                   &hex!("c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470")[..]);
+        assert_eq!(&keccak256(b"abc")[..],
+                   &hex!("4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45")[..]);
+        // One byte short of a block, so the two padding bits share a byte,
+        // and exactly one block, so the padding takes a block of its own
+        assert_eq!(&keccak256(&[0x5a; 135])[..],
+                   &hex!("03c527855334eb2e62b3b9b4d02ab76721707d3dde5fb218369640ee2edc7f3a")[..]);
+        assert_eq!(&keccak256(&[0x5a; 136])[..],
+                   &hex!("ddc757d2caa82320e140f35833c18e8cc3b230b2b9a48def3d98461ffae81716")[..]);
+        // The digest Ethereum's `personal_sign` signs for "hello"
+        assert_eq!(&keccak256(b"\x19Ethereum Signed Message:\n5hello")[..],
+                   &hex!("50b2c43fd39106bafbba0da34fc430e1f91e3c96ea2acee2bc34119f92b37750")[..]);
+    }
+
+    #[test]
+    fn keccak256_incremental() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let expected = hex!("bfb0aa97863e797943cf7c33bb7e880bb4543f3d2703c0923c6901c2af57b890");
+        for split in &[0, 1, 135, 136, 137, 199, 200] {
+            let mut engine = Keccak256::new();
+            engine.input(&data[..*split]);
+            engine.input(&data[*split..]);
+            assert_eq!(&engine.result()[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn ripemd160_vectors() {
+        assert_eq!(&ripemd160(b"")[..], &hex!("9c1185a5c5e9fc54612808977ee8f548b2258d31")[..]);
+        assert_eq!(&ripemd160(b"abc")[..], &hex!("8eb208f7e05d987a9b044a8e98c6b087f15a0bfc")[..]);
+        assert_eq!(&ripemd160(b"message digest")[..], &hex!("5d0689ef49d2fae572b881b123a85ffa21595f36")[..]);
+        assert_eq!(&ripemd160("1234567890".repeat(8).as_bytes())[..],
+                   &hex!("9b752e45573d4b39f4dbd3323cab82bf63326bfb")[..]);
+        let mut engine = Ripemd160::new();
+        for _ in 0..1000 {
+            engine.input(&[b'a'; 1000]);
+        }
+        assert_eq!(&engine.result()[..], &hex!("52783243c1697bdbe16d37f97f68f08325dc1528")[..]);
+
+        // The P2WPKH program of the key with secret 1
+        let generator = hex!("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798");
+        assert_eq!(&hash160(&generator)[..], &hex!("751e76e8199196d454941c45d1b3a323f1433bd6")[..]);
+    }
+}
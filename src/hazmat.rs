@@ -0,0 +1,225 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Hazardous materials
+//! ECDSA with a nonce chosen by the caller, for protocols which must control
+//! `k` themselves, such as adaptor signatures and two-party ECDSA.
+//!
+//! **Everything here can leak the secret key.** Two signatures with the same
+//! nonce, or with nonces related in any way the attacker knows, reveal the
+//! key to anyone who sees them; so does a nonce which is biased by even a
+//! few bits, given enough signatures. `Secp256k1::sign` derives the nonce
+//! from the key and message with RFC6979 and has none of these pitfalls:
+//! use it unless the protocol leaves no choice, and then follow the
+//! protocol's own nonce generation exactly.
+//!
+//! `detect_nonce_reuse` and `recover_seckey_from_nonce_reuse` show what such
+//! a mistake costs, and let a signer check its own output for a broken
+//! nonce source. Requires compilation with the "hazmat" feature.
+
+use std::ptr;
+
+use types::{c_int, c_uchar, c_uint, c_void};
+
+use cleanup;
+use constants;
+use ffi;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error::{self, InvalidNonce};
+use {Message, Secp256k1, Signature, Signing};
+
+/// A nonce function which hands libsecp256k1 the nonce passed as its data,
+/// and gives up when asked for another one
+unsafe extern "C" fn fixed_nonce(nonce32: *mut c_uchar, _msg32: *const c_uchar, _key32: *const c_uchar,
+                                 _algo16: *const c_uchar, data: *mut c_void, attempt: c_uint) -> c_int {
+    // A second attempt means the nonce gave a zero r or s
+    if attempt != 0 {
+        return 0;
+    }
+    ptr::copy_nonoverlapping(data as *const c_uchar, nonce32, constants::SECRET_KEY_SIZE);
+    1
+}
+
+/// Signs `msg` with the secret key `sk` and the nonce `k`, i.e. returns
+/// `(r, s)` with `r` the x-coordinate of `k * G` and
+/// `s = k^-1 (msg + r sk)`. As with `sign`, a high `s` is negated, which
+/// amounts to signing with `-k`; the signature is otherwise exactly what
+/// `sign` would produce had RFC6979 picked `k`. Fails with `InvalidNonce`
+/// in the astronomically unlikely case that `r` or `s` is zero.
+///
+/// Never sign twice with the same `k`, or with nonces whose relation an
+/// attacker could know: the two signatures reveal `sk`.
+pub fn ecdsa_raw_sign_with_nonce<C: Signing>(secp: &Secp256k1<C>, msg: &Message, sk: &SecretKey, k: &SecretKey)
+                                            -> Result<Signature, Error> {
+    debug_log!(trace, "signing with a caller-chosen nonce");
+    let mut ret = unsafe { ffi::Signature::blank() };
+    let res = unsafe {
+        ffi::secp256k1_ecdsa_sign(secp.ctx, &mut ret, msg.as_ptr(), sk.as_ptr(), fixed_nonce,
+                                  k.as_ptr() as *const c_void)
+    };
+    cleanup::scrub_stack();
+    if res == 1 {
+        Ok(Signature::from(ret))
+    } else {
+        Err(InvalidNonce)
+    }
+}
+
+/// The `r` a signature with the nonce `k` has: the x-coordinate of `k * G`,
+/// reduced modulo the curve order. It is zero, which no signature may have,
+/// only in the astronomically unlikely case that `k * G` has an x-coordinate
+/// of exactly the curve order. The point `k * G` itself is
+/// `PublicKey::from_secret_key(secp, k)`.
+pub fn nonce_to_r<C: Signing>(secp: &Secp256k1<C>, k: &SecretKey) -> Scalar {
+    Scalar::from_be_bytes_mod_order(&PublicKey::from_secret_key(secp, k).x_coordinate())
+}
+
+/// Reduces 32 big-endian bytes, such as an `r`, an `s` or a message,
+/// modulo the curve order
+fn reduce(bytes: &[u8]) -> Scalar {
+    let mut array = [0u8; 32];
+    array.copy_from_slice(bytes);
+    Scalar::from_be_bytes_mod_order(&array)
+}
+
+/// Whether two different signatures share their `r`, i.e. were made with
+/// the same nonce or its negation. Signing one message twice with RFC6979
+/// gives the same signature twice, which is fine and does not count.
+pub fn detect_nonce_reuse(sig1: &Signature, sig2: &Signature) -> bool {
+    let (compact1, compact2) = (sig1.compact_bytes(), sig2.compact_bytes());
+    compact1[..32] == compact2[..32] && compact1[32..] != compact2[32..]
+}
+
+/// Recovers the secret key from two signatures by the same key which reuse
+/// a nonce, on the different messages `msg1` and `msg2`. Returns `None` if
+/// the signatures do not share their `r`, if the messages are equal, or if
+/// no key explains both signatures. Requires a signing-capable context, to
+/// tell which of the candidates that low-S normalization leaves is right.
+pub fn recover_seckey_from_nonce_reuse<C: Signing>(secp: &Secp256k1<C>, sig1: &Signature, msg1: &Message,
+                                                   sig2: &Signature, msg2: &Message) -> Option<SecretKey> {
+    if !detect_nonce_reuse(sig1, sig2) {
+        return None;
+    }
+    let (compact1, compact2) = (sig1.compact_bytes(), sig2.compact_bytes());
+    let r = reduce(&compact1[..32]);
+    let (s1, s2) = (reduce(&compact1[32..]), reduce(&compact2[32..]));
+    let (z1, z2) = (reduce(&msg1[..]), reduce(&msg2[..]));
+    let r_inv = r.invert()?;
+
+    // s_i = ±k^-1 (z_i + r d), so k (s1 ∓ s2) = z1 - z2 with the sign
+    // depending on which of the signatures normalization negated
+    let dz = z1.add(&z2.negate());
+    for ds in &[s1.add(&s2.negate()), s1.add(&s2)] {
+        let k = match ds.invert() {
+            Some(ds_inv) => dz.mul(&ds_inv),
+            None => continue,
+        };
+        let k_key = match SecretKey::from_slice(&k.to_be_bytes()) {
+            Ok(k_key) => k_key,
+            Err(_) => continue,
+        };
+        if nonce_to_r(secp, &k_key) != r {
+            continue;
+        }
+        // d = (s1 k - z1) / r, whichever of ±k signed
+        let d = s1.mul(&k).add(&z1.negate()).mul(&r_inv);
+        let ret = SecretKey::from_slice(&d.to_be_bytes()).ok();
+        cleanup::scrub_stack();
+        return ret;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use rand_core::RngCore;
+
+    use super::{detect_nonce_reuse, ecdsa_raw_sign_with_nonce, nonce_to_r, recover_seckey_from_nonce_reuse,
+                reduce};
+    use ffi;
+    use key::{PublicKey, SecretKey};
+    use rng::thread_rng;
+    use scalar::Scalar;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn sign_with_nonce() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let (sk, pk) = s.generate_keypair(&mut rng);
+            let k = SecretKey::new(&s, &mut rng);
+            let mut msg = [0u8; 32];
+            rng.fill_bytes(&mut msg);
+            let msg = Message::from_slice(&msg).unwrap();
+
+            let sig = ecdsa_raw_sign_with_nonce(&s, &msg, &sk, &k).unwrap();
+            assert_eq!(s.verify(&msg, &sig, &pk), Ok(()));
+            let compact = sig.serialize_compact(&s);
+            let r = nonce_to_r(&s, &k);
+            assert_eq!(compact[..32], r[..]);
+            assert_eq!(PublicKey::from_secret_key(&s, &k).x_coordinate(), r.to_be_bytes());
+
+            // s = ±k^-1 (z + r d)
+            let k_inv = Scalar::from_secret_key(&k).invert().unwrap();
+            let expected = k_inv.mul(&reduce(&msg[..]).add(&r.mul(&Scalar::from_secret_key(&sk))));
+            let sig_s = reduce(&compact[32..]);
+            assert!(sig_s == expected || sig_s == expected.negate());
+        }
+    }
+
+    #[test]
+    fn rfc6979_nonce_matches_sign() {
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let mut nonce = [0u8; 32];
+        unsafe {
+            assert_eq!(ffi::secp256k1_nonce_function_rfc6979(nonce.as_mut_ptr(), msg.as_ptr(), sk.as_ptr(),
+                                                             ptr::null(), ptr::null_mut(), 0), 1);
+        }
+        let k = SecretKey::from_slice(&nonce).unwrap();
+        assert_eq!(ecdsa_raw_sign_with_nonce(&s, &msg, &sk, &k), Ok(s.sign(&msg, &sk)));
+    }
+
+    #[test]
+    fn nonce_reuse() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let sk = SecretKey::new(&s, &mut rng);
+            let k = SecretKey::new(&s, &mut rng);
+            let mut msg1 = [0u8; 32];
+            let mut msg2 = [0u8; 32];
+            rng.fill_bytes(&mut msg1);
+            rng.fill_bytes(&mut msg2);
+            let (msg1, msg2) = (Message::from(msg1), Message::from(msg2));
+
+            let sig1 = ecdsa_raw_sign_with_nonce(&s, &msg1, &sk, &k).unwrap();
+            let sig2 = ecdsa_raw_sign_with_nonce(&s, &msg2, &sk, &k).unwrap();
+            assert!(detect_nonce_reuse(&sig1, &sig2));
+            assert_eq!(recover_seckey_from_nonce_reuse(&s, &sig1, &msg1, &sig2, &msg2), Some(sk));
+            assert_eq!(recover_seckey_from_nonce_reuse(&s, &sig2, &msg2, &sig1, &msg1), Some(sk));
+
+            // RFC6979 nonces differ between messages, and repeat only with them
+            let (sig1, sig2) = (s.sign(&msg1, &sk), s.sign(&msg2, &sk));
+            assert!(!detect_nonce_reuse(&sig1, &sig2));
+            assert!(!detect_nonce_reuse(&sig1, &s.sign(&msg1, &sk)));
+            assert_eq!(recover_seckey_from_nonce_reuse(&s, &sig1, &msg1, &sig2, &msg2), None);
+        }
+    }
+}
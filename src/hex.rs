@@ -0,0 +1,132 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Hex
+//! Hex encoding and decoding, as used by the `Display` and `FromStr` impls
+//! throughout the crate. Everything works on caller-provided buffers, so no
+//! allocation is needed, and `decode_array` is a `const fn` for embedding
+//! keys and test vectors as constants. The module is always compiled for the
+//! crate's own use and exported with the "hex" feature, which saves
+//! enclaves from pulling in a separate hex crate.
+
+use std::{fmt, str};
+
+/// Writes `data` to `f` as lowercase hex
+pub fn format(data: &[u8], f: &mut fmt::Formatter) -> fmt::Result {
+    for ch in data {
+        write!(f, "{:02x}", *ch)?;
+    }
+    Ok(())
+}
+
+/// Encodes `data` as lowercase hex into the front of `out`, which must be at
+/// least twice as long, and returns the encoding
+pub fn encode_to<'a>(data: &[u8], out: &'a mut [u8]) -> &'a str {
+    const DIGITS: &'static [u8; 16] = b"0123456789abcdef";
+    assert!(out.len() >= 2 * data.len(), "hex output buffer too short");
+    for (byte, pair) in data.iter().zip(out.chunks_mut(2)) {
+        pair[0] = DIGITS[(byte >> 4) as usize];
+        pair[1] = DIGITS[(byte & 0xf) as usize];
+    }
+    str::from_utf8(&out[..2 * data.len()]).expect("hex digits are ASCII")
+}
+
+const fn digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes `hex` (of either case) into the front of `out`, returning the
+/// number of bytes written. Fails on odd lengths, non-hex characters or if
+/// `out` is too short.
+pub fn decode_from(hex: &str, out: &mut [u8]) -> Option<usize> {
+    let hex = hex.as_bytes();
+    if hex.len() % 2 != 0 || hex.len() / 2 > out.len() {
+        return None;
+    }
+    for (pair, byte) in hex.chunks(2).zip(out.iter_mut()) {
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(hex.len() / 2)
+}
+
+/// Decodes exactly `2 * N` hex digits (of either case) into an array.
+/// Usable in constants, where bad input fails the build.
+///
+/// # Panics
+/// If `hex` isn't exactly `2 * N` hex digits.
+pub const fn decode_array<const N: usize>(hex: &str) -> [u8; N] {
+    let hex = hex.as_bytes();
+    assert!(hex.len() == 2 * N, "wrong number of hex digits");
+    let mut ret = [0; N];
+    let mut i = 0;
+    while i < N {
+        ret[i] = match (digit(hex[2 * i]), digit(hex[2 * i + 1])) {
+            (Some(hi), Some(lo)) => hi << 4 | lo,
+            _ => panic!("invalid hex digit"),
+        };
+        i += 1;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_array, decode_from, encode_to};
+
+    #[test]
+    fn encoding() {
+        let mut buf = [0; 8];
+        assert_eq!(encode_to(&[], &mut buf), "");
+        assert_eq!(encode_to(&[0x00, 0xff, 0x7a], &mut buf), "00ff7a");
+        let mut round = [0; 3];
+        assert_eq!(decode_from(encode_to(&[0x12, 0xab, 0xcd], &mut buf), &mut round), Some(3));
+        assert_eq!(round, [0x12, 0xab, 0xcd]);
+    }
+
+    #[test]
+    fn decoding() {
+        let mut buf = [0; 4];
+        assert_eq!(decode_from("", &mut buf), Some(0));
+        assert_eq!(decode_from("00fF7a", &mut buf), Some(3));
+        assert_eq!(buf[..3], [0x00, 0xff, 0x7a]);
+        assert_eq!(decode_from("0", &mut buf), None);
+        assert_eq!(decode_from("0g", &mut buf), None);
+        assert_eq!(decode_from("+1", &mut buf), None);
+        assert_eq!(decode_from("0102030405", &mut buf), None);
+    }
+
+    #[test]
+    fn const_decoding() {
+        const KEY: [u8; 4] = decode_array("00fF7a10");
+        assert_eq!(KEY, [0x00, 0xff, 0x7a, 0x10]);
+        assert_eq!(decode_array::<0>(""), []);
+    }
+
+    #[test]
+    #[should_panic(expected = "wrong number of hex digits")]
+    fn const_decoding_length() {
+        decode_array::<2>("001");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid hex digit")]
+    fn const_decoding_digit() {
+        decode_array::<2>("00x1");
+    }
+}
@@ -0,0 +1,358 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Key import
+//! One entry point for the secret key formats provisioning tools hand to
+//! enclaves, so that each integration doesn't need its own glue:
+//!
+//! ```ignore
+//! let handle = Importer::new().insert(&secp, &mut vault, pem.as_bytes())?;
+//! let pair = Importer::new().format(KeyFormat::Encrypted).credential(Credential::Kek(&kek))
+//!     .key_pair(&secp, &blob)?;
+//! ```
+//!
+//! The format is detected from the input unless one is given: 64 hex
+//! digits, WIF (either network, compressed or not), PEM PKCS#8 or SEC1
+//! blocks and their DER ("der" feature), and `encrypted_key` blobs, raw or
+//! in hex ("encrypted-keys" feature), which also need a credential.
+//! Surrounding whitespace is ignored in the text formats. Every
+//! intermediate copy of the key is wiped. Requires compilation with the
+//! "hashes" feature.
+
+use std::marker::PhantomData;
+use std::str;
+
+use base58;
+use cleanup::wipe;
+use constants;
+#[cfg(feature = "encrypted-keys")]
+use encrypted_key::{Credential, EncryptedSecretKey, ENCRYPTED_KEY_SIZE};
+use hex;
+use key::{KeyPair, SecretKey};
+use vault::{KeyHandle, Vault};
+use super::Error::{self, InvalidSecretKey, UnknownKeyFormat};
+#[cfg(feature = "encrypted-keys")]
+use super::Error::KeyDecryptionFailed;
+use {Secp256k1, Signing};
+
+/// WIF version bytes of mainnet and testnet keys
+const WIF_MAINNET: u8 = 0x80;
+const WIF_TESTNET: u8 = 0xef;
+
+/// An encoding of a secret key
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum KeyFormat {
+    /// The key as 64 hex digits, of either case
+    Hex,
+    /// Wallet import format, for mainnet or testnet and for compressed or
+    /// uncompressed public keys
+    Wif,
+    /// A PEM `PRIVATE KEY` (PKCS#8) or `EC PRIVATE KEY` (SEC1) block
+    #[cfg(feature = "der")]
+    Pem,
+    /// A DER PKCS#8 `PrivateKeyInfo` or SEC1 `ECPrivateKey`
+    #[cfg(feature = "der")]
+    Der,
+    /// An `encrypted_key::EncryptedSecretKey`, as bytes or hex
+    #[cfg(feature = "encrypted-keys")]
+    Encrypted,
+}
+
+/// Whether every byte of `s` is a hex digit
+fn is_hex(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn detect_text(text: &str) -> Option<KeyFormat> {
+    #[cfg(feature = "der")]
+    {
+        if text.starts_with("-----BEGIN ") {
+            return Some(KeyFormat::Pem);
+        }
+    }
+    #[cfg(feature = "encrypted-keys")]
+    {
+        // Hex of the `SKEY` magic
+        if text.len() == 2 * ENCRYPTED_KEY_SIZE && text.as_bytes()[..8].eq_ignore_ascii_case(b"534b4559") &&
+           is_hex(text) {
+            return Some(KeyFormat::Encrypted);
+        }
+    }
+    if text.len() == 2 * constants::SECRET_KEY_SIZE && is_hex(text) {
+        Some(KeyFormat::Hex)
+    } else if (text.len() == 51 || text.len() == 52) && base58::decode(text).is_some() {
+        Some(KeyFormat::Wif)
+    } else {
+        None
+    }
+}
+
+#[cfg_attr(not(any(feature = "der", feature = "encrypted-keys")), allow(unused_variables))]
+fn detect_binary(data: &[u8]) -> Option<KeyFormat> {
+    #[cfg(feature = "encrypted-keys")]
+    {
+        if data.starts_with(b"SKEY") {
+            return Some(KeyFormat::Encrypted);
+        }
+    }
+    #[cfg(feature = "der")]
+    {
+        // Both DER encodings are a SEQUENCE
+        if data.first() == Some(&0x30) {
+            return Some(KeyFormat::Der);
+        }
+    }
+    None
+}
+
+/// The format of `data`, or `None` if it isn't recognizably any. Input of
+/// printable ASCII is taken for one of the text formats, and anything else
+/// for a binary one.
+pub fn detect(data: &[u8]) -> Option<KeyFormat> {
+    match str::from_utf8(data) {
+        Ok(text) if text.bytes().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace()) => {
+            detect_text(text.trim())
+        }
+        _ => detect_binary(data),
+    }
+}
+
+/// `data` as text without surrounding whitespace
+fn text(data: &[u8]) -> Result<&str, Error> {
+    str::from_utf8(data).map(str::trim).map_err(|_| InvalidSecretKey)
+}
+
+fn parse_hex(text: &str) -> Result<SecretKey, Error> {
+    let mut buf = [0; constants::SECRET_KEY_SIZE];
+    let ret = match hex::decode_from(text, &mut buf) {
+        Some(constants::SECRET_KEY_SIZE) => SecretKey::from_slice(&buf),
+        _ => Err(InvalidSecretKey),
+    };
+    wipe(&mut buf);
+    ret
+}
+
+fn parse_wif(text: &str) -> Result<SecretKey, Error> {
+    let mut payload = base58::decode_check(text).ok_or(InvalidSecretKey)?;
+    let ret = match (payload.len(), payload.first(), payload.last()) {
+        (33, Some(&WIF_MAINNET), _) | (33, Some(&WIF_TESTNET), _) |
+        (34, Some(&WIF_MAINNET), Some(&1)) | (34, Some(&WIF_TESTNET), Some(&1)) => {
+            SecretKey::from_slice(&payload[1..33])
+        }
+        _ => Err(InvalidSecretKey),
+    };
+    wipe(&mut payload);
+    ret
+}
+
+/// Options for importing a secret key, from `Importer::new`
+#[derive(Clone)]
+pub struct Importer<'a> {
+    format: Option<KeyFormat>,
+    #[cfg(feature = "encrypted-keys")]
+    credential: Option<Credential<'a>>,
+    phantom: PhantomData<&'a ()>,
+}
+
+impl<'a> Default for Importer<'a> {
+    fn default() -> Importer<'a> {
+        Importer::new()
+    }
+}
+
+impl<'a> Importer<'a> {
+    /// Starts an import which detects the format and has no credential
+    pub fn new() -> Importer<'a> {
+        Importer {
+            format: None,
+            #[cfg(feature = "encrypted-keys")]
+            credential: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Parses the input as `format` rather than detecting it
+    pub fn format(mut self, format: KeyFormat) -> Importer<'a> {
+        self.format = Some(format);
+        self
+    }
+
+    /// Decrypts encrypted keys with `credential`. Requires compilation with
+    /// the "encrypted-keys" feature.
+    #[cfg(feature = "encrypted-keys")]
+    pub fn credential(mut self, credential: Credential<'a>) -> Importer<'a> {
+        self.credential = Some(credential);
+        self
+    }
+
+    /// Parses a secret key from `data`. Fails with `UnknownKeyFormat` if no
+    /// format was given and none is detected, with `InvalidSecretKey` if
+    /// `data` is not a valid key in the format, and for encrypted keys like
+    /// `EncryptedSecretKey::from_slice` and `decrypt`, or with
+    /// `KeyDecryptionFailed` if there is no credential. Requires a
+    /// signing-capable context.
+    #[cfg_attr(not(feature = "der"), allow(unused_variables))]
+    pub fn secret_key<C: Signing>(&self, secp: &Secp256k1<C>, data: &[u8]) -> Result<SecretKey, Error> {
+        let format = match self.format {
+            Some(format) => format,
+            None => detect(data).ok_or(UnknownKeyFormat)?,
+        };
+        match format {
+            KeyFormat::Hex => parse_hex(text(data)?),
+            KeyFormat::Wif => parse_wif(text(data)?),
+            #[cfg(feature = "der")]
+            KeyFormat::Pem => {
+                let pem = text(data)?;
+                SecretKey::from_pkcs8_pem(secp, pem).or_else(|_| SecretKey::from_sec1_pem(secp, pem))
+            }
+            #[cfg(feature = "der")]
+            KeyFormat::Der => {
+                SecretKey::from_pkcs8_der(secp, data).or_else(|_| SecretKey::from_sec1_der(secp, data))
+            }
+            #[cfg(feature = "encrypted-keys")]
+            KeyFormat::Encrypted => {
+                let encrypted = if data.len() == ENCRYPTED_KEY_SIZE {
+                    EncryptedSecretKey::from_slice(data)?
+                } else {
+                    text(data)?.parse()?
+                };
+                encrypted.decrypt(self.credential.ok_or(KeyDecryptionFailed)?)
+            }
+        }
+    }
+
+    /// Parses a secret key from `data` like `secret_key`, along with its
+    /// public key. Requires a signing-capable context.
+    pub fn key_pair<C: Signing>(&self, secp: &Secp256k1<C>, data: &[u8]) -> Result<KeyPair, Error> {
+        self.secret_key(secp, data).map(|sk| KeyPair::from_secret_key(secp, sk))
+    }
+
+    /// Parses a secret key from `data` like `secret_key` and moves it into
+    /// `vault`. Requires a signing-capable context.
+    pub fn insert<C: Signing>(&self, secp: &Secp256k1<C>, vault: &mut Vault, data: &[u8])
+                              -> Result<KeyHandle, Error> {
+        self.secret_key(secp, data).map(|sk| vault.insert(secp, sk))
+    }
+}
+
+impl KeyPair {
+    /// Parses a keypair from a secret key in any text format `Importer`
+    /// detects without a credential: hex, WIF or, with the "der" feature,
+    /// PEM. Requires a signing-capable context.
+    pub fn from_seckey_str<C: Signing>(secp: &Secp256k1<C>, s: &str) -> Result<KeyPair, Error> {
+        match detect_text(s.trim()) {
+            #[cfg(feature = "encrypted-keys")]
+            Some(KeyFormat::Encrypted) => Err(KeyDecryptionFailed),
+            Some(format) => Importer::new().format(format).key_pair(secp, s.as_bytes()),
+            None => Err(UnknownKeyFormat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, Importer, KeyFormat};
+    use super::super::Error::{InvalidSecretKey, UnknownKeyFormat};
+    use key::{KeyPair, SecretKey};
+    use vault::Vault;
+    use {Message, Secp256k1};
+
+    fn one() -> SecretKey {
+        let mut one = [0; 32];
+        one[31] = 1;
+        SecretKey::from_slice(&one).unwrap()
+    }
+
+    #[test]
+    fn hex_and_wif() {
+        let s = Secp256k1::new();
+        let inputs = ["0000000000000000000000000000000000000000000000000000000000000001",
+                      "  0000000000000000000000000000000000000000000000000000000000000001\n",
+                      "5HpHagT65TZzG1PH3CSu63k8DbpvD8s5ip4nEB3kEsreAnchuDf",
+                      "KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn",
+                      "91avARGdfge8E4tZfYLoxeJ5sGBdNJQH4kvjJoQFacbgwmaKkrx",
+                      "cMahea7zqjxrtgAbB7LSGbcQUr1uX1ojuat9jZodMN87JcbXMTcA"];
+        for (i, input) in inputs.iter().enumerate() {
+            let format = if i < 2 { KeyFormat::Hex } else { KeyFormat::Wif };
+            assert_eq!(detect(input.as_bytes()), Some(format));
+            assert_eq!(Importer::new().secret_key(&s, input.as_bytes()), Ok(one()));
+            assert_eq!(Importer::new().format(format).secret_key(&s, input.as_bytes()), Ok(one()));
+            assert_eq!(KeyPair::from_seckey_str(&s, input), Ok(KeyPair::from_secret_key(&s, one())));
+        }
+
+        // Bad checksum, unknown version byte, and the group order
+        assert_eq!(Importer::new().secret_key(&s, b"KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWo"),
+                   Err(InvalidSecretKey));
+        assert_eq!(Importer::new().format(KeyFormat::Wif).secret_key(&s, b"1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm"),
+                   Err(InvalidSecretKey));
+        assert_eq!(Importer::new().secret_key(&s, b"FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141"),
+                   Err(InvalidSecretKey));
+        assert_eq!(Importer::new().format(KeyFormat::Hex).secret_key(&s, b"01"), Err(InvalidSecretKey));
+
+        for input in [&b""[..], b"00", b"not a key", b"\x00\x01\x02"].iter() {
+            assert_eq!(Importer::new().secret_key(&s, input), Err(UnknownKeyFormat));
+        }
+        assert_eq!(KeyPair::from_seckey_str(&s, "01"), Err(UnknownKeyFormat));
+    }
+
+    #[test]
+    fn into_vault() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let handle = Importer::new()
+            .insert(&s, &mut vault, b"KwDiBf89QgGbjEhKnhXJuH7LrciVrZi3qYjgd9M7rFU73sVHnoWn").unwrap();
+        let pair = KeyPair::from_secret_key(&s, one());
+        assert_eq!(vault.public_key(handle), Ok(pair.public_key()));
+        let msg = Message::from([7; 32]);
+        assert_eq!(vault.sign(&s, handle, &msg), Ok(s.sign(&msg, &one())));
+    }
+
+    #[test]
+    #[cfg(all(feature = "der", not(feature = "no-secret-export")))]
+    fn pem_and_der() {
+        let s = Secp256k1::new();
+        let sk = one();
+        let inputs = [sk.to_pkcs8_pem(&s).into_bytes(), sk.to_sec1_pem(&s).into_bytes(),
+                      sk.to_pkcs8_der(&s), sk.to_sec1_der(&s)];
+        for (i, input) in inputs.iter().enumerate() {
+            let format = if i < 2 { KeyFormat::Pem } else { KeyFormat::Der };
+            assert_eq!(detect(input), Some(format));
+            assert_eq!(Importer::new().secret_key(&s, input), Ok(sk));
+        }
+        let pem = sk.to_sec1_pem(&s);
+        assert_eq!(KeyPair::from_seckey_str(&s, &pem), Ok(KeyPair::from_secret_key(&s, sk)));
+        assert_eq!(Importer::new().format(KeyFormat::Der).secret_key(&s, &inputs[0]), Err(InvalidSecretKey));
+    }
+
+    #[test]
+    #[cfg(all(feature = "encrypted-keys", not(feature = "no-secret-export")))]
+    fn encrypted() {
+        use encrypted_key::{Credential, Protection};
+        use rng::thread_rng;
+        use super::super::Error::KeyDecryptionFailed;
+
+        let s = Secp256k1::new();
+        let kek = [0x42; 32];
+        let encrypted = one().to_encrypted(Protection::Kek(&kek), &mut thread_rng()).unwrap();
+        let hex = encrypted.to_string();
+        for input in [&encrypted.as_bytes()[..], hex.as_bytes()].iter() {
+            assert_eq!(detect(input), Some(KeyFormat::Encrypted));
+            assert_eq!(Importer::new().credential(Credential::Kek(&kek)).secret_key(&s, input), Ok(one()));
+            assert_eq!(Importer::new().secret_key(&s, input), Err(KeyDecryptionFailed));
+            assert_eq!(Importer::new().credential(Credential::Kek(&[0; 32])).secret_key(&s, input),
+                       Err(KeyDecryptionFailed));
+        }
+        assert_eq!(KeyPair::from_seckey_str(&s, &hex), Err(KeyDecryptionFailed));
+    }
+}
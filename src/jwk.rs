@@ -0,0 +1,349 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # JWK
+//! Import and export of keys as JSON Web Keys (RFC 7517), in the form
+//! `{"kty":"EC","crv":"secp256k1","x":...,"y":...,"d":...}`. Only the few
+//! members needed for secp256k1 keys are interpreted; any other members
+//! (`kid`, `use`, `key_ops`, ...) are skipped. Requires compilation with
+//! the "jwk" feature.
+
+use std::string::String;
+
+use base64;
+use cleanup;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+#[cfg(not(feature = "verify-only"))]
+use super::{Secp256k1, Signing};
+use super::Error::{self, InvalidPublicKey};
+#[cfg(not(feature = "verify-only"))]
+use super::Error::InvalidSecretKey;
+
+/// Decodes unpadded base64url into exactly 32 bytes
+fn base64url_decode_32(s: &str) -> Option<[u8; 32]> {
+    let mut bytes = base64::decode(s, base64::URL_SAFE, false)?;
+    let ret = if bytes.len() == 32 {
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&bytes);
+        Some(ret)
+    } else {
+        None
+    };
+    cleanup::wipe(&mut bytes);
+    ret
+}
+
+/// The members of a JWK this module cares about
+#[derive(Default)]
+struct Members<'a> {
+    kty: Option<&'a str>,
+    crv: Option<&'a str>,
+    x: Option<&'a str>,
+    y: Option<&'a str>,
+    d: Option<&'a str>,
+}
+
+/// A minimal JSON reader, sufficient for flat JWK objects
+struct Reader<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Reads a string, returning its raw contents. Escape sequences are
+    /// skipped over but not decoded; none of the members we interpret can
+    /// legitimately contain them.
+    fn string(&mut self) -> Option<&'a str> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        let ret = self.s.get(start..self.pos)?;
+        self.pos += 1;
+        Some(ret)
+    }
+
+    /// Skips over any JSON value
+    fn skip_value(&mut self) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'"' => { self.string()?; }
+            b'[' | b'{' => {
+                let mut depth = 0usize;
+                loop {
+                    match self.peek()? {
+                        b'"' => { self.string()?; continue; }
+                        b'[' | b'{' => depth += 1,
+                        b']' | b'}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.pos += 1;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    match c {
+                        b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r' => break,
+                        _ => self.pos += 1,
+                    }
+                }
+                if self.pos == start {
+                    return None;
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn members(mut self) -> Option<Members<'a>> {
+        let mut ret = Members::default();
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                let name = self.string()?;
+                self.expect(b':')?;
+                let slot = match name {
+                    "kty" => &mut ret.kty,
+                    "crv" => &mut ret.crv,
+                    "x" => &mut ret.x,
+                    "y" => &mut ret.y,
+                    "d" => &mut ret.d,
+                    _ => {
+                        self.skip_value()?;
+                        if self.expect(b',').is_some() { continue; }
+                        self.expect(b'}')?;
+                        break;
+                    }
+                };
+                if slot.is_some() {
+                    return None; // duplicate member
+                }
+                *slot = Some(self.string()?);
+                if self.expect(b',').is_some() { continue; }
+                self.expect(b'}')?;
+                break;
+            }
+        }
+        self.skip_whitespace();
+        if self.pos != self.s.len() {
+            return None;
+        }
+        if ret.kty != Some("EC") || ret.crv != Some("secp256k1") {
+            return None;
+        }
+        Some(ret)
+    }
+}
+
+fn parse(jwk: &str) -> Option<Members> {
+    Reader { s: jwk, pos: 0 }.members()
+}
+
+fn write_public(pk: &PublicKey, out: &mut String) {
+    out.push_str("{\"kty\":\"EC\",\"crv\":\"secp256k1\",\"x\":\"");
+    base64::encode(&pk.x_coordinate(), base64::URL_SAFE, false, out);
+    out.push_str("\",\"y\":\"");
+    base64::encode(&pk.y_coordinate(), base64::URL_SAFE, false, out);
+    out.push('"');
+}
+
+fn public_from_members(members: &Members) -> Option<PublicKey> {
+    let x = base64url_decode_32(members.x?)?;
+    let y = base64url_decode_32(members.y?)?;
+    PublicKey::from_coordinates(&x, &y).ok()
+}
+
+impl PublicKey {
+    /// Serializes the key as a JWK with members `kty`, `crv`, `x` and `y`
+    pub fn to_jwk(&self) -> String {
+        let mut ret = String::with_capacity(128);
+        write_public(self, &mut ret);
+        ret.push('}');
+        ret
+    }
+
+    /// Parses a public key from a JWK. A `d` member, if present, is ignored.
+    pub fn from_jwk(jwk: &str) -> Result<PublicKey, Error> {
+        parse(jwk).and_then(|m| public_from_members(&m)).ok_or(InvalidPublicKey)
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl SecretKey {
+    /// Serializes the key as a JWK, including the public `x` and `y` members
+    /// alongside the secret `d`. Requires a signing-capable context.
+    #[cfg(not(feature = "no-secret-export"))]
+    pub fn to_jwk<C: Signing>(&self, secp: &Secp256k1<C>) -> String {
+        let mut ret = String::with_capacity(192);
+        write_public(&PublicKey::from_secret_key(secp, self), &mut ret);
+        ret.push_str(",\"d\":\"");
+        base64::encode(&self[..], base64::URL_SAFE, false, &mut ret);
+        ret.push_str("\"}");
+        ret
+    }
+
+    /// Parses a secret key from a JWK. If `x` and `y` are present, they must
+    /// match the public key of `d`. Requires a signing-capable context.
+    pub fn from_jwk<C: Signing>(secp: &Secp256k1<C>, jwk: &str) -> Result<SecretKey, Error> {
+        let members = parse(jwk).ok_or(InvalidSecretKey)?;
+        let mut d = members.d.and_then(base64url_decode_32).ok_or(InvalidSecretKey)?;
+        let sk = SecretKey::from_slice(&d);
+        cleanup::wipe(&mut d);
+        let sk = sk?;
+        if members.x.is_some() || members.y.is_some() {
+            match public_from_members(&members) {
+                Some(ref pk) if *pk == PublicKey::from_secret_key(secp, &sk) => {}
+                _ => return Err(InvalidSecretKey),
+            }
+        }
+        Ok(sk)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use super::base64url_decode_32;
+    use base64;
+    use super::super::Secp256k1;
+    use super::super::Error::{InvalidPublicKey, InvalidSecretKey};
+    use key::{SecretKey, PublicKey, ONE_KEY};
+    use rng::thread_rng;
+
+    const G_JWK: &'static str = "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\
+                                 \"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\
+                                 \"y\":\"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lg\"}";
+
+    #[test]
+    fn base64url_32() {
+        let data = [0xa5; 32];
+        let mut s = String::new();
+        base64::encode(&data, base64::URL_SAFE, false, &mut s);
+        assert_eq!(s.len(), 43);
+        assert_eq!(base64url_decode_32(&s), Some(data));
+        assert_eq!(base64url_decode_32(&s[1..]), None);
+        assert_eq!(base64url_decode_32(&s[4..]), None);
+        let mut padded = s.clone();
+        padded.push('=');
+        assert_eq!(base64url_decode_32(&padded), None);
+        // Last character must not carry nonzero padding bits
+        let mut noncanonical = s.clone();
+        noncanonical.pop();
+        noncanonical.push('V');
+        assert_eq!(base64url_decode_32(&noncanonical), None);
+    }
+
+    #[test]
+    fn public_key_jwk() {
+        let s = Secp256k1::new();
+        let g = PublicKey::from_secret_key(&s, &ONE_KEY);
+        assert_eq!(g.to_jwk(), G_JWK);
+        assert_eq!(PublicKey::from_jwk(G_JWK), Ok(g));
+
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(PublicKey::from_jwk(&pk.to_jwk()), Ok(pk));
+
+        // Whitespace, member order and unknown members don't matter
+        let relaxed = "{ \"use\": \"sig\", \"key_ops\": [\"verify\"], \"ext\": true,\n\
+                       \"y\": \"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lg\",\n\
+                       \"x\": \"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\n\
+                       \"crv\": \"secp256k1\", \"kid\": \"a \\\"b\\\"\", \"kty\": \"EC\" }";
+        assert_eq!(PublicKey::from_jwk(relaxed), Ok(g));
+
+        for bad in &[
+            "",
+            "{}",
+            "{\"kty\":\"EC\",\"crv\":\"P-256\",\"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\
+             \"y\":\"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lg\"}",
+            "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\"}",
+            "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\
+             \"y\":\"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lh\"}",
+            "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\
+             \"y\":\"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lg\"} trailing",
+            "{\"kty\":\"EC\",\"kty\":\"EC\",\"crv\":\"secp256k1\",\
+             \"x\":\"eb5mfvncu6xVoGKVzocLBwKb_NstzijZWfKBWxb4F5g\",\
+             \"y\":\"SDradyajxGVdpPv8DhEIqP0XtEimhVQZnEfQj_sQ1Lg\"}",
+        ] {
+            assert_eq!(PublicKey::from_jwk(bad), Err(InvalidPublicKey));
+        }
+    }
+
+    #[test]
+    fn secret_key_jwk() {
+        let s = Secp256k1::new();
+        let jwk = format!("{},\"d\":\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAE\"}}", &G_JWK[..G_JWK.len() - 1]);
+        #[cfg(not(feature = "no-secret-export"))]
+        assert_eq!(ONE_KEY.to_jwk(&s), jwk);
+        assert_eq!(SecretKey::from_jwk(&s, &jwk), Ok(ONE_KEY));
+        assert_eq!(PublicKey::from_jwk(&jwk), Ok(PublicKey::from_secret_key(&s, &ONE_KEY)));
+
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        #[cfg(not(feature = "no-secret-export"))]
+        assert_eq!(SecretKey::from_jwk(&s, &sk.to_jwk(&s)), Ok(sk));
+
+        // `d` alone is enough
+        let d_only = "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\
+                      \"d\":\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAE\"}";
+        assert_eq!(SecretKey::from_jwk(&s, d_only), Ok(ONE_KEY));
+
+        // Mismatched public part, missing `d`, zero `d`
+        let mut d = String::new();
+        base64::encode(&sk.secret_bytes()[..], base64::URL_SAFE, false, &mut d);
+        let mismatched = format!("{},\"d\":\"{}\"}}", &G_JWK[..G_JWK.len() - 1], d);
+        assert_eq!(SecretKey::from_jwk(&s, &mismatched), Err(InvalidSecretKey));
+        assert_eq!(SecretKey::from_jwk(&s, G_JWK), Err(InvalidSecretKey));
+        let zero = "{\"kty\":\"EC\",\"crv\":\"secp256k1\",\
+                    \"d\":\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\"}";
+        assert_eq!(SecretKey::from_jwk(&s, zero), Err(InvalidSecretKey));
+    }
+}
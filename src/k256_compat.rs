@@ -0,0 +1,172 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # k256 conversions
+//! Lossless conversions between this crate's types and those of the
+//! pure-Rust `k256` crate, for code which runs the same logic inside the
+//! enclave (with this crate) and outside it (with `k256`). Conversions which
+//! cannot fail are `From`; the only fallible one is `Signature` to
+//! `k256::ecdsa::Signature`, since `k256` rejects zero `r` or `s` values
+//! which libsecp256k1 will parse. Requires compilation with the "k256"
+//! feature.
+
+use std::convert::TryFrom;
+
+use k256;
+use k256::elliptic_curve::ff::PrimeField;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+
+use ffi;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use scalar::Scalar;
+use super::Signature;
+use super::Error::{self, InvalidSignature};
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl From<SecretKey> for k256::SecretKey {
+    fn from(sk: SecretKey) -> k256::SecretKey {
+        k256::SecretKey::from_slice(&sk[..]).expect("secret keys are always in range")
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl From<k256::SecretKey> for SecretKey {
+    fn from(sk: k256::SecretKey) -> SecretKey {
+        SecretKey::from_slice(&sk.to_bytes()).expect("secret keys are always in range")
+    }
+}
+
+impl From<PublicKey> for k256::PublicKey {
+    fn from(pk: PublicKey) -> k256::PublicKey {
+        k256::PublicKey::from_sec1_bytes(&pk.serialize()).expect("public keys are always valid points")
+    }
+}
+
+impl From<k256::PublicKey> for PublicKey {
+    fn from(pk: k256::PublicKey) -> PublicKey {
+        PublicKey::from_slice(pk.to_encoded_point(true).as_bytes())
+            .expect("public keys are always valid points")
+    }
+}
+
+impl From<Scalar> for k256::Scalar {
+    fn from(s: Scalar) -> k256::Scalar {
+        Option::from(k256::Scalar::from_repr(s.to_be_bytes().into()))
+            .expect("scalars are always in range")
+    }
+}
+
+impl From<k256::Scalar> for Scalar {
+    fn from(s: k256::Scalar) -> Scalar {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(&s.to_bytes());
+        Scalar::from_be_bytes(bytes).expect("scalars are always in range")
+    }
+}
+
+impl TryFrom<Signature> for k256::ecdsa::Signature {
+    type Error = Error;
+
+    /// Fails if `r` or `s` is zero
+    fn try_from(sig: Signature) -> Result<k256::ecdsa::Signature, Error> {
+        let mut compact = [0; 64];
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_signature_serialize_compact(ffi::secp256k1_context_no_precomp,
+                                                                       compact.as_mut_ptr(), sig.as_ptr());
+            // Serialization never fails
+            assert!(err == 1);
+        }
+        k256::ecdsa::Signature::from_slice(&compact).map_err(|_| InvalidSignature)
+    }
+}
+
+impl From<k256::ecdsa::Signature> for Signature {
+    fn from(sig: k256::ecdsa::Signature) -> Signature {
+        let compact = sig.to_bytes();
+        let mut ret = unsafe { ffi::Signature::blank() };
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_signature_parse_compact(ffi::secp256k1_context_no_precomp,
+                                                                   &mut ret, compact.as_ptr());
+            // k256 keeps r and s in range, which is all parsing checks
+            assert!(err == 1);
+        }
+        Signature(ret)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::convert::TryFrom;
+
+    use k256;
+    use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+
+    use super::super::{Message, Secp256k1, Signature};
+    use super::super::Error::InvalidSignature;
+    use key::{SecretKey, PublicKey};
+    use scalar::Scalar;
+    use rng::thread_rng;
+
+    #[test]
+    fn key_round_trips() {
+        let s = Secp256k1::new();
+        for _ in 0..16 {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            let k_sk = k256::SecretKey::from_slice(&sk.secret_bytes()[..]).unwrap();
+            #[cfg(not(feature = "no-secret-export"))]
+            assert_eq!(k256::SecretKey::from(sk), k_sk);
+            let k_pk = k256::PublicKey::from(pk);
+            assert_eq!(k_sk.public_key(), k_pk);
+            assert_eq!(SecretKey::from(k_sk), sk);
+            assert_eq!(PublicKey::from(k_pk), pk);
+
+            let scalar = Scalar::from_secret_key(&sk);
+            assert_eq!(Scalar::from(k256::Scalar::from(scalar)), scalar);
+        }
+        assert_eq!(k256::Scalar::from(Scalar::ZERO), k256::Scalar::ZERO);
+        assert_eq!(k256::Scalar::from(Scalar::ONE.negate()), -k256::Scalar::ONE);
+    }
+
+    /// Both implementations use RFC6979 nonces and low-S normalization, so
+    /// their signatures must agree bit for bit
+    #[test]
+    fn differential_ecdsa() {
+        let s = Secp256k1::new();
+        for i in 0..16u8 {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            let prehash = [i; 32];
+            let msg = Message::from_slice(&prehash).unwrap();
+
+            let ours = s.sign(&msg, &sk);
+            let k_sk = k256::SecretKey::from_slice(&sk.secret_bytes()[..]).unwrap();
+            let signing_key = k256::ecdsa::SigningKey::from(k_sk);
+            let theirs: k256::ecdsa::Signature = signing_key.sign_prehash(&prehash).unwrap();
+            assert_eq!(k256::ecdsa::Signature::try_from(ours), Ok(theirs));
+            assert_eq!(Signature::from(theirs), ours);
+
+            let verifying_key = k256::ecdsa::VerifyingKey::from(k256::PublicKey::from(pk));
+            assert!(verifying_key.verify_prehash(&prehash, &theirs).is_ok());
+            assert_eq!(s.verify(&msg, &Signature::from(theirs), &pk), Ok(()));
+        }
+    }
+
+    #[test]
+    fn zero_signature() {
+        let s = Secp256k1::new();
+        let zero = Signature::from_compact(&s, &[0; 64]).unwrap();
+        assert_eq!(k256::ecdsa::Signature::try_from(zero), Err(InvalidSignature));
+    }
+}
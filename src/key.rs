@@ -15,54 +15,318 @@
 
 //! # Public and secret keys
 
-#[cfg(any(test, feature = "rand"))] use rand::Rng;
-
-use std::{fmt, mem};
-
-use super::{Secp256k1};
-use super::Error::{self, InvalidPublicKey, InvalidSecretKey};
+#[cfg(any(test, feature = "rand"))] use rand_core::{RngCore, CryptoRng};
+
+use std::{cmp, fmt, hash, mem, ops, str};
+#[cfg(not(feature = "verify-only"))]
+use std::ptr;
+use std::convert::TryFrom;
+#[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+use std::io;
+use std::vec::Vec;
+
+use super::Secp256k1;
+#[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+use super::{Message, Signature};
+use super::Error::{self, InvalidMultisig, InvalidParityValue, InvalidPublicKey, InvalidPublicKeyLength,
+                   InvalidPublicKeyPrefix, NotEnoughMemory, PointAtInfinity, PublicKeyNotOnCurve};
+#[cfg(feature = "hashes")]
+use super::Error::InvalidKeyId;
+#[cfg(any(feature = "hashes", not(feature = "verify-only")))]
+use super::Error::InvalidTweak;
+#[cfg(not(feature = "verify-only"))]
+use super::Error::InvalidSecretKey;
+#[cfg(all(any(target_env = "sgx", feature = "sgx", feature = "std"), not(feature = "verify-only")))]
+use global::SECP256K1;
+use scalar::Scalar;
+use scratch::ScratchSpace;
+#[cfg(not(feature = "verify-only"))]
 use Signing;
 use Verification;
+#[cfg(not(feature = "verify-only"))]
+use cleanup;
 use constants;
 use ffi;
+use hex;
+#[cfg(feature = "hashes")] use generator::Generator;
+#[cfg(feature = "hashes")] use hashes;
 
 /// Secret 256-bit key used as `x` in an ECDSA signature
+#[cfg(not(feature = "verify-only"))]
 pub struct SecretKey([u8; constants::SECRET_KEY_SIZE]);
-impl_array_newtype!(SecretKey, u8, constants::SECRET_KEY_SIZE);
-impl_pretty_debug!(SecretKey);
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl_array_newtype!(SecretKey, u8, constants::SECRET_KEY_SIZE, pub);
+// Indexing hands out the key's bytes, so it is guarded like `as_ref`
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl_array_newtype_index!(SecretKey, u8, {
+    #[cfg(feature = "production-hardening")]
+    ::hardening::assert_secret_export();
+});
+#[cfg(all(feature = "no-secret-export", not(feature = "verify-only")))]
+impl_array_newtype!(SecretKey, u8, constants::SECRET_KEY_SIZE, pub(crate));
+
+// Secret keys are redacted by default so that they cannot end up in logs by
+// accident; use `SecretKey::display_secret` to get at the hex encoding.
+#[cfg(not(feature = "verify-only"))]
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretKey(<redacted>)")
+    }
+}
 
+#[cfg(not(feature = "verify-only"))]
 impl fmt::Display for SecretKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for ch in &self.0[..] {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Parses a secret key as `SecretKey::from_slice` does
+#[cfg(not(feature = "verify-only"))]
+impl<'a> TryFrom<&'a [u8]> for SecretKey {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(data: &'a [u8]) -> Result<SecretKey, Error> {
+        SecretKey::from_slice(data)
+    }
+}
+
+/// Adapter which formats a secret key as hex, returned by
+/// `SecretKey::display_secret`
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+pub struct DisplaySecret {
+    secret: [u8; constants::SECRET_KEY_SIZE]
+}
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl fmt::Display for DisplaySecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for ch in &self.secret[..] {
             write!(f, "{:02x}", *ch)?;
         }
         Ok(())
     }
 }
 
-/// The number 1 encoded as a secret key
-/// Deprecated; `static` is not what I want; use `ONE_KEY` instead
-pub static ONE: SecretKey = SecretKey([0, 0, 0, 0, 0, 0, 0, 0,
-                                       0, 0, 0, 0, 0, 0, 0, 0,
-                                       0, 0, 0, 0, 0, 0, 0, 0,
-                                       0, 0, 0, 0, 0, 0, 0, 1]);
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl fmt::Debug for DisplaySecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DisplaySecret(\"{}\")", self)
+    }
+}
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl Drop for DisplaySecret {
+    fn drop(&mut self) {
+        cleanup::wipe(&mut self.secret);
+    }
+}
 
-/// The number 0 encoded as a secret key
+/// The number 0 encoded as a secret key. This is not a valid secret key:
+/// `from_slice` rejects it, and it must never be used for signing or key
+/// derivation.
+#[cfg(not(feature = "verify-only"))]
 pub const ZERO_KEY: SecretKey = SecretKey([0, 0, 0, 0, 0, 0, 0, 0,
                                            0, 0, 0, 0, 0, 0, 0, 0,
                                            0, 0, 0, 0, 0, 0, 0, 0,
                                            0, 0, 0, 0, 0, 0, 0, 0]);
 
 /// The number 1 encoded as a secret key
+#[cfg(not(feature = "verify-only"))]
 pub const ONE_KEY: SecretKey = SecretKey([0, 0, 0, 0, 0, 0, 0, 0,
                                           0, 0, 0, 0, 0, 0, 0, 0,
                                           0, 0, 0, 0, 0, 0, 0, 0,
                                           0, 0, 0, 0, 0, 0, 0, 1]);
 
 /// A Secp256k1 public key, used for verification of signatures
-#[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord, Hash)]
+///
+/// Comparison and hashing use the 33-byte compressed serialization, so keys
+/// order lexicographically as required by BIP67 and independently of the
+/// library-internal representation; see `PublicKey::cmp_serialized`.
+#[derive(Copy, Clone, Debug)]
 pub struct PublicKey(ffi::PublicKey);
 
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &PublicKey) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for PublicKey {}
+
+impl PartialOrd for PublicKey {
+    fn partial_cmp(&self, other: &PublicKey) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PublicKey {
+    fn cmp(&self, other: &PublicKey) -> cmp::Ordering {
+        self.cmp_serialized(other)
+    }
+}
+
+impl hash::Hash for PublicKey {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.serialize()[..].hash(state)
+    }
+}
+
+/// The parity of a point's y-coordinate, as tracked by taproot and MuSig
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Parity {
+    /// y is even (compressed prefix `0x02`)
+    Even,
+    /// y is odd (compressed prefix `0x03`)
+    Odd,
+}
+
+impl Parity {
+    /// The parity as a bit: 0 for even and 1 for odd
+    #[inline]
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Converts a bit to a parity. Fails with `InvalidParityValue` for
+    /// anything other than 0 or 1.
+    #[inline]
+    pub fn from_u8(bit: u8) -> Result<Parity, Error> {
+        match bit {
+            0 => Ok(Parity::Even),
+            1 => Ok(Parity::Odd),
+            _ => Err(InvalidParityValue),
+        }
+    }
+
+    /// Converts a bit to a parity, like `from_u8`
+    #[inline]
+    pub fn from_i32(bit: i32) -> Result<Parity, Error> {
+        if !(0..=1).contains(&bit) {
+            return Err(InvalidParityValue);
+        }
+        Parity::from_u8(bit as u8)
+    }
+}
+
+impl TryFrom<u8> for Parity {
+    type Error = Error;
+
+    fn try_from(bit: u8) -> Result<Parity, Error> {
+        Parity::from_u8(bit)
+    }
+}
+
+impl TryFrom<i32> for Parity {
+    type Error = Error;
+
+    fn try_from(bit: i32) -> Result<Parity, Error> {
+        Parity::from_i32(bit)
+    }
+}
+
+impl From<Parity> for u8 {
+    fn from(parity: Parity) -> u8 {
+        parity.to_u8()
+    }
+}
+
+/// Combines parities as bits: the parity of negating `n` times is the XOR
+/// of the parities of `n`
+impl ops::BitXor for Parity {
+    type Output = Parity;
+
+    fn bitxor(self, other: Parity) -> Parity {
+        if self == other { Parity::Even } else { Parity::Odd }
+    }
+}
+
+/// A set of public key encodings, for `PublicKey::from_slice_with_formats`.
+/// Combine them with `|`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct AllowedFormats(u8);
+
+impl AllowedFormats {
+    /// 33 bytes, prefix `0x02` or `0x03`
+    pub const COMPRESSED: AllowedFormats = AllowedFormats(1);
+    /// 65 bytes, prefix `0x04`
+    pub const UNCOMPRESSED: AllowedFormats = AllowedFormats(2);
+    /// 65 bytes, prefix `0x06` or `0x07` carrying the parity of y, which
+    /// OpenSSL once accepted and `from_slice` still does
+    pub const HYBRID: AllowedFormats = AllowedFormats(4);
+    /// Every format `from_slice` accepts
+    pub const ALL: AllowedFormats = AllowedFormats(7);
+
+    /// Whether every format in `other` is also in `self`
+    #[inline]
+    pub fn contains(self, other: AllowedFormats) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The format of an encoded key, judged by its length and prefix, or
+    /// `None` if it has none of them
+    fn of(data: &[u8]) -> Option<AllowedFormats> {
+        match (data.len(), data.first()) {
+            (constants::PUBLIC_KEY_SIZE, Some(&0x02)) |
+            (constants::PUBLIC_KEY_SIZE, Some(&0x03)) => Some(AllowedFormats::COMPRESSED),
+            (constants::UNCOMPRESSED_PUBLIC_KEY_SIZE, Some(&0x04)) => Some(AllowedFormats::UNCOMPRESSED),
+            (constants::UNCOMPRESSED_PUBLIC_KEY_SIZE, Some(&0x06)) |
+            (constants::UNCOMPRESSED_PUBLIC_KEY_SIZE, Some(&0x07)) => Some(AllowedFormats::HYBRID),
+            _ => None,
+        }
+    }
+}
+
+impl ops::BitOr for AllowedFormats {
+    type Output = AllowedFormats;
+
+    #[inline]
+    fn bitor(self, other: AllowedFormats) -> AllowedFormats {
+        AllowedFormats(self.0 | other.0)
+    }
+}
+
+/// A public key encoding, for `PublicKey::serialize_format` and
+/// `PublicKey::serialize_into`
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PublicKeyFormat {
+    /// 33 bytes: a prefix for the parity of y, then x
+    Compressed,
+    /// 65 bytes: the prefix `0x04`, then x and y
+    Uncompressed,
+}
+
+impl PublicKeyFormat {
+    /// The length of a key in this format
+    #[inline]
+    pub fn serialized_len(self) -> usize {
+        match self {
+            PublicKeyFormat::Compressed => constants::PUBLIC_KEY_SIZE,
+            PublicKeyFormat::Uncompressed => constants::UNCOMPRESSED_PUBLIC_KEY_SIZE,
+        }
+    }
+
+    fn flags(self) -> ::types::c_uint {
+        match self {
+            PublicKeyFormat::Compressed => ffi::SECP256K1_SER_COMPRESSED,
+            PublicKeyFormat::Uncompressed => ffi::SECP256K1_SER_UNCOMPRESSED,
+        }
+    }
+}
+
+/// The format of a key serialized into `N` bytes. Evaluating `FORMAT` for
+/// any other length than 33 or 65 fails the build.
+struct FormatOfSize<const N: usize>;
+
+impl<const N: usize> FormatOfSize<N> {
+    const FORMAT: PublicKeyFormat = match N {
+        constants::PUBLIC_KEY_SIZE => PublicKeyFormat::Compressed,
+        constants::UNCOMPRESSED_PUBLIC_KEY_SIZE => PublicKeyFormat::Uncompressed,
+        _ => panic!("public keys serialize to 33 or 65 bytes"),
+    };
+}
+
 impl fmt::Display for PublicKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let ser = self.serialize();
@@ -73,87 +337,348 @@ impl fmt::Display for PublicKey {
     }
 }
 
-#[cfg(any(test, feature = "rand"))]
-fn random_32_bytes<R: Rng>(rng: &mut R) -> [u8; 32] {
-    let mut ret = [0u8; 32];
-    rng.fill_bytes(&mut ret);
-    ret
+/// The HKDF salt for `SecretKey::from_seed`
+#[cfg(feature = "hashes")]
+pub const SEED_DERIVATION_SALT: &'static [u8] = b"rust-secp256k1-sgx/seed-key-derivation/v1";
+
+/// Checks that `data` is a valid secret key, i.e. nonzero and less than the
+/// curve order, without needing a context. Runs in constant time.
+#[cfg(not(feature = "verify-only"))]
+fn is_valid_secret_bytes(data: &[u8; constants::SECRET_KEY_SIZE]) -> bool {
+    let mut borrow = 0i16;
+    let mut nonzero = 0u8;
+    for i in (0..constants::SECRET_KEY_SIZE).rev() {
+        let diff = data[i] as i16 - constants::CURVE_ORDER[i] as i16 - borrow;
+        borrow = (diff >> 8) & 1;
+        nonzero |= data[i];
+    }
+    borrow == 1 && nonzero != 0
+}
+
+/// The pay-to-contract tweak `SHA256(P || contract)` for the key `pk`
+#[cfg(feature = "hashes")]
+fn contract_tweak(pk: &PublicKey, contract: &[u8]) -> Result<Scalar, Error> {
+    let mut engine = hashes::Sha256::new();
+    engine.input(&pk.serialize());
+    engine.input(contract);
+    Scalar::from_be_bytes(engine.result()).map_err(|_| InvalidTweak)
 }
 
+#[cfg(not(feature = "verify-only"))]
 impl SecretKey {
     /// Creates a new random secret key. Requires compilation with the "rand" feature.
     #[inline]
     #[cfg(any(test, feature = "rand"))]
-    pub fn new<R: Rng, C>(secp: &Secp256k1<C>, rng: &mut R) -> SecretKey {
-        let mut data = random_32_bytes(rng);
+    pub fn new<R: RngCore + CryptoRng, C>(secp: &Secp256k1<C>, rng: &mut R) -> SecretKey {
+        // Rejected candidates are overwritten in place rather than copied
+        let mut data = [0u8; constants::SECRET_KEY_SIZE];
+        rng.fill_bytes(&mut data);
         unsafe {
             while ffi::secp256k1_ec_seckey_verify(secp.ctx, data.as_ptr()) == 0 {
-                data = random_32_bytes(rng);
+                rng.fill_bytes(&mut data);
             }
         }
-        SecretKey(data)
+        let ret = SecretKey(data);
+        cleanup::wipe(&mut data);
+        ret
+    }
+
+    /// Creates a new random secret key without a context or a caller-supplied
+    /// RNG, drawing from the thread-local `sgx_rand` generator (which is fed
+    /// by `sgx_read_rand` inside an enclave and the OS outside of one), or
+    /// with the "no-ocall" feature from the installed `rng::EntropySource`.
+    /// Requires compilation with the "rand" feature.
+    #[inline]
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new_random() -> SecretKey {
+        let mut rng = ::rng::ambient();
+        let mut data = [0u8; constants::SECRET_KEY_SIZE];
+        rng.fill_bytes(&mut data);
+        while !is_valid_secret_bytes(&data) {
+            rng.fill_bytes(&mut data);
+        }
+        let ret = SecretKey(data);
+        cleanup::wipe(&mut data);
+        ret
+    }
+
+    /// Constructs a secret key from 32 bytes of caller-supplied entropy,
+    /// e.g. from RDSEED or a DRBG, without going through an RNG. The bytes
+    /// are the key if they are valid; otherwise (with probability below
+    /// 2^-127) candidates are drawn from the RFC6979 HMAC-DRBG keyed with
+    /// them until one is, so the same entropy always gives the same key.
+    pub fn from_entropy(mut seed32: [u8; constants::SECRET_KEY_SIZE]) -> SecretKey {
+        let mut data = seed32;
+        let mut attempt = 0;
+        while !is_valid_secret_bytes(&data) {
+            unsafe {
+                // RFC6979 always produces output when given no extra data
+                assert_eq!(ffi::secp256k1_nonce_function_rfc6979(data.as_mut_ptr(), [0u8; 32].as_ptr(),
+                                                                 seed32.as_ptr(), ptr::null(),
+                                                                 ptr::null_mut(), attempt), 1);
+            }
+            attempt += 1;
+        }
+        let ret = SecretKey(data);
+        cleanup::wipe(&mut data);
+        cleanup::wipe(&mut seed32);
+        ret
+    }
+
+    /// Constructs a secret key by hashing arbitrary data with SHA-256,
+    /// tagged with `hashes::SECRET_KEY_HASH_TAG` as in BIP340, so that it
+    /// never equals `Message::from_hashed_data` of the same data. In the
+    /// astronomically unlikely case that the digest is not a valid key, it
+    /// is hashed again until it is, so the result is deterministic. Requires
+    /// compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn from_hashed_data(data: &[u8]) -> SecretKey {
+        let mut hash = hashes::tagged_hash(hashes::SECRET_KEY_HASH_TAG, data);
+        while !is_valid_secret_bytes(&hash) {
+            let mut next = hashes::tagged_hash(hashes::SECRET_KEY_HASH_TAG, &hash);
+            hash.copy_from_slice(&next);
+            cleanup::wipe(&mut next);
+        }
+        let ret = SecretKey(hash);
+        cleanup::wipe(&mut hash);
+        ret
+    }
+
+    /// Derives a secret key from `seed` and `label` with HKDF-SHA256 (salt
+    /// `SEED_DERIVATION_SALT`, info the label followed by a counter byte),
+    /// incrementing the counter until the output is a valid key. The same
+    /// seed and label always give the same key; different labels give
+    /// independent keys. Requires compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn from_seed(seed: &[u8], label: &str) -> SecretKey {
+        let mut info = Vec::with_capacity(label.len() + 1);
+        info.extend_from_slice(label.as_bytes());
+        info.push(0);
+        let mut okm = [0u8; constants::SECRET_KEY_SIZE];
+        hashes::hkdf_sha256(SEED_DERIVATION_SALT, seed, &info, &mut okm);
+        while !is_valid_secret_bytes(&okm) {
+            // Failing 256 times in a row is not going to happen
+            *info.last_mut().unwrap() += 1;
+            hashes::hkdf_sha256(SEED_DERIVATION_SALT, seed, &info, &mut okm);
+        }
+        let ret = SecretKey(okm);
+        cleanup::wipe(&mut okm);
+        ret
     }
 
     /// Converts a `SECRET_KEY_SIZE`-byte slice to a secret key
     #[inline]
-    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8])
-                        -> Result<SecretKey, Error> {
+    pub fn from_slice(data: &[u8]) -> Result<SecretKey, Error> {
         match data.len() {
             constants::SECRET_KEY_SIZE => {
                 let mut ret = [0; constants::SECRET_KEY_SIZE];
-                unsafe {
-                    if ffi::secp256k1_ec_seckey_verify(secp.ctx, data.as_ptr()) == 0 {
-                        return Err(InvalidSecretKey);
-                    }
-                }
                 ret[..].copy_from_slice(data);
+                if !is_valid_secret_bytes(&ret) {
+                    debug_log!(debug, "rejected secret key: not a valid scalar");
+                    return Err(InvalidSecretKey);
+                }
                 Ok(SecretKey(ret))
             }
-            _ => Err(InvalidSecretKey)
+            len => {
+                debug_log!(debug, "rejected secret key: {} bytes instead of {}", len, constants::SECRET_KEY_SIZE);
+                Err(InvalidSecretKey)
+            }
+        }
+    }
+
+    /// Creates the secret key with the value `n`, for protocol constants such
+    /// as Lagrange coefficients. Fails with `InvalidSecretKey` if `n` is
+    /// zero; every other `u64` is below the curve order.
+    #[inline]
+    pub fn from_u64(n: u64) -> Result<SecretKey, Error> {
+        if n == 0 {
+            debug_log!(debug, "rejected secret key: zero");
+            return Err(InvalidSecretKey);
+        }
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        ret[constants::SECRET_KEY_SIZE - 8..].copy_from_slice(&n.to_be_bytes());
+        Ok(SecretKey(ret))
+    }
+
+    /// Checks that `data` is a valid secret key, i.e. nonzero and less than
+    /// the curve order, like `secp256k1_ec_seckey_verify`. Fails with
+    /// `InvalidSecretKey` otherwise. Needs neither a context nor a copy of
+    /// the bytes in a `SecretKey`, e.g. for checking a BIP32 `IL` before
+    /// using it. Runs in constant time.
+    #[inline]
+    pub fn validate_bytes(data: &[u8; constants::SECRET_KEY_SIZE]) -> Result<(), Error> {
+        if is_valid_secret_bytes(data) {
+            Ok(())
+        } else {
+            Err(InvalidSecretKey)
         }
     }
     
-    /// Gets a reference to the underlying array
+    /// Gets a reference to the underlying array. With the
+    /// "production-hardening" feature this panics in a debug enclave, and
+    /// with "no-secret-export" it does not exist.
+    #[cfg(not(feature = "no-secret-export"))]
     #[inline]
     pub fn as_ref(&self) -> &[u8; constants::SECRET_KEY_SIZE] {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::assert_secret_export();
         &self.0
     }
 
+    /// The key's bytes, for the crate's own use whether or not secret
+    /// export is compiled out
+    #[inline]
+    pub(crate) fn secret_bytes(&self) -> &[u8; constants::SECRET_KEY_SIZE] {
+        &self.0
+    }
+
+    /// Returns an adapter which formats the key as hex. `Debug` and `Display`
+    /// on `SecretKey` itself are redacted; call this only when the secret
+    /// really has to be printed. With the "production-hardening" feature
+    /// this panics in a debug enclave.
+    #[cfg(not(feature = "no-secret-export"))]
+    #[inline]
+    pub fn display_secret(&self) -> DisplaySecret {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::assert_secret_export();
+        DisplaySecret { secret: self.0 }
+    }
+
+    /// Compares two secret keys in constant time. This is what `==` does as
+    /// well; the method exists to make the intent explicit at call sites.
+    #[inline]
+    pub fn ct_eq(&self, other: &SecretKey) -> bool {
+        ::constant_time_eq(&self.0, &other.0)
+    }
+
+    /// Sets the key to `other` if `mask` is 0xff, keeping it if `mask` is 0,
+    /// with the same memory accesses either way
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    #[inline]
+    pub(crate) fn ct_assign(&mut self, other: &SecretKey, mask: u8) {
+        ::constant_time_assign(&mut self.0, &other.0, mask);
+    }
+
+    /// Overwrites the key with zeroes, for copies of keys (nonces, tweaked
+    /// or decoded keys) once they are done with. The key is then invalid.
+    #[inline]
+    pub(crate) fn wipe(&mut self) {
+        cleanup::wipe(&mut self.0);
+    }
+
+    /// Constructs an ECDSA signature for `msg` using the global context.
+    /// Convenience function for `Secp256k1::sign`.
     #[inline]
-    /// Adds one secret key to another, modulo the curve order
-    pub fn add_assign<C>(&mut self, secp: &Secp256k1<C>, other: &SecretKey)
+    #[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+    pub fn sign_ecdsa(&self, msg: &Message) -> Signature {
+        SECP256K1.sign(msg, self)
+    }
+
+    #[inline]
+    /// Adds a tweak to the secret key, modulo the curve order
+    pub fn add_assign<C>(&mut self, secp: &Secp256k1<C>, other: &Scalar)
                      -> Result<(), Error> {
-        unsafe {
-            if ffi::secp256k1_ec_privkey_tweak_add(secp.ctx, self.as_mut_ptr(), other.as_ptr()) != 1 {
-                Err(InvalidSecretKey)
+        let res = unsafe {
+            ffi::secp256k1_ec_privkey_tweak_add(secp.ctx, self.as_mut_ptr(), other.as_ptr())
+        };
+        cleanup::scrub_stack();
+        if res != 1 {
+            Err(InvalidTweak)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Adds the same tweak to every key in `keys`, modulo the curve order.
+    /// Either all keys are tweaked or, if any result would be zero, none are
+    /// and `InvalidTweak` is returned.
+    pub fn add_tweak_batch<C>(secp: &Secp256k1<C>, keys: &mut [SecretKey], tweak: &Scalar)
+                             -> Result<(), Error> {
+        measure!(batch TweakBatch, keys.len(), Result::is_ok, {
+            // A sum is zero exactly when the key is the tweak's negation
+            let mut minus_tweak = tweak.negate();
+            let mut negated = SecretKey(minus_tweak.to_be_bytes());
+            minus_tweak.wipe();
+            let zero_sum = keys.iter().fold(false, |found, key| found | key.ct_eq(&negated));
+            cleanup::wipe(&mut negated.0);
+            if zero_sum {
+                cleanup::scrub_stack();
+                Err(InvalidTweak)
             } else {
+                for key in keys.iter_mut() {
+                    let res = unsafe {
+                        ffi::secp256k1_ec_privkey_tweak_add(secp.ctx, key.as_mut_ptr(), tweak.as_ptr())
+                    };
+                    // No sum is zero, checked above
+                    assert!(res == 1);
+                }
+                cleanup::scrub_stack();
                 Ok(())
             }
-        }
+        })
     }
 
     #[inline]
-    /// Multiplies one secret key by another, modulo the curve order
-    pub fn mul_assign<C>(&mut self, secp: &Secp256k1<C>, other: &SecretKey)
+    /// Multiplies the secret key by a tweak, modulo the curve order
+    pub fn mul_assign<C>(&mut self, secp: &Secp256k1<C>, other: &Scalar)
                      -> Result<(), Error> {
+        let res = unsafe {
+            ffi::secp256k1_ec_privkey_tweak_mul(secp.ctx, self.as_mut_ptr(), other.as_ptr())
+        };
+        cleanup::scrub_stack();
+        if res != 1 {
+            Err(InvalidTweak)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    /// Replaces the secret key by its multiplicative inverse modulo the curve
+    /// order, in constant time. Every valid key is invertible, so this can't
+    /// fail.
+    pub fn inv_assign<C>(&mut self, secp: &Secp256k1<C>) {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
         unsafe {
-            if ffi::secp256k1_ec_privkey_tweak_mul(secp.ctx, self.as_mut_ptr(), other.as_ptr()) != 1 {
-                Err(InvalidSecretKey)
-            } else {
-                Ok(())
-            }
+            let res = ffi::secp256k1_ec_scalar_inverse(secp.ctx, ret.as_mut_ptr(), self.as_ptr());
+            assert!(res == 1);
         }
+        self.0 = ret;
+        cleanup::wipe(&mut ret);
+        cleanup::scrub_stack();
+    }
+
+    /// The secret key for `PublicKey::blind`: this key times `factor`.
+    /// Fails with `InvalidTweak` if `factor` is zero.
+    pub fn blind<C>(&self, secp: &Secp256k1<C>, factor: &Scalar) -> Result<SecretKey, Error> {
+        let mut ret = *self;
+        ret.mul_assign(secp, factor)?;
+        Ok(ret)
+    }
+
+    /// The secret key for `PublicKey::commit`: this key plus
+    /// `SHA256(P || contract)`, where `P` is its public key. Fails with
+    /// `InvalidTweak` in the astronomically unlikely case that the hash is
+    /// not a valid tweak. Requires compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn commit<C: Signing>(&self, secp: &Secp256k1<C>, contract: &[u8]) -> Result<SecretKey, Error> {
+        let tweak = contract_tweak(&PublicKey::from_secret_key(secp, self), contract)?;
+        let mut ret = *self;
+        ret.add_assign(secp, &tweak)?;
+        Ok(ret)
     }
 }
 
-#[cfg(feature = "serde")]
+#[cfg(all(feature = "serde", not(any(feature = "no-secret-export", feature = "verify-only"))))]
 impl ::serde::Serialize for SecretKey {
     fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::check_secret_export().map_err(::serde::ser::Error::custom)?;
         s.serialize_bytes(&self.0)
     }
 }
 
-#[cfg(feature = "serde")]
+#[cfg(all(feature = "serde", not(feature = "verify-only")))]
 impl<'de> ::serde::Deserialize<'de> for SecretKey {
     fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<SecretKey, D::Error> {
         use ::serde::de::Error;
@@ -161,13 +686,11 @@ impl<'de> ::serde::Deserialize<'de> for SecretKey {
         // serde can actually deserialize a 32-byte array directly rather than deserializing
         // a byte slice and copying, but it has special code for byte-slices and no special
         // code for byte-arrays, meaning this is actually simpler and more efficient
-        let mut arr = [0; 32];
         let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
         if sl.len() != constants::SECRET_KEY_SIZE {
             return Err(D::Error::invalid_length(sl.len(), &"32"));
         }
-        arr.copy_from_slice(sl);
-        Ok(SecretKey(arr))
+        SecretKey::from_slice(sl).map_err(D::Error::custom)
     }
 }
 
@@ -178,8 +701,39 @@ impl PublicKey {
         &self.0 as *const _
     }
 
+    /// Compares the 33-byte compressed serializations of the two keys with
+    /// `secp256k1_ec_pubkey_cmp`, which is what `Ord` and `PartialEq` do
+    /// today; call this where the ordering must stay pinned to the
+    /// serialized form, such as in consensus code. Public keys are not
+    /// secret, so this does not run in constant time.
+    pub fn cmp_serialized(&self, other: &PublicKey) -> cmp::Ordering {
+        let ret = unsafe {
+            ffi::secp256k1_ec_pubkey_cmp(ffi::secp256k1_context_no_precomp, self.as_ptr(),
+                                         other.as_ptr())
+        };
+        ret.cmp(&0)
+    }
+
+    /// Whether the two keys are the same point, like `==` but comparing
+    /// the library's internal representation (which is unique for each
+    /// point) instead of serializing both. Not constant time.
+    #[inline]
+    pub fn eq_fast(&self, other: &PublicKey) -> bool {
+        self.0 == other.0
+    }
+
+    /// Sets the key to `other` if `mask` is 0xff, keeping it if `mask` is 0,
+    /// with the same memory accesses either way
+    #[cfg(not(any(feature = "fast-vault-lookup", feature = "verify-only")))]
+    #[inline]
+    pub(crate) fn ct_assign(&mut self, other: &PublicKey, mask: u8) {
+        let dst = unsafe { ::std::slice::from_raw_parts_mut(self.0.as_mut_ptr(), self.0.len()) };
+        ::constant_time_assign(dst, &other.0[..], mask);
+    }
+
     /// Creates a new public key from a secret key.
     #[inline]
+    #[cfg(not(feature = "verify-only"))]
     pub fn from_secret_key<C: Signing>(secp: &Secp256k1<C>,
                            sk: &SecretKey)
                            -> PublicKey {
@@ -188,193 +742,1140 @@ impl PublicKey {
             // We can assume the return value because it's not possible to construct
             // an invalid `SecretKey` without transmute trickery or something
             let res = ffi::secp256k1_ec_pubkey_create(secp.ctx, &mut pk, sk.as_ptr());
-            debug_assert_eq!(res, 1);
+            assert!(res == 1);
         }
+        cleanup::scrub_stack();
         PublicKey(pk)
     }
 
-    /// Creates a public key directly from a slice
+    /// Creates a uniformly random public key whose secret key nobody knows,
+    /// e.g. for decoys and cover traffic that must look like real keys, or
+    /// for checking that verification rejects unrelated keys. Random
+    /// x-coordinates are drawn until one is on the curve, about two tries
+    /// on average, and given a random y parity. Requires compilation with
+    /// the "rand" feature.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn random<R: RngCore + CryptoRng>(rng: &mut R) -> PublicKey {
+        let mut candidate = [0u8; constants::PUBLIC_KEY_SIZE];
+        loop {
+            rng.fill_bytes(&mut candidate[..]);
+            candidate[0] = 0x02 | (candidate[0] & 1);
+            if let Ok(pk) = PublicKey::from_slice(&candidate) {
+                return pk;
+            }
+        }
+    }
+
+    /// Creates a public key from its affine coordinates, given as 32-byte
+    /// big-endian field elements. Fails if the point is not on the curve.
+    #[inline]
+    pub fn from_coordinates(x: &[u8; 32], y: &[u8; 32]) -> Result<PublicKey, Error> {
+        let mut ser = [0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE];
+        ser[0] = 0x04;
+        ser[1..33].copy_from_slice(x);
+        ser[33..].copy_from_slice(y);
+        PublicKey::from_slice(&ser)
+    }
+
+    /// Returns the affine x coordinate as a 32-byte big-endian field element
     #[inline]
-    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8])
-                      -> Result<PublicKey, Error> {
-        if data.is_empty() {return Err(InvalidPublicKey);}
+    pub fn x_coordinate(&self) -> [u8; 32] {
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&self.serialize_uncompressed()[1..33]);
+        ret
+    }
+
+    /// Returns the affine y coordinate as a 32-byte big-endian field element
+    #[inline]
+    pub fn y_coordinate(&self) -> [u8; 32] {
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&self.serialize_uncompressed()[33..]);
+        ret
+    }
+
+    /// Computes `scalar * G`. Fails with `PointAtInfinity` if the scalar is
+    /// zero, since the point at infinity is not a valid public key. Requires
+    /// a signing-capable context.
+    #[inline]
+    #[cfg(not(feature = "verify-only"))]
+    pub fn from_scalar_mul_g<C: Signing>(secp: &Secp256k1<C>, scalar: &Scalar)
+                                         -> Result<PublicKey, Error> {
+        let mut pk = unsafe { ffi::PublicKey::blank() };
+        let res = unsafe { ffi::secp256k1_ec_pubkey_create(secp.ctx, &mut pk, scalar.as_ptr()) };
+        cleanup::scrub_stack();
+        if res == 1 {
+            Ok(PublicKey(pk))
+        } else {
+            Err(PointAtInfinity)
+        }
+    }
+
+    /// Sorts a slice of public keys lexicographically by their compressed
+    /// serialization, as done for BIP67 multisig scripts
+    #[inline]
+    pub fn sort(keys: &mut [PublicKey]) {
+        keys.sort_by(PublicKey::cmp_serialized);
+    }
+
+    /// Sorts a slice of public keys in BIP67 order. Same as `sort`, under
+    /// the name used in multisig setup code.
+    #[inline]
+    pub fn sort_bip67(keys: &mut [PublicKey]) {
+        PublicKey::sort(keys)
+    }
+
+    /// Builds the BIP67 `threshold`-of-`keys.len()` multisig script
+    /// `OP_m <key_1> ... <key_n> OP_n OP_CHECKMULTISIG`, with the keys
+    /// compressed and sorted. Fails with `InvalidMultisig` unless
+    /// `1 <= threshold <= keys.len() <= 16`.
+    pub fn sorted_multisig_script(threshold: usize, keys: &[PublicKey]) -> Result<Vec<u8>, Error> {
+        if threshold == 0 || threshold > keys.len() || keys.len() > 16 {
+            return Err(InvalidMultisig);
+        }
+        let mut sorted = keys.to_vec();
+        PublicKey::sort_bip67(&mut sorted);
+
+        let mut script = Vec::with_capacity(3 + keys.len() * (1 + constants::PUBLIC_KEY_SIZE));
+        script.push(0x50 + threshold as u8);
+        for key in &sorted {
+            script.push(constants::PUBLIC_KEY_SIZE as u8);
+            script.extend_from_slice(&key.serialize());
+        }
+        script.push(0x50 + keys.len() as u8);
+        script.push(0xae); // OP_CHECKMULTISIG
+        Ok(script)
+    }
+
+    /// Creates a public key directly from a slice. Fails with
+    /// `InvalidPublicKeyLength`, `InvalidPublicKeyPrefix` or
+    /// `PublicKeyNotOnCurve`, depending on what is wrong with it.
+    #[inline]
+    pub fn from_slice(data: &[u8]) -> Result<PublicKey, Error> {
+        if data.is_empty() {return Err(InvalidPublicKeyLength);}
 
         let mut pk = unsafe { ffi::PublicKey::blank() };
         unsafe {
-            if ffi::secp256k1_ec_pubkey_parse(secp.ctx, &mut pk, data.as_ptr(),
-                                              data.len() as ::libc::size_t) == 1 {
+            if ffi::secp256k1_ec_pubkey_parse(ffi::secp256k1_context_no_precomp, &mut pk, data.as_ptr(),
+                                              data.len() as ::types::size_t) == 1 {
                 Ok(PublicKey(pk))
             } else {
+                let err = PublicKey::parse_error(data);
+                debug_log!(debug, "rejected public key: {} bytes starting with {:#04x} ({})",
+                           data.len(), data[0], err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Why libsecp256k1 rejected `data`
+    fn parse_error(data: &[u8]) -> Error {
+        match AllowedFormats::of(data) {
+            Some(AllowedFormats::HYBRID) => {
+                // The point may be fine but for the parity in the prefix
+                let mut uncompressed = [0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE];
+                uncompressed.copy_from_slice(data);
+                uncompressed[0] = 0x04;
+                match PublicKey::from_slice(&uncompressed) {
+                    Ok(_) => InvalidPublicKeyPrefix,
+                    Err(e) => e,
+                }
+            }
+            Some(_) => PublicKeyNotOnCurve,
+            None if data.len() == constants::PUBLIC_KEY_SIZE ||
+                    data.len() == constants::UNCOMPRESSED_PUBLIC_KEY_SIZE => InvalidPublicKeyPrefix,
+            None => InvalidPublicKeyLength,
+        }
+    }
+
+    /// Creates a public key from a slice like `from_slice`, but fails with
+    /// `InvalidPublicKey` unless its encoding is one of `allowed`, e.g.
+    /// `AllowedFormats::COMPRESSED` to accept only compressed keys
+    #[inline]
+    pub fn from_slice_with_formats(data: &[u8], allowed: AllowedFormats) -> Result<PublicKey, Error> {
+        match AllowedFormats::of(data) {
+            Some(format) if allowed.contains(format) => PublicKey::from_slice(data),
+            Some(_) => {
+                debug_log!(debug, "rejected public key: format of {} bytes not allowed", data.len());
                 Err(InvalidPublicKey)
             }
+            None => Err(PublicKey::parse_error(data)),
         }
     }
 
+    /// Creates a public key from the 64-byte library-internal representation
+    /// returned by `to_ffi_bytes`. The bytes are checked to encode a valid
+    /// curve point in canonical form. Requires the "ffi-bytes" feature.
+    ///
+    /// The internal representation is not part of libsecp256k1's stable API;
+    /// only exchange these bytes with processes linked against the very same
+    /// libsecp256k1 build.
+    #[cfg(feature = "ffi-bytes")]
+    pub fn from_ffi_bytes(data: [u8; 64]) -> Result<PublicKey, Error> {
+        let candidate = PublicKey(unsafe { ffi::PublicKey::from_array_unchecked(data) });
+        candidate.check()?;
+        Ok(candidate)
+    }
+
+    /// Whether the key encodes a valid curve point in canonical form, as
+    /// every key this crate parses or computes does. A key converted from an
+    /// `ffi::PublicKey`, e.g. one filled in by C code across an ECALL, is not
+    /// checked and may be the point at infinity or not on the curve; check
+    /// it with this before use. `from_ffi_bytes` checks already.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Fails with `InvalidPublicKey` for the point at infinity or
+    /// unnormalized coordinates, and with `PublicKeyNotOnCurve` for a point
+    /// off the curve
+    fn check(&self) -> Result<(), Error> {
+        let mut ser = [0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE];
+        unsafe {
+            let mut ser_len = constants::UNCOMPRESSED_PUBLIC_KEY_SIZE as ::types::size_t;
+            if ffi::secp256k1_ec_pubkey_serialize(
+                ffi::secp256k1_context_no_precomp,
+                ser.as_mut_ptr(),
+                &mut ser_len,
+                &self.0,
+                ffi::SECP256K1_SER_UNCOMPRESSED,
+            ) != 1 {
+                return Err(InvalidPublicKey);
+            }
+        }
+        // Parsing checks that the point is on the curve; comparing the internal
+        // representations rejects unnormalized field elements, which `==`
+        // would not as it compares serializations
+        if !PublicKey::from_slice(&ser)?.eq_fast(self) {
+            return Err(InvalidPublicKey);
+        }
+        Ok(())
+    }
+
+    /// Returns the 64-byte library-internal representation of the key. See
+    /// `from_ffi_bytes` for the stability caveat. Requires the "ffi-bytes"
+    /// feature.
+    #[cfg(feature = "ffi-bytes")]
+    pub fn to_ffi_bytes(&self) -> [u8; 64] {
+        let mut ret = [0; 64];
+        ret.copy_from_slice(&self.0[..]);
+        ret
+    }
+
     #[inline]
     /// Serialize the key as a byte-encoded pair of values. In compressed form
     /// the y-coordinate is represented by only a single bit, as x determines
     /// it up to one bit.
     pub fn serialize(&self) -> [u8; constants::PUBLIC_KEY_SIZE] {
-        let secp = Secp256k1::without_caps();
         let mut ret = [0; constants::PUBLIC_KEY_SIZE];
+        self.serialize_into(PublicKeyFormat::Compressed, &mut ret);
+        ret
+    }
+
+    /// Serializes the key in `format`, for callers which choose the format
+    /// at runtime
+    pub fn serialize_format(&self, format: PublicKeyFormat) -> Vec<u8> {
+        let mut ret = vec![0; format.serialized_len()];
+        self.serialize_into(format, &mut ret);
+        ret
+    }
+
+    /// Serializes the key compressed or uncompressed, like `serialize_format`
+    #[inline]
+    pub fn serialize_vec(&self, compressed: bool) -> Vec<u8> {
+        self.serialize_format(if compressed { PublicKeyFormat::Compressed } else { PublicKeyFormat::Uncompressed })
+    }
 
+    /// Serializes the key in `format` into the start of `out`, without
+    /// allocating, and returns the number of bytes written. Panics if `out`
+    /// is shorter than `format.serialized_len()`.
+    pub fn serialize_into(&self, format: PublicKeyFormat, out: &mut [u8]) -> usize {
+        let len = format.serialized_len();
+        assert!(out.len() >= len, "{} bytes is too short for a {}-byte public key", out.len(), len);
         unsafe {
-            let mut ret_len = constants::PUBLIC_KEY_SIZE as ::libc::size_t;
+            let mut ret_len = len as ::types::size_t;
             let err = ffi::secp256k1_ec_pubkey_serialize(
-                secp.ctx,
-                ret.as_mut_ptr(),
+                ffi::secp256k1_context_no_precomp,
+                out.as_mut_ptr(),
                 &mut ret_len,
                 self.as_ptr(),
-                ffi::SECP256K1_SER_COMPRESSED,
+                format.flags(),
             );
-            debug_assert_eq!(err, 1);
-            debug_assert_eq!(ret_len, ret.len());
+            // Serialization never fails, and writes exactly the format's size
+            assert!(err == 1);
+            assert_eq!(ret_len, len);
         }
-        ret
+        len
+    }
+
+    /// Serializes the key into a buffer of exactly its length, in the format
+    /// of that length: compressed into 33 bytes, uncompressed into 65. Other
+    /// lengths don't compile.
+    #[inline]
+    pub fn serialize_to<const N: usize>(&self, out: &mut [u8; N]) {
+        self.serialize_into(FormatOfSize::<N>::FORMAT, out);
+    }
+
+    /// Writes the key in `format` to `writer`, e.g. straight into a packet
+    /// being assembled, without allocating
+    #[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+    pub fn write_serialized<W: io::Write>(&self, format: PublicKeyFormat, writer: &mut W) -> io::Result<()> {
+        let mut buf = [0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE];
+        let len = self.serialize_into(format, &mut buf);
+        writer.write_all(&buf[..len])
+    }
+
+    /// The parity of the key's y-coordinate
+    #[inline]
+    pub fn parity(&self) -> Parity {
+        if self.serialize()[0] == 0x03 { Parity::Odd } else { Parity::Even }
+    }
+
+    /// Returns the key with even y, which is this key or its negation, and
+    /// the parity of this key's y-coordinate, which records whether it was
+    /// negated. Its x-coordinate is the x-only key of BIP340.
+    pub fn normalize_even_y(&self) -> (PublicKey, Parity) {
+        let mut ser = self.serialize();
+        let parity = if ser[0] == 0x03 { Parity::Odd } else { Parity::Even };
+        ser[0] = 0x02;
+        let even = PublicKey::from_slice(&ser).expect("negating a valid point gives a valid point");
+        (even, parity)
+    }
+
+    /// Returns the negation of the key: the point with the same x-coordinate
+    /// and the other y
+    #[inline]
+    pub fn negate(&self) -> PublicKey {
+        let mut ser = self.serialize();
+        ser[0] ^= 0x01;
+        PublicKey::from_slice(&ser).expect("negating a valid point gives a valid point")
     }
 
     /// Serialize the key as a byte-encoded pair of values, in uncompressed form
     pub fn serialize_uncompressed(&self) -> [u8; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE] {
-        let secp = Secp256k1::without_caps();
         let mut ret = [0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE];
+        self.serialize_into(PublicKeyFormat::Uncompressed, &mut ret);
+        ret
+    }
 
+    #[inline]
+    /// Adds the pk corresponding to `other` to the pk `self` in place. Fails
+    /// with `PointAtInfinity` if `other` is the negation of this key's secret.
+    pub fn add_exp_assign<C: Verification>(&mut self, secp: &Secp256k1<C>, other: &Scalar)
+                         -> Result<(), Error> {
         unsafe {
-            let mut ret_len = constants::UNCOMPRESSED_PUBLIC_KEY_SIZE as ::libc::size_t;
-            let err = ffi::secp256k1_ec_pubkey_serialize(
-                secp.ctx,
-                ret.as_mut_ptr(),
-                &mut ret_len,
-                self.as_ptr(),
-                ffi::SECP256K1_SER_UNCOMPRESSED,
-            );
-            debug_assert_eq!(err, 1);
-            debug_assert_eq!(ret_len, ret.len());
+            if ffi::secp256k1_ec_pubkey_tweak_add(secp.ctx, &mut self.0 as *mut _,
+                                                  other.as_ptr()) == 1 {
+                Ok(())
+            } else {
+                Err(PointAtInfinity)
+            }
         }
-        ret
     }
 
     #[inline]
-    /// Adds the pk corresponding to `other` to the pk `self` in place
-    pub fn add_exp_assign<C: Verification>(&mut self, secp: &Secp256k1<C>, other: &SecretKey)
+    /// Muliplies the pk `self` in place by the scalar `other`. Fails with
+    /// `PointAtInfinity` if `other` is zero.
+    pub fn mul_assign<C: Verification>(&mut self, secp: &Secp256k1<C>, other: &Scalar)
                          -> Result<(), Error> {
         unsafe {
-            if ffi::secp256k1_ec_pubkey_tweak_add(secp.ctx, &mut self.0 as *mut _,
+            if ffi::secp256k1_ec_pubkey_tweak_mul(secp.ctx, &mut self.0 as *mut _,
                                                   other.as_ptr()) == 1 {
                 Ok(())
             } else {
-                Err(InvalidSecretKey)
+                Err(PointAtInfinity)
             }
         }
     }
 
+    /// Returns `self + tweak * G`, like `add_exp_assign` but leaving this key
+    /// as it is. Fails with `PointAtInfinity` if the result would be the
+    /// point at infinity.
+    #[inline]
+    pub fn add_exp<C: Verification>(&self, secp: &Secp256k1<C>, tweak: &Scalar) -> Result<PublicKey, Error> {
+        let mut ret = *self;
+        ret.add_exp_assign(secp, tweak)?;
+        Ok(ret)
+    }
+
+    /// Computes `a * self + b * G` in a single multiplication, as signature
+    /// verification does, rather than multiplying, tweaking and combining
+    /// separately. Protocols such as adaptor signatures and Pedersen
+    /// commitment openings check equations of this form. Runs in variable
+    /// time, so neither scalar may be secret. Fails with `PointAtInfinity`
+    /// if the result is the point at infinity.
+    pub fn mul_tweak_generator_add<C: Verification>(&self, secp: &Secp256k1<C>, a: &Scalar, b: &Scalar)
+                                                   -> Result<PublicKey, Error> {
+        let mut ret = ffi::PublicKey::new();
+        unsafe {
+            if ffi::secp256k1_ec_pubkey_mul_add_generator(secp.ctx, &mut ret, self.as_ptr(), a.as_ptr(),
+                                                          b.as_ptr()) == 1 {
+                Ok(PublicKey(ret))
+            } else {
+                Err(PointAtInfinity)
+            }
+        }
+    }
+
+    /// Blinds the key by multiplying it by `factor`, as Tor does for onion
+    /// service keys: keys blinded with unrelated factors can't be linked to
+    /// each other or the original without knowing the factors, and the
+    /// holder of the secret key signs for them with `SecretKey::blind`.
+    /// `blinding_factor` derives a factor per context, e.g. per relying
+    /// party. Fails with `PointAtInfinity` if `factor` is zero.
+    #[inline]
+    pub fn blind<C: Verification>(&self, secp: &Secp256k1<C>, factor: &Scalar) -> Result<PublicKey, Error> {
+        let mut ret = *self;
+        ret.mul_assign(secp, factor)?;
+        Ok(ret)
+    }
+
+    /// Adds `tweak` times the generator to every key in `keys`. This computes
+    /// the tweak's point only once and normalizes the results together, so
+    /// for large arrays it is well over ten times faster than calling
+    /// `add_exp_assign` on each key.
+    /// Either all keys are tweaked or, if any result would be the point at
+    /// infinity, none are and `PointAtInfinity` is returned.
+    ///
+    /// Batches are independent, so very large arrays can also be split with
+    /// `chunks_mut` and tweaked on several threads sharing one context.
+    pub fn add_exp_tweak_batch<C: Verification>(secp: &Secp256k1<C>, keys: &mut [PublicKey], tweak: &Scalar)
+                                               -> Result<(), Error> {
+        measure!(batch TweakBatch, keys.len(), Result::is_ok, {
+            let mut tweaked: Vec<ffi::PublicKey> = keys.iter().map(|key| key.0).collect();
+            if unsafe { ffi::secp256k1_ec_pubkey_tweak_add_batch(secp.ctx, tweaked.as_mut_ptr(), tweaked.len(),
+                                                                 tweak.as_ptr()) } != 1 {
+                Err(PointAtInfinity)
+            } else {
+                for (key, tweaked) in keys.iter_mut().zip(tweaked.into_iter()) {
+                    key.0 = tweaked;
+                }
+                Ok(())
+            }
+        })
+    }
+
+    /// Checks that `sig` is a valid ECDSA signature for `msg` under this key,
+    /// using the global context. Convenience function for `Secp256k1::verify`.
+    #[inline]
+    #[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+    pub fn verify(&self, msg: &Message, sig: &Signature) -> Result<(), Error> {
+        #[cfg(not(feature = "verify-only"))]
+        return SECP256K1.verify(msg, sig, self);
+        #[cfg(feature = "verify-only")]
+        return Secp256k1::new_verify_only_static().verify(msg, sig, self);
+    }
+
+    /// Adds a second key to this one, returning the sum. Returns
+    /// `PointAtInfinity` if the result would be the point at infinity, i.e.
+    /// we are adding this point to its own negation
+    pub fn combine<C>(&self, secp: &Secp256k1<C>, other: &PublicKey) -> Result<PublicKey, Error> {
+        unsafe {
+            let mut ret = mem::uninitialized();
+            let ptrs = [self.as_ptr(), other.as_ptr()];
+            if ffi::secp256k1_ec_pubkey_combine(secp.ctx, &mut ret, ptrs.as_ptr(), 2) == 1 {
+                Ok(PublicKey(ret))
+            } else {
+                Err(PointAtInfinity)
+            }
+        }
+    }
+
+    /// Computes the sum `s_1 * P_1 + ... + s_n * P_n` over `terms` in a single
+    /// multi-scalar multiplication, which is much faster than multiplying and
+    /// combining the keys one by one. The intermediate tables are kept in a
+    /// scratch space of at most `scratch_size` bytes; if they don't fit, the
+    /// terms are processed in batches. Returns `NotEnoughMemory` if not even a
+    /// single term fits (see `ScratchSpace::size_for_msm`), and
+    /// `PointAtInfinity` if the sum is the point at infinity (which includes
+    /// the empty sum).
+    ///
+    /// The Strauss and Pippenger algorithms behind it branch and index tables
+    /// on the digits of the scalars, so their running time and memory accesses
+    /// depend on the scalars: they must be public, as in batch verification.
+    /// Multiply by a secret scalar with `mul_assign` instead.
+    pub fn msm<C: Verification>(secp: &Secp256k1<C>, terms: &[(Scalar, PublicKey)], scratch_size: usize)
+                               -> Result<PublicKey, Error> {
+        let mut scratch = ScratchSpace::new(secp, scratch_size)?;
+        PublicKey::msm_with_scratch(secp, &mut scratch, terms)
+    }
+
+    /// Like `msm`, with the intermediate tables kept in `scratch`
+    pub fn msm_with_scratch<C: Verification>(secp: &Secp256k1<C>, scratch: &mut ScratchSpace,
+                                             terms: &[(Scalar, PublicKey)])
+                                             -> Result<PublicKey, Error> {
+        let scalars: Vec<*const u8> = terms.iter().map(|&(ref s, _)| s.as_ptr()).collect();
+        let pks: Vec<*const ffi::PublicKey> = terms.iter().map(|&(_, ref pk)| pk.as_ptr()).collect();
+        let mut ret = ffi::PublicKey::new();
+        let res = unsafe {
+            ffi::secp256k1_ec_pubkey_msm(secp.ctx, scratch.as_mut_ptr(), &mut ret, scalars.as_ptr(),
+                                         pks.as_ptr(), terms.len())
+        };
+        match res {
+            1 => Ok(PublicKey(ret)),
+            -1 => Err(NotEnoughMemory),
+            _ => Err(PointAtInfinity),
+        }
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl PublicKey {
+    /// Commits to `contract` with the pay-to-contract tweak: returns
+    /// `P + SHA256(P || contract) * G`, which only the holder of this key's
+    /// secret can spend (see `SecretKey::commit`) and which binds the
+    /// contract as firmly as a hash would. Fails with `InvalidTweak` in the
+    /// astronomically unlikely case that the hash is not a valid tweak.
+    /// Requires compilation with the "hashes" feature.
+    pub fn commit<C: Verification>(&self, secp: &Secp256k1<C>, contract: &[u8]) -> Result<PublicKey, Error> {
+        let mut ret = *self;
+        ret.add_exp_assign(secp, &contract_tweak(self, contract)?)?;
+        Ok(ret)
+    }
+
+    /// Whether `commitment` is this key's commitment to `contract`, i.e.
+    /// the contract revealed for it is the one it was made for
+    pub fn verify_commitment<C: Verification>(&self, secp: &Secp256k1<C>, contract: &[u8], commitment: &PublicKey)
+                                              -> bool {
+        self.commit(secp, contract).map(|c| c == *commitment).unwrap_or(false)
+    }
+
+    /// The blinding factor of this key for `context`: the tagged hash
+    /// (`hashes::KEY_BLINDING_TAG`) of the key and the context. Anyone who
+    /// knows the key can compute it, so the blinded keys of a public key
+    /// are only unlinkable to those who don't know it. Fails with
+    /// `InvalidTweak` in the astronomically unlikely case that the hash is
+    /// not a valid factor.
+    pub fn blinding_factor(&self, context: &[u8]) -> Result<Scalar, Error> {
+        let hash = hashes::TaggedHasher::new(hashes::KEY_BLINDING_TAG).chain(&self.serialize()).chain(context).result();
+        match Scalar::from_be_bytes(hash) {
+            Ok(ref factor) if factor.is_zero() => Err(InvalidTweak),
+            Ok(factor) => Ok(factor),
+            Err(_) => Err(InvalidTweak),
+        }
+    }
+
+    /// Hashes `data` to a curve point with no known discrete logarithm, in
+    /// the domain `tag`, e.g. for the input points of OPRFs and VRFs. The
+    /// point has x-coordinate `TaggedHash(tag, data || c)`, where `c` is the
+    /// first 4-byte little-endian counter from 0 up whose hash is such an
+    /// x-coordinate, and even y (see `generator::Generator::from_seed`).
+    ///
+    /// This is try-and-increment, not the constant-time RFC 9380 suite: the
+    /// number of attempts depends on `data`, so an attacker timing it learns
+    /// a few bits about the input. Only hash public data with it, or data
+    /// whose hash is public anyway. Requires compilation with the "hashes"
+    /// feature.
+    #[inline]
+    pub fn from_message_hash(tag: &[u8], data: &[u8]) -> PublicKey {
+        Generator::from_seed(tag, data).into()
+    }
+
+    /// The key's identifier: `hash160` of its compressed serialization, as
+    /// Bitcoin uses for P2PKH and P2WPKH and BIP32 for key identifiers
+    #[inline]
+    pub fn fingerprint(&self) -> KeyId {
+        KeyId(hashes::hash160(&self.serialize()))
+    }
+
+    /// The SHA-256 hash of the key's compressed serialization, for systems
+    /// which identify keys by a full-length hash
+    #[inline]
+    pub fn fingerprint_sha256(&self) -> [u8; hashes::SHA256_SIZE] {
+        hashes::sha256(&self.serialize())
+    }
+}
+
+/// A short, stable identifier of a public key, `PublicKey::fingerprint`,
+/// for logs and policies to refer to keys by. It depends only on the key,
+/// not on how the key was encoded or which vault holds it.
+///
+/// `Display`, `FromStr` and serde for human-readable formats use 40 hex
+/// digits; other serde formats use the 20 bytes. Requires compilation with
+/// the "hashes" feature.
+#[cfg(feature = "hashes")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyId([u8; hashes::RIPEMD160_SIZE]);
+
+#[cfg(feature = "hashes")]
+impl KeyId {
+    /// Creates an identifier from its 20 bytes
+    #[inline]
+    pub fn from_bytes(bytes: [u8; hashes::RIPEMD160_SIZE]) -> KeyId {
+        KeyId(bytes)
+    }
+
+    /// Parses a 20-byte identifier. Fails with `InvalidKeyId` for any
+    /// other length.
+    #[inline]
+    pub fn from_slice(data: &[u8]) -> Result<KeyId, Error> {
+        if data.len() != hashes::RIPEMD160_SIZE {
+            return Err(InvalidKeyId);
+        }
+        let mut ret = [0; hashes::RIPEMD160_SIZE];
+        ret.copy_from_slice(data);
+        Ok(KeyId(ret))
+    }
+
+    /// The identifier's 20 bytes
+    #[inline]
+    pub fn to_bytes(&self) -> [u8; hashes::RIPEMD160_SIZE] {
+        self.0
+    }
+
+    /// The BIP32 fingerprint of the key: the first 4 bytes of its
+    /// identifier
+    #[inline]
+    pub fn bip32_fingerprint(&self) -> [u8; 4] {
+        let mut ret = [0; 4];
+        ret.copy_from_slice(&self.0[..4]);
+        ret
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl From<PublicKey> for KeyId {
+    #[inline]
+    fn from(pk: PublicKey) -> KeyId {
+        pk.fingerprint()
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        hex::format(&self.0, f)
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl fmt::Debug for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyId({})", self)
+    }
+}
+
+/// Parses an identifier from exactly `2 * RIPEMD160_SIZE` hex digits
+#[cfg(feature = "hashes")]
+impl str::FromStr for KeyId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KeyId, Error> {
+        let mut ret = [0; hashes::RIPEMD160_SIZE];
+        match hex::decode_from(s, &mut ret) {
+            Some(hashes::RIPEMD160_SIZE) => Ok(KeyId(ret)),
+            _ => Err(InvalidKeyId),
+        }
+    }
+}
+
+/// Serializes as a hex string for human-readable formats and as bytes
+/// otherwise
+#[cfg(all(feature = "hashes", feature = "serde"))]
+impl ::serde::Serialize for KeyId {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            s.serialize_bytes(&self.0)
+        }
+    }
+}
+
+#[cfg(all(feature = "hashes", feature = "serde"))]
+impl<'de> ::serde::Deserialize<'de> for KeyId {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<KeyId, D::Error> {
+        use ::serde::de::Error;
+
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = KeyId;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded 20-byte key id")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<KeyId, E> {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        KeyId::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+/// A secret key together with its public key
+#[cfg(not(feature = "verify-only"))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct KeyPair {
+    secret_key: SecretKey,
+    public_key: PublicKey,
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl KeyPair {
+    /// Computes the public key for `sk`. Requires a signing-capable context.
+    #[inline]
+    pub fn from_secret_key<C: Signing>(secp: &Secp256k1<C>, sk: SecretKey) -> KeyPair {
+        KeyPair { secret_key: sk, public_key: PublicKey::from_secret_key(secp, &sk) }
+    }
+
+    /// Creates a new random keypair. Requires a signing-capable context.
+    /// Requires compilation with the "rand" feature.
+    #[inline]
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new<R: RngCore + CryptoRng, C: Signing>(secp: &Secp256k1<C>, rng: &mut R) -> KeyPair {
+        KeyPair::from_secret_key(secp, SecretKey::new(secp, rng))
+    }
+
+    /// Derives a keypair from 32 bytes of entropy as `SecretKey::from_entropy`
+    /// does. Requires a signing-capable context.
+    #[inline]
+    pub fn from_entropy<C: Signing>(secp: &Secp256k1<C>, seed32: [u8; constants::SECRET_KEY_SIZE]) -> KeyPair {
+        KeyPair::from_secret_key(secp, SecretKey::from_entropy(seed32))
+    }
+
+    /// Derives a keypair from a seed and a label as `SecretKey::from_seed`
+    /// does. Requires a signing-capable context. Requires compilation with
+    /// the "hashes" feature.
+    #[inline]
+    #[cfg(feature = "hashes")]
+    pub fn from_seed<C: Signing>(secp: &Secp256k1<C>, seed: &[u8], label: &str) -> KeyPair {
+        KeyPair::from_secret_key(secp, SecretKey::from_seed(seed, label))
+    }
+
+    /// The secret key
+    #[inline]
+    pub fn secret_key(&self) -> SecretKey {
+        self.secret_key
+    }
+
+    /// The public key
+    #[inline]
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl From<KeyPair> for (SecretKey, PublicKey) {
+    #[inline]
+    fn from(pair: KeyPair) -> (SecretKey, PublicKey) {
+        (pair.secret_key, pair.public_key)
+    }
+}
+
+/// Creates a new public key from a FFI public key
+impl From<ffi::PublicKey> for PublicKey {
+    #[inline]
+    fn from(pk: ffi::PublicKey) -> PublicKey {
+        PublicKey(pk)
+    }
+}
+
+/// Parses a compressed or uncompressed public key as `PublicKey::from_slice`
+/// does
+impl<'a> TryFrom<&'a [u8]> for PublicKey {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(data: &'a [u8]) -> Result<PublicKey, Error> {
+        PublicKey::from_slice(data)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for PublicKey {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&self.serialize())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for PublicKey {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<PublicKey, D::Error> {
+        use ::serde::de::Error;
+
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        PublicKey::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+/// An x-only public key as used by BIP340 and taproot: the x-coordinate of
+/// a point, standing for the point with that x and even y
+///
+/// `Display`, `FromStr` and serde for human-readable formats use 64 hex
+/// digits; other serde formats use the 32 bytes. Keys order by their
+/// serialization, as `PublicKey`s do.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct XOnlyPublicKey(PublicKey);
+
+impl XOnlyPublicKey {
+    /// Parses a 32-byte x-only key. Fails with `InvalidPublicKeyLength` for
+    /// any other length and `PublicKeyNotOnCurve` if no point has this x.
+    #[inline]
+    pub fn from_slice(data: &[u8]) -> Result<XOnlyPublicKey, Error> {
+        if data.len() != constants::XONLY_PUBLIC_KEY_SIZE {
+            return Err(InvalidPublicKeyLength);
+        }
+        let mut ser = [0x02; constants::PUBLIC_KEY_SIZE];
+        ser[1..].copy_from_slice(data);
+        PublicKey::from_slice(&ser).map(XOnlyPublicKey)
+    }
+
+    /// Serializes the key as its 32-byte x-coordinate
+    #[inline]
+    pub fn serialize(&self) -> [u8; constants::XONLY_PUBLIC_KEY_SIZE] {
+        self.0.x_coordinate()
+    }
+
+    /// The full public key with this x-coordinate and the y-coordinate of
+    /// parity `parity`
+    #[inline]
+    pub fn public_key(&self, parity: Parity) -> PublicKey {
+        match parity {
+            Parity::Even => self.0,
+            Parity::Odd => self.0.negate(),
+        }
+    }
+
+    /// Tweaks the key as taproot does for its output key: computes
+    /// `Q = P + tweak * G`, with `P` the even-y point of this key, and
+    /// returns the x-only key of `Q` and the parity of its y-coordinate,
+    /// which a script-path spend commits to in its control block. Fails
+    /// with `PointAtInfinity` if `Q` is the point at infinity.
     #[inline]
-    /// Muliplies the pk `self` in place by the scalar `other`
-    pub fn mul_assign<C: Verification>(&mut self, secp: &Secp256k1<C>, other: &SecretKey)
-                         -> Result<(), Error> {
-        unsafe {
-            if ffi::secp256k1_ec_pubkey_tweak_mul(secp.ctx, &mut self.0 as *mut _,
-                                                  other.as_ptr()) == 1 {
-                Ok(())
-            } else {
-                Err(InvalidSecretKey)
-            }
-        }
+    pub fn add_tweak<C: Verification>(&self, secp: &Secp256k1<C>, tweak: &Scalar)
+                                     -> Result<(XOnlyPublicKey, Parity), Error> {
+        Ok(self.0.add_exp(secp, tweak)?.x_only_public_key())
     }
 
-    /// Adds a second key to this one, returning the sum. Returns an error if
-    /// the result would be the point at infinity, i.e. we are adding this point
-    /// to its own negation
-    pub fn combine<C>(&self, secp: &Secp256k1<C>, other: &PublicKey) -> Result<PublicKey, Error> {
-        unsafe {
-            let mut ret = mem::uninitialized();
-            let ptrs = [self.as_ptr(), other.as_ptr()];
-            if ffi::secp256k1_ec_pubkey_combine(secp.ctx, &mut ret, ptrs.as_ptr(), 2) == 1 {
-                Ok(PublicKey(ret))
-            } else {
-                Err(InvalidPublicKey)
-            }
+    /// Checks that `tweaked` with the y-coordinate parity `parity` is this
+    /// key tweaked by `tweak`, as `add_tweak` computes it, which is how a
+    /// script-path spend verifies the output key against the internal key.
+    /// Like libsecp256k1's `secp256k1_xonly_pubkey_tweak_add_check`, this is
+    /// false whenever `add_tweak` would fail.
+    #[inline]
+    pub fn tweak_add_check<C: Verification>(&self, secp: &Secp256k1<C>, tweaked: &XOnlyPublicKey,
+                                            parity: Parity, tweak: &Scalar) -> bool {
+        match self.add_tweak(secp, tweak) {
+            Ok((key, key_parity)) => key == *tweaked && key_parity == parity,
+            Err(_) => false,
         }
     }
 }
 
-/// Creates a new public key from a FFI public key
-impl From<ffi::PublicKey> for PublicKey {
+impl PublicKey {
+    /// Splits the key into its x-only key and the parity of its
+    /// y-coordinate; `XOnlyPublicKey::public_key` with that parity gives the
+    /// key back
     #[inline]
-    fn from(pk: ffi::PublicKey) -> PublicKey {
-        PublicKey(pk)
+    pub fn x_only_public_key(&self) -> (XOnlyPublicKey, Parity) {
+        let (even, parity) = self.normalize_even_y();
+        (XOnlyPublicKey(even), parity)
+    }
+}
+
+/// Parses a 32-byte x-only key as `XOnlyPublicKey::from_slice` does
+impl<'a> TryFrom<&'a [u8]> for XOnlyPublicKey {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(data: &'a [u8]) -> Result<XOnlyPublicKey, Error> {
+        XOnlyPublicKey::from_slice(data)
+    }
+}
+
+impl fmt::Display for XOnlyPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        hex::format(&self.serialize(), f)
+    }
+}
+
+/// Parses an x-only key from exactly `2 * XONLY_PUBLIC_KEY_SIZE` hex digits
+impl str::FromStr for XOnlyPublicKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<XOnlyPublicKey, Error> {
+        let mut ret = [0; constants::XONLY_PUBLIC_KEY_SIZE];
+        match hex::decode_from(s, &mut ret) {
+            Some(constants::XONLY_PUBLIC_KEY_SIZE) => XOnlyPublicKey::from_slice(&ret),
+            _ => Err(InvalidPublicKey),
+        }
     }
 }
 
+/// Serializes as a hex string for human-readable formats and as bytes
+/// otherwise
 #[cfg(feature = "serde")]
-impl ::serde::Serialize for PublicKey {
+impl ::serde::Serialize for XOnlyPublicKey {
     fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-        s.serialize_bytes(&self.serialize())
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            s.serialize_bytes(&self.serialize())
+        }
     }
 }
 
 #[cfg(feature = "serde")]
-impl<'de> ::serde::Deserialize<'de> for PublicKey {
-    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<PublicKey, D::Error> {
+impl<'de> ::serde::Deserialize<'de> for XOnlyPublicKey {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<XOnlyPublicKey, D::Error> {
         use ::serde::de::Error;
 
-        let secp = Secp256k1::without_caps();
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = XOnlyPublicKey;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded 32-byte x-only public key")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<XOnlyPublicKey, E> {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
         let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
-        PublicKey::from_slice(&secp, sl).map_err(D::Error::custom)
+        XOnlyPublicKey::from_slice(sl).map_err(D::Error::custom)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "verify-only")))]
 mod test {
     use super::super::{Secp256k1};
-    use super::super::Error::{InvalidPublicKey, InvalidSecretKey};
-    use super::{PublicKey, SecretKey};
+    use super::super::Error::{InvalidMultisig, InvalidParityValue, InvalidPublicKey, InvalidPublicKeyLength,
+                              InvalidPublicKeyPrefix, InvalidSecretKey, InvalidTweak, NotEnoughMemory,
+                              PointAtInfinity, PublicKeyNotOnCurve};
+    use super::{KeyPair, Parity, PublicKey, SecretKey, XOnlyPublicKey};
+    use std::convert::TryFrom;
     use super::super::constants;
+    use ffi;
+    use scalar::Scalar;
 
-    use rand::{Rng, thread_rng};
-
-    macro_rules! hex {
-        ($hex:expr) => {
-            {
-                let mut vec = Vec::new();
-                let mut b = 0;
-                for (idx, c) in $hex.as_bytes().iter().enumerate() {
-                    b <<= 4;
-                    match *c {
-                        b'A'...b'F' => b |= c - b'A' + 10,
-                        b'a'...b'f' => b |= c - b'a' + 10,
-                        b'0'...b'9' => b |= c - b'0',
-                        _ => panic!("Bad hex"),
-                    }
-                    if (idx & 1) == 1 {
-                        vec.push(b);
-                        b = 0;
-                    }
-                }
-                vec
+    use rand::Rng;
+    use rand_core::CryptoRng;
+    use rng::{SgxRngAdapter, thread_rng};
+
+    #[test]
+    fn skey_from_slice() {
+        let sk = SecretKey::from_slice(&[1; 31]);
+        assert_eq!(sk, Err(InvalidSecretKey));
+
+        let sk = SecretKey::from_slice(&[1; 32]);
+        assert!(sk.is_ok());
+    }
+
+    #[test]
+    fn skey_ct_eq() {
+        let sk1 = SecretKey::from_slice(&[1; 32]).unwrap();
+        let sk2 = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut bytes = [1; 32];
+        bytes[31] = 2;
+        let sk3 = SecretKey::from_slice(&bytes).unwrap();
+
+        assert!(sk1.ct_eq(&sk2));
+        assert!(!sk1.ct_eq(&sk3));
+        assert_eq!(sk1, sk2);
+        assert!(sk1 != sk3);
+    }
+
+    #[test]
+    fn skey_from_entropy() {
+        let s = Secp256k1::new();
+        assert_eq!(SecretKey::from_entropy([1; 32]), SecretKey::from_slice(&[1; 32]).unwrap());
+        // Invalid entropy is resampled, the same way every time
+        let zero = SecretKey::from_entropy([0; 32]);
+        let order = SecretKey::from_entropy(constants::CURVE_ORDER);
+        assert_eq!(zero, SecretKey::from_entropy([0; 32]));
+        assert!(SecretKey::from_slice(&zero.secret_bytes()[..]).is_ok());
+        assert!(zero != order);
+        assert!(SecretKey::from_entropy([0xff; 32]) != order);
+
+        let pair = KeyPair::from_entropy(&s, [0; 32]);
+        assert_eq!(pair.secret_key(), zero);
+        assert_eq!(pair.public_key(), PublicKey::from_secret_key(&s, &zero));
+        assert_eq!(s.generate_keypair_from_entropy([0; 32]), pair.into());
+        let pair = KeyPair::new(&s, &mut thread_rng());
+        assert_eq!(KeyPair::from_secret_key(&s, pair.secret_key()), pair);
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn skey_from_hashed_data() {
+        let sk = SecretKey::from_hashed_data(b"abc");
+        assert_eq!(&sk.secret_bytes()[..],
+                   &hex!("8a57761350b99e1a3043a204304aebcfb0b8da5723e72ba347bde70ced62fe42")[..]);
+        assert!(&sk.secret_bytes()[..] != &::Message::from_hashed_data(b"abc")[..]);
+        assert_eq!(SecretKey::from_slice(&sk.secret_bytes()[..]), Ok(sk));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn skey_from_seed() {
+        let sk = SecretKey::from_seed(&[1; 32], "tenant-1");
+        assert_eq!(&sk.secret_bytes()[..],
+                   &hex!("088ef82dd3e84ea98c45cb95b8cdbf2a4ba619071ac84e694e800bf833a4bef8")[..]);
+        assert_eq!(SecretKey::from_seed(&[1; 32], "tenant-1"), sk);
+        assert!(SecretKey::from_seed(&[1; 32], "tenant-2") != sk);
+        assert!(SecretKey::from_seed(&[2; 32], "tenant-1") != sk);
+
+        let s = Secp256k1::new();
+        let pair = KeyPair::from_seed(&s, &[1; 32], "tenant-1");
+        assert_eq!(pair.secret_key(), sk);
+        assert_eq!(pair.public_key(), PublicKey::from_secret_key(&s, &sk));
+    }
+
+    #[test]
+    fn parity() {
+        assert_eq!(Parity::from_u8(0), Ok(Parity::Even));
+        assert_eq!(Parity::from_u8(1), Ok(Parity::Odd));
+        assert_eq!(Parity::from_u8(2), Err(InvalidParityValue));
+        assert_eq!(Parity::from_i32(-1), Err(InvalidParityValue));
+        assert_eq!(Parity::try_from(1i32), Ok(Parity::Odd));
+        assert_eq!(Parity::try_from(0u8), Ok(Parity::Even));
+        assert_eq!(u8::from(Parity::Odd), 1);
+        assert_eq!(Parity::Odd ^ Parity::Odd, Parity::Even);
+        assert_eq!(Parity::Even ^ Parity::Odd, Parity::Odd);
+
+        let s = Secp256k1::new();
+        let minus_one = Scalar::ONE.negate();
+        for _ in 0..8 {
+            let (_, pk) = s.generate_keypair(&mut thread_rng());
+            let mut negated = pk;
+            negated.mul_assign(&s, &minus_one).unwrap();
+            assert!(pk.parity() != negated.parity());
+
+            let (even, parity) = pk.normalize_even_y();
+            assert_eq!(even.parity(), Parity::Even);
+            assert_eq!(parity, pk.parity());
+            assert_eq!(even, if parity == Parity::Odd { negated } else { pk });
+            assert_eq!(even.serialize()[1..], pk.serialize()[1..]);
+        }
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn key_id() {
+        use super::KeyId;
+        use super::super::Error::InvalidKeyId;
+
+        let s = Secp256k1::new();
+        let one = PublicKey::from_secret_key(&s, &SecretKey::from_u64(1).unwrap());
+        let id = one.fingerprint();
+        // The P2WPKH program of the key with secret 1
+        assert_eq!(id.to_string(), "751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(id.bip32_fingerprint(), [0x75, 0x1e, 0x76, 0xe8]);
+        assert_eq!(format!("{:?}", id), "KeyId(751e76e8199196d454941c45d1b3a323f1433bd6)");
+        assert_eq!(KeyId::from(one), id);
+        // Independent of the encoding the key came in
+        assert_eq!(PublicKey::from_slice(&one.serialize_uncompressed()).unwrap().fingerprint(), id);
+        assert_eq!(one.fingerprint_sha256()[..],
+                   hex!("0f715baf5d4c2ed329785cef29e562f73488c8a2bb9dbc5700b361d54b9b0554")[..]);
+
+        assert_eq!("751e76e8199196d454941c45d1b3a323f1433bd6".parse(), Ok(id));
+        assert_eq!(KeyId::from_slice(&id.to_bytes()), Ok(id));
+        assert_eq!(KeyId::from_bytes(id.to_bytes()), id);
+        assert_eq!(KeyId::from_slice(&[0; 19]), Err(InvalidKeyId));
+        assert_eq!("751e76e8".parse::<KeyId>(), Err(InvalidKeyId));
+        assert!(id != PublicKey::from_secret_key(&s, &SecretKey::from_u64(2).unwrap()).fingerprint());
+    }
+
+    #[test]
+    fn xonly_tweak() {
+        let s = Secp256k1::new();
+        // Computed independently with textbook affine arithmetic
+        let internal: XOnlyPublicKey = "d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d"
+            .parse().unwrap();
+        let mut tweak = [0; 32];
+        tweak.copy_from_slice(&hex!("b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b945ab91eae4f"));
+        let tweak = Scalar::from_be_bytes(tweak).unwrap();
+        let (output, parity) = internal.add_tweak(&s, &tweak).unwrap();
+        assert_eq!(output.to_string(), "619b1b93ebf605ad647fe32e322716290cc7235d3c28947a64be5f58e22087c3");
+        assert_eq!(parity, Parity::Odd);
+        assert!(internal.tweak_add_check(&s, &output, parity, &tweak));
+        assert!(!internal.tweak_add_check(&s, &output, parity ^ Parity::Odd, &tweak));
+        assert!(!internal.tweak_add_check(&s, &internal, parity, &tweak));
+        assert!(!output.tweak_add_check(&s, &output, parity, &tweak));
+
+        for _ in 0..8 {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            let (xonly, _) = pk.x_only_public_key();
+            let t = Scalar::from_secret_key(&SecretKey::new(&s, &mut thread_rng()));
+            let (tweaked, parity) = xonly.add_tweak(&s, &t).unwrap();
+            let full = xonly.public_key(Parity::Even).add_exp(&s, &t).unwrap();
+            assert_eq!((tweaked, parity), full.x_only_public_key());
+            assert!(xonly.tweak_add_check(&s, &tweaked, parity, &t));
+
+            // A tweak of minus the even-y secret key gives the point at infinity
+            let mut d = Scalar::from_secret_key(&sk);
+            if pk.parity() == Parity::Odd {
+                d = d.negate();
             }
+            assert_eq!(xonly.add_tweak(&s, &d.negate()), Err(PointAtInfinity));
+            assert!(!xonly.tweak_add_check(&s, &tweaked, parity, &d.negate()));
         }
     }
 
     #[test]
-    fn skey_from_slice() {
+    fn xonly_public_key() {
         let s = Secp256k1::new();
-        let sk = SecretKey::from_slice(&s, &[1; 31]);
-        assert_eq!(sk, Err(InvalidSecretKey));
+        for _ in 0..8 {
+            let (_, pk) = s.generate_keypair(&mut thread_rng());
+            let (xonly, parity) = pk.x_only_public_key();
+            assert_eq!(parity, pk.parity());
+            assert_eq!(xonly.public_key(parity), pk);
+            assert_eq!(xonly.public_key(Parity::Even).parity(), Parity::Even);
+            assert_eq!(xonly.serialize(), pk.x_coordinate());
+            assert_eq!(XOnlyPublicKey::from_slice(&xonly.serialize()), Ok(xonly));
+            assert_eq!(XOnlyPublicKey::try_from(&xonly.serialize()[..]), Ok(xonly));
+            assert_eq!(xonly.to_string().parse::<XOnlyPublicKey>(), Ok(xonly));
+        }
 
-        let sk = SecretKey::from_slice(&s, &[1; 32]);
-        assert!(sk.is_ok());
+        // The generator's x-coordinate
+        let hex = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let xonly: XOnlyPublicKey = hex.parse().unwrap();
+        assert_eq!(xonly.to_string(), hex);
+        assert_eq!(xonly.public_key(Parity::Even).serialize()[0], 0x02);
+        assert_eq!(xonly.public_key(Parity::Odd).serialize()[0], 0x03);
+
+        assert_eq!(XOnlyPublicKey::from_slice(&[0x79; 31]), Err(InvalidPublicKeyLength));
+        assert_eq!(XOnlyPublicKey::from_slice(&xonly.public_key(Parity::Even).serialize()),
+                   Err(InvalidPublicKeyLength));
+        // x = 5 is not on the curve
+        let mut five = [0; 32];
+        five[31] = 5;
+        assert_eq!(XOnlyPublicKey::from_slice(&five), Err(PublicKeyNotOnCurve));
+        assert_eq!(hex[..62].parse::<XOnlyPublicKey>(), Err(InvalidPublicKey));
+        assert_eq!("zz".repeat(32).parse::<XOnlyPublicKey>(), Err(InvalidPublicKey));
+
+        assert_eq!(xonly.public_key(Parity::Odd), xonly.public_key(Parity::Even).negate());
+        assert_eq!(xonly.public_key(Parity::Odd).negate().negate(), xonly.public_key(Parity::Odd));
+
+        // Ordering follows the serialization
+        let (a, _) = PublicKey::from_secret_key(&s, &SecretKey::from_slice(&[1; 32]).unwrap()).x_only_public_key();
+        let (b, _) = PublicKey::from_secret_key(&s, &SecretKey::from_slice(&[2; 32]).unwrap()).x_only_public_key();
+        assert_eq!(a.cmp(&b), a.serialize().cmp(&b.serialize()));
     }
 
     #[test]
     fn pubkey_from_slice() {
-        let s = Secp256k1::new();
-        assert_eq!(PublicKey::from_slice(&s, &[]), Err(InvalidPublicKey));
-        assert_eq!(PublicKey::from_slice(&s, &[1, 2, 3]), Err(InvalidPublicKey));
+        assert_eq!(PublicKey::from_slice(&[]), Err(InvalidPublicKeyLength));
+        assert_eq!(PublicKey::from_slice(&[1, 2, 3]), Err(InvalidPublicKeyLength));
 
-        let uncompressed = PublicKey::from_slice(&s, &[4, 54, 57, 149, 239, 162, 148, 175, 246, 254, 239, 75, 154, 152, 10, 82, 234, 224, 85, 220, 40, 100, 57, 121, 30, 162, 94, 156, 135, 67, 74, 49, 179, 57, 236, 53, 162, 124, 149, 144, 168, 77, 74, 30, 72, 211, 229, 110, 111, 55, 96, 193, 86, 227, 183, 152, 195, 155, 51, 247, 123, 113, 60, 228, 188]);
+        let uncompressed = PublicKey::from_slice(&[4, 54, 57, 149, 239, 162, 148, 175, 246, 254, 239, 75, 154, 152, 10, 82, 234, 224, 85, 220, 40, 100, 57, 121, 30, 162, 94, 156, 135, 67, 74, 49, 179, 57, 236, 53, 162, 124, 149, 144, 168, 77, 74, 30, 72, 211, 229, 110, 111, 55, 96, 193, 86, 227, 183, 152, 195, 155, 51, 247, 123, 113, 60, 228, 188]);
         assert!(uncompressed.is_ok());
 
-        let compressed = PublicKey::from_slice(&s, &[3, 23, 183, 225, 206, 31, 159, 148, 195, 42, 67, 115, 146, 41, 248, 140, 11, 3, 51, 41, 111, 180, 110, 143, 114, 134, 88, 73, 198, 174, 52, 184, 78]);
+        let compressed = PublicKey::from_slice(&[3, 23, 183, 225, 206, 31, 159, 148, 195, 42, 67, 115, 146, 41, 248, 140, 11, 3, 51, 41, 111, 180, 110, 143, 114, 134, 88, 73, 198, 174, 52, 184, 78]);
         assert!(compressed.is_ok());
     }
 
@@ -383,32 +1884,74 @@ mod test {
         let s = Secp256k1::new();
 
         let (sk1, pk1) = s.generate_keypair(&mut thread_rng());
-        assert_eq!(SecretKey::from_slice(&s, &sk1[..]), Ok(sk1));
-        assert_eq!(PublicKey::from_slice(&s, &pk1.serialize()[..]), Ok(pk1));
-        assert_eq!(PublicKey::from_slice(&s, &pk1.serialize_uncompressed()[..]), Ok(pk1));
+        assert_eq!(SecretKey::from_slice(&sk1.secret_bytes()[..]), Ok(sk1));
+        assert_eq!(PublicKey::from_slice(&pk1.serialize()[..]), Ok(pk1));
+        assert_eq!(PublicKey::from_slice(&pk1.serialize_uncompressed()[..]), Ok(pk1));
     }
 
     #[test]
     fn invalid_secret_key() {
-        let s = Secp256k1::new();
         // Zero
-        assert_eq!(SecretKey::from_slice(&s, &[0; 32]), Err(InvalidSecretKey));
+        assert_eq!(SecretKey::from_slice(&[0; 32]), Err(InvalidSecretKey));
         // -1
-        assert_eq!(SecretKey::from_slice(&s, &[0xff; 32]), Err(InvalidSecretKey));
+        assert_eq!(SecretKey::from_slice(&[0xff; 32]), Err(InvalidSecretKey));
         // Top of range
-        assert!(SecretKey::from_slice(&s,
-                                      &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        assert!(SecretKey::from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
                                         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
                                         0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B,
                                         0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x40]).is_ok());
         // One past top of range
-        assert!(SecretKey::from_slice(&s,
-                                      &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        assert!(SecretKey::from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
                                         0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
                                         0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B,
                                         0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x41]).is_err());
     }
 
+    #[test]
+    fn skey_from_u64() {
+        assert_eq!(SecretKey::from_u64(0), Err(InvalidSecretKey));
+        assert_eq!(SecretKey::from_u64(1), Ok(super::ONE_KEY));
+        let sk = SecretKey::from_u64(0x0102_0304_0506_0708).unwrap();
+        assert_eq!(sk.secret_bytes()[..24], [0; 24][..]);
+        assert_eq!(sk.secret_bytes()[24..], [1, 2, 3, 4, 5, 6, 7, 8][..]);
+        let max = SecretKey::from_u64(u64::max_value()).unwrap();
+        assert_eq!(SecretKey::from_slice(&max.secret_bytes()[..]), Ok(max));
+        assert_eq!(Scalar::from_secret_key(&max), Scalar::from_u64(u64::max_value()));
+    }
+
+    #[test]
+    fn skey_new_random() {
+        #[cfg(feature = "no-ocall")]
+        ::rng::install_test_source();
+        let sk1 = SecretKey::new_random();
+        let sk2 = SecretKey::new_random();
+        assert!(sk1 != sk2);
+        assert_eq!(SecretKey::from_slice(&sk1.secret_bytes()[..]), Ok(sk1));
+    }
+
+    #[test]
+    fn valid_secret_bytes() {
+        let mut order = constants::CURVE_ORDER;
+        assert!(!super::is_valid_secret_bytes(&[0; 32]));
+        assert!(!super::is_valid_secret_bytes(&[0xff; 32]));
+        assert!(!super::is_valid_secret_bytes(&order));
+        order[31] -= 1;
+        assert!(super::is_valid_secret_bytes(&order));
+        order[0] = 0;
+        order[15] = 0xff;
+        assert!(super::is_valid_secret_bytes(&order));
+        let mut one = [0; 32];
+        one[31] = 1;
+        assert!(super::is_valid_secret_bytes(&one));
+
+        assert_eq!(SecretKey::validate_bytes(&one), Ok(()));
+        assert_eq!(SecretKey::validate_bytes(&[0; 32]), Err(InvalidSecretKey));
+        assert_eq!(SecretKey::validate_bytes(&constants::CURVE_ORDER), Err(InvalidSecretKey));
+        for data in &[one, order, [0; 32], [0xff; 32]] {
+            assert_eq!(SecretKey::validate_bytes(data).is_ok(), SecretKey::from_slice(data).is_ok());
+        }
+    }
+
     #[test]
     fn test_out_of_range() {
 
@@ -430,29 +1973,162 @@ mod test {
                 self.0 -= 1;
             }
         }
+        impl CryptoRng for SgxRngAdapter<BadRng> {}
 
         let s = Secp256k1::new();
-        s.generate_keypair(&mut BadRng(0xff));
+        s.generate_keypair(&mut SgxRngAdapter::new(BadRng(0xff)));
     }
 
+    #[cfg(feature = "ffi-bytes")]
     #[test]
-    fn test_pubkey_from_bad_slice() {
+    fn pubkey_ffi_bytes() {
         let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+
+        let bytes = pk.to_ffi_bytes();
+        assert_eq!(PublicKey::from_ffi_bytes(bytes), Ok(pk));
+
+        assert_eq!(PublicKey::from_ffi_bytes([0; 64]), Err(InvalidPublicKey));
+        let mut off_curve = bytes;
+        off_curve[40] ^= 1;
+        assert_eq!(PublicKey::from_ffi_bytes(off_curve), Err(PublicKeyNotOnCurve));
+    }
+
+    #[test]
+    fn test_pubkey_from_bad_slice() {
         // Bad sizes
-        assert_eq!(PublicKey::from_slice(&s, &[0; constants::PUBLIC_KEY_SIZE - 1]),
-                   Err(InvalidPublicKey));
-        assert_eq!(PublicKey::from_slice(&s, &[0; constants::PUBLIC_KEY_SIZE + 1]),
-                   Err(InvalidPublicKey));
-        assert_eq!(PublicKey::from_slice(&s, &[0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE - 1]),
-                   Err(InvalidPublicKey));
-        assert_eq!(PublicKey::from_slice(&s, &[0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE + 1]),
-                   Err(InvalidPublicKey));
+        assert_eq!(PublicKey::from_slice(&[0; constants::PUBLIC_KEY_SIZE - 1]),
+                   Err(InvalidPublicKeyLength));
+        assert_eq!(PublicKey::from_slice(&[0; constants::PUBLIC_KEY_SIZE + 1]),
+                   Err(InvalidPublicKeyLength));
+        assert_eq!(PublicKey::from_slice(&[0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE - 1]),
+                   Err(InvalidPublicKeyLength));
+        assert_eq!(PublicKey::from_slice(&[0; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE + 1]),
+                   Err(InvalidPublicKeyLength));
 
         // Bad parse
-        assert_eq!(PublicKey::from_slice(&s, &[0xff; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE]),
-                   Err(InvalidPublicKey));
-        assert_eq!(PublicKey::from_slice(&s, &[0x55; constants::PUBLIC_KEY_SIZE]),
-                   Err(InvalidPublicKey));
+        assert_eq!(PublicKey::from_slice(&[0xff; constants::UNCOMPRESSED_PUBLIC_KEY_SIZE]),
+                   Err(InvalidPublicKeyPrefix));
+        assert_eq!(PublicKey::from_slice(&[0x55; constants::PUBLIC_KEY_SIZE]),
+                   Err(InvalidPublicKeyPrefix));
+    }
+
+    #[test]
+    fn pubkey_parse_errors() {
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let compressed = pk.serialize();
+        let uncompressed = pk.serialize_uncompressed();
+
+        // A compressed prefix on an uncompressed key and the other way round
+        let mut data = uncompressed;
+        data[0] = compressed[0];
+        assert_eq!(PublicKey::from_slice(&data), Err(InvalidPublicKeyPrefix));
+        let mut data = compressed;
+        data[0] = 0x04;
+        assert_eq!(PublicKey::from_slice(&data), Err(InvalidPublicKeyPrefix));
+
+        // Hybrid keys whose prefix contradicts the parity of y
+        let mut data = uncompressed;
+        data[0] = 0x07 ^ (uncompressed[64] & 1);
+        assert_eq!(PublicKey::from_slice(&data), Err(InvalidPublicKeyPrefix));
+        data[64] ^= 1;
+        assert_eq!(PublicKey::from_slice(&data), Err(PublicKeyNotOnCurve));
+
+        // A byte too many, e.g. a newline read along with the key
+        let mut data = compressed.to_vec();
+        data.push(b'\n');
+        assert_eq!(PublicKey::from_slice(&data), Err(InvalidPublicKeyLength));
+
+        // y not matching x, x not in the field
+        let mut data = uncompressed;
+        data[64] ^= 1;
+        assert_eq!(PublicKey::from_slice(&data), Err(PublicKeyNotOnCurve));
+        let mut data = compressed;
+        data[1..].copy_from_slice(&constants::FIELD_SIZE);
+        assert_eq!(PublicKey::from_slice(&data), Err(PublicKeyNotOnCurve));
+        assert_eq!(PublicKeyNotOnCurve.to_string(), "secp: public key is not on the curve");
+    }
+
+    #[test]
+    fn pubkey_from_slice_with_formats() {
+        use super::AllowedFormats;
+
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let compressed = pk.serialize();
+        let uncompressed = pk.serialize_uncompressed();
+        let mut hybrid = uncompressed;
+        hybrid[0] = 0x06 | (uncompressed[64] & 1);
+
+        let all = AllowedFormats::ALL;
+        let strict = AllowedFormats::COMPRESSED | AllowedFormats::UNCOMPRESSED;
+        assert_eq!(strict | AllowedFormats::HYBRID, all);
+        assert!(all.contains(strict) && !strict.contains(all));
+        for &(formats, ok) in &[(all, [true, true, true]),
+                                (strict, [true, true, false]),
+                                (AllowedFormats::COMPRESSED, [true, false, false]),
+                                (AllowedFormats::HYBRID, [false, false, true])] {
+            for (data, &ok) in [&compressed[..], &uncompressed[..], &hybrid[..]].iter().zip(ok.iter()) {
+                let expected = if ok { Ok(pk) } else { Err(InvalidPublicKey) };
+                assert_eq!(PublicKey::from_slice_with_formats(data, formats), expected);
+                assert_eq!(PublicKey::from_slice(data), Ok(pk));
+            }
+        }
+
+        // Allowed formats are still checked like `from_slice`
+        let mut wrong_parity = hybrid;
+        wrong_parity[0] ^= 1;
+        assert_eq!(PublicKey::from_slice_with_formats(&wrong_parity, all), Err(InvalidPublicKeyPrefix));
+        assert_eq!(PublicKey::from_slice_with_formats(&[0x55; 33], all), Err(InvalidPublicKeyPrefix));
+        assert_eq!(PublicKey::from_slice_with_formats(&compressed[..32], all), Err(InvalidPublicKeyLength));
+        assert_eq!(PublicKey::from_slice_with_formats(&[], all), Err(InvalidPublicKeyLength));
+    }
+
+    #[test]
+    fn pubkey_serialize_format() {
+        use super::PublicKeyFormat::{Compressed, Uncompressed};
+
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(pk.serialize_format(Compressed), pk.serialize().to_vec());
+        assert_eq!(pk.serialize_format(Uncompressed), pk.serialize_uncompressed().to_vec());
+        assert_eq!(pk.serialize_vec(true), pk.serialize_format(Compressed));
+        assert_eq!(pk.serialize_vec(false), pk.serialize_format(Uncompressed));
+        assert_eq!((Compressed.serialized_len(), Uncompressed.serialized_len()), (33, 65));
+
+        // Into a larger buffer, leaving the rest alone
+        let mut buf = [0xaa; 70];
+        assert_eq!(pk.serialize_into(Compressed, &mut buf), 33);
+        assert_eq!(buf[..33], pk.serialize()[..]);
+        assert_eq!(buf[33..], [0xaa; 37][..]);
+        assert_eq!(pk.serialize_into(Uncompressed, &mut buf), 65);
+        assert_eq!(PublicKey::from_slice(&buf[..65]), Ok(pk));
+
+        // Into arrays of a format's length
+        let mut compressed = [0; 33];
+        let mut uncompressed = [0; 65];
+        pk.serialize_to(&mut compressed);
+        pk.serialize_to(&mut uncompressed);
+        assert_eq!(compressed, pk.serialize());
+        assert_eq!(uncompressed[..], pk.serialize_uncompressed()[..]);
+
+        // Into writers
+        let mut packet = vec![0xaa];
+        pk.write_serialized(Compressed, &mut packet).unwrap();
+        pk.write_serialized(Uncompressed, &mut packet).unwrap();
+        assert_eq!(packet[1..34], compressed[..]);
+        assert_eq!(packet[34..], uncompressed[..]);
+        let mut short = &mut [0; 64][..];
+        assert!(pk.write_serialized(Uncompressed, &mut short).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn pubkey_serialize_into_short() {
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        pk.serialize_into(super::PublicKeyFormat::Uncompressed, &mut [0; 64]);
     }
 
     #[test]
@@ -464,12 +2140,15 @@ mod test {
                 self.0
             }
         }
+        impl CryptoRng for SgxRngAdapter<DumbRng> {}
 
         let s = Secp256k1::new();
-        let (sk, _) = s.generate_keypair(&mut DumbRng(0));
+        let (sk, _) = s.generate_keypair(&mut SgxRngAdapter::new(DumbRng(0)));
 
-        assert_eq!(&format!("{:?}", sk),
-                   "SecretKey(0200000001000000040000000300000006000000050000000800000007000000)");
+        assert_eq!(&format!("{:?}", sk), "SecretKey(<redacted>)");
+        #[cfg(not(feature = "no-secret-export"))]
+        assert_eq!(&format!("{:?}", sk.display_secret()),
+                   "DisplaySecret(\"0200000001000000040000000300000006000000050000000800000007000000\")");
     }
 
     #[test]
@@ -482,11 +2161,13 @@ mod test {
         ];
 
         let s = Secp256k1::signing_only();
-        let sk = SecretKey::from_slice(&s, &SK_BYTES).expect("sk");
+        let sk = SecretKey::from_slice(&SK_BYTES).expect("sk");
         let pk = PublicKey::from_secret_key(&s, &sk);
 
+        assert_eq!(sk.to_string(), "<redacted>");
+        #[cfg(not(feature = "no-secret-export"))]
         assert_eq!(
-            sk.to_string(),
+            sk.display_secret().to_string(),
             "01010101010101010001020304050607ffff0000ffff00006363636363636363"
         );
         assert_eq!(
@@ -504,9 +2185,10 @@ mod test {
                 self.0
             }
         }
+        impl CryptoRng for SgxRngAdapter<DumbRng> {}
 
         let s = Secp256k1::new();
-        let (_, pk1) = s.generate_keypair(&mut DumbRng(0));
+        let (_, pk1) = s.generate_keypair(&mut SgxRngAdapter::new(DumbRng(0)));
         assert_eq!(&pk1.serialize_uncompressed()[..],
                    &[4, 149, 16, 196, 140, 38, 92, 239, 179, 65, 59, 224, 230, 183, 91, 238, 240, 46, 186, 252, 175, 102, 52, 249, 98, 178, 123, 72, 50, 171, 196, 254, 236, 1, 189, 143, 242, 227, 16, 87, 247, 183, 162, 68, 237, 140, 92, 205, 151, 129, 166, 58, 111, 96, 123, 64, 180, 147, 51, 12, 209, 89, 236, 213, 206][..]);
         assert_eq!(&pk1.serialize()[..],
@@ -521,13 +2203,13 @@ mod test {
         let (mut sk2, mut pk2) = s.generate_keypair(&mut thread_rng());
 
         assert_eq!(PublicKey::from_secret_key(&s, &sk1), pk1);
-        assert!(sk1.add_assign(&s, &sk2).is_ok());
-        assert!(pk1.add_exp_assign(&s, &sk2).is_ok());
+        assert!(sk1.add_assign(&s, &Scalar::from_secret_key(&sk2)).is_ok());
+        assert!(pk1.add_exp_assign(&s, &Scalar::from_secret_key(&sk2)).is_ok());
         assert_eq!(PublicKey::from_secret_key(&s, &sk1), pk1);
 
         assert_eq!(PublicKey::from_secret_key(&s, &sk2), pk2);
-        assert!(sk2.add_assign(&s, &sk1).is_ok());
-        assert!(pk2.add_exp_assign(&s, &sk1).is_ok());
+        assert!(sk2.add_assign(&s, &Scalar::from_secret_key(&sk1)).is_ok());
+        assert!(pk2.add_exp_assign(&s, &Scalar::from_secret_key(&sk1)).is_ok());
         assert_eq!(PublicKey::from_secret_key(&s, &sk2), pk2);
     }
 
@@ -539,16 +2221,155 @@ mod test {
         let (mut sk2, mut pk2) = s.generate_keypair(&mut thread_rng());
 
         assert_eq!(PublicKey::from_secret_key(&s, &sk1), pk1);
-        assert!(sk1.mul_assign(&s, &sk2).is_ok());
-        assert!(pk1.mul_assign(&s, &sk2).is_ok());
+        assert!(sk1.mul_assign(&s, &Scalar::from_secret_key(&sk2)).is_ok());
+        assert!(pk1.mul_assign(&s, &Scalar::from_secret_key(&sk2)).is_ok());
         assert_eq!(PublicKey::from_secret_key(&s, &sk1), pk1);
 
         assert_eq!(PublicKey::from_secret_key(&s, &sk2), pk2);
-        assert!(sk2.mul_assign(&s, &sk1).is_ok());
-        assert!(pk2.mul_assign(&s, &sk1).is_ok());
+        assert!(sk2.mul_assign(&s, &Scalar::from_secret_key(&sk1)).is_ok());
+        assert!(pk2.mul_assign(&s, &Scalar::from_secret_key(&sk1)).is_ok());
         assert_eq!(PublicKey::from_secret_key(&s, &sk2), pk2);
     }
 
+    #[test]
+    fn test_inversion() {
+        let s = Secp256k1::new();
+
+        let mut sk = super::ONE_KEY;
+        sk.inv_assign(&s);
+        assert_eq!(sk, super::ONE_KEY);
+
+        for _ in 0..16 {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            let mut inv = sk;
+            inv.inv_assign(&s);
+            assert_eq!(Scalar::from_secret_key(&inv), Scalar::from_secret_key(&sk).invert().unwrap());
+
+            // k^-1 k = 1, and k^-1 (k G) = G
+            let mut one = inv;
+            assert!(one.mul_assign(&s, &Scalar::from_secret_key(&sk)).is_ok());
+            assert_eq!(one, super::ONE_KEY);
+            let mut g = pk;
+            assert!(g.mul_assign(&s, &Scalar::from_secret_key(&inv)).is_ok());
+            assert_eq!(g, PublicKey::from_secret_key(&s, &super::ONE_KEY));
+
+            inv.inv_assign(&s);
+            assert_eq!(inv, sk);
+        }
+    }
+
+    #[test]
+    fn test_tweak_batch() {
+        let s = Secp256k1::new();
+        let tweak = Scalar::from_secret_key(&s.generate_keypair(&mut thread_rng()).0);
+
+        // Spans several of the C library's 64-key chunks
+        let mut sks: Vec<SecretKey> = (0..150).map(|_| s.generate_keypair(&mut thread_rng()).0).collect();
+        let mut pks: Vec<PublicKey> = sks.iter().map(|sk| PublicKey::from_secret_key(&s, sk)).collect();
+        let mut expected = pks.clone();
+        for pk in expected.iter_mut() {
+            pk.add_exp_assign(&s, &tweak).unwrap();
+        }
+
+        assert_eq!(SecretKey::add_tweak_batch(&s, &mut sks, &tweak), Ok(()));
+        assert_eq!(PublicKey::add_exp_tweak_batch(&s, &mut pks, &tweak), Ok(()));
+        assert_eq!(pks, expected);
+        for (sk, pk) in sks.iter().zip(pks.iter()) {
+            assert_eq!(PublicKey::from_secret_key(&s, sk), *pk);
+        }
+
+        // Zero and empty batches are no-ops
+        assert_eq!(PublicKey::add_exp_tweak_batch(&s, &mut pks, &Scalar::ZERO), Ok(()));
+        assert_eq!(pks, expected);
+        assert_eq!(PublicKey::add_exp_tweak_batch(&s, &mut [], &tweak), Ok(()));
+        assert_eq!(SecretKey::add_tweak_batch(&s, &mut [], &tweak), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_tweak_batch() {
+        let s = Secp256k1::new();
+        let mut sks: Vec<SecretKey> = (0..100).map(|_| s.generate_keypair(&mut thread_rng()).0).collect();
+        let mut pks: Vec<PublicKey> = sks.iter().map(|sk| PublicKey::from_secret_key(&s, sk)).collect();
+        let (sks_before, pks_before) = (sks.clone(), pks.clone());
+
+        // Key 70 plus the tweak is zero, so nothing may change
+        let tweak = Scalar::from_secret_key(&sks[70]).negate();
+        assert_eq!(SecretKey::add_tweak_batch(&s, &mut sks, &tweak), Err(InvalidTweak));
+        assert_eq!(PublicKey::add_exp_tweak_batch(&s, &mut pks, &tweak), Err(PointAtInfinity));
+        assert_eq!(sks, sks_before);
+        assert_eq!(pks, pks_before);
+    }
+
+    #[test]
+    fn test_invalid_tweak() {
+        let s = Secp256k1::new();
+
+        // 1 + (n - 1) = 0, which is not a valid secret key
+        let minus_one = Scalar::ONE.negate();
+
+        let mut sk = super::ONE_KEY;
+        assert_eq!(sk.add_assign(&s, &minus_one), Err(InvalidTweak));
+
+        let mut pk = PublicKey::from_secret_key(&s, &super::ONE_KEY);
+        assert_eq!(pk.add_exp_assign(&s, &minus_one), Err(PointAtInfinity));
+
+        // A zero tweak is a no-op for addition but invalid for multiplication
+        let mut sk = super::ONE_KEY;
+        assert_eq!(sk.add_assign(&s, &Scalar::ZERO), Ok(()));
+        assert_eq!(sk, super::ONE_KEY);
+        assert_eq!(sk.mul_assign(&s, &Scalar::ZERO), Err(InvalidTweak));
+
+        let mut pk = PublicKey::from_secret_key(&s, &super::ONE_KEY);
+        assert_eq!(pk.mul_assign(&s, &Scalar::ZERO), Err(PointAtInfinity));
+    }
+
+    #[test]
+    fn pubkey_mul_tweak_generator_add() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+
+        for _ in 0..16 {
+            let (sk, pk) = s.generate_keypair(&mut rng);
+            let a = Scalar::from_secret_key(&SecretKey::new(&s, &mut rng));
+            let b = Scalar::from_secret_key(&SecretKey::new(&s, &mut rng));
+
+            let mut expected = pk;
+            expected.add_exp_assign(&s, &b).unwrap();
+            assert_eq!(pk.add_exp(&s, &b), Ok(expected));
+
+            let mut expected = pk;
+            expected.mul_assign(&s, &a).unwrap();
+            expected.add_exp_assign(&s, &b).unwrap();
+            assert_eq!(pk.mul_tweak_generator_add(&s, &a, &b), Ok(expected));
+            // Either term may be zero
+            assert_eq!(pk.mul_tweak_generator_add(&s, &Scalar::ONE, &Scalar::ZERO), Ok(pk));
+            assert_eq!(pk.mul_tweak_generator_add(&s, &Scalar::ZERO, &Scalar::from_secret_key(&sk)), Ok(pk));
+
+            // a * (sk * G) - a * sk * G is the point at infinity
+            let minus_a_sk = a.mul(&Scalar::from_secret_key(&sk)).negate();
+            assert_eq!(pk.mul_tweak_generator_add(&s, &a, &minus_a_sk), Err(PointAtInfinity));
+            assert_eq!(pk.add_exp(&s, &Scalar::from_secret_key(&sk).negate()), Err(PointAtInfinity));
+        }
+        assert_eq!(PublicKey::from_secret_key(&s, &super::ONE_KEY)
+                       .mul_tweak_generator_add(&s, &Scalar::ZERO, &Scalar::ZERO), Err(PointAtInfinity));
+    }
+
+    #[test]
+    fn sign_ecdsa_and_verify() {
+        use super::super::Message;
+
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[0x55; 32]).unwrap();
+
+        let sig = sk.sign_ecdsa(&msg);
+        assert_eq!(sig, s.sign(&msg, &sk));
+        assert_eq!(pk.verify(&msg, &sig), Ok(()));
+
+        let other = Message::from_slice(&[0x56; 32]).unwrap();
+        assert!(pk.verify(&other, &sig).is_err());
+    }
+
     #[test]
     fn pubkey_hash() {
         use std::collections::hash_map::DefaultHasher;
@@ -577,15 +2398,12 @@ mod test {
     fn pubkey_combine() {
         let s = Secp256k1::without_caps();
         let compressed1 = PublicKey::from_slice(
-            &s,
             &hex!("0241cc121c419921942add6db6482fb36243faf83317c866d2a28d8c6d7089f7ba"),
         ).unwrap();
         let compressed2 = PublicKey::from_slice(
-            &s,
             &hex!("02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443"),
         ).unwrap();
         let exp_sum = PublicKey::from_slice(
-            &s,
             &hex!("0384526253c27c7aef56c7b71a5cd25bebb66dddda437826defc5b2568bde81f07"),
         ).unwrap();
 
@@ -595,18 +2413,117 @@ mod test {
         assert!(sum2.is_ok());
         assert_eq!(sum1, sum2);
         assert_eq!(sum1.unwrap(), exp_sum);
+
+        assert_eq!(compressed1.combine(&s, &compressed1.negate()), Err(PointAtInfinity));
     }
 
     #[test]
-    fn pubkey_equal() {
+    fn pubkey_random() {
+        let mut keys: Vec<PublicKey> = (0..64).map(|_| PublicKey::random(&mut thread_rng())).collect();
+        assert!(keys.iter().all(PublicKey::is_valid));
+        // Both parities, with overwhelming probability
+        assert!(keys.iter().any(|pk| pk.serialize()[0] == 0x02));
+        assert!(keys.iter().any(|pk| pk.serialize()[0] == 0x03));
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 64);
+    }
+
+    #[test]
+    fn pubkey_is_valid() {
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        assert!(pk.is_valid());
+
+        // As if received through the raw 64-byte representation
+        let mut raw = [0; 64];
+        raw.copy_from_slice(&pk.0[..]);
+        assert!(PublicKey::from(unsafe { ffi::PublicKey::from_array_unchecked(raw) }).is_valid());
+        assert!(!PublicKey::from(ffi::PublicKey::new()).is_valid());
+        raw[40] ^= 1;
+        assert!(!PublicKey::from(unsafe { ffi::PublicKey::from_array_unchecked(raw) }).is_valid());
+    }
+
+    #[test]
+    fn pubkey_unnormalized_is_invalid() {
+        // The point with x = 1, for which x + p still fits in the 256 bits
+        // the internal representation stores x in
+        let mut compressed = [0; constants::PUBLIC_KEY_SIZE];
+        compressed[0] = 0x02;
+        compressed[constants::PUBLIC_KEY_SIZE - 1] = 1;
+        let pk = PublicKey::from_slice(&compressed).unwrap();
+
+        let mut raw = [0; 64];
+        raw.copy_from_slice(&pk.0[..]);
+        assert_eq!(raw[0], 1);
+        assert!(raw[1..32].iter().all(|&b| b == 0));
+        // x is stored little-endian; add the field size to it
+        let mut carry = 0u16;
+        for i in 0..32 {
+            let sum = raw[i] as u16 + constants::FIELD_SIZE[31 - i] as u16 + carry;
+            raw[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(carry, 0);
+
+        let forged = PublicKey::from(unsafe { ffi::PublicKey::from_array_unchecked(raw) });
+        assert_eq!(forged, pk);
+        assert!(!forged.eq_fast(&pk));
+        assert_eq!(forged.check(), Err(InvalidPublicKey));
+        assert!(!forged.is_valid());
+        #[cfg(feature = "ffi-bytes")]
+        assert_eq!(PublicKey::from_ffi_bytes(raw), Err(InvalidPublicKey));
+    }
+
+    #[test]
+    fn pubkey_msm() {
+        let s = Secp256k1::new();
+
+        // Sizes on both sides of the Strauss/Pippenger threshold
+        for &n in [1, 2, 10, 100].iter() {
+            let terms: Vec<_> = (0..n).map(|_| {
+                let (sk, _) = s.generate_keypair(&mut thread_rng());
+                let (_, pk) = s.generate_keypair(&mut thread_rng());
+                (Scalar::from_secret_key(&sk), pk)
+            }).collect();
+
+            let mut expected: Option<PublicKey> = None;
+            for &(ref scalar, pk) in terms.iter() {
+                let mut term = pk;
+                term.mul_assign(&s, scalar).unwrap();
+                expected = Some(match expected {
+                    Some(sum) => sum.combine(&s, &term).unwrap(),
+                    None => term,
+                });
+            }
+            let expected = expected.unwrap();
+
+            assert_eq!(PublicKey::msm(&s, &terms, 1 << 20), Ok(expected));
+            // A small scratch space forces several batches
+            assert_eq!(PublicKey::msm(&s, &terms, 8192), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn pubkey_msm_errors() {
         let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let terms = [(Scalar::ONE, pk), (Scalar::ONE.negate(), pk)];
+
+        assert_eq!(PublicKey::msm(&s, &[], 8192), Err(PointAtInfinity));
+        assert_eq!(PublicKey::msm(&s, &terms, 8192), Err(PointAtInfinity));
+        assert_eq!(PublicKey::msm(&s, &terms[..1], 8192), Ok(pk));
+        assert_eq!(PublicKey::msm(&s, &[(Scalar::ZERO, pk)], 8192), Err(PointAtInfinity));
+        assert_eq!(PublicKey::msm(&s, &terms[..1], 0), Err(NotEnoughMemory));
+    }
+
+    #[test]
+    fn pubkey_equal() {
         let pk1 = PublicKey::from_slice(
-            &s,
             &hex!("0241cc121c419921942add6db6482fb36243faf83317c866d2a28d8c6d7089f7ba"),
         ).unwrap();
         let pk2 = pk1.clone();
         let pk3 = PublicKey::from_slice(
-            &s,
             &hex!("02e6642fd69bd211f93f7f1f36ca51a26a5290eb2dd1b0d8279a87bb0d480c8443"),
         ).unwrap();
 
@@ -616,16 +2533,194 @@ mod test {
         assert!(!(pk2 < pk1));
         assert!(!(pk1 < pk2));
 
-        assert!(pk3 < pk1);
-        assert!(pk1 > pk3);
-        assert!(pk3 <= pk1);
-        assert!(pk1 >= pk3);
+        assert!(pk1 < pk3);
+        assert!(pk3 > pk1);
+        assert!(pk1 <= pk3);
+        assert!(pk3 >= pk1);
+    }
+
+    #[test]
+    fn generator_constants() {
+        let s = Secp256k1::new();
+        let g = PublicKey::from_scalar_mul_g(&s, &Scalar::ONE).unwrap();
+        assert_eq!(&g.serialize()[..], &constants::GENERATOR_G_COMPRESSED[..]);
+        assert_eq!(&g.serialize_uncompressed()[..], &constants::GENERATOR_G[..]);
+        assert_eq!(&constants::GENERATOR_G[1..33], &constants::GENERATOR_X[..]);
+        assert_eq!(&constants::GENERATOR_G[33..], &constants::GENERATOR_Y[..]);
+        assert_eq!(PublicKey::from_slice(&constants::GENERATOR_G_COMPRESSED), Ok(g));
+
+        assert_eq!(PublicKey::from_scalar_mul_g(&s, &Scalar::ZERO), Err(PointAtInfinity));
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(PublicKey::from_scalar_mul_g(&s, &Scalar::from_secret_key(&sk)), Ok(pk));
+    }
+
+    #[test]
+    fn pubkey_coordinates() {
+        let s = Secp256k1::new();
+        let g = PublicKey::from_scalar_mul_g(&s, &Scalar::ONE).unwrap();
+        assert_eq!(g.x_coordinate(), constants::GENERATOR_X);
+        assert_eq!(g.y_coordinate(), constants::GENERATOR_Y);
+        assert_eq!(PublicKey::from_coordinates(&constants::GENERATOR_X, &constants::GENERATOR_Y),
+                   Ok(g));
+
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(PublicKey::from_coordinates(&pk.x_coordinate(), &pk.y_coordinate()), Ok(pk));
+
+        let mut bad_y = constants::GENERATOR_Y;
+        bad_y[31] ^= 1;
+        assert_eq!(PublicKey::from_coordinates(&constants::GENERATOR_X, &bad_y),
+                   Err(PublicKeyNotOnCurve));
+        assert_eq!(PublicKey::from_coordinates(&constants::FIELD_SIZE, &constants::GENERATOR_Y),
+                   Err(PublicKeyNotOnCurve));
+    }
+
+    #[test]
+    fn pubkey_sort() {
+        // BIP67 test vector 1
+        let mut keys = [
+            PublicKey::from_slice(
+                &hex!("02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8"),
+            ).unwrap(),
+            PublicKey::from_slice(
+                &hex!("02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f"),
+            ).unwrap(),
+        ];
+        let expected = [keys[1], keys[0]];
+        PublicKey::sort(&mut keys);
+        assert_eq!(keys, expected);
+
+        let s = Secp256k1::new();
+        let mut keys = [PublicKey::from_secret_key(&s, &SecretKey::from_slice(&[1; 32]).unwrap()); 8];
+        for key in keys.iter_mut() {
+            *key = s.generate_keypair(&mut thread_rng()).1;
+        }
+        PublicKey::sort(&mut keys);
+        for pair in keys.windows(2) {
+            assert!(pair[0].serialize()[..] <= pair[1].serialize()[..]);
+        }
+    }
+
+    #[test]
+    fn pubkey_cmp_serialized() {
+        use std::cmp::Ordering;
+
+        let s = Secp256k1::new();
+        for _ in 0..16 {
+            let (_, pk1) = s.generate_keypair(&mut thread_rng());
+            let (_, pk2) = s.generate_keypair(&mut thread_rng());
+            assert_eq!(pk1.cmp_serialized(&pk2), pk1.serialize()[..].cmp(&pk2.serialize()[..]));
+            assert_eq!(pk1.cmp_serialized(&pk1), Ordering::Equal);
+            assert!(pk1.eq_fast(&pk1));
+            assert_eq!(pk1.eq_fast(&pk2), pk1 == pk2);
+            // The internal form doesn't depend on how the key was made
+            let parsed = PublicKey::from_slice(&pk1.serialize_uncompressed()).unwrap();
+            assert!(pk1.eq_fast(&parsed));
+        }
+    }
+
+    #[test]
+    fn bip67_multisig_script() {
+        // BIP67 test vector 1
+        let keys = [
+            PublicKey::from_slice(
+                &hex!("02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8"),
+            ).unwrap(),
+            PublicKey::from_slice(
+                &hex!("02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f"),
+            ).unwrap(),
+        ];
+        assert_eq!(
+            PublicKey::sorted_multisig_script(2, &keys).unwrap(),
+            hex!("522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae"),
+        );
+
+        // BIP67 test vector 2
+        let sorted = [
+            hex!("02632b12f4ac5b1d1b72b2a3b508c19172de44f6f46bcee50ba33f3f9291e47ed0"),
+            hex!("027735a29bae7780a9755fae7a1c4374c656ac6a69ea9f3697fda61bb99a4f3e77"),
+            hex!("02e2cc6bd5f45edd43bebe7cb9b675f0ce9ed3efe613b177588290ad188d11b404"),
+        ];
+        let mut keys: Vec<PublicKey> = [2, 0, 1].iter().map(|&i| PublicKey::from_slice(&sorted[i]).unwrap()).collect();
+        PublicKey::sort_bip67(&mut keys);
+        for (key, expected) in keys.iter().zip(sorted.iter()) {
+            assert_eq!(key.serialize()[..], expected[..]);
+        }
+        let script = PublicKey::sorted_multisig_script(2, &keys).unwrap();
+        assert_eq!(script.len(), 3 + 3 * 34);
+        assert_eq!(script[..2], [0x52, 0x21]);
+        assert_eq!(script[script.len() - 2..], [0x53, 0xae]);
+
+        assert_eq!(PublicKey::sorted_multisig_script(0, &keys), Err(InvalidMultisig));
+        assert_eq!(PublicKey::sorted_multisig_script(4, &keys), Err(InvalidMultisig));
+        assert_eq!(PublicKey::sorted_multisig_script(1, &[keys[0]; 17]), Err(InvalidMultisig));
+        assert!(PublicKey::sorted_multisig_script(16, &[keys[0]; 16]).is_ok());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn pay_to_contract() {
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0x01; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&s, &sk);
+
+        let commitment = pk.commit(&s, b"contract").unwrap();
+        assert_eq!(&commitment.serialize()[..],
+                   &hex!("03e2b5715e2c9fa90fff581a8d84732b123759b00a86b748169fda592b1258f165")[..]);
+        let committed_sk = sk.commit(&s, b"contract").unwrap();
+        assert_eq!(&committed_sk.secret_bytes()[..],
+                   &hex!("d2a835563b3f034934de1bd96734667b23825fe18f9e3135fa04ed634a3e1d9c")[..]);
+        assert_eq!(PublicKey::from_secret_key(&s, &committed_sk), commitment);
+
+        assert!(pk.verify_commitment(&s, b"contract", &commitment));
+        assert!(!pk.verify_commitment(&s, b"contracts", &commitment));
+        assert!(!commitment.verify_commitment(&s, b"contract", &commitment));
+        let (_, other) = s.generate_keypair(&mut thread_rng());
+        assert!(!other.verify_commitment(&s, b"contract", &commitment));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn key_blinding() {
+        use super::super::Message;
+
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+
+        let alice = pk.blinding_factor(b"alice.example").unwrap();
+        let bob = pk.blinding_factor(b"bob.example").unwrap();
+        assert!(alice != bob);
+        assert_eq!(pk.blinding_factor(b"alice.example"), Ok(alice));
+        let blinded = pk.blind(&s, &alice).unwrap();
+        assert!(blinded != pk && blinded != pk.blind(&s, &bob).unwrap());
+
+        // The blinded secret key signs for the blinded public key
+        let blinded_sk = sk.blind(&s, &alice).unwrap();
+        assert_eq!(PublicKey::from_secret_key(&s, &blinded_sk), blinded);
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        assert!(s.verify(&msg, &s.sign(&msg, &blinded_sk), &blinded).is_ok());
+
+        assert_eq!(pk.blind(&s, &Scalar::ZERO), Err(PointAtInfinity));
+        assert_eq!(sk.blind(&s, &Scalar::ZERO), Err(InvalidTweak));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn message_hash() {
+        // The second takes three retries
+        assert_eq!(&PublicKey::from_message_hash(b"rust-secp256k1-sgx/test", b"abc").serialize()[..],
+                   &hex!("02b1105ac377e543d36eb63272fc9057f60abc08245037e5128656d0406229b1f8")[..]);
+        assert_eq!(&PublicKey::from_message_hash(b"rust-secp256k1-sgx/test", b"hello").serialize()[..],
+                   &hex!("02d561ca9ffea9997aab03a0c4b492eac81b8efda40ece3579b0c83beda2330810")[..]);
+        assert!(PublicKey::from_message_hash(b"rust-secp256k1-sgx/other", b"abc")
+                != PublicKey::from_message_hash(b"rust-secp256k1-sgx/test", b"abc"));
     }
 
     #[cfg(feature = "serde")]
     #[test]
     fn test_signature_serde() {
         use serde_test::{Token, assert_tokens};
+        #[cfg(feature = "no-secret-export")]
+        use serde_test::assert_de_tokens;
         static SK_BYTES: [u8; 32] = [
             1, 1, 1, 1, 1, 1, 1, 1,
             0, 1, 2, 3, 4, 5, 6, 7,
@@ -642,12 +2737,52 @@ mod test {
 
         let s = Secp256k1::new();
 
-        let sk = SecretKey::from_slice(&s, &SK_BYTES).unwrap();
+        let sk = SecretKey::from_slice(&SK_BYTES).unwrap();
         let pk = PublicKey::from_secret_key(&s, &sk);
 
+        #[cfg(not(feature = "no-secret-export"))]
         assert_tokens(&sk, &[Token::BorrowedBytes(&SK_BYTES[..])]);
+        #[cfg(feature = "no-secret-export")]
+        assert_de_tokens(&sk, &[Token::BorrowedBytes(&SK_BYTES[..])]);
         assert_tokens(&pk, &[Token::BorrowedBytes(&PK_BYTES[..])]);
     }
+
+    #[cfg(all(feature = "hashes", feature = "serde"))]
+    #[test]
+    fn test_key_id_serde() {
+        use serde_test::{Configure, Token, assert_tokens};
+        use super::KeyId;
+
+        static ID_BYTES: [u8; 20] = [
+            0x75, 0x1e, 0x76, 0xe8, 0x19, 0x91, 0x96, 0xd4, 0x54, 0x94,
+            0x1c, 0x45, 0xd1, 0xb3, 0xa3, 0x23, 0xf1, 0x43, 0x3b, 0xd6,
+        ];
+        static ID_HEX: &'static str = "751e76e8199196d454941c45d1b3a323f1433bd6";
+
+        let id = KeyId::from_bytes(ID_BYTES);
+        assert_tokens(&id.compact(), &[Token::BorrowedBytes(&ID_BYTES[..])]);
+        assert_tokens(&id.readable(), &[Token::BorrowedStr(ID_HEX)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_xonly_serde() {
+        use serde_test::{Configure, Readable, Token, assert_tokens, assert_de_tokens_error};
+
+        static XONLY_BYTES: [u8; 32] = [
+            0x79, 0xbe, 0x66, 0x7e, 0xf9, 0xdc, 0xbb, 0xac,
+            0x55, 0xa0, 0x62, 0x95, 0xce, 0x87, 0x0b, 0x07,
+            0x02, 0x9b, 0xfc, 0xdb, 0x2d, 0xce, 0x28, 0xd9,
+            0x59, 0xf2, 0x81, 0x5b, 0x16, 0xf8, 0x17, 0x98,
+        ];
+        static XONLY_HEX: &'static str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+        let xonly = XOnlyPublicKey::from_slice(&XONLY_BYTES).unwrap();
+        assert_tokens(&xonly.compact(), &[Token::BorrowedBytes(&XONLY_BYTES[..])]);
+        assert_tokens(&xonly.readable(), &[Token::BorrowedStr(XONLY_HEX)]);
+        assert_de_tokens_error::<Readable<XOnlyPublicKey>>(&[Token::BorrowedStr(&XONLY_HEX[2..])],
+                                                          "secp: malformed public key");
+    }
 }
 
 
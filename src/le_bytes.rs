@@ -0,0 +1,41 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Little-endian integers
+//! The integer encodings the crate's own serialization formats (co-signing
+//! sessions, sealed envelopes and vault files) share.
+
+/// `n` as 4 little-endian bytes
+pub(crate) fn u32_bytes(n: u32) -> [u8; 4] {
+    n.to_le_bytes()
+}
+
+/// The u32 in the first 4 bytes of `data`, little-endian
+pub(crate) fn read_u32(data: &[u8]) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[..4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// `n` as 8 little-endian bytes
+pub(crate) fn u64_bytes(n: u64) -> [u8; 8] {
+    n.to_le_bytes()
+}
+
+/// The u64 in the first 8 bytes of `data`, little-endian
+pub(crate) fn read_u64(data: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[..8]);
+    u64::from_le_bytes(bytes)
+}
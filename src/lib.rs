@@ -21,7 +21,99 @@
 //!
 //! To minimize dependencies, some functions are feature-gated. To generate
 //! random keys or to re-randomize a context object, compile with the "rand"
-//! feature. To de/serialize objects with serde, compile with "serde".
+//! feature. To de/serialize objects with serde, compile with "serde", with
+//! borsh, with "borsh", with zero-copy `rkyv`, with "rkyv", and with the
+//! SGX SDK's `sgx_serialize`, with "sgx-serialize". To derive keys from
+//! the enclave's sealing key, and to frame sealed data in versioned
+//! envelopes, compile with "sealing",
+//! and to create reports which bind a key for
+//! attestation, with "attestation". To persist vaults in protected files,
+//! compile with "protected-fs". For fuzz and property tests of code built
+//! on this library, "fuzz" provides `arbitrary` and proptest support, and
+//! to count and time signing, verification and ECDH, compile with "metrics".
+//! "debug-logging" logs context creation, rejected keys, signing and failed
+//! verifications through the `log` crate (never anything secret), for
+//! diagnosing debug enclaves; leave it off in production builds.
+//! Conversely, "production-hardening" stops secret keys from being exported
+//! from an enclave launched in debug mode, and "no-secret-export" compiles
+//! out every way of getting at a secret key's bytes (`as_ref`, indexing,
+//! `display_secret`, serialization and the DER, PEM and JWK encoders), for
+//! enclaves whose keys must never leave them.
+//! "capi" exports `extern "C"` signing, verification and key derivation
+//! functions for trusted C and C++ code, such as ECALL implementations.
+//! "bench" measures signing, verification, ECDH and key generation
+//! throughput inside the enclave, for comparing platforms.
+//! "test-vectors" exports the known-answer vectors of the self test, with
+//! helpers for checking an integration against them, and the RFC6979
+//! nonces this build derives for published test vector keys.
+//! "test-determinism" adds a seeded generator and deterministic contexts,
+//! so that integration tests can compare keys and signatures against
+//! golden files.
+//! "dudect" adds statistical tests for timing leaks of secret keys in
+//! signing, ECDH and key comparison, to run inside the enclave.
+//! "psbt" signs transaction inputs from a sighash computed outside the
+//! enclave, encoded for the witness: ECDSA for legacy and segwit v0 inputs
+//! and BIP340 for taproot, with the BIP341 key tweak for key path spends.
+//! "verify-only" compiles the crate down to parsing and verification, for
+//! enclaves which only check signatures, such as attested light clients:
+//! there is no `SecretKey`, no way to create a signing context and no code
+//! to build the signing table, and the features which need secret keys
+//! cannot be combined with it. See "Verification-only builds" in the README.
+//! "hazmat" adds the `hazmat` module, which signs with a nonce chosen by
+//! the caller for protocols such as adaptor signatures and two-party ECDSA,
+//! and detects nonce reuse; misused, it gives the secret key away.
+//! "context-allocator" makes libsecp256k1 allocate its contexts, tables
+//! and scratch memory through a `ContextAllocator` set with
+//! `allocator::set_allocator`, e.g. a locked arena zeroed on free.
+//! "no-ocall" fails the build if libsecp256k1 calls anything which would
+//! leave the enclave, and takes the entropy of functions which aren't
+//! handed a generator from an `rng::EntropySource` the caller installs. See
+//! "OCALL-free builds" in the README.
+//! "lvi-mitigation" compiles libsecp256k1 with the SGX SDK's LVI mitigation
+//! flags. Whatever the features, `build_info` reports the configuration of
+//! the build at run time, for audit logs and attestation evidence.
+//! "encrypted-keys" adds `SecretKey::to_encrypted` and `from_encrypted`, a
+//! portable format for keys encrypted under a KEK, or under a passphrase
+//! with "scrypt-kdf" or "argon2-kdf", for provisioning keys into enclaves.
+//! "bip38" adds `SecretKey::from_bip38`, which decrypts a BIP38
+//! passphrase-protected Bitcoin key inside the enclave.
+//! "hex" exports the `hex` module, the allocation-free hex encoding and
+//! decoding behind the crate's `Display` and `FromStr` impls.
+//! "transcript" adds `Transcript`, a Merlin-compatible Fiat-Shamir
+//! transcript for protocols built on the crate, which can bind enclave
+//! identities from attestation into their challenges.
+//! "ethereum" adds the `eip712` module, which hashes EIP-712 typed data
+//! into the message to sign.
+//! "proto" adds the `proto` module, fixed-size byte mirrors of the public
+//! types with checked conversions, for protobuf and gRPC services.
+//! "untrusted" adds the `untrusted` module, the request and response
+//! encoding of the common ECALLs of a signing enclave, with typed wrappers
+//! for the host application.
+//!
+//! Every public type is `Send` and `Sync`, except the signers which borrow
+//! a vault from a `RefCell`, and types generic over a caller's transport or
+//! generator, which take both from it. Nothing is mutated through a shared
+//! reference other than under a lock (`ContextPool`, the `metrics` sink),
+//! so contexts, keys and vaults can be shared between enclave threads:
+//! signing, verification and everything else which takes `&self` may run on
+//! many threads at once, while re-randomizing a context or changing a vault
+//! takes `&mut self`, and so a `Mutex` if the value is shared.
+//!
+//! The crate is `no_std`. By default ("sgx" feature) it uses `sgx_tstd` for
+//! what it needs from the standard library; with "std" instead it uses the
+//! standard library of normal targets, and with neither only `core` and
+//! `alloc`, e.g. for bare-metal TEEs. Without a standard library there is no
+//! global context, no `signer` module, no `std::error::Error` implementation
+//! and no "signature" support, and the features which need the SGX SDK
+//! ("rand", "sealing", "attestation") turn on "sgx".
+//!
+//! The "fuzztarget" feature replaces signing, verification, ECDH and key
+//! arithmetic with cheap deterministic fakes, so that fuzzers exploring
+//! code built on this library aren't slowed down by real elliptic curve
+//! math. Signatures and keys keep their types and encodings, and a fake
+//! signature only verifies for the message and key it was made with, but
+//! none of it is secure: it only builds with debug assertions (which
+//! cargo-fuzz turns on), and must never be enabled for anything but fuzzing.
 //!
 //! Where possible, the bindings use the Rust type system to ensure that
 //! API usage errors are impossible. For example, the library uses context
@@ -38,17 +130,15 @@
 //!
 //! ```rust
 //! extern crate secp256k1;
-//! # #[cfg(feature="rand")]
-//! extern crate rand;
 //!
 //! #
 //! # fn main() {
-//! # #[cfg(feature="rand")] {
-//! use rand::OsRng;
+//! # #[cfg(all(feature="rand", not(feature="no-ocall")))] {
 //! use secp256k1::{Secp256k1, Message};
+//! use secp256k1::rng::thread_rng;
 //!
 //! let secp = Secp256k1::new();
-//! let mut rng = OsRng::new().expect("OsRng");
+//! let mut rng = thread_rng();
 //! let (secret_key, public_key) = secp.generate_keypair(&mut rng);
 //! let message = Message::from_slice(&[0xab; 32]).expect("32 bytes");
 //!
@@ -66,7 +156,7 @@
 //! use self::secp256k1::{Secp256k1, Message, SecretKey, PublicKey};
 //!
 //! let secp = Secp256k1::new();
-//! let secret_key = SecretKey::from_slice(&secp, &[0xcd; 32]).expect("32 bytes, within curve order");
+//! let secret_key = SecretKey::from_slice(&[0xcd; 32]).expect("32 bytes, within curve order");
 //! let public_key = PublicKey::from_secret_key(&secp, &secret_key);
 //! let message = Message::from_slice(&[0xab; 32]).expect("32 bytes");
 //!
@@ -83,7 +173,7 @@
 //!
 //! let secp = Secp256k1::verification_only();
 //!
-//! let public_key = PublicKey::from_slice(&secp, &[
+//! let public_key = PublicKey::from_slice(&[
 //!     0x02,
 //!     0xc6, 0x6e, 0x7d, 0x89, 0x66, 0xb5, 0xc5, 0x55,
 //!     0xaf, 0x58, 0x05, 0x98, 0x9d, 0xa9, 0xfb, 0xf8,
@@ -137,41 +227,289 @@
 //#![cfg_attr(all(test, feature = "unstable"), feature(test))]
 //#[cfg(all(test, feature = "unstable"))] extern crate test;
 //#[cfg(any(test, feature = "rand"))] extern crate rand;
-//#[cfg(feature = "serde")] extern crate serde;
-//#[cfg(all(test, feature = "serde"))] extern crate serde_test;
 
 #![cfg_attr(not(target_env = "sgx"), no_std)]
 #![cfg_attr(target_env = "sgx", feature(rustc_private))]
-#[cfg(not(target_env = "sgx"))]
+// `std` is `sgx_tstd` with the "sgx" feature, the real one with "std", and
+// otherwise a facade over `core` and `alloc`
+#[cfg(all(not(target_env = "sgx"), feature = "sgx"))]
 #[macro_use]
 extern crate sgx_tstd as std;
+#[cfg(all(not(target_env = "sgx"), not(feature = "sgx"), feature = "std"))]
+#[macro_use]
+extern crate std;
+#[cfg(not(any(target_env = "sgx", feature = "sgx", feature = "std")))]
+#[macro_use]
+extern crate alloc;
+#[cfg(not(any(target_env = "sgx", feature = "sgx", feature = "std")))]
+mod std {
+    pub use core::*;
+    pub use alloc::{boxed, fmt, slice, str, string, vec};
+    #[cfg(feature = "hashes")]
+    pub use alloc::collections;
+    #[cfg(any(feature = "context-allocator", all(target_arch = "wasm32", target_os = "unknown")))]
+    pub use alloc::alloc;
+}
 #[cfg(not(target_env = "sgx"))]
-use std::prelude::v1::Vec;
+use std::vec::Vec;
 
 extern crate libc;
 
-use libc::size_t;
-use std::{error, fmt, ops, ptr};
+use types::{c_uint, size_t};
+use std::{cmp, fmt, hash, mem, ops, ptr, str};
+use std::convert::TryFrom;
+#[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+use std::error;
 #[cfg(any(test, feature = "rand"))] extern crate sgx_rand as rand;
-#[cfg(any(test, feature = "rand"))] use rand::Rng;
+#[cfg(any(test, feature = "rand", feature = "encrypted-keys"))] pub extern crate rand_core;
+#[cfg(any(test, feature = "rand"))] use rand_core::{RngCore, CryptoRng};
+#[cfg(feature = "signature")] pub extern crate signature;
+#[cfg(feature = "k256")] pub extern crate k256;
+#[cfg(feature = "serde")] pub extern crate serde;
+#[cfg(feature = "borsh")] pub extern crate borsh;
+#[cfg(feature = "rkyv")] pub extern crate rkyv;
+#[cfg(feature = "fuzz")] pub extern crate arbitrary;
+#[cfg(feature = "fuzz")] pub extern crate proptest;
+#[cfg(feature = "debug-logging")] extern crate log;
+#[cfg(feature = "encrypted-keys")] extern crate chacha20poly1305;
+#[cfg(feature = "scrypt")] extern crate scrypt;
+#[cfg(feature = "argon2-kdf")] extern crate argon2;
+#[cfg(feature = "bip38")] extern crate aes;
+#[cfg(feature = "bip38")] extern crate unicode_normalization;
+#[cfg(any(feature = "sealing", feature = "attestation", feature = "production-hardening",
+          feature = "trusted-time"))] extern crate sgx_types;
+#[cfg(any(feature = "sealing", feature = "attestation", feature = "production-hardening"))] extern crate sgx_tse;
+#[cfg(feature = "sgx-serialize")] extern crate sgx_serialize;
+#[cfg(feature = "trusted-time")] extern crate sgx_tservice;
+#[cfg(all(test, feature = "serde"))] extern crate serde_test;
+#[cfg(all(test, feature = "serde"))] extern crate bincode;
+#[cfg(all(test, feature = "serde"))] extern crate postcard;
+
+// "fuzztarget" replaces all elliptic curve operations with insecure fakes,
+// so refuse to build it the way enclaves are shipped. Fuzzers build with
+// debug assertions on, cargo-fuzz included.
+#[cfg(all(feature = "fuzztarget", not(debug_assertions)))]
+compile_error!("the \"fuzztarget\" feature fakes all cryptography and must not be used in release builds");
 
 #[macro_use]
 mod macros;
+mod cleanup;
+#[cfg(feature = "context-allocator")]
+pub mod allocator;
+pub mod attestation;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod audit;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+#[cfg_attr(not(feature = "bip38"), allow(dead_code))]
+mod base58;
+#[cfg(any(feature = "jwk", feature = "der"))]
+mod base64;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(all(feature = "capi", any(target_env = "sgx", feature = "sgx", feature = "std")))]
+pub mod capi;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod bip32;
+#[cfg(all(feature = "bip38", not(feature = "verify-only")))]
+pub mod bip38;
+#[cfg(feature = "borsh")]
+mod borsh_impls;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod blind;
+#[cfg(feature = "hashes")]
+pub mod cache;
+pub mod callback;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod certificate;
 pub mod constants;
+mod context_builder;
+mod context_export;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod commitment;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod cosign;
+#[cfg(feature = "der")]
+pub mod der;
+#[cfg(feature = "dudect")]
+pub mod dudect;
+#[cfg(not(feature = "verify-only"))]
 pub mod ecdh;
+#[cfg(feature = "ethereum")]
+pub mod eip712;
+#[cfg(all(feature = "encrypted-keys", not(feature = "verify-only")))]
+pub mod encrypted_key;
+#[cfg(feature = "sealing")]
+pub mod envelope;
+#[cfg_attr(not(feature = "expose-ffi"), doc(hidden))]
 pub mod ffi;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "hashes")]
+pub mod generator;
+#[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+pub mod global;
+#[cfg(feature = "production-hardening")]
+pub mod hardening;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod halfagg;
+#[cfg(feature = "hashes")]
+pub mod hashes;
+#[cfg(all(feature = "hazmat", not(feature = "verify-only")))]
+pub mod hazmat;
+#[cfg(feature = "hex")]
+pub mod hex;
+#[cfg(not(feature = "hex"))]
+#[allow(dead_code)]
+mod hex;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod import;
+#[cfg(feature = "jwk")]
+pub mod jwk;
+#[cfg(feature = "k256")]
+mod k256_compat;
 pub mod key;
-
+#[cfg(any(all(feature = "hashes", not(feature = "verify-only")), feature = "sealing",
+          all(any(test, feature = "protected-fs"), not(feature = "verify-only"))))]
+mod le_bytes;
+#[cfg(all(any(test, feature = "rand"), not(feature = "verify-only")))]
+pub mod masked;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod oprf;
+pub mod pool;
+pub mod prepared;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "psbt")]
+pub mod psbt;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod rangeproof;
+#[cfg(all(any(test, feature = "rand"), not(feature = "verify-only")))]
+pub mod refresh;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod ring;
+#[cfg(feature = "rkyv")]
+mod rkyv_impls;
+#[cfg(any(test, feature = "rand"))]
+pub mod rng;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod rotation;
+pub mod scalar;
+#[cfg(all(any(test, feature = "rand"), not(feature = "verify-only")))]
+mod scalar_blinding;
+#[cfg(feature = "hashes")]
+pub mod scheme;
+pub mod scratch;
+#[cfg(feature = "sealing")]
+pub mod sealing;
+pub mod self_test;
+#[cfg(feature = "serde")]
+pub mod serde_fixed;
+pub mod serialized_signature;
+#[cfg(all(any(target_env = "sgx", feature = "sgx", feature = "std"), not(feature = "verify-only")))]
+pub mod signer;
+#[cfg(feature = "sgx-serialize")]
+mod sgx_serialize_impls;
+#[cfg(all(feature = "signature", any(target_env = "sgx", feature = "sgx", feature = "std")))]
+mod signature_impls;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod silentpayments;
+#[cfg(feature = "hashes")]
+pub mod taproot;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+#[cfg(not(feature = "test-vectors"))]
+#[cfg_attr(feature = "verify-only", allow(dead_code))]
+mod test_vectors;
+#[cfg(feature = "transcript")]
+pub mod transcript;
+mod types;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod two_party;
+#[cfg(all(feature = "untrusted", not(feature = "verify-only")))]
+pub mod untrusted;
+#[cfg(not(feature = "verify-only"))]
+pub mod vault;
+pub mod watch_set;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod whitelist;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub mod wire;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm;
+
+#[cfg(not(feature = "verify-only"))]
 pub use key::SecretKey;
 pub use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+pub use key::KeyPair;
+pub use key::Parity;
+pub use key::AllowedFormats;
+pub use key::PublicKeyFormat;
+pub use key::XOnlyPublicKey;
+#[cfg(feature = "hashes")]
+pub use key::KeyId;
+#[cfg(feature = "rkyv")]
+pub use rkyv_impls::{ArchivedMessage, ArchivedPublicKey, ArchivedSignature, ArchivedXOnlyPublicKey};
+#[cfg(all(any(target_env = "sgx", feature = "sgx", feature = "std"), not(feature = "verify-only")))]
+pub use global::{with_thread_local_context, SECP256K1};
+pub use scalar::Scalar;
+pub use context_builder::ContextBuilder;
+pub use pool::ContextPool;
+pub use prepared::PreparedPublicKey;
+pub use watch_set::WatchSet;
+#[cfg(feature = "hashes")]
+pub use cache::VerificationCache;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+pub use commitment::NonceCommitment;
+#[cfg(feature = "hashes")]
+pub use generator::Generator;
+#[cfg(feature = "hashes")]
+pub use scheme::SignScheme;
+pub use scratch::ScratchSpace;
+pub use serialized_signature::SerializedSignature;
+#[cfg(feature = "transcript")]
+pub use transcript::Transcript;
 use std::marker::PhantomData;
 
+/// Compares two byte slices in time that depends only on their lengths, not on
+/// their contents
+#[inline]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    // Read back through a volatile pointer so the optimizer cannot turn the
+    // loop above into an early exit
+    unsafe { ptr::read_volatile(&diff) == 0 }
+}
+
+/// Copies `src` over `dst` if `mask` is 0xff and leaves `dst` as it is if
+/// `mask` is 0, reading and writing every byte either way
+// Unused by builds with neither the oblivious vault lookup nor range proofs
+#[allow(dead_code)]
+#[inline]
+fn constant_time_assign(dst: &mut [u8], src: &[u8], mask: u8) {
+    for (d, s) in dst.iter_mut().zip(src.iter()) {
+        *d = (*d & !mask) | (*s & mask);
+    }
+}
+
 /// A tag used for recovering the public key from a compact signature
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct RecoveryId(i32);
 
 /// An ECDSA signature
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+///
+/// Comparison and hashing use the 64-byte compact serialization, so they are
+/// independent of the library-internal representation. `Display` and
+/// `FromStr` use hex-encoded DER.
+#[derive(Copy, Clone, Debug)]
 pub struct Signature(ffi::Signature);
 
 /// An ECDSA signature with a recovery ID for pubkey recovery
@@ -197,25 +535,36 @@ impl RecoveryId {
 
 impl Signature {
     #[inline]
-    /// Converts a DER-encoded byte slice to a signature
+    /// Converts a DER-encoded byte slice to a signature. Fails with
+    /// `InvalidDerSignature` if it is not strict DER, and with
+    /// `SignatureOutOfRange` if r or s is negative or not below the curve
+    /// order.
     pub fn from_der<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<Signature, Error> {
+        Signature::from_der_with(secp.ctx, data)
+    }
+
+    fn from_der_with(ctx: *const ffi::Context, data: &[u8]) -> Result<Signature, Error> {
         let mut ret = unsafe { ffi::Signature::blank() };
 
         unsafe {
-            if ffi::secp256k1_ecdsa_signature_parse_der(secp.ctx, &mut ret,
-                                                        data.as_ptr(), data.len() as libc::size_t) == 1 {
-                Ok(Signature(ret))
-            } else {
-                Err(Error::InvalidSignature)
+            if ffi::secp256k1_ecdsa_signature_parse_der(ctx, &mut ret, data.as_ptr(), data.len() as size_t) != 1 {
+                return Err(Error::InvalidDerSignature);
             }
         }
+        // libsecp256k1 reads an integer out of range as zero, which is all
+        // that stops a strict DER signature from encoding back to itself
+        let ret = Signature(ret);
+        let (der, len) = ret.der_bytes();
+        if der[..len] == *data { Ok(ret) } else { Err(Error::SignatureOutOfRange) }
     }
 
-    /// Converts a 64-byte compact-encoded byte slice to a signature
+    /// Converts a 64-byte compact-encoded byte slice to a signature. Fails
+    /// with `InvalidSignatureLength` if it is not 64 bytes, and with
+    /// `SignatureOutOfRange` if r or s is not below the curve order.
     pub fn from_compact<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<Signature, Error> {
         let mut ret = unsafe { ffi::Signature::blank() };
         if data.len() != 64 {
-            return Err(Error::InvalidSignature)
+            return Err(Error::InvalidSignatureLength)
         }
 
         unsafe {
@@ -223,7 +572,7 @@ impl Signature {
                                                             data.as_ptr()) == 1 {
                 Ok(Signature(ret))
             } else {
-                Err(Error::InvalidSignature)
+                Err(Error::SignatureOutOfRange)
             }
         }
     }
@@ -231,15 +580,17 @@ impl Signature {
     /// Converts a "lax DER"-encoded byte slice to a signature. This is basically
     /// only useful for validating signatures in the Bitcoin blockchain from before
     /// 2016. It should never be used in new applications. This library does not
-    /// support serializing to this "format"
+    /// support serializing to this "format". Fails with `InvalidDerSignature`
+    /// if not even lax DER; r or s out of range is read as zero, as
+    /// consensus requires, so that the signature fails to verify.
     pub fn from_der_lax<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<Signature, Error> {
         unsafe {
             let mut ret = ffi::Signature::blank();
             if ffi::ecdsa_signature_parse_der_lax(secp.ctx, &mut ret,
-                                                  data.as_ptr(), data.len() as libc::size_t) == 1 {
+                                                  data.as_ptr(), data.len() as size_t) == 1 {
                 Ok(Signature(ret))
             } else {
-                Err(Error::InvalidSignature)
+                Err(Error::InvalidDerSignature)
             }
         }
     }
@@ -290,12 +641,29 @@ impl Signature {
         unsafe {
             let err = ffi::secp256k1_ecdsa_signature_serialize_der(secp.ctx, ret.as_mut_ptr(),
                                                                    &mut len, self.as_ptr());
-            debug_assert!(err == 1);
+            // Only fails for a buffer shorter than `MAX_SIGNATURE_SIZE`
+            assert!(err == 1);
             ret.set_len(len as usize);
         }
         ret
     }
 
+    /// Serializes the signature in DER format into `buf`, without allocating
+    /// or needing a context, and returns the length of the encoding, which
+    /// is at most `MAX_SIGNATURE_SIZE`. The rest of `buf` is left as it was.
+    #[inline]
+    pub fn serialize_der_into(&self, buf: &mut [u8; constants::MAX_SIGNATURE_SIZE]) -> usize {
+        let mut len: size_t = buf.len() as size_t;
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_signature_serialize_der(ffi::secp256k1_context_no_precomp,
+                                                                   buf.as_mut_ptr(), &mut len,
+                                                                   self.as_ptr());
+            // Only fails for a buffer shorter than `MAX_SIGNATURE_SIZE`
+            assert!(err == 1);
+        }
+        len as usize
+    }
+
     #[inline]
     /// Serializes the signature in compact format
     pub fn serialize_compact<C>(&self, secp: &Secp256k1<C>) -> [u8; 64] {
@@ -303,10 +671,106 @@ impl Signature {
         unsafe {
             let err = ffi::secp256k1_ecdsa_signature_serialize_compact(secp.ctx, ret.as_mut_ptr(),
                                                                        self.as_ptr());
-            debug_assert!(err == 1);
+            // Serialization never fails
+            assert!(err == 1);
+        }
+        ret
+    }
+
+    /// Serializes the signature in compact format without needing a context
+    fn compact_bytes(&self) -> [u8; constants::COMPACT_SIGNATURE_SIZE] {
+        let mut ret = [0; constants::COMPACT_SIGNATURE_SIZE];
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_signature_serialize_compact(ffi::secp256k1_context_no_precomp,
+                                                                       ret.as_mut_ptr(), self.as_ptr());
+            // Serialization never fails
+            assert!(err == 1);
         }
         ret
     }
+
+    /// Parses a compact signature without needing a context
+    fn from_compact_bytes(data: &[u8; constants::COMPACT_SIGNATURE_SIZE]) -> Result<Signature, Error> {
+        let mut ret = ffi::Signature::new();
+        unsafe {
+            if ffi::secp256k1_ecdsa_signature_parse_compact(ffi::secp256k1_context_no_precomp,
+                                                            &mut ret, data.as_ptr()) == 1 {
+                Ok(Signature(ret))
+            } else {
+                Err(Error::SignatureOutOfRange)
+            }
+        }
+    }
+
+    /// Parses a DER signature like `from_der`, without needing a context
+    pub(crate) fn from_der_bytes(data: &[u8]) -> Result<Signature, Error> {
+        Signature::from_der_with(unsafe { ffi::secp256k1_context_no_precomp }, data)
+    }
+
+    /// Serializes the signature in DER format into a stack buffer, without
+    /// needing a context, and returns the buffer and the encoding's length
+    fn der_bytes(&self) -> ([u8; constants::MAX_SIGNATURE_SIZE], usize) {
+        let mut ret = [0; constants::MAX_SIGNATURE_SIZE];
+        let len = self.serialize_der_into(&mut ret);
+        (ret, len)
+    }
+}
+
+impl PartialEq for Signature {
+    fn eq(&self, other: &Signature) -> bool {
+        self.compact_bytes()[..] == other.compact_bytes()[..]
+    }
+}
+
+impl Eq for Signature {}
+
+impl PartialOrd for Signature {
+    fn partial_cmp(&self, other: &Signature) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Signature {
+    fn cmp(&self, other: &Signature) -> cmp::Ordering {
+        self.compact_bytes()[..].cmp(&other.compact_bytes()[..])
+    }
+}
+
+impl hash::Hash for Signature {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.compact_bytes()[..].hash(state)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (der, len) = self.der_bytes();
+        hex::format(&der[..len], f)
+    }
+}
+
+/// Parses a hex-encoded DER signature, or failing that a hex-encoded
+/// 64-byte compact signature. Fails with `InvalidSignature` if the string
+/// is not hex of at most `MAX_SIGNATURE_SIZE` bytes, and otherwise like
+/// `Signature::from_der`, or `from_compact` for 64 bytes which aren't DER.
+impl str::FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Signature, Error> {
+        let mut buf = [0; constants::MAX_SIGNATURE_SIZE];
+        let len = match hex::decode_from(s, &mut buf) {
+            Some(len) => len,
+            None => return Err(Error::InvalidSignature),
+        };
+        match Signature::from_der_bytes(&buf[..len]) {
+            Err(Error::InvalidDerSignature) if len == constants::COMPACT_SIGNATURE_SIZE => {
+                let mut compact = [0; constants::COMPACT_SIGNATURE_SIZE];
+                compact.copy_from_slice(&buf[..len]);
+                Signature::from_compact_bytes(&compact)
+            }
+            res => res,
+        }
+    }
 }
 
 /// Creates a new signature from a FFI signature
@@ -317,23 +781,38 @@ impl From<ffi::Signature> for Signature {
     }
 }
 
+/// Parses a 64-byte compact signature, like `Signature::from_compact` but
+/// without a context. DER signatures need `Signature::from_der`.
+impl<'a> TryFrom<&'a [u8]> for Signature {
+    type Error = Error;
+
+    fn try_from(data: &'a [u8]) -> Result<Signature, Error> {
+        if data.len() != constants::COMPACT_SIGNATURE_SIZE {
+            return Err(Error::InvalidSignatureLength);
+        }
+        let mut compact = [0; constants::COMPACT_SIGNATURE_SIZE];
+        compact.copy_from_slice(data);
+        Signature::from_compact_bytes(&compact)
+    }
+}
+
 
 impl RecoverableSignature {
     #[inline]
     /// Converts a compact-encoded byte slice to a signature. This
     /// representation is nonstandard and defined by the libsecp256k1
-    /// library.
+    /// library. Fails like `Signature::from_compact`.
     pub fn from_compact<C>(secp: &Secp256k1<C>, data: &[u8], recid: RecoveryId) -> Result<RecoverableSignature, Error> {
         let mut ret = unsafe { ffi::RecoverableSignature::blank() };
 
         unsafe {
             if data.len() != 64 {
-                Err(Error::InvalidSignature)
+                Err(Error::InvalidSignatureLength)
             } else if ffi::secp256k1_ecdsa_recoverable_signature_parse_compact(secp.ctx, &mut ret,
                                                                                data.as_ptr(), recid.0) == 1 {
                 Ok(RecoverableSignature(ret))
             } else {
-                Err(Error::InvalidSignature)
+                Err(Error::SignatureOutOfRange)
             }
         }
     }
@@ -368,6 +847,122 @@ impl RecoverableSignature {
         }
         Signature(ret)
     }
+
+    /// Serializes the signature as `[R || S || v]`, the 65-byte layout used
+    /// by Ethereum and many wire protocols, where `v` is the recovery ID
+    /// (0 to 3). Callers who need Ethereum's legacy `v` of 27 or 28 add 27
+    /// to the last byte.
+    pub fn serialize_compact_rsv(&self) -> [u8; constants::RECOVERABLE_SIGNATURE_SIZE] {
+        let mut ret = [0; constants::RECOVERABLE_SIGNATURE_SIZE];
+        let mut recid = 0i32;
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_recoverable_signature_serialize_compact(
+                ffi::secp256k1_context_no_precomp, ret.as_mut_ptr(), &mut recid, self.as_ptr());
+            assert!(err == 1);
+        }
+        ret[constants::COMPACT_SIGNATURE_SIZE] = recid as u8;
+        ret
+    }
+
+    /// Parses a 65-byte `[R || S || v]` signature as produced by
+    /// `serialize_compact_rsv`. Besides recovery IDs 0 to 3, `v` may carry
+    /// Ethereum's legacy offset of 27. Fails with `InvalidSignatureLength`
+    /// if it is not 65 bytes, with `InvalidRecoveryId` for other values of
+    /// `v`, and with `SignatureOutOfRange` if r or s is not below the curve
+    /// order.
+    pub fn from_compact_rsv(data: &[u8]) -> Result<RecoverableSignature, Error> {
+        if data.len() != constants::RECOVERABLE_SIGNATURE_SIZE {
+            return Err(Error::InvalidSignatureLength);
+        }
+        let recid = match data[constants::COMPACT_SIGNATURE_SIZE] {
+            v @ 0..=3 => v,
+            v @ 27..=30 => v - 27,
+            _ => return Err(Error::InvalidRecoveryId),
+        };
+        let mut ret = ffi::RecoverableSignature::new();
+        unsafe {
+            if ffi::secp256k1_ecdsa_recoverable_signature_parse_compact(ffi::secp256k1_context_no_precomp,
+                                                                       &mut ret, data.as_ptr(),
+                                                                       recid as i32) == 1 {
+                Ok(RecoverableSignature(ret))
+            } else {
+                Err(Error::SignatureOutOfRange)
+            }
+        }
+    }
+    /// Serializes the signature as a compact proof `[v || R || S]`, with the
+    /// recovery ID (0 to 3) first. Together with the message this identifies
+    /// the signer; check it with `Secp256k1::verify_compact_proof`.
+    pub fn to_compact_proof(&self) -> [u8; constants::RECOVERABLE_SIGNATURE_SIZE] {
+        let rsv = self.serialize_compact_rsv();
+        let mut ret = [0; constants::RECOVERABLE_SIGNATURE_SIZE];
+        ret[0] = rsv[constants::COMPACT_SIGNATURE_SIZE];
+        ret[1..].copy_from_slice(&rsv[..constants::COMPACT_SIGNATURE_SIZE]);
+        ret
+    }
+
+    /// Parses a compact proof as produced by `to_compact_proof`, failing
+    /// like `from_compact_rsv`
+    pub fn from_compact_proof(data: &[u8]) -> Result<RecoverableSignature, Error> {
+        if data.len() != constants::RECOVERABLE_SIGNATURE_SIZE {
+            return Err(Error::InvalidSignatureLength);
+        }
+        if data[0] > 3 {
+            return Err(Error::InvalidRecoveryId);
+        }
+        let mut rsv = [0; constants::RECOVERABLE_SIGNATURE_SIZE];
+        rsv[..constants::COMPACT_SIGNATURE_SIZE].copy_from_slice(&data[1..]);
+        rsv[constants::COMPACT_SIGNATURE_SIZE] = data[0];
+        RecoverableSignature::from_compact_rsv(&rsv)
+    }
+}
+
+/// Serializes in the `[R || S || v]` layout of `serialize_compact_rsv`, as
+/// a hex string for human-readable formats and as bytes otherwise
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for RecoverableSignature {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let rsv = self.serialize_compact_rsv();
+        if s.is_human_readable() {
+            let mut buf = [0; 2 * constants::RECOVERABLE_SIGNATURE_SIZE];
+            s.serialize_str(hex::encode_to(&rsv, &mut buf))
+        } else {
+            s.serialize_bytes(&rsv)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for RecoverableSignature {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<RecoverableSignature, D::Error> {
+        use ::serde::de::Error;
+
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = RecoverableSignature;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded 65-byte recoverable signature")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<RecoverableSignature, E> {
+                    let mut buf = [0; constants::RECOVERABLE_SIGNATURE_SIZE];
+                    match hex::decode_from(v, &mut buf) {
+                        Some(constants::RECOVERABLE_SIGNATURE_SIZE) => {
+                            RecoverableSignature::from_compact_rsv(&buf).map_err(E::custom)
+                        }
+                        _ => Err(E::custom(::Error::InvalidSignature)),
+                    }
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        RecoverableSignature::from_compact_rsv(sl).map_err(D::Error::custom)
+    }
 }
 
 /// Creates a new recoverable signature from a FFI one
@@ -414,11 +1009,17 @@ impl ops::Index<ops::RangeFull> for Signature {
     }
 }
 
+/// Serializes as a hex DER string for human-readable formats and as DER
+/// bytes otherwise
 #[cfg(feature = "serde")]
 impl ::serde::Serialize for Signature {
     fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-        let secp = Secp256k1::without_caps();
-        s.serialize_bytes(&self.serialize_der(&secp))
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            let (der, len) = self.der_bytes();
+            s.serialize_bytes(&der[..len])
+        }
     }
 }
 
@@ -427,13 +1028,39 @@ impl<'de> ::serde::Deserialize<'de> for Signature {
     fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<Signature, D::Error> {
         use ::serde::de::Error;
 
-        let secp = Secp256k1::without_caps();
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = Signature;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded DER or compact signature")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<Signature, E> {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
         let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
-        Signature::from_der(&secp, sl).map_err(D::Error::custom)
+        let mut ret = unsafe { ffi::Signature::blank() };
+        unsafe {
+            if ffi::secp256k1_ecdsa_signature_parse_der(ffi::secp256k1_context_no_precomp, &mut ret,
+                                                        sl.as_ptr(), sl.len() as size_t) == 1 {
+                Ok(Signature(ret))
+            } else {
+                Err(D::Error::custom(::Error::InvalidSignature))
+            }
+        }
     }
 }
 
 /// A (hashed) message input to an ECDSA signature
+///
+/// Equality runs in constant time. `Display` and `FromStr` use hex.
 pub struct Message([u8; constants::MESSAGE_SIZE]);
 impl_array_newtype!(Message, u8, constants::MESSAGE_SIZE);
 impl_pretty_debug!(Message);
@@ -451,6 +1078,46 @@ impl Message {
             _ => Err(Error::InvalidMessage)
         }
     }
+
+    /// Like `from_slice`, but also rejects the all-zeros digest, which is
+    /// almost always a buffer that was never filled in (e.g. by forgetting
+    /// to hash) rather than a real hash output
+    #[inline]
+    pub fn from_slice_nonzero(data: &[u8]) -> Result<Message, Error> {
+        let ret = Message::from_slice(data)?;
+        if ::constant_time_eq(&ret.0, &[0; constants::MESSAGE_SIZE]) {
+            Err(Error::InvalidMessage)
+        } else {
+            Ok(ret)
+        }
+    }
+
+    /// Converts a 32-byte digest to a message. This accepts anything which
+    /// converts into a byte array, such as the `GenericArray` output of
+    /// RustCrypto `digest` implementations.
+    #[inline]
+    pub fn from_digest<D: Into<[u8; constants::MESSAGE_SIZE]>>(digest: D) -> Message {
+        Message(digest.into())
+    }
+
+    /// Compares two messages in constant time. This is what `==` does as
+    /// well; the method exists to make the intent explicit at call sites.
+    #[inline]
+    pub fn ct_eq(&self, other: &Message) -> bool {
+        ::constant_time_eq(&self.0, &other.0)
+    }
+
+    /// Constructs a message by hashing arbitrary data with SHA-256, tagged
+    /// with `hashes::MESSAGE_HASH_TAG` as in BIP340, so that it never equals
+    /// `SecretKey::from_hashed_data` of the same data. This is the right
+    /// constructor whenever the data has not been hashed already; to sign
+    /// the plain SHA-256 hash, as other ECDSA implementations do, use
+    /// `MessageDigest`. Requires compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    #[inline]
+    pub fn from_hashed_data(data: &[u8]) -> Message {
+        Message(hashes::tagged_hash(hashes::MESSAGE_HASH_TAG, data))
+    }
 }
 
 /// Creates a message from a `MESSAGE_SIZE` byte array
@@ -460,6 +1127,119 @@ impl From<[u8; constants::MESSAGE_SIZE]> for Message {
     }
 }
 
+/// Converts a `MESSAGE_SIZE`-byte slice as `Message::from_slice` does
+impl<'a> TryFrom<&'a [u8]> for Message {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(data: &'a [u8]) -> Result<Message, Error> {
+        Message::from_slice(data)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        hex::format(&self.0, f)
+    }
+}
+
+/// Parses a message from exactly `2 * MESSAGE_SIZE` hex digits
+impl str::FromStr for Message {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Message, Error> {
+        let mut ret = [0; constants::MESSAGE_SIZE];
+        match hex::decode_from(s, &mut ret) {
+            Some(constants::MESSAGE_SIZE) => Ok(Message(ret)),
+            _ => Err(Error::InvalidMessage),
+        }
+    }
+}
+
+/// Serializes as a hex string for human-readable formats and as bytes
+/// otherwise
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Message {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.collect_str(self)
+        } else {
+            s.serialize_bytes(&self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Message {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<Message, D::Error> {
+        use ::serde::de::Error;
+
+        if d.is_human_readable() {
+            struct HexVisitor;
+
+            impl<'de> ::serde::de::Visitor<'de> for HexVisitor {
+                type Value = Message;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a hex-encoded 32-byte message")
+                }
+
+                fn visit_str<E: Error>(self, v: &str) -> Result<Message, E> {
+                    v.parse().map_err(E::custom)
+                }
+            }
+
+            return d.deserialize_str(HexVisitor);
+        }
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        Message::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+/// Incrementally SHA-256 hashes data into a `Message`, so that large payloads
+/// can be signed or verified as they are streamed in rather than buffered.
+/// Requires compilation with the "hashes" feature.
+#[cfg(feature = "hashes")]
+#[derive(Clone)]
+pub struct MessageDigest(hashes::Sha256);
+
+#[cfg(feature = "hashes")]
+impl MessageDigest {
+    /// Creates a new, empty digest
+    #[inline]
+    pub fn new() -> MessageDigest {
+        MessageDigest(hashes::Sha256::new())
+    }
+
+    /// Feeds the next chunk of the payload into the digest
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.input(data);
+    }
+
+    /// Finishes hashing and returns the resulting message
+    #[inline]
+    pub fn finalize(self) -> Message {
+        Message(self.0.result())
+    }
+
+    /// Finishes hashing and signs the resulting message. Requires a
+    /// signing-capable context.
+    #[inline]
+    #[cfg(not(feature = "verify-only"))]
+    pub fn finalize_and_sign<C: Signing>(self, secp: &Secp256k1<C>, sk: &key::SecretKey) -> Signature {
+        secp.sign(&self.finalize(), sk)
+    }
+
+    /// Finishes hashing and verifies `sig` over the resulting message.
+    /// Requires a verify-capable context.
+    #[inline]
+    pub fn finalize_and_verify<C: Verification>(self, secp: &Secp256k1<C>, sig: &Signature,
+                                                pk: &key::PublicKey) -> Result<(), Error> {
+        secp.verify(&self.finalize(), sig, pk)
+    }
+}
+
 /// An ECDSA error
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
 pub enum Error {
@@ -468,34 +1248,264 @@ pub enum Error {
     /// Badly sized message ("messages" are actually fixed-sized digests; see the `MESSAGE_SIZE`
     /// constant)
     InvalidMessage,
-    /// Bad public key
+    /// Bad public key. Parsing one reports the more specific
+    /// `InvalidPublicKeyLength`, `InvalidPublicKeyPrefix` or
+    /// `PublicKeyNotOnCurve` instead.
     InvalidPublicKey,
-    /// Bad signature
+    /// Bad signature. Parsing one reports the more specific
+    /// `InvalidSignatureLength`, `InvalidDerSignature` or
+    /// `SignatureOutOfRange` instead.
     InvalidSignature,
     /// Bad secret key
     InvalidSecretKey,
     /// Bad recovery id
     InvalidRecoveryId,
+    /// Tweak is out of range or would produce an invalid key (e.g. zero or
+    /// the point at infinity)
+    InvalidTweak,
+    /// Didn't pass enough memory to the context or scratch space
+    NotEnoughMemory,
+    /// Bad parity value; must be 0 or 1
+    InvalidParityValue,
+    /// Scalar is not below the curve order
+    InvalidScalar,
+    /// Context serialization is corrupted, from an incompatible build or
+    /// lacks the requested capabilities
+    InvalidContext,
+    /// An SGX service the operation relies on, such as EGETKEY, failed
+    EnclaveService,
+    /// SGX quote is truncated or of an unknown version
+    InvalidQuote,
+    /// SGX quote does not bind the expected public key
+    KeyBindingMismatch,
+    /// No key with this handle in the vault
+    UnknownKeyHandle,
+    /// Vault key has made as many signatures as its limit allows
+    KeyExhausted,
+    /// Key certificate is malformed or of an unknown version
+    InvalidCertificate,
+    /// Key certificate is outside its validity period
+    CertificateExpired,
+    /// Key rotation record is malformed or of an unknown version
+    InvalidRotationRecord,
+    /// Co-signing session has bad signer indices, names an unknown signer,
+    /// or its serialized state is malformed
+    InvalidCoSigningSession,
+    /// Co-signing message arrived in the wrong round, or differs from one
+    /// already delivered
+    CoSigningOutOfOrder,
+    /// Co-signing nonce does not match the signer's commitment
+    NonceCommitmentMismatch,
+    /// Co-signing partial signature does not match the signer's nonce and
+    /// share key
+    InvalidPartialSignature,
+    /// Blind signer already has as many open sessions as it allows
+    TooManyBlindSessions,
+    /// No open blind signing session with this ID
+    UnknownBlindSession,
+    /// Ring is empty or does not contain the signing key
+    InvalidRing,
+    /// Multisig threshold or key count is out of range
+    InvalidMultisig,
+    /// Vault storage could not be read or written, or is corrupted
+    Storage,
+    /// Vault file was rolled back or replaced since it was last read or
+    /// written
+    VaultRollback,
+    /// Signature is not strict DER as BIP66 requires
+    NonCanonicalDer,
+    /// Signature has a high S value, which strict verification rejects
+    HighS,
+    /// Public key is neither compressed nor uncompressed, e.g. hybrid
+    NonCanonicalPublicKey,
+    /// Secret key material may not be exported from an enclave running in
+    /// debug mode
+    DebugEnclave,
+    /// Presignature doesn't exist, belongs to another key or was already
+    /// used
+    UnknownPresignature,
+    /// Nonce commitment is not 32 bytes
+    InvalidNonceCommitment,
+    /// Half-aggregated signature would hold more signatures than allowed
+    AggregateTooLarge,
+    /// Whitelist is full, its key lists differ in length, or it does not
+    /// contain the signing keys
+    InvalidWhitelist,
+    /// Value is out of the range to be proven, or the range is empty or
+    /// too wide
+    OutOfRange,
+    /// Protocol message is malformed, of another format version, or from a
+    /// sender which may not send it
+    InvalidWireMessage,
+    /// Usage report is malformed or of another format version
+    InvalidUsageReport,
+    /// Key's validity period has ended
+    KeyExpired,
+    /// Key's validity period has not started yet
+    KeyNotYetValid,
+    /// Time is needed but the clock can't tell it, e.g. for lack of
+    /// trusted time
+    TimeUnavailable,
+    /// Key's rate limit allows no use now
+    RateLimited,
+    /// Rate limit allows no uses at all
+    InvalidRateLimit,
+    /// Key needs more approvals, or valid ones, to be used
+    InsufficientApprovals,
+    /// Approval policy has no approvers to meet its threshold, or lists an
+    /// approver twice
+    InvalidApprovalPolicy,
+    /// Public key is neither 33 nor 65 bytes long
+    InvalidPublicKeyLength,
+    /// Public key's prefix byte does not go with its length, or a hybrid
+    /// key's prefix contradicts the parity of y
+    InvalidPublicKeyPrefix,
+    /// Public key's coordinates are not those of a point on the curve
+    PublicKeyNotOnCurve,
+    /// Byte is not a defined sighash type, or the type is not allowed for
+    /// the signature
+    InvalidSighashType,
+    /// Sealed data is not a well-formed envelope
+    InvalidEnvelope,
+    /// Sealed data is in an envelope version newer than this build knows
+    UnsupportedEnvelopeVersion,
+    /// A remote signer's response is not a public key or signature
+    InvalidSignerResponse,
+    /// Nonce gives a signature with a zero r or s
+    InvalidNonce,
+    /// Key identifier is not 20 bytes (40 hex digits)
+    InvalidKeyId,
+    /// Context's precomputed tables gave a wrong known answer, so they have
+    /// been corrupted since the context was built
+    ContextCorrupted,
+    /// Interpolation points are missing, or an x-coordinate is zero or
+    /// repeated
+    InvalidInterpolationPoints,
+    /// Encrypted secret key is malformed, or uses a KDF or parameters this
+    /// build doesn't accept
+    InvalidEncryptedKey,
+    /// Encrypted secret key can't be decrypted with the given KEK or
+    /// passphrase, or was tampered with
+    KeyDecryptionFailed,
+    /// String is not a BIP38 encrypted key
+    InvalidBip38Key,
+    /// Nonce counter returned a value no greater than one it returned
+    /// before
+    NonceCounterNotMonotonic,
+    /// Input is in none of the secret key formats `import` detects
+    UnknownKeyFormat,
+    /// Compact signature is not 64 bytes long (65 with a recovery id)
+    InvalidSignatureLength,
+    /// Signature is not valid DER
+    InvalidDerSignature,
+    /// Signature's r or s is negative or not below the curve order
+    SignatureOutOfRange,
+    /// Taproot control block has the wrong length
+    InvalidControlBlock,
+    /// EIP-712 value does not match the type of its field
+    InvalidTypedData,
+    /// Secret key is not the key of a published test vector
+    NotATestVector,
+    /// Result of a point operation is the point at infinity, which is not a
+    /// public key
+    PointAtInfinity,
+    /// Enclave refused or failed the request of an ECALL
+    EnclaveRefused,
+    /// Key destruction certificate is malformed or of another format version
+    InvalidDestructionCertificate,
 }
 
 // Passthrough Debug to Display, since errors should be user-visible
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        f.write_str(error::Error::description(self))
+        f.write_str(self.as_str())
     }
 }
 
+#[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
 impl error::Error for Error {
     fn cause(&self) -> Option<&error::Error> { None }
 
     fn description(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Error {
+    fn as_str(&self) -> &str {
         match *self {
             Error::IncorrectSignature => "secp: signature failed verification",
             Error::InvalidMessage => "secp: message was not 32 bytes (do you need to hash?)",
             Error::InvalidPublicKey => "secp: malformed public key",
             Error::InvalidSignature => "secp: malformed signature",
             Error::InvalidSecretKey => "secp: malformed or out-of-range secret key",
-            Error::InvalidRecoveryId => "secp: bad recovery id"
+            Error::InvalidRecoveryId => "secp: bad recovery id",
+            Error::InvalidTweak => "secp: bad tweak",
+            Error::NotEnoughMemory => "secp: not enough memory allocated",
+            Error::InvalidParityValue => "secp: parity value must be 0 or 1",
+            Error::InvalidScalar => "secp: scalar out of range",
+            Error::InvalidContext => "secp: malformed or incompatible context serialization",
+            Error::EnclaveService => "secp: an SGX service call failed",
+            Error::InvalidQuote => "secp: malformed SGX quote",
+            Error::KeyBindingMismatch => "secp: quote does not bind the expected key",
+            Error::UnknownKeyHandle => "secp: no key with this handle in the vault",
+            Error::KeyExhausted => "secp: key has reached its usage limit",
+            Error::InvalidCertificate => "secp: malformed key certificate",
+            Error::CertificateExpired => "secp: key certificate is not valid at this time",
+            Error::InvalidRotationRecord => "secp: malformed key rotation record",
+            Error::InvalidCoSigningSession => "secp: malformed co-signing session",
+            Error::CoSigningOutOfOrder => "secp: co-signing step out of order",
+            Error::NonceCommitmentMismatch => "secp: nonce does not match its commitment",
+            Error::InvalidPartialSignature => "secp: invalid partial signature",
+            Error::TooManyBlindSessions => "secp: too many open blind signing sessions",
+            Error::UnknownBlindSession => "secp: no open blind signing session with this id",
+            Error::InvalidRing => "secp: ring does not contain the signing key",
+            Error::InvalidMultisig => "secp: multisig threshold or key count out of range",
+            Error::Storage => "secp: vault storage failed",
+            Error::VaultRollback => "secp: vault file was rolled back or replaced",
+            Error::NonCanonicalDer => "secp: signature is not strict DER",
+            Error::HighS => "secp: signature has a high S value",
+            Error::NonCanonicalPublicKey => "secp: public key is neither compressed nor uncompressed",
+            Error::DebugEnclave => "secp: refusing to export secrets from a debug enclave",
+            Error::UnknownPresignature => "secp: unknown or already used presignature",
+            Error::InvalidNonceCommitment => "secp: malformed nonce commitment",
+            Error::AggregateTooLarge => "secp: too many signatures to aggregate",
+            Error::InvalidWhitelist => "secp: invalid whitelist or signing keys",
+            Error::OutOfRange => "secp: value or range out of bounds",
+            Error::InvalidWireMessage => "secp: malformed or unsupported protocol message",
+            Error::InvalidUsageReport => "secp: malformed usage report",
+            Error::KeyExpired => "secp: key has expired",
+            Error::KeyNotYetValid => "secp: key is not valid yet",
+            Error::TimeUnavailable => "secp: trusted time unavailable",
+            Error::RateLimited => "secp: key rate limit exceeded",
+            Error::InvalidRateLimit => "secp: invalid rate limit",
+            Error::InsufficientApprovals => "secp: not enough valid approvals",
+            Error::InvalidApprovalPolicy => "secp: invalid approval policy",
+            Error::InvalidPublicKeyLength => "secp: public key is not 33 or 65 bytes",
+            Error::InvalidPublicKeyPrefix => "secp: public key has a bad prefix byte",
+            Error::PublicKeyNotOnCurve => "secp: public key is not on the curve",
+            Error::InvalidSighashType => "secp: invalid sighash type",
+            Error::InvalidEnvelope => "secp: invalid sealed-data envelope",
+            Error::UnsupportedEnvelopeVersion => "secp: sealed-data envelope version not supported",
+            Error::InvalidSignerResponse => "secp: malformed remote signer response",
+            Error::InvalidNonce => "secp: nonce gives an invalid signature",
+            Error::InvalidKeyId => "secp: malformed key id",
+            Error::ContextCorrupted => "secp: context failed its integrity check",
+            Error::InvalidInterpolationPoints => "secp: interpolation points missing, zero or repeated",
+            Error::InvalidEncryptedKey => "secp: malformed encrypted secret key",
+            Error::KeyDecryptionFailed => "secp: wrong credential or tampered encrypted secret key",
+            Error::InvalidBip38Key => "secp: malformed BIP38 encrypted key",
+            Error::NonceCounterNotMonotonic => "secp: nonce counter did not advance",
+            Error::UnknownKeyFormat => "secp: unrecognized secret key format",
+            Error::InvalidSignatureLength => "secp: compact signature has the wrong length",
+            Error::InvalidDerSignature => "secp: signature is not valid DER",
+            Error::SignatureOutOfRange => "secp: signature r or s out of range",
+            Error::InvalidControlBlock => "secp: malformed taproot control block",
+            Error::InvalidTypedData => "secp: EIP-712 value does not match its type",
+            Error::NotATestVector => "secp: secret key is not a published test vector key",
+            Error::PointAtInfinity => "secp: result is the point at infinity",
+            Error::EnclaveRefused => "secp: the enclave refused the request",
+            Error::InvalidDestructionCertificate => "secp: malformed key destruction certificate",
         }
     }
 }
@@ -580,43 +1590,367 @@ impl fmt::Debug for Secp256k1<All> {
     }
 }
 
+/// The precomputed tables a kind of context is created with. Building them
+/// is most of the cost of creating a context, and takes time roughly
+/// proportional to their size; cloning or importing a context copies them
+/// instead, which is much faster. See "Memory usage" in the README for how
+/// to shrink them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CreationCost {
+    /// Bytes of tables for signing and public key derivation
+    pub signing_table_bytes: usize,
+    /// Bytes of tables for verification
+    pub verification_table_bytes: usize,
+}
+
+impl CreationCost {
+    /// The combined size of the tables
+    #[inline]
+    pub fn table_bytes(&self) -> usize {
+        self.signing_table_bytes + self.verification_table_bytes
+    }
+
+    /// The tables built for the libsecp256k1 capability `flags`
+    fn for_flags(flags: c_uint) -> CreationCost {
+        let (mut sign, mut verify) = (0, 0);
+        unsafe { ffi::secp256k1_context_table_sizes(&mut sign, &mut verify, flags); }
+        CreationCost { signing_table_bytes: sign, verification_table_bytes: verify }
+    }
+}
+
+/// The cryptographic configuration of this build, so that operators and
+/// auditors can check, and an enclave can bind into attestation evidence,
+/// exactly what is running. The options, table sizes and modules come from
+/// libsecp256k1 itself, so with "external-secp" they describe the external
+/// library.
+///
+/// `Display` writes the configuration as `key=value` lines in a fixed
+/// order, which `digest` hashes.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BuildInfo {
+    /// The version of this crate
+    pub crate_version: &'static str,
+    /// The features this crate was compiled with, as Cargo names them
+    /// (including optional dependencies), sorted and separated by commas
+    pub features: &'static str,
+    /// Linked with "external-secp" rather than compiled from the vendored
+    /// sources
+    pub external_library: bool,
+    /// The hex SHA-256 of the external archive or, for the vendored
+    /// library, of the sources under `depend/secp256k1`, each hashed as its
+    /// path, length and contents in order of path
+    pub library_sha256: &'static str,
+    /// Compiled with the SGX SDK's LVI mitigation flags, with the
+    /// "lvi-mitigation" feature
+    pub lvi_mitigation: bool,
+    /// Field and scalar arithmetic in x86_64 assembly, with the "asm" feature
+    pub asm: bool,
+    /// Arithmetic with 128-bit products rather than 64-bit ones
+    pub widemul_int128: bool,
+    /// Compiled without the code which builds the signing table
+    pub verify_only: bool,
+    /// Allocating through the `allocator` module
+    pub context_allocator: bool,
+    /// Verifying with the GLV endomorphism
+    pub endomorphism: bool,
+    /// The window size of the verification table, `SECP256K1_ECMULT_WINDOW_SIZE`
+    pub ecmult_window_size: u32,
+    /// The precision of the signing table, `SECP256K1_ECMULT_GEN_PREC_BITS`
+    pub ecmult_gen_prec_bits: u32,
+    /// The `SECP256K1_BUILD_MODULE_` options
+    modules: c_uint,
+}
+
+/// The optional libsecp256k1 modules, by the name of their directory
+const MODULES: [(&'static str, c_uint); 7] = [
+    ("ecdh", ffi::SECP256K1_BUILD_MODULE_ECDH),
+    ("recovery", ffi::SECP256K1_BUILD_MODULE_RECOVERY),
+    ("scalar_ops", ffi::SECP256K1_BUILD_MODULE_SCALAR_OPS),
+    ("context_export", ffi::SECP256K1_BUILD_MODULE_CONTEXT_EXPORT),
+    ("prepared", ffi::SECP256K1_BUILD_MODULE_PREPARED),
+    ("msm", ffi::SECP256K1_BUILD_MODULE_MSM),
+    ("tweak_batch", ffi::SECP256K1_BUILD_MODULE_TWEAK_BATCH),
+];
+
+impl BuildInfo {
+    /// The optional libsecp256k1 modules compiled in, such as `"ecdh"` and
+    /// `"recovery"`
+    pub fn modules(&self) -> Vec<&'static str> {
+        MODULES.iter().filter(|&&(_, bit)| self.modules & bit == bit).map(|&(name, _)| name).collect()
+    }
+
+    /// The SHA-256 of the `Display` form, short enough for attestation
+    /// evidence such as a report's REPORT_DATA. Requires compilation with
+    /// the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn digest(&self) -> [u8; 32] {
+        use std::fmt::Write;
+
+        /// Hashes what is written to it
+        struct Engine(hashes::Sha256);
+
+        impl Write for Engine {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.0.input(s.as_bytes());
+                Ok(())
+            }
+        }
+
+        let mut engine = Engine(hashes::Sha256::new());
+        write!(engine, "{}", self).expect("hashing never fails");
+        engine.0.result()
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let options = [("lvi-mitigation", self.lvi_mitigation), ("asm", self.asm),
+                       ("widemul-int128", self.widemul_int128), ("verify-only", self.verify_only),
+                       ("context-allocator", self.context_allocator), ("endomorphism", self.endomorphism)];
+        let options: Vec<&str> = options.iter().filter(|&&(_, on)| on).map(|&(name, _)| name).collect();
+        writeln!(f, "crate=secp256k1-sgx {}", self.crate_version)?;
+        writeln!(f, "features={}", self.features)?;
+        writeln!(f, "library={} {}", if self.external_library { "external" } else { "vendored" },
+                 self.library_sha256)?;
+        writeln!(f, "options={}", options.join(","))?;
+        writeln!(f, "modules={}", self.modules().join(","))?;
+        writeln!(f, "ecmult-window-size={}", self.ecmult_window_size)?;
+        writeln!(f, "ecmult-gen-prec-bits={}", self.ecmult_gen_prec_bits)
+    }
+}
+
+/// Reports the cryptographic configuration of this build
+pub fn build_info() -> BuildInfo {
+    let (mut window_size, mut gen_prec_bits) = (0, 0);
+    let options = unsafe { ffi::secp256k1_build_info(&mut window_size, &mut gen_prec_bits) };
+    let has = |option| options & option == option;
+    BuildInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        features: env!("SECP256K1_FEATURES"),
+        external_library: cfg!(feature = "external-secp"),
+        library_sha256: env!("SECP256K1_LIBRARY_SHA256"),
+        lvi_mitigation: has(ffi::SECP256K1_BUILD_LVI_MITIGATION),
+        asm: has(ffi::SECP256K1_BUILD_ASM_X86_64),
+        widemul_int128: has(ffi::SECP256K1_BUILD_WIDEMUL_INT128),
+        verify_only: has(ffi::SECP256K1_BUILD_VERIFY_ONLY),
+        context_allocator: has(ffi::SECP256K1_BUILD_CONTEXT_ALLOCATOR),
+        endomorphism: has(ffi::SECP256K1_BUILD_ENDOMORPHISM),
+        ecmult_window_size: window_size,
+        ecmult_gen_prec_bits: gen_prec_bits,
+        modules: options & MODULES.iter().fold(0, |all, &(_, bit)| all | bit),
+    }
+}
+
+impl<C> Secp256k1<C> {
+    /// Creates a context with the libsecp256k1 `flags`, which must match `C`
+    fn create(flags: c_uint) -> Secp256k1<C> {
+        debug_log!(debug, "creating context with flags {:#x}", flags);
+        Secp256k1 { ctx: unsafe { ffi::secp256k1_context_create(flags) }, phantom: PhantomData }
+    }
+}
+
 impl Secp256k1<None> {
     /// Creates a new Secp256k1 context with no capabilities (just de/serialization)
     pub fn without_caps() -> Secp256k1<None> {
-        Secp256k1 { ctx: unsafe { ffi::secp256k1_context_create(ffi::SECP256K1_START_NONE) }, phantom: PhantomData }
+        Secp256k1::create(ffi::SECP256K1_START_NONE)
+    }
+
+    /// The precomputation `without_caps` does, which is none
+    pub fn creation_cost_hint() -> CreationCost {
+        CreationCost::for_flags(ffi::SECP256K1_START_NONE)
+    }
+
+    /// The bytes a context without capabilities takes, in a buffer for
+    /// `ContextBuilder::preallocated` or on the heap
+    pub fn preallocate_size() -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(ffi::SECP256K1_START_NONE) }
     }
 }
 
 impl Secp256k1<All> {
     /// Creates a new Secp256k1 context with all capabilities
+    #[cfg(not(feature = "verify-only"))]
     pub fn new() -> Secp256k1<All> {
-        Secp256k1 { ctx: unsafe { ffi::secp256k1_context_create(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY) }, phantom: PhantomData }
+        Secp256k1::create(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY)
+    }
+
+    /// Creates a new Secp256k1 context with all capabilities, randomized by
+    /// an `rng::HmacDrbg` seeded with `seed`, so that tests run with the same
+    /// context state everywhere. Generate keys with another `HmacDrbg` to
+    /// make them reproducible too. Requires compilation with the
+    /// "test-determinism" feature, for tests only.
+    #[cfg(all(feature = "test-determinism", not(feature = "verify-only")))]
+    pub fn new_deterministic(seed: &[u8]) -> Secp256k1<All> {
+        let mut ret = Secp256k1::new();
+        ret.randomize(&mut rng::HmacDrbg::new(seed));
+        ret
+    }
+
+    /// The precomputed tables `new` builds, for judging what creating a
+    /// context will cost before doing it, e.g. at enclave start-up
+    pub fn creation_cost_hint() -> CreationCost {
+        CreationCost::for_flags(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY)
+    }
+
+    /// The bytes a context with all capabilities takes, tables included, in
+    /// a buffer for `ContextBuilder::preallocated` or on the heap
+    pub fn preallocate_size() -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY) }
     }
 }
 
 impl Secp256k1<SignOnly> {
     /// Creates a new Secp256k1 context that can only be used for signing
+    #[cfg(not(feature = "verify-only"))]
     pub fn signing_only() -> Secp256k1<SignOnly> {
-        Secp256k1 { ctx: unsafe { ffi::secp256k1_context_create(ffi::SECP256K1_START_SIGN) }, phantom: PhantomData }
+        Secp256k1::create(ffi::SECP256K1_START_SIGN)
+    }
+
+    /// The precomputed tables `signing_only` builds
+    pub fn creation_cost_hint() -> CreationCost {
+        CreationCost::for_flags(ffi::SECP256K1_START_SIGN)
+    }
+
+    /// The bytes a signing context takes, tables included, in a buffer for
+    /// `ContextBuilder::preallocated` or on the heap
+    pub fn preallocate_signing_size() -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(ffi::SECP256K1_START_SIGN) }
     }
 }
 
 impl Secp256k1<VerifyOnly> {
     /// Creates a new Secp256k1 context that can only be used for verification
     pub fn verification_only() -> Secp256k1<VerifyOnly> {
-        Secp256k1 { ctx: unsafe { ffi::secp256k1_context_create(ffi::SECP256K1_START_VERIFY) }, phantom: PhantomData }
+        Secp256k1::create(ffi::SECP256K1_START_VERIFY)
     }
-}
 
-impl<C> Secp256k1<C> {
+    /// The precomputed tables `verification_only` builds
+    pub fn creation_cost_hint() -> CreationCost {
+        CreationCost::for_flags(ffi::SECP256K1_START_VERIFY)
+    }
 
-    /// (Re)randomizes the Secp256k1 context for cheap sidechannel resistance;
-    /// see comment in libsecp256k1 commit d2275795f by Gregory Maxwell. Requires
-    /// compilation with "rand" feature.
+    /// The bytes a verification context takes, tables included, in a buffer
+    /// for `ContextBuilder::preallocated` or on the heap
+    pub fn preallocate_verification_size() -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(ffi::SECP256K1_START_VERIFY) }
+    }
+}
+
+impl Secp256k1<All> {
+    /// Narrows this context to signing, keeping its tables and blinding. The
+    /// verification tables stay allocated, so build a `signing_only` context
+    /// instead when memory is short.
+    pub fn into_sign_only(self) -> Secp256k1<SignOnly> {
+        self.recast()
+    }
+
+    /// Narrows this context to verification, keeping its tables. The signing
+    /// tables stay allocated, so build a `verification_only` context instead
+    /// when memory is short.
+    pub fn into_verify_only(self) -> Secp256k1<VerifyOnly> {
+        self.recast()
+    }
+}
+
+impl<C> Secp256k1<C> {
+    /// Hands the context over to another capability type, without checking
+    /// that it has the tables the new type needs
+    fn recast<D>(self) -> Secp256k1<D> {
+        let ctx = self.ctx;
+        mem::forget(self);
+        Secp256k1 { ctx, phantom: PhantomData }
+    }
+
+    /// Hands the context over to another capability type if it has the
+    /// tables for `flags`, failing with `InvalidContext` otherwise
+    fn try_recast<D>(self, flags: c_uint) -> Result<Secp256k1<D>, Error> {
+        if unsafe { ffi::secp256k1_context_capabilities(self.ctx) } & flags == flags {
+            Ok(self.recast())
+        } else {
+            Err(Error::InvalidContext)
+        }
+    }
+
+    /// The precomputed tables this context holds. A context narrowed with
+    /// `into_sign_only` or `into_verify_only` still holds the tables of
+    /// the capabilities it gave up.
+    pub fn table_sizes(&self) -> CreationCost {
+        CreationCost::for_flags(unsafe { ffi::secp256k1_context_capabilities(self.ctx) })
+    }
+
+    /// The bytes this context takes, its tables and bookkeeping, which is
+    /// the memory it keeps allocated until dropped and that each clone
+    /// allocates again. Slightly overestimated, by the alignment slack a
+    /// preallocated buffer needs.
+    pub fn allocated_size(&self) -> usize {
+        unsafe { ffi::secp256k1_context_preallocated_size(ffi::secp256k1_context_capabilities(self.ctx)) }
+    }
+}
+
+impl From<Secp256k1<All>> for Secp256k1<SignOnly> {
+    fn from(secp: Secp256k1<All>) -> Secp256k1<SignOnly> {
+        secp.into_sign_only()
+    }
+}
+
+impl From<Secp256k1<All>> for Secp256k1<VerifyOnly> {
+    fn from(secp: Secp256k1<All>) -> Secp256k1<VerifyOnly> {
+        secp.into_verify_only()
+    }
+}
+
+impl TryFrom<Secp256k1<SignOnly>> for Secp256k1<All> {
+    type Error = Error;
+
+    /// Succeeds if the context still has verification tables, i.e. it was
+    /// narrowed from a context with all capabilities
+    fn try_from(secp: Secp256k1<SignOnly>) -> Result<Secp256k1<All>, Error> {
+        secp.try_recast(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+impl TryFrom<Secp256k1<VerifyOnly>> for Secp256k1<All> {
+    type Error = Error;
+
+    /// Succeeds if the context still has signing tables, i.e. it was
+    /// narrowed from a context with all capabilities
+    fn try_from(secp: Secp256k1<VerifyOnly>) -> Result<Secp256k1<All>, Error> {
+        secp.try_recast(ffi::SECP256K1_START_SIGN | ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+impl TryFrom<Secp256k1<SignOnly>> for Secp256k1<VerifyOnly> {
+    type Error = Error;
+
+    /// Succeeds if the context has verification tables
+    fn try_from(secp: Secp256k1<SignOnly>) -> Result<Secp256k1<VerifyOnly>, Error> {
+        secp.try_recast(ffi::SECP256K1_START_VERIFY)
+    }
+}
+
+impl TryFrom<Secp256k1<VerifyOnly>> for Secp256k1<SignOnly> {
+    type Error = Error;
+
+    /// Succeeds if the context has signing tables
+    fn try_from(secp: Secp256k1<VerifyOnly>) -> Result<Secp256k1<SignOnly>, Error> {
+        secp.try_recast(ffi::SECP256K1_START_SIGN)
+    }
+}
+
+impl<C> Secp256k1<C> {
+
+    /// (Re)randomizes the Secp256k1 context for cheap sidechannel resistance;
+    /// see comment in libsecp256k1 commit d2275795f by Gregory Maxwell. Requires
+    /// compilation with "rand" feature.
     #[cfg(any(test, feature = "rand"))]
-    pub fn randomize<R: Rng>(&mut self, rng: &mut R) {
+    pub fn randomize<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
         let mut seed = [0; 32];
         rng.fill_bytes(&mut seed);
+        self.randomize_with_seed(&seed);
+    }
+
+    /// Randomizes the context with `seed`, as `randomize` does
+    fn randomize_with_seed(&mut self, seed: &[u8; 32]) {
         unsafe {
             let err = ffi::secp256k1_context_randomize(self.ctx, seed.as_ptr());
             // This function cannot fail; it has an error return for future-proofing.
@@ -633,13 +1967,24 @@ impl<C> Secp256k1<C> {
 
 }
 
+#[cfg(not(feature = "verify-only"))]
 impl<C: Signing> Secp256k1<C> {
 
     /// Constructs a signature for `msg` using the secret key `sk` and RFC6979 nonce
     /// Requires a signing-capable context.
     pub fn sign(&self, msg: &Message, sk: &key::SecretKey)
                 -> Signature {
+        debug_log!(trace, "signing");
+        measure!(Sign, |_| true, {
+            let ret = self.sign_unmeasured(msg, sk);
+            cleanup::scrub_stack();
+            ret
+        })
+    }
 
+    /// `sign` without the metrics, logging and stack scrubbing, which batch
+    /// signing does once for the whole batch
+    fn sign_unmeasured(&self, msg: &Message, sk: &key::SecretKey) -> Signature {
         let mut ret = unsafe { ffi::Signature::blank() };
         unsafe {
             // We can assume the return value because it's not possible to construct
@@ -648,25 +1993,81 @@ impl<C: Signing> Secp256k1<C> {
                                                  sk.as_ptr(), ffi::secp256k1_nonce_function_rfc6979,
                                                  ptr::null()), 1);
         }
-
         Signature::from(ret)
     }
 
+    /// Signs each message with its secret key, giving the same signatures as
+    /// calling `sign` on each pair in turn. The output is reserved up front,
+    /// and metrics, logging and stack scrubbing happen once for the batch
+    /// rather than once per signature, so an enclave signing many messages
+    /// per ECALL pays for one call's worth of setup. Requires a
+    /// signing-capable context.
+    pub fn sign_batch(&self, items: &[(Message, &key::SecretKey)]) -> Vec<Signature> {
+        debug_log!(trace, "signing a batch of {}", items.len());
+        measure!(batch SignBatch, items.len(), |_| true, {
+            let mut ret = Vec::with_capacity(items.len());
+            ret.extend(items.iter().map(|&(ref msg, sk)| self.sign_unmeasured(msg, sk)));
+            cleanup::scrub_stack();
+            ret
+        })
+    }
+
+    /// Signs every message with the same secret key, like `sign_batch`.
+    /// Requires a signing-capable context.
+    pub fn sign_batch_with_key(&self, msgs: &[Message], sk: &key::SecretKey) -> Vec<Signature> {
+        debug_log!(trace, "signing a batch of {} with one key", msgs.len());
+        measure!(batch SignBatch, msgs.len(), |_| true, {
+            let mut ret = Vec::with_capacity(msgs.len());
+            ret.extend(msgs.iter().map(|msg| self.sign_unmeasured(msg, sk)));
+            cleanup::scrub_stack();
+            ret
+        })
+    }
+
+    /// Like `sign_batch`, but splits the batch into contiguous chunks signed
+    /// on up to `threads` scoped threads sharing this context. The
+    /// signatures come back in the order of `items`. Requires a
+    /// signing-capable context. Requires compilation with the "std" feature
+    /// and without the "sgx" one, since the SGX standard library has no
+    /// scoped threads.
+    #[cfg(all(feature = "std", not(feature = "sgx")))]
+    pub fn sign_batch_parallel(&self, items: &[(Message, &key::SecretKey)], threads: usize) -> Vec<Signature> {
+        debug_log!(trace, "signing a batch of {} on {} threads", items.len(), threads);
+        let chunk = cmp::max(1, (items.len() + threads.saturating_sub(1)) / cmp::max(1, threads));
+        measure!(batch SignBatch, items.len(), |_| true, {
+            let mut ret = Vec::with_capacity(items.len());
+            ::std::thread::scope(|scope| {
+                let handles: Vec<_> = items.chunks(chunk).map(|chunk| scope.spawn(move || {
+                    let sigs: Vec<_> = chunk.iter().map(|&(ref msg, sk)| self.sign_unmeasured(msg, sk)).collect();
+                    cleanup::scrub_stack();
+                    sigs
+                })).collect();
+                for handle in handles {
+                    ret.extend(handle.join().expect("signing never panics"));
+                }
+            });
+            ret
+        })
+    }
+
     /// Constructs a signature for `msg` using the secret key `sk` and RFC6979 nonce
     /// Requires a signing-capable context.
     pub fn sign_recoverable(&self, msg: &Message, sk: &key::SecretKey)
                             -> RecoverableSignature {
+        debug_log!(trace, "signing recoverably");
+        measure!(SignRecoverable, |_| true, {
+            let mut ret = unsafe { ffi::RecoverableSignature::blank() };
+            unsafe {
+                // We can assume the return value because it's not possible to construct
+                // an invalid signature from a valid `Message` and `SecretKey`
+                assert_eq!(ffi::secp256k1_ecdsa_sign_recoverable(self.ctx, &mut ret, msg.as_ptr(),
+                                                                 sk.as_ptr(), ffi::secp256k1_nonce_function_rfc6979,
+                                                                 ptr::null()), 1);
+            }
+            cleanup::scrub_stack();
 
-        let mut ret = unsafe { ffi::RecoverableSignature::blank() };
-        unsafe {
-            // We can assume the return value because it's not possible to construct
-            // an invalid signature from a valid `Message` and `SecretKey`
-            assert_eq!(ffi::secp256k1_ecdsa_sign_recoverable(self.ctx, &mut ret, msg.as_ptr(),
-                                                             sk.as_ptr(), ffi::secp256k1_nonce_function_rfc6979,
-                                                             ptr::null()), 1);
-        }
-
-        RecoverableSignature::from(ret)
+            RecoverableSignature::from(ret)
+        })
     }
 
     /// Generates a random keypair. Convenience function for `key::SecretKey::new`
@@ -675,12 +2076,23 @@ impl<C: Signing> Secp256k1<C> {
     /// with the "rand" feature.
     #[inline]
     #[cfg(any(test, feature = "rand"))]
-    pub fn generate_keypair<R: Rng>(&self, rng: &mut R)
+    pub fn generate_keypair<R: RngCore + CryptoRng>(&self, rng: &mut R)
                                     -> (key::SecretKey, key::PublicKey) {
         let sk = key::SecretKey::new(self, rng);
         let pk = key::PublicKey::from_secret_key(self, &sk);
         (sk, pk)
     }
+
+    /// Derives a keypair from 32 bytes of caller-supplied entropy, for
+    /// callers whose randomness doesn't come through an RNG. Convenience
+    /// function for `key::SecretKey::from_entropy` and
+    /// `key::PublicKey::from_secret_key`; see `key::KeyPair::from_entropy`
+    /// to get a `KeyPair` instead. Requires a signing-capable context.
+    #[inline]
+    pub fn generate_keypair_from_entropy(&self, seed32: [u8; constants::SECRET_KEY_SIZE])
+                                         -> (key::SecretKey, key::PublicKey) {
+        key::KeyPair::from_entropy(self, seed32).into()
+    }
 }
 
 impl<C: Verification> Secp256k1<C> {
@@ -701,6 +2113,28 @@ impl<C: Verification> Secp256k1<C> {
         Ok(key::PublicKey::from(pk))
     }
 
+    /// Checks a compact proof (see `RecoverableSignature::to_compact_proof`)
+    /// that `expected` signed `msg`, by recovering the signer's key and
+    /// comparing. Fails like `RecoverableSignature::from_compact_proof` if
+    /// the proof is malformed, with `InvalidSignature` if it has a high S
+    /// value, and with
+    /// `IncorrectSignature` if it was made by another key or for another
+    /// message. Requires a verify-capable context.
+    pub fn verify_compact_proof(&self, msg: &Message, proof: &[u8], expected: &key::PublicKey)
+                                -> Result<(), Error> {
+        let sig = RecoverableSignature::from_compact_proof(proof)?;
+        let standard = sig.to_standard(self);
+        let mut normalized = standard;
+        normalized.normalize_s(self);
+        if normalized != standard {
+            return Err(Error::InvalidSignature);
+        }
+        match self.recover(msg, &sig) {
+            Ok(ref pk) if pk == expected => Ok(()),
+            _ => Err(Error::IncorrectSignature),
+        }
+    }
+
     /// Checks that `sig` is a valid ECDSA signature for `msg` using the public
     /// key `pubkey`. Returns `Ok(true)` on success. Note that this function cannot
     /// be used for Bitcoin consensus checking since there may exist signatures
@@ -708,51 +2142,248 @@ impl<C: Verification> Secp256k1<C> {
     /// verify-capable context.
     #[inline]
     pub fn verify(&self, msg: &Message, sig: &Signature, pk: &key::PublicKey) -> Result<(), Error> {
-        unsafe {
+        measure!(Verify, Result::is_ok, unsafe {
             if ffi::secp256k1_ecdsa_verify(self.ctx, sig.as_ptr(), msg.as_ptr(), pk.as_ptr()) == 0 {
+                debug_log!(debug, "signature verification failed for public key {}", pk);
                 Err(Error::IncorrectSignature)
             } else {
                 Ok(())
             }
+        })
+    }
+
+    /// Checks `sig_der` on `msg` against the serialized public key `pk`,
+    /// applying Bitcoin's policy rules on top of `verify`: the signature
+    /// must be strict DER as required by BIP66 (without a sighash byte),
+    /// with a low S value, and the key must be compressed or uncompressed.
+    /// Fails with `NonCanonicalDer`, `HighS` or `NonCanonicalPublicKey` if
+    /// one of these is broken, with `SignatureOutOfRange` or
+    /// `PublicKeyNotOnCurve` if an encoding is well-formed but its value is
+    /// not, and with
+    /// `IncorrectSignature` if the signature does not verify. Requires a
+    /// verify-capable context.
+    pub fn verify_strict(&self, msg: &Message, sig_der: &[u8], pk: &[u8]) -> Result<(), Error> {
+        if !is_strict_der(sig_der) {
+            return Err(Error::NonCanonicalDer);
+        }
+        let sig = Signature::from_der(self, sig_der)?;
+        let mut normalized = sig;
+        normalized.normalize_s(self);
+        if normalized != sig {
+            return Err(Error::HighS);
         }
+        match (pk.len(), pk.first()) {
+            (constants::PUBLIC_KEY_SIZE, Some(&0x02)) |
+            (constants::PUBLIC_KEY_SIZE, Some(&0x03)) |
+            (constants::UNCOMPRESSED_PUBLIC_KEY_SIZE, Some(&0x04)) => {}
+            _ => return Err(Error::NonCanonicalPublicKey),
+        }
+        let pk = key::PublicKey::from_slice(pk)?;
+        self.verify(msg, &sig, &pk)
+    }
+
+    /// Parses a DER signature and a serialized public key, as delivered
+    /// across an ECALL boundary, and checks the signature on `msg32`. Unlike
+    /// `verify_strict` a high S value is normalized rather than rejected. Fails
+    /// like `Signature::from_der` if the signature does not parse, with the error
+    /// of `PublicKey::from_slice` if the key does not, and with
+    /// `IncorrectSignature` if the signature does not verify. Requires a
+    /// verify-capable context.
+    pub fn verify_serialized(&self, msg32: &[u8; constants::MESSAGE_SIZE], sig_der: &[u8], pubkey_bytes: &[u8])
+                             -> Result<(), Error> {
+        let mut sig = Signature::from_der(self, sig_der)?;
+        sig.normalize_s(self);
+        let pk = key::PublicKey::from_slice(pubkey_bytes)?;
+        self.verify(&Message::from(*msg32), &sig, &pk)
     }
 }
 
-#[cfg(test)]
+/// Whether `sig` is a DER signature encoding which meets BIP66, i.e.
+/// Bitcoin's `IsValidSignatureEncoding` without the trailing sighash byte
+fn is_strict_der(sig: &[u8]) -> bool {
+    // 0x30 [total length] 0x02 [R length] [R] 0x02 [S length] [S]
+    if sig.len() < 8 || sig.len() > 72 || sig[0] != 0x30 || sig[1] as usize != sig.len() - 2 {
+        return false;
+    }
+    let len_r = sig[3] as usize;
+    if 5 + len_r >= sig.len() {
+        return false;
+    }
+    let len_s = sig[5 + len_r] as usize;
+    if len_r + len_s + 6 != sig.len() {
+        return false;
+    }
+    // Each integer is present, not negative and without excess zero padding
+    let integer_ok = |at: usize, len: usize| {
+        sig[at - 2] == 0x02 && len != 0 && sig[at] & 0x80 == 0 &&
+        !(len > 1 && sig[at] == 0 && sig[at + 1] & 0x80 == 0)
+    };
+    integer_ok(4, len_r) && integer_ok(6 + len_r, len_s)
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
 mod tests {
-    use rand::{Rng, thread_rng};
+    use std::convert::TryFrom;
+
+    use rand_core::RngCore;
+    use rng::thread_rng;
 
     use key::{SecretKey, PublicKey};
+    use scalar::Scalar;
     use super::constants;
-    use super::{Secp256k1, Signature, RecoverableSignature, Message, RecoveryId};
-    use super::Error::{InvalidMessage, IncorrectSignature, InvalidSignature};
-
-    macro_rules! hex {
-        ($hex:expr) => {
-            {
-                let mut vec = Vec::new();
-                let mut b = 0;
-                for (idx, c) in $hex.as_bytes().iter().enumerate() {
-                    b <<= 4;
-                    match *c {
-                        b'A'...b'F' => b |= c - b'A' + 10,
-                        b'a'...b'f' => b |= c - b'a' + 10,
-                        b'0'...b'9' => b |= c - b'0',
-                        _ => panic!("Bad hex"),
-                    }
-                    if (idx & 1) == 1 {
-                        vec.push(b);
-                        b = 0;
-                    }
+    use super::{All, Secp256k1, SignOnly, Signature, RecoverableSignature, Message, RecoveryId, VerifyOnly};
+    use super::Error::{InvalidContext, InvalidMessage, IncorrectSignature, InvalidSignature, InvalidRecoveryId};
+    use super::Error::{InvalidDerSignature, InvalidSignatureLength, SignatureOutOfRange};
+
+    #[test]
+    fn capability_conversions() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let full = Secp256k1::new();
+        let pk = PublicKey::from_secret_key(&full, &sk);
+        let sig = full.sign(&msg, &sk);
+
+        let sign = full.clone().into_sign_only();
+        assert_eq!(sign.sign(&msg, &sk), sig);
+        let vrfy = Secp256k1::<VerifyOnly>::from(full.clone());
+        assert!(vrfy.verify(&msg, &sig, &pk).is_ok());
+
+        // Narrowed contexts keep their tables, so they convert back
+        let full = Secp256k1::<All>::try_from(sign).unwrap();
+        assert!(full.verify(&msg, &full.sign(&msg, &sk), &pk).is_ok());
+        let sign = Secp256k1::<SignOnly>::try_from(vrfy).unwrap();
+        assert_eq!(sign.sign(&msg, &sk), sig);
+        let vrfy = Secp256k1::<VerifyOnly>::try_from(sign).unwrap();
+        assert!(Secp256k1::<All>::try_from(vrfy).is_ok());
+
+        // Contexts built without them don't
+        assert_eq!(Secp256k1::<All>::try_from(Secp256k1::signing_only()).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::<All>::try_from(Secp256k1::verification_only()).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::<SignOnly>::try_from(Secp256k1::verification_only()).err(), Some(InvalidContext));
+        assert_eq!(Secp256k1::<VerifyOnly>::try_from(Secp256k1::signing_only()).err(), Some(InvalidContext));
+    }
+
+    #[cfg(feature = "debug-logging")]
+    #[test]
+    fn debug_logging() {
+        use std::sync::Mutex;
+        use log::{self, LevelFilter, Log, Metadata, Record};
+
+        struct Capture(Mutex<Vec<String>>);
+
+        impl Log for Capture {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.target() == "secp256k1"
+            }
+
+            fn log(&self, record: &Record) {
+                if self.enabled(record.metadata()) {
+                    self.0.lock().unwrap().push(record.args().to_string());
                 }
-                vec
             }
+
+            fn flush(&self) {}
         }
+
+        let capture: &'static Capture = Box::leak(Box::new(Capture(Mutex::new(Vec::new()))));
+        log::set_logger(capture).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let pk = PublicKey::from_secret_key(&s, &sk);
+        assert!(SecretKey::from_slice(&[0xff; 32]).is_err());
+        assert!(SecretKey::from_slice(&[0xcd; 31]).is_err());
+        assert!(PublicKey::from_slice(&[0x05; 33]).is_err());
+        let sig = s.sign(&Message::from_slice(&[1; 32]).unwrap(), &sk);
+        assert!(s.verify(&Message::from_slice(&[2; 32]).unwrap(), &sig, &pk).is_err());
+
+        let logged = capture.0.lock().unwrap();
+        for expected in &[
+            "creating context with flags 0x301".to_string(),
+            "rejected secret key: not a valid scalar".to_string(),
+            "rejected secret key: 31 bytes instead of 32".to_string(),
+            "rejected public key: 33 bytes starting with 0x05 (secp: public key has a bad prefix byte)".to_string(),
+            "signing".to_string(),
+            format!("signature verification failed for public key {}", pk),
+        ] {
+            assert!(logged.contains(expected), "missing {:?}", expected);
+        }
+        assert!(logged.iter().all(|line| !line.contains("cdcdcd")));
+    }
+
+    #[test]
+    fn creation_cost_hint() {
+        let none = Secp256k1::<super::None>::creation_cost_hint();
+        let sign = Secp256k1::<SignOnly>::creation_cost_hint();
+        let vrfy = Secp256k1::<VerifyOnly>::creation_cost_hint();
+        let full = Secp256k1::<All>::creation_cost_hint();
+        assert_eq!(none.table_bytes(), 0);
+        assert_eq!((sign.signing_table_bytes, sign.verification_table_bytes), (full.signing_table_bytes, 0));
+        assert_eq!((vrfy.signing_table_bytes, vrfy.verification_table_bytes), (0, full.verification_table_bytes));
+        assert!(sign.table_bytes() > 0 && vrfy.table_bytes() > 0);
+        assert_eq!(full.table_bytes(), sign.table_bytes() + vrfy.table_bytes());
+        // An export is the tables behind a 40-byte header and checksum
+        assert_eq!(Secp256k1::verification_only().export_context().len(), 40 + vrfy.table_bytes());
+    }
+
+    #[test]
+    fn allocated_size() {
+        let none = Secp256k1::<super::None>::preallocate_size();
+        let sign = Secp256k1::<SignOnly>::preallocate_signing_size();
+        let vrfy = Secp256k1::<VerifyOnly>::preallocate_verification_size();
+        let full = Secp256k1::<All>::preallocate_size();
+        assert!(none < sign && none < vrfy);
+        assert!(sign - none >= Secp256k1::<SignOnly>::creation_cost_hint().table_bytes());
+        assert!(vrfy - none >= Secp256k1::<VerifyOnly>::creation_cost_hint().table_bytes());
+        assert_eq!(full + none, sign + vrfy);
+        assert_eq!(Secp256k1::builder().signing().verification().preallocated_size(), full);
+
+        let secp = Secp256k1::new();
+        assert_eq!(secp.allocated_size(), full);
+        assert_eq!(secp.table_sizes(), Secp256k1::<All>::creation_cost_hint());
+        assert_eq!(secp.clone().allocated_size(), full);
+        // Narrowing keeps the tables
+        assert_eq!(secp.into_verify_only().allocated_size(), full);
+        assert_eq!(Secp256k1::signing_only().allocated_size(), sign);
+        assert_eq!(Secp256k1::verification_only().table_sizes(), Secp256k1::<VerifyOnly>::creation_cost_hint());
+        assert_eq!(Secp256k1::without_caps().allocated_size(), none);
+    }
+
+    #[cfg(not(feature = "external-secp"))]
+    #[test]
+    fn build_info() {
+        let info = super::build_info();
+        assert_eq!(info.lvi_mitigation, cfg!(feature = "lvi-mitigation"));
+        assert_eq!(info.asm, cfg!(feature = "asm"));
+        assert_eq!(info.verify_only, cfg!(feature = "verify-only"));
+        assert_eq!(info.context_allocator, cfg!(feature = "context-allocator"));
+        assert!(info.widemul_int128 || !cfg!(feature = "widemul-int128"));
+        assert!(info.ecmult_window_size >= 2 && info.ecmult_window_size <= 24);
+        assert!([2, 4, 8].contains(&info.ecmult_gen_prec_bits));
+        assert!(info.endomorphism);
+        assert_eq!(info.modules(), ["ecdh", "recovery", "scalar_ops", "context_export", "prepared", "msm",
+                                    "tweak_batch"]);
+
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.external_library);
+        assert_eq!(info.library_sha256.len(), 64);
+        let features: Vec<&str> = info.features.split(',').collect();
+        assert_eq!(features.contains(&"rand"), cfg!(feature = "rand"));
+        assert_eq!(features.contains(&"hashes"), cfg!(feature = "hashes"));
+
+        let text = info.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[0], format!("crate=secp256k1-sgx {}", info.crate_version));
+        assert_eq!(lines[2], format!("library=vendored {}", info.library_sha256));
+        assert_eq!(lines[4], "modules=ecdh,recovery,scalar_ops,context_export,prepared,msm,tweak_batch");
+        #[cfg(feature = "hashes")]
+        assert_eq!(info.digest(), ::hashes::sha256(text.as_bytes()));
     }
 
     #[test]
     fn capabilities() {
-        let none = Secp256k1::without_caps();
         let sign = Secp256k1::signing_only();
         let vrfy = Secp256k1::verification_only();
         let full = Secp256k1::new();
@@ -782,14 +2413,32 @@ mod tests {
                    full.recover(&msg, &sigr));
         assert_eq!(full.recover(&msg, &sigr), Ok(pk));
 
-        // Check that we can produce keys from slices with no precomputation
-        let (pk_slice, sk_slice) = (&pk.serialize(), &sk[..]);
-        let new_pk = PublicKey::from_slice(&none, pk_slice).unwrap();
-        let new_sk = SecretKey::from_slice(&none, sk_slice).unwrap();
+        // Check that we can produce keys from slices without a context
+        let (pk_slice, sk_slice) = (&pk.serialize(), &sk.secret_bytes()[..]);
+        let new_pk = PublicKey::from_slice(pk_slice).unwrap();
+        let new_sk = SecretKey::from_slice(sk_slice).unwrap();
         assert_eq!(sk, new_sk);
         assert_eq!(pk, new_pk);
     }
 
+    #[cfg(feature = "test-determinism")]
+    #[test]
+    fn deterministic_context() {
+        use rng::HmacDrbg;
+
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let run = || {
+            let s = Secp256k1::new_deterministic(b"golden file seed");
+            let (sk, pk) = s.generate_keypair(&mut HmacDrbg::new(b"golden file seed"));
+            (sk, pk, s.sign(&msg, &sk))
+        };
+        let (sk, pk, sig) = run();
+        assert_eq!(run(), (sk, pk, sig));
+        assert_eq!(&sk.secret_bytes()[..],
+                   &hex!("72e247b6811ccf5bc30bdca4e9a915a731bcbb6243fb9405956c3fd9d7a0a161")[..]);
+        assert!(Secp256k1::verification_only().verify(&msg, &sig, &pk).is_ok());
+    }
+
     #[test]
     fn recid_sanity_check() {
         let one = RecoveryId(1);
@@ -803,7 +2452,7 @@ mod tests {
         let one = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
 
-        let sk = SecretKey::from_slice(&s, &one).unwrap();
+        let sk = SecretKey::from_slice(&one).unwrap();
         let msg = Message::from_slice(&one).unwrap();
 
         let sig = s.sign_recoverable(&msg, &sk);
@@ -835,6 +2484,11 @@ mod tests {
             let sig2 = Signature::from_der(&s, &der[..]).unwrap();
             assert_eq!(sig1, sig2);
 
+            let mut buf = [0xff; constants::MAX_SIGNATURE_SIZE];
+            let len = sig1.serialize_der_into(&mut buf);
+            assert_eq!(buf[..len], der[..]);
+            assert!(buf[len..].iter().all(|&b| b == 0xff));
+
             let compact = sig1.serialize_compact(&s);
             let sig2 = Signature::from_compact(&s, &compact[..]).unwrap();
             assert_eq!(sig1, sig2);
@@ -865,6 +2519,50 @@ mod tests {
         check_lax_sig!("3044022023ee4e95151b2fbbb08a72f35babe02830d14d54bd7ed1320e4751751d1baa4802206235245254f58fd1be6ff19ca291817da76da65c2f6d81d654b5185dd86b8acf");
     }
 
+    #[test]
+    fn signature_display_fromstr() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let sk = SecretKey::from_slice(&[2; 32]).unwrap();
+        let sig = s.sign(&msg, &sk);
+
+        let der_hex = sig.to_string();
+        assert_eq!(der_hex.len(), 2 * sig.serialize_der(&s).len());
+        assert_eq!(der_hex.parse::<Signature>(), Ok(sig));
+        assert_eq!(der_hex.to_uppercase().parse::<Signature>(), Ok(sig));
+
+        let compact_hex: String = sig.serialize_compact(&s).iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(compact_hex.parse::<Signature>(), Ok(sig));
+
+        assert_eq!("".parse::<Signature>(), Err(InvalidDerSignature));
+        assert_eq!(der_hex[1..].parse::<Signature>(), Err(InvalidSignature));
+        assert_eq!(der_hex[..der_hex.len() - 2].parse::<Signature>(), Err(InvalidDerSignature));
+        assert_eq!(compact_hex[..126].parse::<Signature>(), Err(InvalidDerSignature));
+        assert_eq!(format!("{}00", der_hex).parse::<Signature>(), Err(InvalidDerSignature));
+        assert_eq!(der_hex.replace("0", "g").parse::<Signature>(), Err(InvalidSignature));
+    }
+
+    #[test]
+    fn signature_eq_hash() {
+        use std::collections::HashSet;
+
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let sigs: Vec<Signature> = (1..4u8).map(|i| {
+            s.sign(&msg, &SecretKey::from_slice(&[i; 32]).unwrap())
+        }).collect();
+
+        let set: HashSet<Signature> = sigs.iter().chain(sigs.iter()).cloned().collect();
+        assert_eq!(set.len(), 3);
+
+        // Ordering follows the compact serialization
+        let mut sorted = sigs.clone();
+        sorted.sort();
+        for pair in sorted.windows(2) {
+            assert!(pair[0].serialize_compact(&s)[..] < pair[1].serialize_compact(&s)[..]);
+        }
+    }
+
     #[test]
     fn sign_and_verify() {
         let mut s = Secp256k1::new();
@@ -881,6 +2579,37 @@ mod tests {
          }
     }
 
+    #[test]
+    fn sign_batch() {
+        let mut s = Secp256k1::new();
+        s.randomize(&mut thread_rng());
+
+        let mut items = vec![];
+        let keys: Vec<_> = (0..10).map(|_| s.generate_keypair(&mut thread_rng())).collect();
+        for (sk, _) in &keys {
+            let mut msg = [0; 32];
+            thread_rng().fill_bytes(&mut msg);
+            items.push((Message::from_slice(&msg).unwrap(), sk));
+        }
+
+        let sigs = s.sign_batch(&items);
+        assert_eq!(sigs.len(), items.len());
+        for (&(ref msg, sk), sig) in items.iter().zip(&sigs) {
+            assert_eq!(*sig, s.sign(msg, sk));
+        }
+        #[cfg(all(feature = "std", not(feature = "sgx")))]
+        for threads in 0..12 {
+            assert_eq!(s.sign_batch_parallel(&items, threads), sigs);
+        }
+
+        let msgs: Vec<_> = items.iter().map(|&(msg, _)| msg).collect();
+        let sigs = s.sign_batch_with_key(&msgs, &keys[0].0);
+        for (msg, sig) in msgs.iter().zip(&sigs) {
+            assert_eq!(s.verify(msg, sig, &keys[0].1), Ok(()));
+        }
+        assert!(s.sign_batch(&[]).is_empty());
+    }
+
     #[test]
     fn sign_and_verify_extreme() {
         let mut s = Secp256k1::new();
@@ -902,7 +2631,7 @@ mod tests {
         wild_keys[1][0] -= 1;
         wild_msgs[1][0] -= 1;
 
-        for key in wild_keys.iter().map(|k| SecretKey::from_slice(&s, &k[..]).unwrap()) {
+        for key in wild_keys.iter().map(|k| SecretKey::from_slice(&k[..]).unwrap()) {
             for msg in wild_msgs.iter().map(|m| Message::from_slice(&m[..]).unwrap()) {
                 let sig = s.sign(&msg, &key);
                 let pk = PublicKey::from_secret_key(&s, &key);
@@ -950,6 +2679,41 @@ mod tests {
         assert_eq!(s.recover(&msg, &sig), Ok(pk));
     }
 
+    #[test]
+    fn compact_proof() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x55; 32]).unwrap();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let sig = s.sign_recoverable(&msg, &sk);
+
+        let proof = sig.to_compact_proof();
+        assert_eq!(proof[0] as i32, sig.serialize_compact(&s).0.to_i32());
+        assert_eq!(proof[1..], sig.serialize_compact(&s).1[..]);
+        assert_eq!(RecoverableSignature::from_compact_proof(&proof), Ok(sig));
+        assert_eq!(s.verify_compact_proof(&msg, &proof, &pk), Ok(()));
+
+        let other_msg = Message::from_slice(&[0x66; 32]).unwrap();
+        assert_eq!(s.verify_compact_proof(&other_msg, &proof, &pk), Err(IncorrectSignature));
+        let (_, other_pk) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(s.verify_compact_proof(&msg, &proof, &other_pk), Err(IncorrectSignature));
+        let mut flipped = proof;
+        flipped[0] ^= 1;
+        assert_eq!(s.verify_compact_proof(&msg, &flipped, &pk), Err(IncorrectSignature));
+
+        // Malformed proofs, and the high-S twin of the signature
+        assert_eq!(s.verify_compact_proof(&msg, &proof[1..], &pk), Err(InvalidSignatureLength));
+        let mut bad_recid = proof;
+        bad_recid[0] = 4;
+        assert_eq!(s.verify_compact_proof(&msg, &bad_recid, &pk), Err(InvalidRecoveryId));
+        let mut high_s = proof;
+        let mut sig_s = [0u8; 32];
+        sig_s.copy_from_slice(&proof[33..]);
+        high_s[33..].copy_from_slice(&Scalar::from_be_bytes(sig_s).unwrap().negate().to_be_bytes());
+        high_s[0] ^= 1;
+        assert_eq!(s.recover(&msg, &RecoverableSignature::from_compact_proof(&high_s).unwrap()), Ok(pk));
+        assert_eq!(s.verify_compact_proof(&msg, &high_s, &pk), Err(InvalidSignature));
+    }
+
     #[test]
     fn bad_recovery() {
         let mut s = Secp256k1::new();
@@ -969,9 +2733,9 @@ mod tests {
     fn test_bad_slice() {
         let s = Secp256k1::new();
         assert_eq!(Signature::from_der(&s, &[0; constants::MAX_SIGNATURE_SIZE + 1]),
-                   Err(InvalidSignature));
+                   Err(InvalidDerSignature));
         assert_eq!(Signature::from_der(&s, &[0; constants::MAX_SIGNATURE_SIZE]),
-                   Err(InvalidSignature));
+                   Err(InvalidDerSignature));
 
         assert_eq!(Message::from_slice(&[0; constants::MESSAGE_SIZE - 1]),
                    Err(InvalidMessage));
@@ -980,6 +2744,100 @@ mod tests {
         assert!(Message::from_slice(&[0; constants::MESSAGE_SIZE]).is_ok());
     }
 
+    #[test]
+    fn try_from_slices() {
+        // As generic parsing code would use them
+        fn parse<'a, T: TryFrom<&'a [u8], Error = super::Error>>(data: &'a [u8]) -> Result<T, super::Error> {
+            T::try_from(data)
+        }
+
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[0xab; 32]).unwrap();
+        let sig = s.sign(&msg, &sk);
+        let compact = sig.serialize_compact(&s);
+        let verify = || -> Result<(), super::Error> {
+            let sig: Signature = parse(&compact)?;
+            s.verify(&parse(&msg[..])?, &sig, &parse(&pk.serialize_uncompressed())?)?;
+            assert_eq!(parse::<SecretKey>(&sk.secret_bytes()[..])?, sk);
+            Ok(())
+        };
+        assert_eq!(verify(), Ok(()));
+
+        assert_eq!(parse::<Signature>(&compact[..63]), Err(InvalidSignatureLength));
+        assert_eq!(parse::<Signature>(&sig.serialize_der(&s)), Err(InvalidSignatureLength));
+        assert_eq!(parse::<Signature>(&[0xff; 64]), Err(SignatureOutOfRange));
+        assert_eq!(parse::<Message>(&[0; 33]), Err(InvalidMessage));
+        assert_eq!(parse::<PublicKey>(&[1, 2, 3]), Err(super::Error::InvalidPublicKeyLength));
+        assert_eq!(parse::<SecretKey>(&[0; 32]), Err(super::Error::InvalidSecretKey));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_message_digest_streaming() {
+        use super::MessageDigest;
+
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+
+        let mut payload = vec![0u8; 100000];
+        thread_rng().fill_bytes(&mut payload);
+
+        let mut digest = MessageDigest::new();
+        for chunk in payload.chunks(4097) {
+            digest.update(chunk);
+        }
+        let plain = Message::from_digest(::hashes::sha256(&payload));
+        assert_eq!(digest.clone().finalize(), plain);
+
+        let sig = digest.finalize_and_sign(&s, &sk);
+        assert_eq!(sig, s.sign(&plain, &sk));
+
+        let mut digest = MessageDigest::new();
+        digest.update(&payload);
+        assert_eq!(digest.finalize_and_verify(&s, &sig, &pk), Ok(()));
+
+        let mut digest = MessageDigest::new();
+        digest.update(&payload[1..]);
+        assert_eq!(digest.finalize_and_verify(&s, &sig, &pk), Err(IncorrectSignature));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn test_message_from_hashed_data() {
+        let msg = Message::from_hashed_data(b"abc");
+        assert_eq!(&msg[..], &hex!("8d50276da47cdc8c1739177ca9e737c45496ebcd1491302a5df98529c4414abc")[..]);
+        assert_eq!(msg, Message::from_digest(::hashes::tagged_hash(b"rust-secp256k1-sgx/message", b"abc")));
+    }
+
+    #[test]
+    fn test_message_constructors() {
+        assert_eq!(Message::from_slice_nonzero(&[0; constants::MESSAGE_SIZE]), Err(InvalidMessage));
+        assert_eq!(Message::from_slice_nonzero(&[0; constants::MESSAGE_SIZE - 1]), Err(InvalidMessage));
+        let mut data = [0; constants::MESSAGE_SIZE];
+        data[31] = 1;
+        assert_eq!(Message::from_slice_nonzero(&data), Message::from_slice(&data));
+
+        let msg = Message::from_digest(data);
+        assert_eq!(&msg[..], &data[..]);
+        assert!(msg.ct_eq(&Message::from(data)));
+        assert!(!msg.ct_eq(&Message::from([1; constants::MESSAGE_SIZE])));
+    }
+
+    #[test]
+    fn test_message_display_fromstr() {
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let msg: Message = hex.parse().unwrap();
+        assert_eq!(&msg[..], &hex!(hex)[..]);
+        assert_eq!(msg.to_string(), hex);
+        assert_eq!(hex.to_uppercase().parse::<Message>(), Ok(msg));
+
+        assert_eq!("".parse::<Message>(), Err(InvalidMessage));
+        assert_eq!(hex[2..].parse::<Message>(), Err(InvalidMessage));
+        assert_eq!(format!("{}00", hex).parse::<Message>(), Err(InvalidMessage));
+        assert_eq!(hex.replace("b", "x").parse::<Message>(), Err(InvalidMessage));
+    }
+
     #[test]
     fn test_debug_output() {
         let s = Secp256k1::new();
@@ -1023,6 +2881,34 @@ mod tests {
         assert_eq!(&bytes_in[..], &bytes_out[..]);
     }
 
+    #[test]
+    fn test_recov_sig_compact_rsv() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let sk = SecretKey::from_slice(&[2; 32]).unwrap();
+        let sig = s.sign_recoverable(&msg, &sk);
+
+        let (recid, compact) = sig.serialize_compact(&s);
+        let rsv = sig.serialize_compact_rsv();
+        assert_eq!(&rsv[..64], &compact[..]);
+        assert_eq!(rsv[64] as i32, recid.to_i32());
+        assert_eq!(RecoverableSignature::from_compact_rsv(&rsv), Ok(sig));
+
+        let mut legacy = rsv;
+        legacy[64] += 27;
+        assert_eq!(RecoverableSignature::from_compact_rsv(&legacy), Ok(sig));
+
+        for &v in &[4, 26, 31, 0xff] {
+            legacy[64] = v;
+            assert_eq!(RecoverableSignature::from_compact_rsv(&legacy), Err(InvalidRecoveryId));
+        }
+        assert_eq!(RecoverableSignature::from_compact_rsv(&rsv[..64]), Err(InvalidSignatureLength));
+        assert_eq!(RecoverableSignature::from_compact_rsv(&[0xff; 65][..]), Err(InvalidRecoveryId));
+        let mut overflow = [0xff; 65];
+        overflow[64] = 0;
+        assert_eq!(RecoverableSignature::from_compact_rsv(&overflow), Err(SignatureOutOfRange));
+    }
+
     #[test]
     fn test_recov_id_conversion_between_i32() {
         assert!(RecoveryId::from_i32(-1).is_err());
@@ -1048,7 +2934,7 @@ mod tests {
 
         let secp = Secp256k1::new();
         let mut sig = Signature::from_der(&secp, &sig[..]).unwrap();
-        let pk = PublicKey::from_slice(&secp, &pk[..]).unwrap();
+        let pk = PublicKey::from_slice(&pk[..]).unwrap();
         let msg = Message::from_slice(&msg[..]).unwrap();
 
         // without normalization we expect this will fail
@@ -1058,15 +2944,88 @@ mod tests {
         assert_eq!(secp.verify(&msg, &sig, &pk), Ok(()));
     }
 
+    #[test]
+    fn verify_serialized() {
+        use super::Error::{InvalidPublicKeyLength, InvalidPublicKeyPrefix};
+
+        // The high-S signature from `test_low_s`
+        let high = hex!("3046022100839c1fbc5304de944f697c9f4b1d01d1faeba32d751c0f7acb21ac8a0f436a72022100e89bd46bb3a5a62adc679f659b7ce876d83ee297c7a5587b2011c4fcc72eab45");
+        let pk = hex!("031ee99d2b786ab3b0991325f2de8489246a6a3fdb700f6d0511b1d80cf5f4cd43");
+        let mut msg = [0; 32];
+        msg.copy_from_slice(&hex!("a4965ca63b7d8562736ceec36dfa5a11bf426eb65be8ea3f7a49ae363032da0d"));
+
+        let secp = Secp256k1::new();
+        assert_eq!(secp.verify_serialized(&msg, &high, &pk), Ok(()));
+        let full = PublicKey::from_slice(&pk).unwrap().serialize_uncompressed();
+        assert_eq!(secp.verify_serialized(&msg, &high, &full), Ok(()));
+        assert_eq!(secp.verify_serialized(&[1; 32], &high, &pk), Err(IncorrectSignature));
+        assert_eq!(secp.verify_serialized(&msg, &high[..high.len() - 1], &pk), Err(InvalidDerSignature));
+        assert_eq!(secp.verify_serialized(&msg, &high, &pk[..32]), Err(InvalidPublicKeyLength));
+        let mut bad_prefix = pk.clone();
+        bad_prefix[0] = 0x05;
+        assert_eq!(secp.verify_serialized(&msg, &high, &bad_prefix), Err(InvalidPublicKeyPrefix));
+    }
+
+    #[test]
+    fn verify_strict() {
+        use super::Error::{HighS, NonCanonicalDer, NonCanonicalPublicKey, PublicKeyNotOnCurve};
+
+        // The high-S signature from `test_low_s`
+        let high = hex!("3046022100839c1fbc5304de944f697c9f4b1d01d1faeba32d751c0f7acb21ac8a0f436a72022100e89bd46bb3a5a62adc679f659b7ce876d83ee297c7a5587b2011c4fcc72eab45");
+        let pk = hex!("031ee99d2b786ab3b0991325f2de8489246a6a3fdb700f6d0511b1d80cf5f4cd43");
+        let msg = Message::from_slice(&hex!("a4965ca63b7d8562736ceec36dfa5a11bf426eb65be8ea3f7a49ae363032da0d")).unwrap();
+
+        let secp = Secp256k1::new();
+        assert_eq!(secp.verify_strict(&msg, &high, &pk), Err(HighS));
+        let mut sig = Signature::from_der(&secp, &high).unwrap();
+        sig.normalize_s(&secp);
+        let low = sig.serialize_der(&secp);
+        assert_eq!(secp.verify_strict(&msg, &low, &pk), Ok(()));
+        let other = Message::from_slice(&[1; 32]).unwrap();
+        assert_eq!(secp.verify_strict(&other, &low, &pk), Err(IncorrectSignature));
+
+        // Uncompressed keys are fine, hybrid ones are not
+        let full = PublicKey::from_slice(&pk).unwrap().serialize_uncompressed();
+        assert_eq!(secp.verify_strict(&msg, &low, &full), Ok(()));
+        let mut hybrid = full;
+        hybrid[0] = 0x06 | (full[64] & 1);
+        assert!(PublicKey::from_slice(&hybrid).is_ok());
+        assert_eq!(secp.verify_strict(&msg, &low, &hybrid), Err(NonCanonicalPublicKey));
+        assert_eq!(secp.verify_strict(&msg, &low, &pk[..32]), Err(NonCanonicalPublicKey));
+        assert_eq!(secp.verify_strict(&msg, &low, &[]), Err(NonCanonicalPublicKey));
+        let mut off_curve = pk.clone();
+        off_curve[32] ^= 1;
+        if PublicKey::from_slice(&off_curve).is_err() {
+            assert_eq!(secp.verify_strict(&msg, &low, &off_curve), Err(PublicKeyNotOnCurve));
+        }
+
+        // An extra zero byte in front of R
+        let mut padded = vec![0x30, low[1] + 1, 0x02, low[3] + 1, 0x00];
+        padded.extend_from_slice(&low[4..]);
+        assert!(Signature::from_der_lax(&secp, &padded).is_ok());
+        assert_eq!(secp.verify_strict(&msg, &padded, &pk), Err(NonCanonicalDer));
+        // A trailing byte, a sighash byte among them
+        let mut trailing = low.clone();
+        trailing.push(0x01);
+        assert_eq!(secp.verify_strict(&msg, &trailing, &pk), Err(NonCanonicalDer));
+        // A negative S
+        let mut negative = low.clone();
+        let s_at = 6 + low[3] as usize;
+        negative[s_at] |= 0x80;
+        assert_eq!(secp.verify_strict(&msg, &negative, &pk), Err(NonCanonicalDer));
+        assert_eq!(secp.verify_strict(&msg, &[], &pk), Err(NonCanonicalDer));
+        assert_eq!(secp.verify_strict(&msg, &low[..low.len() - 1], &pk), Err(NonCanonicalDer));
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn test_signature_serde() {
-        use serde_test::{Token, assert_tokens};
+        use serde_test::{Configure, Token, assert_tokens};
 
         let s = Secp256k1::new();
 
         let msg = Message::from_slice(&[1; 32]).unwrap();
-        let sk = SecretKey::from_slice(&s, &[2; 32]).unwrap();
+        let sk = SecretKey::from_slice(&[2; 32]).unwrap();
         let sig = s.sign(&msg, &sk);
         static SIG_BYTES: [u8; 71] = [
             48, 69, 2, 33, 0, 157, 11, 173, 87, 103, 25, 211, 42, 231, 107, 237,
@@ -1076,16 +3035,248 @@ mod tests {
             147, 247, 99, 25, 15, 103, 118
         ];
 
-        assert_tokens(&sig, &[Token::BorrowedBytes(&SIG_BYTES[..])]);
+        static SIG_HEX: &'static str = "3045022100\
+            9d0bad576719d32ae76bedb34c774866673cbde3f4e12951555c9408e6ce774b\
+            02202876e7102f204f6bfee26c967c3926ce702cf97d4b010062e193f763190f6776";
+
+        assert_tokens(&sig.compact(), &[Token::BorrowedBytes(&SIG_BYTES[..])]);
+        assert_tokens(&sig.readable(), &[Token::BorrowedStr(SIG_HEX)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_message_serde() {
+        use serde_test::{Configure, Token, assert_tokens};
+
+        static MSG_BYTES: [u8; 32] = [0x5a; 32];
+        static MSG_HEX: &'static str = "5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a5a";
+
+        let msg = Message::from_slice(&MSG_BYTES).unwrap();
+        assert_tokens(&msg.compact(), &[Token::BorrowedBytes(&MSG_BYTES[..])]);
+        assert_tokens(&msg.readable(), &[Token::BorrowedStr(MSG_HEX)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_recoverable_signature_serde() {
+        use serde_test::{Configure, Readable, Token, assert_tokens, assert_de_tokens_error};
+
+        static RSV_HEX: &'static str = "\
+            6673ffad2147741f04772b6f921f0ba6af0c1e77fc439e65c36dedf4092e8898\
+            4c1a971652e0ada880120ef8025e709fff2080c4a39aae068d12eed009b68c89\
+            01";
+
+        static RSV_BYTES: [u8; 65] = [
+            0x66, 0x73, 0xff, 0xad, 0x21, 0x47, 0x74, 0x1f,
+            0x04, 0x77, 0x2b, 0x6f, 0x92, 0x1f, 0x0b, 0xa6,
+            0xaf, 0x0c, 0x1e, 0x77, 0xfc, 0x43, 0x9e, 0x65,
+            0xc3, 0x6d, 0xed, 0xf4, 0x09, 0x2e, 0x88, 0x98,
+            0x4c, 0x1a, 0x97, 0x16, 0x52, 0xe0, 0xad, 0xa8,
+            0x80, 0x12, 0x0e, 0xf8, 0x02, 0x5e, 0x70, 0x9f,
+            0xff, 0x20, 0x80, 0xc4, 0xa3, 0x9a, 0xae, 0x06,
+            0x8d, 0x12, 0xee, 0xd0, 0x09, 0xb6, 0x8c, 0x89,
+            0x01
+        ];
+
+        let sig = RecoverableSignature::from_compact_rsv(&RSV_BYTES).unwrap();
+
+        assert_tokens(&sig.compact(), &[Token::BorrowedBytes(&RSV_BYTES[..])]);
+        assert_tokens(&sig.readable(), &[Token::BorrowedStr(RSV_HEX)]);
+        assert_de_tokens_error::<Readable<RecoverableSignature>>(&[Token::BorrowedStr(&RSV_HEX[2..])],
+                                                                 "secp: malformed signature");
+    }
+}
+
+#[cfg(all(test, feature = "verify-only"))]
+mod verify_only_tests {
+    use test_vectors::{MESSAGE, PUBLIC_KEY, SIGNATURE};
+    use super::{Message, PublicKey, Secp256k1, Signature};
+    use super::Error::IncorrectSignature;
+
+    #[test]
+    fn verify() {
+        let secp = Secp256k1::verification_only();
+        assert!(secp.verify_self_test());
+        assert!(Secp256k1::new_verify_only_static().verify_self_test());
+        assert!(Secp256k1::builder().verification().build().unwrap().verify_self_test());
+
+        let msg = Message::from_slice(&MESSAGE).unwrap();
+        let pk = PublicKey::from_slice(&PUBLIC_KEY).unwrap();
+        let sig = Signature::from_compact(&secp, &SIGNATURE).unwrap();
+        assert_eq!(pk.verify(&msg, &sig), Ok(()));
+        assert_eq!(pk.verify(&Message::from_slice(&[0xa5; 32]).unwrap(), &sig), Err(IncorrectSignature));
+        assert_eq!(Signature::from_der(&secp, &sig.serialize_der(&secp)), Ok(sig));
+    }
+}
+
+#[cfg(test)]
+mod send_sync_tests {
+    use super::*;
+
+    fn send_sync<T: Send + Sync>() {}
+
+    /// Pins down which public types can be moved and shared between enclave
+    /// threads, so that a change losing `Send` or `Sync` fails here rather
+    /// than in a multi-threaded enclave. `VaultSigner` and `FileVaultSigner`
+    /// are neither: they borrow a `RefCell`, which confines them to one
+    /// thread.
+    #[test]
+    fn send_sync_types() {
+        send_sync::<Secp256k1<None>>();
+        send_sync::<Secp256k1<VerifyOnly>>();
+        send_sync::<ContextBuilder<VerifyOnly>>();
+        send_sync::<CreationCost>();
+        send_sync::<PublicKey>();
+        send_sync::<XOnlyPublicKey>();
+        send_sync::<key::Parity>();
+        send_sync::<key::AllowedFormats>();
+        send_sync::<key::PublicKeyFormat>();
+        send_sync::<Signature>();
+        send_sync::<RecoverableSignature>();
+        send_sync::<RecoveryId>();
+        send_sync::<Message>();
+        send_sync::<Error>();
+        send_sync::<Scalar>();
+        send_sync::<PreparedPublicKey>();
+        send_sync::<scratch::ScratchSpace<'static>>();
+        send_sync::<pool::ContextPool<VerifyOnly>>();
+        send_sync::<pool::PooledContext<'static, VerifyOnly>>();
+        send_sync::<callback::CallbackKind>();
+        send_sync::<attestation::TcbLevel>();
+        send_sync::<attestation::EnclaveIdentity>();
+        #[cfg(feature = "context-allocator")]
+        send_sync::<allocator::HeapAllocator>();
+
+        #[cfg(not(feature = "verify-only"))]
+        {
+            send_sync::<Secp256k1<SignOnly>>();
+            send_sync::<Secp256k1<All>>();
+            send_sync::<ContextBuilder<All>>();
+            #[cfg(any(target_env = "sgx", feature = "sgx", feature = "std"))]
+            send_sync::<global::GlobalContext>();
+            send_sync::<SecretKey>();
+            send_sync::<KeyPair>();
+            #[cfg(not(feature = "no-secret-export"))]
+            send_sync::<key::DisplaySecret>();
+            send_sync::<ecdh::SharedSecret>();
+            send_sync::<masked::MaskedSecretKey>();
+            send_sync::<vault::Vault>();
+            send_sync::<vault::KeyHandle>();
+            send_sync::<vault::PresignatureId>();
+            send_sync::<vault::KeyUsage>();
+            send_sync::<vault::RateLimit>();
+            send_sync::<vault::ApprovalPolicy>();
+            send_sync::<vault::Approval>();
+            #[cfg(feature = "protected-fs")]
+            send_sync::<vault::FileBackedVault>();
+            #[cfg(feature = "trusted-time")]
+            send_sync::<vault::TrustedTimeClock>();
+        }
+
+        #[cfg(feature = "test-determinism")]
+        send_sync::<rng::HmacDrbg>();
+
+        #[cfg(feature = "hashes")]
+        {
+            send_sync::<KeyId>();
+            send_sync::<MessageDigest>();
+            send_sync::<cache::VerificationCache>();
+            send_sync::<cache::CacheStats>();
+            send_sync::<generator::Generator>();
+            send_sync::<scheme::SignScheme<'static>>();
+            send_sync::<hashes::Sha256>();
+            send_sync::<hashes::Sha512>();
+            send_sync::<hashes::HmacSha256>();
+            send_sync::<hashes::HmacSha512>();
+            send_sync::<hashes::TaggedHasher>();
+            send_sync::<hashes::Keccak256>();
+            send_sync::<hashes::Ripemd160>();
+        }
+
+        #[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+        {
+            send_sync::<audit::UsageReport>();
+            send_sync::<audit::DestructionCertificate>();
+            send_sync::<blind::BlindSigner>();
+            send_sync::<blind::Unblinder>();
+            send_sync::<certificate::CertificateBody>();
+            send_sync::<certificate::KeyCertificate>();
+            send_sync::<commitment::NonceCommitment>();
+            send_sync::<cosign::Round>();
+            send_sync::<cosign::CoSignature>();
+            send_sync::<cosign::Coordinator>();
+            send_sync::<cosign::SignerSession>();
+            send_sync::<halfagg::AggregateSignature>();
+            send_sync::<halfagg::Aggregator>();
+            send_sync::<oprf::Blind>();
+            send_sync::<rangeproof::RangeProof>();
+            send_sync::<ring::KeyImage>();
+            send_sync::<ring::RingSignature>();
+            send_sync::<rotation::RotationRecord>();
+            send_sync::<silentpayments::FoundOutput>();
+            send_sync::<silentpayments::Scanner>();
+            send_sync::<two_party::PartialTerms>();
+            send_sync::<two_party::Party1Session>();
+            send_sync::<two_party::Party2Session>();
+            send_sync::<whitelist::Whitelist>();
+            send_sync::<whitelist::WhitelistSignature>();
+            send_sync::<wire::Payload>();
+            send_sync::<wire::WireMessage>();
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            send_sync::<metrics::Operation>();
+            send_sync::<metrics::OperationStats>();
+            send_sync::<metrics::Counters>();
+        }
+        #[cfg(feature = "bench")]
+        {
+            send_sync::<bench::Rdtsc>();
+            send_sync::<bench::Benchmark>();
+            send_sync::<bench::Measurement>();
+            send_sync::<bench::Report>();
+        }
+        #[cfg(feature = "dudect")]
+        {
+            send_sync::<dudect::Target>();
+            send_sync::<dudect::Leakage>();
+        }
+        #[cfg(feature = "psbt")]
+        {
+            send_sync::<psbt::SighashType>();
+            send_sync::<psbt::Spend>();
+        }
+        #[cfg(feature = "sealing")]
+        {
+            send_sync::<envelope::SealedKind>();
+            send_sync::<envelope::Envelope>();
+            send_sync::<sealing::KeyPolicy>();
+            send_sync::<sealing::SealingKeyDerivation>();
+        }
+        #[cfg(feature = "proto")]
+        {
+            send_sync::<proto::ProtoPublicKey>();
+            send_sync::<proto::ProtoSignature>();
+        }
+        #[cfg(feature = "rkyv")]
+        {
+            send_sync::<ArchivedPublicKey>();
+            send_sync::<ArchivedXOnlyPublicKey>();
+            send_sync::<ArchivedSignature>();
+            send_sync::<ArchivedMessage>();
+        }
     }
 }
 
 #[cfg(all(test, feature = "unstable"))]
 mod benches {
-    use rand::{Rng, thread_rng};
+    use rand::Rng;
+    use rand_core::{RngCore, CryptoRng};
     use test::{Bencher, black_box};
 
-    use super::{Secp256k1, Message};
+    use rng::{SgxRngAdapter, thread_rng};
+    use super::{Secp256k1, Message, PreparedPublicKey, PublicKey, Scalar};
 
     #[bench]
     pub fn generate(bh: &mut Bencher) {
@@ -1093,9 +3284,10 @@ mod benches {
         impl Rng for CounterRng {
             fn next_u32(&mut self) -> u32 { self.0 += 1; self.0 }
         }
+        impl CryptoRng for SgxRngAdapter<CounterRng> {}
 
         let s = Secp256k1::new();
-        let mut r = CounterRng(0);
+        let mut r = SgxRngAdapter::new(CounterRng(0));
         bh.iter( || {
             let (sk, pk) = s.generate_keypair(&mut r);
             black_box(sk);
@@ -1132,6 +3324,56 @@ mod benches {
         });
     }
 
+    #[bench]
+    pub fn bench_verify_prepared(bh: &mut Bencher) {
+        let s = Secp256k1::new();
+        let mut msg = [0u8; 32];
+        thread_rng().fill_bytes(&mut msg);
+        let msg = Message::from_slice(&msg).unwrap();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let sig = s.sign(&msg, &sk);
+        let prepared = PreparedPublicKey::new(&pk);
+
+        bh.iter(|| {
+            let res = s.verify_prepared(&msg, &sig, &prepared).unwrap();
+            black_box(res);
+        });
+    }
+
+    #[bench]
+    pub fn bench_msm_100(bh: &mut Bencher) {
+        let s = Secp256k1::new();
+        let terms: Vec<_> = (0..100).map(|_| {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            (Scalar::from_secret_key(&sk), pk)
+        }).collect();
+
+        bh.iter(|| {
+            let res = PublicKey::msm(&s, &terms, 1 << 20).unwrap();
+            black_box(res);
+        });
+    }
+
+    #[bench]
+    pub fn bench_mul_combine_100(bh: &mut Bencher) {
+        let s = Secp256k1::new();
+        let terms: Vec<_> = (0..100).map(|_| {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            (Scalar::from_secret_key(&sk), pk)
+        }).collect();
+
+        bh.iter(|| {
+            let mut sum = terms[0].1;
+            sum.mul_assign(&s, &terms[0].0).unwrap();
+            for &(ref scalar, pk) in terms[1..].iter() {
+                let mut term = pk;
+                term.mul_assign(&s, scalar).unwrap();
+                sum = sum.combine(&s, &term).unwrap();
+            }
+            black_box(sum);
+        });
+    }
+
     #[bench]
     pub fn bench_recover(bh: &mut Bencher) {
         let s = Secp256k1::new();
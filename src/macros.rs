@@ -16,19 +16,25 @@
 // This is a macro that routinely comes in handy
 macro_rules! impl_array_newtype {
     ($thing:ident, $ty:ty, $len:expr) => {
+        impl_array_newtype!($thing, $ty, $len, pub);
+        impl_array_newtype_index!($thing, $ty);
+    };
+    // Without indexing, and with raw pointers only as visible as `$vis`, for
+    // types whose contents must not leave the crate
+    ($thing:ident, $ty:ty, $len:expr, $vis:vis) => {
         impl Copy for $thing {}
 
         impl $thing {
             #[inline]
             /// Converts the object to a raw pointer for FFI interfacing
-            pub fn as_ptr(&self) -> *const $ty {
+            $vis fn as_ptr(&self) -> *const $ty {
                 let &$thing(ref dat) = self;
                 dat.as_ptr()
             }
 
             #[inline]
             /// Converts the object to a mutable raw pointer for FFI interfacing
-            pub fn as_mut_ptr(&mut self) -> *mut $ty {
+            $vis fn as_mut_ptr(&mut self) -> *mut $ty {
                 let &mut $thing(ref mut dat) = self;
                 dat.as_mut_ptr()
             }
@@ -42,10 +48,12 @@ macro_rules! impl_array_newtype {
             pub fn is_empty(&self) -> bool { false }
         }
 
+        // Many of these types hold secret material, so equality never
+        // exits early on the first differing byte
         impl PartialEq for $thing {
             #[inline]
             fn eq(&self, other: &$thing) -> bool {
-                &self[..] == &other[..]
+                ::constant_time_eq(&self.0[..], &other.0[..])
             }
         }
 
@@ -54,37 +62,38 @@ macro_rules! impl_array_newtype {
         impl PartialOrd for $thing {
             #[inline]
             fn partial_cmp(&self, other: &$thing) -> Option<::std::cmp::Ordering> {
-                self[..].partial_cmp(&other[..])
+                Some(self.cmp(other))
             }
         }
 
         impl Ord for $thing {
             #[inline]
             fn cmp(&self, other: &$thing) -> ::std::cmp::Ordering {
-                self[..].cmp(&other[..])
+                self.0[..].cmp(&other.0[..])
             }            
         }
 
         impl Clone for $thing {
             #[inline]
             fn clone(&self) -> $thing {
-                unsafe {
-                    use std::intrinsics::copy_nonoverlapping;
-                    use std::mem;
-                    let mut ret: $thing = mem::uninitialized();
-                    copy_nonoverlapping(self.as_ptr(),
-                                        ret.as_mut_ptr(),
-                                        $len);
-                    ret
-                }
+                *self
             }
         }
+    }
+}
 
+macro_rules! impl_array_newtype_index {
+    ($thing:ident, $ty:ty) => {
+        impl_array_newtype_index!($thing, $ty, {});
+    };
+    // With `$check` run before every index, e.g. to refuse access to secrets
+    ($thing:ident, $ty:ty, $check:block) => {
         impl ::std::ops::Index<usize> for $thing {
             type Output = $ty;
 
             #[inline]
             fn index(&self, index: usize) -> &$ty {
+                $check
                 let &$thing(ref dat) = self;
                 &dat[index]
             }
@@ -95,6 +104,7 @@ macro_rules! impl_array_newtype {
 
             #[inline]
             fn index(&self, index: ::std::ops::Range<usize>) -> &[$ty] {
+                $check
                 let &$thing(ref dat) = self;
                 &dat[index]
             }
@@ -105,6 +115,7 @@ macro_rules! impl_array_newtype {
 
             #[inline]
             fn index(&self, index: ::std::ops::RangeTo<usize>) -> &[$ty] {
+                $check
                 let &$thing(ref dat) = self;
                 &dat[index]
             }
@@ -115,6 +126,7 @@ macro_rules! impl_array_newtype {
 
             #[inline]
             fn index(&self, index: ::std::ops::RangeFrom<usize>) -> &[$ty] {
+                $check
                 let &$thing(ref dat) = self;
                 &dat[index]
             }
@@ -125,6 +137,7 @@ macro_rules! impl_array_newtype {
 
             #[inline]
             fn index(&self, _: ::std::ops::RangeFull) -> &[$ty] {
+                $check
                 let &$thing(ref dat) = self;
                 &dat[..]
             }
@@ -158,3 +171,49 @@ macro_rules! impl_raw_debug {
         }
      }
 }
+
+/// Evaluates `$body`, reporting it as the operation `$op` to the metrics
+/// sink when compiled with the "metrics" feature; `$succeeded` tells from
+/// the result whether the operation succeeded. The `batch` form also
+/// reports the number of items, `$size`.
+macro_rules! measure {
+    ($op:ident, $succeeded:expr, $body:expr) => {{
+        #[cfg(feature = "metrics")]
+        let ret = ::metrics::measure(::metrics::Operation::$op, $succeeded, || $body);
+        #[cfg(not(feature = "metrics"))]
+        let ret = $body;
+        ret
+    }};
+    (batch $op:ident, $size:expr, $succeeded:expr, $body:expr) => {{
+        #[cfg(feature = "metrics")]
+        let ret = ::metrics::measure_batch(::metrics::Operation::$op, $size, $succeeded, || $body);
+        #[cfg(not(feature = "metrics"))]
+        let ret = $body;
+        ret
+    }};
+}
+
+/// Logs through the `log` crate under the "secp256k1" target when compiled
+/// with the "debug-logging" feature, and does nothing otherwise. Never pass
+/// it anything derived from secret material.
+macro_rules! debug_log {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "debug-logging")]
+        { ::log::$level!(target: "secp256k1", $($arg)+); }
+        // Nothing is formatted, but the arguments still count as used
+        #[cfg(not(feature = "debug-logging"))]
+        { let _ = format_args!($($arg)+); }
+    };
+}
+
+/// Decodes a hex string into a `Vec<u8>` for test vectors, panicking on
+/// invalid hex
+#[cfg(test)]
+macro_rules! hex {
+    ($hex:expr) => {{
+        let hex: &str = $hex;
+        let mut buf = vec![0; hex.len() / 2];
+        assert_eq!(::hex::decode_from(hex, &mut buf), Some(buf.len()), "invalid hex {:?}", hex);
+        buf
+    }}
+}
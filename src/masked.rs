@@ -0,0 +1,170 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Masked secret keys
+//! Storage for long-lived secret keys which keeps them XOR-split into two
+//! random shares, so that a single leaked memory region (a stale page, a
+//! cold-boot image, a partial disclosure bug) does not reveal the key. The
+//! key is only recombined on the stack for the duration of an operation,
+//! wiped afterwards, and the shares are then re-randomized.
+//!
+//! This raises the bar against memory disclosure; it does not protect
+//! against an attacker who can observe the enclave while an operation is in
+//! progress. Requires compilation with the "rand" feature.
+
+use std::fmt;
+
+use rand_core::{RngCore, CryptoRng};
+
+use cleanup::wipe;
+use constants;
+use ecdh::SharedSecret;
+use key::{SecretKey, PublicKey};
+use {Message, Secp256k1, Signature, Signing};
+
+/// A secret key stored as two XOR shares, each of which is uniformly random
+/// on its own. The shares are wiped on drop.
+pub struct MaskedSecretKey {
+    share: [u8; constants::SECRET_KEY_SIZE],
+    mask: [u8; constants::SECRET_KEY_SIZE],
+}
+
+impl fmt::Debug for MaskedSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("MaskedSecretKey(<redacted>)")
+    }
+}
+
+impl Drop for MaskedSecretKey {
+    fn drop(&mut self) {
+        wipe(&mut self.share);
+        wipe(&mut self.mask);
+    }
+}
+
+impl MaskedSecretKey {
+    /// Splits `sk` into two fresh shares. The caller remains responsible
+    /// for its own copy of `sk`.
+    pub fn new<R: RngCore + CryptoRng>(sk: &SecretKey, rng: &mut R) -> MaskedSecretKey {
+        let mut ret = MaskedSecretKey {
+            share: [0; constants::SECRET_KEY_SIZE],
+            mask: [0; constants::SECRET_KEY_SIZE],
+        };
+        rng.fill_bytes(&mut ret.mask);
+        for ((share, &mask), &byte) in ret.share.iter_mut().zip(&ret.mask).zip(sk.secret_bytes()) {
+            *share = byte ^ mask;
+        }
+        ret
+    }
+
+    /// Re-randomizes the shares without changing the key they encode
+    pub fn remask<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        let mut delta = [0; constants::SECRET_KEY_SIZE];
+        rng.fill_bytes(&mut delta);
+        for ((share, mask), &d) in self.share.iter_mut().zip(self.mask.iter_mut()).zip(&delta) {
+            *share ^= d;
+            *mask ^= d;
+        }
+        wipe(&mut delta);
+    }
+
+    /// Recombines the key, passes it to `f`, wipes the recombined copy and
+    /// re-randomizes the shares. `f` should not let the key escape.
+    pub fn with_secret_key<R, F, T>(&mut self, rng: &mut R, f: F) -> T
+        where R: RngCore + CryptoRng,
+              F: FnOnce(&SecretKey) -> T
+    {
+        let mut bytes = [0; constants::SECRET_KEY_SIZE];
+        for ((byte, &share), &mask) in bytes.iter_mut().zip(&self.share).zip(&self.mask) {
+            *byte = share ^ mask;
+        }
+        let mut sk = SecretKey::from_slice(&bytes).expect("shares always encode a valid key");
+        wipe(&mut bytes);
+
+        let ret = f(&sk);
+
+        sk.wipe();
+        self.remask(rng);
+        ret
+    }
+
+    /// Constructs an ECDSA signature for `msg`, recombining the key only for
+    /// the duration of the call
+    pub fn sign<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, msg: &Message,
+                                                    rng: &mut R) -> Signature {
+        self.with_secret_key(rng, |sk| secp.sign(msg, sk))
+    }
+
+    /// Computes an ECDH shared secret with `point`, recombining the key only
+    /// for the duration of the call
+    pub fn shared_secret<C, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, point: &PublicKey,
+                                                    rng: &mut R) -> SharedSecret {
+        self.with_secret_key(rng, |sk| SharedSecret::new(secp, point, sk))
+    }
+
+    /// Computes the public key, recombining the key only for the duration
+    /// of the call
+    pub fn public_key<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>,
+                                                          rng: &mut R) -> PublicKey {
+        self.with_secret_key(rng, |sk| PublicKey::from_secret_key(secp, sk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MaskedSecretKey;
+    use ecdh::SharedSecret;
+    use key::{SecretKey, PublicKey};
+    use rng::thread_rng;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn shares_encode_key() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let mut masked = MaskedSecretKey::new(&sk, &mut thread_rng());
+        assert!(masked.share[..] != sk.secret_bytes()[..]);
+        assert!(masked.mask[..] != sk.secret_bytes()[..]);
+
+        let (share, mask) = (masked.share, masked.mask);
+        masked.remask(&mut thread_rng());
+        assert!(masked.share != share && masked.mask != mask);
+
+        let (share, mask) = (masked.share, masked.mask);
+        assert_eq!(masked.with_secret_key(&mut thread_rng(), |k| *k), sk);
+        // Using the key re-randomizes the shares
+        assert!(masked.share != share && masked.mask != mask);
+        assert_eq!(masked.public_key(&s, &mut thread_rng()), pk);
+    }
+
+    #[test]
+    fn operations_match_plain_key() {
+        let s = Secp256k1::new();
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        let (other_sk, other_pk) = s.generate_keypair(&mut thread_rng());
+        let mut masked = MaskedSecretKey::new(&sk, &mut thread_rng());
+
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        assert_eq!(masked.sign(&s, &msg, &mut thread_rng()), s.sign(&msg, &sk));
+        assert_eq!(masked.shared_secret(&s, &other_pk, &mut thread_rng()),
+                   SharedSecret::new(&s, &PublicKey::from_secret_key(&s, &sk), &other_sk));
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let masked = MaskedSecretKey::new(&sk, &mut thread_rng());
+        assert_eq!(format!("{:?}", masked), "MaskedSecretKey(<redacted>)");
+    }
+}
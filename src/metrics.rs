@@ -0,0 +1,376 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Metrics
+//! Instrumentation of signing, batch signing, verification, ECDH, batch
+//! tweaking and vault rate limits, so
+//! that operators of enclave signing services can see what their enclaves
+//! are doing. Every instrumented operation is reported to the `MetricsSink`
+//! installed with `set_sink`, which can forward it over an OCALL or an
+//! attested channel, or keep count like the ready-made `Counters` does.
+//!
+//! There is no portable clock inside an enclave, so operations are only
+//! timed if the sink provides one with `MetricsSink::now_nanos`. With no
+//! sink installed the instrumentation costs one atomic load per operation.
+//! Requires compilation with the "metrics" feature.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// An instrumented operation
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operation {
+    /// `Secp256k1::sign`
+    Sign,
+    /// `Secp256k1::sign_recoverable`
+    SignRecoverable,
+    /// `Secp256k1::verify`
+    Verify,
+    /// `Secp256k1::verify_prepared`
+    VerifyPrepared,
+    /// Computation of an ECDH shared secret
+    Ecdh,
+    /// `SecretKey::add_tweak_batch` and `PublicKey::add_exp_tweak_batch`
+    TweakBatch,
+    /// A use of a rate-limited `vault` key, failing if the rate limit
+    /// refused it
+    RateLimit,
+    /// `Secp256k1::sign_batch` and its variants
+    SignBatch,
+}
+
+/// The number of `Operation`s
+pub const OPERATION_COUNT: usize = 8;
+
+impl Operation {
+    /// Every operation, in the order of `Operation::index`
+    pub const ALL: [Operation; OPERATION_COUNT] = [
+        Operation::Sign,
+        Operation::SignRecoverable,
+        Operation::Verify,
+        Operation::VerifyPrepared,
+        Operation::Ecdh,
+        Operation::TweakBatch,
+        Operation::RateLimit,
+        Operation::SignBatch,
+    ];
+
+    /// A distinct index below `OPERATION_COUNT`, for keeping per-operation
+    /// data in arrays
+    pub fn index(self) -> usize {
+        match self {
+            Operation::Sign => 0,
+            Operation::SignRecoverable => 1,
+            Operation::Verify => 2,
+            Operation::VerifyPrepared => 3,
+            Operation::Ecdh => 4,
+            Operation::TweakBatch => 5,
+            Operation::RateLimit => 6,
+            Operation::SignBatch => 7,
+        }
+    }
+}
+
+/// A destination for metrics. It is called on whichever thread ran the
+/// operation, possibly several at once, and must not panic.
+pub trait MetricsSink: Sync {
+    /// Called after every instrumented operation with whether it succeeded
+    /// and, if the sink has a clock, how many nanoseconds it took
+    fn record(&self, op: Operation, succeeded: bool, elapsed_nanos: Option<u64>);
+
+    /// Called before a batch operation with the number of items in it
+    fn record_batch(&self, _op: Operation, _size: usize) {}
+
+    /// The current time in nanoseconds since any fixed point, used to time
+    /// operations. The default of `None` turns timing off.
+    fn now_nanos(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The installed sink, guarded by a spin lock like `callback::record`'s
+/// buffer. `ENABLED` lets operations skip the lock when there is none.
+struct Slot {
+    lock: AtomicBool,
+    sink: UnsafeCell<Option<&'static MetricsSink>>,
+}
+
+unsafe impl Sync for Slot {}
+
+static SLOT: Slot = Slot { lock: AtomicBool::new(false), sink: UnsafeCell::new(None) };
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn with_sink<T, F: FnOnce(&mut Option<&'static MetricsSink>) -> T>(f: F) -> T {
+    while SLOT.lock.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {}
+    let ret = f(unsafe { &mut *SLOT.sink.get() });
+    SLOT.lock.store(false, Ordering::Release);
+    ret
+}
+
+/// Sends metrics for all subsequent operations to `sink`, replacing any
+/// sink installed before
+pub fn set_sink(sink: &'static MetricsSink) {
+    with_sink(|slot| *slot = Some(sink));
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Stops sending metrics anywhere
+pub fn clear_sink() {
+    ENABLED.store(false, Ordering::Release);
+    with_sink(|slot| *slot = None);
+}
+
+fn sink() -> Option<&'static MetricsSink> {
+    if !ENABLED.load(Ordering::Acquire) {
+        return None;
+    }
+    with_sink(|slot| *slot)
+}
+
+/// Runs `f`, reporting it to the installed sink as `op`
+pub(crate) fn measure<T, F: FnOnce() -> T>(op: Operation, succeeded: fn(&T) -> bool, f: F) -> T {
+    let sink = match sink() {
+        Some(sink) => sink,
+        None => return f(),
+    };
+    let start = sink.now_nanos();
+    let ret = f();
+    let elapsed = match (start, sink.now_nanos()) {
+        (Some(start), Some(end)) => Some(end.wrapping_sub(start)),
+        _ => None,
+    };
+    sink.record(op, succeeded(&ret), elapsed);
+    ret
+}
+
+/// Runs the batch operation `f` on `size` items, reporting it to the
+/// installed sink as `op`
+pub(crate) fn measure_batch<T, F: FnOnce() -> T>(op: Operation, size: usize, succeeded: fn(&T) -> bool, f: F) -> T {
+    if let Some(sink) = sink() {
+        sink.record_batch(op, size);
+    }
+    measure(op, succeeded, f)
+}
+
+/// The number of buckets in a timing histogram. Bucket `i` counts
+/// operations which took between `2^i` and `2^(i + 1)` nanoseconds (bucket
+/// 0 also counts those which took none), and the last bucket everything
+/// slower.
+pub const HISTOGRAM_BUCKETS: usize = 40;
+
+/// The numbers recorded by `Counters` for one operation
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OperationStats {
+    /// Times the operation ran
+    pub calls: usize,
+    /// Times it failed
+    pub failures: usize,
+    /// Total number of items over all batches
+    pub batch_items: usize,
+    /// Timings, if the sink had a clock
+    pub histogram: [usize; HISTOGRAM_BUCKETS],
+}
+
+struct OperationCounters {
+    calls: AtomicUsize,
+    failures: AtomicUsize,
+    batch_items: AtomicUsize,
+    histogram: [AtomicUsize; HISTOGRAM_BUCKETS],
+}
+
+// Only used to initialize arrays, for which each use makes a fresh copy
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO_COUNTERS: OperationCounters = OperationCounters {
+    calls: ZERO,
+    failures: ZERO,
+    batch_items: ZERO,
+    histogram: [ZERO; HISTOGRAM_BUCKETS],
+};
+
+/// A sink which counts calls, failures and batch items, and keeps a timing
+/// histogram, for each operation. It can live in a `static`:
+///
+/// ```ignore
+/// static COUNTERS: Counters = Counters::new();
+/// metrics::set_sink(&COUNTERS);
+/// ```
+///
+/// Wrap it in a sink with a `now_nanos` to get timings.
+pub struct Counters {
+    operations: [OperationCounters; OPERATION_COUNT],
+}
+
+impl Counters {
+    /// Creates a set of counters, all zero
+    pub const fn new() -> Counters {
+        Counters { operations: [ZERO_COUNTERS; OPERATION_COUNT] }
+    }
+
+    /// The numbers recorded so far for `op`
+    pub fn get(&self, op: Operation) -> OperationStats {
+        let counters = &self.operations[op.index()];
+        let mut histogram = [0; HISTOGRAM_BUCKETS];
+        for (count, counter) in histogram.iter_mut().zip(counters.histogram.iter()) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+        OperationStats {
+            calls: counters.calls.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            batch_items: counters.batch_items.load(Ordering::Relaxed),
+            histogram,
+        }
+    }
+
+    /// Zeroes every counter
+    pub fn reset(&self) {
+        for counters in self.operations.iter() {
+            counters.calls.store(0, Ordering::Relaxed);
+            counters.failures.store(0, Ordering::Relaxed);
+            counters.batch_items.store(0, Ordering::Relaxed);
+            for counter in counters.histogram.iter() {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Default for Counters {
+    fn default() -> Counters {
+        Counters::new()
+    }
+}
+
+impl MetricsSink for Counters {
+    fn record(&self, op: Operation, succeeded: bool, elapsed_nanos: Option<u64>) {
+        let counters = &self.operations[op.index()];
+        counters.calls.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            counters.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(nanos) = elapsed_nanos {
+            let bucket = (63 - (nanos | 1).leading_zeros() as usize).min(HISTOGRAM_BUCKETS - 1);
+            counters.histogram[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_batch(&self, op: Operation, size: usize) {
+        self.operations[op.index()].batch_items.fetch_add(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{clear_sink, set_sink, Counters, MetricsSink, Operation, HISTOGRAM_BUCKETS};
+    use key::{PublicKey, SecretKey};
+    use rng::thread_rng;
+    use scalar::Scalar;
+    use vault::{Clock, RateLimit, Vault};
+    use Error::RateLimited;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn operation_indices() {
+        for (i, op) in Operation::ALL.iter().enumerate() {
+            assert_eq!(op.index(), i);
+        }
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let counters = Counters::new();
+        for &nanos in &[0, 1, 3, 4, 1000, !0] {
+            counters.record(Operation::Verify, true, Some(nanos));
+        }
+        counters.record(Operation::Verify, false, None);
+        let stats = counters.get(Operation::Verify);
+        assert_eq!((stats.calls, stats.failures), (7, 1));
+        assert_eq!(&stats.histogram[..3], &[2, 1, 1]);
+        assert_eq!(stats.histogram[9], 1);
+        assert_eq!(stats.histogram[HISTOGRAM_BUCKETS - 1], 1);
+        counters.reset();
+        assert_eq!(counters.get(Operation::Verify).calls, 0);
+    }
+
+    // A counting sink with a fake clock which ticks 100ns per reading
+    struct Clocked {
+        counters: Counters,
+        clock: AtomicUsize,
+    }
+
+    impl MetricsSink for Clocked {
+        fn record(&self, op: Operation, succeeded: bool, elapsed_nanos: Option<u64>) {
+            self.counters.record(op, succeeded, elapsed_nanos)
+        }
+
+        fn record_batch(&self, op: Operation, size: usize) {
+            self.counters.record_batch(op, size)
+        }
+
+        fn now_nanos(&self) -> Option<u64> {
+            Some(self.clock.fetch_add(100, Ordering::Relaxed) as u64)
+        }
+    }
+
+    static SINK: Clocked = Clocked { counters: Counters::new(), clock: AtomicUsize::new(0) };
+
+    // A vault clock stuck at one time
+    struct Stopped;
+
+    impl Clock for Stopped {
+        fn now(&mut self) -> Option<u64> {
+            Some(1_000)
+        }
+    }
+
+    // The sink is global, so this is the only test which installs one, and
+    // it only checks lower bounds in case other tests run at the same time
+    #[test]
+    fn instrumented_operations() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+
+        set_sink(&SINK);
+        let sig = s.sign(&msg, &sk);
+        assert!(s.verify(&msg, &sig, &pk).is_ok());
+        assert!(s.verify(&Message::from_slice(&[2; 32]).unwrap(), &sig, &pk).is_err());
+        let mut keys = [sk; 3];
+        SecretKey::add_tweak_batch(&s, &mut keys, &Scalar::ONE).unwrap();
+        let mut pks = [pk; 2];
+        PublicKey::add_exp_tweak_batch(&s, &mut pks, &Scalar::ONE).unwrap();
+        let mut vault = Vault::new();
+        vault.set_clock(Stopped);
+        let handle = vault.insert(&s, sk);
+        vault.set_rate_limit(handle, Some(RateLimit { uses: 1, interval: 60, burst: 1 })).unwrap();
+        vault.sign(&s, handle, &msg).unwrap();
+        assert_eq!(vault.sign(&s, handle, &msg), Err(RateLimited));
+        clear_sink();
+        s.sign(&msg, &sk);
+
+        let sign = SINK.counters.get(Operation::Sign);
+        assert!(sign.calls >= 1);
+        // Every operation is timed
+        assert!(sign.histogram.iter().sum::<usize>() >= 1);
+        let verify = SINK.counters.get(Operation::Verify);
+        assert!(verify.calls >= 2 && verify.failures >= 1);
+        let batch = SINK.counters.get(Operation::TweakBatch);
+        assert!(batch.calls >= 2 && batch.batch_items >= 5);
+        let rate_limit = SINK.counters.get(Operation::RateLimit);
+        assert!(rate_limit.calls >= 2 && rate_limit.failures >= 1);
+    }
+}
@@ -0,0 +1,132 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Oblivious PRF
+//! An elliptic curve OPRF: a client learns `F(k, x)` for its input `x` and
+//! the server's key `k`, while the server learns neither `x` nor the
+//! output, as for password hardening and private set membership:
+//!
+//! 1. the client hashes its input to `H(x)` with `PublicKey::from_message_hash`
+//!    and sends `B = r * H(x)` for a random `r` (`blind`);
+//! 2. the server answers with `Z = k * B` (`evaluate`, or
+//!    `vault::Vault::evaluate_oprf` for a key held in a vault);
+//! 3. the client removes the blinding, `N = r^-1 * Z = k * H(x)`
+//!    (`unblind`), and hashes it with its input into the output
+//!    (`finalize`).
+//!
+//! Nothing here proves that the server used the right key. Hashing the
+//! input to the curve takes time that depends on the input, so `blind`
+//! should run where the client's timing can't be observed by others. Every
+//! evaluation is a Diffie-Hellman oracle on the server's key, so OPRF keys
+//! should not be used for anything else. Requires compilation with the
+//! "hashes" feature.
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use hashes::{TaggedHasher, OPRF_FINALIZE_TAG};
+#[cfg(any(test, feature = "rand"))]
+use hashes::OPRF_HASH_TO_CURVE_TAG;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error;
+use {Secp256k1, Verification};
+
+/// The size (in bytes) of an OPRF output
+pub const OPRF_OUTPUT_SIZE: usize = 32;
+
+/// The client's blinding factor `r` for one input, wiped on drop
+pub struct Blind(Scalar);
+
+impl Drop for Blind {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+/// Hashes `input` to the curve and blinds it, returning the blinding factor
+/// to keep and the blinded element to send to the server
+#[cfg(any(test, feature = "rand"))]
+pub fn blind<C: Verification, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R, input: &[u8])
+                                                     -> Result<(Blind, PublicKey), Error> {
+    let r = Blind(Scalar::from_secret_key(&SecretKey::new(secp, rng)));
+    let mut blinded = PublicKey::from_message_hash(OPRF_HASH_TO_CURVE_TAG, input);
+    blinded.mul_assign(secp, &r.0)?;
+    Ok((r, blinded))
+}
+
+/// Evaluates the PRF with the key `sk` on a blinded element, for the
+/// server side
+#[inline]
+pub fn evaluate<C: Verification>(secp: &Secp256k1<C>, sk: &SecretKey, blinded: &PublicKey)
+                                 -> Result<PublicKey, Error> {
+    let mut evaluated = *blinded;
+    let mut k = Scalar::from_secret_key(sk);
+    let ret = evaluated.mul_assign(secp, &k);
+    k.wipe();
+    ret.map(|_| evaluated)
+}
+
+/// Removes the blinding from the server's answer
+pub fn unblind<C: Verification>(secp: &Secp256k1<C>, blind: &Blind, evaluated: &PublicKey)
+                                -> Result<PublicKey, Error> {
+    let mut r_inv = blind.0.invert().expect("blinding factors are not zero");
+    let mut unblinded = *evaluated;
+    let ret = unblinded.mul_assign(secp, &r_inv);
+    r_inv.wipe();
+    ret.map(|_| unblinded)
+}
+
+/// The PRF output for `input` and its unblinded evaluation
+pub fn finalize(input: &[u8], unblinded: &PublicKey) -> [u8; OPRF_OUTPUT_SIZE] {
+    let len = input.len() as u64;
+    let mut len_bytes = [0u8; 8];
+    for (i, byte) in len_bytes.iter_mut().enumerate() {
+        *byte = (len >> (8 * i)) as u8;
+    }
+    TaggedHasher::new(OPRF_FINALIZE_TAG).chain(&len_bytes).chain(input).chain(&unblinded.serialize()).result()
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{blind, evaluate, finalize, unblind};
+    use super::super::{PublicKey, Scalar, Secp256k1};
+    use super::super::hashes::OPRF_HASH_TO_CURVE_TAG;
+
+    #[test]
+    fn oprf() {
+        let s = Secp256k1::new();
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        let (other_sk, _) = s.generate_keypair(&mut thread_rng());
+
+        let output = |input: &[u8], key| {
+            let (r, blinded) = blind(&s, &mut thread_rng(), input).unwrap();
+            let evaluated = evaluate(&s, key, &blinded).unwrap();
+            (blinded, finalize(input, &unblind(&s, &r, &evaluated).unwrap()))
+        };
+        let (blinded1, out1) = output(b"password", &sk);
+        let (blinded2, out2) = output(b"password", &sk);
+        // Fresh blinding each time, but the same output
+        assert!(blinded1 != blinded2);
+        assert_eq!(out1, out2);
+        assert!(output(b"passwore", &sk).1 != out1);
+        assert!(output(b"password", &other_sk).1 != out1);
+
+        // The output is the PRF evaluated directly
+        let mut direct = PublicKey::from_message_hash(OPRF_HASH_TO_CURVE_TAG, b"password");
+        direct.mul_assign(&s, &Scalar::from_secret_key(&sk)).unwrap();
+        assert_eq!(finalize(b"password", &direct), out1);
+    }
+}
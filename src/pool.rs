@@ -0,0 +1,199 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Context pools
+//! A fixed set of contexts shared by an enclave's worker threads.
+//!
+//! A `Secp256k1` is `Sync`, and signing and verification only need `&self`,
+//! so threads can already share a single context. What they can't do is
+//! re-randomize or reconfigure it without excluding every other thread, and
+//! they all share its blinding. A `ContextPool` holds one clone per worker
+//! (cloning copies the precomputed tables, which is much cheaper than
+//! building them), and `ContextPool::get` hands out exclusive access to a
+//! free one. Slots are guarded by spin locks, which work the same inside and
+//! outside the enclave; size the pool to the number of TCSs so that a thread
+//! never has to wait for one.
+
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use Secp256k1;
+
+struct Slot<C> {
+    busy: AtomicBool,
+    ctx: UnsafeCell<Secp256k1<C>>,
+}
+
+/// A fixed number of contexts, each used by one thread at a time
+pub struct ContextPool<C> {
+    slots: Vec<Slot<C>>,
+    next: AtomicUsize,
+}
+
+// A context is only reachable through the `PooledContext` which set its
+// slot's `busy` flag, so no two threads ever share one
+unsafe impl<C> Sync for ContextPool<C> {}
+
+impl<C> ContextPool<C> {
+    /// Creates a pool of `size` clones of `ctx`, which is moved into the
+    /// first slot. Panics if `size` is zero.
+    pub fn new(ctx: Secp256k1<C>, size: usize) -> ContextPool<C> {
+        assert!(size > 0, "a context pool needs at least one context");
+        let mut slots = Vec::with_capacity(size);
+        for _ in 1..size {
+            slots.push(Slot { busy: AtomicBool::new(false), ctx: UnsafeCell::new(ctx.clone()) });
+        }
+        slots.push(Slot { busy: AtomicBool::new(false), ctx: UnsafeCell::new(ctx) });
+        ContextPool { slots, next: AtomicUsize::new(0) }
+    }
+
+    /// The number of contexts in the pool
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Takes a free context, or returns `None` if all are in use
+    pub fn try_get<'a>(&'a self) -> Option<PooledContext<'a, C>> {
+        // Start where the last caller left off, to spread threads over slots
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.slots.len() {
+            let slot = &self.slots[start.wrapping_add(i) % self.slots.len()];
+            if slot.busy.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return Some(PooledContext { slot });
+            }
+        }
+        None
+    }
+
+    /// Takes a free context, spinning until one is returned if all are in use
+    pub fn get<'a>(&'a self) -> PooledContext<'a, C> {
+        loop {
+            if let Some(ctx) = self.try_get() {
+                return ctx;
+            }
+        }
+    }
+
+    /// Re-randomizes every context in the pool, each with its own seed.
+    /// Requires compilation with the "rand" feature.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn randomize<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        for slot in self.slots.iter_mut() {
+            unsafe { (*slot.ctx.get()).randomize(rng); }
+        }
+    }
+}
+
+/// Exclusive access to one of a pool's contexts, which goes back to the pool
+/// when this is dropped
+pub struct PooledContext<'a, C: 'a> {
+    slot: &'a Slot<C>,
+}
+
+// The guard has the slot's context to itself, like a `&mut Secp256k1<C>`,
+// which is `Send` and `Sync`
+unsafe impl<'a, C> Send for PooledContext<'a, C> {}
+unsafe impl<'a, C> Sync for PooledContext<'a, C> {}
+
+impl<'a, C> Deref for PooledContext<'a, C> {
+    type Target = Secp256k1<C>;
+
+    fn deref(&self) -> &Secp256k1<C> {
+        unsafe { &*self.slot.ctx.get() }
+    }
+}
+
+impl<'a, C> DerefMut for PooledContext<'a, C> {
+    fn deref_mut(&mut self) -> &mut Secp256k1<C> {
+        unsafe { &mut *self.slot.ctx.get() }
+    }
+}
+
+impl<'a, C> Drop for PooledContext<'a, C> {
+    fn drop(&mut self) {
+        self.slot.busy.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::ContextPool;
+    use key::{PublicKey, SecretKey};
+    use rng::thread_rng;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn checkout() {
+        let pool = ContextPool::new(Secp256k1::new(), 2);
+        assert_eq!(pool.size(), 2);
+        let a = pool.try_get().unwrap();
+        let b = pool.try_get().unwrap();
+        assert!(a.ctx != b.ctx);
+        assert!(pool.try_get().is_none());
+        drop(a);
+        let mut c = pool.get();
+        c.randomize(&mut thread_rng());
+        drop(b);
+        assert!(pool.try_get().is_some());
+    }
+
+    #[test]
+    fn stress() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 100;
+        let mut pool = ContextPool::new(Secp256k1::new(), 3);
+        pool.randomize(&mut thread_rng());
+        let pool = Arc::new(pool);
+        let in_use = Arc::new(AtomicUsize::new(0));
+        let max_in_use = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..THREADS).map(|t| {
+            let (pool, in_use, max_in_use) = (pool.clone(), in_use.clone(), max_in_use.clone());
+            thread::spawn(move || {
+                for i in 0..ROUNDS {
+                    let mut ctx = pool.get();
+                    let now = in_use.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_use.fetch_max(now, Ordering::SeqCst);
+                    if i % 10 == 0 {
+                        ctx.randomize(&mut thread_rng());
+                    }
+                    let sk = SecretKey::from_slice(&[1 + (t * ROUNDS + i) as u8 % 0x7f; 32]).unwrap();
+                    let pk = PublicKey::from_secret_key(&ctx, &sk);
+                    let msg = Message::from_slice(&[i as u8; 32]).unwrap();
+                    let sig = ctx.sign(&msg, &sk);
+                    assert!(ctx.verify(&msg, &sig, &pk).is_ok());
+                    in_use.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(max_in_use.load(Ordering::SeqCst) <= 3);
+        assert_eq!(in_use.load(Ordering::SeqCst), 0);
+        // Every context came back
+        let all: Vec<_> = (0..3).map(|_| pool.try_get().unwrap()).collect();
+        assert!(pool.try_get().is_none());
+        drop(all);
+    }
+}
@@ -0,0 +1,150 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Prepared public keys
+//! Public keys with a precomputed table of multiples, for verifying many
+//! signatures by the same key. Plain verification rebuilds a small table for
+//! the key on every call; a `PreparedPublicKey` builds a larger one once,
+//! which makes each verification noticeably cheaper (see the benchmarks in
+//! the crate root). Preparing costs a little less than one verification and
+//! the table takes 8 KiB.
+
+use std::fmt;
+use std::vec::Vec;
+
+use ffi;
+use key::PublicKey;
+use {Error, Message, Secp256k1, Signature, Verification};
+
+/// A public key together with its precomputed verification table. The
+/// table is specific to this build of libsecp256k1, so only the public key
+/// itself should be serialized.
+#[derive(Clone)]
+pub struct PreparedPublicKey {
+    pk: PublicKey,
+    // u64s keep the table 8-byte aligned
+    table: Vec<u64>,
+}
+
+impl fmt::Debug for PreparedPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PreparedPublicKey({})", self.pk)
+    }
+}
+
+impl PartialEq for PreparedPublicKey {
+    fn eq(&self, other: &PreparedPublicKey) -> bool {
+        self.pk == other.pk
+    }
+}
+
+impl Eq for PreparedPublicKey {}
+
+impl PreparedPublicKey {
+    /// Precomputes the verification table for `pk`
+    pub fn new(pk: &PublicKey) -> PreparedPublicKey {
+        unsafe {
+            let size = ffi::secp256k1_prepared_pubkey_size();
+            let mut table = vec![0u64; size.div_ceil(8)];
+            let err = ffi::secp256k1_prepared_pubkey_create(ffi::secp256k1_context_no_precomp,
+                                                            table.as_mut_ptr() as *mut ffi::PreparedPublicKey,
+                                                            pk.as_ptr());
+            // A `PublicKey` is always valid
+            assert!(err == 1);
+            PreparedPublicKey { pk: *pk, table }
+        }
+    }
+
+    /// The public key this table was prepared for
+    #[inline]
+    pub fn public_key(&self) -> &PublicKey {
+        &self.pk
+    }
+
+    /// Obtains a raw pointer suitable for use with FFI functions
+    #[inline]
+    pub fn as_ptr(&self) -> *const ffi::PreparedPublicKey {
+        self.table.as_ptr() as *const ffi::PreparedPublicKey
+    }
+}
+
+impl From<PublicKey> for PreparedPublicKey {
+    fn from(pk: PublicKey) -> PreparedPublicKey {
+        PreparedPublicKey::new(&pk)
+    }
+}
+
+impl<C: Verification> Secp256k1<C> {
+    /// Checks that `sig` is a valid ECDSA signature for `msg` using the
+    /// prepared public key `pk`. Gives the same result as `verify` with
+    /// `pk.public_key()`, only faster.
+    #[inline]
+    pub fn verify_prepared(&self, msg: &Message, sig: &Signature, pk: &PreparedPublicKey) -> Result<(), Error> {
+        measure!(VerifyPrepared, Result::is_ok, unsafe {
+            if ffi::secp256k1_ecdsa_verify_prepared(self.ctx, sig.as_ptr(), msg.as_ptr(), pk.as_ptr()) == 0 {
+                debug_log!(debug, "signature verification failed for prepared public key {}", pk.pk);
+                Err(Error::IncorrectSignature)
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use rand_core::RngCore;
+
+    use rng::thread_rng;
+    use super::PreparedPublicKey;
+    use super::super::{Message, Secp256k1};
+    use super::super::Error::IncorrectSignature;
+
+    #[test]
+    fn agrees_with_verify() {
+        let mut s = Secp256k1::new();
+        s.randomize(&mut thread_rng());
+
+        let mut msg = [0; 32];
+        for _ in 0..100 {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            let prepared = PreparedPublicKey::new(&pk);
+            assert_eq!(prepared.public_key(), &pk);
+
+            thread_rng().fill_bytes(&mut msg);
+            let sig = s.sign(&Message::from_slice(&msg).unwrap(), &sk);
+            assert_eq!(s.verify_prepared(&Message::from_slice(&msg).unwrap(), &sig, &prepared), Ok(()));
+
+            msg[0] ^= 1;
+            let wrong = Message::from_slice(&msg).unwrap();
+            assert_eq!(s.verify(&wrong, &sig, &pk), Err(IncorrectSignature));
+            assert_eq!(s.verify_prepared(&wrong, &sig, &prepared), Err(IncorrectSignature));
+
+            let (_, other) = s.generate_keypair(&mut thread_rng());
+            assert_eq!(s.verify_prepared(&wrong, &sig, &PreparedPublicKey::from(other)),
+                       Err(IncorrectSignature));
+        }
+    }
+
+    #[test]
+    fn verification_only_context() {
+        let full = Secp256k1::new();
+        let vrfy = Secp256k1::verification_only();
+        let (sk, pk) = full.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let sig = full.sign(&msg, &sk);
+        let prepared = PreparedPublicKey::new(&pk);
+        assert_eq!(vrfy.verify_prepared(&msg, &sig, &prepared.clone()), Ok(()));
+    }
+}
@@ -0,0 +1,190 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Protobuf mirrors
+//! Plain-old-data mirrors of the crate's public types for services which
+//! carry them in protobuf `bytes` fields, e.g. enclave signing over gRPC.
+//! Each mirror is the type's fixed-size encoding in a byte array: the
+//! 33-byte compressed form for a `PublicKey`, the 32 bytes of an
+//! `XOnlyPublicKey` or `Message`, the 64-byte compact `r || s` for a
+//! `Signature` and the 65-byte `r || s || v` for a `RecoverableSignature`.
+//!
+//! Converting into a mirror can't fail, and neither can converting it into
+//! the `Vec<u8>` that protobuf code generators use for `bytes`. The other
+//! way takes two checks: `from_slice` (or `TryFrom<&[u8]>`) checks the
+//! field's length, failing with the error the type's `from_slice` gives for
+//! a wrong length, and `TryFrom` into the crate's type checks the encoding,
+//! failing as its parser does. A message only has a length to check.
+//!
+//! Secret keys have no mirror: they should not cross a service boundary.
+//! Requires compilation with the "proto" feature.
+
+use std::convert::TryFrom;
+use std::vec::Vec;
+
+use constants;
+use key::{PublicKey, XOnlyPublicKey};
+use super::Error::{self, InvalidMessage, InvalidPublicKeyLength, InvalidSignatureLength};
+use {Message, RecoverableSignature, Signature};
+
+macro_rules! impl_proto {
+    ($thing:ident, $proto:ident, $doc:expr, $len:expr, $len_err:expr, $to_bytes:expr) => {
+        #[doc = $doc]
+        #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+        #[repr(transparent)]
+        pub struct $proto(pub [u8; $len]);
+
+        impl $proto {
+            /// Takes the bytes of a protobuf `bytes` field, checking only
+            /// their length
+            #[inline]
+            pub fn from_slice(data: &[u8]) -> Result<$proto, Error> {
+                let mut ret = [0; $len];
+                if data.len() != ret.len() {
+                    return Err($len_err);
+                }
+                ret.copy_from_slice(data);
+                Ok($proto(ret))
+            }
+        }
+
+        impl From<$thing> for $proto {
+            #[inline]
+            fn from(thing: $thing) -> $proto {
+                $proto($to_bytes(&thing))
+            }
+        }
+
+        impl<'a> TryFrom<&'a [u8]> for $proto {
+            type Error = Error;
+
+            #[inline]
+            fn try_from(data: &'a [u8]) -> Result<$proto, Error> {
+                $proto::from_slice(data)
+            }
+        }
+
+        impl AsRef<[u8]> for $proto {
+            #[inline]
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl From<$proto> for Vec<u8> {
+            #[inline]
+            fn from(proto: $proto) -> Vec<u8> {
+                proto.0.to_vec()
+            }
+        }
+    };
+}
+
+macro_rules! impl_proto_try_from {
+    ($thing:ident, $proto:ident, $from_bytes:expr) => {
+        impl TryFrom<$proto> for $thing {
+            type Error = Error;
+
+            #[inline]
+            fn try_from(proto: $proto) -> Result<$thing, Error> {
+                $from_bytes(&proto.0)
+            }
+        }
+    };
+}
+
+impl_proto!(PublicKey, ProtoPublicKey, "A `PublicKey` in its 33-byte compressed form",
+            constants::PUBLIC_KEY_SIZE, InvalidPublicKeyLength, |pk: &PublicKey| pk.serialize());
+impl_proto_try_from!(PublicKey, ProtoPublicKey, |data: &[u8; constants::PUBLIC_KEY_SIZE]| PublicKey::from_slice(data));
+
+impl_proto!(XOnlyPublicKey, ProtoXOnlyPublicKey, "An `XOnlyPublicKey` in its 32 bytes",
+            constants::XONLY_PUBLIC_KEY_SIZE, InvalidPublicKeyLength, |pk: &XOnlyPublicKey| pk.serialize());
+impl_proto_try_from!(XOnlyPublicKey, ProtoXOnlyPublicKey,
+                     |data: &[u8; constants::XONLY_PUBLIC_KEY_SIZE]| XOnlyPublicKey::from_slice(data));
+
+impl_proto!(Signature, ProtoSignature, "A `Signature` in its 64-byte compact form",
+            constants::COMPACT_SIGNATURE_SIZE, InvalidSignatureLength, |sig: &Signature| sig.compact_bytes());
+impl_proto_try_from!(Signature, ProtoSignature, Signature::from_compact_bytes);
+
+impl_proto!(RecoverableSignature, ProtoRecoverableSignature,
+            "A `RecoverableSignature` in its 65-byte `r || s || v` form",
+            constants::RECOVERABLE_SIGNATURE_SIZE, InvalidSignatureLength,
+            |sig: &RecoverableSignature| sig.serialize_compact_rsv());
+impl_proto_try_from!(RecoverableSignature, ProtoRecoverableSignature,
+                     |data: &[u8; constants::RECOVERABLE_SIGNATURE_SIZE]| RecoverableSignature::from_compact_rsv(data));
+
+impl_proto!(Message, ProtoMessage, "A `Message` in its 32 bytes",
+            constants::MESSAGE_SIZE, InvalidMessage, |msg: &Message| msg.0);
+
+/// Every 32 bytes are a message
+impl From<ProtoMessage> for Message {
+    #[inline]
+    fn from(proto: ProtoMessage) -> Message {
+        Message(proto.0)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::convert::TryFrom;
+    use std::vec::Vec;
+
+    use super::{ProtoMessage, ProtoPublicKey, ProtoRecoverableSignature, ProtoSignature, ProtoXOnlyPublicKey};
+    use super::super::Error::{InvalidMessage, InvalidPublicKeyLength, InvalidRecoveryId, InvalidSignatureLength,
+                              PublicKeyNotOnCurve, SignatureOutOfRange};
+    use key::{PublicKey, XOnlyPublicKey};
+    use rng::thread_rng;
+    use {Message, RecoverableSignature, Secp256k1, Signature};
+
+    #[test]
+    fn round_trip() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sig = s.sign(&msg, &sk);
+        let rsig = s.sign_recoverable(&msg, &sk);
+        let (xonly, _) = pk.x_only_public_key();
+
+        // Through the `Vec<u8>` a generated protobuf message would hold
+        let field: Vec<u8> = ProtoPublicKey::from(pk).into();
+        assert_eq!(field, &pk.serialize()[..]);
+        assert_eq!(PublicKey::try_from(ProtoPublicKey::try_from(&field[..]).unwrap()), Ok(pk));
+        let field: Vec<u8> = ProtoXOnlyPublicKey::from(xonly).into();
+        assert_eq!(XOnlyPublicKey::try_from(ProtoXOnlyPublicKey::from_slice(&field).unwrap()), Ok(xonly));
+        let field: Vec<u8> = ProtoSignature::from(sig).into();
+        assert_eq!(Signature::try_from(ProtoSignature::from_slice(&field).unwrap()), Ok(sig));
+        let field: Vec<u8> = ProtoRecoverableSignature::from(rsig).into();
+        assert_eq!(RecoverableSignature::try_from(ProtoRecoverableSignature::from_slice(&field).unwrap()), Ok(rsig));
+        let field: Vec<u8> = ProtoMessage::from(msg).into();
+        assert_eq!(Message::from(ProtoMessage::from_slice(&field).unwrap()), msg);
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(ProtoPublicKey::from_slice(&[2; 32]), Err(InvalidPublicKeyLength));
+        assert_eq!(ProtoXOnlyPublicKey::from_slice(&[2; 33]), Err(InvalidPublicKeyLength));
+        assert_eq!(ProtoSignature::from_slice(&[1; 65]), Err(InvalidSignatureLength));
+        assert_eq!(ProtoRecoverableSignature::from_slice(&[1; 64]), Err(InvalidSignatureLength));
+        assert_eq!(ProtoMessage::from_slice(&[]), Err(InvalidMessage));
+
+        let mut off_curve = [0; 33];
+        // y^2 = 7 has no solution
+        off_curve[0] = 0x02;
+        assert_eq!(PublicKey::try_from(ProtoPublicKey(off_curve)), Err(PublicKeyNotOnCurve));
+        assert_eq!(Signature::try_from(ProtoSignature([0xff; 64])), Err(SignatureOutOfRange));
+        let mut bad_recid = [1; 65];
+        bad_recid[64] = 4;
+        assert_eq!(RecoverableSignature::try_from(ProtoRecoverableSignature(bad_recid)), Err(InvalidRecoveryId));
+    }
+}
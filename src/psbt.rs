@@ -0,0 +1,344 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # PSBT input signatures
+//! A wallet outside the enclave computes the sighash of each transaction
+//! input it wants signed (BIP143 for legacy and segwit v0 inputs, BIP341
+//! for taproot) and the enclave signs it with `Secp256k1::sign_input`,
+//! `KeyPair::sign_input` or `Vault::sign_input`. The result is encoded as
+//! the input's witness and the PSBT's partial signature fields expect it:
+//! a DER signature followed by the sighash type byte for ECDSA, and a
+//! 64-byte BIP340 signature for taproot, followed by the sighash type
+//! unless it is `SIGHASH_DEFAULT`. Taproot key path spends are signed with
+//! the key tweaked by BIP341 for the spend's script tree.
+//!
+//! The enclave never sees the transaction, so it can't check that the
+//! sighash, sighash type and spend match it: a wrong sighash type only
+//! produces a signature which the network rejects, but whoever computes
+//! the sighash decides what gets signed. Requires compilation with the
+//! "psbt" feature.
+
+use std::convert::TryFrom;
+use std::vec::Vec;
+
+use blind::{self, SCHNORR_SIGNATURE_SIZE};
+use cleanup::wipe;
+use hashes::{self, TaggedHasher, BIP340_AUX_TAG, BIP340_NONCE_TAG};
+use key::{KeyPair, Parity, PublicKey, SecretKey, XOnlyPublicKey};
+use scalar::Scalar;
+use taproot::TapTweakHash;
+use super::Error::{self, InvalidPublicKey, InvalidSighashType, InvalidTweak};
+use {Message, Secp256k1, Signing, Verification};
+
+/// The largest ECDSA input signature: a 72-byte DER signature and the
+/// sighash type
+pub const MAX_ECDSA_INPUT_SIGNATURE_SIZE: usize = 73;
+
+/// The largest taproot input signature: a BIP340 signature and the sighash
+/// type
+pub const MAX_TAPROOT_INPUT_SIGNATURE_SIZE: usize = SCHNORR_SIGNATURE_SIZE + 1;
+
+/// Which outputs and inputs of the transaction a signature commits to
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SighashType {
+    /// The whole transaction, like `All`, with no byte appended to the
+    /// signature. Taproot only.
+    Default = 0x00,
+    /// All inputs and outputs
+    All = 0x01,
+    /// All inputs and no outputs
+    None = 0x02,
+    /// All inputs and the output with the same index as the input
+    Single = 0x03,
+    /// Only this input, and all outputs
+    AllPlusAnyoneCanPay = 0x81,
+    /// Only this input, and no outputs
+    NonePlusAnyoneCanPay = 0x82,
+    /// Only this input, and the output with the same index
+    SinglePlusAnyoneCanPay = 0x83,
+}
+
+impl SighashType {
+    /// The sighash type byte
+    #[inline]
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Converts a sighash type byte. Fails with `InvalidSighashType` for
+    /// anything but the seven types BIP341 defines.
+    pub fn from_u8(byte: u8) -> Result<SighashType, Error> {
+        match byte {
+            0x00 => Ok(SighashType::Default),
+            0x01 => Ok(SighashType::All),
+            0x02 => Ok(SighashType::None),
+            0x03 => Ok(SighashType::Single),
+            0x81 => Ok(SighashType::AllPlusAnyoneCanPay),
+            0x82 => Ok(SighashType::NonePlusAnyoneCanPay),
+            0x83 => Ok(SighashType::SinglePlusAnyoneCanPay),
+            _ => Err(InvalidSighashType),
+        }
+    }
+}
+
+impl TryFrom<u8> for SighashType {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<SighashType, Error> {
+        SighashType::from_u8(byte)
+    }
+}
+
+impl From<SighashType> for u8 {
+    fn from(sighash_type: SighashType) -> u8 {
+        sighash_type.to_u8()
+    }
+}
+
+/// How the input being signed is spent
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Spend {
+    /// A legacy or segwit v0 input, signed with ECDSA
+    Ecdsa,
+    /// A taproot key path spend, signed with the key tweaked for the output
+    /// with this script tree root, or none for an output without scripts
+    TaprootKeyPath {
+        /// The merkle root of the output's script tree
+        merkle_root: Option<[u8; 32]>,
+    },
+    /// A taproot script path spend, signed with the untweaked key for a
+    /// `OP_CHECKSIG` in the script
+    TaprootScriptPath,
+}
+
+/// The x-only BIP341 output key for the x-only internal key `internal` and
+/// the script tree root `merkle_root`, if any. Fails with
+/// `InvalidPublicKey` if `internal` is not on the curve, and with
+/// `InvalidTweak` in the negligible case that the tweak is out of range.
+pub fn taproot_output_key<C: Verification>(secp: &Secp256k1<C>, internal: &[u8; 32],
+                                           merkle_root: Option<&[u8; 32]>) -> Result<[u8; 32], Error> {
+    let mut q = XOnlyPublicKey::from_slice(internal).map_err(|_| InvalidPublicKey)?.public_key(Parity::Even);
+    q.add_exp_assign(secp, &tap_tweak(internal, merkle_root)?)?;
+    Ok(q.x_coordinate())
+}
+
+/// The BIP341 tweak of the x-only internal key `internal` for `merkle_root`
+fn tap_tweak(internal: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> Result<Scalar, Error> {
+    TapTweakHash::from_x_and_tweak(internal, merkle_root).to_scalar()
+}
+
+/// `sk`, negated if its public key has odd y, and the x-only public key
+fn even_key<C: Signing>(secp: &Secp256k1<C>, sk: &SecretKey) -> (Scalar, [u8; 32]) {
+    let (pk, parity) = PublicKey::from_secret_key(secp, sk).x_only_public_key();
+    let d = Scalar::from_secret_key(sk);
+    (if parity == Parity::Even { d } else { d.negate() }, pk.serialize())
+}
+
+/// The key for a BIP341 key path spend of the output with `merkle_root`
+fn tweak_key<C: Signing>(secp: &Secp256k1<C>, sk: &SecretKey, merkle_root: Option<&[u8; 32]>)
+                         -> Result<SecretKey, Error> {
+    let (mut d, internal) = even_key(secp, sk);
+    let tweaked = tap_tweak(&internal, merkle_root)
+        .and_then(|t| SecretKey::from_slice(&d.add(&t)[..]).map_err(|_| InvalidTweak));
+    d.wipe();
+    tweaked
+}
+
+/// The BIP340 signature of `msg` with `sk` and the auxiliary randomness
+/// `aux_rand`
+fn sign_schnorr<C: Signing>(secp: &Secp256k1<C>, msg: &Message, sk: &SecretKey, aux_rand: &[u8; 32])
+                            -> Result<[u8; SCHNORR_SIGNATURE_SIZE], Error> {
+    let (mut d, pk) = even_key(secp, sk);
+    let mut masked = hashes::tagged_hash(BIP340_AUX_TAG, aux_rand);
+    for (m, d) in masked.iter_mut().zip(d[..].iter()) {
+        *m ^= *d;
+    }
+    let engine = TaggedHasher::new(BIP340_NONCE_TAG).chain(&masked).chain(&pk).chain(&msg[..]);
+    let mut hash = engine.result();
+    wipe(&mut masked);
+    let nonce = SecretKey::from_slice(&Scalar::from_be_bytes_mod_order(&hash)[..]);
+    wipe(&mut hash);
+    let mut nonce = match nonce {
+        Ok(nonce) => nonce,
+        Err(e) => {
+            d.wipe();
+            return Err(e);
+        }
+    };
+
+    let (mut k, r) = even_key(secp, &nonce);
+    nonce.wipe();
+    let s = k.add(&blind::challenge(&r, &pk, msg).mul(&d));
+    d.wipe();
+    k.wipe();
+    let mut sig = [0u8; SCHNORR_SIGNATURE_SIZE];
+    sig[..32].copy_from_slice(&r);
+    sig[32..].copy_from_slice(&s[..]);
+    Ok(sig)
+}
+
+/// Checks that `sighash_type` is allowed for `spend`: anything but
+/// `SighashType::Default` for ECDSA, and anything for taproot
+pub(crate) fn check_sighash_type(spend: Spend, sighash_type: SighashType) -> Result<(), Error> {
+    if spend == Spend::Ecdsa && sighash_type == SighashType::Default { Err(InvalidSighashType) } else { Ok(()) }
+}
+
+impl<C: Signing> Secp256k1<C> {
+    /// Signs the input with the sighash `sighash` of type `sighash_type`,
+    /// spent as `spend`, with `sk`, and encodes the signature for the
+    /// input's witness. Taproot signatures use `aux_rand` as BIP340's
+    /// auxiliary randomness, which should be fresh random bytes to protect
+    /// against side channels; ECDSA ignores it. Fails with
+    /// `InvalidSighashType` for `SighashType::Default` with ECDSA, and with
+    /// `InvalidTweak` if the taproot key tweak fails, which is negligibly
+    /// unlikely. Requires a signing-capable context.
+    pub fn sign_input(&self, sighash: &Message, spend: Spend, sighash_type: SighashType, sk: &SecretKey,
+                      aux_rand: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        check_sighash_type(spend, sighash_type)?;
+        let mut sig = match spend {
+            Spend::Ecdsa => self.sign(sighash, sk).serialize_der(self),
+            Spend::TaprootKeyPath { merkle_root } => {
+                let tweaked = tweak_key(self, sk, merkle_root.as_ref())?;
+                sign_schnorr(self, sighash, &tweaked, aux_rand)?.to_vec()
+            }
+            Spend::TaprootScriptPath => sign_schnorr(self, sighash, sk, aux_rand)?.to_vec(),
+        };
+        if sighash_type != SighashType::Default {
+            sig.push(sighash_type.to_u8());
+        }
+        Ok(sig)
+    }
+}
+
+impl KeyPair {
+    /// Signs the input with this keypair's secret key like
+    /// `Secp256k1::sign_input`
+    pub fn sign_input<C: Signing>(&self, secp: &Secp256k1<C>, sighash: &Message, spend: Spend,
+                                  sighash_type: SighashType, aux_rand: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        secp.sign_input(sighash, spend, sighash_type, &self.secret_key(), aux_rand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use blind::verify_schnorr;
+    use rng::thread_rng;
+    use super::{taproot_output_key, SighashType, Spend, MAX_ECDSA_INPUT_SIGNATURE_SIZE,
+                MAX_TAPROOT_INPUT_SIGNATURE_SIZE};
+    use super::super::Error::{InvalidPublicKey, InvalidSighashType};
+    use {KeyPair, Message, PublicKey, Secp256k1, SecretKey, Signature};
+
+    #[test]
+    fn sighash_types() {
+        let types = [SighashType::Default, SighashType::All, SighashType::None, SighashType::Single,
+                     SighashType::AllPlusAnyoneCanPay, SighashType::NonePlusAnyoneCanPay,
+                     SighashType::SinglePlusAnyoneCanPay];
+        for &ty in types.iter() {
+            assert_eq!(SighashType::from_u8(ty.to_u8()), Ok(ty));
+            assert_eq!(SighashType::try_from(u8::from(ty)), Ok(ty));
+        }
+        for &byte in [0x04, 0x80, 0x84, 0xff].iter() {
+            assert_eq!(SighashType::from_u8(byte), Err(InvalidSighashType));
+        }
+    }
+
+    #[test]
+    fn bip340_vectors() {
+        // Vectors 0 and 1 of BIP340, signed as script path spends
+        let vectors = [
+            ("0000000000000000000000000000000000000000000000000000000000000003",
+             "0000000000000000000000000000000000000000000000000000000000000000",
+             "0000000000000000000000000000000000000000000000000000000000000000",
+             "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+              25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0"),
+            ("b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+             "0000000000000000000000000000000000000000000000000000000000000001",
+             "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+             "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341\
+              8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a"),
+        ];
+        let s = Secp256k1::new();
+        for &(sk, aux, msg, sig) in vectors.iter() {
+            let sk = SecretKey::from_slice(&hex!(sk)).unwrap();
+            let mut aux_rand = [0u8; 32];
+            aux_rand.copy_from_slice(&hex!(aux));
+            let msg = Message::from_slice(&hex!(msg)).unwrap();
+            let signed = s.sign_input(&msg, Spend::TaprootScriptPath, SighashType::Default, &sk, &aux_rand);
+            assert_eq!(signed, Ok(hex!(sig)));
+        }
+    }
+
+    #[test]
+    fn taproot_key_path() {
+        let s = Secp256k1::new();
+        let keypair = KeyPair::from_secret_key(&s, SecretKey::from_slice(&[0x42; 32]).unwrap());
+        let msg = Message::from_slice(&[0x11; 32]).unwrap();
+        let internal = keypair.public_key().x_coordinate();
+        // Output keys and signatures from an independent implementation
+        let cases = [
+            (None,
+             "3c290579497bd757ebad069edf55fdf9d3776624c34da3b2f5ce7dc3da667fe0",
+             "0e77054a56aa67881c10daa132e8bf951cf39706b90b063d12bdb53c0dec9291\
+              74aa43239d3bf816d2241243b9f691c3bd1fa8f9e8814e70f97de0580bbf2426"),
+            (Some([0x33; 32]),
+             "40b286b21defcaca7092191a9ec2cdd024e5828ad1ab6d4ad790958e6be361bc",
+             "0a89caaeaa8d7b51dee58cc6c09a354272ebf7aedd7b9d51fbcc2acab0c98e79\
+              62a12252856e1c161e64157cd50fc793dc8ab565fd259b72bfb5e0466c920f14"),
+        ];
+        for &(merkle_root, output_key, sig) in cases.iter() {
+            let q = taproot_output_key(&s, &internal, merkle_root.as_ref()).unwrap();
+            assert_eq!(q[..], hex!(output_key)[..]);
+
+            let spend = Spend::TaprootKeyPath { merkle_root };
+            let signed = keypair.sign_input(&s, &msg, spend, SighashType::Default, &[0x22; 32]).unwrap();
+            assert_eq!(signed, hex!(sig));
+            let mut schnorr = [0u8; 64];
+            schnorr.copy_from_slice(&signed);
+            assert_eq!(verify_schnorr(&s, &schnorr, &msg, &q), Ok(()));
+            assert!(verify_schnorr(&s, &schnorr, &msg, &internal).is_err());
+
+            // Any other type is appended
+            let signed = keypair.sign_input(&s, &msg, spend, SighashType::SinglePlusAnyoneCanPay, &[0x22; 32]);
+            let signed = signed.unwrap();
+            assert_eq!(signed.len(), MAX_TAPROOT_INPUT_SIGNATURE_SIZE);
+            assert_eq!(signed[..64], schnorr[..]);
+            assert_eq!(signed[64], 0x83);
+        }
+        assert_eq!(taproot_output_key(&s, &[0xff; 32], None), Err(InvalidPublicKey));
+    }
+
+    #[test]
+    fn ecdsa() {
+        let s = Secp256k1::new();
+        let keypair = KeyPair::new(&s, &mut thread_rng());
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let aux_rand = [0u8; 32];
+
+        let signed = keypair.sign_input(&s, &msg, Spend::Ecdsa, SighashType::All, &aux_rand).unwrap();
+        assert!(signed.len() <= MAX_ECDSA_INPUT_SIGNATURE_SIZE);
+        assert_eq!(*signed.last().unwrap(), 0x01);
+        let sig = Signature::from_der(&s, &signed[..signed.len() - 1]).unwrap();
+        assert_eq!(sig, s.sign(&msg, &keypair.secret_key()));
+        assert_eq!(s.verify(&msg, &sig, &PublicKey::from_secret_key(&s, &keypair.secret_key())), Ok(()));
+
+        let signed = s.sign_input(&msg, Spend::Ecdsa, SighashType::NonePlusAnyoneCanPay, &keypair.secret_key(),
+                                  &[0xff; 32]).unwrap();
+        assert_eq!(*signed.last().unwrap(), 0x82);
+        assert_eq!(Signature::from_der(&s, &signed[..signed.len() - 1]), Ok(sig));
+        assert_eq!(keypair.sign_input(&s, &msg, Spend::Ecdsa, SighashType::Default, &aux_rand),
+                   Err(InvalidSighashType));
+    }
+}
@@ -0,0 +1,394 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Range proofs of discrete logarithms
+//! A zero-knowledge proof that the secret key `x` of a public key
+//! `X = x * G` is below `2^bits`, as threshold ECDSA protocols need for the
+//! shares and nonces their parties commit to. The prover commits to each
+//! bit `b_i` of `x` as `C_i = b_i * G + r_i * H`, where `H` is
+//! `pedersen_generator()`, proves for each commitment that it opens to 0
+//! or 1 (a Cramer-Damgard-Schoenmakers OR-proof), and finally proves with
+//! a Schnorr proof on `H` that `sum(2^i * C_i) - X` is a multiple of `H`,
+//! i.e. that the bits add up to `x`.
+//!
+//! Proofs are `1 + 161 * bits + 64` bytes long and take about `4 * bits`
+//! point multiplications to make or check; `bits` is at most
+//! `MAX_RANGE_BITS`, which keeps the sum of the bits from wrapping around
+//! the group order. The bits of `x` are only used through constant-time
+//! selections. The Paillier-based range proofs of GG18 and GG20 need
+//! arithmetic modulo an RSA modulus, which this crate does not provide.
+//! Requires compilation with the "hashes" feature.
+
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use constants;
+use generator::Generator;
+use hashes::{TaggedHasher, RANGE_PROOF_CHALLENGE_TAG, RANGE_PROOF_GENERATOR_TAG};
+use key::PublicKey;
+#[cfg(any(test, feature = "rand"))]
+use key::SecretKey;
+use scalar::Scalar;
+use super::Error::{self, IncorrectSignature, InvalidSignature};
+#[cfg(any(test, feature = "rand"))]
+use super::Error::OutOfRange;
+use {Secp256k1, Verification};
+
+/// The widest range a proof can cover, in bits
+pub const MAX_RANGE_BITS: usize = 252;
+
+/// The size (in bytes) of the proof for one bit: its commitment and the
+/// two challenges and two responses of its OR-proof
+const BIT_PROOF_SIZE: usize = constants::PUBLIC_KEY_SIZE + 4 * 32;
+
+/// The generator `H` of the bit commitments, whose discrete logarithm with
+/// respect to `G` nobody knows
+pub fn pedersen_generator() -> Generator {
+    Generator::from_seed(RANGE_PROOF_GENERATOR_TAG, b"H")
+}
+
+/// The proof that one bit commitment `C` opens to 0 or 1: a proof of the
+/// discrete logarithm to base `H` of `C` or of `C - G`, with the challenges
+/// `e0 + e1` adding up to the hash of both nonces
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct BitProof {
+    commitment: PublicKey,
+    e0: Scalar,
+    e1: Scalar,
+    s0: Scalar,
+    s1: Scalar,
+}
+
+/// A proof that the secret key of a public key is below `2^bits`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RangeProof {
+    bits: Vec<BitProof>,
+    e: Scalar,
+    s: Scalar,
+}
+
+/// The hash of the context, the public key and the bit commitments, which
+/// every challenge commits to
+fn challenge_prefix(context: &[u8], pk: &PublicKey, bits: &[BitProof]) -> TaggedHasher {
+    let len = context.len() as u64;
+    let mut len_bytes = [0u8; 8];
+    for (i, byte) in len_bytes.iter_mut().enumerate() {
+        *byte = (len >> (8 * i)) as u8;
+    }
+    let mut engine = TaggedHasher::new(RANGE_PROOF_CHALLENGE_TAG).chain(&len_bytes).chain(context);
+    engine.input(&pk.serialize());
+    engine.input(&[bits.len() as u8]);
+    for bit in bits {
+        engine.input(&bit.commitment.serialize());
+    }
+    engine
+}
+
+/// The challenge for bit `index`, or for the final Schnorr proof with
+/// index 0xff, and its nonce points
+fn challenge(prefix: &TaggedHasher, index: u8, nonces: &[&PublicKey]) -> Scalar {
+    let mut engine = prefix.clone();
+    engine.input(&[index]);
+    for r in nonces {
+        engine.input(&r.serialize());
+    }
+    Scalar::from_be_bytes_mod_order(&engine.result())
+}
+
+/// `s * H - e * p`, failing for the point at infinity
+fn nonce<C: Verification>(secp: &Secp256k1<C>, h: &Generator, s: &Scalar, e: &Scalar, p: &PublicKey)
+                          -> Result<PublicKey, Error> {
+    let mut ep = *p;
+    ep.mul_assign(secp, &e.negate())?;
+    h.mul(secp, s)?.combine(secp, &ep)
+}
+
+/// `-G`
+fn negated_generator<C: Verification>(secp: &Secp256k1<C>) -> PublicKey {
+    Generator::g().mul(secp, &Scalar::ONE.negate()).expect("minus one is not zero")
+}
+
+/// `a` if `mask` is 0xff and `b` if it is 0, with the same memory accesses
+/// either way
+#[cfg(any(test, feature = "rand"))]
+fn select_point(mask: u8, a: &PublicKey, b: &PublicKey) -> PublicKey {
+    let mut ret = b.serialize();
+    ::constant_time_assign(&mut ret, &a.serialize(), mask);
+    PublicKey::from_slice(&ret).expect("selected a valid point")
+}
+
+/// `a` if `mask` is 0xff and `b` if it is 0, with the same memory accesses
+/// either way
+#[cfg(any(test, feature = "rand"))]
+fn select_scalar(mask: u8, a: &Scalar, b: &Scalar) -> Scalar {
+    let mut ret = b.to_be_bytes();
+    ::constant_time_assign(&mut ret, &a[..], mask);
+    Scalar::from_be_bytes(ret).expect("selected a valid scalar")
+}
+
+#[cfg(any(test, feature = "rand"))]
+fn random_scalar<C, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R) -> Scalar {
+    Scalar::from_secret_key(&SecretKey::new(secp, rng))
+}
+
+fn read_scalar(data: &[u8]) -> Result<Scalar, Error> {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[..32]);
+    Scalar::from_be_bytes(bytes).map_err(|_| InvalidSignature)
+}
+
+impl RangeProof {
+    /// Proves that `sk` is below `2^bits`, in `context`, which the verifier
+    /// must use too and which should bind the proof to its protocol
+    /// session. Fails with `OutOfRange` if `bits` is zero or above
+    /// `MAX_RANGE_BITS`, or if `sk` is not in the range.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn prove<C: Verification, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R, sk: &SecretKey,
+                                                          bits: usize, context: &[u8])
+                                                          -> Result<RangeProof, Error> {
+        if bits == 0 || bits > MAX_RANGE_BITS {
+            return Err(OutOfRange);
+        }
+        // Check that no bit at or above `bits` is set, looking at every byte
+        let mut high = 0u8;
+        for i in 0..constants::SECRET_KEY_SIZE {
+            let low_bits = 8 * (constants::SECRET_KEY_SIZE - 1 - i);
+            let mask = if low_bits >= bits {
+                0xff
+            } else if low_bits + 8 <= bits {
+                0
+            } else {
+                0xffu8 << (bits - low_bits)
+            };
+            high |= sk.secret_bytes()[i] & mask;
+        }
+        if high != 0 {
+            return Err(OutOfRange);
+        }
+
+        let h = pedersen_generator();
+        let minus_g = negated_generator(secp);
+        let pk = Generator::g().mul(secp, &Scalar::from_secret_key(sk))?;
+
+        // Commit to each bit, keeping the blinding factors
+        let mut blinds = Vec::with_capacity(bits);
+        let mut masks = Vec::with_capacity(bits);
+        let mut proofs = Vec::with_capacity(bits);
+        for i in 0..bits {
+            let mask = ((sk.secret_bytes()[constants::SECRET_KEY_SIZE - 1 - i / 8] >> (i % 8)) & 1).wrapping_neg();
+            let r = random_scalar(secp, rng);
+            let zero = h.mul(secp, &r)?;
+            let one = zero.combine(secp, Generator::g().as_public_key())?;
+            proofs.push(BitProof {
+                commitment: select_point(mask, &one, &zero),
+                e0: Scalar::ZERO,
+                e1: Scalar::ZERO,
+                s0: Scalar::ZERO,
+                s1: Scalar::ZERO,
+            });
+            blinds.push(r);
+            masks.push(mask);
+        }
+        let prefix = challenge_prefix(context, &pk, &proofs);
+
+        // For each bit, prove the true statement and simulate the other:
+        // statement 0 is `C = r * H` and statement 1 is `C - G = r * H`
+        for (i, proof) in proofs.iter_mut().enumerate() {
+            let mask = masks[i];
+            let minus_one = proof.commitment.combine(secp, &minus_g)?;
+            let simulated = select_point(mask, &proof.commitment, &minus_one);
+            let e_sim = random_scalar(secp, rng);
+            let s_sim = random_scalar(secp, rng);
+            let r_sim = nonce(secp, &h, &s_sim, &e_sim, &simulated)?;
+            let mut k = random_scalar(secp, rng);
+            let r_real = h.mul(secp, &k)?;
+
+            let e = challenge(&prefix, i as u8, &[&select_point(mask, &r_sim, &r_real),
+                                                  &select_point(mask, &r_real, &r_sim)]);
+            let e_real = e.add(&e_sim.negate());
+            let s_real = k.add(&e_real.mul(&blinds[i]));
+            k.wipe();
+            proof.e0 = select_scalar(mask, &e_sim, &e_real);
+            proof.e1 = select_scalar(mask, &e_real, &e_sim);
+            proof.s0 = select_scalar(mask, &s_sim, &s_real);
+            proof.s1 = select_scalar(mask, &s_real, &s_sim);
+        }
+
+        // sum(2^i * C_i) - X = rho * H with rho = sum(2^i * r_i)
+        let mut rho = Scalar::ZERO;
+        let mut weight = Scalar::ONE;
+        for r in blinds.iter_mut() {
+            rho = rho.add(&weight.mul(r));
+            weight = weight.add(&weight);
+            r.wipe();
+        }
+        let mut k = random_scalar(secp, rng);
+        let e = challenge(&prefix, 0xff, &[&h.mul(secp, &k)?]);
+        let s = k.add(&e.mul(&rho));
+        k.wipe();
+        rho.wipe();
+        Ok(RangeProof { bits: proofs, e, s })
+    }
+
+    /// Checks that the secret key of `pk` is below `2^self.bits()`, for a
+    /// proof made in `context`. Fails with `IncorrectSignature` if not.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, pk: &PublicKey, context: &[u8])
+                                   -> Result<(), Error> {
+        let h = pedersen_generator();
+        let minus_g = negated_generator(secp);
+        let prefix = challenge_prefix(context, pk, &self.bits);
+
+        let mut sum: Option<PublicKey> = None;
+        let mut weight = Scalar::ONE;
+        for (i, proof) in self.bits.iter().enumerate() {
+            let minus_one = proof.commitment.combine(secp, &minus_g).map_err(|_| IncorrectSignature)?;
+            let r0 = nonce(secp, &h, &proof.s0, &proof.e0, &proof.commitment).map_err(|_| IncorrectSignature)?;
+            let r1 = nonce(secp, &h, &proof.s1, &proof.e1, &minus_one).map_err(|_| IncorrectSignature)?;
+            if challenge(&prefix, i as u8, &[&r0, &r1]) != proof.e0.add(&proof.e1) {
+                return Err(IncorrectSignature);
+            }
+
+            let mut term = proof.commitment;
+            term.mul_assign(secp, &weight)?;
+            sum = Some(match sum {
+                Some(sum) => sum.combine(secp, &term).map_err(|_| IncorrectSignature)?,
+                None => term,
+            });
+            weight = weight.add(&weight);
+        }
+
+        let mut minus_pk = *pk;
+        minus_pk.mul_assign(secp, &Scalar::ONE.negate())?;
+        let d = sum.ok_or(IncorrectSignature)?.combine(secp, &minus_pk).map_err(|_| IncorrectSignature)?;
+        let r = nonce(secp, &h, &self.s, &self.e, &d).map_err(|_| IncorrectSignature)?;
+        if challenge(&prefix, 0xff, &[&r]) == self.e {
+            Ok(())
+        } else {
+            Err(IncorrectSignature)
+        }
+    }
+
+    /// The width of the range, in bits
+    #[inline]
+    pub fn bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Encodes the proof: the number of bits, then each bit's commitment,
+    /// challenges and responses, then the final challenge and response
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + BIT_PROOF_SIZE * self.bits.len() + 64);
+        out.push(self.bits.len() as u8);
+        for proof in &self.bits {
+            out.extend_from_slice(&proof.commitment.serialize());
+            for scalar in [&proof.e0, &proof.e1, &proof.s0, &proof.s1].iter() {
+                out.extend_from_slice(&scalar[..]);
+            }
+        }
+        out.extend_from_slice(&self.e[..]);
+        out.extend_from_slice(&self.s[..]);
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` for
+    /// any other data.
+    pub fn from_slice(data: &[u8]) -> Result<RangeProof, Error> {
+        let bits = match data.first() {
+            Some(&n) if n != 0 && n as usize <= MAX_RANGE_BITS => n as usize,
+            _ => return Err(InvalidSignature),
+        };
+        if data.len() != 1 + BIT_PROOF_SIZE * bits + 64 {
+            return Err(InvalidSignature);
+        }
+        let mut proofs = Vec::with_capacity(bits);
+        for chunk in data[1..1 + BIT_PROOF_SIZE * bits].chunks(BIT_PROOF_SIZE) {
+            let scalars = &chunk[constants::PUBLIC_KEY_SIZE..];
+            proofs.push(BitProof {
+                commitment: PublicKey::from_slice(&chunk[..constants::PUBLIC_KEY_SIZE])
+                    .map_err(|_| InvalidSignature)?,
+                e0: read_scalar(scalars)?,
+                e1: read_scalar(&scalars[32..])?,
+                s0: read_scalar(&scalars[64..])?,
+                s1: read_scalar(&scalars[96..])?,
+            });
+        }
+        let tail = &data[data.len() - 64..];
+        Ok(RangeProof { bits: proofs, e: read_scalar(tail)?, s: read_scalar(&tail[32..])? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{RangeProof, MAX_RANGE_BITS};
+    use super::super::{PublicKey, Secp256k1, SecretKey};
+    use super::super::Error::{IncorrectSignature, InvalidSignature, OutOfRange};
+
+    fn key(s: &Secp256k1<::All>, value: u64) -> (SecretKey, PublicKey) {
+        let mut bytes = [0u8; 32];
+        for i in 0..8 {
+            bytes[31 - i] = (value >> (8 * i)) as u8;
+        }
+        let sk = SecretKey::from_slice(&bytes).unwrap();
+        (sk, PublicKey::from_secret_key(s, &sk))
+    }
+
+    #[test]
+    fn prove_and_verify() {
+        let s = Secp256k1::new();
+        for &(value, bits) in [(1, 1), (5, 3), (0xff, 8), (0x100, 9), (0x1234_5678, 32)].iter() {
+            let (sk, pk) = key(&s, value);
+            let proof = RangeProof::prove(&s, &mut thread_rng(), &sk, bits, b"session").unwrap();
+            assert_eq!(proof.bits(), bits);
+            assert_eq!(proof.verify(&s, &pk, b"session"), Ok(()));
+            assert_eq!(proof.verify(&s, &pk, b"other session"), Err(IncorrectSignature));
+            let (_, other) = key(&s, value + 1);
+            assert_eq!(proof.verify(&s, &other, b"session"), Err(IncorrectSignature));
+        }
+
+        let (sk, _) = key(&s, 0x100);
+        assert_eq!(RangeProof::prove(&s, &mut thread_rng(), &sk, 8, b"").unwrap_err(), OutOfRange);
+        assert_eq!(RangeProof::prove(&s, &mut thread_rng(), &sk, 0, b"").unwrap_err(), OutOfRange);
+        assert_eq!(RangeProof::prove(&s, &mut thread_rng(), &sk, MAX_RANGE_BITS + 1, b"").unwrap_err(),
+                   OutOfRange);
+        let (big, _) = s.generate_keypair(&mut thread_rng());
+        assert!(RangeProof::prove(&s, &mut thread_rng(), &big, 64, b"").is_err());
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let (sk, pk) = key(&s, 0x2a);
+        let proof = RangeProof::prove(&s, &mut thread_rng(), &sk, 8, b"").unwrap();
+
+        let encoded = proof.serialize();
+        assert_eq!(encoded.len(), 1 + 161 * 8 + 64);
+        assert_eq!(RangeProof::from_slice(&encoded), Ok(proof.clone()));
+        assert_eq!(RangeProof::from_slice(&encoded[..encoded.len() - 1]), Err(InvalidSignature));
+        assert_eq!(RangeProof::from_slice(&[]), Err(InvalidSignature));
+        let mut zero_bits = encoded.clone();
+        zero_bits[0] = 0;
+        assert_eq!(RangeProof::from_slice(&zero_bits), Err(InvalidSignature));
+
+        // Claiming a narrower range fails
+        let mut narrower = proof.clone();
+        narrower.bits.pop();
+        assert_eq!(narrower.verify(&s, &pk, b""), Err(IncorrectSignature));
+        let mut tampered = encoded.clone();
+        tampered[40] ^= 1;
+        assert_eq!(RangeProof::from_slice(&tampered).unwrap().verify(&s, &pk, b""), Err(IncorrectSignature));
+    }
+}
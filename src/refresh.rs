@@ -0,0 +1,192 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Precomputation refresh
+//! Signing and key derivation go through the context's signing table,
+//! masked by its blinding. Anyone who learns the blinding, e.g. from a
+//! partial leak through a side channel, can unmask every later signing
+//! operation until it changes, and a table corrupted in memory silently
+//! gives wrong results. `Secp256k1::refresh_precomputation` rebuilds the
+//! table from scratch and re-randomizes the blinding, and a
+//! `RefreshingContext` does so on its own after every so many signatures.
+//!
+//! Rebuilding costs as much as creating a signing context (see
+//! `Secp256k1::creation_cost_hint`), so an interval of a few thousand
+//! signatures keeps the overhead small. Requires compilation with the
+//! "rand" feature.
+
+use std::ops::Deref;
+
+use rand_core::{RngCore, CryptoRng};
+
+use cleanup::wipe;
+use ffi;
+use key::SecretKey;
+use {Message, RecoverableSignature, Secp256k1, Signature, Signing};
+
+impl<C: Signing> Secp256k1<C> {
+    /// Rebuilds the signing table in the memory it already occupies, so a
+    /// preallocated context stays in its buffer, and re-randomizes the
+    /// context with a seed drawn from `rng`
+    pub fn refresh_precomputation<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        debug_log!(debug, "rebuilding the signing table");
+        unsafe {
+            // Every context of a `Signing` type has the tables
+            let res = ffi::secp256k1_context_rebuild_sign(self.ctx);
+            assert_eq!(res, 1);
+        }
+        let mut seed = [0; 32];
+        rng.fill_bytes(&mut seed);
+        self.randomize_with_seed(&seed);
+        wipe(&mut seed);
+    }
+}
+
+/// A signing context which refreshes its precomputation, with
+/// `Secp256k1::refresh_precomputation`, after every `interval` signatures
+/// made through it. Everything else the context does is available through
+/// `Deref`, but only `sign` and `sign_recoverable` count towards the
+/// interval.
+pub struct RefreshingContext<C, R> {
+    secp: Secp256k1<C>,
+    rng: R,
+    interval: u64,
+    remaining: u64,
+}
+
+impl<C: Signing, R: RngCore + CryptoRng> RefreshingContext<C, R> {
+    /// Wraps `secp`, refreshing it with entropy from `rng` after every
+    /// `interval` signatures. The context is refreshed first, so it starts
+    /// with a fresh table and blinding. Panics if `interval` is zero.
+    pub fn new(mut secp: Secp256k1<C>, mut rng: R, interval: u64) -> RefreshingContext<C, R> {
+        assert!(interval > 0, "the refresh interval must be at least one signature");
+        secp.refresh_precomputation(&mut rng);
+        RefreshingContext { secp, rng, interval, remaining: interval }
+    }
+
+    /// The number of signatures between refreshes
+    #[inline]
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// The number of signatures left until the next refresh
+    #[inline]
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Refreshes the context now, restarting the interval
+    pub fn refresh(&mut self) {
+        self.secp.refresh_precomputation(&mut self.rng);
+        self.remaining = self.interval;
+    }
+
+    /// Counts a signature, refreshing the context after the last one of the
+    /// interval
+    fn count(&mut self) {
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.refresh();
+        }
+    }
+
+    /// Signs as `Secp256k1::sign`
+    pub fn sign(&mut self, msg: &Message, sk: &SecretKey) -> Signature {
+        let ret = self.secp.sign(msg, sk);
+        self.count();
+        ret
+    }
+
+    /// Signs as `Secp256k1::sign_recoverable`
+    pub fn sign_recoverable(&mut self, msg: &Message, sk: &SecretKey) -> RecoverableSignature {
+        let ret = self.secp.sign_recoverable(msg, sk);
+        self.count();
+        ret
+    }
+
+    /// Unwraps the context
+    pub fn into_inner(self) -> Secp256k1<C> {
+        self.secp
+    }
+}
+
+impl<C, R> Deref for RefreshingContext<C, R> {
+    type Target = Secp256k1<C>;
+
+    fn deref(&self) -> &Secp256k1<C> {
+        &self.secp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+    use std::vec;
+
+    use super::RefreshingContext;
+    use key::{PublicKey, SecretKey};
+    use rng::thread_rng;
+    use {Message, Secp256k1};
+    use super::super::Error::ContextCorrupted;
+
+    #[test]
+    fn refresh_precomputation() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let plain = Secp256k1::new();
+
+        let mut secp = Secp256k1::new();
+        secp.refresh_precomputation(&mut thread_rng());
+        assert_eq!(secp.sign(&msg, &sk), plain.sign(&msg, &sk));
+        assert_eq!(PublicKey::from_secret_key(&secp, &sk), PublicKey::from_secret_key(&plain, &sk));
+        assert!(secp.self_test().passed());
+
+        // A corrupted table is rebuilt in its buffer
+        let size = Secp256k1::builder().signing().preallocated_size();
+        let buf = Box::leak(vec![0u8; size].into_boxed_slice());
+        let ptr = buf.as_mut_ptr();
+        let mut secp = Secp256k1::builder().signing().preallocated(buf).build().unwrap();
+        for i in (size / 8..size).step_by(64) {
+            unsafe { *ptr.add(i) ^= 0x10; }
+        }
+        assert_eq!(secp.integrity_check(), Err(ContextCorrupted));
+        secp.refresh_precomputation(&mut thread_rng());
+        assert_eq!(secp.integrity_check(), Ok(()));
+        assert_eq!(secp.sign(&msg, &sk), plain.sign(&msg, &sk));
+    }
+
+    #[test]
+    fn refreshing_context() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let plain = Secp256k1::new();
+        let pk = PublicKey::from_secret_key(&plain, &sk);
+
+        let mut secp = RefreshingContext::new(Secp256k1::new(), thread_rng(), 3);
+        assert_eq!(secp.interval(), 3);
+        assert_eq!(secp.remaining(), 3);
+        for i in 0..7 {
+            let sig = secp.sign(&msg, &sk);
+            assert_eq!(sig, plain.sign(&msg, &sk));
+            assert_eq!(secp.verify(&msg, &sig, &pk), Ok(()));
+            assert_eq!(secp.remaining(), 3 - (i + 1) % 3);
+        }
+        assert_eq!(secp.sign_recoverable(&msg, &sk), plain.sign_recoverable(&msg, &sk));
+        assert_eq!(secp.remaining(), 1);
+        secp.refresh();
+        assert_eq!(secp.remaining(), 3);
+        assert!(secp.into_inner().self_test().passed());
+    }
+}
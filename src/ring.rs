@@ -0,0 +1,295 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Linkable ring signatures
+//! LSAG signatures: a signature by one of the keys in a ring, which doesn't
+//! reveal which one, as for "one of these enclaves signed this". Each
+//! signature carries the signer's key image `I = x * Hp(P)`, which is the
+//! same for every signature by the same key and reveals nothing else, so
+//! two signatures by the same signer can be linked (e.g. to reject double
+//! votes) whatever the rings and messages.
+//!
+//! `Hp` hashes a public key to a curve point by try-and-increment, which
+//! takes variable time but only ever sees public keys. Signatures are
+//! `KEY_IMAGE_SIZE + 32 * (ring size + 1)` bytes long. Requires compilation
+//! with the "hashes" feature.
+
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use blind::mul_add;
+use constants;
+use generator::Generator;
+use hashes::{TaggedHasher, RING_CHALLENGE_TAG, RING_HASH_TO_POINT_TAG};
+use key::{PublicKey, SecretKey};
+use le_bytes::u32_bytes;
+use scalar::Scalar;
+use super::Error::{self, IncorrectSignature, InvalidRing, InvalidSignature};
+#[cfg(any(test, feature = "rand"))]
+use super::Error::PointAtInfinity;
+use {Message, Secp256k1, Signing, Verification};
+
+/// The size (in bytes) of an encoded key image
+pub const KEY_IMAGE_SIZE: usize = constants::PUBLIC_KEY_SIZE;
+
+/// The key image of a ring member's secret key, which links all the
+/// signatures it makes
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct KeyImage(PublicKey);
+
+impl KeyImage {
+    /// Computes the key image of `sk`
+    pub fn new<C: Signing + Verification>(secp: &Secp256k1<C>, sk: &SecretKey) -> KeyImage {
+        let mut image = hash_to_point(&PublicKey::from_secret_key(secp, sk));
+        image.mul_assign(secp, &Scalar::from_secret_key(sk)).expect("secret keys are not zero");
+        KeyImage(image)
+    }
+
+    /// Encodes the key image as a compressed point
+    #[inline]
+    pub fn serialize(&self) -> [u8; KEY_IMAGE_SIZE] {
+        self.0.serialize()
+    }
+
+    /// Parses the output of `serialize`
+    #[inline]
+    pub fn from_slice(data: &[u8]) -> Result<KeyImage, Error> {
+        PublicKey::from_slice(data).map(KeyImage)
+    }
+}
+
+/// A linkable ring signature
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RingSignature {
+    key_image: KeyImage,
+    challenge: Scalar,
+    responses: Vec<Scalar>,
+}
+
+/// Hashes a public key to a point with unknown discrete logarithm
+fn hash_to_point(pk: &PublicKey) -> PublicKey {
+    Generator::from_seed(RING_HASH_TO_POINT_TAG, &pk.serialize()).into()
+}
+
+/// The hash of everything a signature is over except the ring's current
+/// commitments `l` and `r`
+fn challenge_prefix(ring: &[PublicKey], key_image: &KeyImage, msg: &Message) -> TaggedHasher {
+    let mut engine = TaggedHasher::new(RING_CHALLENGE_TAG);
+    engine.input(&u32_bytes(ring.len() as u32));
+    for pk in ring {
+        engine.input(&pk.serialize());
+    }
+    engine.input(&key_image.serialize());
+    engine.input(&msg[..]);
+    engine
+}
+
+fn challenge(prefix: &TaggedHasher, l: &PublicKey, r: &PublicKey) -> Scalar {
+    let mut engine = prefix.clone();
+    engine.input(&l.serialize());
+    engine.input(&r.serialize());
+    Scalar::from_be_bytes_mod_order(&engine.result())
+}
+
+impl RingSignature {
+    /// Signs `msg` with `sk` on behalf of `ring`, which must contain the
+    /// public key of `sk`. The position of the key in the ring is hidden;
+    /// the order of the ring is not, so verifiers must use the same order.
+    /// Fails with `InvalidRing` if `sk` is not in the ring.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn sign<C: Signing + Verification, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R,
+                                                                   ring: &[PublicKey], sk: &SecretKey,
+                                                                   msg: &Message) -> Result<RingSignature, Error> {
+        let pk = PublicKey::from_secret_key(secp, sk);
+        let signer = ring.iter().position(|p| *p == pk).ok_or(InvalidRing)?;
+        let key_image = KeyImage::new(secp, sk);
+        let prefix = challenge_prefix(ring, &key_image, msg);
+        let n = ring.len();
+
+        let mut responses: Vec<Scalar> = (0..n).map(|_| Scalar::from_secret_key(&SecretKey::new(secp, rng))).collect();
+        let mut alpha = Scalar::from_secret_key(&SecretKey::new(secp, rng));
+        let mut challenges = vec![Scalar::ZERO; n];
+        let mut l = PublicKey::from_scalar_mul_g(secp, &alpha)?;
+        let mut r = hash_to_point(&pk);
+        r.mul_assign(secp, &alpha)?;
+        for step in 1..n {
+            let i = (signer + step) % n;
+            challenges[i] = challenge(&prefix, &l, &r);
+            l = mul_add(secp, &responses[i], &Generator::g().into(), &challenges[i], &ring[i])
+                .ok_or(PointAtInfinity)?;
+            r = mul_add(secp, &responses[i], &hash_to_point(&ring[i]), &challenges[i], &key_image.0)
+                .ok_or(PointAtInfinity)?;
+        }
+        challenges[signer] = challenge(&prefix, &l, &r);
+
+        // s = alpha - c * x closes the ring
+        let mut x = Scalar::from_secret_key(sk);
+        responses[signer] = alpha.add(&challenges[signer].mul(&x).negate());
+        x.wipe();
+        alpha.wipe();
+        Ok(RingSignature { key_image, challenge: challenges[0], responses })
+    }
+
+    /// Checks the signature for `msg` and `ring`. Fails with
+    /// `IncorrectSignature` if it is wrong, including for a ring of the
+    /// wrong size.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, ring: &[PublicKey], msg: &Message)
+                                   -> Result<(), Error> {
+        if ring.len() != self.responses.len() {
+            return Err(IncorrectSignature);
+        }
+        let prefix = challenge_prefix(ring, &self.key_image, msg);
+        let mut c = self.challenge;
+        for (pk, s) in ring.iter().zip(&self.responses) {
+            let l = mul_add(secp, s, &Generator::g().into(), &c, pk).ok_or(IncorrectSignature)?;
+            let r = mul_add(secp, s, &hash_to_point(pk), &c, &self.key_image.0).ok_or(IncorrectSignature)?;
+            c = challenge(&prefix, &l, &r);
+        }
+        if c == self.challenge {
+            Ok(())
+        } else {
+            Err(IncorrectSignature)
+        }
+    }
+
+    /// The signer's key image
+    #[inline]
+    pub fn key_image(&self) -> &KeyImage {
+        &self.key_image
+    }
+
+    /// Whether both signatures were made by the same key. Only meaningful
+    /// for verified signatures.
+    #[inline]
+    pub fn is_linked(&self, other: &RingSignature) -> bool {
+        self.key_image == other.key_image
+    }
+
+    /// Encodes the signature: the key image, the initial challenge, then
+    /// one response per ring member
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(KEY_IMAGE_SIZE + 32 * (1 + self.responses.len()));
+        out.extend_from_slice(&self.key_image.serialize());
+        out.extend_from_slice(&self.challenge[..]);
+        for s in &self.responses {
+            out.extend_from_slice(&s[..]);
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` for
+    /// any other data.
+    pub fn from_slice(data: &[u8]) -> Result<RingSignature, Error> {
+        let scalars = data.len().saturating_sub(KEY_IMAGE_SIZE) / 32;
+        if scalars < 2 || KEY_IMAGE_SIZE + 32 * scalars != data.len() {
+            return Err(InvalidSignature);
+        }
+        let key_image = KeyImage::from_slice(&data[..KEY_IMAGE_SIZE]).map_err(|_| InvalidSignature)?;
+        let mut scalars = data[KEY_IMAGE_SIZE..].chunks(32).map(|chunk| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(chunk);
+            Scalar::from_be_bytes(bytes).map_err(|_| InvalidSignature)
+        });
+        let challenge = scalars.next().expect("length checked")?;
+        Ok(RingSignature { key_image, challenge, responses: scalars.collect::<Result<Vec<Scalar>, Error>>()? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{KeyImage, RingSignature};
+    use super::super::{Message, PublicKey, Secp256k1, SecretKey};
+    use super::super::Error::{IncorrectSignature, InvalidRing, InvalidSignature};
+
+    fn ring(s: &Secp256k1<::All>, n: usize) -> (Vec<SecretKey>, Vec<PublicKey>) {
+        (0..n).map(|_| s.generate_keypair(&mut thread_rng())).unzip()
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let other_msg = Message::from_slice(&[0x43; 32]).unwrap();
+        for &n in [1, 2, 5].iter() {
+            let (sks, pks) = ring(&s, n);
+            for sk in &sks {
+                let sig = RingSignature::sign(&s, &mut thread_rng(), &pks, sk, &msg).unwrap();
+                assert_eq!(sig.verify(&s, &pks, &msg), Ok(()));
+                assert_eq!(*sig.key_image(), KeyImage::new(&s, sk));
+                assert_eq!(sig.verify(&s, &pks, &other_msg), Err(IncorrectSignature));
+                assert_eq!(sig.verify(&s, &pks[1..], &msg), Err(IncorrectSignature));
+                let mut reversed = pks.clone();
+                reversed.reverse();
+                if n > 1 {
+                    assert_eq!(sig.verify(&s, &reversed, &msg), Err(IncorrectSignature));
+                }
+            }
+        }
+
+        let (_, pks) = ring(&s, 3);
+        let (outsider, _) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(RingSignature::sign(&s, &mut thread_rng(), &pks, &outsider, &msg), Err(InvalidRing));
+        assert_eq!(RingSignature::sign(&s, &mut thread_rng(), &[], &outsider, &msg), Err(InvalidRing));
+    }
+
+    #[test]
+    fn linkability() {
+        let s = Secp256k1::new();
+        let (sks, pks) = ring(&s, 4);
+        let (more_sks, mut more_pks) = ring(&s, 2);
+        more_pks.push(pks[1]);
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let other_msg = Message::from_slice(&[0x43; 32]).unwrap();
+
+        let first = RingSignature::sign(&s, &mut thread_rng(), &pks, &sks[1], &msg).unwrap();
+        // Another message and ring, but the same signer
+        let second = RingSignature::sign(&s, &mut thread_rng(), &more_pks, &sks[1], &other_msg).unwrap();
+        assert_eq!(second.verify(&s, &more_pks, &other_msg), Ok(()));
+        assert!(first.is_linked(&second));
+        let third = RingSignature::sign(&s, &mut thread_rng(), &more_pks, &more_sks[0], &other_msg).unwrap();
+        assert!(!first.is_linked(&third));
+
+        // A key image can't be swapped for another signer's
+        let mut forged = first.clone();
+        forged.key_image = *third.key_image();
+        assert_eq!(forged.verify(&s, &pks, &msg), Err(IncorrectSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let (sks, pks) = ring(&s, 3);
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sig = RingSignature::sign(&s, &mut thread_rng(), &pks, &sks[2], &msg).unwrap();
+
+        let encoded = sig.serialize();
+        assert_eq!(encoded.len(), 33 + 32 * 4);
+        assert_eq!(RingSignature::from_slice(&encoded), Ok(sig.clone()));
+        assert_eq!(KeyImage::from_slice(&encoded[..33]), Ok(*sig.key_image()));
+
+        assert_eq!(RingSignature::from_slice(&encoded[..encoded.len() - 1]), Err(InvalidSignature));
+        assert_eq!(RingSignature::from_slice(&encoded[..33 + 32]), Err(InvalidSignature));
+        let mut overflow = encoded.clone();
+        for byte in overflow[33..65].iter_mut() {
+            *byte = 0xff;
+        }
+        assert_eq!(RingSignature::from_slice(&overflow), Err(InvalidSignature));
+        let mut tampered = encoded.clone();
+        tampered[100] ^= 1;
+        assert_eq!(RingSignature::from_slice(&tampered).unwrap().verify(&s, &pks, &msg), Err(IncorrectSignature));
+    }
+}
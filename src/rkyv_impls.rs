@@ -0,0 +1,162 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # rkyv
+//! Implementations of the `rkyv` 0.8 traits, for zero-copy queues between
+//! threads or across the enclave boundary. Each type archives as its
+//! fixed-size encoding in a byte array, with no alignment requirement: the
+//! 33-byte compressed form for a `PublicKey`, the 32 bytes of an
+//! `XOnlyPublicKey` or `Message` and the 64-byte compact `r || s` for a
+//! `Signature`.
+//!
+//! Any bytes pass validation, so `rkyv::access` on untrusted data costs no
+//! more than the archive's layout checks. The encoding itself is checked
+//! when an archived value is converted back, with `to_native` or by
+//! deserializing, which fails with the error `from_slice` gives.
+//!
+//! Secret keys can't be archived: an archive lives in a buffer the library
+//! can't wipe, often in memory the host shares. Requires compilation with
+//! the "rkyv" feature.
+
+use rkyv::{Archive, Deserialize, Place, Portable, Serialize};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::{Fallible, Source};
+use rkyv::traits::NoUndef;
+
+use constants;
+use key::{PublicKey, XOnlyPublicKey};
+use super::Error;
+use {Message, Signature};
+
+macro_rules! impl_rkyv {
+    ($thing:ident, $archived:ident, $len:expr, $to_bytes:expr, $from_bytes:expr) => {
+        #[doc = concat!("An archived `", stringify!($thing), "`: its ", stringify!($len), "-byte encoding")]
+        #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+        #[repr(transparent)]
+        pub struct $archived([u8; $len]);
+
+        impl $archived {
+            /// The encoding, as it lies in the archive
+            #[inline]
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+
+            #[doc = concat!("Parses the encoding back into a `", stringify!($thing), "`")]
+            #[inline]
+            pub fn to_native(&self) -> Result<$thing, Error> {
+                $from_bytes(&self.0)
+            }
+        }
+
+        // SAFETY: a byte array has alignment 1, no padding and no interior
+        // mutability, and every bit pattern is a valid value
+        unsafe impl Portable for $archived {}
+        unsafe impl NoUndef for $archived {}
+
+        unsafe impl<C: Fallible + ?Sized> CheckBytes<C> for $archived {
+            #[inline]
+            unsafe fn check_bytes(_value: *const Self, _context: &mut C) -> Result<(), C::Error> {
+                Ok(())
+            }
+        }
+
+        impl Archive for $thing {
+            type Archived = $archived;
+            type Resolver = ();
+
+            #[inline]
+            fn resolve(&self, _resolver: (), out: Place<$archived>) {
+                out.write($archived($to_bytes(self)));
+            }
+        }
+
+        impl<S: Fallible + ?Sized> Serialize<S> for $thing {
+            #[inline]
+            fn serialize(&self, _serializer: &mut S) -> Result<(), S::Error> {
+                Ok(())
+            }
+        }
+
+        impl<D: Fallible + ?Sized> Deserialize<$thing, D> for $archived where D::Error: Source {
+            #[inline]
+            fn deserialize(&self, _deserializer: &mut D) -> Result<$thing, D::Error> {
+                self.to_native().map_err(D::Error::new)
+            }
+        }
+    };
+}
+
+impl_rkyv!(PublicKey, ArchivedPublicKey, constants::PUBLIC_KEY_SIZE,
+           |pk: &PublicKey| pk.serialize(), |data: &[u8; constants::PUBLIC_KEY_SIZE]| PublicKey::from_slice(data));
+impl_rkyv!(XOnlyPublicKey, ArchivedXOnlyPublicKey, constants::XONLY_PUBLIC_KEY_SIZE,
+           |pk: &XOnlyPublicKey| pk.serialize(),
+           |data: &[u8; constants::XONLY_PUBLIC_KEY_SIZE]| XOnlyPublicKey::from_slice(data));
+impl_rkyv!(Signature, ArchivedSignature, constants::COMPACT_SIGNATURE_SIZE,
+           |sig: &Signature| sig.compact_bytes(), Signature::from_compact_bytes);
+impl_rkyv!(Message, ArchivedMessage, constants::MESSAGE_SIZE,
+           |msg: &Message| msg.0, |data: &[u8; constants::MESSAGE_SIZE]| Ok(Message(*data)));
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use rkyv::rancor;
+
+    use super::{ArchivedPublicKey, ArchivedSignature};
+    use key::{PublicKey, XOnlyPublicKey};
+    use rng::thread_rng;
+    use {Message, Secp256k1, Signature};
+    use super::super::Error::PublicKeyNotOnCurve;
+
+    #[test]
+    fn round_trip() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sig = s.sign(&msg, &sk);
+        let (xonly, _) = pk.x_only_public_key();
+
+        let bytes = rkyv::to_bytes::<rancor::Error>(&(pk, sig, xonly, msg)).unwrap();
+        assert_eq!(bytes.len(), 33 + 64 + 32 + 32);
+        let archived = rkyv::access::<rkyv::Archived<(PublicKey, Signature, XOnlyPublicKey, Message)>,
+                                      rancor::Error>(&bytes).unwrap();
+        assert_eq!(archived.0.as_bytes(), &pk.serialize());
+        assert_eq!(archived.0.to_native(), Ok(pk));
+        assert_eq!(archived.1.to_native(), Ok(sig));
+        assert_eq!(archived.2.to_native(), Ok(xonly));
+        assert_eq!(archived.3.to_native(), Ok(msg));
+        assert_eq!(s.verify(&archived.3.to_native().unwrap(), &archived.1.to_native().unwrap(),
+                            &archived.0.to_native().unwrap()), Ok(()));
+
+        let back = rkyv::from_bytes::<(PublicKey, Signature, XOnlyPublicKey, Message), rancor::Error>(&bytes);
+        assert_eq!(back.unwrap(), (pk, sig, xonly, msg));
+
+        let bytes = rkyv::to_bytes::<rancor::Error>(&pk).unwrap();
+        assert_eq!(rkyv::from_bytes::<PublicKey, rancor::Error>(&bytes).unwrap(), pk);
+    }
+
+    #[test]
+    fn invalid() {
+        // Access succeeds on any bytes; conversion checks them
+        let mut bad_key = [0u8; 33];
+        bad_key[0] = 0x02;
+        bad_key[32] = 5;
+        let archived = rkyv::access::<ArchivedPublicKey, rancor::Error>(&bad_key).unwrap();
+        assert_eq!(archived.to_native(), Err(PublicKeyNotOnCurve));
+        assert!(rkyv::from_bytes::<PublicKey, rancor::Error>(&bad_key).is_err());
+
+        let archived = rkyv::access::<ArchivedSignature, rancor::Error>(&[0xff; 64]).unwrap();
+        assert!(archived.to_native().is_err());
+        assert!(rkyv::from_bytes::<Signature, rancor::Error>(&[0xff; 64]).is_err());
+    }
+}
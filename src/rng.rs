@@ -0,0 +1,366 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Random number generation
+//! Functions taking randomness are generic over `rand_core::RngCore +
+//! rand_core::CryptoRng`, the traits shared by every modern RNG crate.
+//! `sgx_rand` still implements the older `Rng` trait, so its generators are
+//! wrapped in an `SgxRngAdapter` to satisfy the same bound. Only the
+//! adapters around the RDRAND-backed `SgxRng` and the thread-local
+//! generator are `CryptoRng`; other `sgx_rand` generators, such as
+//! `XorShiftRng`, are predictable and can't be used to make keys. Requires
+//! compilation with the "rand" feature.
+//!
+//! For tests which must produce the same keys and signatures on every run
+//! and platform, e.g. against golden files, the "test-determinism" feature
+//! adds `HmacDrbg`, a generator seeded by the caller. Its output is only as
+//! secret as the seed, so it must never be used outside tests.
+//!
+//! With the "no-ocall" feature, the functions which draw entropy without
+//! being handed a generator (`SecretKey::new_random`,
+//! `ContextBuilder::randomize` and the randomization of the global and
+//! thread-local contexts) take it from the `EntropySource` installed with
+//! `set_entropy_source` instead of `thread_rng`, which is compiled out.
+
+use rand;
+use rand_core::{self, RngCore, CryptoRng};
+#[cfg(feature = "test-determinism")]
+use hashes::{HmacSha256, SHA256_SIZE};
+#[cfg(feature = "no-ocall")]
+use std::cell::UnsafeCell;
+#[cfg(feature = "no-ocall")]
+use std::num::NonZeroU32;
+#[cfg(feature = "no-ocall")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Wraps an `sgx_rand` generator so that it can be passed anywhere a
+/// `RngCore` is expected.
+///
+/// The adapter is only `CryptoRng`, and so only accepted for keys and
+/// nonces, when it wraps `SgxRng` or the thread-local generator.
+#[derive(Debug, Clone)]
+pub struct SgxRngAdapter<R>(R);
+
+impl<R: rand::Rng> SgxRngAdapter<R> {
+    /// Wraps an `sgx_rand` generator
+    #[inline]
+    pub fn new(rng: R) -> SgxRngAdapter<R> {
+        SgxRngAdapter(rng)
+    }
+
+    /// Returns the wrapped generator
+    #[inline]
+    pub fn into_inner(self) -> R {
+        self.0
+    }
+}
+
+impl<R: rand::Rng> RngCore for SgxRngAdapter<R> {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    #[inline]
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SgxRngAdapter<rand::SgxRng> {}
+
+impl CryptoRng for SgxRngAdapter<rand::ThreadRng> {}
+
+/// Returns the thread-local `sgx_rand` generator wrapped in an adapter
+#[inline]
+#[cfg(any(test, not(feature = "no-ocall")))]
+pub fn thread_rng() -> SgxRngAdapter<rand::ThreadRng> {
+    SgxRngAdapter(rand::thread_rng())
+}
+
+/// Where the library gets entropy it isn't handed, e.g. an enclave's
+/// RDRAND wrapper. It may be called from several threads at once. Requires
+/// compilation with the "no-ocall" feature.
+#[cfg(feature = "no-ocall")]
+pub trait EntropySource: Sync {
+    /// Fills `dest` with fresh random bytes, or returns false if there are
+    /// none to be had
+    fn fill(&self, dest: &mut [u8]) -> bool;
+}
+
+/// The installed source, guarded by a spin lock like `metrics`' sink
+#[cfg(feature = "no-ocall")]
+struct Slot {
+    lock: AtomicBool,
+    source: UnsafeCell<Option<&'static EntropySource>>,
+}
+
+#[cfg(feature = "no-ocall")]
+unsafe impl Sync for Slot {}
+
+#[cfg(feature = "no-ocall")]
+static SLOT: Slot = Slot { lock: AtomicBool::new(false), source: UnsafeCell::new(None) };
+
+#[cfg(feature = "no-ocall")]
+fn with_source<T, F: FnOnce(&mut Option<&'static EntropySource>) -> T>(f: F) -> T {
+    while SLOT.lock.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {}
+    let ret = f(unsafe { &mut *SLOT.source.get() });
+    SLOT.lock.store(false, Ordering::Release);
+    ret
+}
+
+/// Makes `source` the library's entropy, replacing any source installed
+/// before. Requires compilation with the "no-ocall" feature.
+#[cfg(feature = "no-ocall")]
+pub fn set_entropy_source(source: &'static EntropySource) {
+    with_source(|slot| *slot = Some(source));
+}
+
+/// Removes the installed entropy source. Requires compilation with the
+/// "no-ocall" feature.
+#[cfg(feature = "no-ocall")]
+pub fn clear_entropy_source() {
+    with_source(|slot| *slot = None);
+}
+
+/// A generator reading the source installed with `set_entropy_source`.
+/// `try_fill_bytes` fails, and the other methods panic, if there is none or
+/// it has no entropy. Requires compilation with the "no-ocall" feature.
+#[cfg(feature = "no-ocall")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EntropyRng;
+
+#[cfg(feature = "no-ocall")]
+impl RngCore for EntropyRng {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if self.try_fill_bytes(dest).is_err() {
+            panic!("no entropy source; install one with rng::set_entropy_source");
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        let filled = with_source(|slot| *slot).map(|source| source.fill(dest)).unwrap_or(false);
+        if filled {
+            Ok(())
+        } else {
+            Err(NonZeroU32::new(rand_core::Error::CUSTOM_START).unwrap().into())
+        }
+    }
+}
+
+#[cfg(feature = "no-ocall")]
+impl CryptoRng for EntropyRng {}
+
+/// Installs a source which draws from `thread_rng`, for tests of the
+/// functions which aren't handed a generator, and returns the number of
+/// bytes it has given out
+#[cfg(all(test, feature = "no-ocall"))]
+pub fn install_test_source() -> usize {
+    use std::sync::atomic::AtomicUsize;
+
+    struct Counting(AtomicUsize);
+
+    impl EntropySource for Counting {
+        fn fill(&self, dest: &mut [u8]) -> bool {
+            self.0.fetch_add(dest.len(), Ordering::SeqCst);
+            thread_rng().fill_bytes(dest);
+            true
+        }
+    }
+
+    static SOURCE: Counting = Counting(AtomicUsize::new(0));
+    set_entropy_source(&SOURCE);
+    SOURCE.0.load(Ordering::SeqCst)
+}
+
+/// The generator behind the functions which aren't handed one
+#[cfg(not(feature = "no-ocall"))]
+#[inline]
+pub(crate) fn ambient() -> SgxRngAdapter<rand::ThreadRng> {
+    thread_rng()
+}
+
+/// The generator behind the functions which aren't handed one
+#[cfg(feature = "no-ocall")]
+#[inline]
+pub(crate) fn ambient() -> EntropyRng {
+    EntropyRng
+}
+
+/// The HMAC-SHA256 DRBG of NIST SP 800-90A, without reseeding or
+/// additional input, instantiated with `seed` as its entropy input. Each
+/// `fill_bytes` call is one generate request. Requires compilation with the
+/// "test-determinism" feature.
+///
+/// The generator claims `CryptoRng` so that it can stand in for the real
+/// one, but anyone who knows the seed can replay everything it generated.
+#[cfg(feature = "test-determinism")]
+#[derive(Clone)]
+pub struct HmacDrbg {
+    k: [u8; SHA256_SIZE],
+    v: [u8; SHA256_SIZE],
+}
+
+#[cfg(feature = "test-determinism")]
+impl HmacDrbg {
+    /// Instantiates the generator from `seed`, which may have any length
+    pub fn new(seed: &[u8]) -> HmacDrbg {
+        let mut drbg = HmacDrbg { k: [0; SHA256_SIZE], v: [1; SHA256_SIZE] };
+        drbg.update(seed);
+        drbg
+    }
+
+    fn hmac(&self, separator: Option<u8>, data: &[u8]) -> [u8; SHA256_SIZE] {
+        let mut engine = HmacSha256::new(&self.k);
+        engine.input(&self.v);
+        if let Some(separator) = separator {
+            engine.input(&[separator]);
+        }
+        engine.input(data);
+        engine.result()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.k = self.hmac(Some(0), data);
+        self.v = self.hmac(None, &[]);
+        if !data.is_empty() {
+            self.k = self.hmac(Some(1), data);
+            self.v = self.hmac(None, &[]);
+        }
+    }
+}
+
+#[cfg(feature = "test-determinism")]
+impl RngCore for HmacDrbg {
+    #[inline]
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    #[inline]
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(SHA256_SIZE) {
+            self.v = self.hmac(None, &[]);
+            chunk.copy_from_slice(&self.v[..chunk.len()]);
+        }
+        self.update(&[]);
+    }
+
+    #[inline]
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "test-determinism")]
+impl CryptoRng for HmacDrbg {}
+
+#[cfg(feature = "test-determinism")]
+impl Drop for HmacDrbg {
+    fn drop(&mut self) {
+        ::cleanup::wipe(&mut self.k);
+        ::cleanup::wipe(&mut self.v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use rand_core::RngCore;
+    use super::SgxRngAdapter;
+
+    struct CounterRng(u32);
+    impl Rng for CounterRng {
+        fn next_u32(&mut self) -> u32 { self.0 += 1; self.0 }
+    }
+
+    #[test]
+    fn adapter_forwards_to_inner() {
+        let mut direct = CounterRng(0);
+        let mut adapted = SgxRngAdapter::new(CounterRng(0));
+
+        assert_eq!(adapted.next_u32(), direct.next_u32());
+        assert_eq!(adapted.next_u64(), direct.next_u64());
+
+        let mut expected = [0; 13];
+        let mut actual = [0; 13];
+        direct.fill_bytes(&mut expected);
+        adapted.try_fill_bytes(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+        assert_eq!(adapted.into_inner().0, direct.0);
+    }
+
+    #[cfg(feature = "test-determinism")]
+    #[test]
+    fn hmac_drbg() {
+        use super::HmacDrbg;
+
+        let mut drbg = HmacDrbg::new(b"golden file seed");
+        let mut buf = [0; 80];
+        let mut out = [0; 40];
+        drbg.fill_bytes(&mut out);
+        assert_eq!(::hex::encode_to(&out, &mut buf),
+                   "72e247b6811ccf5bc30bdca4e9a915a731bcbb6243fb9405956c3fd9d7a0a161779c94dc7813bb38");
+        let mut out = [0; 32];
+        drbg.clone().fill_bytes(&mut out);
+        assert_eq!(::hex::encode_to(&out, &mut buf),
+                   "14fb20c179bb99906aea55a60fa35f492c98e0d55a327c52a32d6f3d74631078");
+        // The words are the first bytes of the next request
+        assert_eq!(&drbg.next_u64().to_le_bytes()[..], &out[..8]);
+
+        let mut other = [0; 32];
+        HmacDrbg::new(b"golden file seee").fill_bytes(&mut other);
+        assert!(other[..] != out[..]);
+    }
+
+    #[cfg(feature = "no-ocall")]
+    #[test]
+    fn entropy_source() {
+        use super::{install_test_source, EntropyRng};
+        use key::SecretKey;
+
+        let before = install_test_source();
+        let mut out = [0; 40];
+        EntropyRng.try_fill_bytes(&mut out).unwrap();
+        assert!(SecretKey::new_random() != SecretKey::new_random());
+        // Other tests may draw from the source at the same time
+        assert!(install_test_source() >= before + 40 + 64);
+    }
+}
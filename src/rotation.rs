@@ -0,0 +1,231 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Key rotation
+//! Continuity proofs for replacing a vault key with a successor. The old
+//! key signs the new public key and the new key signs the old one, so that
+//! anyone who trusts the old key can follow the rotation, and nobody can
+//! claim a successor (or predecessor) without holding both keys. Requires
+//! compilation with the "hashes" feature.
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use constants;
+use hashes::Sha256;
+use key::PublicKey;
+#[cfg(any(test, feature = "rand"))]
+use vault::{KeyHandle, Vault};
+use super::Error::{self, InvalidRotationRecord};
+use {Message, Secp256k1, Signature, Verification};
+#[cfg(any(test, feature = "rand"))]
+use Signing;
+
+/// Format version of the encoding
+const VERSION: u8 = 1;
+
+/// Prefixed, with a byte for the signing direction, to both keys before
+/// hashing
+const SIGNING_TAG: &'static [u8] = b"rust-secp256k1-sgx key rotation";
+
+/// The size (in bytes) of an encoded rotation record
+pub const ROTATION_RECORD_SIZE: usize = 1 + 2 * constants::PUBLIC_KEY_SIZE + 2 * constants::COMPACT_SIGNATURE_SIZE;
+
+/// The proof that `old_key` was rotated to `new_key`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RotationRecord {
+    /// The key being replaced
+    pub old_key: PublicKey,
+    /// Its successor
+    pub new_key: PublicKey,
+    /// Signature by the old key over the new key
+    pub old_signature: Signature,
+    /// Signature by the new key over the old key
+    pub new_signature: Signature,
+}
+
+/// The message signed by the old key (`by_old`) or by the new one
+fn signing_message(old_key: &PublicKey, new_key: &PublicKey, by_old: bool) -> Message {
+    let mut engine = Sha256::new();
+    engine.input(SIGNING_TAG);
+    engine.input(&[if by_old { 0 } else { 1 }]);
+    engine.input(&old_key.serialize());
+    engine.input(&new_key.serialize());
+    Message::from(engine.result())
+}
+
+impl RotationRecord {
+    /// Checks both signatures. Fails with `IncorrectSignature` if either is
+    /// wrong.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>) -> Result<(), Error> {
+        secp.verify(&signing_message(&self.old_key, &self.new_key, true), &self.old_signature, &self.old_key)?;
+        secp.verify(&signing_message(&self.old_key, &self.new_key, false), &self.new_signature, &self.new_key)
+    }
+
+    /// Encodes the record: a version byte, the old and new keys, then the
+    /// old and new keys' compact signatures
+    pub fn serialize(&self) -> [u8; ROTATION_RECORD_SIZE] {
+        let mut out = [0u8; ROTATION_RECORD_SIZE];
+        out[0] = VERSION;
+        out[1..34].copy_from_slice(&self.old_key.serialize());
+        out[34..67].copy_from_slice(&self.new_key.serialize());
+        out[67..131].copy_from_slice(&self.old_signature.compact_bytes());
+        out[131..].copy_from_slice(&self.new_signature.compact_bytes());
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidRotationRecord`
+    /// for any other data; the signatures are only checked by `verify`.
+    pub fn from_slice(data: &[u8]) -> Result<RotationRecord, Error> {
+        if data.len() != ROTATION_RECORD_SIZE || data[0] != VERSION {
+            return Err(InvalidRotationRecord);
+        }
+        let mut sig = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        sig.copy_from_slice(&data[67..131]);
+        let old_signature = Signature::from_compact_bytes(&sig).map_err(|_| InvalidRotationRecord)?;
+        sig.copy_from_slice(&data[131..]);
+        let new_signature = Signature::from_compact_bytes(&sig).map_err(|_| InvalidRotationRecord)?;
+        Ok(RotationRecord {
+            old_key: PublicKey::from_slice(&data[1..34]).map_err(|_| InvalidRotationRecord)?,
+            new_key: PublicKey::from_slice(&data[34..67]).map_err(|_| InvalidRotationRecord)?,
+            old_signature,
+            new_signature,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for RotationRecord {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&self.serialize())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for RotationRecord {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<RotationRecord, D::Error> {
+        use ::serde::de::Error;
+
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        RotationRecord::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(any(test, feature = "rand"))]
+impl Vault {
+    /// Generates a successor to the key for `handle`, with the same usage
+    /// limit, and returns its handle and the rotation record. The old key
+    /// stays in the vault until it is removed. Each of the record's
+    /// signatures counts as a use of its key, so rotate a limited key
+    /// before it is exhausted. Fails like `Vault::sign`.
+    pub fn rotate<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle,
+                                                      rng: &mut R) -> Result<(KeyHandle, RotationRecord), Error> {
+        let old_key = self.public_key(handle)?;
+        let limit = self.limit(handle)?;
+        let new_handle = self.generate(secp, rng);
+        self.set_limit(new_handle, limit)?;
+        let new_key = self.public_key(new_handle)?;
+
+        let signatures = self.sign(secp, handle, &signing_message(&old_key, &new_key, true))
+            .and_then(|old_sig| {
+                self.sign(secp, new_handle, &signing_message(&old_key, &new_key, false)).map(|new_sig| (old_sig, new_sig))
+            });
+        match signatures {
+            Ok((old_signature, new_signature)) => {
+                Ok((new_handle, RotationRecord { old_key, new_key, old_signature, new_signature }))
+            }
+            Err(e) => {
+                // Don't leave behind a successor without a record
+                self.remove(new_handle)?;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{RotationRecord, ROTATION_RECORD_SIZE};
+    use super::super::{Message, Secp256k1};
+    use super::super::vault::Vault;
+    use super::super::Error::{IncorrectSignature, InvalidRotationRecord, KeyExhausted, UnknownKeyHandle};
+
+    #[test]
+    fn rotate() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let old = vault.generate(&s, &mut thread_rng());
+        vault.set_limit(old, Some(10)).unwrap();
+        vault.sign(&s, old, &Message::from_slice(&[1; 32]).unwrap()).unwrap();
+
+        let (new, record) = vault.rotate(&s, old, &mut thread_rng()).unwrap();
+        assert_eq!(record.old_key, vault.public_key(old).unwrap());
+        assert_eq!(record.new_key, vault.public_key(new).unwrap());
+        assert_eq!(record.verify(&s), Ok(()));
+        assert_eq!(vault.uses(old), Ok(2));
+        assert_eq!(vault.uses(new), Ok(1));
+        assert_eq!(vault.limit(new), Ok(Some(10)));
+
+        // Chains of rotations
+        vault.remove(old).unwrap();
+        let (_, next) = vault.rotate(&s, new, &mut thread_rng()).unwrap();
+        assert_eq!(next.old_key, record.new_key);
+        assert_eq!(next.verify(&s), Ok(()));
+        assert_eq!(vault.rotate(&s, old, &mut thread_rng()).unwrap_err(), UnknownKeyHandle);
+
+        // An exhausted key can't vouch for a successor, and none is left behind
+        let exhausted = vault.generate(&s, &mut thread_rng());
+        vault.set_limit(exhausted, Some(0)).unwrap();
+        let len = vault.len();
+        assert_eq!(vault.rotate(&s, exhausted, &mut thread_rng()).unwrap_err(), KeyExhausted);
+        assert_eq!(vault.len(), len);
+
+        // Neither signature can stand in for the other
+        let mut swapped = record;
+        swapped.old_signature = record.new_signature;
+        assert_eq!(swapped.verify(&s), Err(IncorrectSignature));
+        let mut reversed = record;
+        reversed.old_key = record.new_key;
+        reversed.new_key = record.old_key;
+        reversed.old_signature = record.new_signature;
+        reversed.new_signature = record.old_signature;
+        assert_eq!(reversed.verify(&s), Err(IncorrectSignature));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let old = vault.generate(&s, &mut thread_rng());
+        let (_, record) = vault.rotate(&s, old, &mut thread_rng()).unwrap();
+
+        let encoded = record.serialize();
+        assert_eq!(encoded.len(), ROTATION_RECORD_SIZE);
+        assert_eq!(RotationRecord::from_slice(&encoded), Ok(record));
+
+        assert_eq!(RotationRecord::from_slice(&encoded[1..]), Err(InvalidRotationRecord));
+        let mut bad_version = encoded;
+        bad_version[0] = 0;
+        assert_eq!(RotationRecord::from_slice(&bad_version), Err(InvalidRotationRecord));
+        let mut bad_signature = encoded;
+        for byte in bad_signature[67..99].iter_mut() {
+            *byte = 0xff;
+        }
+        assert_eq!(RotationRecord::from_slice(&bad_signature), Err(InvalidRotationRecord));
+        let mut tampered = encoded;
+        tampered[150] ^= 1;
+        assert_eq!(RotationRecord::from_slice(&tampered).unwrap().verify(&s), Err(IncorrectSignature));
+    }
+}
@@ -0,0 +1,419 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Scalars
+//! Integers modulo the curve order. Unlike `SecretKey`, a `Scalar` may be
+//! zero, which makes it the right type for tweaks and for protocol values
+//! such as MuSig coefficients or Lagrange interpolation weights. All
+//! arithmetic is done by libsecp256k1 in constant time.
+//!
+//! `lagrange_coefficient` and `interpolate_at_zero` are the interpolation
+//! every threshold scheme (Shamir sharing, FROST, DKG) needs, over the
+//! curve order. The x-coordinates of the shares are public and are checked
+//! without regard to timing; the shares themselves only go through the
+//! constant-time arithmetic.
+
+use std::fmt;
+use std::vec::Vec;
+
+#[cfg(not(feature = "verify-only"))]
+use cleanup;
+use ffi;
+use constants;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use super::Error::{self, InvalidInterpolationPoints, InvalidScalar};
+
+/// A scalar modulo the secp256k1 curve order, stored as 32 big-endian bytes
+pub struct Scalar([u8; constants::SECRET_KEY_SIZE]);
+impl_array_newtype!(Scalar, u8, constants::SECRET_KEY_SIZE);
+
+// Scalars are as often secret (tweaks, nonces, shares) as not, so they are
+// redacted like `SecretKey`; `to_be_bytes` gets at the value.
+impl fmt::Debug for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Scalar(<redacted>)")
+    }
+}
+
+impl Scalar {
+    /// The scalar zero
+    pub const ZERO: Scalar = Scalar([0; constants::SECRET_KEY_SIZE]);
+
+    /// The scalar one
+    pub const ONE: Scalar = Scalar([0, 0, 0, 0, 0, 0, 0, 0,
+                                    0, 0, 0, 0, 0, 0, 0, 0,
+                                    0, 0, 0, 0, 0, 0, 0, 0,
+                                    0, 0, 0, 0, 0, 0, 0, 1]);
+
+    /// Creates the scalar with the value `n`, for small protocol constants
+    /// such as Lagrange coefficients. Every `u64` is below the curve order.
+    #[inline]
+    pub fn from_u64(n: u64) -> Scalar {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        ret[constants::SECRET_KEY_SIZE - 8..].copy_from_slice(&n.to_be_bytes());
+        Scalar(ret)
+    }
+
+    /// Creates a scalar from 32 big-endian bytes, failing if the value is not
+    /// below the curve order
+    #[inline]
+    pub fn from_be_bytes(bytes: [u8; constants::SECRET_KEY_SIZE]) -> Result<Scalar, Error> {
+        unsafe {
+            if ffi::secp256k1_ec_scalar_verify(ffi::secp256k1_context_no_precomp, bytes.as_ptr()) == 1 {
+                Ok(Scalar(bytes))
+            } else {
+                Err(InvalidScalar)
+            }
+        }
+    }
+
+    /// Creates a scalar by reducing a 32-byte big-endian integer modulo the
+    /// curve order, e.g. a hash output, a message or an x-coordinate as ECDSA
+    /// and Schnorr challenges use them
+    #[inline]
+    pub fn from_be_bytes_mod_order(bytes: &[u8; constants::SECRET_KEY_SIZE]) -> Scalar {
+        let mut wide = [0; 64];
+        wide[64 - constants::SECRET_KEY_SIZE..].copy_from_slice(bytes);
+        Scalar::from_be_bytes_mod_order_wide(&wide)
+    }
+
+    /// Creates a scalar by reducing a 64-byte big-endian integer modulo the
+    /// curve order. Reducing a uniformly random 64-byte string (e.g. a hash
+    /// output) gives a scalar with negligible bias.
+    #[inline]
+    pub fn from_be_bytes_mod_order_wide(bytes: &[u8; 64]) -> Scalar {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        unsafe {
+            let err = ffi::secp256k1_ec_scalar_reduce512(ffi::secp256k1_context_no_precomp,
+                                                         ret.as_mut_ptr(), bytes.as_ptr());
+            // Every 512-bit integer has a remainder
+            assert!(err == 1);
+        }
+        Scalar(ret)
+    }
+
+    /// The scalar of a secret key, for the crate's own use whether or not
+    /// secret export is compiled out
+    #[inline]
+    #[cfg(not(feature = "verify-only"))]
+    pub(crate) fn from_secret_key(sk: &SecretKey) -> Scalar {
+        Scalar(*sk.secret_bytes())
+    }
+
+    /// Overwrites the scalar with zeroes, for scalars which are secret
+    /// (nonces, blinding factors, key material) once they are done with
+    #[inline]
+    #[cfg(not(feature = "verify-only"))]
+    pub(crate) fn wipe(&mut self) {
+        cleanup::wipe(&mut self.0);
+    }
+
+    /// Returns the scalar as 32 big-endian bytes
+    #[inline]
+    pub fn to_be_bytes(&self) -> [u8; constants::SECRET_KEY_SIZE] {
+        self.0
+    }
+
+    /// Whether the scalar is zero
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        *self == Scalar::ZERO
+    }
+
+    /// Adds two scalars modulo the curve order
+    #[inline]
+    pub fn add(&self, other: &Scalar) -> Scalar {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        unsafe {
+            // Cannot fail since both inputs were range checked on construction
+            let err = ffi::secp256k1_ec_scalar_add(ffi::secp256k1_context_no_precomp,
+                                                   ret.as_mut_ptr(), self.as_ptr(), other.as_ptr());
+            assert!(err == 1);
+        }
+        Scalar(ret)
+    }
+
+    /// Multiplies two scalars modulo the curve order
+    #[inline]
+    pub fn mul(&self, other: &Scalar) -> Scalar {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        unsafe {
+            // Cannot fail since both inputs were range checked on construction
+            let err = ffi::secp256k1_ec_scalar_mul(ffi::secp256k1_context_no_precomp,
+                                                   ret.as_mut_ptr(), self.as_ptr(), other.as_ptr());
+            assert!(err == 1);
+        }
+        Scalar(ret)
+    }
+
+    /// Negates the scalar modulo the curve order
+    #[inline]
+    pub fn negate(&self) -> Scalar {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        unsafe {
+            // Cannot fail since the input was range checked on construction
+            let err = ffi::secp256k1_ec_scalar_negate(ffi::secp256k1_context_no_precomp,
+                                                      ret.as_mut_ptr(), self.as_ptr());
+            assert!(err == 1);
+        }
+        Scalar(ret)
+    }
+
+    /// Computes the multiplicative inverse modulo the curve order, or `None`
+    /// if the scalar is zero
+    #[inline]
+    pub fn invert(&self) -> Option<Scalar> {
+        let mut ret = [0; constants::SECRET_KEY_SIZE];
+        unsafe {
+            if ffi::secp256k1_ec_scalar_inverse(ffi::secp256k1_context_no_precomp,
+                                                ret.as_mut_ptr(), self.as_ptr()) == 1 {
+                Some(Scalar(ret))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// Fails unless the x-coordinates are nonzero, distinct and not empty
+fn check_indices(indices: &[Scalar]) -> Result<(), Error> {
+    if indices.is_empty() || indices.iter().any(Scalar::is_zero) {
+        return Err(InvalidInterpolationPoints);
+    }
+    for (n, x) in indices.iter().enumerate() {
+        if indices[..n].contains(x) {
+            return Err(InvalidInterpolationPoints);
+        }
+    }
+    Ok(())
+}
+
+/// The Lagrange coefficient at zero of the share at x-coordinate `index`,
+/// among the shares at `indices`: the product of `x_j / (x_j - index)` over
+/// the other x-coordinates. Fails with `InvalidInterpolationPoints` if
+/// `indices` is empty, contains zero or a repeated value, or doesn't contain
+/// `index`.
+pub fn lagrange_coefficient(indices: &[Scalar], index: &Scalar) -> Result<Scalar, Error> {
+    check_indices(indices)?;
+    if !indices.contains(index) {
+        return Err(InvalidInterpolationPoints);
+    }
+    let minus_index = index.negate();
+    let mut num = Scalar::ONE;
+    let mut den = Scalar::ONE;
+    for x in indices.iter().filter(|&x| x != index) {
+        num = num.mul(x);
+        den = den.mul(&x.add(&minus_index));
+    }
+    // The x-coordinates are distinct, so the denominator is not zero
+    Ok(num.mul(&den.invert().ok_or(InvalidInterpolationPoints)?))
+}
+
+/// The value at zero of the polynomial of least degree through `points`,
+/// given as `(x, y)` pairs: the secret of a Shamir sharing, from shares at
+/// least as many as its threshold. Fails with `InvalidInterpolationPoints`
+/// if there are no points or an x-coordinate is zero or repeated.
+pub fn interpolate_at_zero(points: &[(Scalar, Scalar)]) -> Result<Scalar, Error> {
+    let indices: Vec<Scalar> = points.iter().map(|&(x, _)| x).collect();
+    check_indices(&indices)?;
+    let mut ret = Scalar::ZERO;
+    for &(x, ref y) in points {
+        ret = ret.add(&lagrange_coefficient(&indices, &x)?.mul(y));
+    }
+    Ok(ret)
+}
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl From<SecretKey> for Scalar {
+    /// With the "production-hardening" feature this panics in a debug
+    /// enclave, since the scalar's bytes are the key's
+    #[inline]
+    fn from(sk: SecretKey) -> Scalar {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::assert_secret_export();
+        Scalar::from_secret_key(&sk)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use super::{interpolate_at_zero, lagrange_coefficient, Scalar};
+    use super::super::Error::{InvalidInterpolationPoints, InvalidScalar};
+    use constants;
+    use key::SecretKey;
+
+    fn scalar(n: u8) -> Scalar {
+        let mut bytes = [0; 32];
+        bytes[31] = n;
+        Scalar::from_be_bytes(bytes).unwrap()
+    }
+
+    fn hex_scalar(hex: &str) -> Scalar {
+        let mut bytes = [0; 32];
+        assert_eq!(::hex::decode_from(hex, &mut bytes), Some(32));
+        Scalar::from_be_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn scalar_from_be_bytes() {
+        assert_eq!(Scalar::from_be_bytes([0; 32]), Ok(Scalar::ZERO));
+        assert_eq!(Scalar::from_be_bytes(constants::CURVE_ORDER), Err(InvalidScalar));
+        assert_eq!(Scalar::from_be_bytes([0xff; 32]), Err(InvalidScalar));
+
+        let mut max = constants::CURVE_ORDER;
+        max[31] -= 1;
+        let max = Scalar::from_be_bytes(max).unwrap();
+        assert_eq!(max, Scalar::ONE.negate());
+        assert_eq!(max.to_be_bytes()[..], Scalar::ONE.negate()[..]);
+    }
+
+    #[test]
+    fn scalar_from_u64() {
+        assert_eq!(Scalar::from_u64(0), Scalar::ZERO);
+        assert_eq!(Scalar::from_u64(1), Scalar::ONE);
+        assert_eq!(Scalar::from_u64(200), scalar(200));
+        assert_eq!(Scalar::from_u64(0x0102_0304_0506_0708).to_be_bytes()[24..], [1, 2, 3, 4, 5, 6, 7, 8][..]);
+        // A Lagrange coefficient at zero for the points {1, 2}: 2 / (2 - 1)
+        let two = Scalar::from_u64(2);
+        assert_eq!(two.mul(&two.add(&Scalar::ONE.negate()).invert().unwrap()), two);
+    }
+
+    #[test]
+    fn scalar_debug_is_redacted() {
+        assert_eq!(format!("{:?}", scalar(200)), "Scalar(<redacted>)");
+    }
+
+    #[test]
+    fn scalar_arithmetic() {
+        let minus_one = Scalar::ONE.negate();
+        assert!(minus_one.add(&Scalar::ONE).is_zero());
+        assert_eq!(scalar(2).add(&scalar(3)), scalar(5));
+        assert_eq!(scalar(2).mul(&scalar(3)), scalar(6));
+        assert_eq!(minus_one.mul(&minus_one), Scalar::ONE);
+        assert_eq!(Scalar::ZERO.negate(), Scalar::ZERO);
+
+        assert_eq!(Scalar::ZERO.invert(), None);
+        assert_eq!(Scalar::ONE.invert(), Some(Scalar::ONE));
+        let x = scalar(7);
+        assert_eq!(x.mul(&x.invert().unwrap()), Scalar::ONE);
+        assert_eq!(minus_one.invert(), Some(minus_one));
+    }
+
+    #[test]
+    fn scalar_wide_reduction() {
+        let mut wide = [0; 64];
+        wide[63] = 5;
+        assert_eq!(Scalar::from_be_bytes_mod_order_wide(&wide), scalar(5));
+
+        // n * 2^256 + n - 1 reduces to -1
+        wide[..32].copy_from_slice(&constants::CURVE_ORDER);
+        wide[32..].copy_from_slice(&constants::CURVE_ORDER);
+        wide[63] -= 1;
+        assert_eq!(Scalar::from_be_bytes_mod_order_wide(&wide), Scalar::ONE.negate());
+
+        // n reduces to zero
+        let mut wide = [0; 64];
+        wide[32..].copy_from_slice(&constants::CURVE_ORDER);
+        assert!(Scalar::from_be_bytes_mod_order_wide(&wide).is_zero());
+
+        // 2^256 mod n = 2^256 - n
+        let mut wide = [0; 64];
+        wide[31] = 1;
+        let two_256 = Scalar::from_be_bytes_mod_order_wide(&wide);
+        let mut expected = [0; 32];
+        expected[15] = 1;
+        expected[16..].copy_from_slice(&[0x45, 0x51, 0x23, 0x19, 0x50, 0xb7, 0x5f, 0xc4,
+                                         0x40, 0x2d, 0xa1, 0x73, 0x2f, 0xc9, 0xbe, 0xbf]);
+        assert_eq!(two_256.to_be_bytes(), expected);
+    }
+
+    #[test]
+    fn scalar_reduction() {
+        assert_eq!(Scalar::from_be_bytes_mod_order(&[0; 32]), Scalar::ZERO);
+        assert!(Scalar::from_be_bytes_mod_order(&constants::CURVE_ORDER).is_zero());
+        let mut order_plus_five = constants::CURVE_ORDER;
+        order_plus_five[31] += 5;
+        assert_eq!(Scalar::from_be_bytes_mod_order(&order_plus_five), scalar(5));
+        assert_eq!(Scalar::from_be_bytes_mod_order(&[0x11; 32]), Scalar::from_be_bytes([0x11; 32]).unwrap());
+    }
+
+    #[test]
+    fn lagrange() {
+        // {1, 2, 3}: 3, -3 and 1
+        let indices = [scalar(1), scalar(2), scalar(3)];
+        assert_eq!(lagrange_coefficient(&indices, &scalar(1)), Ok(scalar(3)));
+        assert_eq!(lagrange_coefficient(&indices, &scalar(2)), Ok(scalar(3).negate()));
+        assert_eq!(lagrange_coefficient(&indices, &scalar(3)), Ok(Scalar::ONE));
+
+        // Vectors computed independently with Python's arbitrary-precision integers
+        let indices = [scalar(3), scalar(5), scalar(9)];
+        for &(i, expected) in &[
+            (3, "3fffffffffffffffffffffffffffffffaeabb739abd2280eeff497a3340d9054"),
+            (5, "5fffffffffffffffffffffffffffffff860192d681bb3c1667eee374ce145875"),
+            (9, "5fffffffffffffffffffffffffffffff860192d681bb3c1667eee374ce145879"),
+        ] {
+            assert_eq!(lagrange_coefficient(&indices, &scalar(i)), Ok(hex_scalar(expected)));
+        }
+        let big = hex_scalar("1d3a0c3b1a14e8d1a4d4b1e0b7c4c8f2a3e9d6b5c4a3f2e1d0c9b8a7f6e5d4c3");
+        let indices = [big, scalar(7)];
+        assert_eq!(lagrange_coefficient(&indices, &big),
+                   Ok(hex_scalar("82c7a34fc6a0d80879bb0a58aaf9be0c993fb6aed912baccd2f6a21a1a94e64c")));
+        assert_eq!(lagrange_coefficient(&indices, &scalar(7)),
+                   Ok(hex_scalar("7d385cb0395f27f78644f5a7550641f2216f2637d635e56eecdbbc72b5a15af6")));
+
+        // The coefficients of any set sum to one
+        let indices = [scalar(4), scalar(200), big, Scalar::ONE.negate()];
+        let sum = indices.iter()
+            .fold(Scalar::ZERO, |acc, x| acc.add(&lagrange_coefficient(&indices, x).unwrap()));
+        assert_eq!(sum, Scalar::ONE);
+        assert_eq!(lagrange_coefficient(&[scalar(5)], &scalar(5)), Ok(Scalar::ONE));
+
+        assert_eq!(lagrange_coefficient(&[], &scalar(1)), Err(InvalidInterpolationPoints));
+        assert_eq!(lagrange_coefficient(&indices, &scalar(2)), Err(InvalidInterpolationPoints));
+        assert_eq!(lagrange_coefficient(&[scalar(1), scalar(4), scalar(1)], &scalar(4)),
+                   Err(InvalidInterpolationPoints));
+        assert_eq!(lagrange_coefficient(&[Scalar::ZERO, scalar(4)], &scalar(4)), Err(InvalidInterpolationPoints));
+    }
+
+    #[test]
+    fn interpolation() {
+        // f(x) = s + a1 x + a2 x^2 through x = 2, 7 and 11, computed with Python
+        let secret = hex_scalar("8a3f1c2e9b7d6a5f4e3d2c1b0a99887766554433221100ffeeddccbbaa998877");
+        let points = [
+            (scalar(2), hex_scalar("87f8915f8825ce814bf6a14bf741ec9e795345c95196e432ed4dc7b95668e791")),
+            (scalar(7), hex_scalar("5a75b932054da1291e73c91e7469bf7f7b13990efefa8c5a07d050cb3a07419f")),
+            (scalar(11), hex_scalar("0d1749b173aaedacd115599de2c70c5eac8708ad20eac80fc74749b019223393")),
+        ];
+        assert_eq!(interpolate_at_zero(&points), Ok(secret));
+        let reordered = [points[2], points[0], points[1]];
+        assert_eq!(interpolate_at_zero(&reordered), Ok(secret));
+        // Two points of a degree-two polynomial give another line
+        assert!(interpolate_at_zero(&points[..2]).unwrap() != secret);
+        // A constant through a single point
+        assert_eq!(interpolate_at_zero(&points[..1]), Ok(points[0].1));
+
+        assert_eq!(interpolate_at_zero(&[]), Err(InvalidInterpolationPoints));
+        assert_eq!(interpolate_at_zero(&[points[0], points[0]]), Err(InvalidInterpolationPoints));
+        assert_eq!(interpolate_at_zero(&[(Scalar::ZERO, secret)]), Err(InvalidInterpolationPoints));
+    }
+
+    #[test]
+    fn scalar_from_secret_key() {
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        assert_eq!(Scalar::from_secret_key(&sk)[..], sk.secret_bytes()[..]);
+        #[cfg(not(feature = "no-secret-export"))]
+        assert_eq!(Scalar::from(sk), Scalar::from_secret_key(&sk));
+    }
+}
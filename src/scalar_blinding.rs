@@ -0,0 +1,164 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Per-signature scalar blinding
+//! `Secp256k1::sign_blinded`, an ECDSA signer which randomizes the secret
+//! scalar arithmetic afresh for every signature. Context randomization
+//! already blinds the nonce point multiplication; what it leaves is
+//! `s = k^-1 (z + r d)`, where libsecp256k1 works on the key `d` and the
+//! nonce `k` as they are. A malicious OS which can single-step the enclave
+//! sees every run of that code with the same key, and a single trace of it
+//! with the same values every time.
+//!
+//! Here, with fresh random nonzero scalars `c` and `a` for each signature,
+//! the key only enters as `d c` split into `(d c - a) + a`, and the nonce
+//! is only inverted as `k c`:
+//!
+//! ```text
+//! s = (k c)^-1 (z c + r (d c - a) + r a) = k^-1 (z + r d)
+//! ```
+//!
+//! The nonce is the same RFC6979 nonce `Secp256k1::sign` uses, so the
+//! signatures are identical to its; only the path to them differs.
+//! Temporaries are wiped. Requires compilation with the "rand" feature.
+
+use std::ptr;
+
+use rand_core::{CryptoRng, RngCore};
+
+use cleanup;
+use constants;
+use ffi;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use {Message, Secp256k1, Signature, Signing};
+
+/// Draws a uniformly random nonzero scalar
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; constants::SECRET_KEY_SIZE];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        match Scalar::from_be_bytes(bytes) {
+            Ok(s) if !s.is_zero() => {
+                cleanup::wipe(&mut bytes);
+                return s;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<C: Signing> Secp256k1<C> {
+    /// Constructs a signature for `msg` using the secret key `sk` and RFC6979
+    /// nonce, like `sign`, but with the key and nonce arithmetic blinded by
+    /// fresh scalars from `rng`; see the module documentation. This costs a
+    /// few scalar multiplications and an inversion on top of `sign`.
+    /// Requires a signing-capable context. Requires compilation with the
+    /// "rand" feature.
+    pub fn sign_blinded<R: RngCore + CryptoRng>(&self, msg: &Message, sk: &SecretKey, rng: &mut R) -> Signature {
+        debug_log!(trace, "signing with a blinded key");
+        measure!(Sign, |_| true, {
+            let z = Scalar::from_be_bytes_mod_order(&msg.0);
+            let mut d = Scalar::from_secret_key(sk);
+            let mut nonce = [0u8; constants::SECRET_KEY_SIZE];
+            let mut attempt = 0;
+            let sig = loop {
+                unsafe {
+                    // RFC6979 always produces output when given no extra data
+                    assert_eq!(ffi::secp256k1_nonce_function_rfc6979(nonce.as_mut_ptr(), msg.as_ptr(),
+                                                                     sk.as_ptr(), ptr::null(),
+                                                                     ptr::null_mut(), attempt), 1);
+                }
+                attempt += 1;
+                // As in libsecp256k1, out-of-range nonces and signatures with
+                // a zero r or s move on to the next nonce
+                if let Ok(mut k) = SecretKey::from_slice(&nonce) {
+                    let sig = self.sign_blinded_with_nonce(&z, &d, &k, rng);
+                    k.wipe();
+                    if let Some(sig) = sig {
+                        break sig;
+                    }
+                }
+            };
+            cleanup::wipe(&mut nonce);
+            d.wipe();
+            sig
+        })
+    }
+
+    /// Computes the signature for the message scalar `z`, key `d` and nonce
+    /// `k`, or `None` if r or s comes out zero
+    fn sign_blinded_with_nonce<R: RngCore + CryptoRng>(&self, z: &Scalar, d: &Scalar, k: &SecretKey,
+                                                       rng: &mut R) -> Option<Signature> {
+        let mut c = random_scalar(rng);
+        let mut a = random_scalar(rng);
+
+        let r = Scalar::from_be_bytes_mod_order(&PublicKey::from_secret_key(self, k).x_coordinate());
+        if r.is_zero() {
+            return None;
+        }
+        let mut dc = d.mul(&c);
+        let mut dc_split = dc.add(&a.negate());
+        dc.wipe();
+        let mut t = z.mul(&c).add(&r.mul(&dc_split)).add(&r.mul(&a));
+        let mut kc = Scalar::from_secret_key(k).mul(&c);
+        let mut kc_inv = kc.invert().expect("k and c are nonzero");
+        let s = kc_inv.mul(&t);
+        for temp in [&mut c, &mut a, &mut dc_split, &mut t, &mut kc, &mut kc_inv].iter_mut() {
+            temp.wipe();
+        }
+        if s.is_zero() {
+            return None;
+        }
+
+        let mut compact = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        compact[..32].copy_from_slice(&r[..]);
+        compact[32..].copy_from_slice(&s[..]);
+        let mut sig = Signature::from_compact_bytes(&compact).expect("r and s are in range");
+        sig.normalize_s(self);
+        Some(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::RngCore;
+
+    use key::SecretKey;
+    use rng::thread_rng;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn matches_sign() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+        for _ in 0..32 {
+            let (sk, pk) = s.generate_keypair(&mut rng);
+            let mut msg = [0u8; 32];
+            rng.fill_bytes(&mut msg);
+            let msg = Message::from(msg);
+
+            let sig = s.sign_blinded(&msg, &sk, &mut rng);
+            assert_eq!(sig, s.sign(&msg, &sk));
+            assert!(s.verify(&msg, &sig, &pk).is_ok());
+            // Different blinding, same signature
+            assert_eq!(s.sign_blinded(&msg, &sk, &mut rng), sig);
+        }
+
+        // Messages at or above the curve order are reduced like in `sign`
+        let sk = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let msg = Message::from([0xff; 32]);
+        assert_eq!(s.sign_blinded(&msg, &sk, &mut rng), s.sign(&msg, &sk));
+    }
+}
@@ -0,0 +1,128 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Signing schemes
+//! ECDSA signs a 32-byte digest, and protocols disagree on how the data
+//! becomes one: Bitcoin-style protocols hash with SHA-256, Ethereum with
+//! Keccak-256, newer protocols with tagged hashes, and some hand over the
+//! digest itself. Mixing these up produces signatures which verify
+//! nowhere. `Secp256k1::sign_with_scheme` and `verify_with_scheme` take the
+//! raw data and a `SignScheme` and do the hashing themselves, so every call
+//! site states its convention. Requires compilation with the "hashes"
+//! feature.
+
+use hashes;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use super::Error::{self, InvalidMessage};
+use {constants, Message, Secp256k1, Signature, Verification};
+#[cfg(not(feature = "verify-only"))]
+use Signing;
+
+/// How the data to sign becomes the message ECDSA signs
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SignScheme<'a> {
+    /// The data already is the 32-byte digest
+    RawDigest,
+    /// The SHA-256 digest of the data
+    Sha256,
+    /// The BIP340 tagged hash of the data with this tag
+    TaggedSha256(&'a [u8]),
+    /// The Keccak-256 digest of the data, as Ethereum hashes (which is not
+    /// SHA3-256)
+    Keccak256,
+}
+
+impl<'a> SignScheme<'a> {
+    /// The message to sign for `data`. Fails with `InvalidMessage` for
+    /// `RawDigest` unless `data` is 32 bytes.
+    pub fn message(&self, data: &[u8]) -> Result<Message, Error> {
+        let digest = match *self {
+            SignScheme::RawDigest => {
+                if data.len() != constants::MESSAGE_SIZE {
+                    return Err(InvalidMessage);
+                }
+                let mut digest = [0; constants::MESSAGE_SIZE];
+                digest.copy_from_slice(data);
+                digest
+            }
+            SignScheme::Sha256 => hashes::sha256(data),
+            SignScheme::TaggedSha256(tag) => hashes::tagged_hash(tag, data),
+            SignScheme::Keccak256 => hashes::keccak256(data),
+        };
+        Ok(Message::from(digest))
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl<C: Signing> Secp256k1<C> {
+    /// Signs `data`, hashed as `scheme` says. Fails with `InvalidMessage`
+    /// if `scheme` is `RawDigest` and `data` is not 32 bytes. Requires a
+    /// signing-capable context.
+    pub fn sign_with_scheme(&self, scheme: SignScheme, data: &[u8], sk: &SecretKey) -> Result<Signature, Error> {
+        Ok(self.sign(&scheme.message(data)?, sk))
+    }
+}
+
+impl<C: Verification> Secp256k1<C> {
+    /// Checks that `sig` is a valid signature of `data`, hashed as `scheme`
+    /// says, by `pk`. Fails with `InvalidMessage` if `scheme` is `RawDigest`
+    /// and `data` is not 32 bytes. Requires a verify-capable context.
+    pub fn verify_with_scheme(&self, scheme: SignScheme, data: &[u8], sig: &Signature,
+                              pk: &PublicKey) -> Result<(), Error> {
+        self.verify(&scheme.message(data)?, sig, pk)
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use super::SignScheme;
+    use super::super::Error::{IncorrectSignature, InvalidMessage};
+    use hashes::{keccak256, sha256, tagged_hash, BIP340_CHALLENGE_TAG};
+    use rng::thread_rng;
+    use {Message, Secp256k1};
+
+    #[test]
+    fn messages() {
+        let data = b"transfer 10 to alice";
+        assert_eq!(SignScheme::Sha256.message(data), Ok(Message::from(sha256(data))));
+        assert_eq!(SignScheme::Keccak256.message(data), Ok(Message::from(keccak256(data))));
+        assert_eq!(SignScheme::TaggedSha256(BIP340_CHALLENGE_TAG).message(data),
+                   Ok(Message::from(tagged_hash(BIP340_CHALLENGE_TAG, data))));
+        assert_eq!(SignScheme::RawDigest.message(&[7; 32]), Ok(Message::from([7; 32])));
+        assert_eq!(SignScheme::RawDigest.message(data), Err(InvalidMessage));
+        assert_eq!(SignScheme::RawDigest.message(&[7; 33]), Err(InvalidMessage));
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let data = [0x42; 32];
+        let schemes = [SignScheme::RawDigest, SignScheme::Sha256, SignScheme::TaggedSha256(b"test/tag"),
+                       SignScheme::TaggedSha256(b"test/other"), SignScheme::Keccak256];
+
+        for (i, &scheme) in schemes.iter().enumerate() {
+            let sig = s.sign_with_scheme(scheme, &data, &sk).unwrap();
+            assert_eq!(sig, s.sign(&scheme.message(&data).unwrap(), &sk));
+            // A signature only verifies under the scheme it was made with
+            for (j, &other) in schemes.iter().enumerate() {
+                let expected = if i == j { Ok(()) } else { Err(IncorrectSignature) };
+                assert_eq!(s.verify_with_scheme(other, &data, &sig, &pk), expected);
+            }
+        }
+        assert_eq!(s.sign_with_scheme(SignScheme::RawDigest, &data[1..], &sk), Err(InvalidMessage));
+    }
+}
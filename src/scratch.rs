@@ -0,0 +1,161 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Scratch spaces
+//! Working memory for the algorithms which operate on many points at once,
+//! such as `PublicKey::msm_with_scratch`. Inside an enclave every byte comes
+//! out of the EPC, so the size is always chosen by the caller: a scratch
+//! space never grows past its limit, and an operation whose data doesn't fit
+//! works in batches instead (or fails with `NotEnoughMemory` if not even one
+//! item fits).
+//!
+//! `ScratchSpace::new` allocates on demand, up to the limit, from the C heap.
+//! `ScratchSpace::from_buffer` instead works entirely within a caller-owned
+//! buffer, for enclaves whose heap can't spare large blocks.
+
+use std::marker::PhantomData;
+
+use types::c_void;
+
+use ffi;
+use super::Error::{self, NotEnoughMemory};
+use Secp256k1;
+
+/// Scratch memory for multi-point operations, freed on drop. It borrows the
+/// context it was created with and, for `from_buffer`, the buffer.
+pub struct ScratchSpace<'a> {
+    ptr: *mut ffi::ScratchSpace,
+    phantom: PhantomData<&'a mut [u8]>,
+}
+
+// The scratch space only points to the (`Sync`) context and to memory it
+// owns or exclusively borrows
+unsafe impl<'a> Send for ScratchSpace<'a> {}
+// Its frames are only pushed and popped through `as_mut_ptr`, which takes
+// `&mut self`; `max_size` merely reads
+unsafe impl<'a> Sync for ScratchSpace<'a> {}
+
+impl<'a> ScratchSpace<'a> {
+    /// Creates a scratch space which allocates at most `max_size` bytes
+    pub fn new<C>(secp: &'a Secp256k1<C>, max_size: usize) -> Result<ScratchSpace<'a>, Error> {
+        let ptr = unsafe { ffi::secp256k1_scratch_space_create(secp.ctx, max_size) };
+        if ptr.is_null() {
+            return Err(NotEnoughMemory);
+        }
+        Ok(ScratchSpace { ptr, phantom: PhantomData })
+    }
+
+    /// Creates a scratch space within `buf`, which then holds all of its
+    /// memory. Under 200 bytes go to alignment and bookkeeping, so `max_size`
+    /// is a little less than the buffer's length. Fails with `NotEnoughMemory` if
+    /// the buffer is too small even for the bookkeeping.
+    pub fn from_buffer<C>(secp: &'a Secp256k1<C>, buf: &'a mut [u8]) -> Result<ScratchSpace<'a>, Error> {
+        let ptr = unsafe {
+            ffi::secp256k1_scratch_space_create_preallocated(secp.ctx, buf.as_mut_ptr() as *mut c_void,
+                                                             buf.len())
+        };
+        if ptr.is_null() {
+            return Err(NotEnoughMemory);
+        }
+        Ok(ScratchSpace { ptr, phantom: PhantomData })
+    }
+
+    /// The scratch space size with which `PublicKey::msm_with_scratch`
+    /// processes `n` terms in a single batch. Anything down to the size for
+    /// a single term works too, just more slowly.
+    pub fn size_for_msm(n: usize) -> usize {
+        unsafe { ffi::secp256k1_ec_pubkey_msm_scratch_size(n) }
+    }
+
+    /// The most memory this scratch space will use for its frames
+    #[inline]
+    pub fn max_size(&self) -> usize {
+        unsafe { ffi::secp256k1_scratch_space_max_size(self.ptr) }
+    }
+
+    /// Obtains a raw pointer suitable for use with FFI functions
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut ffi::ScratchSpace {
+        self.ptr
+    }
+}
+
+impl<'a> Drop for ScratchSpace<'a> {
+    fn drop(&mut self) {
+        unsafe { ffi::secp256k1_scratch_space_destroy(self.ptr) };
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use rng::thread_rng;
+    use super::ScratchSpace;
+    use super::super::{Error, PublicKey, Scalar, Secp256k1};
+
+    fn random_terms(s: &Secp256k1<::All>, n: usize) -> Vec<(Scalar, PublicKey)> {
+        (0..n).map(|_| {
+            let (sk, pk) = s.generate_keypair(&mut thread_rng());
+            (Scalar::from_secret_key(&sk), pk)
+        }).collect()
+    }
+
+    #[test]
+    fn sizes() {
+        let s = Secp256k1::new();
+        assert_eq!(ScratchSpace::size_for_msm(0), 0);
+        // Around the switch from Strauss' algorithm to Pippenger's, which
+        // needs less memory per point
+        for &n in [1, 2, 10, 87, 88, 100, 1000].iter() {
+            let size = ScratchSpace::size_for_msm(n);
+
+            let terms = random_terms(&s, n);
+            let expected = PublicKey::msm(&s, &terms, 1 << 24).unwrap();
+            let mut scratch = ScratchSpace::new(&s, size).unwrap();
+            assert_eq!(scratch.max_size(), size);
+            assert_eq!(PublicKey::msm_with_scratch(&s, &mut scratch, &terms), Ok(expected));
+        }
+
+        // Anything smaller than the size for one term is useless
+        let terms = random_terms(&s, 1);
+        let mut scratch = ScratchSpace::new(&s, ScratchSpace::size_for_msm(1) - 1).unwrap();
+        assert_eq!(PublicKey::msm_with_scratch(&s, &mut scratch, &terms), Err(Error::NotEnoughMemory));
+    }
+
+    #[test]
+    fn preallocated() {
+        let s = Secp256k1::new();
+        let terms = random_terms(&s, 100);
+        let expected = PublicKey::msm(&s, &terms, 1 << 20).unwrap();
+
+        let mut buf = vec![0u8; ScratchSpace::size_for_msm(100) + 200];
+        {
+            let mut scratch = ScratchSpace::from_buffer(&s, &mut buf).unwrap();
+            assert!(scratch.max_size() >= ScratchSpace::size_for_msm(100));
+            // The scratch space can be reused
+            for _ in 0..3 {
+                assert_eq!(PublicKey::msm_with_scratch(&s, &mut scratch, &terms), Ok(expected));
+            }
+        }
+        assert!(buf.iter().any(|&b| b != 0));
+
+        // Misaligned, and too small for a single batch
+        let mut small = [0u8; 8193];
+        let mut scratch = ScratchSpace::from_buffer(&s, &mut small[1..]).unwrap();
+        assert!(scratch.max_size() < 8192);
+        assert_eq!(PublicKey::msm_with_scratch(&s, &mut scratch, &terms), Ok(expected));
+
+        let mut tiny = [0u8; 64];
+        assert!(ScratchSpace::from_buffer(&s, &mut tiny).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Sealing-key derivation
+//! Secret keys derived from the enclave's sealing key, so that the same
+//! enclave on the same platform gets the same key back without ever storing
+//! it. The 128-bit sealing key comes from EGETKEY; it is expanded with
+//! HKDF-SHA256 (salt `DERIVATION_SALT`, info = the caller's label) into 64
+//! bytes, which are reduced modulo the curve order.
+//!
+//! The key request uses the enclave's current ISV SVN and CPU SVN, so an
+//! enclave update which bumps the SVN, or a microcode update, changes the
+//! derived keys. `derive_for_svn` re-derives the key for an older SVN, as
+//! recorded in a `SealingKeyDerivation`.
+
+use std::fmt;
+use std::vec::Vec;
+
+use sgx_tse::{rsgx_get_key, rsgx_self_report};
+use sgx_types::{sgx_key_request_t, SGX_KEYPOLICY_MRENCLAVE, SGX_KEYPOLICY_MRSIGNER, SGX_KEYSELECT_SEAL};
+
+use cleanup::wipe;
+use global::SECP256K1;
+use hashes::{hkdf_sha256, sha256};
+use hex;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error::{self, EnclaveService};
+
+/// The HKDF salt. It names and versions the derivation.
+pub const DERIVATION_SALT: &'static [u8] = b"rust-secp256k1-sgx/sealing-key-derivation/v1";
+
+/// The HKDF salt for `derive_bytes`
+pub const BYTES_DERIVATION_SALT: &'static [u8] = b"rust-secp256k1-sgx/sealing-bytes-derivation/v1";
+
+// The masks the SDK's own sealing uses: the key depends on every attribute
+// flag except MODE64BIT and a reserved bit, and on none of XFRM
+const FLAGS_MASK: u64 = 0xffff_ffff_ffff_fff3;
+const XFRM_MASK: u64 = 0;
+const MISC_MASK: u32 = 0xf000_0000;
+
+/// Which enclaves can derive the same key
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum KeyPolicy {
+    /// Only this exact enclave build (same MRENCLAVE)
+    MrEnclave,
+    /// Any enclave from the same signer (same MRSIGNER) with the same product
+    /// ID, at this ISV SVN or a later one
+    MrSigner,
+}
+
+impl KeyPolicy {
+    fn bits(self) -> u16 {
+        match self {
+            KeyPolicy::MrEnclave => SGX_KEYPOLICY_MRENCLAVE,
+            KeyPolicy::MrSigner => SGX_KEYPOLICY_MRSIGNER,
+        }
+    }
+}
+
+impl fmt::Display for KeyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyPolicy::MrEnclave => "MRENCLAVE",
+            KeyPolicy::MrSigner => "MRSIGNER",
+        })
+    }
+}
+
+/// Everything that goes into a derived key except the sealing key itself:
+/// the key request and the label, along with the resulting public key. The
+/// host can check the enclave's identity and policy against an attestation
+/// report and then rely on the public key, without learning anything secret.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SealingKeyDerivation {
+    /// Key policy of the request
+    pub key_policy: KeyPolicy,
+    /// Measurement of the deriving enclave
+    pub mr_enclave: [u8; 32],
+    /// Hash of the deriving enclave's signing key
+    pub mr_signer: [u8; 32],
+    /// Product ID of the deriving enclave
+    pub isv_prod_id: u16,
+    /// ISV SVN of the request
+    pub isv_svn: u16,
+    /// Config SVN of the request
+    pub config_svn: u16,
+    /// CPU SVN of the request
+    pub cpu_svn: [u8; 16],
+    /// Attribute flags the key depends on
+    pub flags_mask: u64,
+    /// XFRM bits the key depends on
+    pub xfrm_mask: u64,
+    /// MISCSELECT bits the key depends on
+    pub misc_mask: u32,
+    /// Key ID of the request, fixed for this derivation
+    pub key_id: [u8; 32],
+    /// The caller's label, used as HKDF info
+    pub label: Vec<u8>,
+    /// Public key of the derived secret key
+    pub public_key: PublicKey,
+}
+
+impl SealingKeyDerivation {
+    /// Derives the key for `key_policy` and `label` at the current SVNs and
+    /// describes how it was derived
+    pub fn describe(key_policy: KeyPolicy, label: &[u8]) -> Result<SealingKeyDerivation, Error> {
+        let report = rsgx_self_report();
+        derive_for_svn(key_policy, label, report.body.isv_svn, report.body.cpu_svn.svn).map(|(_, d)| d)
+    }
+}
+
+impl fmt::Display for SealingKeyDerivation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "key_policy: {}", self.key_policy)?;
+        f.write_str("mr_enclave: ")?;
+        hex::format(&self.mr_enclave, f)?;
+        f.write_str("\nmr_signer: ")?;
+        hex::format(&self.mr_signer, f)?;
+        writeln!(f, "\nisv_prod_id: {}", self.isv_prod_id)?;
+        writeln!(f, "isv_svn: {}", self.isv_svn)?;
+        writeln!(f, "config_svn: {}", self.config_svn)?;
+        f.write_str("cpu_svn: ")?;
+        hex::format(&self.cpu_svn, f)?;
+        writeln!(f, "\nflags_mask: {:016x}", self.flags_mask)?;
+        writeln!(f, "xfrm_mask: {:016x}", self.xfrm_mask)?;
+        writeln!(f, "misc_mask: {:08x}", self.misc_mask)?;
+        f.write_str("key_id: ")?;
+        hex::format(&self.key_id, f)?;
+        f.write_str("\nlabel: ")?;
+        hex::format(&self.label, f)?;
+        write!(f, "\npublic_key: {}", self.public_key)
+    }
+}
+
+/// The key ID of every request, so that these keys never coincide with
+/// sealing keys the SDK derives for sealed data (which use random key IDs)
+fn key_id() -> [u8; 32] {
+    sha256(DERIVATION_SALT)
+}
+
+/// Requests the 128-bit sealing key for `key_policy` at the given SVNs
+fn sealing_key(key_policy: KeyPolicy, isv_svn: u16, cpu_svn: [u8; 16], config_svn: u16)
+               -> Result<[u8; 16], Error> {
+    let mut request = sgx_key_request_t {
+        key_name: SGX_KEYSELECT_SEAL,
+        key_policy: key_policy.bits(),
+        isv_svn,
+        config_svn,
+        misc_mask: MISC_MASK,
+        ..Default::default()
+    };
+    request.cpu_svn.svn = cpu_svn;
+    request.attribute_mask.flags = FLAGS_MASK;
+    request.attribute_mask.xfrm = XFRM_MASK;
+    request.key_id.id = key_id();
+    rsgx_get_key(&request).map_err(|_| EnclaveService)
+}
+
+/// Derives the key for `key_policy` and `label` at the given SVNs, which
+/// must not be higher than the enclave's and the platform's current ones.
+/// Fails with `EnclaveService` if EGETKEY refuses the request.
+pub fn derive_for_svn(key_policy: KeyPolicy, label: &[u8], isv_svn: u16, cpu_svn: [u8; 16])
+                      -> Result<(SecretKey, SealingKeyDerivation), Error> {
+    let report = rsgx_self_report();
+    let mut sealing_key = sealing_key(key_policy, isv_svn, cpu_svn, report.body.config_svn)?;
+    let sk = derive_from_key_material(&sealing_key, label);
+    wipe(&mut sealing_key);
+    let sk = sk?;
+
+    let derivation = SealingKeyDerivation {
+        key_policy,
+        mr_enclave: report.body.mr_enclave.m,
+        mr_signer: report.body.mr_signer.m,
+        isv_prod_id: report.body.isv_prod_id,
+        isv_svn,
+        config_svn: report.body.config_svn,
+        cpu_svn,
+        flags_mask: FLAGS_MASK,
+        xfrm_mask: XFRM_MASK,
+        misc_mask: MISC_MASK,
+        key_id: key_id(),
+        label: label.to_vec(),
+        public_key: PublicKey::from_secret_key(SECP256K1, &sk),
+    };
+    Ok((sk, derivation))
+}
+
+/// Fills `okm` with key material for other uses than secp256k1 keys, such
+/// as symmetric keys, derived from the sealing key for `key_policy` at the
+/// given SVNs. It uses the salt `BYTES_DERIVATION_SALT`, so it never
+/// reproduces the material behind a `derive_for_svn` key. Fails with
+/// `EnclaveService` if EGETKEY refuses the request.
+pub fn derive_bytes_for_svn(key_policy: KeyPolicy, label: &[u8], isv_svn: u16, cpu_svn: [u8; 16],
+                            okm: &mut [u8]) -> Result<(), Error> {
+    let report = rsgx_self_report();
+    let mut sealing_key = sealing_key(key_policy, isv_svn, cpu_svn, report.body.config_svn)?;
+    hkdf_sha256(BYTES_DERIVATION_SALT, &sealing_key, label, okm);
+    wipe(&mut sealing_key);
+    Ok(())
+}
+
+/// `derive_bytes_for_svn` at the current SVNs
+pub fn derive_bytes(key_policy: KeyPolicy, label: &[u8], okm: &mut [u8]) -> Result<(), Error> {
+    let report = rsgx_self_report();
+    derive_bytes_for_svn(key_policy, label, report.body.isv_svn, report.body.cpu_svn.svn, okm)
+}
+
+/// The HKDF step, separate from EGETKEY so it can be tested anywhere
+fn derive_from_key_material(ikm: &[u8], label: &[u8]) -> Result<SecretKey, Error> {
+    let mut okm = [0u8; 64];
+    hkdf_sha256(DERIVATION_SALT, ikm, label, &mut okm);
+    let mut bytes = Scalar::from_be_bytes_mod_order_wide(&okm).to_be_bytes();
+    wipe(&mut okm);
+    // Zero with probability 2^-256
+    let sk = SecretKey::from_slice(&bytes);
+    wipe(&mut bytes);
+    sk
+}
+
+impl SecretKey {
+    /// Derives a secret key from the enclave's sealing key under
+    /// `key_policy`. The same enclave (or, with `MrSigner`, the same signer
+    /// and product) on the same platform and SVNs gets the same key for the
+    /// same `label`; different labels give independent keys. Fails with
+    /// `EnclaveService` if EGETKEY fails.
+    pub fn derive_from_sealing_key(key_policy: KeyPolicy, label: &[u8]) -> Result<SecretKey, Error> {
+        let report = rsgx_self_report();
+        derive_for_svn(key_policy, label, report.body.isv_svn, report.body.cpu_svn.svn).map(|(sk, _)| sk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::derive_from_key_material;
+    use super::super::SecretKey;
+
+    #[test]
+    fn key_material() {
+        let ikm = [0x0b; 16];
+        let sk = derive_from_key_material(&ikm, b"wallet/0").unwrap();
+        assert_eq!(derive_from_key_material(&ikm, b"wallet/0"), Ok(sk));
+        assert!(derive_from_key_material(&ikm, b"wallet/1").unwrap() != sk);
+        assert!(derive_from_key_material(&[0x0c; 16], b"wallet/0").unwrap() != sk);
+        // Pins the derivation, which must never change for existing keys
+        assert_eq!(sk, SecretKey::from_slice(&KEY_MATERIAL_VECTOR).unwrap());
+    }
+
+    // HKDF-SHA256 with `DERIVATION_SALT`, 16 bytes of 0x0b and "wallet/0",
+    // reduced modulo the order
+    const KEY_MATERIAL_VECTOR: [u8; 32] = [
+        0x74, 0x97, 0xa0, 0x03, 0xdc, 0x45, 0xc6, 0xc6, 0x9d, 0x6e, 0x20, 0xd6, 0x4f, 0xf7, 0x6e, 0x8d,
+        0xc6, 0xd3, 0x9d, 0xff, 0xa6, 0x5e, 0xce, 0xc9, 0x54, 0x3b, 0x7d, 0x0b, 0xa3, 0x2a, 0x89, 0x37,
+    ];
+}
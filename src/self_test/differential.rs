@@ -0,0 +1,195 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Differential testing
+//! Cross-checks the linked libsecp256k1 against the pure-Rust `k256` crate
+//! on random inputs: public key derivation, RFC6979 signing (both produce
+//! the same low-S signature), verification in both directions, additive
+//! and multiplicative tweaks of secret and public keys, and the x
+//! coordinate of the ECDH shared point. Since the inputs are random rather
+//! than secret, nothing is wiped; pass a seeded generator to make a
+//! reported mismatch reproducible.
+
+use std::fmt;
+
+use k256;
+use k256::ecdsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+use k256::elliptic_curve::point::AffineCoordinates;
+use rand_core::RngCore;
+
+use ecdh::SharedSecret;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use {Message, Secp256k1, Signature, Signing, Verification};
+
+/// An operation checked by `run_differential`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Operation {
+    /// Public key derivation from a secret key
+    KeyDerivation,
+    /// Deterministic ECDSA signing
+    Sign,
+    /// ECDSA verification of the other implementation's signatures, and
+    /// rejection of them on another message
+    Verify,
+    /// Additive tweaking of secret and public keys
+    TweakAdd,
+    /// Multiplicative tweaking of secret and public keys
+    TweakMul,
+    /// ECDH shared point computation
+    Ecdh,
+}
+
+/// An operation on which the two implementations disagreed
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Mismatch {
+    /// Zero-based round in which the disagreement showed up
+    pub round: usize,
+    /// The operation whose results differed
+    pub operation: Operation,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "differential round {}: k256 disagrees on {:?}", self.round, self.operation)
+    }
+}
+
+/// Draws a random valid secret key
+fn secret_key<R: RngCore>(rng: &mut R) -> SecretKey {
+    let mut bytes = [0u8; 32];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if let Ok(sk) = SecretKey::from_slice(&bytes) {
+            return sk;
+        }
+    }
+}
+
+/// Draws a random scalar below the curve order
+fn scalar<R: RngCore>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 32];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        if let Ok(s) = Scalar::from_be_bytes(bytes) {
+            return s;
+        }
+    }
+}
+
+/// A `k256` scalar as a secret key, or `None` if it is zero
+fn k256_secret_key(s: k256::Scalar) -> Option<SecretKey> {
+    k256::SecretKey::from_bytes(&s.to_bytes()).ok().map(SecretKey::from)
+}
+
+/// A `k256` point as a public key, or `None` if it is the point at infinity
+fn k256_public_key(p: k256::ProjectivePoint) -> Option<PublicKey> {
+    k256::PublicKey::from_affine(p.to_affine()).ok().map(PublicKey::from)
+}
+
+/// Runs one round of the comparison, returning the first operation on
+/// which the implementations disagree
+fn round<C: Signing + Verification, R: RngCore>(secp: &Secp256k1<C>, rng: &mut R) -> Result<(), Operation> {
+    let sk = secret_key(rng);
+    let other_sk = secret_key(rng);
+    let tweak = scalar(rng);
+    let mut prehash = [0u8; 32];
+    rng.fill_bytes(&mut prehash);
+    let mut other_prehash = prehash;
+    other_prehash[0] ^= 1;
+    let (msg, other_msg) = (Message::from(prehash), Message::from(other_prehash));
+
+    let k_sk = k256::SecretKey::from_slice(&sk.secret_bytes()[..]).expect("secret keys are always in range");
+    let k_scalar = k256::Scalar::from(Scalar::from_secret_key(&sk));
+    let k_tweak = k256::Scalar::from(tweak);
+
+    let pk = PublicKey::from_secret_key(secp, &sk);
+    let k_pk = k_sk.public_key();
+    if PublicKey::from(k_pk) != pk {
+        return Err(Operation::KeyDerivation);
+    }
+
+    let sig = secp.sign(&msg, &sk);
+    let k_sig: k256::ecdsa::Signature = match k256::ecdsa::SigningKey::from(k_sk).sign_prehash(&prehash) {
+        Ok(sig) => sig,
+        Err(_) => return Err(Operation::Sign),
+    };
+    if Signature::from(k_sig) != sig {
+        return Err(Operation::Sign);
+    }
+
+    let verifying_key = k256::ecdsa::VerifyingKey::from(k_pk);
+    if secp.verify(&msg, &Signature::from(k_sig), &pk).is_err() ||
+       secp.verify(&other_msg, &Signature::from(k_sig), &pk).is_ok() ||
+       verifying_key.verify_prehash(&prehash, &k_sig).is_err() ||
+       verifying_key.verify_prehash(&other_prehash, &k_sig).is_ok() {
+        return Err(Operation::Verify);
+    }
+
+    let (mut sum_sk, mut sum_pk) = (sk, pk);
+    let sum_sk = sum_sk.add_assign(secp, &tweak).ok().map(|_| sum_sk);
+    let sum_pk = sum_pk.add_exp_assign(secp, &tweak).ok().map(|_| sum_pk);
+    if sum_sk != k256_secret_key(k_scalar + k_tweak) ||
+       sum_pk != k256_public_key(k_pk.to_projective() + k256::ProjectivePoint::GENERATOR * k_tweak) {
+        return Err(Operation::TweakAdd);
+    }
+
+    let (mut product_sk, mut product_pk) = (sk, pk);
+    let product_sk = product_sk.mul_assign(secp, &tweak).ok().map(|_| product_sk);
+    let product_pk = product_pk.mul_assign(secp, &tweak).ok().map(|_| product_pk);
+    if product_sk != k256_secret_key(k_scalar * k_tweak) ||
+       product_pk != k256_public_key(k_pk.to_projective() * k_tweak) {
+        return Err(Operation::TweakMul);
+    }
+
+    let other_pk = PublicKey::from_secret_key(secp, &other_sk);
+    let shared = SharedSecret::new_with_hash(secp, &other_pk, &sk, |x, _| *x);
+    let k_shared = (k256::PublicKey::from(other_pk).to_projective() * k_scalar).to_affine().x();
+    if shared[..] != k_shared[..] {
+        return Err(Operation::Ecdh);
+    }
+    Ok(())
+}
+
+/// Compares `rounds` rounds of random operations between this context and
+/// `k256`, returning the number of rounds run or the first disagreement.
+/// Works inside the enclave as well as outside it, so that the SGX build of
+/// libsecp256k1 can be checked on the target itself.
+pub fn run_differential<C: Signing + Verification, R: RngCore>(secp: &Secp256k1<C>, rng: &mut R, rounds: usize)
+                                                                -> Result<usize, Mismatch> {
+    for i in 0..rounds {
+        round(secp, rng).map_err(|operation| Mismatch { round: i, operation })?;
+    }
+    Ok(rounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_differential, Mismatch, Operation};
+    use rng::thread_rng;
+    use Secp256k1;
+
+    #[test]
+    fn differential() {
+        let s = Secp256k1::new();
+        assert_eq!(run_differential(&s, &mut thread_rng(), 64), Ok(64));
+        assert_eq!(run_differential(&s, &mut thread_rng(), 0), Ok(0));
+    }
+
+    #[test]
+    fn mismatch_display() {
+        let mismatch = Mismatch { round: 3, operation: Operation::TweakMul };
+        assert_eq!(mismatch.to_string(), "differential round 3: k256 disagrees on TweakMul");
+    }
+}
@@ -0,0 +1,300 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Self tests
+//! Known-answer tests which can be run inside the enclave at startup, to
+//! check that the linked libsecp256k1 behaves as expected on the target
+//! before any key material is touched.
+//!
+//! `Secp256k1::self_test` is a quick power-on test of key derivation,
+//! signing, verification, ECDH and tweaking against the vectors of
+//! `test_vectors`. `Secp256k1::verify_self_test` tests verification alone,
+//! for verification-only contexts and the "verify-only" build.
+//! `Secp256k1::integrity_check` reruns the tests which go through the
+//! precomputed tables on a context already in service, to catch tables
+//! corrupted since it was built. With the
+//! "wycheproof" feature, `run_wycheproof` additionally checks the full
+//! Project Wycheproof ECDSA corpus. With the
+//! "differential" feature, `run_differential` cross-checks the main
+//! operations against the pure-Rust `k256` crate on random inputs.
+
+#[cfg(feature = "wycheproof")]
+mod wycheproof;
+#[cfg(feature = "wycheproof")]
+pub use self::wycheproof::{run_wycheproof, Failure};
+#[cfg(feature = "differential")]
+mod differential;
+#[cfg(feature = "differential")]
+pub use self::differential::{run_differential, Mismatch, Operation};
+
+#[cfg(not(feature = "verify-only"))]
+use std::ptr;
+
+#[cfg(not(feature = "verify-only"))]
+use ecdh::SharedSecret;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use scalar::Scalar;
+use test_vectors::{MESSAGE, PUBLIC_KEY, SIGNATURE};
+#[cfg(not(feature = "verify-only"))]
+use test_vectors::{OTHER_SECRET_KEY, PRODUCT_PUBLIC_KEY, PRODUCT_SECRET_KEY, PUBLIC_KEY_UNCOMPRESSED, SECRET_KEY,
+                   SHARED_SECRET, SUM_PUBLIC_KEY, SUM_SECRET_KEY, TWEAK};
+use ffi;
+use {Message, Secp256k1, Signature, Verification};
+use super::Error::{self, ContextCorrupted};
+#[cfg(not(feature = "verify-only"))]
+use Signing;
+
+/// The outcome of each known-answer test run by `Secp256k1::self_test`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Report {
+    /// Public key derivation from a secret key
+    pub key_derivation: bool,
+    /// Deterministic ECDSA signing
+    pub sign: bool,
+    /// ECDSA verification, including rejection of a tampered signature and
+    /// of the wrong message
+    pub verify: bool,
+    /// ECDH shared secret computation
+    pub ecdh: bool,
+    /// Additive tweaking of secret and public keys
+    pub tweak_add: bool,
+    /// Multiplicative tweaking of secret and public keys
+    pub tweak_mul: bool,
+}
+
+impl Report {
+    /// Whether every test passed
+    pub fn passed(&self) -> bool {
+        self.key_derivation && self.sign && self.verify && self.ecdh && self.tweak_add && self.tweak_mul
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn secret_key(bytes: &[u8; 32]) -> SecretKey {
+    SecretKey::from_slice(bytes).expect("valid self-test key")
+}
+
+#[cfg(not(feature = "verify-only"))]
+fn scalar(bytes: [u8; 32]) -> Scalar {
+    Scalar::from_be_bytes(bytes).expect("valid self-test scalar")
+}
+
+impl<C: Verification> Secp256k1<C> {
+    /// Runs the verification known-answer test of `self_test` alone: the
+    /// known signature verifies, and neither a tampered signature nor
+    /// another message does
+    pub fn verify_self_test(&self) -> bool {
+        let msg = Message::from_slice(&MESSAGE).expect("32-byte message");
+        let sig = Signature::from_compact(self, &SIGNATURE).expect("valid self-test signature");
+        let mut tampered = SIGNATURE;
+        tampered[63] ^= 1;
+        let tampered = Signature::from_compact(self, &tampered).expect("valid self-test signature");
+        let mut other_msg = MESSAGE;
+        other_msg[0] ^= 1;
+        let other_msg = Message::from_slice(&other_msg).expect("32-byte message");
+        let pk = PublicKey::from_slice(&PUBLIC_KEY).expect("valid self-test key");
+
+        self.verify(&msg, &sig, &pk).is_ok() &&
+            self.verify(&msg, &tampered, &pk).is_err() &&
+            self.verify(&other_msg, &sig, &pk).is_err()
+    }
+}
+
+impl<C> Secp256k1<C> {
+    /// Checks the precomputed tables of the context against known answers:
+    /// key derivation and signing for a signing context, and verification
+    /// of a known signature, and rejection of a tampered one, for a
+    /// verification context. Fails with `ContextCorrupted` if any answer is
+    /// wrong, after which the context must not sign anything.
+    ///
+    /// Each check only reads the table entries its scalars select, so it
+    /// detects corruption of a whole table or of the entries it happens to
+    /// use, not of every single entry. Re-randomizing a signing context
+    /// moves the entries key derivation and signing go through. It is cheap
+    /// enough, about the cost of four signatures and three verifications, to
+    /// run periodically or before signing with a long-lived key.
+    pub fn integrity_check(&self) -> Result<(), Error> {
+        let caps = unsafe { ffi::secp256k1_context_capabilities(self.ctx) };
+        #[cfg(not(feature = "verify-only"))]
+        {
+            if caps & ffi::SECP256K1_START_SIGN == ffi::SECP256K1_START_SIGN && !self.signing_tables_intact() {
+                return Err(ContextCorrupted);
+            }
+        }
+        if caps & ffi::SECP256K1_START_VERIFY == ffi::SECP256K1_START_VERIFY && !self.verification_tables_intact() {
+            return Err(ContextCorrupted);
+        }
+        Ok(())
+    }
+
+    /// Derives the public keys of three known secret keys and makes the
+    /// known signature
+    #[cfg(not(feature = "verify-only"))]
+    fn signing_tables_intact(&self) -> bool {
+        let keys = [(&SECRET_KEY, &PUBLIC_KEY), (&SUM_SECRET_KEY, &SUM_PUBLIC_KEY),
+                    (&PRODUCT_SECRET_KEY, &PRODUCT_PUBLIC_KEY)];
+        let derived = keys.iter().all(|&(sk, expected)| unsafe {
+            let mut pk = ffi::PublicKey::blank();
+            ffi::secp256k1_ec_pubkey_create(self.ctx, &mut pk, sk.as_ptr()) == 1 &&
+                PublicKey::from(pk).serialize()[..] == expected[..]
+        });
+        let signed = unsafe {
+            let mut sig = ffi::Signature::blank();
+            ffi::secp256k1_ecdsa_sign(self.ctx, &mut sig, MESSAGE.as_ptr(), SECRET_KEY.as_ptr(),
+                                      ffi::secp256k1_nonce_function_rfc6979, ptr::null()) == 1 &&
+                Signature::from(sig).compact_bytes()[..] == SIGNATURE[..]
+        };
+        ::cleanup::scrub_stack();
+        derived && signed
+    }
+
+    /// Verifies the known signature, and neither a tampered one nor one on
+    /// another message, as `verify_self_test` does
+    fn verification_tables_intact(&self) -> bool {
+        let sig = Signature::from_compact_bytes(&SIGNATURE).expect("valid self-test signature");
+        let mut tampered = SIGNATURE;
+        tampered[63] ^= 1;
+        let tampered = Signature::from_compact_bytes(&tampered).expect("valid self-test signature");
+        let mut other_msg = MESSAGE;
+        other_msg[0] ^= 1;
+        let pk = PublicKey::from_slice(&PUBLIC_KEY).expect("valid self-test key");
+        let verify = |sig: &Signature, msg: &[u8; 32]| unsafe {
+            ffi::secp256k1_ecdsa_verify(self.ctx, sig.as_ptr(), msg.as_ptr(), pk.as_ptr()) == 1
+        };
+        verify(&sig, &MESSAGE) && !verify(&tampered, &MESSAGE) && !verify(&sig, &other_msg)
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl<C: Signing + Verification> Secp256k1<C> {
+    /// Runs known-answer tests of the main operations against this context
+    /// and reports which of them passed. Meant to be called once at enclave
+    /// initialization, refusing service unless `Report::passed` holds.
+    pub fn self_test(&self) -> Report {
+        let sk = secret_key(&SECRET_KEY);
+        let pk = PublicKey::from_secret_key(self, &sk);
+        let msg = Message::from_slice(&MESSAGE).expect("32-byte message");
+
+        let sig = self.sign(&msg, &sk);
+        let expected_pk = PublicKey::from_slice(&PUBLIC_KEY).expect("valid self-test key");
+
+        let other_sk = secret_key(&OTHER_SECRET_KEY);
+        let other_pk = PublicKey::from_secret_key(self, &other_sk);
+        let ecdh = SharedSecret::new(self, &other_pk, &sk)[..] == SHARED_SECRET[..] &&
+                   SharedSecret::new(self, &pk, &other_sk)[..] == SHARED_SECRET[..];
+
+        let tweak = scalar(TWEAK);
+        let (mut sum_sk, mut sum_pk) = (sk, expected_pk);
+        let tweak_add = sum_sk.add_assign(self, &tweak).is_ok() &&
+                        sum_pk.add_exp_assign(self, &tweak).is_ok() &&
+                        sum_sk.secret_bytes()[..] == SUM_SECRET_KEY[..] &&
+                        sum_pk.serialize()[..] == SUM_PUBLIC_KEY[..];
+        let (mut product_sk, mut product_pk) = (sk, expected_pk);
+        let tweak_mul = product_sk.mul_assign(self, &tweak).is_ok() &&
+                        product_pk.mul_assign(self, &tweak).is_ok() &&
+                        product_sk.secret_bytes()[..] == PRODUCT_SECRET_KEY[..] &&
+                        product_pk.serialize()[..] == PRODUCT_PUBLIC_KEY[..];
+
+        Report {
+            key_derivation: pk.serialize()[..] == PUBLIC_KEY[..] &&
+                            pk.serialize_uncompressed()[..] == PUBLIC_KEY_UNCOMPRESSED[..],
+            sign: sig.serialize_compact(self)[..] == SIGNATURE[..],
+            verify: self.verify_self_test(),
+            ecdh,
+            tweak_add,
+            tweak_mul,
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::boxed::Box;
+    use std::vec;
+
+    use super::Report;
+    use rng::thread_rng;
+    use test_vectors::{SECRET_KEY, MESSAGE, SIGNATURE};
+    use key::SecretKey;
+    use {Message, Secp256k1};
+    use super::super::Error::ContextCorrupted;
+
+    #[test]
+    fn self_test_passes() {
+        let s = Secp256k1::new();
+        let report = s.self_test();
+        assert_eq!(report, Report {
+            key_derivation: true,
+            sign: true,
+            verify: true,
+            ecdh: true,
+            tweak_add: true,
+            tweak_mul: true,
+        });
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn self_test_vectors_are_consistent() {
+        // Guards against a typo in the vectors making `verify` pass
+        // vacuously: the known signature must be exactly what we produce
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&SECRET_KEY).unwrap();
+        let sig = s.sign(&Message::from_slice(&MESSAGE).unwrap(), &sk);
+        assert_eq!(sig.serialize_compact(&s)[..], SIGNATURE[..]);
+
+        let mut report = s.self_test();
+        report.ecdh = false;
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn integrity_check() {
+        let mut s = Secp256k1::new();
+        assert_eq!(s.integrity_check(), Ok(()));
+        s.randomize(&mut thread_rng());
+        assert_eq!(s.integrity_check(), Ok(()));
+        assert_eq!(Secp256k1::signing_only().integrity_check(), Ok(()));
+        assert_eq!(Secp256k1::verification_only().integrity_check(), Ok(()));
+        assert_eq!(Secp256k1::without_caps().integrity_check(), Ok(()));
+
+        // Flip a bit in every 64-byte table entry past the first eighth of
+        // a context built in a buffer
+        fn corrupted<C>(size: usize, build: &Fn(&'static mut [u8]) -> Secp256k1<C>) -> Secp256k1<C> {
+            let buf = Box::leak(vec![0u8; size].into_boxed_slice());
+            let ptr = buf.as_mut_ptr();
+            let secp = build(buf);
+            assert_eq!(secp.integrity_check(), Ok(()));
+            for i in (size / 8..size).step_by(64) {
+                unsafe { *ptr.add(i) ^= 0x10; }
+            }
+            secp
+        }
+        let size = Secp256k1::builder().signing().preallocated_size();
+        let secp = corrupted(size, &|buf| Secp256k1::builder().signing().preallocated(buf).build().unwrap());
+        assert_eq!(secp.integrity_check(), Err(ContextCorrupted));
+        let size = Secp256k1::builder().verification().preallocated_size();
+        let secp = corrupted(size, &|buf| Secp256k1::builder().verification().preallocated(buf).build().unwrap());
+        assert_eq!(secp.integrity_check(), Err(ContextCorrupted));
+    }
+
+    #[test]
+    fn verify_self_test_passes() {
+        assert!(Secp256k1::verification_only().verify_self_test());
+        assert!(Secp256k1::new().verify_self_test());
+    }
+}
@@ -0,0 +1,185 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Wycheproof
+//! The Project Wycheproof `ecdsa_secp256k1_sha256` corpus: 379 signature
+//! verifications, most of them negative tests such as malformed DER,
+//! out-of-range scalars and edge-case points. The vectors are embedded in
+//! the `blobby` encoding used by the RustCrypto test suites (taken from the
+//! `k256` crate). Wycheproof treats high-S signatures as valid, so
+//! signatures are normalized before verification. The Wycheproof ECDH
+//! vectors are not included, since `ecdh::SharedSecret` hashes the shared
+//! point rather than returning its x coordinate.
+
+use std::fmt;
+
+use key::PublicKey;
+use {Message, Secp256k1, Signature, Verification};
+
+static ECDSA_SECP256K1_SHA256: &'static [u8] =
+    include_bytes!("wycheproof_ecdsa_secp256k1_sha256.blb");
+
+/// A Wycheproof vector whose outcome did not match its expected result
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Failure {
+    /// Zero-based position of the vector in the corpus
+    pub index: usize,
+    /// Whether the vector was expected to verify
+    pub expected_valid: bool,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "wycheproof vector {}: expected {} signature", self.index,
+               if self.expected_valid { "a valid" } else { "an invalid" })
+    }
+}
+
+/// Reads a git-style variable-length quantity
+fn read_vlq(data: &[u8], pos: &mut usize) -> usize {
+    let mut val = 0;
+    let mut first = true;
+    loop {
+        let b = data[*pos];
+        *pos += 1;
+        val = if first { 0 } else { (val + 1) << 7 } + (b & 0x7f) as usize;
+        first = false;
+        if b & 0x80 == 0 {
+            return val;
+        }
+    }
+}
+
+/// Splits a `blobby` file into its blobs. The embedded data is trusted, so
+/// malformed input panics.
+fn blobs(data: &'static [u8]) -> Blobs {
+    let mut pos = 0;
+    let mut dedup = [&[][..]; 64];
+    let n_dedup = read_vlq(data, &mut pos);
+    assert!(n_dedup <= dedup.len());
+    for entry in dedup.iter_mut().take(n_dedup) {
+        let len = read_vlq(data, &mut pos);
+        *entry = &data[pos..pos + len];
+        pos += len;
+    }
+    Blobs { data, pos, dedup }
+}
+
+struct Blobs {
+    data: &'static [u8],
+    pos: usize,
+    dedup: [&'static [u8]; 64],
+}
+
+impl Iterator for Blobs {
+    type Item = &'static [u8];
+
+    fn next(&mut self) -> Option<&'static [u8]> {
+        if self.pos == self.data.len() {
+            return None;
+        }
+        let val = read_vlq(self.data, &mut self.pos);
+        if val & 1 == 1 {
+            Some(self.dedup[val >> 1])
+        } else {
+            let start = self.pos;
+            self.pos += val >> 1;
+            Some(&self.data[start..self.pos])
+        }
+    }
+}
+
+/// Left-pads or strips leading zeros from a Wycheproof coordinate
+fn coordinate(data: &[u8]) -> Option<[u8; 32]> {
+    let mut ret = [0; 32];
+    if data.len() > 32 {
+        let (excess, rest) = data.split_at(data.len() - 32);
+        if excess.iter().any(|&b| b != 0) {
+            return None;
+        }
+        ret.copy_from_slice(rest);
+    } else {
+        ret[32 - data.len()..].copy_from_slice(data);
+    }
+    Some(ret)
+}
+
+/// Checks a single signature the way a Wycheproof-conformant verifier would
+fn verify<C: Verification>(secp: &Secp256k1<C>, wx: &[u8], wy: &[u8], msg: &[u8], sig: &[u8]) -> bool {
+    let pk = match (coordinate(wx), coordinate(wy)) {
+        (Some(x), Some(y)) => match PublicKey::from_coordinates(&x, &y) {
+            Ok(pk) => pk,
+            Err(_) => return false,
+        },
+        _ => return false,
+    };
+    let mut sig = match Signature::from_der(secp, sig) {
+        Ok(sig) => sig,
+        Err(_) => return false,
+    };
+    sig.normalize_s(secp);
+    secp.verify(&Message::from_digest(::hashes::sha256(msg)), &sig, &pk).is_ok()
+}
+
+/// Runs the Wycheproof ECDSA secp256k1/SHA-256 vectors, returning the
+/// number of vectors checked or the first one which failed
+pub fn run_wycheproof<C: Verification>(secp: &Secp256k1<C>) -> Result<usize, Failure> {
+    let mut blobs = blobs(ECDSA_SECP256K1_SHA256);
+    let mut index = 0;
+    while let Some(wx) = blobs.next() {
+        let mut field = || blobs.next().expect("truncated wycheproof vector");
+        let (wy, msg, sig, result) = (field(), field(), field(), field());
+        let expected_valid = result == &[1][..];
+        if verify(secp, wx, wy, msg, sig) != expected_valid {
+            return Err(Failure { index, expected_valid });
+        }
+        index += 1;
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blobs, coordinate, run_wycheproof, ECDSA_SECP256K1_SHA256};
+    use Secp256k1;
+
+    #[test]
+    fn wycheproof_ecdsa() {
+        assert_eq!(run_wycheproof(&Secp256k1::verification_only()), Ok(379));
+        assert_eq!(run_wycheproof(&Secp256k1::new()), Ok(379));
+    }
+
+    #[test]
+    fn wycheproof_corpus() {
+        let all: Vec<_> = blobs(ECDSA_SECP256K1_SHA256).collect();
+        assert_eq!(all.len(), 5 * 379);
+        let valid = all.chunks(5).filter(|row| row[4] == &[1][..]).count();
+        assert_eq!(valid, 142);
+        // The first vector is a valid high-S signature of "123400"
+        assert_eq!(all[2], b"123400");
+        assert_eq!(all[4], &[1][..]);
+    }
+
+    #[test]
+    fn coordinates() {
+        let mut expected = [0; 32];
+        expected[31] = 7;
+        assert_eq!(coordinate(&[7]), Some(expected));
+        let mut long = [0; 33];
+        long[32] = 7;
+        assert_eq!(coordinate(&long), Some(expected));
+        long[0] = 1;
+        assert_eq!(coordinate(&long), None);
+    }
+}
@@ -0,0 +1,339 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Fixed-length serde encodings
+//! The serde encodings of this crate's types, and helpers for fixed-length
+//! ones which binary formats write without a length prefix.
+//!
+//! ## Stability
+//! The encodings below are part of the API: data written by one release
+//! reads back in every later one, so they can go into sealed state. A
+//! different encoding will only ever come as a new helper, never as a
+//! change to what an existing impl or helper writes.
+//!
+//! The `Serialize` impls of the types themselves write byte strings, which
+//! binary formats prefix with the length: 8 bytes little-endian in bincode
+//! 1, a varint in postcard. Human-readable formats get the same bytes, or
+//! the hex string where noted.
+//!
+//! | Type                   | Bytes                           | Human-readable |
+//! |------------------------|---------------------------------|----------------|
+//! | `SecretKey`            | 32                              | bytes          |
+//! | `PublicKey`            | 33, compressed                  | bytes          |
+//! | `XOnlyPublicKey`       | 32                              | hex            |
+//! | `Message`              | 32                              | hex            |
+//! | `Signature`            | DER, at most 72                 | hex DER        |
+//! | `RecoverableSignature` | 65, `r \|\| s \|\| v`           | hex            |
+//! | `KeyId`                | 20                              | hex            |
+//!
+//! The modules here are for `#[serde(with = "...")]` on fields, and write a
+//! fixed number of bytes as a tuple, which bincode and postcard encode as
+//! the bytes alone: 32 for a secret key, 33 for a compressed public key, 32
+//! for an x-only key or a message and 64 for a compact `r || s` signature.
+//! Human-readable formats get the same bytes as a hex string. Reading
+//! checks the value as `from_slice` does.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Sealed {
+//!     #[serde(with = "secp256k1::serde_fixed::public_key")]
+//!     owner: PublicKey,
+//!     #[serde(with = "secp256k1::serde_fixed::signature")]
+//!     approval: Signature,
+//! }
+//! ```
+//!
+//! Requires compilation with the "serde" feature.
+
+use std::fmt;
+
+use serde::{Deserializer, Serializer};
+use serde::de::{self, SeqAccess, Unexpected, Visitor};
+use serde::ser::SerializeTuple;
+
+use hex;
+
+/// The longest fixed encoding, a compact signature
+const MAX_SIZE: usize = 64;
+
+/// Writes `data` as hex for human-readable formats and as a tuple of bytes
+/// otherwise
+fn serialize_fixed<S: Serializer>(data: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    if s.is_human_readable() {
+        let mut buf = [0; 2 * MAX_SIZE];
+        s.serialize_str(hex::encode_to(data, &mut buf))
+    } else {
+        let mut tuple = s.serialize_tuple(data.len())?;
+        for byte in data {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+/// Reads what `serialize_fixed` wrote into `out`, which must be as long
+fn deserialize_fixed<'de, D: Deserializer<'de>>(d: D, out: &mut [u8], expecting: &'static str)
+                                                -> Result<(), D::Error> {
+    let len = out.len();
+    let visitor = FixedVisitor { out, expecting };
+    if d.is_human_readable() {
+        d.deserialize_str(visitor)
+    } else {
+        d.deserialize_tuple(len, visitor)
+    }
+}
+
+struct FixedVisitor<'a> {
+    out: &'a mut [u8],
+    expecting: &'static str,
+}
+
+impl<'a, 'de> Visitor<'de> for FixedVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+        match hex::decode_from(v, self.out) {
+            Some(len) if len == self.out.len() => Ok(()),
+            _ => Err(E::invalid_value(Unexpected::Str(v), &self)),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        for i in 0..self.out.len() {
+            match seq.next_element()? {
+                Some(byte) => self.out[i] = byte,
+                None => return Err(de::Error::invalid_length(i, &self)),
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! fixed_module {
+    ($(#[$attr:meta])* $module:ident, $thing:ident, $len:expr, $expecting:expr, $to_bytes:expr, $from_bytes:expr) => {
+        $(#[$attr])*
+        pub mod $module {
+            use serde::{Deserializer, Serializer};
+            use serde::de::Error;
+
+            use super::{deserialize_fixed, serialize_fixed};
+            #[allow(unused_imports)]
+            use key::{PublicKey, XOnlyPublicKey};
+            #[allow(unused_imports)]
+            use {Message, Signature};
+
+            #[doc = concat!("Writes the ", stringify!($len), " bytes of `value`")]
+            pub fn serialize<S: Serializer>(value: &$thing, s: S) -> Result<S::Ok, S::Error> {
+                serialize_fixed(&$to_bytes(value), s)
+            }
+
+            #[doc = concat!("Reads ", stringify!($len), " bytes and checks them as `from_slice` does")]
+            pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<$thing, D::Error> {
+                let mut buf = [0; $len];
+                deserialize_fixed(d, &mut buf, $expecting)?;
+                $from_bytes(&buf).map_err(D::Error::custom)
+            }
+        }
+    };
+}
+
+fixed_module!(
+    /// A `PublicKey` as its 33-byte compressed serialization
+    public_key, PublicKey, 33, "a 33-byte compressed public key",
+    |pk: &PublicKey| pk.serialize(), |data: &[u8; 33]| PublicKey::from_slice(data)
+);
+
+fixed_module!(
+    /// An `XOnlyPublicKey` as its 32 bytes
+    x_only_public_key, XOnlyPublicKey, 32, "a 32-byte x-only public key",
+    |pk: &XOnlyPublicKey| pk.serialize(), |data: &[u8; 32]| XOnlyPublicKey::from_slice(data)
+);
+
+fixed_module!(
+    /// A `Message` as its 32 bytes
+    message, Message, 32, "a 32-byte message",
+    |msg: &Message| msg.0, |data: &[u8; 32]| Message::from_slice(data)
+);
+
+fixed_module!(
+    /// A `Signature` as its 64-byte compact `r || s`
+    signature, Signature, 64, "a 64-byte compact signature",
+    |sig: &Signature| sig.compact_bytes(), Signature::from_compact_bytes
+);
+
+/// A `SecretKey` as its 32 bytes
+#[cfg(not(feature = "verify-only"))]
+pub mod secret_key {
+    use serde::Deserializer;
+    #[cfg(not(feature = "no-secret-export"))]
+    use serde::Serializer;
+    use serde::de::Error;
+
+    use super::deserialize_fixed;
+    #[cfg(not(feature = "no-secret-export"))]
+    use super::serialize_fixed;
+    use cleanup;
+    use constants;
+    use key::SecretKey;
+
+    /// Writes the 32 bytes of `value`
+    #[cfg(not(feature = "no-secret-export"))]
+    pub fn serialize<S: Serializer>(value: &SecretKey, s: S) -> Result<S::Ok, S::Error> {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::check_secret_export().map_err(::serde::ser::Error::custom)?;
+        serialize_fixed(&value[..], s)
+    }
+
+    /// Reads 32 bytes and checks them as `from_slice` does
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SecretKey, D::Error> {
+        let mut buf = [0; constants::SECRET_KEY_SIZE];
+        let res = deserialize_fixed(d, &mut buf, "a 32-byte secret key")
+            .and_then(|_| SecretKey::from_slice(&buf).map_err(D::Error::custom));
+        cleanup::wipe(&mut buf);
+        res
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use bincode;
+    use postcard;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_test::{Configure, Token, assert_tokens};
+
+    use key::{PublicKey, SecretKey, XOnlyPublicKey};
+    use {Message, Secp256k1, Signature};
+
+    /// A value encoded with the helpers in `$module`, as a field with
+    /// `#[serde(with = "...")]` would be
+    macro_rules! fixed {
+        ($name:ident, $thing:ty, $module:ident) => {
+            #[derive(Debug, PartialEq)]
+            struct $name($thing);
+
+            impl Serialize for $name {
+                fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                    super::$module::serialize(&self.0, s)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(d: D) -> Result<$name, D::Error> {
+                    super::$module::deserialize(d).map($name)
+                }
+            }
+        };
+    }
+
+    fixed!(FixedSecretKey, SecretKey, secret_key);
+    fixed!(FixedPublicKey, PublicKey, public_key);
+    fixed!(FixedXOnly, XOnlyPublicKey, x_only_public_key);
+    fixed!(FixedMessage, Message, message);
+    fixed!(FixedSignature, Signature, signature);
+
+    /// The key with secret 1, its x-only key, a message and its signature
+    fn values() -> (SecretKey, PublicKey, XOnlyPublicKey, Message, Signature) {
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_u64(1).unwrap();
+        let pk = PublicKey::from_secret_key(&s, &sk);
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        (sk, pk, pk.x_only_public_key().0, msg, s.sign(&msg, &sk))
+    }
+
+    fn prefixed_bincode(data: &[u8]) -> Vec<u8> {
+        let mut ret = (data.len() as u64).to_le_bytes().to_vec();
+        ret.extend_from_slice(data);
+        ret
+    }
+
+    fn prefixed_postcard(data: &[u8]) -> Vec<u8> {
+        assert!(data.len() < 0x80);
+        let mut ret = vec![data.len() as u8];
+        ret.extend_from_slice(data);
+        ret
+    }
+
+    #[test]
+    fn default_encodings() {
+        let (sk, pk, xonly, msg, sig) = values();
+        let compressed = pk.serialize();
+        let der = sig.serialize_der(&Secp256k1::new());
+
+        assert_eq!(bincode::serialize(&sk).unwrap(), prefixed_bincode(&sk[..]));
+        assert_eq!(bincode::serialize(&pk).unwrap(), prefixed_bincode(&compressed));
+        assert_eq!(bincode::serialize(&xonly).unwrap(), prefixed_bincode(&xonly.serialize()));
+        assert_eq!(bincode::serialize(&msg).unwrap(), prefixed_bincode(&msg[..]));
+        assert_eq!(bincode::serialize(&sig).unwrap(), prefixed_bincode(&der));
+        assert_eq!(postcard::to_allocvec(&sk).unwrap(), prefixed_postcard(&sk[..]));
+        assert_eq!(postcard::to_allocvec(&pk).unwrap(), prefixed_postcard(&compressed));
+        assert_eq!(postcard::to_allocvec(&xonly).unwrap(), prefixed_postcard(&xonly.serialize()));
+        assert_eq!(postcard::to_allocvec(&msg).unwrap(), prefixed_postcard(&msg[..]));
+        assert_eq!(postcard::to_allocvec(&sig).unwrap(), prefixed_postcard(&der));
+
+        let data = bincode::serialize(&(sk, pk, xonly, msg, sig)).unwrap();
+        assert_eq!(bincode::deserialize::<(SecretKey, PublicKey, XOnlyPublicKey, Message, Signature)>(&data).unwrap(),
+                   (sk, pk, xonly, msg, sig));
+        let data = postcard::to_allocvec(&(sk, pk, xonly, msg, sig)).unwrap();
+        assert_eq!(postcard::from_bytes::<(SecretKey, PublicKey, XOnlyPublicKey, Message, Signature)>(&data).unwrap(),
+                   (sk, pk, xonly, msg, sig));
+    }
+
+    #[test]
+    fn fixed_encodings() {
+        let (sk, pk, xonly, msg, sig) = values();
+        let compact = sig.serialize_compact(&Secp256k1::new());
+        let fixed = (FixedSecretKey(sk), FixedPublicKey(pk), FixedXOnly(xonly), FixedMessage(msg),
+                     FixedSignature(sig));
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&sk[..]);
+        expected.extend_from_slice(&pk.serialize());
+        expected.extend_from_slice(&xonly.serialize());
+        expected.extend_from_slice(&msg[..]);
+        expected.extend_from_slice(&compact);
+        assert_eq!(expected.len(), 32 + 33 + 32 + 32 + 64);
+
+        let data = bincode::serialize(&fixed).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(bincode::deserialize::<(FixedSecretKey, FixedPublicKey, FixedXOnly, FixedMessage,
+                                           FixedSignature)>(&data).unwrap(), fixed);
+        let data = postcard::to_allocvec(&fixed).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(postcard::from_bytes::<(FixedSecretKey, FixedPublicKey, FixedXOnly, FixedMessage,
+                                           FixedSignature)>(&data).unwrap(), fixed);
+
+        // The generator, compressed
+        static PK_HEX: &'static str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        assert_tokens(&FixedPublicKey(pk).readable(), &[Token::BorrowedStr(PK_HEX)]);
+        let mut tokens = vec![Token::Tuple { len: 33 }];
+        tokens.extend(pk.serialize().iter().map(|&byte| Token::U8(byte)));
+        tokens.push(Token::TupleEnd);
+        assert_tokens(&FixedPublicKey(pk).compact(), &tokens);
+    }
+
+    #[test]
+    fn fixed_invalid() {
+        let (_, pk, _, _, _) = values();
+        let mut data = bincode::serialize(&FixedPublicKey(pk)).unwrap();
+        assert!(bincode::deserialize::<FixedPublicKey>(&data[..32]).is_err());
+        data[0] = 0x05;
+        assert!(bincode::deserialize::<FixedPublicKey>(&data).is_err());
+        assert!(bincode::deserialize::<FixedSecretKey>(&[0; 32]).is_err());
+        assert!(bincode::deserialize::<FixedSignature>(&[0xff; 64]).is_err());
+        assert!(postcard::from_bytes::<FixedMessage>(&[0; 31]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Serialized signatures
+//! `SerializedSignature`, a DER signature in a buffer on the stack, which
+//! can be passed around, compared and iterated over like a byte slice
+//! without allocating or needing a context.
+
+use std::{fmt, ops, slice};
+use std::borrow::Borrow;
+use std::hash::{Hash, Hasher};
+
+use constants::MAX_SIGNATURE_SIZE;
+use hex;
+use super::{Error, Signature};
+
+/// A DER-encoded signature, from `Signature::to_der`
+#[derive(Copy, Clone)]
+pub struct SerializedSignature {
+    data: [u8; MAX_SIGNATURE_SIZE],
+    len: usize,
+}
+
+impl SerializedSignature {
+    /// Encodes `sig` as DER
+    pub fn from_signature(sig: &Signature) -> SerializedSignature {
+        let mut data = [0; MAX_SIGNATURE_SIZE];
+        let len = sig.serialize_der_into(&mut data);
+        SerializedSignature { data, len }
+    }
+
+    /// The encoding
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// The length of the encoding, at most `MAX_SIGNATURE_SIZE`
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always false, since no signature encodes to nothing
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Parses the encoding back into a signature
+    pub fn to_signature(&self) -> Result<Signature, Error> {
+        Signature::from_der_bytes(self.as_bytes())
+    }
+}
+
+impl Signature {
+    /// Serializes the signature in DER format into a `SerializedSignature`,
+    /// without allocating or needing a context
+    #[inline]
+    pub fn to_der(&self) -> SerializedSignature {
+        SerializedSignature::from_signature(self)
+    }
+}
+
+impl From<Signature> for SerializedSignature {
+    #[inline]
+    fn from(sig: Signature) -> SerializedSignature {
+        SerializedSignature::from_signature(&sig)
+    }
+}
+
+impl ops::Deref for SerializedSignature {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for SerializedSignature {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Borrow<[u8]> for SerializedSignature {
+    #[inline]
+    fn borrow(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl PartialEq for SerializedSignature {
+    #[inline]
+    fn eq(&self, other: &SerializedSignature) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for SerializedSignature {}
+
+impl PartialEq<[u8]> for SerializedSignature {
+    #[inline]
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_bytes() == other
+    }
+}
+
+impl PartialEq<SerializedSignature> for [u8] {
+    #[inline]
+    fn eq(&self, other: &SerializedSignature) -> bool {
+        self == other.as_bytes()
+    }
+}
+
+impl Hash for SerializedSignature {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl fmt::Debug for SerializedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SerializedSignature(")?;
+        hex::format(self.as_bytes(), f)?;
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for SerializedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        hex::format(self.as_bytes(), f)
+    }
+}
+
+impl<'a> IntoIterator for &'a SerializedSignature {
+    type Item = &'a u8;
+    type IntoIter = slice::Iter<'a, u8>;
+
+    #[inline]
+    fn into_iter(self) -> slice::Iter<'a, u8> {
+        self.as_bytes().iter()
+    }
+}
+
+impl IntoIterator for SerializedSignature {
+    type Item = u8;
+    type IntoIter = IntoIter;
+
+    #[inline]
+    fn into_iter(self) -> IntoIter {
+        IntoIter { sig: self, pos: 0 }
+    }
+}
+
+/// The bytes of a `SerializedSignature`, by value
+#[derive(Clone, Debug)]
+pub struct IntoIter {
+    sig: SerializedSignature,
+    pos: usize,
+}
+
+impl IntoIter {
+    /// The bytes not yet returned
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.sig.as_bytes()[self.pos..]
+    }
+}
+
+impl Iterator for IntoIter {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        let ret = self.sig.as_bytes().get(self.pos).cloned();
+        if ret.is_some() {
+            self.pos += 1;
+        }
+        ret
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.sig.len - self.pos;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for IntoIter {
+    #[inline]
+    fn next_back(&mut self) -> Option<u8> {
+        if self.pos == self.sig.len {
+            return None;
+        }
+        self.sig.len -= 1;
+        Some(self.sig.data[self.sig.len])
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "verify-only"))]
+    use std::vec::Vec;
+
+    #[cfg(not(feature = "verify-only"))]
+    use rng::thread_rng;
+    #[cfg(not(feature = "verify-only"))]
+    use super::SerializedSignature;
+    use super::super::Error::{InvalidDerSignature, SignatureOutOfRange};
+    #[cfg(not(feature = "verify-only"))]
+    use Message;
+    use {Secp256k1, Signature};
+
+    #[cfg(not(feature = "verify-only"))]
+    #[test]
+    fn byte_container() {
+        let s = Secp256k1::new();
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        let sig = s.sign(&Message::from([3; 32]), &sk);
+        let der = sig.serialize_der(&s);
+
+        let ser = sig.to_der();
+        assert_eq!(ser, SerializedSignature::from(sig));
+        assert_eq!(ser.as_ref(), &der[..]);
+        assert_eq!(ser, der[..]);
+        assert_eq!(der[..], ser);
+        assert_eq!(ser.len(), der.len());
+        assert!(!ser.is_empty());
+        assert_eq!(ser.to_string(), sig.to_string());
+        assert_eq!(ser.to_signature(), Ok(sig));
+
+        assert_eq!((&ser).into_iter().cloned().collect::<Vec<_>>(), der);
+        assert_eq!(ser.into_iter().collect::<Vec<_>>(), der);
+        assert_eq!(ser.into_iter().rev().collect::<Vec<_>>(), der.iter().rev().cloned().collect::<Vec<_>>());
+        let mut iter = ser.into_iter();
+        assert_eq!(iter.len(), der.len());
+        assert_eq!(iter.next(), Some(0x30));
+        assert_eq!(iter.next_back(), der.last().cloned());
+        assert_eq!(iter.as_slice(), &der[1..der.len() - 1]);
+        assert_eq!(iter.len(), der.len() - 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let s = Secp256k1::verification_only();
+        // r is the curve order, s is 1
+        let mut der = vec![0x30, 0x26, 0x02, 0x21, 0x00];
+        der.extend_from_slice(&::constants::CURVE_ORDER);
+        der.extend_from_slice(&[0x02, 0x01, 0x01]);
+        assert_eq!(Signature::from_der(&s, &der), Err(SignatureOutOfRange));
+        // A negative r
+        let negative = [0x30, 0x06, 0x02, 0x01, 0x80, 0x02, 0x01, 0x01];
+        assert_eq!(Signature::from_der(&s, &negative), Err(SignatureOutOfRange));
+        assert!(Signature::from_der_lax(&s, &negative).is_ok());
+        assert_eq!(Signature::from_der(&s, &negative[..7]), Err(InvalidDerSignature));
+        assert_eq!(Signature::from_der(&s, &[]), Err(InvalidDerSignature));
+        assert_eq!(Signature::from_der_lax(&s, &[0x31]), Err(InvalidDerSignature));
+    }
+}
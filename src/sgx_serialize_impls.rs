@@ -0,0 +1,180 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # SGX serialization
+//! Implementations of the SGX SDK's `Serializable` and `DeSerializable`
+//! traits, so that keys and signatures can be fields of sealed state structs
+//! deriving them with `sgx_serialize_derive`. Each is encoded as a sequence
+//! of bytes: the 32 bytes of a `SecretKey`, the 33-byte compressed form of a
+//! `PublicKey`, the 64-byte compact `r || s` of a `Signature` and the 32
+//! bytes of a `NonceCommitment` (with the "hashes" feature). Decoding
+//! checks the data as `from_slice` does. Requires compilation with the
+//! "sgx-serialize" feature.
+
+use sgx_serialize::{DeSerializable, Decoder, Encoder, Serializable};
+
+#[cfg(not(feature = "verify-only"))]
+use cleanup;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+use commitment::{NonceCommitment, NONCE_COMMITMENT_SIZE};
+use constants;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use super::Signature;
+
+fn encode_bytes<S: Encoder>(s: &mut S, bytes: &[u8]) -> Result<(), S::Error> {
+    s.emit_seq(bytes.len(), |s| {
+        for (i, &b) in bytes.iter().enumerate() {
+            s.emit_seq_elt(i, |s| s.emit_u8(b))?;
+        }
+        Ok(())
+    })
+}
+
+/// Reads a sequence of exactly `buf.len()` bytes into `buf`
+fn decode_bytes<D: Decoder>(d: &mut D, buf: &mut [u8]) -> Result<(), D::Error> {
+    d.read_seq(|d, len| {
+        if len != buf.len() {
+            return Err(d.error("wrong length"));
+        }
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = d.read_seq_elt(i, |d| d.read_u8())?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(not(any(feature = "no-secret-export", feature = "verify-only")))]
+impl Serializable for SecretKey {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        encode_bytes(s, &self[..])
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl DeSerializable for SecretKey {
+    fn decode<D: Decoder>(d: &mut D) -> Result<SecretKey, D::Error> {
+        let mut buf = [0u8; constants::SECRET_KEY_SIZE];
+        let res = decode_bytes(d, &mut buf)
+            .and_then(|_| SecretKey::from_slice(&buf).map_err(|_| d.error("invalid secret key")));
+        cleanup::wipe(&mut buf);
+        res
+    }
+}
+
+impl Serializable for PublicKey {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        encode_bytes(s, &self.serialize())
+    }
+}
+
+impl DeSerializable for PublicKey {
+    fn decode<D: Decoder>(d: &mut D) -> Result<PublicKey, D::Error> {
+        let mut buf = [0u8; constants::PUBLIC_KEY_SIZE];
+        decode_bytes(d, &mut buf)?;
+        if buf[0] != 0x02 && buf[0] != 0x03 {
+            return Err(d.error("public key not compressed"));
+        }
+        PublicKey::from_slice(&buf).map_err(|_| d.error("invalid public key"))
+    }
+}
+
+impl Serializable for Signature {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        encode_bytes(s, &self.compact_bytes())
+    }
+}
+
+impl DeSerializable for Signature {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Signature, D::Error> {
+        let mut buf = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        decode_bytes(d, &mut buf)?;
+        Signature::from_compact_bytes(&buf).map_err(|_| d.error("invalid signature"))
+    }
+}
+
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+impl Serializable for NonceCommitment {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        encode_bytes(s, &self.serialize())
+    }
+}
+
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+impl DeSerializable for NonceCommitment {
+    fn decode<D: Decoder>(d: &mut D) -> Result<NonceCommitment, D::Error> {
+        let mut buf = [0u8; NONCE_COMMITMENT_SIZE];
+        decode_bytes(d, &mut buf)?;
+        Ok(NonceCommitment::from(buf))
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use sgx_serialize::{DeSerializeHelper, SerializeHelper};
+
+    use rng::thread_rng;
+    use super::super::{Message, PublicKey, Secp256k1, SecretKey, Signature};
+
+    #[test]
+    fn round_trip() {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let sig = s.sign(&Message::from_slice(&[1; 32]).unwrap(), &sk);
+
+        #[cfg(not(feature = "no-secret-export"))]
+        {
+            let encoded = SerializeHelper::new().encode(sk).unwrap();
+            assert_eq!(DeSerializeHelper::<SecretKey>::new(encoded).decode(), Some(sk));
+        }
+        let encoded = SerializeHelper::new().encode(pk).unwrap();
+        assert_eq!(DeSerializeHelper::<PublicKey>::new(encoded).decode(), Some(pk));
+        let encoded = SerializeHelper::new().encode(sig).unwrap();
+        assert_eq!(DeSerializeHelper::<Signature>::new(encoded).decode(), Some(sig));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn nonce_commitment() {
+        use commitment::NonceCommitment;
+
+        let s = Secp256k1::new();
+        let (_, r) = s.generate_keypair(&mut thread_rng());
+        let c = NonceCommitment::commit(&r);
+        let encoded = SerializeHelper::new().encode(c).unwrap();
+        assert_eq!(DeSerializeHelper::<NonceCommitment>::new(encoded).decode(), Some(c));
+        let encoded = SerializeHelper::new().encode(vec![1u8; 33]).unwrap();
+        assert_eq!(DeSerializeHelper::<NonceCommitment>::new(encoded).decode(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let encoded = SerializeHelper::new().encode(vec![0u8; 32]).unwrap();
+        assert_eq!(DeSerializeHelper::<SecretKey>::new(encoded).decode(), None);
+        let encoded = SerializeHelper::new().encode(vec![1u8; 31]).unwrap();
+        assert_eq!(DeSerializeHelper::<SecretKey>::new(encoded).decode(), None);
+
+        let s = Secp256k1::new();
+        let (_, pk) = s.generate_keypair(&mut thread_rng());
+        let mut bytes = pk.serialize().to_vec();
+        bytes[0] = 0x04;
+        let encoded = SerializeHelper::new().encode(bytes).unwrap();
+        assert_eq!(DeSerializeHelper::<PublicKey>::new(encoded).decode(), None);
+        let encoded = SerializeHelper::new().encode(pk.serialize_uncompressed().to_vec()).unwrap();
+        assert_eq!(DeSerializeHelper::<PublicKey>::new(encoded).decode(), None);
+        let encoded = SerializeHelper::new().encode(vec![0xffu8; 64]).unwrap();
+        assert_eq!(DeSerializeHelper::<Signature>::new(encoded).decode(), None);
+    }
+}
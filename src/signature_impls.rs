@@ -0,0 +1,195 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # RustCrypto `signature` traits
+//! Implementations of the `signature` 2.x traits, so that `SecretKey` and
+//! `PublicKey` can be used wherever code is generic over the `ecdsa`/`k256`
+//! signing and verifying key bounds. As with `k256`, `Signer` and
+//! `Verifier` hash the message with SHA-256 and therefore also need the
+//! "hashes" feature; the digest and prehash variants do not. Signatures
+//! encode as 64-byte compact `r || s`, and decode with the crate's
+//! `TryFrom<&[u8]>`, whose error is the crate's `Error` rather than
+//! `signature::Error`. Requires compilation with the "signature" feature.
+
+use signature::{self, SignatureEncoding, DigestVerifier};
+#[cfg(not(feature = "verify-only"))]
+use signature::DigestSigner;
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+use signature::Signer;
+#[cfg(feature = "hashes")]
+use signature::Verifier;
+use signature::digest::Digest;
+use signature::digest::consts::U32;
+#[cfg(not(feature = "verify-only"))]
+use signature::hazmat::PrehashSigner;
+use signature::hazmat::PrehashVerifier;
+
+use ffi;
+use key::PublicKey;
+#[cfg(not(feature = "verify-only"))]
+use key::SecretKey;
+use super::{Message, Signature};
+
+impl From<Signature> for [u8; 64] {
+    /// Serializes the signature in compact format
+    fn from(sig: Signature) -> [u8; 64] {
+        let mut ret = [0; 64];
+        unsafe {
+            let err = ffi::secp256k1_ecdsa_signature_serialize_compact(ffi::secp256k1_context_no_precomp,
+                                                                       ret.as_mut_ptr(), sig.as_ptr());
+            // Serialization never fails
+            assert!(err == 1);
+        }
+        ret
+    }
+}
+
+impl SignatureEncoding for Signature {
+    type Repr = [u8; 64];
+}
+
+fn message_from_prehash(prehash: &[u8]) -> Result<Message, signature::Error> {
+    Message::from_slice(prehash).map_err(|_| signature::Error::new())
+}
+
+#[cfg(all(feature = "hashes", not(feature = "verify-only")))]
+impl Signer<Signature> for SecretKey {
+    /// Signs the SHA-256 hash of `msg` using the global context
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature, signature::Error> {
+        Ok(self.sign_ecdsa(&Message::from_digest(::hashes::sha256(msg))))
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl<D: Digest<OutputSize = U32>> DigestSigner<D, Signature> for SecretKey {
+    fn try_sign_digest(&self, digest: D) -> Result<Signature, signature::Error> {
+        Ok(self.sign_ecdsa(&message_from_prehash(&digest.finalize())?))
+    }
+}
+
+#[cfg(not(feature = "verify-only"))]
+impl PrehashSigner<Signature> for SecretKey {
+    /// Signs a 32-byte prehash; any other length is an error
+    fn sign_prehash(&self, prehash: &[u8]) -> Result<Signature, signature::Error> {
+        Ok(self.sign_ecdsa(&message_from_prehash(prehash)?))
+    }
+}
+
+#[cfg(feature = "hashes")]
+impl Verifier<Signature> for PublicKey {
+    /// Verifies a signature over the SHA-256 hash of `msg` using the global
+    /// context
+    fn verify(&self, msg: &[u8], sig: &Signature) -> Result<(), signature::Error> {
+        PublicKey::verify(self, &Message::from_digest(::hashes::sha256(msg)), sig).map_err(|_| signature::Error::new())
+    }
+}
+
+impl<D: Digest<OutputSize = U32>> DigestVerifier<D, Signature> for PublicKey {
+    fn verify_digest(&self, digest: D, sig: &Signature) -> Result<(), signature::Error> {
+        let msg = message_from_prehash(&digest.finalize())?;
+        PublicKey::verify(self, &msg, sig).map_err(|_| signature::Error::new())
+    }
+}
+
+impl PrehashVerifier<Signature> for PublicKey {
+    /// Verifies a signature over a 32-byte prehash; any other length is an
+    /// error
+    fn verify_prehash(&self, prehash: &[u8], sig: &Signature) -> Result<(), signature::Error> {
+        let msg = message_from_prehash(prehash)?;
+        PublicKey::verify(self, &msg, sig).map_err(|_| signature::Error::new())
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::convert::TryFrom;
+
+    use signature::{SignatureEncoding, DigestSigner, DigestVerifier};
+    use signature::digest::{self, Digest, FixedOutput, HashMarker, OutputSizeUser, Update};
+    use signature::digest::consts::U32;
+    use signature::hazmat::{PrehashSigner, PrehashVerifier};
+
+    use super::super::{Message, Secp256k1, Signature};
+    use key::{SecretKey, PublicKey};
+
+    /// A "digest" which just keeps the last 32 bytes it was fed, so the
+    /// digest impls can be checked against the prehash ones
+    #[derive(Clone, Default)]
+    struct Last32([u8; 32]);
+    impl HashMarker for Last32 {}
+    impl OutputSizeUser for Last32 { type OutputSize = U32; }
+    impl Update for Last32 {
+        fn update(&mut self, data: &[u8]) {
+            for &b in data {
+                self.0.copy_within(1.., 0);
+                self.0[31] = b;
+            }
+        }
+    }
+    impl FixedOutput for Last32 {
+        fn finalize_into(self, out: &mut digest::Output<Self>) {
+            out.copy_from_slice(&self.0);
+        }
+    }
+
+    fn keypair() -> (SecretKey, PublicKey) {
+        let s = Secp256k1::new();
+        let sk = SecretKey::from_slice(&[0xcd; 32]).unwrap();
+        (sk, PublicKey::from_secret_key(&s, &sk))
+    }
+
+    #[test]
+    fn prehash_and_digest() {
+        let (sk, pk) = keypair();
+        let prehash = [0x5a; 32];
+
+        let sig: Signature = sk.sign_prehash(&prehash).unwrap();
+        assert_eq!(sig, sk.sign_ecdsa(&Message::from_slice(&prehash).unwrap()));
+        assert!(pk.verify_prehash(&prehash, &sig).is_ok());
+        assert!(pk.verify_prehash(&[0xa5; 32], &sig).is_err());
+        assert!(PrehashSigner::<Signature>::sign_prehash(&sk, &prehash[1..]).is_err());
+        assert!(pk.verify_prehash(&prehash[1..], &sig).is_err());
+
+        let digest = Last32::new().chain_update(&prehash);
+        assert_eq!(sk.sign_digest(digest.clone()), sig);
+        assert!(pk.verify_digest(digest, &sig).is_ok());
+        assert!(pk.verify_digest(Last32::new(), &sig).is_err());
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn signer_verifier() {
+        use signature::{Signer, Verifier};
+
+        let (sk, pk) = keypair();
+        let sig: Signature = sk.sign(b"hello world");
+        assert_eq!(sig, sk.sign_ecdsa(&Message::from_digest(::hashes::sha256(b"hello world"))));
+        assert!(Verifier::verify(&pk, b"hello world", &sig).is_ok());
+        assert!(Verifier::verify(&pk, b"hello world!", &sig).is_err());
+    }
+
+    #[test]
+    fn signature_encoding() {
+        let s = Secp256k1::new();
+        let (sk, _) = keypair();
+        let sig = sk.sign_ecdsa(&Message::from_slice(&[0x5a; 32]).unwrap());
+
+        let bytes = sig.to_bytes();
+        assert_eq!(bytes[..], sig.serialize_compact(&s)[..]);
+        assert_eq!(sig.encoded_len(), 64);
+        assert_eq!(Signature::try_from(&bytes[..]).unwrap(), sig);
+        assert!(Signature::try_from(&bytes[1..]).is_err());
+        assert!(Signature::try_from(&[0xff; 64][..]).is_err());
+    }
+}
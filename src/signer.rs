@@ -0,0 +1,326 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Signers
+//! `Signer` is one abstraction over wherever a key lives: a `KeyPair` in
+//! this enclave, a key in a `Vault` (through `VaultSigner`), or a key held
+//! by another enclave or an HSM (through `RemoteSigner`). Application code
+//! written against `&Signer` doesn't change when the key moves.
+//!
+//! Signing takes `&self` and no context, so a signer can be stored in a
+//! trait object and handed to request handlers, which may be driven by an
+//! event loop on the host. `KeyPair` signs with the global context; the
+//! vault signers borrow their vault from a `RefCell`, as `Vault::sign`
+//! needs it mutably.
+//!
+//! `RemoteSigner` sends requests over a `Transport` supplied by the host
+//! application, typically an OCALL:
+//!
+//! ```ignore
+//! extern "C" {
+//!     fn ocall_remote_sign(ret: *mut u32, req: *const u8, req_len: usize,
+//!                          resp: *mut u8, resp_cap: usize, resp_len: *mut usize) -> sgx_status_t;
+//! }
+//!
+//! struct Ocall;
+//!
+//! impl Transport for Ocall {
+//!     fn call(&self, request: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+//!         let (mut ret, mut len) = (0, 0);
+//!         let status = unsafe {
+//!             ocall_remote_sign(&mut ret, request.as_ptr(), request.len(),
+//!                               response.as_mut_ptr(), response.len(), &mut len)
+//!         };
+//!         if status != sgx_status_t::SGX_SUCCESS || ret != 0 || len > response.len() {
+//!             return Err(Error::EnclaveService);
+//!         }
+//!         Ok(len)
+//!     }
+//! }
+//!
+//! let signer = RemoteSigner::connect(Ocall, key_id)?;
+//! ```
+//!
+//! The host is untrusted, so `RemoteSigner` checks every signature it gets
+//! back against the key's public key before returning it.
+
+use std::cell::RefCell;
+
+#[cfg(feature = "protected-fs")]
+use vault::FileBackedVault;
+use vault::{KeyHandle, Vault};
+use super::Error::{self, InvalidSignerResponse};
+use {constants, KeyPair, Message, PublicKey, Secp256k1, Signature, Signing, SECP256K1};
+
+/// A key which signs messages, wherever it is kept
+pub trait Signer {
+    /// The public key of the signing key
+    fn public_key(&self) -> Result<PublicKey, Error>;
+
+    /// Signs `msg`
+    fn sign(&self, msg: &Message) -> Result<Signature, Error>;
+}
+
+impl Signer for KeyPair {
+    fn public_key(&self) -> Result<PublicKey, Error> {
+        Ok(KeyPair::public_key(self))
+    }
+
+    /// Signs `msg` using the global context
+    fn sign(&self, msg: &Message) -> Result<Signature, Error> {
+        Ok(SECP256K1.sign(msg, &self.secret_key()))
+    }
+}
+
+/// The key for a handle in a `Vault`, signing with `Vault::sign` and so
+/// subject to the key's limits and policies
+pub struct VaultSigner<'a, C: 'a> {
+    secp: &'a Secp256k1<C>,
+    vault: &'a RefCell<Vault>,
+    handle: KeyHandle,
+}
+
+impl<'a, C: Signing> VaultSigner<'a, C> {
+    /// Signs with the key for `handle` in `vault`
+    pub fn new(secp: &'a Secp256k1<C>, vault: &'a RefCell<Vault>, handle: KeyHandle) -> VaultSigner<'a, C> {
+        VaultSigner { secp, vault, handle }
+    }
+}
+
+impl<'a, C: Signing> Signer for VaultSigner<'a, C> {
+    fn public_key(&self) -> Result<PublicKey, Error> {
+        self.vault.borrow().public_key(self.handle)
+    }
+
+    fn sign(&self, msg: &Message) -> Result<Signature, Error> {
+        self.vault.borrow_mut().sign(self.secp, self.handle, msg)
+    }
+}
+
+/// The key for a handle in a `FileBackedVault`, signing with
+/// `FileBackedVault::sign`, which saves the vault before each signature.
+/// Requires compilation with the "protected-fs" feature.
+#[cfg(feature = "protected-fs")]
+pub struct FileVaultSigner<'a, C: 'a> {
+    secp: &'a Secp256k1<C>,
+    vault: &'a RefCell<FileBackedVault>,
+    handle: KeyHandle,
+}
+
+#[cfg(feature = "protected-fs")]
+impl<'a, C: Signing> FileVaultSigner<'a, C> {
+    /// Signs with the key for `handle` in `vault`
+    pub fn new(secp: &'a Secp256k1<C>, vault: &'a RefCell<FileBackedVault>, handle: KeyHandle)
+               -> FileVaultSigner<'a, C> {
+        FileVaultSigner { secp, vault, handle }
+    }
+}
+
+#[cfg(feature = "protected-fs")]
+impl<'a, C: Signing> Signer for FileVaultSigner<'a, C> {
+    fn public_key(&self) -> Result<PublicKey, Error> {
+        self.vault.borrow().vault().public_key(self.handle)
+    }
+
+    fn sign(&self, msg: &Message) -> Result<Signature, Error> {
+        self.vault.borrow_mut().sign(self.secp, self.handle, msg)
+    }
+}
+
+/// Request for the public key of a remote key: the tag, then the key id
+/// (u32 LE). The response is the 33-byte compressed public key.
+pub const REQUEST_PUBLIC_KEY: u8 = 1;
+/// Request for a signature by a remote key: the tag, the key id (u32 LE)
+/// and the 32-byte message. The response is the 64-byte compact signature.
+pub const REQUEST_SIGN: u8 = 2;
+
+/// The longest request a `RemoteSigner` sends
+pub const MAX_REQUEST_SIZE: usize = 1 + 4 + constants::MESSAGE_SIZE;
+/// The longest response a `RemoteSigner` accepts
+pub const MAX_RESPONSE_SIZE: usize = constants::COMPACT_SIGNATURE_SIZE;
+
+/// A channel to a signer outside this enclave, e.g. an OCALL to the host
+pub trait Transport {
+    /// Sends `request` and writes the response into `response`, returning
+    /// its length
+    fn call(&self, request: &[u8], response: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// A key held outside this enclave, reached over a `Transport`. Requests
+/// and responses are as described at `REQUEST_PUBLIC_KEY` and
+/// `REQUEST_SIGN`.
+#[derive(Debug)]
+pub struct RemoteSigner<T> {
+    transport: T,
+    key_id: u32,
+    public_key: PublicKey,
+}
+
+impl<T: Transport> RemoteSigner<T> {
+    /// Fetches the public key of the remote key `key_id`. Fails with the
+    /// transport's error, or with `InvalidSignerResponse` if the response
+    /// is not a compressed public key.
+    pub fn connect(transport: T, key_id: u32) -> Result<RemoteSigner<T>, Error> {
+        let mut request = [0; 5];
+        request[0] = REQUEST_PUBLIC_KEY;
+        request[1..5].copy_from_slice(&key_id.to_le_bytes());
+        let mut response = [0; MAX_RESPONSE_SIZE];
+        let len = transport.call(&request, &mut response)?;
+        if len != constants::PUBLIC_KEY_SIZE {
+            return Err(InvalidSignerResponse);
+        }
+        let public_key = PublicKey::from_slice(&response[..len]).map_err(|_| InvalidSignerResponse)?;
+        Ok(RemoteSigner { transport, key_id, public_key })
+    }
+
+    /// The id of the remote key
+    pub fn key_id(&self) -> u32 {
+        self.key_id
+    }
+
+    /// The transport requests are sent over
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+}
+
+impl<T: Transport> Signer for RemoteSigner<T> {
+    fn public_key(&self) -> Result<PublicKey, Error> {
+        Ok(self.public_key)
+    }
+
+    /// Asks the remote key to sign `msg`. Fails with the transport's error,
+    /// with `InvalidSignerResponse` if the response is not a compact
+    /// signature, and with `IncorrectSignature` if it is not a valid
+    /// signature of `msg` by the remote key.
+    fn sign(&self, msg: &Message) -> Result<Signature, Error> {
+        let mut request = [0; MAX_REQUEST_SIZE];
+        request[0] = REQUEST_SIGN;
+        request[1..5].copy_from_slice(&self.key_id.to_le_bytes());
+        request[5..].copy_from_slice(&msg[..]);
+        let mut response = [0; MAX_RESPONSE_SIZE];
+        let len = self.transport.call(&request, &mut response)?;
+        if len != constants::COMPACT_SIGNATURE_SIZE {
+            return Err(InvalidSignerResponse);
+        }
+        let sig = Signature::from_compact_bytes(&response).map_err(|_| InvalidSignerResponse)?;
+        self.public_key.verify(msg, &sig)?;
+        Ok(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::vec::Vec;
+
+    use super::{RemoteSigner, Signer, Transport, VaultSigner, REQUEST_PUBLIC_KEY, REQUEST_SIGN};
+    use super::super::Error::{self, EnclaveService, IncorrectSignature, InvalidSignerResponse, KeyExhausted};
+    use rng::thread_rng;
+    use vault::Vault;
+    use {KeyPair, Message, Secp256k1};
+
+    // The other end of a `RemoteSigner`, serving a `KeyPair`
+    struct Loopback {
+        key_id: u32,
+        key: KeyPair,
+        // Corrupts the response: 1 truncates it, 2 flips a bit, 3 signs
+        // another message
+        corrupt: Cell<u8>,
+        requests: Cell<usize>,
+    }
+
+    impl Transport for Loopback {
+        fn call(&self, request: &[u8], response: &mut [u8]) -> Result<usize, Error> {
+            self.requests.set(self.requests.get() + 1);
+            let mut key_id = [0; 4];
+            key_id.copy_from_slice(&request[1..5]);
+            if u32::from_le_bytes(key_id) != self.key_id {
+                return Err(EnclaveService);
+            }
+            let mut data: Vec<u8> = match request[0] {
+                REQUEST_PUBLIC_KEY if request.len() == 5 => self.key.public_key().serialize().to_vec(),
+                REQUEST_SIGN if request.len() == 37 => {
+                    let mut msg = Message::from_slice(&request[5..]).unwrap();
+                    if self.corrupt.get() == 3 {
+                        msg = Message::from_slice(&[0x99; 32]).unwrap();
+                    }
+                    self.key.sign(&msg)?.compact_bytes().to_vec()
+                }
+                _ => return Err(EnclaveService),
+            };
+            match self.corrupt.get() {
+                1 => { data.pop(); }
+                2 => data[40] ^= 1,
+                _ => {}
+            }
+            response[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    fn loopback(key_id: u32) -> Loopback {
+        let key = KeyPair::new(&Secp256k1::new(), &mut thread_rng());
+        Loopback { key_id, key, corrupt: Cell::new(0), requests: Cell::new(0) }
+    }
+
+    // Application code only sees the abstraction
+    fn sign_and_check(signer: &Signer, msg: &Message) -> Result<(), Error> {
+        let sig = signer.sign(msg)?;
+        Secp256k1::verification_only().verify(msg, &sig, &signer.public_key()?)
+    }
+
+    #[test]
+    fn keypair_and_vault() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        let key = KeyPair::new(&s, &mut thread_rng());
+        assert_eq!(Signer::public_key(&key), Ok(key.public_key()));
+        assert_eq!(Signer::sign(&key, &msg), Ok(s.sign(&msg, &key.secret_key())));
+        assert_eq!(sign_and_check(&key, &msg), Ok(()));
+
+        let vault = RefCell::new(Vault::new());
+        let handle = vault.borrow_mut().insert(&s, key.secret_key());
+        vault.borrow_mut().set_limit(handle, Some(1)).unwrap();
+        let signer = VaultSigner::new(&s, &vault, handle);
+        assert_eq!(signer.public_key(), Ok(key.public_key()));
+        assert_eq!(sign_and_check(&signer, &msg), Ok(()));
+        // The vault's limits still apply
+        assert_eq!(vault.borrow().uses(handle), Ok(1));
+        assert_eq!(signer.sign(&msg), Err(KeyExhausted));
+    }
+
+    #[test]
+    fn remote() {
+        let msg = Message::from_slice(&[0x5a; 32]).unwrap();
+        assert_eq!(RemoteSigner::connect(loopback(7), 8).err(), Some(EnclaveService));
+
+        let remote = RemoteSigner::connect(loopback(7), 7).unwrap();
+        assert_eq!(remote.key_id(), 7);
+        assert_eq!(remote.public_key(), Ok(remote.transport().key.public_key()));
+        assert_eq!(sign_and_check(&remote, &msg), Ok(()));
+        assert_eq!(remote.transport().requests.get(), 2);
+
+        remote.transport().corrupt.set(1);
+        assert_eq!(remote.sign(&msg), Err(InvalidSignerResponse));
+        remote.transport().corrupt.set(2);
+        assert!(remote.sign(&msg).is_err());
+        remote.transport().corrupt.set(3);
+        assert_eq!(remote.sign(&msg), Err(IncorrectSignature));
+
+        let short = loopback(1);
+        short.corrupt.set(1);
+        assert_eq!(RemoteSigner::connect(short, 1).err(), Some(InvalidSignerResponse));
+    }
+}
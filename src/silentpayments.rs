@@ -0,0 +1,321 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Silent payments
+//! The key arithmetic of BIP352 silent payments. A receiver publishes a
+//! scan key `B_scan` and a spend key `B_spend`; a sender derives fresh
+//! taproot output keys for them from the transaction's inputs, and the
+//! receiver finds those outputs by ECDH with its scan key.
+//!
+//! A `Scanner` holds the scan secret key, so a watch-tower enclave can scan
+//! transactions for the receiver without the scan key ever leaving it; it
+//! only needs the spend public key. Transaction parsing is left to the
+//! caller, which supplies each transaction's smallest outpoint, the sum
+//! `A` of its eligible input public keys (taproot keys with even y) and its
+//! taproot output keys. Requires compilation with the "hashes" feature.
+
+use std::vec::Vec;
+
+use hashes::{TaggedHasher, BIP352_INPUTS_TAG, BIP352_LABEL_TAG, BIP352_SHARED_SECRET_TAG};
+use key::{Parity, PublicKey, SecretKey, XOnlyPublicKey};
+use scalar::Scalar;
+use super::Error;
+use {Secp256k1, Signing, Verification};
+
+/// The size (in bytes) of a serialized outpoint: the txid as it appears in
+/// transactions, then the output index (little-endian)
+pub const OUTPOINT_SIZE: usize = 36;
+
+/// A BIP352 tagged hash of `parts`, as a scalar. Fails with `InvalidScalar`
+/// if it is not below the curve order.
+fn tagged_scalar(tag: &[u8], parts: &[&[u8]]) -> Result<Scalar, Error> {
+    let mut engine = TaggedHasher::new(tag);
+    for part in parts {
+        engine.input(part);
+    }
+    Scalar::from_be_bytes(engine.result())
+}
+
+fn u32_be(n: u32) -> [u8; 4] {
+    [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+/// The input hash of a transaction, from its smallest outpoint
+/// (lexicographically, as serialized) and the sum of its eligible input
+/// public keys
+pub fn input_hash(smallest_outpoint: &[u8; OUTPOINT_SIZE], input_sum: &PublicKey) -> Result<Scalar, Error> {
+    tagged_scalar(BIP352_INPUTS_TAG, &[&smallest_outpoint[..], &input_sum.serialize()])
+}
+
+/// The sender's ECDH shared secret with the receiver's scan key,
+/// `input_hash * a * B_scan`, where `a` is the sum of the eligible input
+/// secret keys (each negated if its taproot public key has odd y)
+pub fn sender_shared_secret<C: Verification>(secp: &Secp256k1<C>, input_hash: &Scalar, input_secret: &SecretKey,
+                                             scan_key: &PublicKey) -> Result<PublicKey, Error> {
+    let mut a = Scalar::from_secret_key(input_secret);
+    let mut shared = *scan_key;
+    let res = shared.mul_assign(secp, &a.mul(input_hash));
+    a.wipe();
+    res.map(|_| shared)
+}
+
+/// The tweak `t_k` of the `k`th output for a shared secret
+pub fn output_tweak(shared_secret: &PublicKey, k: u32) -> Result<Scalar, Error> {
+    tagged_scalar(BIP352_SHARED_SECRET_TAG, &[&shared_secret.serialize(), &u32_be(k)])
+}
+
+/// The `k`th output key for a shared secret, `B_spend + t_k * G`, where
+/// `spend_key` may be labeled. Its x-coordinate is the taproot output key.
+pub fn output_key<C: Verification>(secp: &Secp256k1<C>, shared_secret: &PublicKey, spend_key: &PublicKey, k: u32)
+                                   -> Result<PublicKey, Error> {
+    let mut key = *spend_key;
+    key.add_exp_assign(secp, &output_tweak(shared_secret, k)?)?;
+    Ok(key)
+}
+
+/// An output found by a `Scanner`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FoundOutput {
+    /// The taproot output key
+    pub output: [u8; 32],
+    /// What to add to the spend secret key to spend the output
+    pub tweak: Scalar,
+    /// The label the output was paid to, if any
+    pub label: Option<u32>,
+}
+
+impl FoundOutput {
+    /// The secret key for the output, given the spend secret key. (Its
+    /// public key may have odd y, in which case BIP340 signing negates it.)
+    pub fn secret_key<C>(&self, secp: &Secp256k1<C>, spend_secret: &SecretKey) -> Result<SecretKey, Error> {
+        let mut sk = *spend_secret;
+        sk.add_assign(secp, &self.tweak)?;
+        Ok(sk)
+    }
+}
+
+/// A receiver's scan key, with the spend public key and labels, which
+/// finds the outputs paid to the receiver. The scan key is wiped on drop.
+pub struct Scanner {
+    scan_key: SecretKey,
+    spend_key: PublicKey,
+    labels: Vec<(PublicKey, u32, Scalar)>,
+}
+
+impl Drop for Scanner {
+    fn drop(&mut self) {
+        self.scan_key.wipe();
+    }
+}
+
+impl Scanner {
+    /// Creates a scanner for the scan secret key and the spend public key
+    pub fn new(scan_key: SecretKey, spend_key: PublicKey) -> Scanner {
+        Scanner { scan_key, spend_key, labels: Vec::new() }
+    }
+
+    /// The scan public key, for the receiver's address
+    pub fn scan_public_key<C: Signing>(&self, secp: &Secp256k1<C>) -> PublicKey {
+        PublicKey::from_secret_key(secp, &self.scan_key)
+    }
+
+    /// The spend public key
+    #[inline]
+    pub fn spend_public_key(&self) -> &PublicKey {
+        &self.spend_key
+    }
+
+    /// Scans for outputs paid to label `m` from now on, and returns the
+    /// labeled spend key `B_spend + hash(b_scan || m) * G` for the
+    /// receiver's address with that label. Label 0 is reserved for change.
+    pub fn add_label<C: Signing>(&mut self, secp: &Secp256k1<C>, m: u32) -> Result<PublicKey, Error> {
+        let tweak = tagged_scalar(BIP352_LABEL_TAG, &[&self.scan_key.secret_bytes()[..], &u32_be(m)])?;
+        let point = PublicKey::from_scalar_mul_g(secp, &tweak)?;
+        if !self.labels.iter().any(|&(_, label, _)| label == m) {
+            self.labels.push((point, m, tweak));
+        }
+        self.spend_key.combine(secp, &point)
+    }
+
+    /// Finds the outputs among `outputs` (taproot output keys) which are
+    /// paid to the receiver, given the transaction's input hash and input
+    /// public key sum
+    pub fn scan<C: Verification>(&self, secp: &Secp256k1<C>, input_hash: &Scalar, input_sum: &PublicKey,
+                                 outputs: &[[u8; 32]]) -> Result<Vec<FoundOutput>, Error> {
+        let mut b = Scalar::from_secret_key(&self.scan_key);
+        let mut shared = *input_sum;
+        let res = shared.mul_assign(secp, &b.mul(input_hash));
+        b.wipe();
+        res?;
+
+        let minus_one = Scalar::ONE.negate();
+        let mut found: Vec<FoundOutput> = Vec::new();
+        let mut k = 0;
+        loop {
+            let tweak = output_tweak(&shared, k)?;
+            let mut key = self.spend_key;
+            key.add_exp_assign(secp, &tweak)?;
+            let x = key.x_coordinate();
+            let mut neg_key = key;
+            neg_key.mul_assign(secp, &minus_one)?;
+            let mut matched = None;
+
+            let unclaimed = outputs.iter().filter(|o| !found.iter().any(|f| f.output == **o));
+            for output in unclaimed {
+                if *output == x {
+                    matched = Some(FoundOutput { output: x, tweak, label: None });
+                    break;
+                }
+                if self.labels.is_empty() {
+                    continue;
+                }
+                // The output minus the unlabeled key, for either parity of
+                // the output, must be a label point
+                let xonly = match XOnlyPublicKey::from_slice(output) {
+                    Ok(key) => key,
+                    Err(_) => continue,
+                };
+                let mut candidates = Vec::with_capacity(2);
+                for &parity in &[Parity::Even, Parity::Odd] {
+                    candidates.extend(xonly.public_key(parity).combine(secp, &neg_key).ok());
+                }
+                let label = self.labels.iter().find(|l| candidates.contains(&l.0));
+                if let Some(&(_, m, ref label_tweak)) = label {
+                    matched = Some(FoundOutput { output: *output, tweak: tweak.add(label_tweak), label: Some(m) });
+                    break;
+                }
+            }
+
+            match matched {
+                Some(output) => found.push(output),
+                None => return Ok(found),
+            }
+            k += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use scalar::Scalar;
+    use super::{input_hash, output_key, sender_shared_secret, Scanner, OUTPOINT_SIZE};
+    use super::super::{PublicKey, Secp256k1, SecretKey};
+
+    fn outpoint() -> [u8; OUTPOINT_SIZE] {
+        let mut outpoint = [0x44; OUTPOINT_SIZE];
+        outpoint[32..].copy_from_slice(&[1, 0, 0, 0]);
+        outpoint
+    }
+
+    fn secret_key(hex: &str) -> SecretKey {
+        SecretKey::from_slice(&hex!(hex)).unwrap()
+    }
+
+    fn array32(hex: &str) -> [u8; 32] {
+        let mut ret = [0; 32];
+        ret.copy_from_slice(&hex!(hex));
+        ret
+    }
+
+    // BIP352 "Simple send: two inputs", with the same transaction paying the
+    // receiver's address with label 2 as well
+    #[test]
+    fn bip352_vector() {
+        let s = Secp256k1::new();
+        let inputs = [secret_key("eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1"),
+                      secret_key("93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16")];
+        let scan = secret_key("0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c");
+        let spend = secret_key("9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3");
+        // Outpoint a1075db5...:0, the smaller of the two
+        let mut smallest = [0; OUTPOINT_SIZE];
+        smallest.copy_from_slice(&hex!("169e1e83e930853391bc6f35f605c6754cfead57cf8387639d3b4096c54f18f400000000"));
+
+        let mut input_secret = inputs[0];
+        input_secret.add_assign(&s, &Scalar::from_secret_key(&inputs[1])).unwrap();
+        let input_sum = PublicKey::from_secret_key(&s, &inputs[0])
+            .combine(&s, &PublicKey::from_secret_key(&s, &inputs[1])).unwrap();
+        let hash = input_hash(&smallest, &input_sum).unwrap();
+        assert_eq!(&hash.to_be_bytes()[..],
+                   &hex!("5bfe5321d759e01a2ac9292f0f396ff9c3d8b58d89ccb21a6922e84bb7ad0668")[..]);
+
+        let scan_pk = PublicKey::from_secret_key(&s, &scan);
+        let spend_pk = PublicKey::from_secret_key(&s, &spend);
+        assert_eq!(&scan_pk.serialize()[..],
+                   &hex!("0220bcfac5b99e04ad1a06ddfb016ee13582609d60b6291e98d01a9bc9a16c96d4")[..]);
+        assert_eq!(&spend_pk.serialize()[..],
+                   &hex!("025cc9856d6f8375350e123978daac200c260cb5b5ae83106cab90484dcd8fcf36")[..]);
+        let shared = sender_shared_secret(&s, &hash, &input_secret, &scan_pk).unwrap();
+        let output = array32("3e9fce73d4e77a4809908e3c3a2e54ee147b9312dc5044a193d1fc85de46e3c1");
+        assert_eq!(output_key(&s, &shared, &spend_pk, 0).unwrap().x_coordinate(), output);
+
+        let mut scanner = Scanner::new(scan, spend_pk);
+        let labeled = scanner.add_label(&s, 2).unwrap();
+        assert_eq!(&labeled.serialize()[..],
+                   &hex!("0259352add837b6686e8d22b87017814a46b3ad308702167c65bd5c8599cd28d1c")[..]);
+        let labeled_output = array32("f371bc2e01413c9eca6903a80be883467972b0c40b929be0a6be708cb5442d57");
+        assert_eq!(output_key(&s, &shared, &labeled, 0).unwrap().x_coordinate(), labeled_output);
+
+        let found = scanner.scan(&s, &hash, &input_sum, &[output]).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].label, None);
+        assert_eq!(&found[0].tweak.to_be_bytes()[..],
+                   &hex!("f438b40179a3c4262de12986c0e6cce0634007cdc79c1dcd3e20b9ebc2e7eef6")[..]);
+        let found = scanner.scan(&s, &hash, &input_sum, &[labeled_output]).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].label, Some(2));
+        assert_eq!(&found[0].tweak.to_be_bytes()[..],
+                   &hex!("123f957612148ee91b81938b663691cf62a8f6904fd22ab724e9be6563d057ad")[..]);
+        let sk = found[0].secret_key(&s, &spend).unwrap();
+        assert_eq!(PublicKey::from_secret_key(&s, &sk).x_coordinate(), labeled_output);
+    }
+    #[test]
+    fn send_and_scan() {
+        let s = Secp256k1::new();
+        let (scan, scan_pk) = s.generate_keypair(&mut thread_rng());
+        let (spend, spend_pk) = s.generate_keypair(&mut thread_rng());
+        let mut scanner = Scanner::new(scan, spend_pk);
+        assert_eq!(scanner.scan_public_key(&s), scan_pk);
+        let labeled = scanner.add_label(&s, 7).unwrap();
+
+        let (input, input_sum) = s.generate_keypair(&mut thread_rng());
+        let hash = input_hash(&outpoint(), &input_sum).unwrap();
+        let shared = sender_shared_secret(&s, &hash, &input, &scan_pk).unwrap();
+        // Two plain outputs and one to the label, among unrelated ones
+        let (_, unrelated) = s.generate_keypair(&mut thread_rng());
+        let outputs = [
+            unrelated.x_coordinate(),
+            output_key(&s, &shared, &spend_pk, 1).unwrap().x_coordinate(),
+            output_key(&s, &shared, &labeled, 2).unwrap().x_coordinate(),
+            output_key(&s, &shared, &spend_pk, 0).unwrap().x_coordinate(),
+        ];
+
+        let found = scanner.scan(&s, &hash, &input_sum, &outputs).unwrap();
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].output, outputs[3]);
+        assert_eq!(found[1].output, outputs[1]);
+        assert_eq!(found[2].output, outputs[2]);
+        assert_eq!(found.iter().map(|f| f.label).collect::<Vec<_>>(), vec![None, None, Some(7)]);
+        for f in &found {
+            let sk = f.secret_key(&s, &spend).unwrap();
+            assert_eq!(PublicKey::from_secret_key(&s, &sk).x_coordinate(), f.output);
+        }
+
+        // Another transaction's inputs, or a gap in k, find nothing
+        let (_, other_sum) = s.generate_keypair(&mut thread_rng());
+        let other_hash = input_hash(&outpoint(), &other_sum).unwrap();
+        assert!(scanner.scan(&s, &other_hash, &other_sum, &outputs).unwrap().is_empty());
+        assert!(scanner.scan(&s, &hash, &input_sum, &outputs[..2]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,335 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Taproot
+//! The BIP341 script tree hashes: `TapLeafHash` of a script, `TapBranchHash`
+//! of two child nodes, and `TapTweakHash` of an internal key and the tree's
+//! merkle root, which is the tweak `XOnlyPublicKey::tap_tweak` adds to the
+//! internal key to get the output key. `ControlBlock` is what a script path
+//! spend puts in the witness to prove the script is in the output's tree.
+//!
+//! A node hash is either a leaf or a branch hash, so the functions which
+//! take one take its 32 bytes. Requires compilation with the "hashes"
+//! feature.
+
+use std::fmt;
+use std::vec::Vec;
+
+use hashes::{TaggedHasher, TAPBRANCH_TAG, TAPLEAF_TAG, TAPTWEAK_TAG};
+use hex;
+use key::{Parity, XOnlyPublicKey};
+use scalar::Scalar;
+use super::Error::{self, InvalidControlBlock, InvalidTweak};
+use {Secp256k1, Verification};
+
+/// The leaf version of BIP342 tapscript
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+/// The size (in bytes) of a control block without merkle branch: the leaf
+/// version and parity byte and the internal key
+pub const CONTROL_BLOCK_BASE_SIZE: usize = 33;
+
+/// The most hashes a control block's merkle branch can have
+pub const CONTROL_BLOCK_MAX_DEPTH: usize = 128;
+
+macro_rules! impl_tap_hash {
+    ($thing:ident) => {
+        impl $thing {
+            /// Creates the hash from its 32 bytes
+            #[inline]
+            pub fn from_bytes(bytes: [u8; 32]) -> $thing {
+                $thing(bytes)
+            }
+
+            /// The hash's 32 bytes
+            #[inline]
+            pub fn to_bytes(&self) -> [u8; 32] {
+                self.0
+            }
+        }
+
+        impl AsRef<[u8]> for $thing {
+            #[inline]
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $thing {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                hex::format(&self.0, f)
+            }
+        }
+
+        impl fmt::Debug for $thing {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($thing), self)
+            }
+        }
+    };
+}
+
+/// The BIP341 hash of a leaf of the script tree
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TapLeafHash([u8; 32]);
+impl_tap_hash!(TapLeafHash);
+
+impl TapLeafHash {
+    /// The hash of the leaf with `script` and `leaf_version` (normally
+    /// `TAPSCRIPT_LEAF_VERSION`)
+    pub fn from_script(script: &[u8], leaf_version: u8) -> TapLeafHash {
+        let mut engine = TaggedHasher::new(TAPLEAF_TAG).chain(&[leaf_version & 0xfe]);
+        write_compact_size(&mut engine, script.len() as u64);
+        engine.input(script);
+        TapLeafHash(engine.result())
+    }
+}
+
+/// The BIP341 hash of an inner node of the script tree
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TapBranchHash([u8; 32]);
+impl_tap_hash!(TapBranchHash);
+
+impl TapBranchHash {
+    /// The hash of the node with the children whose (leaf or branch) hashes
+    /// are `a` and `b`, in either order
+    pub fn from_node_hashes(a: &[u8; 32], b: &[u8; 32]) -> TapBranchHash {
+        let (first, second) = if a <= b { (a, b) } else { (b, a) };
+        TapBranchHash(TaggedHasher::new(TAPBRANCH_TAG).chain(first).chain(second).result())
+    }
+}
+
+/// The BIP341 tweak of an internal key for a script tree
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TapTweakHash([u8; 32]);
+impl_tap_hash!(TapTweakHash);
+
+impl TapTweakHash {
+    /// The tweak of `internal_key` for the tree with `merkle_root`, or for
+    /// no scripts at all
+    #[inline]
+    pub fn from_key_and_tweak(internal_key: &XOnlyPublicKey, merkle_root: Option<&[u8; 32]>) -> TapTweakHash {
+        TapTweakHash::from_x_and_tweak(&internal_key.serialize(), merkle_root)
+    }
+
+    /// `from_key_and_tweak` on the key's serialization, which is not
+    /// checked to be on the curve
+    pub(crate) fn from_x_and_tweak(internal_key: &[u8; 32], merkle_root: Option<&[u8; 32]>) -> TapTweakHash {
+        let mut engine = TaggedHasher::new(TAPTWEAK_TAG).chain(internal_key);
+        if let Some(root) = merkle_root {
+            engine.input(root);
+        }
+        TapTweakHash(engine.result())
+    }
+
+    /// The tweak as a scalar. Fails with `InvalidTweak` in the negligible
+    /// case that the hash is not below the curve order.
+    #[inline]
+    pub fn to_scalar(&self) -> Result<Scalar, Error> {
+        Scalar::from_be_bytes(self.0).map_err(|_| InvalidTweak)
+    }
+}
+
+/// The merkle root of the tree which contains `leaf` with the sibling hashes
+/// `merkle_branch`, from the leaf up
+pub fn merkle_root(leaf: &TapLeafHash, merkle_branch: &[[u8; 32]]) -> [u8; 32] {
+    merkle_branch.iter().fold(leaf.0, |node, sibling| TapBranchHash::from_node_hashes(&node, sibling).0)
+}
+
+impl XOnlyPublicKey {
+    /// The BIP341 output key of this internal key for the script tree with
+    /// `merkle_root`, or for no scripts at all, and the parity its control
+    /// blocks commit to. Fails with `InvalidTweak` or `PointAtInfinity` in
+    /// the negligible case that the tweak is out of range or gives the point
+    /// at infinity.
+    pub fn tap_tweak<C: Verification>(&self, secp: &Secp256k1<C>, merkle_root: Option<&[u8; 32]>)
+                                      -> Result<(XOnlyPublicKey, Parity), Error> {
+        let tweak = TapTweakHash::from_key_and_tweak(self, merkle_root).to_scalar()?;
+        self.add_tweak(secp, &tweak)
+    }
+}
+
+/// The control block of a script path spend
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ControlBlock {
+    /// The leaf version of the script being spent
+    pub leaf_version: u8,
+    /// The y-coordinate parity of the output key
+    pub output_key_parity: Parity,
+    /// The internal key
+    pub internal_key: XOnlyPublicKey,
+    /// The sibling hashes on the path from the leaf to the root
+    pub merkle_branch: Vec<[u8; 32]>,
+}
+
+impl ControlBlock {
+    /// The control block for spending `script` with `leaf_version` from the
+    /// output of `internal_key` whose tree has the leaf's siblings
+    /// `merkle_branch`. Fails with `InvalidControlBlock` if the branch is
+    /// longer than `CONTROL_BLOCK_MAX_DEPTH` and as `tap_tweak` does.
+    pub fn new<C: Verification>(secp: &Secp256k1<C>, internal_key: XOnlyPublicKey, script: &[u8], leaf_version: u8,
+                                merkle_branch: &[[u8; 32]]) -> Result<ControlBlock, Error> {
+        if merkle_branch.len() > CONTROL_BLOCK_MAX_DEPTH {
+            return Err(InvalidControlBlock);
+        }
+        let root = merkle_root(&TapLeafHash::from_script(script, leaf_version), merkle_branch);
+        let (_, output_key_parity) = internal_key.tap_tweak(secp, Some(&root))?;
+        Ok(ControlBlock {
+            leaf_version: leaf_version & 0xfe,
+            output_key_parity,
+            internal_key,
+            merkle_branch: merkle_branch.to_vec(),
+        })
+    }
+
+    /// Parses a control block from the witness. Fails with
+    /// `InvalidControlBlock` if its length is not 33 plus a multiple of 32
+    /// up to `CONTROL_BLOCK_MAX_DEPTH` hashes, and with `InvalidPublicKey`
+    /// if the internal key is not on the curve.
+    pub fn from_slice(data: &[u8]) -> Result<ControlBlock, Error> {
+        let hashes = data.get(CONTROL_BLOCK_BASE_SIZE..).ok_or(InvalidControlBlock)?.chunks_exact(32);
+        if !hashes.remainder().is_empty() || hashes.len() > CONTROL_BLOCK_MAX_DEPTH {
+            return Err(InvalidControlBlock);
+        }
+        let merkle_branch = hashes.map(|chunk| {
+            let mut hash = [0; 32];
+            hash.copy_from_slice(chunk);
+            hash
+        }).collect();
+        Ok(ControlBlock {
+            leaf_version: data[0] & 0xfe,
+            output_key_parity: Parity::from_u8(data[0] & 1)?,
+            internal_key: XOnlyPublicKey::from_slice(&data[1..CONTROL_BLOCK_BASE_SIZE])?,
+            merkle_branch,
+        })
+    }
+
+    /// The encoding for the witness
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(CONTROL_BLOCK_BASE_SIZE + 32 * self.merkle_branch.len());
+        ret.push((self.leaf_version & 0xfe) | self.output_key_parity.to_u8());
+        ret.extend_from_slice(&self.internal_key.serialize());
+        for hash in &self.merkle_branch {
+            ret.extend_from_slice(hash);
+        }
+        ret
+    }
+
+    /// Whether this control block proves that `script` is in the tree of
+    /// `output_key`, as BIP341 script path validation checks
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, output_key: &XOnlyPublicKey, script: &[u8]) -> bool {
+        let root = merkle_root(&TapLeafHash::from_script(script, self.leaf_version), &self.merkle_branch);
+        match TapTweakHash::from_key_and_tweak(&self.internal_key, Some(&root)).to_scalar() {
+            Ok(tweak) => self.internal_key.tweak_add_check(secp, output_key, self.output_key_parity, &tweak),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Feeds `n` to `engine` as a Bitcoin CompactSize
+fn write_compact_size(engine: &mut TaggedHasher, n: u64) {
+    if n < 0xfd {
+        engine.input(&[n as u8]);
+    } else if n <= 0xffff {
+        engine.input(&[0xfd]);
+        engine.input(&(n as u16).to_le_bytes());
+    } else if n <= 0xffff_ffff {
+        engine.input(&[0xfe]);
+        engine.input(&(n as u32).to_le_bytes());
+    } else {
+        engine.input(&[0xff]);
+        engine.input(&n.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{merkle_root, ControlBlock, TapBranchHash, TapLeafHash, TapTweakHash, TAPSCRIPT_LEAF_VERSION};
+    use super::super::Error::InvalidControlBlock;
+    use key::{Parity, XOnlyPublicKey};
+    use Secp256k1;
+
+    fn xonly(s: &str) -> XOnlyPublicKey {
+        XOnlyPublicKey::from_slice(&hex!(s)).unwrap()
+    }
+
+    // The scriptPubKey vectors of BIP341's wallet-test-vectors.json
+    #[test]
+    fn bip341_vectors() {
+        let s = Secp256k1::verification_only();
+
+        // No script tree
+        let internal = xonly("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d");
+        let tweak = TapTweakHash::from_key_and_tweak(&internal, None);
+        assert_eq!(tweak.to_string(), "b86e7be8f39bab32a6f2c0443abbc210f0edac0e2c53d501b36b64437d9c6c70");
+        let (output, _) = internal.tap_tweak(&s, None).unwrap();
+        assert_eq!(output, xonly("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343"));
+
+        // One leaf
+        let internal = xonly("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let script = hex!("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac");
+        let leaf = TapLeafHash::from_script(&script, TAPSCRIPT_LEAF_VERSION);
+        assert_eq!(leaf.to_string(), "5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21");
+        let root = merkle_root(&leaf, &[]);
+        assert_eq!(root, leaf.to_bytes());
+        let tweak = TapTweakHash::from_key_and_tweak(&internal, Some(&root));
+        assert_eq!(tweak.to_string(), "cbd8679ba636c1110ea247542cfbd964131a6be84f873f7f3b62a777528ed001");
+        let (output, parity) = internal.tap_tweak(&s, Some(&root)).unwrap();
+        assert_eq!(output, xonly("147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3"));
+        let control = ControlBlock::new(&s, internal, &script, TAPSCRIPT_LEAF_VERSION, &[]).unwrap();
+        assert_eq!(control.output_key_parity, parity);
+        assert_eq!(control.serialize(), hex!("c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"));
+        assert!(control.verify(&s, &output, &script));
+        assert!(!control.verify(&s, &output, &script[1..]));
+        assert!(!control.verify(&s, &internal, &script));
+    }
+
+    #[test]
+    fn control_block() {
+        let s = Secp256k1::verification_only();
+        let internal = xonly("187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27");
+        let scripts: [&[u8]; 3] = [b"\x51", b"\x52", b"\x53"];
+        let leaves: Vec<[u8; 32]> = scripts.iter()
+            .map(|script| TapLeafHash::from_script(script, TAPSCRIPT_LEAF_VERSION).to_bytes())
+            .collect();
+        // ((1, 2), 3)
+        let inner = TapBranchHash::from_node_hashes(&leaves[0], &leaves[1]).to_bytes();
+        assert_eq!(inner, TapBranchHash::from_node_hashes(&leaves[1], &leaves[0]).to_bytes());
+        let root = TapBranchHash::from_node_hashes(&inner, &leaves[2]).to_bytes();
+        let (output, _) = internal.tap_tweak(&s, Some(&root)).unwrap();
+
+        let branches = [vec![leaves[1], leaves[2]], vec![leaves[0], leaves[2]], vec![inner]];
+        for (script, branch) in scripts.iter().zip(branches.iter()) {
+            let control = ControlBlock::new(&s, internal, script, TAPSCRIPT_LEAF_VERSION, branch).unwrap();
+            assert!(control.verify(&s, &output, script));
+            let encoded = control.serialize();
+            assert_eq!(encoded.len(), 33 + 32 * branch.len());
+            assert_eq!(ControlBlock::from_slice(&encoded), Ok(control.clone()));
+
+            let mut flipped = control.clone();
+            flipped.output_key_parity = match control.output_key_parity {
+                Parity::Even => Parity::Odd,
+                Parity::Odd => Parity::Even,
+            };
+            assert!(!flipped.verify(&s, &output, script));
+        }
+
+        assert_eq!(ControlBlock::from_slice(&[0xc0; 32]), Err(InvalidControlBlock));
+        assert_eq!(ControlBlock::from_slice(&[0xc0; 34]), Err(InvalidControlBlock));
+        assert_eq!(ControlBlock::from_slice(&vec![0xc0; 33 + 32 * 129]), Err(InvalidControlBlock));
+        assert!(ControlBlock::new(&s, internal, b"\x51", TAPSCRIPT_LEAF_VERSION, &vec![[0; 32]; 129]).is_err());
+    }
+}
@@ -0,0 +1,321 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Test vectors
+//! The known-answer vectors of `Secp256k1::self_test`, for downstream
+//! enclave crates to check their own integration against: that keys,
+//! messages and signatures cross their ECALL boundaries, wire formats and
+//! language bindings with the right byte order and encoding. The `assert_*`
+//! functions take the integration's operation on raw bytes, e.g. a wrapper
+//! around an ECALL, and panic with the mismatching bytes if it disagrees.
+//!
+//! The vectors were computed independently of libsecp256k1. The signature
+//! uses RFC6979 nonces with low-S normalization; the shared secret is the
+//! SHA-256 of the compressed shared point, as `ecdh::SharedSecret` defines.
+//! Public with the "test-vectors" feature.
+//!
+//! For auditing RFC6979 conformance, `rfc6979_nonce` returns the nonce this
+//! build derives when signing, which signing itself never reveals. It only
+//! accepts the secret keys of published vectors: those of this module and
+//! of `RFC6979_VECTORS`, widely used secp256k1 vectors whose nonces were
+//! computed independently. A real key fails with `NotATestVector`, so the
+//! feature cannot be used to extract the nonces of production signatures.
+
+#[cfg(feature = "test-vectors")]
+use std::ptr;
+
+#[cfg(feature = "test-vectors")]
+use ffi;
+#[cfg(feature = "test-vectors")]
+use Error::{self, NotATestVector};
+
+/// The secret key of the vectors
+pub const SECRET_KEY: [u8; 32] = [0xcd; 32];
+
+/// The secret key of the other party to the ECDH vector
+pub const OTHER_SECRET_KEY: [u8; 32] = [0x11; 32];
+
+/// The tweak added to and multiplied with `SECRET_KEY` and `PUBLIC_KEY`
+pub const TWEAK: [u8; 32] = [0x22; 32];
+
+/// The message hash signed by `SIGNATURE`
+pub const MESSAGE: [u8; 32] = [0x5a; 32];
+
+
+/// The public key of `SECRET_KEY`, compressed
+pub const PUBLIC_KEY: [u8; 33] = [0x02, 0xb9, 0x8a, 0x7f, 0xb8, 0xcc, 0x00, 0x70,
+                                  0x48, 0x62, 0x5b, 0x64, 0x46, 0xad, 0x49, 0xa1,
+                                  0xb3, 0xa7, 0x22, 0xdf, 0x8c, 0x1c, 0xa9, 0x75,
+                                  0xb8, 0x71, 0x60, 0x02, 0x3e, 0x14, 0xd1, 0x90,
+                                  0x97];
+
+/// The public key of `SECRET_KEY`, uncompressed
+pub const PUBLIC_KEY_UNCOMPRESSED: [u8; 65] = [0x04, 0xb9, 0x8a, 0x7f, 0xb8, 0xcc, 0x00, 0x70,
+                                               0x48, 0x62, 0x5b, 0x64, 0x46, 0xad, 0x49, 0xa1,
+                                               0xb3, 0xa7, 0x22, 0xdf, 0x8c, 0x1c, 0xa9, 0x75,
+                                               0xb8, 0x71, 0x60, 0x02, 0x3e, 0x14, 0xd1, 0x90,
+                                               0x97, 0xbf, 0x74, 0x08, 0x34, 0x54, 0xf6, 0xf6,
+                                               0x49, 0xa9, 0x99, 0x58, 0xc7, 0x25, 0x79, 0x15,
+                                               0x39, 0x2d, 0xae, 0xe2, 0x11, 0x97, 0x0d, 0xa9,
+                                               0x24, 0xa0, 0x07, 0xb4, 0x85, 0xd6, 0x77, 0x0e,
+                                               0x22];
+
+/// The compact signature of `MESSAGE` by `SECRET_KEY`
+pub const SIGNATURE: [u8; 64] = [0x7a, 0xb8, 0x29, 0xf4, 0xef, 0xdb, 0xa2, 0x92,
+                                 0x98, 0xf5, 0xae, 0xce, 0x0c, 0xb0, 0x63, 0xdd,
+                                 0x7d, 0xf8, 0x4a, 0xed, 0xcc, 0x37, 0x4a, 0x90,
+                                 0x5d, 0x80, 0xc2, 0x71, 0x0c, 0xd0, 0x86, 0xb5,
+                                 0x27, 0x4a, 0x1f, 0xb9, 0x11, 0x37, 0xe3, 0x91,
+                                 0x39, 0x59, 0xf7, 0xc2, 0x92, 0x9e, 0x9c, 0xda,
+                                 0x53, 0xbe, 0x2a, 0x76, 0x06, 0x20, 0x2f, 0xd1,
+                                 0x41, 0x50, 0x05, 0xc1, 0x9c, 0x44, 0x58, 0x25];
+
+/// The ECDH shared secret of `SECRET_KEY` and `OTHER_SECRET_KEY`
+pub const SHARED_SECRET: [u8; 32] = [0xbe, 0x22, 0xb8, 0x59, 0x04, 0xf0, 0xec, 0x20,
+                                     0x1a, 0xff, 0xcf, 0xe8, 0xa1, 0xd9, 0x69, 0xab,
+                                     0xe0, 0x48, 0xde, 0x94, 0x15, 0xd3, 0xa3, 0x3d,
+                                     0xe7, 0x00, 0xac, 0x58, 0x2f, 0xea, 0x75, 0xb8];
+
+/// `SECRET_KEY` plus `TWEAK`, which does not wrap around the curve order
+pub const SUM_SECRET_KEY: [u8; 32] = [0xef; 32];
+
+/// `PUBLIC_KEY` tweaked by adding `TWEAK` times the generator
+pub const SUM_PUBLIC_KEY: [u8; 33] = [0x03, 0xac, 0x9c, 0x93, 0xcf, 0xd6, 0x3a, 0x76,
+                                      0x32, 0x88, 0xf1, 0x7c, 0xb6, 0x3f, 0x72, 0xfe,
+                                      0x3c, 0x33, 0x60, 0xeb, 0x44, 0xb7, 0xbb, 0xff,
+                                      0x3a, 0x90, 0xd3, 0x61, 0xef, 0x69, 0x55, 0xb9,
+                                      0x78];
+
+/// `SECRET_KEY` times `TWEAK`
+pub const PRODUCT_SECRET_KEY: [u8; 32] = [0xc1, 0x8f, 0xe7, 0xa6, 0x72, 0x4d, 0x82, 0x23,
+                                          0xef, 0xe4, 0x1d, 0xb4, 0x15, 0xe3, 0x1c, 0x9a,
+                                          0xa5, 0xeb, 0x02, 0x25, 0x63, 0x8a, 0x52, 0x8f,
+                                          0xef, 0x72, 0xa1, 0xac, 0x10, 0x17, 0x5a, 0x7e];
+
+/// `PUBLIC_KEY` times `TWEAK`
+pub const PRODUCT_PUBLIC_KEY: [u8; 33] = [0x03, 0xa1, 0xf3, 0xde, 0xe9, 0x16, 0x9e, 0xdc,
+                                          0x9c, 0x5b, 0x0a, 0xac, 0xbf, 0xd5, 0xd3, 0xf2,
+                                          0xb8, 0x43, 0xf7, 0xf8, 0xab, 0x79, 0x2e, 0x73,
+                                          0x73, 0xe8, 0xbd, 0x6a, 0xc3, 0x45, 0xf2, 0x03,
+                                          0x70];
+
+/// The RFC6979 nonce of `MESSAGE` and `SECRET_KEY`, whose x coordinate is
+/// the r of `SIGNATURE`
+#[cfg(feature = "test-vectors")]
+pub const NONCE: [u8; 32] = [0xe3, 0xa1, 0x54, 0xd0, 0x5b, 0xd0, 0x10, 0xd5,
+                             0x43, 0x23, 0xe7, 0xe8, 0xf4, 0x25, 0x3e, 0xe8,
+                             0x02, 0x98, 0x99, 0x91, 0x5b, 0x35, 0x38, 0x61,
+                             0x66, 0xe4, 0xcd, 0xf0, 0xec, 0x82, 0x4c, 0x59];
+
+/// A published RFC6979 vector: the nonce derived for signing `message` with
+/// `secret_key`, without extra entropy
+#[cfg(feature = "test-vectors")]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Rfc6979Vector {
+    /// The secret key
+    pub secret_key: [u8; 32],
+    /// The message hash
+    pub message: [u8; 32],
+    /// The nonce
+    pub nonce: [u8; 32],
+}
+
+/// Published secp256k1 RFC6979 vectors, with keys 1, n - 1 and an arbitrary
+/// one, over SHA-256 message hashes
+#[cfg(feature = "test-vectors")]
+pub const RFC6979_VECTORS: [Rfc6979Vector; 4] = [
+    // SHA-256 of "Satoshi Nakamoto"
+    Rfc6979Vector {
+        secret_key: [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+        message: [0xa0, 0xdc, 0x65, 0xff, 0xca, 0x79, 0x98, 0x73,
+                  0xcb, 0xea, 0x0a, 0xc2, 0x74, 0x01, 0x5b, 0x95,
+                  0x26, 0x50, 0x5d, 0xaa, 0xae, 0xd3, 0x85, 0x15,
+                  0x54, 0x25, 0xf7, 0x33, 0x77, 0x04, 0x88, 0x3e],
+        nonce: [0x8f, 0x8a, 0x27, 0x6c, 0x19, 0xf4, 0x14, 0x96,
+                0x56, 0xb2, 0x80, 0x62, 0x1e, 0x35, 0x8c, 0xce,
+                0x24, 0xf5, 0xf5, 0x25, 0x42, 0x77, 0x26, 0x91,
+                0xee, 0x69, 0x06, 0x3b, 0x74, 0xf1, 0x5d, 0x15],
+    },
+    // SHA-256 of "All those moments will be lost in time, like tears in rain. Time to die..."
+    Rfc6979Vector {
+        secret_key: [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01],
+        message: [0x7d, 0x18, 0x33, 0xf5, 0x48, 0x54, 0xac, 0x51,
+                  0x65, 0x95, 0x21, 0xaf, 0xcd, 0x0e, 0xc6, 0xdc,
+                  0xa2, 0xce, 0x23, 0x51, 0x42, 0x96, 0x14, 0xbf,
+                  0xa2, 0x8a, 0x75, 0x6b, 0x1b, 0x3c, 0x63, 0x7f],
+        nonce: [0x38, 0xaa, 0x22, 0xd7, 0x23, 0x76, 0xb4, 0xdb,
+                0xc4, 0x72, 0xe0, 0x6c, 0x3b, 0xa4, 0x03, 0xee,
+                0x0a, 0x39, 0x4d, 0xa6, 0x3f, 0xc5, 0x8d, 0x88,
+                0x68, 0x6c, 0x61, 0x1a, 0xba, 0x98, 0xd6, 0xb3],
+    },
+    // SHA-256 of "Satoshi Nakamoto"
+    Rfc6979Vector {
+        secret_key: [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+                     0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b,
+                     0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x40],
+        message: [0xa0, 0xdc, 0x65, 0xff, 0xca, 0x79, 0x98, 0x73,
+                  0xcb, 0xea, 0x0a, 0xc2, 0x74, 0x01, 0x5b, 0x95,
+                  0x26, 0x50, 0x5d, 0xaa, 0xae, 0xd3, 0x85, 0x15,
+                  0x54, 0x25, 0xf7, 0x33, 0x77, 0x04, 0x88, 0x3e],
+        nonce: [0x33, 0xa1, 0x9b, 0x60, 0xe2, 0x5f, 0xb6, 0xf4,
+                0x43, 0x5a, 0xf5, 0x3a, 0x3d, 0x42, 0xd4, 0x93,
+                0x64, 0x48, 0x27, 0x36, 0x7e, 0x64, 0x53, 0x92,
+                0x85, 0x54, 0xf4, 0x3e, 0x49, 0xaa, 0x6f, 0x90],
+    },
+    // SHA-256 of "Alan Turing"
+    Rfc6979Vector {
+        secret_key: [0xf8, 0xb8, 0xaf, 0x8c, 0xe3, 0xc7, 0xcc, 0xa5,
+                     0xe3, 0x00, 0xd3, 0x39, 0x39, 0x54, 0x0c, 0x10,
+                     0xd4, 0x5c, 0xe0, 0x01, 0xb8, 0xf2, 0x52, 0xbf,
+                     0xbc, 0x57, 0xba, 0x03, 0x42, 0x90, 0x41, 0x81],
+        message: [0x4b, 0xa3, 0x8d, 0x48, 0xa6, 0x0f, 0x1b, 0x29,
+                  0xe9, 0xeb, 0x72, 0x6e, 0xaf, 0xf0, 0x8b, 0x2e,
+                  0x83, 0xd8, 0xd8, 0x1e, 0x03, 0x16, 0x66, 0xfe,
+                  0xe5, 0x0e, 0x85, 0x90, 0x0d, 0x7d, 0xc1, 0xef],
+        nonce: [0x52, 0x5a, 0x82, 0xb7, 0x0e, 0x67, 0x87, 0x43,
+                0x98, 0x06, 0x75, 0x43, 0xfd, 0x84, 0xc8, 0x3d,
+                0x30, 0xc1, 0x75, 0xfd, 0xc4, 0x5f, 0xde, 0xee,
+                0x08, 0x2f, 0xe1, 0x3b, 0x1d, 0x7c, 0xfd, 0xf1],
+    },
+];
+
+/// The RFC6979 nonce this build derives for signing `message` with
+/// `secret_key`, without extra entropy. Fails with `NotATestVector` unless
+/// `secret_key` is `SECRET_KEY`, `OTHER_SECRET_KEY` or the key of one of
+/// `RFC6979_VECTORS`; any message is accepted.
+#[cfg(feature = "test-vectors")]
+pub fn rfc6979_nonce(secret_key: &[u8; 32], message: &[u8; 32]) -> Result<[u8; 32], Error> {
+    // Compare against every published key, so that a rejected key's timing
+    // doesn't depend on it
+    let mut published = ::constant_time_eq(secret_key, &SECRET_KEY) | ::constant_time_eq(secret_key, &OTHER_SECRET_KEY);
+    for vector in &RFC6979_VECTORS {
+        published |= ::constant_time_eq(secret_key, &vector.secret_key);
+    }
+    if !published {
+        return Err(NotATestVector);
+    }
+
+    let mut nonce = [0u8; 32];
+    unsafe {
+        let err = ffi::secp256k1_nonce_function_rfc6979(nonce.as_mut_ptr(), message.as_ptr(), secret_key.as_ptr(),
+                                                        ptr::null(), ptr::null_mut(), 0);
+        // The RFC6979 nonce function always succeeds
+        assert!(err == 1);
+    }
+    Ok(nonce)
+}
+
+/// Checks that `derive` computes the public key of `SECRET_KEY`, in either
+/// format
+#[cfg(feature = "test-vectors")]
+pub fn assert_public_key<T: AsRef<[u8]>, F: FnOnce(&[u8; 32]) -> T>(derive: F) {
+    let pk = derive(&SECRET_KEY);
+    if pk.as_ref().len() == PUBLIC_KEY_UNCOMPRESSED.len() {
+        assert_eq!(pk.as_ref(), &PUBLIC_KEY_UNCOMPRESSED[..], "public key mismatch");
+    } else {
+        assert_eq!(pk.as_ref(), &PUBLIC_KEY[..], "public key mismatch");
+    }
+}
+
+/// Checks that `sign` signs `MESSAGE` with `SECRET_KEY` into the compact
+/// `SIGNATURE`
+#[cfg(feature = "test-vectors")]
+pub fn assert_signature<T: AsRef<[u8]>, F: FnOnce(&[u8; 32], &[u8; 32]) -> T>(sign: F) {
+    assert_eq!(sign(&MESSAGE, &SECRET_KEY).as_ref(), &SIGNATURE[..], "signature mismatch");
+}
+
+/// Checks that `verify`, given a message, compact signature and compressed
+/// public key, accepts `SIGNATURE` and rejects it altered or for another
+/// message
+#[cfg(feature = "test-vectors")]
+pub fn assert_verification<F: FnMut(&[u8; 32], &[u8; 64], &[u8; 33]) -> bool>(mut verify: F) {
+    assert!(verify(&MESSAGE, &SIGNATURE, &PUBLIC_KEY), "valid signature rejected");
+    let mut tampered = SIGNATURE;
+    tampered[63] ^= 1;
+    assert!(!verify(&MESSAGE, &tampered, &PUBLIC_KEY), "altered signature accepted");
+    let mut other_msg = MESSAGE;
+    other_msg[0] ^= 1;
+    assert!(!verify(&other_msg, &SIGNATURE, &PUBLIC_KEY), "signature accepted for another message");
+}
+
+/// Checks that `ecdh`, given a compressed public key and a secret key,
+/// computes `SHARED_SECRET` from `PUBLIC_KEY` and `OTHER_SECRET_KEY`
+#[cfg(feature = "test-vectors")]
+pub fn assert_shared_secret<T: AsRef<[u8]>, F: FnOnce(&[u8; 33], &[u8; 32]) -> T>(ecdh: F) {
+    assert_eq!(ecdh(&PUBLIC_KEY, &OTHER_SECRET_KEY).as_ref(), &SHARED_SECRET[..], "shared secret mismatch");
+}
+
+#[cfg(all(test, feature = "test-vectors"))]
+mod tests {
+    use super::{assert_public_key, assert_shared_secret, assert_signature, assert_verification};
+    use super::{rfc6979_nonce, MESSAGE, NONCE, OTHER_SECRET_KEY, RFC6979_VECTORS, SECRET_KEY, SIGNATURE};
+    use ecdh::SharedSecret;
+    use key::{PublicKey, SecretKey};
+    use {Error, Message, Secp256k1, Signature};
+
+    #[test]
+    fn own_integration() {
+        let s = Secp256k1::new();
+        let sk = |bytes: &[u8; 32]| SecretKey::from_slice(bytes).unwrap();
+        assert_public_key(|bytes| PublicKey::from_secret_key(&s, &sk(bytes)).serialize());
+        assert_public_key(|bytes| PublicKey::from_secret_key(&s, &sk(bytes)).serialize_uncompressed().to_vec());
+        assert_signature(|msg, bytes| s.sign(&Message::from_slice(msg).unwrap(), &sk(bytes)).serialize_compact(&s));
+        assert_verification(|msg, sig, pk| {
+            let sig = Signature::from_compact(&s, sig).unwrap();
+            s.verify(&Message::from_slice(msg).unwrap(), &sig, &PublicKey::from_slice(pk).unwrap()).is_ok()
+        });
+        assert_shared_secret(|pk, bytes| {
+            SharedSecret::new(&s, &PublicKey::from_slice(pk).unwrap(), &sk(bytes))[..].to_vec()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "signature mismatch")]
+    fn wrong_byte_order() {
+        // As if an integration encoded r and s little-endian
+        let s = Secp256k1::new();
+        assert_signature(|msg, bytes| {
+            let mut sig = s.sign(&Message::from_slice(msg).unwrap(), &SecretKey::from_slice(bytes).unwrap())
+                           .serialize_compact(&s);
+            sig[..32].reverse();
+            sig[32..].reverse();
+            sig
+        });
+    }
+
+    #[test]
+    fn rfc6979_nonces() {
+        for vector in &RFC6979_VECTORS {
+            assert_eq!(rfc6979_nonce(&vector.secret_key, &vector.message), Ok(vector.nonce));
+        }
+        assert_eq!(rfc6979_nonce(&SECRET_KEY, &MESSAGE), Ok(NONCE));
+        assert!(rfc6979_nonce(&OTHER_SECRET_KEY, &MESSAGE).is_ok());
+
+        // The nonce is the one signing used
+        let s = Secp256k1::new();
+        let r = PublicKey::from_secret_key(&s, &SecretKey::from_slice(&NONCE).unwrap()).x_coordinate();
+        assert_eq!(r[..], SIGNATURE[..32]);
+
+        let mut key = SECRET_KEY;
+        key[31] ^= 1;
+        assert_eq!(rfc6979_nonce(&key, &MESSAGE), Err(Error::NotATestVector));
+    }
+}
@@ -0,0 +1,331 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Transcripts
+//! `Transcript`, a Fiat-Shamir transcript for interactive protocols made
+//! non-interactive. Each party appends the same labelled messages (public
+//! keys, nonce points, enclave identities, anything else the protocol
+//! commits to) and draws challenges from it; a challenge depends on every
+//! message before it, their labels and their order.
+//!
+//! The construction is Merlin's: STROBE-128 over Keccak-f[1600], with the
+//! same framing of labels and lengths, so a transcript here gives the same
+//! challenges as `merlin::Transcript` for the same calls and a proof can be
+//! checked by a verifier outside the enclave which uses the merlin crate.
+//! Only the operations Merlin's transcripts need (meta-AD, AD and PRF) are
+//! implemented, on the crate's own Keccak.
+//!
+//! The crate's existing proofs (ring signatures, range proofs, whitelist
+//! and blinding proofs) keep their tagged-hash challenges, which are part
+//! of their formats; transcripts are for protocols built on top of the
+//! crate. Requires compilation with the "transcript" feature.
+
+use std::fmt;
+
+use attestation::EnclaveIdentity;
+use cleanup::wipe;
+use hashes::keccak_f;
+use key::{PublicKey, XOnlyPublicKey};
+use scalar::Scalar;
+
+/// The STROBE rate for the 128-bit security level
+const STROBE_R: u8 = 166;
+
+const FLAG_I: u8 = 1;
+const FLAG_A: u8 = 1 << 1;
+const FLAG_C: u8 = 1 << 2;
+const FLAG_M: u8 = 1 << 4;
+
+/// The protocol label of every Merlin transcript
+const MERLIN_PROTOCOL_LABEL: &'static [u8] = b"Merlin v1.0";
+
+/// The part of STROBE-128 which Merlin uses
+#[derive(Clone)]
+struct Strobe128 {
+    state: [u8; 200],
+    pos: u8,
+    pos_begin: u8,
+    cur_flags: u8,
+}
+
+impl Strobe128 {
+    fn new(protocol_label: &[u8]) -> Strobe128 {
+        let mut state = [0u8; 200];
+        state[..6].copy_from_slice(&[1, STROBE_R + 2, 1, 0, 1, 96]);
+        state[6..18].copy_from_slice(b"STROBEv1.0.2");
+        permute(&mut state);
+
+        let mut strobe = Strobe128 {
+            state,
+            pos: 0,
+            pos_begin: 0,
+            cur_flags: 0,
+        };
+        strobe.meta_ad(protocol_label, false);
+        strobe
+    }
+
+    fn meta_ad(&mut self, data: &[u8], more: bool) {
+        self.begin_op(FLAG_M | FLAG_A, more);
+        self.absorb(data);
+    }
+
+    fn ad(&mut self, data: &[u8], more: bool) {
+        self.begin_op(FLAG_A, more);
+        self.absorb(data);
+    }
+
+    fn prf(&mut self, data: &mut [u8], more: bool) {
+        self.begin_op(FLAG_I | FLAG_A | FLAG_C, more);
+        self.squeeze(data);
+    }
+
+    fn run_f(&mut self) {
+        self.state[self.pos as usize] ^= self.pos_begin;
+        self.state[self.pos as usize + 1] ^= 0x04;
+        self.state[STROBE_R as usize + 1] ^= 0x80;
+        permute(&mut self.state);
+        self.pos = 0;
+        self.pos_begin = 0;
+    }
+
+    fn absorb(&mut self, data: &[u8]) {
+        for byte in data {
+            self.state[self.pos as usize] ^= *byte;
+            self.pos += 1;
+            if self.pos == STROBE_R {
+                self.run_f();
+            }
+        }
+    }
+
+    fn squeeze(&mut self, data: &mut [u8]) {
+        for byte in data {
+            *byte = self.state[self.pos as usize];
+            self.state[self.pos as usize] = 0;
+            self.pos += 1;
+            if self.pos == STROBE_R {
+                self.run_f();
+            }
+        }
+    }
+
+    fn begin_op(&mut self, flags: u8, more: bool) {
+        if more {
+            debug_assert_eq!(self.cur_flags, flags, "continued a STROBE operation with other flags");
+            return;
+        }
+        let old_begin = self.pos_begin;
+        self.pos_begin = self.pos + 1;
+        self.cur_flags = flags;
+        self.absorb(&[old_begin, flags]);
+
+        // Cipher operations start on a fresh block
+        if flags & FLAG_C != 0 && self.pos != 0 {
+            self.run_f();
+        }
+    }
+}
+
+impl Drop for Strobe128 {
+    fn drop(&mut self) {
+        wipe(&mut self.state);
+    }
+}
+
+/// Keccak-f[1600] over the state as 200 little-endian bytes
+fn permute(state: &mut [u8; 200]) {
+    let mut lanes = [0u64; 25];
+    for (lane, bytes) in lanes.iter_mut().zip(state.chunks(8)) {
+        let mut word = [0; 8];
+        word.copy_from_slice(bytes);
+        *lane = u64::from_le_bytes(word);
+    }
+    keccak_f(&mut lanes);
+    for (bytes, lane) in state.chunks_mut(8).zip(lanes.iter_mut()) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+        *lane = 0;
+    }
+}
+
+/// A Merlin-compatible protocol transcript. Labels are `'static` since
+/// they belong to the protocol, not to the data.
+///
+/// Cloning forks the transcript, e.g. to try several continuations; a
+/// transcript may hold secrets a prover appended, so its state is wiped on
+/// drop and left out of `Debug`.
+#[derive(Clone)]
+pub struct Transcript {
+    strobe: Strobe128,
+}
+
+impl Transcript {
+    /// Starts a transcript for the protocol named by `label`, which should
+    /// be unique to the protocol and its version
+    pub fn new(label: &'static [u8]) -> Transcript {
+        let mut transcript = Transcript {
+            strobe: Strobe128::new(MERLIN_PROTOCOL_LABEL),
+        };
+        transcript.append_message(b"dom-sep", label);
+        transcript
+    }
+
+    /// Appends `message` under `label`. Messages longer than `u32::MAX`
+    /// bytes are not supported, as in Merlin.
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        assert!(message.len() <= u32::MAX as usize, "transcript message too long");
+        self.strobe.meta_ad(label, false);
+        self.strobe.meta_ad(&(message.len() as u32).to_le_bytes(), true);
+        self.strobe.ad(message, false);
+    }
+
+    /// Appends `x` as 8 little-endian bytes, as Merlin does
+    pub fn append_u64(&mut self, label: &'static [u8], x: u64) {
+        self.append_message(label, &x.to_le_bytes());
+    }
+
+    /// Appends the compressed serialization of `pk`
+    pub fn append_point(&mut self, label: &'static [u8], pk: &PublicKey) {
+        self.append_message(label, &pk.serialize());
+    }
+
+    /// Appends the 32 bytes of `pk`
+    pub fn append_xonly(&mut self, label: &'static [u8], pk: &XOnlyPublicKey) {
+        self.append_message(label, &pk.serialize());
+    }
+
+    /// Appends the 32 big-endian bytes of `s`
+    pub fn append_scalar(&mut self, label: &'static [u8], s: &Scalar) {
+        self.append_message(label, &s.to_be_bytes());
+    }
+
+    /// Appends the identity of an attested enclave, as returned by
+    /// `attestation::verify_key_binding`, so that the challenges (and so
+    /// the proof) are only valid for that enclave: its measurement, signer,
+    /// product ID, security versions and attributes, each under its own
+    /// label. The TCB levels are left out, since a TCB recovery changes
+    /// them without changing the enclave.
+    pub fn append_enclave_identity(&mut self, id: &EnclaveIdentity) {
+        self.append_message(b"mr_enclave", &id.mr_enclave);
+        self.append_message(b"mr_signer", &id.mr_signer);
+        self.append_u64(b"isv_prod_id", id.isv_prod_id as u64);
+        self.append_u64(b"isv_svn", id.isv_svn as u64);
+        self.append_u64(b"config_svn", id.config_svn as u64);
+        self.append_u64(b"flags", id.flags);
+        self.append_u64(b"xfrm", id.xfrm);
+        self.append_u64(b"misc_select", id.misc_select as u64);
+    }
+
+    /// Fills `dest` with challenge bytes under `label`
+    pub fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        assert!(dest.len() <= u32::MAX as usize, "transcript challenge too long");
+        self.strobe.meta_ad(label, false);
+        self.strobe.meta_ad(&(dest.len() as u32).to_le_bytes(), true);
+        self.strobe.prf(dest, false);
+    }
+
+    /// A challenge scalar under `label`: 64 challenge bytes reduced modulo
+    /// the curve order, so the bias is negligible. It can be zero only with
+    /// negligible probability, which protocols may still want to reject.
+    pub fn challenge_scalar(&mut self, label: &'static [u8]) -> Scalar {
+        let mut wide = [0u8; 64];
+        self.challenge_bytes(label, &mut wide);
+        let ret = Scalar::from_be_bytes_mod_order_wide(&wide);
+        wipe(&mut wide);
+        ret
+    }
+}
+
+impl fmt::Debug for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Transcript(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transcript;
+    use attestation::{EnclaveIdentity, TcbLevel};
+    use {PublicKey, Secp256k1, SecretKey};
+
+    // Challenges from merlin 3.0.0 for the same calls
+    #[test]
+    fn merlin_compatible() {
+        let mut t = Transcript::new(b"test protocol");
+        t.append_message(b"some label", b"some data");
+        let mut challenge = [0u8; 32];
+        t.challenge_bytes(b"challenge", &mut challenge);
+        assert_eq!(challenge[..], hex!("d5a21972d0d5fe320c0d263fac7fffb8145aa640af6e9bca177c03c7efcf0615")[..]);
+
+        // Crosses many STROBE blocks in both directions
+        let mut t = Transcript::new(b"test protocol");
+        t.append_message(b"step1", b"some data");
+        let data = [99u8; 1024];
+        for _ in 0..32 {
+            t.challenge_bytes(b"challenge", &mut challenge);
+            t.append_message(b"bigdata", &data);
+            t.append_message(b"challengedata", &challenge);
+        }
+        assert_eq!(challenge[..], hex!("a8c933f54fae76e3f9bea93648c1308e7dfa2152dd51674ff3ca438351cf003c")[..]);
+
+        let mut t = Transcript::new(b"test protocol");
+        t.append_u64(b"n", 0x0102030405060708);
+        let mut long = [0u8; 300];
+        t.challenge_bytes(b"long", &mut long);
+        assert_eq!(long[..32], hex!("1970bb29bc6d91d49b4b014bf7397b8f8618da900e14c0020a66994095029f99")[..]);
+        assert_eq!(long[280..], hex!("bbc8b9db2968e7f0db66ee9be5693d02138e7e2a")[..]);
+    }
+
+    #[test]
+    fn binds_everything() {
+        let s = Secp256k1::new();
+        let pk = PublicKey::from_secret_key(&s, &SecretKey::from_slice(&[1; 32]).unwrap());
+        let id = EnclaveIdentity {
+            version: 3,
+            mr_enclave: [1; 32],
+            mr_signer: [2; 32],
+            isv_prod_id: 1,
+            isv_svn: 2,
+            config_svn: 0,
+            flags: 0x4,
+            xfrm: 0x3,
+            misc_select: 0,
+            tcb: TcbLevel { cpu_svn: [0; 16], qe_svn: 1, pce_svn: 1 },
+        };
+        let challenge = |pk: &PublicKey, id: &EnclaveIdentity, label: &'static [u8]| {
+            let mut t = Transcript::new(b"binds everything");
+            t.append_enclave_identity(id);
+            t.append_point(b"pk", pk);
+            t.challenge_scalar(label)
+        };
+
+        let c = challenge(&pk, &id, b"c");
+        assert_eq!(c, challenge(&pk, &id, b"c"));
+        assert!(c != challenge(&pk, &id, b"d"));
+        let other_pk = PublicKey::from_secret_key(&s, &SecretKey::from_slice(&[2; 32]).unwrap());
+        assert!(c != challenge(&other_pk, &id, b"c"));
+        assert!(c != challenge(&pk, &EnclaveIdentity { isv_svn: 3, ..id }, b"c"));
+        // A TCB recovery keeps the challenge
+        let recovered = EnclaveIdentity { tcb: TcbLevel { cpu_svn: [1; 16], ..id.tcb }, ..id };
+        assert_eq!(c, challenge(&pk, &recovered, b"c"));
+
+        // A fork draws the same challenges as the original
+        let mut t = Transcript::new(b"fork");
+        t.append_xonly(b"x", &pk.x_only_public_key().0);
+        let mut fork = t.clone();
+        assert_eq!(t.challenge_scalar(b"c"), fork.challenge_scalar(b"c"));
+        assert_eq!(t.challenge_scalar(b"c"), fork.challenge_scalar(b"c"));
+        assert_eq!(format!("{:?}", t), "Transcript(..)");
+    }
+}
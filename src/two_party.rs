@@ -0,0 +1,336 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Two-party ECDSA
+//! Building blocks for Lindell-style two-party ECDSA, where the secret key
+//! is split multiplicatively between two parties, e.g. an enclave and a
+//! client device, and neither can sign alone. The result is an ordinary
+//! ECDSA signature under the joint key.
+//!
+//! Party 1 holds `x1`, party 2 holds `x2`, and after exchanging
+//! `Q1 = x1 * G` and `Q2 = x2 * G` both compute the joint key
+//! `Q = x1 * x2 * G` with `joint_public_key`. A signature on `m` then takes
+//! three messages:
+//!
+//! 1. party 1 picks a nonce `k1` and sends a commitment to `R1 = k1 * G`;
+//! 2. party 2 picks a nonce `k2` and sends `R2 = k2 * G`, to which party 1
+//!    answers by opening its commitment;
+//! 3. with `R = k1 * k2 * G` and `r` its x-coordinate, party 2 contributes
+//!    `s' = k2^-1 (m + r x2 x1)`, and party 1 finishes with
+//!    `s = k1^-1 s'`, checking the signature before it is released.
+//!
+//! Party 2 only knows `x1` as `Q1`, so it can't compute `s'` directly:
+//! `Party2Session::partial_terms` gives it as `a + b x1`, and the parties
+//! evaluate that under an additively homomorphic encryption of `x1` held
+//! by party 2 (Paillier in Lindell's protocol) or another
+//! multiplicative-to-additive conversion. That part, with its proofs, is
+//! out of scope here. `a` and `b` together reveal `k2` and `x2`, so they
+//! must never be sent as they are. Requires compilation with the "hashes"
+//! feature.
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use commitment::NonceCommitment;
+use constants;
+use cosign::Round;
+use key::{PublicKey, SecretKey};
+use scalar::Scalar;
+use super::Error::{self, CoSigningOutOfOrder, InvalidPartialSignature};
+use {Message, Secp256k1, Signature, Verification};
+#[cfg(any(test, feature = "rand"))]
+use Signing;
+
+/// The context of party 1's nonce commitment
+const COMMITMENT_CONTEXT: &[u8] = b"two-party ecdsa";
+
+/// The joint public key `x1 * x2 * G`, from this party's secret share and
+/// the other party's public share
+pub fn joint_public_key<C: Verification>(secp: &Secp256k1<C>, share: &SecretKey, peer_share: &PublicKey)
+                                         -> Result<PublicKey, Error> {
+    let mut ret = *peer_share;
+    ret.mul_assign(secp, &Scalar::from_secret_key(share))?;
+    Ok(ret)
+}
+
+/// The combined nonce point `k * other`
+fn combined_nonce<C: Verification>(secp: &Secp256k1<C>, nonce: &SecretKey, other: &PublicKey)
+                                   -> Result<PublicKey, Error> {
+    let mut ret = *other;
+    ret.mul_assign(secp, &Scalar::from_secret_key(nonce))?;
+    Ok(ret)
+}
+
+/// Party 2's contribution `s' = a + b x1`, as its two terms
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PartialTerms {
+    /// `k2^-1 m`
+    pub a: Scalar,
+    /// `k2^-1 r x2`
+    pub b: Scalar,
+}
+
+impl PartialTerms {
+    /// Evaluates the terms with party 1's share in the clear. Only useful
+    /// where both shares are in one place, such as tests.
+    pub fn evaluate(&self, x1: &SecretKey) -> Scalar {
+        self.a.add(&self.b.mul(&Scalar::from_secret_key(x1)))
+    }
+}
+
+/// Party 1's side of a signing session: commits to its nonce, opens the
+/// commitment and completes the signature. The nonce is wiped once used
+/// and on drop.
+pub struct Party1Session {
+    joint_key: PublicKey,
+    msg: Message,
+    nonce: Option<SecretKey>,
+    nonce_point: PublicKey,
+    peer_nonce: Option<PublicKey>,
+}
+
+impl Drop for Party1Session {
+    fn drop(&mut self) {
+        if let Some(ref mut nonce) = self.nonce {
+            nonce.wipe();
+        }
+    }
+}
+
+impl Party1Session {
+    /// Starts a session for signing `msg` under `joint_key`, picking a
+    /// fresh nonce
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new<C: Signing, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R, joint_key: PublicKey,
+                                                   msg: Message) -> Party1Session {
+        let nonce = SecretKey::new(secp, rng);
+        Party1Session {
+            joint_key,
+            msg,
+            nonce: Some(nonce),
+            nonce_point: PublicKey::from_secret_key(secp, &nonce),
+            peer_nonce: None,
+        }
+    }
+
+    /// What the session is waiting for: party 2's nonce point (`Commit`),
+    /// its contribution (`Sign`), or nothing (`Done`)
+    pub fn round(&self) -> Round {
+        if self.nonce.is_none() {
+            Round::Done
+        } else if self.peer_nonce.is_none() {
+            Round::Commit
+        } else {
+            Round::Sign
+        }
+    }
+
+    /// The commitment to send in the first message
+    pub fn commitment(&self) -> NonceCommitment {
+        NonceCommitment::commit_with_context(&self.nonce_point, COMMITMENT_CONTEXT)
+    }
+
+    /// Takes party 2's nonce point and returns this party's, opening the
+    /// commitment. Revealing again for the same point returns the same
+    /// answer. Fails with `CoSigningOutOfOrder` after signing or for a
+    /// different point than before.
+    pub fn reveal(&mut self, peer_nonce: PublicKey) -> Result<PublicKey, Error> {
+        match self.round() {
+            Round::Commit => {
+                self.peer_nonce = Some(peer_nonce);
+                Ok(self.nonce_point)
+            }
+            Round::Sign if self.peer_nonce == Some(peer_nonce) => Ok(self.nonce_point),
+            _ => Err(CoSigningOutOfOrder),
+        }
+    }
+
+    /// Completes the signature from party 2's contribution `s'`. The
+    /// signature is checked against the joint key and normalized to low S.
+    /// The nonce is then wiped, so a session signs at most once. Fails with
+    /// `CoSigningOutOfOrder` before `reveal` or after signing, and with
+    /// `InvalidPartialSignature` if the result does not verify, in which
+    /// case the session can't be retried.
+    pub fn complete<C: Verification>(&mut self, secp: &Secp256k1<C>, partial: &Scalar) -> Result<Signature, Error> {
+        if self.round() != Round::Sign {
+            return Err(CoSigningOutOfOrder);
+        }
+        let mut nonce = self.nonce.take().expect("round is Sign");
+        let r = combined_nonce(secp, &nonce, &self.peer_nonce.expect("round is Sign")).map(|point| Scalar::from_be_bytes_mod_order(&point.x_coordinate()));
+        nonce.inv_assign(secp);
+        let mut k_inv = Scalar::from_secret_key(&nonce);
+        let s = k_inv.mul(partial);
+        nonce.wipe();
+        k_inv.wipe();
+        let r = r?;
+
+        let mut compact = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+        compact[..32].copy_from_slice(&r[..]);
+        compact[32..].copy_from_slice(&s[..]);
+        let mut sig = Signature::from_compact_bytes(&compact).map_err(|_| InvalidPartialSignature)?;
+        sig.normalize_s(secp);
+        secp.verify(&self.msg, &sig, &self.joint_key).map_err(|_| InvalidPartialSignature)?;
+        Ok(sig)
+    }
+}
+
+/// Party 2's side of a signing session: answers party 1's commitment with
+/// its nonce point and, once the commitment is opened, produces the terms
+/// of its contribution. The nonce is wiped once used and on drop.
+pub struct Party2Session {
+    msg: Message,
+    commitment: NonceCommitment,
+    nonce: Option<SecretKey>,
+    nonce_point: PublicKey,
+}
+
+impl Drop for Party2Session {
+    fn drop(&mut self) {
+        if let Some(ref mut nonce) = self.nonce {
+            nonce.wipe();
+        }
+    }
+}
+
+impl Party2Session {
+    /// Starts a session for signing `msg` on receiving party 1's
+    /// `commitment`, picking a fresh nonce
+    #[cfg(any(test, feature = "rand"))]
+    pub fn new<C: Signing, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R, msg: Message,
+                                                   commitment: NonceCommitment) -> Party2Session {
+        let nonce = SecretKey::new(secp, rng);
+        Party2Session {
+            msg,
+            commitment,
+            nonce: Some(nonce),
+            nonce_point: PublicKey::from_secret_key(secp, &nonce),
+        }
+    }
+
+    /// What the session is waiting for: party 1's nonce point (`Reveal`) or
+    /// nothing (`Done`)
+    pub fn round(&self) -> Round {
+        if self.nonce.is_some() { Round::Reveal } else { Round::Done }
+    }
+
+    /// The nonce point to send in answer to the commitment
+    #[inline]
+    pub fn nonce_point(&self) -> PublicKey {
+        self.nonce_point
+    }
+
+    /// Takes party 1's nonce point, checks it against its commitment and
+    /// returns the terms of this party's contribution with `share`. The
+    /// nonce is then wiped, so a session contributes at most once. Fails
+    /// with `CoSigningOutOfOrder` if it already has, and with
+    /// `NonceCommitmentMismatch` if the nonce point does not match the
+    /// commitment.
+    pub fn partial_terms<C: Verification>(&mut self, secp: &Secp256k1<C>, share: &SecretKey,
+                                          peer_nonce: &PublicKey) -> Result<PartialTerms, Error> {
+        if self.round() != Round::Reveal {
+            return Err(CoSigningOutOfOrder);
+        }
+        self.commitment.verify_with_context(peer_nonce, COMMITMENT_CONTEXT)?;
+        let mut nonce = self.nonce.take().expect("round is Reveal");
+        let r = combined_nonce(secp, &nonce, peer_nonce).map(|point| Scalar::from_be_bytes_mod_order(&point.x_coordinate()));
+        nonce.inv_assign(secp);
+        let mut k_inv = Scalar::from_secret_key(&nonce);
+        nonce.wipe();
+        let terms = r.map(|r| {
+            let mut x = Scalar::from_secret_key(share);
+            let terms = PartialTerms {
+                a: k_inv.mul(&Scalar::from_be_bytes_mod_order(&self.msg.0)),
+                b: k_inv.mul(&r).mul(&x),
+            };
+            x.wipe();
+            terms
+        });
+        k_inv.wipe();
+        terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::RngCore;
+
+    use cosign::Round;
+    use rng::thread_rng;
+    use scalar::Scalar;
+    use super::{joint_public_key, Party1Session, Party2Session};
+    use super::super::Error::{CoSigningOutOfOrder, InvalidPartialSignature, NonceCommitmentMismatch};
+    use {Message, Secp256k1};
+
+    #[test]
+    fn sign() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let (x1, q1) = s.generate_keypair(&mut rng);
+            let (x2, q2) = s.generate_keypair(&mut rng);
+            let joint_key = joint_public_key(&s, &x1, &q2).unwrap();
+            assert_eq!(joint_public_key(&s, &x2, &q1).unwrap(), joint_key);
+            let mut msg = [0u8; 32];
+            rng.fill_bytes(&mut msg);
+            let msg = Message::from(msg);
+
+            let mut p1 = Party1Session::new(&s, &mut rng, joint_key, msg);
+            assert_eq!(p1.round(), Round::Commit);
+            let mut p2 = Party2Session::new(&s, &mut rng, msg, p1.commitment());
+            assert_eq!(p2.round(), Round::Reveal);
+            let r1 = p1.reveal(p2.nonce_point()).unwrap();
+            assert_eq!(p1.reveal(p2.nonce_point()), Ok(r1));
+            assert_eq!(p1.round(), Round::Sign);
+            let terms = p2.partial_terms(&s, &x2, &r1).unwrap();
+            assert_eq!(p2.round(), Round::Done);
+
+            let sig = p1.complete(&s, &terms.evaluate(&x1)).unwrap();
+            assert_eq!(p1.round(), Round::Done);
+            assert!(s.verify(&msg, &sig, &joint_key).is_ok());
+            let mut normalized = sig;
+            normalized.normalize_s(&s);
+            assert_eq!(normalized, sig);
+
+            // Each session signs once
+            assert_eq!(p2.partial_terms(&s, &x2, &r1), Err(CoSigningOutOfOrder));
+            assert_eq!(p1.complete(&s, &terms.evaluate(&x1)), Err(CoSigningOutOfOrder));
+        }
+    }
+
+    #[test]
+    fn misbehaviour() {
+        let s = Secp256k1::new();
+        let mut rng = thread_rng();
+        let (x1, _) = s.generate_keypair(&mut rng);
+        let (x2, q2) = s.generate_keypair(&mut rng);
+        let joint_key = joint_public_key(&s, &x1, &q2).unwrap();
+        let msg = Message::from([7; 32]);
+        let (_, other) = s.generate_keypair(&mut rng);
+
+        let mut p1 = Party1Session::new(&s, &mut rng, joint_key, msg);
+        let mut p2 = Party2Session::new(&s, &mut rng, msg, p1.commitment());
+        assert_eq!(p1.complete(&s, &Scalar::ONE), Err(CoSigningOutOfOrder));
+        let r1 = p1.reveal(p2.nonce_point()).unwrap();
+        assert_eq!(p1.reveal(other), Err(CoSigningOutOfOrder));
+
+        // Party 1 can't change its nonce after committing
+        assert_eq!(p2.partial_terms(&s, &x2, &other), Err(NonceCommitmentMismatch));
+        let terms = p2.partial_terms(&s, &x2, &r1).unwrap();
+
+        // A wrong contribution is caught before the signature is released
+        let (wrong_share, _) = s.generate_keypair(&mut rng);
+        assert_eq!(p1.complete(&s, &terms.evaluate(&wrong_share)), Err(InvalidPartialSignature));
+        assert_eq!(p1.complete(&s, &terms.evaluate(&x1)), Err(CoSigningOutOfOrder));
+    }
+}
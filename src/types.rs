@@ -0,0 +1,36 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # C types
+//! The C types used by the FFI. They come from `libc`, except on
+//! `wasm32-unknown-unknown`, where `libc` defines nothing but `c_void` and
+//! they are spelled out here to match clang's wasm32 ABI.
+
+#![allow(non_camel_case_types)]
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use libc::{c_char, c_int, c_uchar, c_uint, c_void, size_t};
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use libc::c_void;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type c_char = i8;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type c_int = i32;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type c_uchar = u8;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type c_uint = u32;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub type size_t = usize;
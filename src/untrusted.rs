@@ -0,0 +1,362 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Untrusted ECALL wrappers
+//! One wire contract for the common ECALLs of a signing enclave: generating
+//! a key in its `Vault`, getting a key's public key with a report binding
+//! it (see `attestation`), and signing. The host application builds the
+//! crate without "sgx" and calls the typed wrappers here, `generate_key`,
+//! `public_key_report` and `sign`, each given the raw ECALL as a function
+//! from request bytes to response bytes. The enclave parses the same
+//! requests with `Request::from_slice`, serves them, and answers with
+//! `Response::serialize`, so neither side writes its own encoding.
+//!
+//! A message is encoded as the format version (`ECALL_VERSION`), the
+//! message type and the body: key handles as u32, little-endian, points
+//! compressed and signatures compact. The SGX target info and report
+//! travel as the SDK's `sgx_target_info_t` and `sgx_report_t` bytes.
+//! Parsing is strict, as `wire` is: it fails with `InvalidWireMessage` for
+//! other versions, unknown types, wrong lengths, invalid points and
+//! out-of-range signatures, and the wrappers also reject a response which
+//! doesn't answer their request. An enclave which can't serve a request
+//! answers `Response::Refused`, which the wrappers return as
+//! `EnclaveRefused`; why stays in the enclave.
+//!
+//! Nothing here authenticates the enclave: a host which doesn't trust
+//! itself checks the report's quote with `attestation::verify_key_binding`
+//! and signatures against the attested key. Requires compilation with the
+//! "untrusted" feature.
+
+use std::vec::Vec;
+
+use constants;
+use key::PublicKey;
+use vault::KeyHandle;
+use super::Error::{self, EnclaveRefused, InvalidWireMessage};
+use {Message, Signature};
+
+/// The current version of the ECALL message format
+pub const ECALL_VERSION: u8 = 1;
+
+/// The size (in bytes) of the SGX SDK's `sgx_target_info_t`
+pub const TARGET_INFO_SIZE: usize = 512;
+
+/// The size (in bytes) of the SGX SDK's `sgx_report_t`
+pub const REPORT_SIZE: usize = 432;
+
+/// A request from the host to the enclave
+// Requests and responses live for one ECALL, so the size of the report
+// variants costs nothing worth a box
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Request {
+    /// Generate a key in the vault
+    GenerateKey,
+    /// The public key of a key, with a report for the quoting enclave
+    /// described by `target_info` which binds it
+    PublicKeyReport {
+        /// The key
+        handle: KeyHandle,
+        /// The quoting enclave's `sgx_target_info_t`
+        target_info: [u8; TARGET_INFO_SIZE],
+    },
+    /// Sign a message with a key
+    Sign {
+        /// The key
+        handle: KeyHandle,
+        /// The message hash to sign
+        message: Message,
+    },
+}
+
+/// The enclave's answer to a `Request`
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Response {
+    /// The key `GenerateKey` generated
+    KeyGenerated {
+        /// Its handle
+        handle: KeyHandle,
+        /// Its public key
+        public_key: PublicKey,
+    },
+    /// The answer to `PublicKeyReport`
+    PublicKeyReport {
+        /// The public key
+        public_key: PublicKey,
+        /// The `sgx_report_t` binding it
+        report: [u8; REPORT_SIZE],
+    },
+    /// The signature `Sign` made
+    Signature(Signature),
+    /// The enclave could not or would not serve the request, e.g. for an
+    /// unknown key or one which reached its limit
+    Refused,
+}
+
+fn push_u32(out: &mut Vec<u8>, n: u32) {
+    for i in 0..4 {
+        out.push((n >> (8 * i)) as u8);
+    }
+}
+
+fn handle_at(data: &[u8]) -> KeyHandle {
+    let mut n = 0u32;
+    for (i, byte) in data[..4].iter().enumerate() {
+        n |= (*byte as u32) << (8 * i);
+    }
+    KeyHandle::from_u32(n)
+}
+
+fn point(data: &[u8]) -> Result<PublicKey, Error> {
+    PublicKey::from_slice(data).map_err(|_| InvalidWireMessage)
+}
+
+/// The message type and body of `data`, if it has the current version
+fn split(data: &[u8]) -> Result<(u8, &[u8]), Error> {
+    if data.len() < 2 || data[0] != ECALL_VERSION {
+        return Err(InvalidWireMessage);
+    }
+    Ok((data[1], &data[2..]))
+}
+
+impl Request {
+    /// Encodes the request in the current format
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 + 4 + TARGET_INFO_SIZE);
+        out.push(ECALL_VERSION);
+        match *self {
+            Request::GenerateKey => out.push(1),
+            Request::PublicKeyReport { handle, ref target_info } => {
+                out.push(2);
+                push_u32(&mut out, handle.to_u32());
+                out.extend_from_slice(target_info);
+            }
+            Request::Sign { handle, ref message } => {
+                out.push(3);
+                push_u32(&mut out, handle.to_u32());
+                out.extend_from_slice(&message[..]);
+            }
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidWireMessage`
+    /// for any other data.
+    pub fn from_slice(data: &[u8]) -> Result<Request, Error> {
+        match split(data)? {
+            (1, []) => Ok(Request::GenerateKey),
+            (2, body) if body.len() == 4 + TARGET_INFO_SIZE => {
+                let mut target_info = [0u8; TARGET_INFO_SIZE];
+                target_info.copy_from_slice(&body[4..]);
+                Ok(Request::PublicKeyReport { handle: handle_at(body), target_info })
+            }
+            (3, body) if body.len() == 4 + constants::MESSAGE_SIZE => {
+                let message = Message::from_slice(&body[4..]).map_err(|_| InvalidWireMessage)?;
+                Ok(Request::Sign { handle: handle_at(body), message })
+            }
+            _ => Err(InvalidWireMessage),
+        }
+    }
+
+    /// Whether `response` answers this request, which `Refused` does for
+    /// any request
+    pub fn is_answered_by(&self, response: &Response) -> bool {
+        matches!((self, response),
+                 (_, Response::Refused) |
+                 (Request::GenerateKey, Response::KeyGenerated { .. }) |
+                 (Request::PublicKeyReport { .. }, Response::PublicKeyReport { .. }) |
+                 (Request::Sign { .. }, Response::Signature(_)))
+    }
+}
+
+impl Response {
+    /// Encodes the response in the current format
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 + constants::PUBLIC_KEY_SIZE + REPORT_SIZE);
+        out.push(ECALL_VERSION);
+        match *self {
+            Response::KeyGenerated { handle, ref public_key } => {
+                out.push(0x81);
+                push_u32(&mut out, handle.to_u32());
+                out.extend_from_slice(&public_key.serialize());
+            }
+            Response::PublicKeyReport { ref public_key, ref report } => {
+                out.push(0x82);
+                out.extend_from_slice(&public_key.serialize());
+                out.extend_from_slice(report);
+            }
+            Response::Signature(ref sig) => {
+                out.push(0x83);
+                out.extend_from_slice(&sig.compact_bytes());
+            }
+            Response::Refused => out.push(0xff),
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidWireMessage`
+    /// for any other data.
+    pub fn from_slice(data: &[u8]) -> Result<Response, Error> {
+        match split(data)? {
+            (0x81, body) if body.len() == 4 + constants::PUBLIC_KEY_SIZE => {
+                Ok(Response::KeyGenerated { handle: handle_at(body), public_key: point(&body[4..])? })
+            }
+            (0x82, body) if body.len() == constants::PUBLIC_KEY_SIZE + REPORT_SIZE => {
+                let mut report = [0u8; REPORT_SIZE];
+                report.copy_from_slice(&body[constants::PUBLIC_KEY_SIZE..]);
+                Ok(Response::PublicKeyReport { public_key: point(&body[..constants::PUBLIC_KEY_SIZE])?, report })
+            }
+            (0x83, body) if body.len() == constants::COMPACT_SIGNATURE_SIZE => {
+                let mut compact = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+                compact.copy_from_slice(body);
+                Ok(Response::Signature(Signature::from_compact_bytes(&compact).map_err(|_| InvalidWireMessage)?))
+            }
+            (0xff, []) => Ok(Response::Refused),
+            _ => Err(InvalidWireMessage),
+        }
+    }
+}
+
+/// Sends `request` through `ecall` and parses the response, checking that
+/// it answers the request
+fn call<F: FnOnce(&[u8]) -> Vec<u8>>(ecall: F, request: &Request) -> Result<Response, Error> {
+    let response = Response::from_slice(&ecall(&request.serialize()))?;
+    if !request.is_answered_by(&response) {
+        return Err(InvalidWireMessage);
+    }
+    if response == Response::Refused {
+        return Err(EnclaveRefused);
+    }
+    Ok(response)
+}
+
+/// Asks the enclave behind `ecall` to generate a key, returning its handle
+/// and public key
+pub fn generate_key<F: FnOnce(&[u8]) -> Vec<u8>>(ecall: F) -> Result<(KeyHandle, PublicKey), Error> {
+    match call(ecall, &Request::GenerateKey)? {
+        Response::KeyGenerated { handle, public_key } => Ok((handle, public_key)),
+        _ => unreachable!("checked by call"),
+    }
+}
+
+/// Asks the enclave behind `ecall` for the public key of `handle` and a
+/// report for the quoting enclave `target_info` which binds it
+pub fn public_key_report<F: FnOnce(&[u8]) -> Vec<u8>>(ecall: F, handle: KeyHandle,
+                                                     target_info: &[u8; TARGET_INFO_SIZE])
+                                                     -> Result<(PublicKey, [u8; REPORT_SIZE]), Error> {
+    match call(ecall, &Request::PublicKeyReport { handle, target_info: *target_info })? {
+        Response::PublicKeyReport { public_key, report } => Ok((public_key, report)),
+        _ => unreachable!("checked by call"),
+    }
+}
+
+/// Asks the enclave behind `ecall` to sign `message` with `handle`
+pub fn sign<F: FnOnce(&[u8]) -> Vec<u8>>(ecall: F, handle: KeyHandle, message: &Message)
+                                        -> Result<Signature, Error> {
+    match call(ecall, &Request::Sign { handle, message: *message })? {
+        Response::Signature(sig) => Ok(sig),
+        _ => unreachable!("checked by call"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{generate_key, public_key_report, sign, Request, Response, ECALL_VERSION, REPORT_SIZE,
+                TARGET_INFO_SIZE};
+    use super::super::Error::{EnclaveRefused, InvalidWireMessage};
+    use attestation::report_data;
+    use rng::thread_rng;
+    use vault::{KeyHandle, Vault};
+    use {Message, Secp256k1};
+
+    /// An enclave serving requests from its vault, with a report which is
+    /// just the REPORT_DATA
+    fn enclave<'a>(vault: &'a mut Vault) -> impl FnMut(&[u8]) -> Vec<u8> + 'a {
+        let s = Secp256k1::new();
+        move |data| {
+            let response = match Request::from_slice(data).unwrap() {
+                Request::GenerateKey => {
+                    let handle = vault.generate(&s, &mut thread_rng());
+                    Response::KeyGenerated { handle, public_key: vault.public_key(handle).unwrap() }
+                }
+                Request::PublicKeyReport { handle, .. } => match vault.public_key(handle) {
+                    Ok(public_key) => {
+                        let mut report = [0u8; REPORT_SIZE];
+                        report[320..384].copy_from_slice(&report_data(&public_key));
+                        Response::PublicKeyReport { public_key, report }
+                    }
+                    Err(_) => Response::Refused,
+                },
+                Request::Sign { handle, message } => match vault.sign(&s, handle, &message) {
+                    Ok(sig) => Response::Signature(sig),
+                    Err(_) => Response::Refused,
+                },
+            };
+            response.serialize()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let mut ecall = enclave(&mut vault);
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+
+        let (handle, pk) = generate_key(&mut ecall).unwrap();
+        let (reported, report) = public_key_report(&mut ecall, handle, &[7; TARGET_INFO_SIZE]).unwrap();
+        assert_eq!(reported, pk);
+        assert_eq!(report[320..384], report_data(&pk)[..]);
+        let sig = sign(&mut ecall, handle, &msg).unwrap();
+        assert_eq!(s.verify(&msg, &sig, &pk), Ok(()));
+
+        let unknown = KeyHandle::from_u32(handle.to_u32() + 1);
+        assert_eq!(sign(&mut ecall, unknown, &msg), Err(EnclaveRefused));
+        assert_eq!(public_key_report(&mut ecall, unknown, &[7; TARGET_INFO_SIZE]), Err(EnclaveRefused));
+    }
+
+    #[test]
+    fn strict_parsing() {
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let request = Request::Sign { handle: KeyHandle::from_u32(3), message: msg };
+        let data = request.serialize();
+        assert_eq!(data.len(), 2 + 4 + 32);
+        assert_eq!(Request::from_slice(&data), Ok(request));
+        assert_eq!(Request::from_slice(&data[..data.len() - 1]), Err(InvalidWireMessage));
+        let mut other_version = data.clone();
+        other_version[0] = ECALL_VERSION + 1;
+        assert_eq!(Request::from_slice(&other_version), Err(InvalidWireMessage));
+        assert_eq!(Response::from_slice(&data), Err(InvalidWireMessage));
+
+        assert_eq!(Response::from_slice(&[ECALL_VERSION, 0x83]), Err(InvalidWireMessage));
+        let mut out_of_range = vec![ECALL_VERSION, 0x83];
+        out_of_range.extend_from_slice(&[0xff; 64]);
+        assert_eq!(Response::from_slice(&out_of_range), Err(InvalidWireMessage));
+        let mut off_curve = vec![ECALL_VERSION, 0x81, 1, 0, 0, 0, 0x02];
+        off_curve.extend_from_slice(&[0; 32]);
+        assert_eq!(Response::from_slice(&off_curve), Err(InvalidWireMessage));
+
+        // An answer to another request
+        let refused = Response::Refused.serialize();
+        assert_eq!(Response::from_slice(&refused), Ok(Response::Refused));
+        let generated = |_: &[u8]| Response::KeyGenerated {
+            handle: KeyHandle::from_u32(1),
+            public_key: ::key::PublicKey::from_slice(&::constants::GENERATOR_G_COMPRESSED).unwrap(),
+        }.serialize();
+        assert_eq!(sign(generated, KeyHandle::from_u32(1), &msg), Err(InvalidWireMessage));
+    }
+}
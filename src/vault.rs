@@ -0,0 +1,2182 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Vault
+//! Secret keys which never leave the enclave. Callers refer to keys by
+//! `KeyHandle`, an opaque number which can safely cross the enclave
+//! boundary, and only ever get public keys and signatures back. The vault
+//! counts the signatures made with each key and can limit their number, for
+//! one-time or limited-use keys.
+//!
+//! A count kept in enclave memory is lost (or rolled back along with sealed
+//! state) on restart, which would re-enable an exhausted key. A
+//! `UsageCounter` set with `Vault::set_counter` persists each count, e.g.
+//! in an SGX monotonic counter, before the signature is made, and
+//! `FileBackedVault::sign` does the same by saving the vault file.
+//!
+//! Besides the count, each key records when it was last used, by the
+//! `Clock` set with `Vault::set_clock` (trusted time, where the platform
+//! has it), and a hash chain over every message it signed and element it
+//! evaluated (with the "hashes" feature), which an auditor holding the log
+//! of requests can recompute with `usage_chain`. `Vault::key_usage` returns
+//! these statistics, and `Vault::attested_usage_report` (in the `audit`
+//! module) signs them with an enclave identity key. `Vault::destroy`, also
+//! in `audit`, shreds a key and signs a certificate of its destruction.
+//!
+//! A key can also be limited to a validity period with
+//! `Vault::set_validity`, checked against the vault's clock on each use. A
+//! key with a validity period can't be used while the clock tells no time.
+//! With the "trusted-time" feature, `TrustedTimeClock` reads SGX trusted
+//! time from the Platform Services Enclave; platforms without it (such as
+//! DCAP-only ones) need a `Clock` of their own, e.g. one fed by time stamps
+//! signed by a trusted time server.
+//!
+//! `Vault::set_rate_limit` puts a key behind a token bucket, so that a
+//! compromised host can't get signatures out of it faster than the
+//! configured rate however many valid requests it makes. The bucket refills
+//! by the vault's clock, and with the "metrics" feature every use of a
+//! rate-limited key is reported as `metrics::Operation::RateLimit`, failing
+//! when the limit refused it.
+//!
+//! A high-value key can be put under a two-man rule with
+//! `Vault::set_approval_policy`: it then only signs through
+//! `Vault::sign_approved`, given signatures of enough of its registered
+//! approvers over the request. Each `Approval` covers the key, the
+//! message and the use count the signature will bring the key to, so it
+//! can't be replayed for another message or a later signature of the same
+//! one. Checking approvals reveals which key they were for. Approvals need
+//! the "hashes" feature; without it, a key with an approval policy can't
+//! be used at all.
+//!
+//! `FileBackedVault` (with the "protected-fs" feature) persists a vault in a
+//! file encrypted and integrity-protected by the SGX protected file system,
+//! under a key derived from the enclave's sealing key. Each save bumps a
+//! generation number stored in the file, which makes rollback of the file
+//! detectable.
+//!
+//! A presignature, made with `Vault::presign` ahead of time, is a nonce for
+//! one key with the elliptic curve work already done, so that
+//! `Vault::sign_presigned` can sign a later message with a few scalar
+//! operations. Each presignature is used up by the first attempt to sign
+//! with it, successful or not. Signing two messages with one nonce reveals
+//! the key, so presignatures kept in a `FileBackedVault` are only as
+//! single-use as rollback of its file is detectable: use a `UsageCounter`
+//! which refuses counts it has already seen, or check the generation
+//! against a trusted record on opening.
+//!
+//! With the "hashes" feature, keys also have a `KeyId`, the short hash of
+//! the public key which `Vault::key_id` returns and `Vault::find` looks up,
+//! so that logs and policies can name keys independently of their handles.
+//!
+//! Looking up a key by handle is oblivious: signing, and reading or setting
+//! a key's counts, reads every entry (and updates write every entry),
+//! picking out the one for the handle with constant-time masks. Which
+//! tenant's key was used therefore doesn't show in the enclave's memory
+//! access pattern, at the cost of time linear in the number of keys. Only
+//! whether the handle exists is revealed. Using a presignature is oblivious
+//! in the same way. Inserting and removing keys, and making presignatures,
+//! are not oblivious. The "fast-vault-lookup" feature turns this off in favour
+//! of a plain search, for vaults whose callers are all trusted.
+
+use std::boxed::Box;
+use std::cmp;
+use std::fmt;
+#[cfg(not(feature = "fast-vault-lookup"))]
+use std::ptr;
+use std::vec::Vec;
+#[cfg(feature = "protected-fs")]
+use std::io::{self, Read, Write};
+#[cfg(feature = "protected-fs")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "protected-fs")]
+use std::sgxfs::SgxFile;
+
+#[cfg(feature = "trusted-time")]
+use sgx_tservice::{rsgx_close_pse_session, rsgx_create_pse_session};
+#[cfg(feature = "trusted-time")]
+use sgx_tservice::sgxtime::SgxTime;
+#[cfg(feature = "trusted-time")]
+use sgx_types::SgxResult;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+#[cfg(feature = "protected-fs")]
+use cleanup::wipe;
+use constants;
+#[cfg(feature = "hashes")]
+use hashes::{TaggedHasher, VAULT_APPROVAL_TAG, VAULT_USAGE_CHAIN_TAG};
+#[cfg(feature = "hashes")]
+use key::KeyId;
+use key::{PublicKey, SecretKey};
+#[cfg(any(test, feature = "protected-fs"))]
+use le_bytes::{read_u32, read_u64, u32_bytes, u64_bytes};
+use scalar::Scalar;
+#[cfg(feature = "psbt")]
+use psbt::{self, SighashType, Spend};
+#[cfg(feature = "protected-fs")]
+use sealing::{self, KeyPolicy};
+use super::Error::{self, InsufficientApprovals, InvalidRateLimit, InvalidSignature, KeyExhausted, KeyExpired,
+                   KeyNotYetValid, RateLimited, TimeUnavailable, UnknownKeyHandle, UnknownPresignature};
+#[cfg(feature = "hashes")]
+use super::Error::InvalidApprovalPolicy;
+#[cfg(any(test, feature = "protected-fs"))]
+use super::Error::Storage;
+#[cfg(feature = "protected-fs")]
+use super::Error::VaultRollback;
+use {Message, Secp256k1, Signature, Signing, Verification};
+
+/// Refers to a key in a `Vault`. Handles are never reused, so a handle to a
+/// removed key stays invalid.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct KeyHandle(u32);
+
+impl KeyHandle {
+    /// Creates a handle from its numeric value, e.g. one passed in an ECALL
+    #[inline]
+    pub fn from_u32(n: u32) -> KeyHandle {
+        KeyHandle(n)
+    }
+
+    /// The numeric value of the handle
+    #[inline]
+    pub fn to_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for KeyHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Refers to a presignature in a `Vault`. Like handles, ids are never
+/// reused.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PresignatureId(u64);
+
+impl PresignatureId {
+    /// Creates an id from its numeric value
+    #[inline]
+    pub fn from_u64(n: u64) -> PresignatureId {
+        PresignatureId(n)
+    }
+
+    /// The numeric value of the id
+    #[inline]
+    pub fn to_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for PresignatureId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "presignature #{}", self.0)
+    }
+}
+
+/// Persists signature counts. The vault only signs once `advance` has
+/// succeeded, so a count can never fall behind the signatures made.
+pub trait UsageCounter {
+    /// Durably records that the key for `handle` is about to be used for
+    /// the `uses`-th time, or with `uses` of `DESTROYED`, that it is about
+    /// to be destroyed. Any error stops the signature (or destruction).
+    fn advance(&mut self, handle: KeyHandle, uses: u64) -> Result<(), Error>;
+}
+
+/// The count a `UsageCounter` is advanced to when `Vault::destroy` destroys
+/// a key: a tombstone above any count the key could reach. A counter which
+/// refuses counts it has already passed thereby refuses every use of the
+/// key from a rolled-back copy of the vault.
+pub const DESTROYED: u64 = !0;
+
+/// Tells the time recorded as a key's last use
+pub trait Clock {
+    /// The current time in seconds since the Unix epoch, or `None` if no
+    /// time can be had. Times from the untrusted host can be made up by
+    /// it, so prefer a trusted source.
+    fn now(&mut self) -> Option<u64>;
+}
+
+/// A `Clock` on SGX trusted time, which counts seconds from a point the
+/// Platform Services Enclave picks, anchored to Unix time when the clock
+/// is made. It stops telling the time if the platform's trusted time
+/// source is reset.
+#[cfg(feature = "trusted-time")]
+pub struct TrustedTimeClock {
+    anchor: u64,
+    start: SgxTime,
+}
+
+#[cfg(feature = "trusted-time")]
+impl TrustedTimeClock {
+    /// Starts a clock which tells `unix_time` now, e.g. a time the enclave
+    /// has checked the signature of. Fails with `TimeUnavailable` on
+    /// platforms without trusted time.
+    pub fn new(unix_time: u64) -> Result<TrustedTimeClock, Error> {
+        Ok(TrustedTimeClock { anchor: unix_time, start: with_pse_session(SgxTime::now)? })
+    }
+}
+
+#[cfg(feature = "trusted-time")]
+impl Clock for TrustedTimeClock {
+    fn now(&mut self) -> Option<u64> {
+        // Fails if the time source nonce changed since `start`
+        let elapsed = with_pse_session(|| self.start.elapsed()).ok()?;
+        self.anchor.checked_add(elapsed.as_secs())
+    }
+}
+
+/// Runs `f` in a session with the Platform Services Enclave
+#[cfg(feature = "trusted-time")]
+fn with_pse_session<T, F: FnOnce() -> SgxResult<T>>(f: F) -> Result<T, Error> {
+    rsgx_create_pse_session().map_err(|_| TimeUnavailable)?;
+    let ret = f();
+    let _ = rsgx_close_pse_session();
+    ret.map_err(|_| TimeUnavailable)
+}
+
+/// The usage statistics of one key
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct KeyUsage {
+    /// The key's handle
+    pub handle: KeyHandle,
+    /// The key's public key
+    pub public_key: PublicKey,
+    /// The number of signatures (and OPRF evaluations) made with the key
+    pub uses: u64,
+    /// The most signatures the key may make, if limited
+    pub limit: Option<u64>,
+    /// When the key was last used (seconds since the Unix epoch), if it
+    /// has been used and the vault's clock told the time then
+    pub last_used: Option<u64>,
+    /// The hash chain of everything the key was used on, all zeroes before
+    /// the first use or without the "hashes" feature
+    pub chain: [u8; 32],
+}
+
+#[cfg(feature = "hashes")]
+impl KeyUsage {
+    /// The identifier of the key. Requires compilation with the "hashes"
+    /// feature.
+    #[inline]
+    pub fn key_id(&self) -> KeyId {
+        self.public_key.fingerprint()
+    }
+}
+
+/// A token bucket limit on how fast a key may be used. The bucket holds up
+/// to `burst` uses and gains `uses` of them at the end of each `interval`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RateLimit {
+    /// The uses allowed per interval
+    pub uses: u64,
+    /// The length of the interval in seconds
+    pub interval: u64,
+    /// The most uses that can be saved up, and so made at once
+    pub burst: u64,
+}
+
+/// The keys allowed to approve uses of a key, and how many of them must
+/// approve each use
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ApprovalPolicy {
+    /// The approvers' public keys
+    pub approvers: Vec<PublicKey>,
+    /// The number of different approvers needed
+    pub threshold: usize,
+}
+
+/// The size (in bytes) of an encoded `Approval`
+pub const APPROVAL_SIZE: usize = constants::PUBLIC_KEY_SIZE + constants::COMPACT_SIGNATURE_SIZE;
+
+/// An approver's signature allowing one use of a key under an
+/// `ApprovalPolicy`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Approval {
+    /// The approver's public key
+    pub approver: PublicKey,
+    /// The approver's signature over `Approval::signing_message`
+    pub signature: Signature,
+}
+
+impl Approval {
+    /// The message an approver signs to allow the key `key` to sign `msg`
+    /// as its `use_number`-th use, i.e. when `Vault::uses` is one less
+    #[cfg(feature = "hashes")]
+    pub fn signing_message(key: &PublicKey, use_number: u64, msg: &Message) -> Message {
+        Message::from(TaggedHasher::new(VAULT_APPROVAL_TAG).chain(&key.serialize())
+                      .chain(&::le_bytes::u64_bytes(use_number)).chain(&msg[..]).result())
+    }
+
+    /// Approves the `use_number`-th use of `key`, to sign `msg`, with the
+    /// approver's secret key
+    #[cfg(feature = "hashes")]
+    pub fn sign<C: Signing>(secp: &Secp256k1<C>, approver: &SecretKey, key: &PublicKey, use_number: u64,
+                            msg: &Message) -> Approval {
+        Approval {
+            approver: PublicKey::from_secret_key(secp, approver),
+            signature: secp.sign(&Approval::signing_message(key, use_number, msg), approver),
+        }
+    }
+
+    /// Encodes the approval: the compressed approver key followed by the
+    /// compact signature
+    pub fn serialize<C>(&self, secp: &Secp256k1<C>) -> [u8; APPROVAL_SIZE] {
+        let mut out = [0u8; APPROVAL_SIZE];
+        out[..constants::PUBLIC_KEY_SIZE].copy_from_slice(&self.approver.serialize());
+        out[constants::PUBLIC_KEY_SIZE..].copy_from_slice(&self.signature.serialize_compact(secp));
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` for
+    /// any other data.
+    pub fn from_slice<C>(secp: &Secp256k1<C>, data: &[u8]) -> Result<Approval, Error> {
+        if data.len() != APPROVAL_SIZE {
+            return Err(InvalidSignature);
+        }
+        Ok(Approval {
+            approver: PublicKey::from_slice(&data[..constants::PUBLIC_KEY_SIZE]).map_err(|_| InvalidSignature)?,
+            signature: Signature::from_compact(secp, &data[constants::PUBLIC_KEY_SIZE..])
+                .map_err(|_| InvalidSignature)?,
+        })
+    }
+}
+
+/// The next link of a key's usage chain after the link `chain`, for a use
+/// on `data`: the message for a signature, or the compressed blinded
+/// element for an OPRF evaluation. The chain of an unused key is all
+/// zeroes.
+#[cfg(feature = "hashes")]
+pub fn usage_chain(chain: &[u8; 32], data: &[u8]) -> [u8; 32] {
+    TaggedHasher::new(VAULT_USAGE_CHAIN_TAG).chain(chain).chain(data).result()
+}
+
+/// Without hashes, chains stay at all zeroes
+#[cfg(not(feature = "hashes"))]
+fn usage_chain(chain: &[u8; 32], _data: &[u8]) -> [u8; 32] {
+    *chain
+}
+
+/// The limit of a key without one. Limits are stored as plain numbers so
+/// that they can be selected with masks.
+const NO_LIMIT: u64 = !0;
+
+/// The validity period of a key without one: from the start of time and,
+/// like `NO_LIMIT`, to a time no clock reaches
+const ALWAYS_VALID: (u64, u64) = (0, !0);
+
+/// The rate limit of a key without one, which is the only one with a zero
+/// interval
+const NO_RATE_LIMIT: RateLimit = RateLimit { uses: 0, interval: 0, burst: 0 };
+
+struct Entry {
+    handle: KeyHandle,
+    sk: SecretKey,
+    pk: PublicKey,
+    uses: u64,
+    limit: u64,
+    /// Zero if unknown
+    last_used: u64,
+    chain: [u8; 32],
+    not_before: u64,
+    not_after: u64,
+    rate: RateLimit,
+    /// The uses left in the bucket as of `refilled_at`
+    tokens: u64,
+    refilled_at: u64,
+    /// The threshold of the key's approval policy, zero if it has none
+    approvals_needed: u64,
+}
+
+impl Entry {
+    /// A copy of the entry, whose key is wiped when it is dropped like the
+    /// original's
+    fn copy(&self) -> Entry {
+        Entry {
+            handle: self.handle,
+            sk: self.sk,
+            pk: self.pk,
+            uses: self.uses,
+            limit: self.limit,
+            last_used: self.last_used,
+            chain: self.chain,
+            not_before: self.not_before,
+            not_after: self.not_after,
+            rate: self.rate,
+            tokens: self.tokens,
+            refilled_at: self.refilled_at,
+            approvals_needed: self.approvals_needed,
+        }
+    }
+
+    /// Takes a use out of the bucket at `now`, after refilling it for the
+    /// intervals ended since the last refill. Fails with `RateLimited` if
+    /// it is empty.
+    fn take_token(&mut self, now: u64) -> Result<(), Error> {
+        // A clock which goes back refills nothing
+        if now > self.refilled_at {
+            let intervals = (now - self.refilled_at) / self.rate.interval;
+            let earned = intervals.saturating_mul(self.rate.uses);
+            self.tokens = cmp::min(self.rate.burst, self.tokens.saturating_add(earned));
+            self.refilled_at += intervals * self.rate.interval;
+        }
+        if self.tokens == 0 {
+            return Err(RateLimited);
+        }
+        self.tokens -= 1;
+        Ok(())
+    }
+
+    fn usage(&self) -> KeyUsage {
+        KeyUsage {
+            handle: self.handle,
+            public_key: self.pk,
+            uses: self.uses,
+            limit: if self.limit == NO_LIMIT { None } else { Some(self.limit) },
+            last_used: if self.last_used == 0 { None } else { Some(self.last_used) },
+            chain: self.chain,
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.sk.wipe();
+    }
+}
+
+/// A nonce `k` for the key of `handle`, kept as `k^-1` and the `r` of its
+/// point
+struct Presignature {
+    id: u64,
+    handle: KeyHandle,
+    k_inv: SecretKey,
+    r: [u8; 32],
+    /// All ones until the presignature is used, then zero
+    live: u64,
+}
+
+impl Presignature {
+    /// A copy of the presignature, wiped on drop like the original
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    fn copy(&self) -> Presignature {
+        Presignature { id: self.id, handle: self.handle, k_inv: self.k_inv, r: self.r, live: self.live }
+    }
+}
+
+impl Drop for Presignature {
+    fn drop(&mut self) {
+        self.k_inv.wipe();
+    }
+}
+
+/// A set of secret keys which are only used in place. Keys are wiped when
+/// they are removed or the vault is dropped.
+pub struct Vault {
+    entries: Vec<Entry>,
+    next_handle: u32,
+    presignatures: Vec<Presignature>,
+    /// The approvers of each key with an approval policy
+    approvers: Vec<(KeyHandle, Vec<PublicKey>)>,
+    // Only read when making presignatures or saving
+    #[cfg_attr(not(any(test, feature = "rand", feature = "protected-fs")), allow(dead_code))]
+    next_presignature: u64,
+    counter: Option<Box<UsageCounter + Send>>,
+    clock: Option<Box<Clock + Send>>,
+}
+
+// The counter and clock are only called through `&mut self`, so sharing a
+// `&Vault` never shares them, and they need not be `Sync`
+unsafe impl Sync for Vault {}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vault({} keys)", self.entries.len())
+    }
+}
+
+impl Default for Vault {
+    fn default() -> Vault {
+        Vault::new()
+    }
+}
+
+impl Vault {
+    /// Creates an empty vault
+    pub fn new() -> Vault {
+        Vault {
+            entries: Vec::new(),
+            next_handle: 1,
+            presignatures: Vec::new(),
+            approvers: Vec::new(),
+            next_presignature: 1,
+            counter: None,
+            clock: None,
+        }
+    }
+
+    /// Makes every signature first advance `counter`
+    pub fn set_counter<U: UsageCounter + Send + 'static>(&mut self, counter: U) {
+        self.counter = Some(Box::new(counter));
+    }
+
+    /// Records the time from `clock` as the last use of each key
+    pub fn set_clock<T: Clock + Send + 'static>(&mut self, clock: T) {
+        self.clock = Some(Box::new(clock));
+    }
+
+    /// The time from the clock, if there is one and it tells the time
+    pub(crate) fn now(&mut self) -> Option<u64> {
+        self.clock.as_mut().and_then(|clock| clock.now())
+    }
+
+    /// Adds `sk` to the vault
+    pub fn insert<C: Signing>(&mut self, secp: &Secp256k1<C>, sk: SecretKey) -> KeyHandle {
+        let handle = KeyHandle(self.next_handle);
+        self.next_handle = self.next_handle.checked_add(1).expect("vault handles exhausted");
+        let pk = PublicKey::from_secret_key(secp, &sk);
+        self.entries.push(Entry { handle, sk, pk, uses: 0,
+            limit: NO_LIMIT,
+            last_used: 0,
+            chain: [0; 32],
+            not_before: ALWAYS_VALID.0,
+            not_after: ALWAYS_VALID.1,
+            rate: NO_RATE_LIMIT,
+            tokens: 0,
+            refilled_at: 0,
+            approvals_needed: 0,
+        });
+        handle
+    }
+
+    /// Generates a new key inside the vault
+    #[cfg(any(test, feature = "rand"))]
+    pub fn generate<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, rng: &mut R) -> KeyHandle {
+        let sk = SecretKey::new(secp, rng);
+        self.insert(secp, sk)
+    }
+
+    /// Copies out the entry for `handle`, reading every entry
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    fn load(&self, handle: KeyHandle) -> Result<Entry, Error> {
+        let mut ret = match self.entries.first() {
+            Some(first) => first.copy(),
+            None => return Err(UnknownKeyHandle),
+        };
+        ret.handle = handle;
+        let mut found = 0;
+        for entry in &self.entries {
+            visit();
+            let mask = ct_eq_mask(entry.handle.0 as u64, handle.0 as u64);
+            ret.sk.ct_assign(&entry.sk, mask as u8);
+            ret.pk.ct_assign(&entry.pk, mask as u8);
+            ret.uses = ct_select(mask, entry.uses, ret.uses);
+            ret.limit = ct_select(mask, entry.limit, ret.limit);
+            ret.last_used = ct_select(mask, entry.last_used, ret.last_used);
+            ::constant_time_assign(&mut ret.chain, &entry.chain, mask as u8);
+            ret.not_before = ct_select(mask, entry.not_before, ret.not_before);
+            ret.not_after = ct_select(mask, entry.not_after, ret.not_after);
+            ret.rate.uses = ct_select(mask, entry.rate.uses, ret.rate.uses);
+            ret.rate.interval = ct_select(mask, entry.rate.interval, ret.rate.interval);
+            ret.rate.burst = ct_select(mask, entry.rate.burst, ret.rate.burst);
+            ret.tokens = ct_select(mask, entry.tokens, ret.tokens);
+            ret.refilled_at = ct_select(mask, entry.refilled_at, ret.refilled_at);
+            ret.approvals_needed = ct_select(mask, entry.approvals_needed, ret.approvals_needed);
+            found |= mask;
+        }
+        if found == 0 { Err(UnknownKeyHandle) } else { Ok(ret) }
+    }
+
+    /// Sets the counts and usage statistics of the entry for `new`'s
+    /// handle to `new`'s, writing every entry
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    fn store(&mut self, new: &Entry) -> Result<(), Error> {
+        let mut found = 0;
+        for entry in self.entries.iter_mut() {
+            visit();
+            let mask = ct_eq_mask(entry.handle.0 as u64, new.handle.0 as u64);
+            entry.uses = ct_select(mask, new.uses, entry.uses);
+            entry.limit = ct_select(mask, new.limit, entry.limit);
+            entry.last_used = ct_select(mask, new.last_used, entry.last_used);
+            ::constant_time_assign(&mut entry.chain, &new.chain, mask as u8);
+            entry.not_before = ct_select(mask, new.not_before, entry.not_before);
+            entry.not_after = ct_select(mask, new.not_after, entry.not_after);
+            entry.rate.uses = ct_select(mask, new.rate.uses, entry.rate.uses);
+            entry.rate.interval = ct_select(mask, new.rate.interval, entry.rate.interval);
+            entry.rate.burst = ct_select(mask, new.rate.burst, entry.rate.burst);
+            entry.tokens = ct_select(mask, new.tokens, entry.tokens);
+            entry.refilled_at = ct_select(mask, new.refilled_at, entry.refilled_at);
+            entry.approvals_needed = ct_select(mask, new.approvals_needed, entry.approvals_needed);
+            found |= mask;
+        }
+        if found == 0 { Err(UnknownKeyHandle) } else { Ok(()) }
+    }
+
+    /// Marks the live presignature `id` of the key for `handle` used and
+    /// copies it out, reading and writing every presignature
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    fn take_presignature(&mut self, handle: KeyHandle, id: PresignatureId) -> Result<Presignature, Error> {
+        let mut ret = match self.presignatures.first() {
+            Some(first) => first.copy(),
+            None => return Err(UnknownPresignature),
+        };
+        let mut found = 0;
+        for presig in self.presignatures.iter_mut() {
+            visit();
+            let mask = ct_eq_mask(presig.id, id.0) & ct_eq_mask(presig.handle.0 as u64, handle.0 as u64) &
+                       presig.live;
+            ret.k_inv.ct_assign(&presig.k_inv, mask as u8);
+            ::constant_time_assign(&mut ret.r, &presig.r, mask as u8);
+            presig.live &= !mask;
+            found |= mask;
+        }
+        if found == 0 { Err(UnknownPresignature) } else { Ok(ret) }
+    }
+
+    /// Copies out the entry for `handle`
+    #[cfg(feature = "fast-vault-lookup")]
+    fn load(&self, handle: KeyHandle) -> Result<Entry, Error> {
+        self.entries.iter().find(|e| e.handle == handle).map(Entry::copy).ok_or(UnknownKeyHandle)
+    }
+
+    /// Sets the counts and usage statistics of the entry for `new`'s
+    /// handle to `new`'s
+    #[cfg(feature = "fast-vault-lookup")]
+    fn store(&mut self, new: &Entry) -> Result<(), Error> {
+        let entry = self.entries.iter_mut().find(|e| e.handle == new.handle).ok_or(UnknownKeyHandle)?;
+        entry.uses = new.uses;
+        entry.limit = new.limit;
+        entry.last_used = new.last_used;
+        entry.chain = new.chain;
+        entry.not_before = new.not_before;
+        entry.not_after = new.not_after;
+        entry.rate = new.rate;
+        entry.tokens = new.tokens;
+        entry.refilled_at = new.refilled_at;
+        entry.approvals_needed = new.approvals_needed;
+        Ok(())
+    }
+
+    /// Removes the presignature `id` of the key for `handle`
+    #[cfg(feature = "fast-vault-lookup")]
+    fn take_presignature(&mut self, handle: KeyHandle, id: PresignatureId) -> Result<Presignature, Error> {
+        let index = self.presignatures.iter().position(|p| p.id == id.0 && p.handle == handle && p.live != 0)
+            .ok_or(UnknownPresignature)?;
+        Ok(self.presignatures.remove(index))
+    }
+
+    /// The public key for `handle`
+    pub fn public_key(&self, handle: KeyHandle) -> Result<PublicKey, Error> {
+        self.load(handle).map(|e| e.pk)
+    }
+
+    /// The identifier of the key for `handle`, as logs and policies refer
+    /// to it. Requires compilation with the "hashes" feature.
+    #[cfg(feature = "hashes")]
+    pub fn key_id(&self, handle: KeyHandle) -> Result<KeyId, Error> {
+        self.public_key(handle).map(|pk| pk.fingerprint())
+    }
+
+    /// The handle of the key with identifier `id`. Fails with
+    /// `UnknownKeyHandle` if the vault holds no such key. Every key is
+    /// hashed and compared in constant time, so the time taken doesn't
+    /// tell which key it was. Requires compilation with the "hashes"
+    /// feature.
+    #[cfg(all(feature = "hashes", not(feature = "fast-vault-lookup")))]
+    pub fn find(&self, id: &KeyId) -> Result<KeyHandle, Error> {
+        let mut handle = 0;
+        let mut found = 0;
+        for entry in &self.entries {
+            visit();
+            let mask = (::constant_time_eq(&entry.pk.fingerprint().to_bytes(), &id.to_bytes()) as u64).wrapping_neg();
+            // The first match wins, should two keys share an identifier
+            handle = ct_select(mask & !found, entry.handle.0 as u64, handle);
+            found |= mask;
+        }
+        if found == 0 { Err(UnknownKeyHandle) } else { Ok(KeyHandle(handle as u32)) }
+    }
+
+    /// The handle of the key with identifier `id`. Fails with
+    /// `UnknownKeyHandle` if the vault holds no such key. Requires
+    /// compilation with the "hashes" feature.
+    #[cfg(all(feature = "hashes", feature = "fast-vault-lookup"))]
+    pub fn find(&self, id: &KeyId) -> Result<KeyHandle, Error> {
+        self.entries.iter().find(|e| e.pk.fingerprint() == *id).map(|e| e.handle).ok_or(UnknownKeyHandle)
+    }
+
+    /// The number of signatures made with the key for `handle`
+    pub fn uses(&self, handle: KeyHandle) -> Result<u64, Error> {
+        self.load(handle).map(|e| e.uses)
+    }
+
+    /// The most signatures the key for `handle` may make, if limited
+    pub fn limit(&self, handle: KeyHandle) -> Result<Option<u64>, Error> {
+        self.load(handle).map(|e| e.usage().limit)
+    }
+
+    /// The usage statistics of the key for `handle`
+    pub fn key_usage(&self, handle: KeyHandle) -> Result<KeyUsage, Error> {
+        self.load(handle).map(|e| e.usage())
+    }
+
+    /// The usage statistics of all keys, oldest first
+    pub fn key_usages(&self) -> Vec<KeyUsage> {
+        self.entries.iter().map(Entry::usage).collect()
+    }
+
+    /// Limits the key for `handle` to `limit` signatures in total (counting
+    /// those already made), or lifts the limit with `None`
+    pub fn set_limit(&mut self, handle: KeyHandle, limit: Option<u64>) -> Result<(), Error> {
+        let mut entry = self.load(handle)?;
+        entry.limit = limit.unwrap_or(NO_LIMIT);
+        self.store(&entry)
+    }
+
+    /// The validity period of the key for `handle`: the first second it
+    /// may be used in and the first it may no longer be, each if set
+    pub fn validity(&self, handle: KeyHandle) -> Result<(Option<u64>, Option<u64>), Error> {
+        let entry = self.load(handle)?;
+        Ok((if entry.not_before == ALWAYS_VALID.0 { None } else { Some(entry.not_before) },
+            if entry.not_after == ALWAYS_VALID.1 { None } else { Some(entry.not_after) }))
+    }
+
+    /// Only lets the key for `handle` be used from `not_before` (inclusive)
+    /// until `not_after` (exclusive), both in seconds since the Unix epoch
+    /// by the vault's clock, or lifts either bound with `None`
+    pub fn set_validity(&mut self, handle: KeyHandle, not_before: Option<u64>, not_after: Option<u64>)
+                        -> Result<(), Error> {
+        let mut entry = self.load(handle)?;
+        entry.not_before = not_before.unwrap_or(ALWAYS_VALID.0);
+        entry.not_after = not_after.unwrap_or(ALWAYS_VALID.1);
+        self.store(&entry)
+    }
+
+    /// The rate limit of the key for `handle`, if any
+    pub fn rate_limit(&self, handle: KeyHandle) -> Result<Option<RateLimit>, Error> {
+        self.load(handle).map(|e| if e.rate == NO_RATE_LIMIT { None } else { Some(e.rate) })
+    }
+
+    /// Puts the key for `handle` behind a token bucket with the rate limit
+    /// `rate`, starting full, or lifts the rate limit with `None`. Fails with
+    /// `InvalidRateLimit` if any part of `rate` is zero.
+    pub fn set_rate_limit(&mut self, handle: KeyHandle, rate: Option<RateLimit>) -> Result<(), Error> {
+        let rate = rate.unwrap_or(NO_RATE_LIMIT);
+        if rate != NO_RATE_LIMIT && (rate.uses == 0 || rate.interval == 0 || rate.burst == 0) {
+            return Err(InvalidRateLimit);
+        }
+        let mut entry = self.load(handle)?;
+        entry.rate = rate;
+        entry.tokens = rate.burst;
+        entry.refilled_at = 0;
+        self.store(&entry)
+    }
+
+    /// The approval policy of the key for `handle`, if any
+    pub fn approval_policy(&self, handle: KeyHandle) -> Result<Option<ApprovalPolicy>, Error> {
+        let threshold = self.load(handle)?.approvals_needed as usize;
+        Ok(self.approvers.iter().find(|a| a.0 == handle)
+            .map(|a| ApprovalPolicy { approvers: a.1.clone(), threshold }))
+    }
+
+    /// Makes the key for `handle` only sign with the approval of
+    /// `policy.threshold` of `policy.approvers`, through `sign_approved`, or
+    /// lifts the policy with `None`. Fails with `InvalidApprovalPolicy` if
+    /// the threshold is zero or more than the number of approvers, or an
+    /// approver is listed twice.
+    #[cfg(feature = "hashes")]
+    pub fn set_approval_policy(&mut self, handle: KeyHandle, policy: Option<ApprovalPolicy>) -> Result<(), Error> {
+        if let Some(ref policy) = policy {
+            let approvers = &policy.approvers;
+            if policy.threshold == 0 || policy.threshold > approvers.len() ||
+               approvers.len() > u32::max_value() as usize ||
+               approvers.iter().enumerate().any(|(i, pk)| approvers[..i].contains(pk)) {
+                return Err(InvalidApprovalPolicy);
+            }
+        }
+        let mut entry = self.load(handle)?;
+        entry.approvals_needed = policy.as_ref().map_or(0, |p| p.threshold as u64);
+        self.store(&entry)?;
+        self.approvers.retain(|a| a.0 != handle);
+        if let Some(policy) = policy {
+            self.approvers.push((handle, policy.approvers));
+        }
+        Ok(())
+    }
+
+    /// Checks that `approvals` approve the next use of the key for `handle`
+    /// to sign `msg` under its approval policy, if it has one. Fails with
+    /// `InsufficientApprovals` if fewer than the threshold of the approvers
+    /// have approved it.
+    #[cfg(feature = "hashes")]
+    fn check_approvals<C: Verification>(&self, secp: &Secp256k1<C>, handle: KeyHandle, msg: &Message,
+                                        approvals: &[Approval]) -> Result<(), Error> {
+        let entry = self.load(handle)?;
+        let approvers = match self.approvers.iter().find(|a| a.0 == handle) {
+            Some(&(_, ref approvers)) => approvers,
+            None => return Ok(()),
+        };
+        let approved = Approval::signing_message(&entry.pk, entry.uses + 1, msg);
+        // Each approver counts once, however many approvals it gave
+        let count = approvers.iter().filter(|&approver| {
+            approvals.iter().any(|a| a.approver == *approver && secp.verify(&approved, &a.signature, approver).is_ok())
+        }).count();
+        if (count as u64) < entry.approvals_needed { Err(InsufficientApprovals) } else { Ok(()) }
+    }
+
+    /// Copies out the entry for `handle` with its usage statistics advanced
+    /// for a use on `data`, after advancing the counter, if any. Fails with
+    /// `KeyExhausted` if the key has reached its limit, with
+    /// `InsufficientApprovals` if it has an approval policy and the use is
+    /// not `approved`, outside its validity period with `KeyNotYetValid` or
+    /// `KeyExpired`, with `RateLimited` if its rate limit allows no use now,
+    /// and with `TimeUnavailable` if it needs the time and the clock tells
+    /// none. The new statistics are not stored.
+    fn advance(&mut self, handle: KeyHandle, data: &[u8], approved: bool) -> Result<Entry, Error> {
+        let mut entry = self.load(handle)?;
+        // Keys without a limit have `NO_LIMIT`, which no count reaches
+        if entry.uses >= entry.limit {
+            return Err(KeyExhausted);
+        }
+        if entry.approvals_needed != 0 && !approved {
+            return Err(InsufficientApprovals);
+        }
+        let now = self.now();
+        if (entry.not_before, entry.not_after) != ALWAYS_VALID {
+            let now = now.ok_or(TimeUnavailable)?;
+            if now < entry.not_before {
+                return Err(KeyNotYetValid);
+            }
+            if now >= entry.not_after {
+                return Err(KeyExpired);
+            }
+        }
+        if entry.rate != NO_RATE_LIMIT {
+            let now = now.ok_or(TimeUnavailable)?;
+            measure!(RateLimit, Result::is_ok, entry.take_token(now))?;
+        }
+        entry.uses += 1;
+        if let Some(ref mut counter) = self.counter {
+            counter.advance(handle, entry.uses)?;
+        }
+        entry.last_used = now.unwrap_or(0);
+        entry.chain = usage_chain(&entry.chain, data);
+        Ok(entry)
+    }
+
+    /// Signs `msg` with the key for `handle`. Fails with `KeyExhausted` if
+    /// the key has reached its limit, with `KeyNotYetValid` or `KeyExpired`
+    /// outside its validity period, with `RateLimited` if its rate limit
+    /// allows no signature now (or `TimeUnavailable` if either needs the
+    /// time and the clock tells none), and with the counter's error if the
+    /// `UsageCounter` can't be advanced. Keys with an approval policy fail
+    /// with `InsufficientApprovals`; they sign with `sign_approved`.
+    pub fn sign<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, msg: &Message)
+                            -> Result<Signature, Error> {
+        let entry = self.advance(handle, &msg[..], false)?;
+        self.store(&entry)?;
+        Ok(secp.sign(msg, &entry.sk))
+    }
+
+    /// Signs `msg` with the key for `handle` like `sign`, given the
+    /// `approvals` its approval policy needs, if it has one. Fails with
+    /// `InsufficientApprovals` if they are not enough, and otherwise like
+    /// `sign`.
+    #[cfg(feature = "hashes")]
+    pub fn sign_approved<C: Signing + Verification>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, msg: &Message,
+                                                    approvals: &[Approval]) -> Result<Signature, Error> {
+        self.check_approvals(secp, handle, msg, approvals)?;
+        let entry = self.advance(handle, &msg[..], true)?;
+        self.store(&entry)?;
+        Ok(secp.sign(msg, &entry.sk))
+    }
+
+    /// Makes a presignature for the key of `handle`: picks a nonce and does
+    /// the point multiplication and inversion for it now, so that
+    /// `sign_presigned` only needs scalar arithmetic. Unlike lookups, this
+    /// reveals the key's use of the vault, as does the number of
+    /// presignatures held.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn presign<C: Signing, R: RngCore + CryptoRng>(&mut self, secp: &Secp256k1<C>, rng: &mut R,
+                                                       handle: KeyHandle) -> Result<PresignatureId, Error> {
+        self.load(handle)?;
+        let (k_inv, r) = loop {
+            let mut k = SecretKey::new(secp, rng);
+            let r = Scalar::from_be_bytes_mod_order(&PublicKey::from_secret_key(secp, &k).x_coordinate());
+            k.inv_assign(secp);
+            if !r.is_zero() {
+                break (k, r.to_be_bytes());
+            }
+        };
+        // Used presignatures are only dropped here, where it reveals nothing
+        self.presignatures.retain(|p| p.live != 0);
+        let id = self.next_presignature;
+        self.next_presignature = id.checked_add(1).expect("presignature ids exhausted");
+        self.presignatures.push(Presignature { id, handle, k_inv, r, live: !0 });
+        Ok(PresignatureId(id))
+    }
+
+    /// The unused presignatures of the key for `handle`, oldest first
+    pub fn presignatures(&self, handle: KeyHandle) -> Result<Vec<PresignatureId>, Error> {
+        self.load(handle)?;
+        Ok(self.presignatures.iter().filter(|p| p.handle == handle && p.live != 0)
+            .map(|p| PresignatureId(p.id)).collect())
+    }
+
+    /// Uses up the presignature `id` and copies out the entry for `handle`
+    /// with its usage statistics advanced for `msg`, like `advance`. The
+    /// presignature is gone even if this fails. The new statistics are not
+    /// stored.
+    fn advance_presigned(&mut self, handle: KeyHandle, id: PresignatureId, msg: &Message)
+                         -> Result<(Entry, Presignature), Error> {
+        let presig = self.take_presignature(handle, id)?;
+        Ok((self.advance(handle, &msg[..], false)?, presig))
+    }
+
+    /// Signs `msg` with the key for `handle` and its presignature `id`, which
+    /// is used up by this call whether it succeeds or not. The signature
+    /// counts towards the key's limit like one from `sign`. Fails with
+    /// `UnknownPresignature` if `id` is not an unused presignature of this
+    /// key, and otherwise like `sign`.
+    pub fn sign_presigned<C>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, id: PresignatureId,
+                             msg: &Message) -> Result<Signature, Error> {
+        let (entry, presig) = self.advance_presigned(handle, id, msg)?;
+        self.store(&entry)?;
+        complete_presigned(secp, &entry, &presig, msg)
+    }
+
+    /// Evaluates an oblivious PRF (see the `oprf` module) with the key for
+    /// `handle` on the blinded element `blinded`. Each evaluation counts
+    /// towards the key's limit like a signature, which can cap e.g. the
+    /// password guesses made against a hardened password. Fails like
+    /// `sign`.
+    pub fn evaluate_oprf<C: Verification>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, blinded: &PublicKey)
+                                          -> Result<PublicKey, Error> {
+        let entry = self.advance(handle, &blinded.serialize(), false)?;
+        self.store(&entry)?;
+        evaluate_oprf(secp, &entry, blinded)
+    }
+
+    /// Signs a transaction input's `sighash` with the key for `handle` like
+    /// `Secp256k1::sign_input`, and encodes the signature for the witness.
+    /// Each signature counts towards the key's limit like one from `sign`.
+    /// Fails with `InvalidSighashType` (without using the key) if
+    /// `sighash_type` is not allowed for `spend`, and otherwise like `sign`.
+    /// Requires compilation with the "psbt" feature.
+    #[cfg(feature = "psbt")]
+    pub fn sign_input<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, sighash: &Message,
+                                  spend: Spend, sighash_type: SighashType, aux_rand: &[u8; 32])
+                                  -> Result<Vec<u8>, Error> {
+        psbt::check_sighash_type(spend, sighash_type)?;
+        let entry = self.advance(handle, &sighash[..], false)?;
+        self.store(&entry)?;
+        secp.sign_input(sighash, spend, sighash_type, &entry.sk, aux_rand)
+    }
+
+    /// Removes and wipes the key for `handle` and its presignatures. Unlike
+    /// lookups, this reveals the key's position in the vault.
+    pub fn remove(&mut self, handle: KeyHandle) -> Result<(), Error> {
+        let index = self.entries.iter().position(|e| e.handle == handle).ok_or(UnknownKeyHandle)?;
+        self.entries.remove(index);
+        self.presignatures.retain(|p| p.handle != handle);
+        self.approvers.retain(|a| a.0 != handle);
+        Ok(())
+    }
+
+    /// Removes and wipes the key for `handle` like `remove`, after
+    /// advancing the counter, if any, to the `DESTROYED` tombstone. Fails
+    /// with the counter's error, keeping the key, if it can't be advanced.
+    #[cfg(feature = "hashes")]
+    pub(crate) fn shred(&mut self, handle: KeyHandle) -> Result<(), Error> {
+        if !self.entries.iter().any(|e| e.handle == handle) {
+            return Err(UnknownKeyHandle);
+        }
+        if let Some(ref mut counter) = self.counter {
+            counter.advance(handle, DESTROYED)?;
+        }
+        self.remove(handle)
+    }
+
+    /// The handles of all keys, oldest first
+    pub fn handles(&self) -> Vec<KeyHandle> {
+        self.entries.iter().map(|e| e.handle).collect()
+    }
+
+    /// The number of keys in the vault
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the vault holds no keys
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// The signature `s = k^-1 (z + r d)` for `msg` from the key in `entry`
+/// and the nonce in `presig`
+fn complete_presigned<C>(secp: &Secp256k1<C>, entry: &Entry, presig: &Presignature, msg: &Message)
+                         -> Result<Signature, Error> {
+    let r = Scalar::from_be_bytes(presig.r)?;
+    let mut d = Scalar::from_secret_key(&entry.sk);
+    let mut k_inv = Scalar::from_secret_key(&presig.k_inv);
+    let mut t = Scalar::from_be_bytes_mod_order(&msg.0).add(&r.mul(&d));
+    let s = k_inv.mul(&t);
+    for temp in [&mut d, &mut k_inv, &mut t].iter_mut() {
+        temp.wipe();
+    }
+
+    let mut compact = [0u8; constants::COMPACT_SIGNATURE_SIZE];
+    compact[..32].copy_from_slice(&presig.r);
+    compact[32..].copy_from_slice(&s[..]);
+    let mut sig = Signature::from_compact_bytes(&compact)?;
+    sig.normalize_s(secp);
+    Ok(sig)
+}
+
+/// `k * B` for the key `k` in `entry` and the blinded element `B`
+fn evaluate_oprf<C: Verification>(secp: &Secp256k1<C>, entry: &Entry, blinded: &PublicKey)
+                                  -> Result<PublicKey, Error> {
+    let mut k = Scalar::from_secret_key(&entry.sk);
+    let mut evaluated = *blinded;
+    let ret = evaluated.mul_assign(secp, &k);
+    k.wipe();
+    ret.map(|_| evaluated)
+}
+
+/// All ones if `a == b` and zero otherwise, without branching
+#[cfg(not(feature = "fast-vault-lookup"))]
+#[inline]
+fn ct_eq_mask(a: u64, b: u64) -> u64 {
+    // The top bit of `x | -x` is set unless `x` is 0
+    let x = a ^ b;
+    let mask = ((x | x.wrapping_neg()) >> 63).wrapping_sub(1);
+    // Read back through a volatile pointer so the optimizer cannot branch on it
+    unsafe { ptr::read_volatile(&mask) }
+}
+
+/// `a` where `mask` is all ones and `b` where it is zero
+#[cfg(not(feature = "fast-vault-lookup"))]
+#[inline]
+fn ct_select(mask: u64, a: u64, b: u64) -> u64 {
+    (a & mask) | (b & !mask)
+}
+
+#[cfg(test)]
+thread_local!(static VISITS: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0));
+
+/// Counts an entry read or written by an oblivious lookup, in tests
+#[cfg(not(feature = "fast-vault-lookup"))]
+#[inline]
+fn visit() {
+    #[cfg(test)]
+    VISITS.with(|v| v.set(v.get() + 1));
+}
+
+// The serialized vault, which only ever exists inside the protected file:
+// magic and version (8 bytes), generation (8), next handle (4), key count
+// (4), then for each key its handle (4), use count (8), limit (8, with
+// `NO_LIMIT` for none) and secret key (32). Version 2 adds the next
+// presignature id (8) and presignature count (4), then for each unused
+// presignature its id (8), key handle (4), r (32) and inverted nonce (32).
+// Version 3 adds, for each key in the same order, its last use time (8)
+// and usage chain (32), to which version 4 adds its validity period (8
+// and 8, with `ALWAYS_VALID` for none), and version 5 its rate limit's
+// uses, interval and burst (8 each, all zero for none), bucket tokens (8)
+// and last refill time (8). Version 6 ends with the approval policy count
+// (4), then for each policy its key handle (4), threshold (4), approver
+// count (4) and approvers (33 each). Integers are little-endian.
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC: &'static [u8; 8] = b"secpvlt\x06";
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC_V5: &'static [u8; 8] = b"secpvlt\x05";
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC_V4: &'static [u8; 8] = b"secpvlt\x04";
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC_V3: &'static [u8; 8] = b"secpvlt\x03";
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC_V2: &'static [u8; 8] = b"secpvlt\x02";
+#[cfg(any(test, feature = "protected-fs"))]
+const MAGIC_V1: &'static [u8; 8] = b"secpvlt\x01";
+#[cfg(any(test, feature = "protected-fs"))]
+const HEADER_SIZE: usize = 24;
+#[cfg(any(test, feature = "protected-fs"))]
+const ENTRY_SIZE: usize = 4 + 8 + 8 + constants::SECRET_KEY_SIZE;
+#[cfg(any(test, feature = "protected-fs"))]
+const PRESIGNATURE_HEADER_SIZE: usize = 8 + 4;
+#[cfg(any(test, feature = "protected-fs"))]
+const PRESIGNATURE_SIZE: usize = 8 + 4 + 32 + constants::SECRET_KEY_SIZE;
+#[cfg(any(test, feature = "protected-fs"))]
+const USAGE_SIZE: usize = 8 + 32;
+#[cfg(any(test, feature = "protected-fs"))]
+const VALIDITY_SIZE: usize = 8 + 8;
+#[cfg(any(test, feature = "protected-fs"))]
+const RATE_LIMIT_SIZE: usize = 5 * 8;
+
+#[cfg(any(test, feature = "protected-fs"))]
+fn write_u32(out: &mut Vec<u8>, n: u32) {
+    out.extend_from_slice(&u32_bytes(n));
+}
+
+#[cfg(any(test, feature = "protected-fs"))]
+fn write_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend_from_slice(&u64_bytes(n));
+}
+
+/// The format version with the magic bytes `magic`, if any
+#[cfg(any(test, feature = "protected-fs"))]
+fn format_version(magic: &[u8]) -> Option<u32> {
+    if *magic == MAGIC[..] {
+        Some(6)
+    } else if *magic == MAGIC_V5[..] {
+        Some(5)
+    } else if *magic == MAGIC_V4[..] {
+        Some(4)
+    } else if *magic == MAGIC_V3[..] {
+        Some(3)
+    } else if *magic == MAGIC_V2[..] {
+        Some(2)
+    } else if *magic == MAGIC_V1[..] {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+#[cfg(any(test, feature = "protected-fs"))]
+impl Vault {
+    /// Serializes the vault, secret keys included, with `generation`
+    fn to_bytes(&self, generation: u64) -> Vec<u8> {
+        let live: Vec<&Presignature> = self.presignatures.iter().filter(|p| p.live != 0).collect();
+        let mut out = Vec::with_capacity(HEADER_SIZE + ENTRY_SIZE * self.entries.len() +
+                                         PRESIGNATURE_HEADER_SIZE + PRESIGNATURE_SIZE * live.len() +
+                                         (USAGE_SIZE + VALIDITY_SIZE + RATE_LIMIT_SIZE) * self.entries.len());
+        out.extend_from_slice(MAGIC);
+        write_u64(&mut out, generation);
+        write_u32(&mut out, self.next_handle);
+        write_u32(&mut out, self.entries.len() as u32);
+        for entry in &self.entries {
+            write_u32(&mut out, entry.handle.0);
+            write_u64(&mut out, entry.uses);
+            write_u64(&mut out, entry.limit);
+            out.extend_from_slice(&entry.sk.secret_bytes()[..]);
+        }
+        write_u64(&mut out, self.next_presignature);
+        write_u32(&mut out, live.len() as u32);
+        for presig in live {
+            write_u64(&mut out, presig.id);
+            write_u32(&mut out, presig.handle.0);
+            out.extend_from_slice(&presig.r);
+            out.extend_from_slice(&presig.k_inv.secret_bytes()[..]);
+        }
+        for entry in &self.entries {
+            write_u64(&mut out, entry.last_used);
+            out.extend_from_slice(&entry.chain);
+            write_u64(&mut out, entry.not_before);
+            write_u64(&mut out, entry.not_after);
+            for &n in &[entry.rate.uses, entry.rate.interval, entry.rate.burst, entry.tokens, entry.refilled_at] {
+                write_u64(&mut out, n);
+            }
+        }
+        write_u32(&mut out, self.approvers.len() as u32);
+        for &(handle, ref approvers) in &self.approvers {
+            write_u32(&mut out, handle.0);
+            let threshold = self.entries.iter().find(|e| e.handle == handle).map_or(0, |e| e.approvals_needed);
+            write_u32(&mut out, threshold as u32);
+            write_u32(&mut out, approvers.len() as u32);
+            for pk in approvers {
+                out.extend_from_slice(&pk.serialize());
+            }
+        }
+        out
+    }
+
+    /// Parses the output of `to_bytes`, returning the vault (without a
+    /// counter or clock) and its generation. Fails with `Storage` if `data` is
+    /// malformed.
+    fn from_bytes<C: Signing>(secp: &Secp256k1<C>, data: &[u8]) -> Result<(Vault, u64), Error> {
+        if data.len() < HEADER_SIZE {
+            return Err(Storage);
+        }
+        let version = format_version(&data[..8]).ok_or(Storage)?;
+        let v1 = version == 1;
+        let generation = read_u64(&data[8..]);
+        let next_handle = read_u32(&data[16..]);
+        let count = read_u32(&data[20..]) as usize;
+        let keys_end = count.checked_mul(ENTRY_SIZE).and_then(|n| n.checked_add(HEADER_SIZE)).ok_or(Storage)?;
+        let min_len = if v1 { keys_end } else { keys_end.checked_add(PRESIGNATURE_HEADER_SIZE).ok_or(Storage)? };
+        if min_len > data.len() || (v1 && min_len != data.len()) {
+            return Err(Storage);
+        }
+
+        let mut vault = Vault {
+            entries: Vec::with_capacity(count),
+            next_handle,
+            presignatures: Vec::new(),
+            approvers: Vec::new(),
+            next_presignature: 1,
+            counter: None,
+            clock: None,
+        };
+        for chunk in data[HEADER_SIZE..keys_end].chunks(ENTRY_SIZE) {
+            let handle = KeyHandle(read_u32(chunk));
+            if handle.0 >= next_handle || vault.entries.iter().any(|e| e.handle == handle) {
+                return Err(Storage);
+            }
+            let sk = SecretKey::from_slice(&chunk[20..]).map_err(|_| Storage)?;
+            vault.entries.push(Entry {
+                handle,
+                sk,
+                pk: PublicKey::from_secret_key(secp, &sk),
+                uses: read_u64(&chunk[4..]),
+                limit: read_u64(&chunk[12..]),
+                last_used: 0,
+                chain: [0; 32],
+                not_before: ALWAYS_VALID.0,
+                not_after: ALWAYS_VALID.1,
+                rate: NO_RATE_LIMIT,
+                tokens: 0,
+                refilled_at: 0,
+                approvals_needed: 0,
+            });
+        }
+        if v1 {
+            return Ok((vault, generation));
+        }
+
+        let presigs = &data[keys_end..];
+        vault.next_presignature = read_u64(presigs);
+        let presigs_end = (read_u32(&presigs[8..]) as usize).checked_mul(PRESIGNATURE_SIZE)
+            .and_then(|n| n.checked_add(PRESIGNATURE_HEADER_SIZE)).ok_or(Storage)?;
+        let trailer_size = match version {
+            2 => 0,
+            3 => USAGE_SIZE,
+            4 => USAGE_SIZE + VALIDITY_SIZE,
+            _ => USAGE_SIZE + VALIDITY_SIZE + RATE_LIMIT_SIZE,
+        };
+        let trailers_end = count.checked_mul(trailer_size).and_then(|n| n.checked_add(presigs_end)).ok_or(Storage)?;
+        // Version 6 has a policy count at least
+        let min_len = if version >= 6 { trailers_end.checked_add(4).ok_or(Storage)? } else { trailers_end };
+        if min_len > presigs.len() || (version < 6 && min_len != presigs.len()) {
+            return Err(Storage);
+        }
+        for chunk in presigs[PRESIGNATURE_HEADER_SIZE..presigs_end].chunks(PRESIGNATURE_SIZE) {
+            let id = read_u64(chunk);
+            let handle = KeyHandle(read_u32(&chunk[8..]));
+            if id >= vault.next_presignature || vault.presignatures.iter().any(|p| p.id == id) ||
+               !vault.entries.iter().any(|e| e.handle == handle) {
+                return Err(Storage);
+            }
+            let mut r = [0u8; 32];
+            r.copy_from_slice(&chunk[12..44]);
+            match Scalar::from_be_bytes(r) {
+                Ok(ref r) if !r.is_zero() => {}
+                _ => return Err(Storage),
+            }
+            vault.presignatures.push(Presignature {
+                id,
+                handle,
+                k_inv: SecretKey::from_slice(&chunk[44..]).map_err(|_| Storage)?,
+                r,
+                live: !0,
+            });
+        }
+        if version == 2 {
+            return Ok((vault, generation));
+        }
+        for (entry, chunk) in vault.entries.iter_mut().zip(presigs[presigs_end..].chunks(trailer_size)) {
+            entry.last_used = read_u64(chunk);
+            entry.chain.copy_from_slice(&chunk[8..40]);
+            if version >= 4 {
+                entry.not_before = read_u64(&chunk[40..]);
+                entry.not_after = read_u64(&chunk[48..]);
+            }
+            if version >= 5 {
+                entry.rate = RateLimit {
+                    uses: read_u64(&chunk[56..]),
+                    interval: read_u64(&chunk[64..]),
+                    burst: read_u64(&chunk[72..]),
+                };
+                entry.tokens = read_u64(&chunk[80..]);
+                entry.refilled_at = read_u64(&chunk[88..]);
+                if entry.rate != NO_RATE_LIMIT && (entry.rate.uses == 0 || entry.rate.interval == 0) {
+                    return Err(Storage);
+                }
+            }
+        }
+        if version < 6 {
+            return Ok((vault, generation));
+        }
+
+        let mut policies = &presigs[trailers_end..];
+        let policy_count = read_u32(policies);
+        policies = &policies[4..];
+        for _ in 0..policy_count {
+            if policies.len() < 12 {
+                return Err(Storage);
+            }
+            let handle = KeyHandle(read_u32(policies));
+            let threshold = read_u32(&policies[4..]) as u64;
+            let approver_count = read_u32(&policies[8..]) as usize;
+            let size = approver_count.checked_mul(constants::PUBLIC_KEY_SIZE).and_then(|n| n.checked_add(12))
+                .ok_or(Storage)?;
+            if size > policies.len() || threshold == 0 || threshold > approver_count as u64 ||
+               vault.approvers.iter().any(|a| a.0 == handle) {
+                return Err(Storage);
+            }
+            let approvers = policies[12..size].chunks(constants::PUBLIC_KEY_SIZE)
+                .map(|pk| PublicKey::from_slice(pk).map_err(|_| Storage))
+                .collect::<Result<Vec<_>, _>>()?;
+            vault.entries.iter_mut().find(|e| e.handle == handle).ok_or(Storage)?.approvals_needed = threshold;
+            vault.approvers.push((handle, approvers));
+            policies = &policies[size..];
+        }
+        if !policies.is_empty() {
+            return Err(Storage);
+        }
+        Ok((vault, generation))
+    }
+}
+
+/// File key label for `sealing::derive_bytes`
+#[cfg(feature = "protected-fs")]
+const FILE_KEY_LABEL: &'static [u8] = b"vault file key";
+
+/// A `Vault` persisted in an SGX protected file. Changes only reach the
+/// file on `save`.
+///
+/// The file key is derived from the sealing key at the current SVNs, so
+/// after an SVN bump the file has to be opened with `open_at_svn` for the
+/// old SVNs; the next `save` moves it to the new ones.
+#[cfg(feature = "protected-fs")]
+pub struct FileBackedVault {
+    vault: Vault,
+    path: PathBuf,
+    key_policy: KeyPolicy,
+    file_key: [u8; 16],
+    generation: u64,
+}
+
+#[cfg(feature = "protected-fs")]
+impl fmt::Debug for FileBackedVault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FileBackedVault({}, {:?}, generation {})", self.path.display(), self.vault, self.generation)
+    }
+}
+
+#[cfg(feature = "protected-fs")]
+impl Drop for FileBackedVault {
+    fn drop(&mut self) {
+        wipe(&mut self.file_key);
+    }
+}
+
+/// Reads the vault file at `path`, or returns `None` if there is none
+#[cfg(feature = "protected-fs")]
+fn read_file<C: Signing>(secp: &Secp256k1<C>, path: &Path, file_key: &[u8; 16])
+                         -> Result<Option<(Vault, u64)>, Error> {
+    let mut file = match SgxFile::open_ex(path, file_key) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(_) => return Err(Storage),
+    };
+    let mut data = Vec::new();
+    let result = file.read_to_end(&mut data).map_err(|_| Storage)
+        .and_then(|_| Vault::from_bytes(secp, &data));
+    wipe(&mut data);
+    result.map(Some)
+}
+
+/// Reads only the generation from the header of the vault file at `path`,
+/// or returns `None` if there is no file
+#[cfg(feature = "protected-fs")]
+fn read_generation(path: &Path, file_key: &[u8; 16]) -> Result<Option<u64>, Error> {
+    let mut file = match SgxFile::open_ex(path, file_key) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(_) => return Err(Storage),
+    };
+    // The magic and the generation
+    let mut header = [0u8; 16];
+    file.read_exact(&mut header).map_err(|_| Storage)?;
+    format_version(&header[..8]).ok_or(Storage)?;
+    Ok(Some(read_u64(&header[8..])))
+}
+
+#[cfg(feature = "protected-fs")]
+impl FileBackedVault {
+    /// Opens the vault at `path`, whose key is derived under `key_policy`,
+    /// or starts an empty one (generation 0) if there is no file yet. Fails
+    /// with `Storage` if the file can't be read or decrypted, e.g. because
+    /// it was tampered with or belongs to another enclave.
+    pub fn open<C: Signing, P: AsRef<Path>>(secp: &Secp256k1<C>, path: P, key_policy: KeyPolicy)
+                                            -> Result<FileBackedVault, Error> {
+        let mut file_key = [0u8; 16];
+        sealing::derive_bytes(key_policy, FILE_KEY_LABEL, &mut file_key)?;
+        FileBackedVault::open_with_key(secp, path.as_ref(), key_policy, file_key)
+    }
+
+    /// Like `open`, but for a file last saved at older SVNs
+    pub fn open_at_svn<C: Signing, P: AsRef<Path>>(secp: &Secp256k1<C>, path: P, key_policy: KeyPolicy,
+                                                   isv_svn: u16, cpu_svn: [u8; 16])
+                                                   -> Result<FileBackedVault, Error> {
+        let mut file_key = [0u8; 16];
+        sealing::derive_bytes_for_svn(key_policy, FILE_KEY_LABEL, isv_svn, cpu_svn, &mut file_key)?;
+        FileBackedVault::open_with_key(secp, path.as_ref(), key_policy, file_key)
+    }
+
+    fn open_with_key<C: Signing>(secp: &Secp256k1<C>, path: &Path, key_policy: KeyPolicy, file_key: [u8; 16])
+                                 -> Result<FileBackedVault, Error> {
+        let mut ret = FileBackedVault {
+            vault: Vault::new(),
+            path: path.to_path_buf(),
+            key_policy,
+            file_key,
+            generation: 0,
+        };
+        if let Some((vault, generation)) = read_file(secp, path, &ret.file_key)? {
+            ret.vault = vault;
+            ret.generation = generation;
+        }
+        Ok(ret)
+    }
+
+    /// Writes the vault to its file, bumping the generation. Fails with
+    /// `VaultRollback` if the file no longer holds the generation this
+    /// vault last read or wrote, i.e. it was replaced (with an older copy or
+    /// by another writer), and with `Storage` if it can't be written. With
+    /// the "production-hardening" feature it fails with `DebugEnclave` in a
+    /// debug enclave. Only the file's header is read for the check.
+    pub fn save(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "production-hardening")]
+        ::hardening::check_secret_export()?;
+        // Saved files start at generation 1
+        let expected = if self.generation == 0 { None } else { Some(self.generation) };
+        let on_disk = read_generation(&self.path, &self.file_key)?;
+        if on_disk != expected {
+            return Err(VaultRollback);
+        }
+
+        let mut file_key = [0u8; 16];
+        sealing::derive_bytes(self.key_policy, FILE_KEY_LABEL, &mut file_key)?;
+        let generation = self.generation + 1;
+        let mut data = self.vault.to_bytes(generation);
+        let result = SgxFile::create_ex(&self.path, &file_key)
+            .and_then(|mut file| file.write_all(&data).and_then(|_| file.flush()))
+            .map_err(|_| Storage);
+        wipe(&mut data);
+        if result.is_ok() {
+            self.file_key = file_key;
+            self.generation = generation;
+        }
+        wipe(&mut file_key);
+        result
+    }
+
+    /// Signs `msg` with the key for `handle` like `Vault::sign`, but first
+    /// saves the vault with the key's use count advanced, so that the count
+    /// survives a restart. Fails like `save` (and makes no signature) if the
+    /// vault can't be saved.
+    pub fn sign<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, msg: &Message)
+                            -> Result<Signature, Error> {
+        let previous = self.vault.load(handle)?;
+        let entry = self.vault.advance(handle, &msg[..], false)?;
+        self.vault.store(&entry)?;
+        if let Err(e) = self.save() {
+            self.vault.store(&previous)?;
+            return Err(e);
+        }
+        Ok(secp.sign(msg, &entry.sk))
+    }
+
+    /// Signs `msg` with the key for `handle` and `approvals` like
+    /// `Vault::sign_approved`, but first saves the vault with the key's use
+    /// count advanced, which also uses up the approvals. Fails like `save`
+    /// (and makes no signature) if the vault can't be saved.
+    pub fn sign_approved<C: Signing + Verification>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle,
+                                                    msg: &Message, approvals: &[Approval])
+                                                    -> Result<Signature, Error> {
+        self.vault.check_approvals(secp, handle, msg, approvals)?;
+        let previous = self.vault.load(handle)?;
+        let entry = self.vault.advance(handle, &msg[..], true)?;
+        self.vault.store(&entry)?;
+        if let Err(e) = self.save() {
+            self.vault.store(&previous)?;
+            return Err(e);
+        }
+        Ok(secp.sign(msg, &entry.sk))
+    }
+
+    /// Signs `msg` with the key for `handle` and its presignature `id` like
+    /// `Vault::sign_presigned`, but first saves the vault without the
+    /// presignature and with the key's use count advanced. If the vault
+    /// can't be saved this fails like `save`, and the presignature is still
+    /// used up.
+    pub fn sign_presigned<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, id: PresignatureId,
+                                      msg: &Message) -> Result<Signature, Error> {
+        let previous = self.vault.load(handle)?;
+        let (entry, presig) = self.vault.advance_presigned(handle, id, msg)?;
+        self.vault.store(&entry)?;
+        if let Err(e) = self.save() {
+            self.vault.store(&previous)?;
+            return Err(e);
+        }
+        complete_presigned(secp, &entry, &presig, msg)
+    }
+
+    /// Evaluates an oblivious PRF with the key for `handle` like
+    /// `Vault::evaluate_oprf`, but first saves the vault with the key's use
+    /// count advanced. Fails like `save` (and evaluates nothing) if the
+    /// vault can't be saved.
+    pub fn evaluate_oprf<C: Signing + Verification>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle,
+                                                    blinded: &PublicKey) -> Result<PublicKey, Error> {
+        let previous = self.vault.load(handle)?;
+        let entry = self.vault.advance(handle, &blinded.serialize(), false)?;
+        self.vault.store(&entry)?;
+        if let Err(e) = self.save() {
+            self.vault.store(&previous)?;
+            return Err(e);
+        }
+        evaluate_oprf(secp, &entry, blinded)
+    }
+
+    /// Signs a transaction input's `sighash` with the key for `handle` like
+    /// `Vault::sign_input`, but first saves the vault with the key's use
+    /// count advanced. Fails like `save` (and makes no signature) if the
+    /// vault can't be saved. Requires compilation with the "psbt" feature.
+    #[cfg(feature = "psbt")]
+    pub fn sign_input<C: Signing>(&mut self, secp: &Secp256k1<C>, handle: KeyHandle, sighash: &Message,
+                                  spend: Spend, sighash_type: SighashType, aux_rand: &[u8; 32])
+                                  -> Result<Vec<u8>, Error> {
+        psbt::check_sighash_type(spend, sighash_type)?;
+        let previous = self.vault.load(handle)?;
+        let entry = self.vault.advance(handle, &sighash[..], false)?;
+        self.vault.store(&entry)?;
+        if let Err(e) = self.save() {
+            self.vault.store(&previous)?;
+            return Err(e);
+        }
+        secp.sign_input(sighash, spend, sighash_type, &entry.sk, aux_rand)
+    }
+
+    /// The generation of the file as last read or written. Comparing it with
+    /// a trusted record, such as a monotonic counter, detects a rollback
+    /// that happened while the enclave was down.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The in-memory vault
+    #[inline]
+    pub fn vault(&self) -> &Vault {
+        &self.vault
+    }
+
+    /// The in-memory vault, for changes to save later
+    #[inline]
+    pub fn vault_mut(&mut self) -> &mut Vault {
+        &mut self.vault
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use std::sync::{Arc, Mutex};
+
+    use super::{Clock, KeyHandle, PresignatureId, RateLimit, UsageCounter, Vault, HEADER_SIZE, MAGIC_V1, MAGIC_V2,
+                MAGIC_V3, MAGIC_V4, MAGIC_V5};
+    use super::super::{Error, Message, Scalar, Secp256k1};
+    use super::super::Error::{InvalidRateLimit, KeyExhausted, KeyExpired, KeyNotYetValid, RateLimited, Storage,
+                              TimeUnavailable, UnknownKeyHandle, UnknownPresignature};
+
+    // Records the counts it is advanced to, and fails once `fail` is set
+    #[derive(Clone, Default)]
+    struct TestCounter {
+        log: Arc<Mutex<Vec<(KeyHandle, u64)>>>,
+        fail: Arc<Mutex<bool>>,
+    }
+
+    impl UsageCounter for TestCounter {
+        fn advance(&mut self, handle: KeyHandle, uses: u64) -> Result<(), Error> {
+            if *self.fail.lock().unwrap() {
+                return Err(Storage);
+            }
+            self.log.lock().unwrap().push((handle, uses));
+            Ok(())
+        }
+    }
+
+    // Ticks one second per reading, and tells no time once `stopped` is set
+    #[derive(Clone, Default)]
+    struct TestClock {
+        time: Arc<Mutex<u64>>,
+        stopped: Arc<Mutex<bool>>,
+    }
+
+    impl Clock for TestClock {
+        fn now(&mut self) -> Option<u64> {
+            if *self.stopped.lock().unwrap() {
+                return None;
+            }
+            let mut time = self.time.lock().unwrap();
+            *time += 1;
+            Some(*time)
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        assert!(vault.is_empty());
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        assert!(a != b);
+        assert_eq!(vault.handles(), vec![a, b]);
+
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let sig = vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(s.verify(&msg, &sig, &vault.public_key(a).unwrap()), Ok(()));
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.uses(a), Ok(2));
+        assert_eq!(vault.uses(b), Ok(0));
+
+        vault.remove(a).unwrap();
+        assert_eq!(vault.len(), 1);
+        assert_eq!(vault.public_key(a), Err(UnknownKeyHandle));
+        assert_eq!(vault.sign(&s, a, &msg), Err(UnknownKeyHandle));
+        assert_eq!(vault.remove(a), Err(UnknownKeyHandle));
+        // Handles are not reused
+        let c = vault.generate(&s, &mut thread_rng());
+        assert!(c != a && c != b);
+        assert_eq!(vault.public_key(KeyHandle::from_u32(c.to_u32())), vault.public_key(c));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn key_ids() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let id_a = vault.key_id(a).unwrap();
+        assert_eq!(id_a, vault.public_key(a).unwrap().fingerprint());
+        assert_eq!(vault.key_usage(a).unwrap().key_id(), id_a);
+        assert_eq!(vault.find(&id_a), Ok(a));
+        assert_eq!(vault.find(&vault.key_id(b).unwrap()), Ok(b));
+
+        vault.remove(a).unwrap();
+        assert_eq!(vault.key_id(a), Err(UnknownKeyHandle));
+        assert_eq!(vault.find(&id_a), Err(UnknownKeyHandle));
+    }
+
+    #[test]
+    fn limits_and_counters() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let counter = TestCounter::default();
+        vault.set_counter(counter.clone());
+
+        // A one-time key
+        vault.set_limit(a, Some(1)).unwrap();
+        assert_eq!(vault.limit(a), Ok(Some(1)));
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.sign(&s, a, &msg), Err(KeyExhausted));
+        assert_eq!(vault.uses(a), Ok(1));
+        // Raising the limit re-enables it
+        vault.set_limit(a, Some(2)).unwrap();
+        vault.sign(&s, a, &msg).unwrap();
+        vault.sign(&s, b, &msg).unwrap();
+        assert_eq!(*counter.log.lock().unwrap(), vec![(a, 1), (a, 2), (b, 1)]);
+
+        // No signature, and no change to the count, without the counter
+        *counter.fail.lock().unwrap() = true;
+        assert_eq!(vault.sign(&s, b, &msg), Err(Storage));
+        assert_eq!(vault.uses(b), Ok(1));
+        *counter.fail.lock().unwrap() = false;
+        vault.sign(&s, b, &msg).unwrap();
+        assert_eq!(counter.log.lock().unwrap().last(), Some(&(b, 2)));
+    }
+
+    #[test]
+    fn oprf_evaluation() {
+        let s = Secp256k1::new();
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        let (_, blinded) = s.generate_keypair(&mut thread_rng());
+        let mut vault = Vault::new();
+        let a = vault.insert(&s, sk);
+
+        let mut expected = blinded;
+        expected.mul_assign(&s, &Scalar::from_secret_key(&sk)).unwrap();
+        assert_eq!(vault.evaluate_oprf(&s, a, &blinded), Ok(expected));
+        assert_eq!(vault.uses(a), Ok(1));
+        // Evaluations are limited like signatures
+        vault.set_limit(a, Some(1)).unwrap();
+        assert_eq!(vault.evaluate_oprf(&s, a, &blinded), Err(KeyExhausted));
+        assert_eq!(vault.evaluate_oprf(&s, KeyHandle::from_u32(99), &blinded), Err(UnknownKeyHandle));
+    }
+
+    #[cfg(feature = "psbt")]
+    #[test]
+    fn input_signing() {
+        use psbt::{SighashType, Spend};
+        use super::super::Error::InvalidSighashType;
+
+        let s = Secp256k1::new();
+        let (sk, _) = s.generate_keypair(&mut thread_rng());
+        let mut vault = Vault::new();
+        let a = vault.insert(&s, sk);
+        let sighash = Message::from_slice(&[0x11; 32]).unwrap();
+        let spend = Spend::TaprootKeyPath { merkle_root: None };
+
+        let signed = vault.sign_input(&s, a, &sighash, spend, SighashType::All, &[0x22; 32]);
+        assert_eq!(signed, s.sign_input(&sighash, spend, SighashType::All, &sk, &[0x22; 32]));
+        assert_eq!(vault.uses(a), Ok(1));
+        // A type the spend doesn't allow doesn't use the key
+        assert_eq!(vault.sign_input(&s, a, &sighash, Spend::Ecdsa, SighashType::Default, &[0; 32]),
+                   Err(InvalidSighashType));
+        assert_eq!(vault.uses(a), Ok(1));
+        vault.set_limit(a, Some(1)).unwrap();
+        assert_eq!(vault.sign_input(&s, a, &sighash, Spend::Ecdsa, SighashType::All, &[0; 32]), Err(KeyExhausted));
+    }
+
+    #[test]
+    fn usage_statistics() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let msg1 = Message::from_slice(&[1; 32]).unwrap();
+        let msg2 = Message::from_slice(&[2; 32]).unwrap();
+
+        // Without a clock no times are recorded
+        vault.sign(&s, a, &msg1).unwrap();
+        let usage = vault.key_usage(a).unwrap();
+        assert_eq!((usage.handle, usage.uses, usage.limit, usage.last_used), (a, 1, None, None));
+        assert_eq!(usage.public_key, vault.public_key(a).unwrap());
+
+        let clock = TestClock::default();
+        *clock.time.lock().unwrap() = 1_000;
+        vault.set_clock(clock.clone());
+        vault.sign(&s, a, &msg2).unwrap();
+        vault.set_limit(a, Some(2)).unwrap();
+        assert_eq!(vault.sign(&s, a, &msg1), Err(KeyExhausted));
+        let usage = vault.key_usage(a).unwrap();
+        assert_eq!((usage.uses, usage.limit, usage.last_used), (2, Some(2), Some(1_001)));
+        #[cfg(feature = "hashes")]
+        {
+            use super::usage_chain;
+            assert_eq!(usage.chain, usage_chain(&usage_chain(&[0; 32], &msg1[..]), &msg2[..]));
+        }
+
+        // The other key is untouched until used
+        assert_eq!(vault.key_usage(b).unwrap().last_used, None);
+        assert_eq!(vault.key_usage(b).unwrap().chain, [0; 32]);
+        let (_, blinded) = s.generate_keypair(&mut thread_rng());
+        vault.evaluate_oprf(&s, b, &blinded).unwrap();
+        *clock.stopped.lock().unwrap() = true;
+        let presig = vault.presign(&s, &mut thread_rng(), b).unwrap();
+        vault.sign_presigned(&s, b, presig, &msg1).unwrap();
+        let usage = vault.key_usage(b).unwrap();
+        assert_eq!((usage.uses, usage.last_used), (2, None));
+        #[cfg(feature = "hashes")]
+        {
+            use super::usage_chain;
+            assert_eq!(usage.chain, usage_chain(&usage_chain(&[0; 32], &blinded.serialize()), &msg1[..]));
+        }
+
+        assert_eq!(vault.key_usages(), vec![vault.key_usage(a).unwrap(), usage]);
+        assert_eq!(vault.key_usage(KeyHandle::from_u32(99)), Err(UnknownKeyHandle));
+    }
+
+    #[test]
+    fn validity() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        vault.set_validity(a, Some(1_003), Some(1_005)).unwrap();
+        assert_eq!(vault.validity(a), Ok((Some(1_003), Some(1_005))));
+        assert_eq!(vault.validity(b), Ok((None, None)));
+
+        // Keys with a validity period need the time, others don't
+        assert_eq!(vault.sign(&s, a, &msg), Err(TimeUnavailable));
+        vault.sign(&s, b, &msg).unwrap();
+
+        let clock = TestClock::default();
+        *clock.time.lock().unwrap() = 1_000;
+        vault.set_clock(clock.clone());
+        let (_, blinded) = s.generate_keypair(&mut thread_rng());
+        assert_eq!(vault.sign(&s, a, &msg), Err(KeyNotYetValid));
+        assert_eq!(vault.evaluate_oprf(&s, a, &blinded), Err(KeyNotYetValid));
+        vault.sign(&s, a, &msg).unwrap();
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.sign(&s, a, &msg), Err(KeyExpired));
+        assert_eq!(vault.uses(a), Ok(2));
+        *clock.stopped.lock().unwrap() = true;
+        assert_eq!(vault.sign(&s, a, &msg), Err(TimeUnavailable));
+
+        // Lifting the bounds
+        vault.set_validity(a, Some(1_003), None).unwrap();
+        *clock.stopped.lock().unwrap() = false;
+        vault.sign(&s, a, &msg).unwrap();
+        vault.set_validity(a, None, None).unwrap();
+        *clock.stopped.lock().unwrap() = true;
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.set_validity(KeyHandle::from_u32(99), None, None), Err(UnknownKeyHandle));
+    }
+
+    #[test]
+    fn rate_limits() {
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let rate = RateLimit { uses: 2, interval: 10, burst: 3 };
+        vault.set_rate_limit(a, Some(rate)).unwrap();
+        assert_eq!(vault.rate_limit(a), Ok(Some(rate)));
+        assert_eq!(vault.rate_limit(b), Ok(None));
+        assert_eq!(vault.sign(&s, a, &msg), Err(TimeUnavailable));
+
+        // Each reading of the clock is a second later
+        let clock = TestClock::default();
+        *clock.time.lock().unwrap() = 100;
+        vault.set_clock(clock.clone());
+        // The bucket starts full, with a burst of three
+        for _ in 0..3 {
+            vault.sign(&s, a, &msg).unwrap();
+        }
+        assert_eq!(vault.sign(&s, a, &msg), Err(RateLimited));
+        assert_eq!(vault.uses(a), Ok(3));
+        // Other keys are not limited
+        for _ in 0..5 {
+            vault.sign(&s, b, &msg).unwrap();
+        }
+        // Two more at the end of the interval, at 110
+        *clock.time.lock().unwrap() = 109;
+        vault.sign(&s, a, &msg).unwrap();
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.sign(&s, a, &msg), Err(RateLimited));
+        // A long wait fills the bucket up to the burst only
+        *clock.time.lock().unwrap() = 1_000;
+        for _ in 0..3 {
+            vault.sign(&s, a, &msg).unwrap();
+        }
+        assert_eq!(vault.sign(&s, a, &msg), Err(RateLimited));
+        // A clock going back refills nothing
+        *clock.time.lock().unwrap() = 0;
+        assert_eq!(vault.sign(&s, a, &msg), Err(RateLimited));
+        assert_eq!(vault.uses(a), Ok(8));
+
+        vault.set_rate_limit(a, None).unwrap();
+        vault.sign(&s, a, &msg).unwrap();
+        assert_eq!(vault.set_rate_limit(a, Some(RateLimit { uses: 1, interval: 0, burst: 1 })),
+                   Err(InvalidRateLimit));
+        assert_eq!(vault.set_rate_limit(a, Some(RateLimit { uses: 0, interval: 1, burst: 1 })),
+                   Err(InvalidRateLimit));
+        assert_eq!(vault.rate_limit(a), Ok(None));
+    }
+
+    #[cfg(feature = "hashes")]
+    #[test]
+    fn approvals() {
+        use super::{Approval, ApprovalPolicy, APPROVAL_SIZE};
+        use super::super::Error::{InsufficientApprovals, InvalidApprovalPolicy};
+
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let mut vault = Vault::new();
+        let key = vault.generate(&s, &mut thread_rng());
+        let pk = vault.public_key(key).unwrap();
+        let approvers: Vec<_> = (0..3).map(|_| s.generate_keypair(&mut thread_rng())).collect();
+        let policy = ApprovalPolicy { approvers: approvers.iter().map(|a| a.1).collect(), threshold: 2 };
+        vault.set_approval_policy(key, Some(policy.clone())).unwrap();
+        assert_eq!(vault.approval_policy(key), Ok(Some(policy.clone())));
+
+        // Only approved signatures, with approvals from two approvers
+        assert_eq!(vault.sign(&s, key, &msg), Err(InsufficientApprovals));
+        assert_eq!(vault.sign_approved(&s, key, &msg, &[]), Err(InsufficientApprovals));
+        let approve = |i: usize, use_number, msg| Approval::sign(&s, &approvers[i].0, &pk, use_number, msg);
+        let one = [approve(0, 1, &msg), approve(0, 1, &msg)];
+        assert_eq!(vault.sign_approved(&s, key, &msg, &one), Err(InsufficientApprovals));
+        let two = [approve(0, 1, &msg), approve(2, 1, &msg)];
+        let sig = vault.sign_approved(&s, key, &msg, &two).unwrap();
+        assert!(s.verify(&msg, &sig, &pk).is_ok());
+        assert_eq!(vault.uses(key), Ok(1));
+
+        // Approvals can't be replayed, or used for another message
+        assert_eq!(vault.sign_approved(&s, key, &msg, &two), Err(InsufficientApprovals));
+        let other = Message::from_slice(&[7; 32]).unwrap();
+        let next = [approve(1, 2, &msg), approve(2, 2, &msg)];
+        assert_eq!(vault.sign_approved(&s, key, &other, &next), Err(InsufficientApprovals));
+        vault.sign_approved(&s, key, &msg, &next).unwrap();
+        // Nor do outsiders count
+        let (outsider, _) = s.generate_keypair(&mut thread_rng());
+        let approvals = [approve(1, 3, &msg), Approval::sign(&s, &outsider, &pk, 3, &msg)];
+        assert_eq!(vault.sign_approved(&s, key, &msg, &approvals), Err(InsufficientApprovals));
+
+        // Approvals encode to 97 bytes
+        let data = approvals[0].serialize(&s);
+        assert_eq!(data.len(), APPROVAL_SIZE);
+        assert_eq!(Approval::from_slice(&s, &data), Ok(approvals[0]));
+        assert!(Approval::from_slice(&s, &data[1..]).is_err());
+
+        // Keys without a policy sign either way
+        vault.set_approval_policy(key, None).unwrap();
+        assert_eq!(vault.approval_policy(key), Ok(None));
+        vault.sign(&s, key, &msg).unwrap();
+        vault.sign_approved(&s, key, &msg, &[]).unwrap();
+
+        for &(threshold, duplicate) in &[(0, false), (4, false), (2, true)] {
+            let mut bad = policy.clone();
+            bad.threshold = threshold;
+            if duplicate {
+                bad.approvers[1] = bad.approvers[0];
+            }
+            assert_eq!(vault.set_approval_policy(key, Some(bad)), Err(InvalidApprovalPolicy));
+        }
+        assert_eq!(vault.set_approval_policy(KeyHandle::from_u32(99), Some(policy)), Err(UnknownKeyHandle));
+    }
+
+    #[test]
+    fn presignatures() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let counter = TestCounter::default();
+        vault.set_counter(counter.clone());
+        let pk = vault.public_key(a).unwrap();
+
+        let p1 = vault.presign(&s, &mut thread_rng(), a).unwrap();
+        let p2 = vault.presign(&s, &mut thread_rng(), a).unwrap();
+        let q = vault.presign(&s, &mut thread_rng(), b).unwrap();
+        assert_eq!(vault.presignatures(a), Ok(vec![p1, p2]));
+        assert_eq!(vault.presign(&s, &mut thread_rng(), KeyHandle::from_u32(99)), Err(UnknownKeyHandle));
+
+        for (i, &id) in [p2, p1].iter().enumerate() {
+            let msg = Message::from_slice(&[i as u8 + 1; 32]).unwrap();
+            let sig = vault.sign_presigned(&s, a, id, &msg).unwrap();
+            assert!(s.verify(&msg, &sig, &pk).is_ok());
+            let mut normalized = sig;
+            normalized.normalize_s(&s);
+            assert_eq!(normalized, sig);
+            // Strictly single use
+            assert_eq!(vault.sign_presigned(&s, a, id, &msg), Err(UnknownPresignature));
+        }
+        assert_eq!(vault.presignatures(a), Ok(vec![]));
+        assert_eq!(vault.uses(a), Ok(2));
+        assert_eq!(*counter.log.lock().unwrap(), vec![(a, 1), (a, 2)]);
+
+        // Presignatures belong to their key
+        let msg = Message::from_slice(&[7; 32]).unwrap();
+        assert_eq!(vault.sign_presigned(&s, a, q, &msg), Err(UnknownPresignature));
+        assert_eq!(vault.sign_presigned(&s, b, PresignatureId::from_u64(99), &msg), Err(UnknownPresignature));
+        assert_eq!(vault.presignatures(b), Ok(vec![q]));
+
+        // A failed attempt uses the presignature up, without counting
+        vault.set_limit(b, Some(0)).unwrap();
+        assert_eq!(vault.sign_presigned(&s, b, q, &msg), Err(KeyExhausted));
+        vault.set_limit(b, None).unwrap();
+        assert_eq!(vault.sign_presigned(&s, b, q, &msg), Err(UnknownPresignature));
+        assert_eq!(vault.uses(b), Ok(0));
+
+        // And removing a key drops its presignatures
+        let q = vault.presign(&s, &mut thread_rng(), b).unwrap();
+        vault.remove(b).unwrap();
+        assert_eq!(vault.sign_presigned(&s, b, q, &msg), Err(UnknownPresignature));
+        assert_eq!(vault.presignatures(b), Err(UnknownKeyHandle));
+    }
+
+    #[cfg(not(feature = "fast-vault-lookup"))]
+    #[test]
+    fn oblivious_lookup() {
+        use super::{ct_eq_mask, VISITS};
+
+        assert_eq!(ct_eq_mask(7, 7), !0);
+        assert_eq!(ct_eq_mask(0, 0), !0);
+        assert_eq!(ct_eq_mask(7, 6), 0);
+        assert_eq!(ct_eq_mask(0, !0), 0);
+        assert_eq!(ct_eq_mask(1 << 31, 0), 0);
+        assert_eq!(ct_eq_mask(1 << 63, 0), 0);
+        assert_eq!(ct_eq_mask(!0, !0), !0);
+
+        let s = Secp256k1::new();
+        let msg = Message::from_slice(&[0x42; 32]).unwrap();
+        let mut vault = Vault::new();
+        let handles: Vec<_> = (0..5).map(|_| vault.generate(&s, &mut thread_rng())).collect();
+        let visits = |f: &mut FnMut()| {
+            VISITS.with(|v| v.set(0));
+            f();
+            VISITS.with(|v| v.get())
+        };
+
+        // Every lookup touches all five entries, whichever key it is for
+        for &handle in &[handles[0], handles[4]] {
+            assert_eq!(visits(&mut || { vault.public_key(handle).unwrap(); }), 5);
+            assert_eq!(visits(&mut || { vault.set_limit(handle, Some(3)).unwrap(); }), 10);
+            assert_eq!(visits(&mut || { vault.sign(&s, handle, &msg).unwrap(); }), 10);
+        }
+        // Unknown handles stop after the first pass
+        let unknown = KeyHandle::from_u32(99);
+        assert_eq!(visits(&mut || { vault.public_key(unknown).unwrap_err(); }), 5);
+        assert_eq!(visits(&mut || { vault.sign(&s, unknown, &msg).unwrap_err(); }), 5);
+        // Looking up an identifier compares it with every key
+        #[cfg(feature = "hashes")]
+        for &handle in &[handles[0], handles[4]] {
+            let id = vault.key_id(handle).unwrap();
+            assert_eq!(visits(&mut || { assert_eq!(vault.find(&id), Ok(handle)); }), 5);
+        }
+        // Using a presignature touches every presignature too
+        let presigs: Vec<_> = handles.iter().map(|&h| vault.presign(&s, &mut thread_rng(), h).unwrap()).collect();
+        assert_eq!(visits(&mut || { vault.sign_presigned(&s, handles[1], presigs[1], &msg).unwrap(); }), 15);
+        assert_eq!(visits(&mut || { vault.sign_presigned(&s, handles[1], presigs[1], &msg).unwrap_err(); }), 5);
+        // And picks out the right one
+        for (i, &handle) in handles.iter().enumerate() {
+            let pk = vault.public_key(handle).unwrap();
+            for (j, &other) in handles.iter().enumerate() {
+                assert_eq!(pk == vault.public_key(other).unwrap(), i == j);
+            }
+            let sig = vault.sign(&s, handle, &msg).unwrap();
+            assert!(s.verify(&msg, &sig, &pk).is_ok());
+        }
+        assert_eq!(vault.uses(handles[0]), Ok(2));
+        assert_eq!(vault.uses(handles[2]), Ok(1));
+        assert_eq!(vault.limit(handles[4]), Ok(Some(3)));
+        assert_eq!(vault.limit(handles[1]), Ok(None));
+    }
+
+    #[test]
+    fn serialization() {
+        let s = Secp256k1::new();
+        let mut vault = Vault::new();
+        vault.set_clock(TestClock::default());
+        let a = vault.generate(&s, &mut thread_rng());
+        let b = vault.generate(&s, &mut thread_rng());
+        let c = vault.generate(&s, &mut thread_rng());
+        vault.remove(a).unwrap();
+        vault.sign(&s, b, &Message::from_slice(&[1; 32]).unwrap()).unwrap();
+        vault.set_limit(c, Some(5)).unwrap();
+        vault.set_validity(c, Some(1), Some(1_000)).unwrap();
+        vault.set_rate_limit(c, Some(RateLimit { uses: 1, interval: 60, burst: 5 })).unwrap();
+        let used = vault.presign(&s, &mut thread_rng(), b).unwrap();
+        let presig = vault.presign(&s, &mut thread_rng(), c).unwrap();
+        vault.sign_presigned(&s, b, used, &Message::from_slice(&[2; 32]).unwrap()).unwrap();
+        #[cfg(feature = "hashes")]
+        let policy = {
+            let approvers = (0..2).map(|_| s.generate_keypair(&mut thread_rng()).1).collect();
+            let policy = super::ApprovalPolicy { approvers, threshold: 2 };
+            vault.set_approval_policy(b, Some(policy.clone())).unwrap();
+            policy
+        };
+
+        let data = vault.to_bytes(7);
+        let usages = vault.key_usages();
+        let (mut copy, generation) = Vault::from_bytes(&s, &data).unwrap();
+        assert_eq!(generation, 7);
+        assert_eq!(copy.handles(), vec![b, c]);
+        assert_eq!(copy.public_key(b), vault.public_key(b));
+        assert_eq!(copy.uses(b), Ok(2));
+        assert_eq!(copy.limit(b), Ok(None));
+        assert_eq!(copy.limit(c), Ok(Some(5)));
+        assert_eq!(copy.key_usages(), usages);
+        assert_eq!(copy.validity(b), Ok((None, None)));
+        assert_eq!(copy.validity(c), Ok((Some(1), Some(1_000))));
+        assert_eq!(copy.rate_limit(c), Ok(Some(RateLimit { uses: 1, interval: 60, burst: 5 })));
+        #[cfg(feature = "hashes")]
+        assert_eq!(copy.approval_policy(b), Ok(Some(policy)));
+        assert_eq!(copy.approval_policy(c), Ok(None));
+        assert_eq!(copy.presignatures(b), Ok(vec![]));
+        assert_eq!(copy.presignatures(c), Ok(vec![presig]));
+        let msg = Message::from_slice(&[3; 32]).unwrap();
+        copy.set_clock(TestClock::default());
+        assert_eq!(copy.sign_presigned(&s, c, presig, &msg), vault.sign_presigned(&s, c, presig, &msg));
+        assert_eq!(copy.generate(&s, &mut thread_rng()), vault.generate(&s, &mut thread_rng()));
+        assert!(copy.presign(&s, &mut thread_rng(), c).unwrap() > presig);
+
+        // Version 5 files have no approval policies
+        let policies_size = if cfg!(feature = "hashes") { 4 + 12 + 2 * 33 } else { 4 };
+        let trailers_end = data.len() - policies_size;
+        let mut v5 = data[..trailers_end].to_vec();
+        assert_eq!(Vault::from_bytes(&s, &v5).unwrap_err(), Storage);
+        v5[..8].copy_from_slice(MAGIC_V5);
+        let (copy, _) = Vault::from_bytes(&s, &v5).unwrap();
+        assert_eq!(copy.approval_policy(b), Ok(None));
+        assert_eq!(copy.rate_limit(c), Ok(Some(RateLimit { uses: 1, interval: 60, burst: 5 })));
+
+        // Versions 3 and 4 have no rate limits, and version 3 no validity
+        // periods either
+        let trailer_size = super::USAGE_SIZE + super::VALIDITY_SIZE + super::RATE_LIMIT_SIZE;
+        let presigs_end = trailers_end - 2 * trailer_size;
+        let mut v4 = data[..presigs_end].to_vec();
+        let mut v3 = v4.clone();
+        for trailer in data[presigs_end..trailers_end].chunks(trailer_size) {
+            v4.extend_from_slice(&trailer[..super::USAGE_SIZE + super::VALIDITY_SIZE]);
+            v3.extend_from_slice(&trailer[..super::USAGE_SIZE]);
+        }
+        assert_eq!(Vault::from_bytes(&s, &v4).unwrap_err(), Storage);
+        v4[..8].copy_from_slice(MAGIC_V4);
+        let (copy, _) = Vault::from_bytes(&s, &v4).unwrap();
+        assert_eq!(copy.validity(c), Ok((Some(1), Some(1_000))));
+        assert_eq!(copy.rate_limit(c), Ok(None));
+        assert_eq!(Vault::from_bytes(&s, &v3).unwrap_err(), Storage);
+        v3[..8].copy_from_slice(MAGIC_V3);
+        let (copy, _) = Vault::from_bytes(&s, &v3).unwrap();
+        assert_eq!(copy.key_usages(), usages);
+        assert_eq!(copy.validity(c), Ok((None, None)));
+
+        // Version 2 files have no usage statistics
+        let v2 = &data[..presigs_end];
+        assert_eq!(Vault::from_bytes(&s, v2).unwrap_err(), Storage);
+        let mut v2 = v2.to_vec();
+        v2[..8].copy_from_slice(MAGIC_V2);
+        let (copy, _) = Vault::from_bytes(&s, &v2).unwrap();
+        assert_eq!(copy.uses(b), Ok(2));
+        assert_eq!(copy.key_usage(b).unwrap().last_used, None);
+        assert_eq!(copy.presignatures(c), Ok(vec![presig]));
+
+        // Version 1 files have no presignatures
+        let keys_end = HEADER_SIZE + 2 * super::ENTRY_SIZE;
+        let mut v1 = data[..keys_end].to_vec();
+        v1[..8].copy_from_slice(MAGIC_V1);
+        let (copy, _) = Vault::from_bytes(&s, &v1).unwrap();
+        assert_eq!(copy.handles(), vec![b, c]);
+        assert_eq!(copy.presignatures(c), Ok(vec![]));
+        v1.push(0);
+        assert_eq!(Vault::from_bytes(&s, &v1).unwrap_err(), Storage);
+        assert_eq!(Vault::from_bytes(&s, &data[..keys_end]).unwrap_err(), Storage);
+
+        assert_eq!(Vault::from_bytes(&s, &data[..data.len() - 1]).unwrap_err(), Storage);
+        assert_eq!(Vault::from_bytes(&s, &data[..HEADER_SIZE]).unwrap_err(), Storage);
+        let mut bad_magic = data.clone();
+        bad_magic[7] = 7;
+        assert_eq!(Vault::from_bytes(&s, &bad_magic).unwrap_err(), Storage);
+        // A zero secret key
+        let mut bad_key = data.clone();
+        for byte in bad_key[HEADER_SIZE + 20..HEADER_SIZE + 52].iter_mut() {
+            *byte = 0;
+        }
+        assert_eq!(Vault::from_bytes(&s, &bad_key).unwrap_err(), Storage);
+    }
+}
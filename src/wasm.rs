@@ -0,0 +1,76 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # wasm32 C runtime
+//! `wasm32-unknown-unknown` has no C library. The memory functions come
+//! from `compiler_builtins` and `depend/wasm-sysroot` turns libsecp256k1's
+//! logging into no-ops, which leaves the allocation functions and `abort`,
+//! provided here on top of the Rust global allocator. Each block is preceded
+//! by a header holding its size, since `free` isn't told it.
+
+use std::alloc::{self, Layout};
+use std::{cmp, ptr};
+
+use types::{c_void, size_t};
+
+/// Size and alignment of the header, which is also the alignment of every
+/// block (`alignof(max_align_t)` on wasm32)
+const HEADER: usize = 16;
+
+fn layout(size: usize) -> Option<Layout> {
+    size.checked_add(HEADER).and_then(|total| Layout::from_size_align(total, HEADER).ok())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: size_t) -> *mut c_void {
+    let layout = match layout(size) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    let block = alloc::alloc(layout);
+    if block.is_null() {
+        return ptr::null_mut();
+    }
+    *(block as *mut usize) = size;
+    block.add(HEADER) as *mut c_void
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn free(p: *mut c_void) {
+    if p.is_null() {
+        return;
+    }
+    let block = (p as *mut u8).sub(HEADER);
+    let size = *(block as *const usize);
+    alloc::dealloc(block, layout(size).expect("allocated with this layout"));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn realloc(p: *mut c_void, size: size_t) -> *mut c_void {
+    if p.is_null() {
+        return malloc(size);
+    }
+    let ret = malloc(size);
+    if !ret.is_null() {
+        let old_size = *((p as *const u8).sub(HEADER) as *const usize);
+        ptr::copy_nonoverlapping(p as *const u8, ret as *mut u8, cmp::min(old_size, size));
+        free(p);
+    }
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn abort() -> ! {
+    panic!("libsecp256k1 called abort");
+}
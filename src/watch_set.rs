@@ -0,0 +1,138 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Watch sets
+//! `WatchSet`, a set of public keys (e.g. the authorized co-signers of a
+//! policy) whose membership queries don't reveal which entry matched. A
+//! query compares the key against every entry, reading every byte of each
+//! and without stopping at a match, so its time and memory access pattern
+//! depend only on the size of the set: a policy check inside the enclave
+//! shows whether a key was authorized, which its result says anyway, but
+//! not which of the authorized keys it was.
+//!
+//! Keys are compared by their compressed serialization. Adding keys is not
+//! oblivious, and nor is the order of the set, which is that of insertion.
+
+use std::vec::Vec;
+
+use constants;
+use key::PublicKey;
+
+/// A set of public keys with constant-time membership queries
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WatchSet {
+    keys: Vec<[u8; constants::PUBLIC_KEY_SIZE]>,
+}
+
+impl WatchSet {
+    /// Creates an empty set
+    #[inline]
+    pub fn new() -> WatchSet {
+        WatchSet { keys: Vec::new() }
+    }
+
+    /// Adds `pk`, returning false if it was already in the set
+    pub fn insert(&mut self, pk: &PublicKey) -> bool {
+        let key = pk.serialize();
+        if self.keys.contains(&key) {
+            return false;
+        }
+        self.keys.push(key);
+        true
+    }
+
+    /// The number of keys in the set
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the set is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Whether `pk` is in the set, in time which depends only on the size
+    /// of the set
+    pub fn contains(&self, pk: &PublicKey) -> bool {
+        let key = pk.serialize();
+        let mut found = false;
+        for entry in &self.keys {
+            // `|` rather than `||`, so that every entry is compared
+            found |= ::constant_time_eq(entry, &key);
+        }
+        found
+    }
+
+    /// How many of `pks` are in the set, e.g. to check a threshold of
+    /// co-signers, in time which depends only on the size of the set and
+    /// the number of `pks`. A key given twice counts twice.
+    pub fn count_contained(&self, pks: &[PublicKey]) -> usize {
+        pks.iter().map(|pk| self.contains(pk) as usize).sum()
+    }
+
+    /// The keys in the set, in the order they were added
+    pub fn keys(&self) -> Vec<PublicKey> {
+        self.keys.iter()
+            .map(|key| PublicKey::from_slice(key).expect("serialized from a public key"))
+            .collect()
+    }
+}
+
+impl<'a> From<&'a [PublicKey]> for WatchSet {
+    fn from(pks: &'a [PublicKey]) -> WatchSet {
+        let mut ret = WatchSet::new();
+        for pk in pks {
+            ret.insert(pk);
+        }
+        ret
+    }
+}
+
+#[cfg(all(test, not(feature = "verify-only")))]
+mod tests {
+    use std::vec::Vec;
+
+    use rng::thread_rng;
+    use super::WatchSet;
+    use {PublicKey, Secp256k1};
+
+    #[test]
+    fn membership() {
+        let s = Secp256k1::new();
+        let keys: Vec<PublicKey> = (0..5).map(|_| s.generate_keypair(&mut thread_rng()).1).collect();
+        let (_, outsider) = s.generate_keypair(&mut thread_rng());
+
+        let mut set = WatchSet::new();
+        assert!(set.is_empty());
+        assert!(!set.contains(&keys[0]));
+        for pk in &keys[..4] {
+            assert!(set.insert(pk));
+        }
+        assert!(!set.insert(&keys[2]));
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.keys(), &keys[..4]);
+        assert_eq!(set, WatchSet::from(&keys[..4]));
+
+        for pk in &keys[..4] {
+            assert!(set.contains(pk));
+        }
+        assert!(!set.contains(&keys[4]));
+        assert!(!set.contains(&outsider));
+        assert_eq!(set.count_contained(&[keys[0], outsider, keys[3], keys[4]]), 2);
+        assert_eq!(set.count_contained(&[keys[1], keys[1]]), 2);
+        assert_eq!(set.count_contained(&[]), 0);
+    }
+}
@@ -0,0 +1,399 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Whitelist signatures
+//! The whitelist construction of secp256k1-zkp, as used by Elements
+//! federations for peg-outs: a proof that a key `W` was whitelisted by one
+//! of a list of members, without revealing which. Each member has an online
+//! key `O_i`, kept on the signing host, and an offline key `F_i`, kept in
+//! cold storage. Whitelisting `W` takes a ring signature over the keys
+//! `O_i + t_i * (F_i + W)` with `t_i = H(F_i + W)`: the signer needs both
+//! the secret of its online key and the secret of the sum `F_i + W`, so
+//! neither a stolen online key nor the owner of `W` can whitelist alone.
+//!
+//! The ring signature is the single-ring Borromean (AOS) signature of the
+//! original module, over a challenge that commits to `W` and the whole key
+//! list. Hashes use this crate's tagged hashes, so signatures do not verify
+//! against secp256k1-zkp. Signatures are `1 + 32 * (keys + 1)` bytes long,
+//! for at most 255 keys. Requires compilation with the "hashes" feature.
+
+use std::vec::Vec;
+
+#[cfg(any(test, feature = "rand"))]
+use rand_core::{RngCore, CryptoRng};
+
+use constants;
+use hashes::{TaggedHasher, WHITELIST_CHALLENGE_TAG, WHITELIST_TWEAK_TAG};
+use key::PublicKey;
+#[cfg(any(test, feature = "rand"))]
+use key::SecretKey;
+use scalar::Scalar;
+use super::Error::{self, IncorrectSignature, InvalidSignature, InvalidWhitelist};
+use {Secp256k1, Verification};
+#[cfg(any(test, feature = "rand"))]
+use Signing;
+
+/// The most keys a whitelist can hold
+pub const MAX_WHITELIST_KEYS: usize = 255;
+
+/// The federation members' online and offline keys, in signing order
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Whitelist {
+    online: Vec<PublicKey>,
+    offline: Vec<PublicKey>,
+}
+
+impl Whitelist {
+    /// Creates an empty whitelist
+    #[inline]
+    pub fn new() -> Whitelist {
+        Whitelist::default()
+    }
+
+    /// Creates a whitelist from parallel lists of online and offline keys.
+    /// Fails with `InvalidWhitelist` if their lengths differ or exceed
+    /// `MAX_WHITELIST_KEYS`.
+    pub fn from_keys(online: &[PublicKey], offline: &[PublicKey]) -> Result<Whitelist, Error> {
+        if online.len() != offline.len() || online.len() > MAX_WHITELIST_KEYS {
+            return Err(InvalidWhitelist);
+        }
+        Ok(Whitelist { online: online.to_vec(), offline: offline.to_vec() })
+    }
+
+    /// Appends a member. Fails with `InvalidWhitelist` if the whitelist is
+    /// full or already has this online key.
+    pub fn add(&mut self, online: PublicKey, offline: PublicKey) -> Result<(), Error> {
+        if self.online.len() == MAX_WHITELIST_KEYS || self.online.contains(&online) {
+            return Err(InvalidWhitelist);
+        }
+        self.online.push(online);
+        self.offline.push(offline);
+        Ok(())
+    }
+
+    /// Removes the member with this online key, keeping the others in
+    /// order. Returns whether it was present.
+    pub fn remove(&mut self, online: &PublicKey) -> bool {
+        match self.position(online) {
+            Some(i) => {
+                self.online.remove(i);
+                self.offline.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The index of the member with this online key
+    #[inline]
+    pub fn position(&self, online: &PublicKey) -> Option<usize> {
+        self.online.iter().position(|pk| pk == online)
+    }
+
+    /// The number of members
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.online.len()
+    }
+
+    /// Whether the whitelist has no members
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.online.is_empty()
+    }
+
+    /// The members' online keys
+    #[inline]
+    pub fn online_keys(&self) -> &[PublicKey] {
+        &self.online
+    }
+
+    /// The members' offline keys
+    #[inline]
+    pub fn offline_keys(&self) -> &[PublicKey] {
+        &self.offline
+    }
+
+    /// The ring keys `O_i + t_i * (F_i + W)` for whitelisting `sub`
+    fn ring<C: Verification>(&self, secp: &Secp256k1<C>, sub: &PublicKey) -> Result<Vec<PublicKey>, Error> {
+        self.online.iter().zip(&self.offline).map(|(online, offline)| {
+            let mut summed = offline.combine(secp, sub)?;
+            summed.mul_assign(secp, &tweak(&summed))?;
+            online.combine(secp, &summed)
+        }).collect()
+    }
+}
+
+/// A proof that a key was whitelisted by a member of a `Whitelist`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WhitelistSignature {
+    challenge: Scalar,
+    responses: Vec<Scalar>,
+}
+
+/// `t = H(F + W)`
+fn tweak(summed: &PublicKey) -> Scalar {
+    Scalar::from_be_bytes_mod_order(&TaggedHasher::new(WHITELIST_TWEAK_TAG).chain(&summed.serialize()).result())
+}
+
+/// The hash of the whitelisted key and the key list, which every challenge
+/// commits to
+fn challenge_prefix(whitelist: &Whitelist, sub: &PublicKey) -> TaggedHasher {
+    let mut engine = TaggedHasher::new(WHITELIST_CHALLENGE_TAG);
+    engine.input(&sub.serialize());
+    engine.input(&[whitelist.len() as u8]);
+    for (online, offline) in whitelist.online.iter().zip(&whitelist.offline) {
+        engine.input(&online.serialize());
+        engine.input(&offline.serialize());
+    }
+    engine
+}
+
+fn challenge(prefix: &TaggedHasher, index: usize, r: &PublicKey) -> Scalar {
+    let mut engine = prefix.clone();
+    engine.input(&[index as u8]);
+    engine.input(&r.serialize());
+    Scalar::from_be_bytes_mod_order(&engine.result())
+}
+
+/// `s * G + e * p`, failing for the point at infinity
+fn commitment<C: Verification>(secp: &Secp256k1<C>, s: &Scalar, e: &Scalar, p: &PublicKey)
+                               -> Result<PublicKey, Error> {
+    let mut sg = PublicKey::from_slice(&constants::GENERATOR_G_COMPRESSED).expect("generator is valid");
+    let mut ep = *p;
+    sg.mul_assign(secp, s)?;
+    ep.mul_assign(secp, e)?;
+    sg.combine(secp, &ep)
+}
+
+impl WhitelistSignature {
+    /// Whitelists `sub` as the member with online secret key `online_sk`.
+    /// `summed_sk` is the secret of the member's offline key plus `sub`,
+    /// i.e. the sum of their secret keys. Fails with `InvalidWhitelist` if
+    /// the online key is not in the whitelist or `summed_sk` does not match.
+    #[cfg(any(test, feature = "rand"))]
+    pub fn sign<C: Signing + Verification, R: RngCore + CryptoRng>(secp: &Secp256k1<C>, rng: &mut R,
+                                                                   whitelist: &Whitelist, online_sk: &SecretKey,
+                                                                   summed_sk: &SecretKey, sub: &PublicKey)
+                                                                   -> Result<WhitelistSignature, Error> {
+        let signer = whitelist.position(&PublicKey::from_secret_key(secp, online_sk)).ok_or(InvalidWhitelist)?;
+        let summed = whitelist.offline[signer].combine(secp, sub).map_err(|_| InvalidWhitelist)?;
+        if PublicKey::from_secret_key(secp, summed_sk) != summed {
+            return Err(InvalidWhitelist);
+        }
+        let ring = whitelist.ring(secp, sub).map_err(|_| InvalidWhitelist)?;
+        let prefix = challenge_prefix(whitelist, sub);
+        let n = ring.len();
+
+        let mut responses: Vec<Scalar> = (0..n).map(|_| Scalar::from_secret_key(&SecretKey::new(secp, rng))).collect();
+        let mut alpha = Scalar::from_secret_key(&SecretKey::new(secp, rng));
+        let mut challenges = vec![Scalar::ZERO; n];
+        let mut r = PublicKey::from_scalar_mul_g(secp, &alpha)?;
+        for step in 1..n {
+            let i = (signer + step) % n;
+            challenges[i] = challenge(&prefix, i, &r);
+            r = commitment(secp, &responses[i], &challenges[i], &ring[i])?;
+        }
+        challenges[signer] = challenge(&prefix, signer, &r);
+
+        // The ring key's secret is x = o + t * y; s = alpha - e * x closes
+        // the ring
+        let mut x = Scalar::from_secret_key(online_sk).add(&tweak(&summed).mul(&Scalar::from_secret_key(summed_sk)));
+        responses[signer] = alpha.add(&challenges[signer].mul(&x).negate());
+        x.wipe();
+        alpha.wipe();
+        Ok(WhitelistSignature { challenge: challenges[0], responses })
+    }
+
+    /// Checks that `sub` was whitelisted by a member of `whitelist`. Fails
+    /// with `IncorrectSignature` if not, including for a whitelist of the
+    /// wrong size.
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, whitelist: &Whitelist, sub: &PublicKey)
+                                   -> Result<(), Error> {
+        if whitelist.len() != self.responses.len() {
+            return Err(IncorrectSignature);
+        }
+        let ring = whitelist.ring(secp, sub).map_err(|_| IncorrectSignature)?;
+        let prefix = challenge_prefix(whitelist, sub);
+        let mut e = self.challenge;
+        for (i, (pk, s)) in ring.iter().zip(&self.responses).enumerate() {
+            let r = commitment(secp, s, &e, pk).map_err(|_| IncorrectSignature)?;
+            e = challenge(&prefix, (i + 1) % ring.len(), &r);
+        }
+        if e == self.challenge {
+            Ok(())
+        } else {
+            Err(IncorrectSignature)
+        }
+    }
+
+    /// The number of keys in the whitelist the signature is for
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    /// Whether the signature is for an empty whitelist, which never happens
+    /// for a parsed or created signature
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+
+    /// Encodes the signature: the number of keys, the initial challenge,
+    /// then one response per key
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 32 * (1 + self.responses.len()));
+        out.push(self.responses.len() as u8);
+        out.extend_from_slice(&self.challenge[..]);
+        for s in &self.responses {
+            out.extend_from_slice(&s[..]);
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidSignature` for
+    /// any other data.
+    pub fn from_slice(data: &[u8]) -> Result<WhitelistSignature, Error> {
+        if data.is_empty() || data[0] == 0 || data.len() != 1 + 32 * (1 + data[0] as usize) {
+            return Err(InvalidSignature);
+        }
+        let mut scalars = data[1..].chunks(32).map(|chunk| {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(chunk);
+            Scalar::from_be_bytes(bytes).map_err(|_| InvalidSignature)
+        });
+        let challenge = scalars.next().expect("length checked")?;
+        Ok(WhitelistSignature { challenge, responses: scalars.collect::<Result<Vec<Scalar>, Error>>()? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{Whitelist, WhitelistSignature, MAX_WHITELIST_KEYS};
+    use super::super::{PublicKey, Scalar, Secp256k1, SecretKey};
+    use super::super::Error::{IncorrectSignature, InvalidSignature, InvalidWhitelist};
+
+    struct Member {
+        online: SecretKey,
+        offline: SecretKey,
+    }
+
+    fn federation(s: &Secp256k1<::All>, n: usize) -> (Vec<Member>, Whitelist) {
+        let mut whitelist = Whitelist::new();
+        let members: Vec<Member> = (0..n).map(|_| Member {
+            online: s.generate_keypair(&mut thread_rng()).0,
+            offline: s.generate_keypair(&mut thread_rng()).0,
+        }).collect();
+        for m in &members {
+            whitelist.add(PublicKey::from_secret_key(s, &m.online), PublicKey::from_secret_key(s, &m.offline))
+                     .unwrap();
+        }
+        (members, whitelist)
+    }
+
+    fn summed(s: &Secp256k1<::All>, offline: &SecretKey, sub: &SecretKey) -> SecretKey {
+        let mut sk = *offline;
+        sk.add_assign(s, &Scalar::from_secret_key(sub)).unwrap();
+        sk
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let s = Secp256k1::new();
+        let (sub_sk, sub) = s.generate_keypair(&mut thread_rng());
+        let (_, other_sub) = s.generate_keypair(&mut thread_rng());
+        for &n in [1, 2, 4].iter() {
+            let (members, whitelist) = federation(&s, n);
+            for m in &members {
+                let sig = WhitelistSignature::sign(&s, &mut thread_rng(), &whitelist, &m.online,
+                                                   &summed(&s, &m.offline, &sub_sk), &sub).unwrap();
+                assert_eq!(sig.len(), n);
+                assert_eq!(sig.verify(&s, &whitelist, &sub), Ok(()));
+                assert_eq!(sig.verify(&s, &whitelist, &other_sub), Err(IncorrectSignature));
+
+                let encoded = sig.serialize();
+                assert_eq!(encoded.len(), 1 + 32 * (n + 1));
+                assert_eq!(WhitelistSignature::from_slice(&encoded), Ok(sig.clone()));
+
+                let mut shorter = whitelist.clone();
+                shorter.remove(&whitelist.online_keys()[0]);
+                assert_eq!(sig.verify(&s, &shorter, &sub), Err(IncorrectSignature));
+            }
+        }
+
+        // Both secrets are needed
+        let (members, whitelist) = federation(&s, 3);
+        let m = &members[1];
+        assert_eq!(WhitelistSignature::sign(&s, &mut thread_rng(), &whitelist, &m.online, &m.offline, &sub),
+                   Err(InvalidWhitelist));
+        assert_eq!(WhitelistSignature::sign(&s, &mut thread_rng(), &whitelist, &sub_sk,
+                                            &summed(&s, &m.offline, &sub_sk), &sub),
+                   Err(InvalidWhitelist));
+    }
+
+    #[test]
+    fn key_list() {
+        let s = Secp256k1::new();
+        let (members, mut whitelist) = federation(&s, 3);
+        let online = whitelist.online_keys().to_vec();
+        let offline = whitelist.offline_keys().to_vec();
+        assert_eq!(Whitelist::from_keys(&online, &offline), Ok(whitelist.clone()));
+        assert_eq!(Whitelist::from_keys(&online, &offline[1..]), Err(InvalidWhitelist));
+        assert_eq!(whitelist.add(online[0], offline[1]), Err(InvalidWhitelist));
+
+        assert!(whitelist.remove(&online[1]));
+        assert!(!whitelist.remove(&online[1]));
+        assert_eq!(whitelist.online_keys(), &[online[0], online[2]][..]);
+        assert_eq!(whitelist.offline_keys(), &[offline[0], offline[2]][..]);
+        assert_eq!(whitelist.position(&online[2]), Some(1));
+
+        // A removed member can no longer sign
+        let (sub_sk, sub) = s.generate_keypair(&mut thread_rng());
+        let m = &members[1];
+        assert_eq!(WhitelistSignature::sign(&s, &mut thread_rng(), &whitelist, &m.online,
+                                            &summed(&s, &m.offline, &sub_sk), &sub),
+                   Err(InvalidWhitelist));
+
+        let mut full = Whitelist::new();
+        for _ in 0..MAX_WHITELIST_KEYS {
+            full.add(s.generate_keypair(&mut thread_rng()).1, offline[0]).unwrap();
+        }
+        assert_eq!(full.add(online[0], offline[0]), Err(InvalidWhitelist));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = Secp256k1::new();
+        let (sub_sk, sub) = s.generate_keypair(&mut thread_rng());
+        let (members, whitelist) = federation(&s, 2);
+        let sig = WhitelistSignature::sign(&s, &mut thread_rng(), &whitelist, &members[0].online,
+                                           &summed(&s, &members[0].offline, &sub_sk), &sub).unwrap();
+        let encoded = sig.serialize();
+
+        assert_eq!(WhitelistSignature::from_slice(&encoded[..encoded.len() - 1]), Err(InvalidSignature));
+        assert_eq!(WhitelistSignature::from_slice(&[0]), Err(InvalidSignature));
+        assert_eq!(WhitelistSignature::from_slice(&[]), Err(InvalidSignature));
+        let mut overflow = encoded.clone();
+        for byte in overflow[1..33].iter_mut() {
+            *byte = 0xff;
+        }
+        assert_eq!(WhitelistSignature::from_slice(&overflow), Err(InvalidSignature));
+        let mut tampered = encoded.clone();
+        tampered[50] ^= 1;
+        assert_eq!(WhitelistSignature::from_slice(&tampered).unwrap().verify(&s, &whitelist, &sub),
+                   Err(IncorrectSignature));
+    }
+}
@@ -0,0 +1,295 @@
+// Bitcoin secp256k1 bindings
+// Written in 2018 by
+//   The rust-secp256k1-sgx developers
+//
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! # Wire messages
+//! One versioned format for the messages which the multi-party protocols
+//! (`cosign` and `two_party`) exchange between enclaves, so that
+//! implementations interoperate without designing their own. Every
+//! message names its session and sender, and carries one protocol step's
+//! payload.
+//!
+//! A message is encoded as the format version (`WIRE_VERSION`), the
+//! payload type, the 32-byte session ID, the sender (u32, little-endian)
+//! and the payload: points compressed, scalars and commitments as 32
+//! bytes. Parsing is strict: it rejects other versions, unknown types,
+//! trailing bytes, invalid points and scalars, co-signers with index 0,
+//! and two-party payloads from the wrong party. Messages serialize to
+//! these bytes with serde too, and deserializing them is just as strict.
+//! Nothing here authenticates the sender; run the protocols over an
+//! attested, authenticated channel. Requires compilation with the "hashes"
+//! feature.
+
+use std::vec::Vec;
+
+use commitment::NonceCommitment;
+use constants;
+use key::PublicKey;
+use scalar::Scalar;
+use super::Error::{self, InvalidWireMessage};
+use two_party::PartialTerms;
+
+/// The current version of the wire format
+pub const WIRE_VERSION: u8 = 1;
+
+/// The size (in bytes) of the header before the payload: version, type,
+/// session ID and sender
+pub const WIRE_HEADER_SIZE: usize = 1 + 1 + 32 + 4;
+
+/// The sender index of party 1 in a `two_party` session
+pub const TWO_PARTY_PARTY1: u32 = 1;
+
+/// The sender index of party 2 in a `two_party` session
+pub const TWO_PARTY_PARTY2: u32 = 2;
+
+/// The content of one protocol step
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Payload {
+    /// A co-signer's nonce commitment, `cosign::nonce_commitment`
+    CoSignCommitment([u8; 32]),
+    /// A co-signer's revealed nonce point
+    CoSignNonce(PublicKey),
+    /// A co-signer's partial signature
+    CoSignPartial(Scalar),
+    /// Party 1's commitment to its nonce point
+    TwoPartyCommitment(NonceCommitment),
+    /// Party 2's nonce point
+    TwoPartyNonce(PublicKey),
+    /// Party 1's revealed nonce point
+    TwoPartyReveal(PublicKey),
+    /// Party 2's partial signature terms
+    TwoPartyPartial(PartialTerms),
+}
+
+impl Payload {
+    /// The type byte of the payload
+    fn kind(&self) -> u8 {
+        match *self {
+            Payload::CoSignCommitment(_) => 1,
+            Payload::CoSignNonce(_) => 2,
+            Payload::CoSignPartial(_) => 3,
+            Payload::TwoPartyCommitment(_) => 16,
+            Payload::TwoPartyNonce(_) => 17,
+            Payload::TwoPartyReveal(_) => 18,
+            Payload::TwoPartyPartial(_) => 19,
+        }
+    }
+
+    /// Whether `sender` may send this payload
+    fn allows_sender(&self, sender: u32) -> bool {
+        match *self {
+            Payload::CoSignCommitment(_) | Payload::CoSignNonce(_) | Payload::CoSignPartial(_) => sender != 0,
+            Payload::TwoPartyCommitment(_) | Payload::TwoPartyReveal(_) => sender == TWO_PARTY_PARTY1,
+            Payload::TwoPartyNonce(_) | Payload::TwoPartyPartial(_) => sender == TWO_PARTY_PARTY2,
+        }
+    }
+}
+
+/// A protocol message from one participant of a session
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WireMessage {
+    /// The session, e.g. a random ID chosen by the coordinator
+    pub session: [u8; 32],
+    /// The sender: its share index for `cosign`, `TWO_PARTY_PARTY1` or
+    /// `TWO_PARTY_PARTY2` for `two_party`
+    pub sender: u32,
+    /// The content
+    pub payload: Payload,
+}
+
+fn scalar_at(data: &[u8]) -> Result<Scalar, Error> {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data[..32]);
+    Scalar::from_be_bytes(bytes).map_err(|_| InvalidWireMessage)
+}
+
+fn point(data: &[u8]) -> Result<PublicKey, Error> {
+    if data.len() != constants::PUBLIC_KEY_SIZE {
+        return Err(InvalidWireMessage);
+    }
+    PublicKey::from_slice(data).map_err(|_| InvalidWireMessage)
+}
+
+impl WireMessage {
+    /// Creates a message. Fails with `InvalidWireMessage` if `sender` may
+    /// not send `payload`.
+    pub fn new(session: [u8; 32], sender: u32, payload: Payload) -> Result<WireMessage, Error> {
+        if !payload.allows_sender(sender) {
+            return Err(InvalidWireMessage);
+        }
+        Ok(WireMessage { session, sender, payload })
+    }
+
+    /// Encodes the message in the current wire format
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(WIRE_HEADER_SIZE + 64);
+        out.push(WIRE_VERSION);
+        out.push(self.payload.kind());
+        out.extend_from_slice(&self.session);
+        for i in 0..4 {
+            out.push((self.sender >> (8 * i)) as u8);
+        }
+        match self.payload {
+            Payload::CoSignCommitment(ref commitment) => out.extend_from_slice(commitment),
+            Payload::CoSignNonce(ref nonce) | Payload::TwoPartyNonce(ref nonce) |
+            Payload::TwoPartyReveal(ref nonce) => out.extend_from_slice(&nonce.serialize()),
+            Payload::CoSignPartial(ref s) => out.extend_from_slice(&s[..]),
+            Payload::TwoPartyCommitment(ref commitment) => out.extend_from_slice(&commitment.serialize()),
+            Payload::TwoPartyPartial(ref terms) => {
+                out.extend_from_slice(&terms.a[..]);
+                out.extend_from_slice(&terms.b[..]);
+            }
+        }
+        out
+    }
+
+    /// Parses the output of `serialize`. Fails with `InvalidWireMessage`
+    /// for any other data, including messages of another format version.
+    pub fn from_slice(data: &[u8]) -> Result<WireMessage, Error> {
+        if data.len() < WIRE_HEADER_SIZE || data[0] != WIRE_VERSION {
+            return Err(InvalidWireMessage);
+        }
+        let mut session = [0u8; 32];
+        session.copy_from_slice(&data[2..34]);
+        let mut sender = 0u32;
+        for (i, byte) in data[34..WIRE_HEADER_SIZE].iter().enumerate() {
+            sender |= (*byte as u32) << (8 * i);
+        }
+        let body = &data[WIRE_HEADER_SIZE..];
+        let payload = match data[1] {
+            1 | 3 if body.len() != 32 => return Err(InvalidWireMessage),
+            19 if body.len() != 64 => return Err(InvalidWireMessage),
+            1 => {
+                let mut commitment = [0u8; 32];
+                commitment.copy_from_slice(body);
+                Payload::CoSignCommitment(commitment)
+            }
+            2 => Payload::CoSignNonce(point(body)?),
+            3 => Payload::CoSignPartial(scalar_at(body)?),
+            16 => Payload::TwoPartyCommitment(NonceCommitment::from_slice(body).map_err(|_| InvalidWireMessage)?),
+            17 => Payload::TwoPartyNonce(point(body)?),
+            18 => Payload::TwoPartyReveal(point(body)?),
+            19 => Payload::TwoPartyPartial(PartialTerms { a: scalar_at(body)?, b: scalar_at(&body[32..])? }),
+            _ => return Err(InvalidWireMessage),
+        };
+        WireMessage::new(session, sender, payload)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for WireMessage {
+    fn serialize<S: ::serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&WireMessage::serialize(self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for WireMessage {
+    fn deserialize<D: ::serde::Deserializer<'de>>(d: D) -> Result<WireMessage, D::Error> {
+        use ::serde::de::Error;
+
+        let sl: &[u8] = ::serde::Deserialize::deserialize(d)?;
+        WireMessage::from_slice(sl).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rng::thread_rng;
+    use super::{Payload, WireMessage, TWO_PARTY_PARTY1, TWO_PARTY_PARTY2, WIRE_HEADER_SIZE};
+    use super::super::{NonceCommitment, Scalar, Secp256k1};
+    use super::super::Error::InvalidWireMessage;
+    use super::super::two_party::PartialTerms;
+
+    fn messages() -> Vec<WireMessage> {
+        let s = Secp256k1::new();
+        let (sk, pk) = s.generate_keypair(&mut thread_rng());
+        let session = [0x5a; 32];
+        vec![
+            WireMessage::new(session, 3, Payload::CoSignCommitment([7; 32])).unwrap(),
+            WireMessage::new(session, 3, Payload::CoSignNonce(pk)).unwrap(),
+            WireMessage::new(session, 3, Payload::CoSignPartial(Scalar::from_secret_key(&sk))).unwrap(),
+            WireMessage::new(session, TWO_PARTY_PARTY1, Payload::TwoPartyCommitment(NonceCommitment::commit(&pk)))
+                .unwrap(),
+            WireMessage::new(session, TWO_PARTY_PARTY2, Payload::TwoPartyNonce(pk)).unwrap(),
+            WireMessage::new(session, TWO_PARTY_PARTY1, Payload::TwoPartyReveal(pk)).unwrap(),
+            WireMessage::new(session, TWO_PARTY_PARTY2,
+                             Payload::TwoPartyPartial(PartialTerms { a: Scalar::ONE, b: Scalar::from_secret_key(&sk) }))
+                .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        for msg in messages() {
+            let encoded = msg.serialize();
+            assert_eq!(encoded[0], 1);
+            assert_eq!(&encoded[34..38], &[msg.sender as u8, 0, 0, 0]);
+            assert_eq!(WireMessage::from_slice(&encoded), Ok(msg));
+        }
+    }
+
+    #[test]
+    fn strict_parsing() {
+        for msg in messages() {
+            let encoded = msg.serialize();
+            let mut longer = encoded.clone();
+            longer.push(0);
+            assert_eq!(WireMessage::from_slice(&longer), Err(InvalidWireMessage));
+            assert_eq!(WireMessage::from_slice(&encoded[..encoded.len() - 1]), Err(InvalidWireMessage));
+            let mut version = encoded.clone();
+            version[0] = 2;
+            assert_eq!(WireMessage::from_slice(&version), Err(InvalidWireMessage));
+            // Every sender is wrong for some payload: 0 for co-signing,
+            // and one of the parties for two-party signing
+            for &sender in [0u8, 1, 2].iter() {
+                if sender as u32 != msg.sender {
+                    let mut other = encoded.clone();
+                    other[34] = sender;
+                    let parsed = WireMessage::from_slice(&other);
+                    assert_eq!(parsed.is_ok(), sender != 0 && msg.payload.kind() < 16);
+                }
+            }
+        }
+        assert_eq!(WireMessage::from_slice(&[1; WIRE_HEADER_SIZE - 1]), Err(InvalidWireMessage));
+        let mut unknown = messages()[0].serialize();
+        unknown[1] = 4;
+        assert_eq!(WireMessage::from_slice(&unknown), Err(InvalidWireMessage));
+        let mut bad_point = messages()[1].serialize();
+        bad_point[WIRE_HEADER_SIZE] = 5;
+        assert_eq!(WireMessage::from_slice(&bad_point), Err(InvalidWireMessage));
+        let mut bad_scalar = messages()[2].serialize();
+        for byte in bad_scalar[WIRE_HEADER_SIZE..].iter_mut() {
+            *byte = 0xff;
+        }
+        assert_eq!(WireMessage::from_slice(&bad_scalar), Err(InvalidWireMessage));
+        let nonce = messages()[4].payload;
+        assert_eq!(WireMessage::new([0; 32], TWO_PARTY_PARTY1, nonce), Err(InvalidWireMessage));
+        assert_eq!(WireMessage::new([0; 32], 0, messages()[1].payload), Err(InvalidWireMessage));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_test::{Token, assert_de_tokens_error, assert_tokens};
+
+        let msg = messages()[0];
+        let encoded: &'static [u8] = Box::leak(msg.serialize().into_boxed_slice());
+        assert_tokens(&msg, &[Token::BorrowedBytes(encoded)]);
+        let mut bad = encoded.to_vec();
+        bad[0] = 0;
+        let bad: &'static [u8] = Box::leak(bad.into_boxed_slice());
+        assert_de_tokens_error::<WireMessage>(&[Token::BorrowedBytes(bad)],
+                                              "secp: malformed or unsupported protocol message");
+    }
+}